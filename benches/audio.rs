@@ -0,0 +1,33 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use mixlab::engine::{InputRef, CHANNELS, CONTROL_SAMPLES_PER_TICK, SAMPLES_PER_TICK};
+use mixlab::module::mixer::sum_channel;
+
+fn mixer_sum(c: &mut Criterion) {
+    let input = (0..SAMPLES_PER_TICK * CHANNELS)
+        .map(|i| (i as f32 / 100.0).sin())
+        .collect::<Vec<_>>();
+
+    let fader = (0..CONTROL_SAMPLES_PER_TICK)
+        .map(|i| i as f32 / CONTROL_SAMPLES_PER_TICK as f32)
+        .collect::<Vec<_>>();
+
+    let mut master = vec![0.0; SAMPLES_PER_TICK * CHANNELS];
+
+    let mut group = c.benchmark_group("mixer sum, one tick");
+    group.throughput(Throughput::Elements((SAMPLES_PER_TICK * CHANNELS) as u64));
+
+    group.bench_function("fixed gain", |b| {
+        b.iter(|| sum_channel(&mut master, black_box(&input), black_box(0.5), None))
+    });
+
+    group.bench_function("fader modulated", |b| {
+        let fader_mod = InputRef::Control(&fader).expect_control();
+        b.iter(|| sum_channel(&mut master, black_box(&input), black_box(0.5), fader_mod))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, mixer_sum);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use fdk_aac::enc as aac;
+
+use mixlab::engine::{CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK};
+use mixlab::video::encode::{AudioCtx, AudioParams, VideoCtx, VideoParams, Profile};
+use mixlab_codec::ffmpeg::{AvFrame, PictureSettings};
+
+fn aac(c: &mut Criterion) {
+    let mut audio_ctx = AudioCtx::new(AudioParams {
+        bit_rate: aac::BitRate::Cbr(160000),
+        sample_rate: SAMPLE_RATE,
+        transport: aac::Transport::Raw,
+    });
+
+    let tick = (0..SAMPLES_PER_TICK * CHANNELS)
+        .map(|i| (i as f32 / 20.0).sin() * 0.5)
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("aac encode");
+    group.throughput(Throughput::Elements(SAMPLES_PER_TICK as u64));
+
+    // most ticks only buffer, roughly every other tick encodes a frame
+    group.bench_function("one tick", |b| {
+        b.iter(|| { black_box(audio_ctx.send_audio(&tick)); })
+    });
+
+    group.finish();
+}
+
+fn h264(c: &mut Criterion) {
+    let picture = PictureSettings::yuv420p(1280, 720);
+
+    let mut group = c.benchmark_group("h264 encode");
+    group.throughput(Throughput::Elements(1));
+    group.sample_size(20);
+
+    for (name, profile) in vec![("monitor", Profile::Monitor), ("stream", Profile::Stream)] {
+        let mut video_ctx = VideoCtx::new(VideoParams {
+            picture: picture.clone(),
+            time_base: SAMPLE_RATE,
+            profile,
+        });
+
+        let frame = AvFrame::blank(&picture);
+        let mut pts = 0;
+
+        group.bench_function(&format!("720p frame, {} profile", name), |b| {
+            b.iter(|| {
+                let mut frame = frame.clone();
+                frame.set_presentation_timestamp(pts);
+                pts += (SAMPLE_RATE / 30) as i64;
+
+                video_ctx.send_frame(frame);
+
+                while let Some(packet) = video_ctx.recv_packet() {
+                    black_box(packet);
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, aac, h264);
+criterion_main!(benches);
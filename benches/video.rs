@@ -0,0 +1,66 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use mixlab::module::video_mixer::fade_line;
+use mixlab::video::encode::DynamicScaler;
+use mixlab_codec::ffmpeg::{AvFrame, PictureSettings};
+
+fn crossfade(c: &mut Criterion) {
+    let picture = PictureSettings::yuv420p(1920, 1080);
+
+    let a = AvFrame::blank(&picture);
+    let b = AvFrame::blank(&picture);
+    let mut out = AvFrame::blank(&picture);
+
+    let mut group = c.benchmark_group("video mixer crossfade");
+    group.throughput(Throughput::Bytes((picture.width * picture.height) as u64));
+
+    group.bench_function("1080p luma plane", |bench| {
+        let a = a.frame_data();
+        let b = b.frame_data();
+        let out = out.frame_data_mut();
+
+        bench.iter(|| unsafe {
+            for y in 0..picture.height {
+                fade_line(
+                    out.data(0).add(y * out.stride(0)),
+                    a.data(0).add(y * a.stride(0)),
+                    b.data(0).add(y * b.stride(0)),
+                    picture.width,
+                    black_box(128));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn scale(c: &mut Criterion) {
+    let hd = PictureSettings::yuv420p(1280, 720);
+    let full_hd = PictureSettings::yuv420p(1920, 1080);
+
+    let mut group = c.benchmark_group("scaler");
+
+    group.bench_function("720p to 1080p", |b| {
+        let mut scaler = DynamicScaler::new(full_hd.clone());
+        let mut frame = AvFrame::blank(&hd);
+        b.iter(|| { black_box(scaler.scale(&mut frame)); })
+    });
+
+    group.bench_function("1080p to 720p", |b| {
+        let mut scaler = DynamicScaler::new(hd.clone());
+        let mut frame = AvFrame::blank(&full_hd);
+        b.iter(|| { black_box(scaler.scale(&mut frame)); })
+    });
+
+    // differently shaped input is letterboxed
+    group.bench_function("4:3 to 1080p", |b| {
+        let mut scaler = DynamicScaler::new(full_hd.clone());
+        let mut frame = AvFrame::blank(&PictureSettings::yuv420p(640, 480));
+        b.iter(|| { black_box(scaler.scale(&mut frame)); })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, crossfade, scale);
+criterion_main!(benches);
@@ -8,6 +8,8 @@ use sys as ff;
 
 pub mod codec;
 pub mod media;
+mod audio_encode;
+mod audio_stream;
 mod format;
 mod frame;
 mod ioctx;
@@ -15,8 +17,10 @@ mod packet;
 mod pixfmt;
 mod scale;
 
+pub use audio_encode::{AudioEncoder, AudioEncodeParams, AudioEncodeError, AudioEncoding};
+pub use audio_stream::{AudioDecodeStream, OpenAudioError, SeekableReader, SequentialReader};
 pub use format::InputContainer;
-pub use frame::{AvFrame, PictureSettings, PictureData, PictureDataMut};
+pub use frame::{AvFrame, PictureSettings, PictureData, PictureDataMut, UnsupportedSampleFormat};
 pub use ioctx::{AvIoError, IoReader, AvIoReader};
 pub use packet::{AvPacket, AvPacketRef, PacketInfo};
 pub use pixfmt::{PixelFormat, PixFmtDescriptor, PlaneInfo, ColorFormat};
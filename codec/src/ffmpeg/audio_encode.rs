@@ -0,0 +1,181 @@
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::ffi::CString;
+use std::mem::MaybeUninit;
+use std::os::raw::c_int;
+use std::slice;
+
+use derive_more::From;
+use ffmpeg_dev::sys as ff;
+
+use crate::ffmpeg::codec::AvCodecContext;
+use crate::ffmpeg::media::Audio;
+use crate::ffmpeg::{AvDict, AvError, AvFrame, AvPacket};
+
+// frame size for encoders which take frames of any size
+const DEFAULT_FRAME_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioEncoding {
+    Vorbis,
+    Mp3,
+}
+
+impl AudioEncoding {
+    // tried in order. the external libraries are preferred over ffmpeg's own
+    // encoders, where it has one at all
+    fn encoder_names(self) -> &'static [&'static str] {
+        match self {
+            AudioEncoding::Vorbis => &["libvorbis", "vorbis"],
+            AudioEncoding::Mp3 => &["libmp3lame"],
+        }
+    }
+}
+
+pub struct AudioEncodeParams {
+    pub encoding: AudioEncoding,
+    pub sample_rate: usize,
+    pub channels: usize,
+    pub bit_rate: usize,
+}
+
+#[derive(Debug, From)]
+pub enum AudioEncodeError {
+    // ffmpeg was built without an encoder for the format
+    EncoderNotFound,
+    Av(AvError),
+}
+
+#[derive(Debug)]
+pub struct AudioEncoder {
+    ctx: AvCodecContext,
+    sample_rate: usize,
+    channels: usize,
+    // samples per channel in each frame the encoder takes
+    frame_size: usize,
+    // interleaved samples waiting to make up a whole frame
+    pending: Vec<f32>,
+    // in samples, which is the encoder's time base
+    next_pts: i64,
+    packets: VecDeque<AvPacket>,
+}
+
+impl AudioEncoder {
+    pub fn new(params: AudioEncodeParams) -> Result<Self, AudioEncodeError> {
+        let codec = params.encoding.encoder_names().iter()
+            .map(|name| {
+                let name = CString::new(*name).unwrap();
+                unsafe { ff::avcodec_find_encoder_by_name(name.as_ptr()) }
+            })
+            .find(|codec| !codec.is_null())
+            .ok_or(AudioEncodeError::EncoderNotFound)?;
+
+        let mut ctx = unsafe { AvCodecContext::alloc(codec) };
+
+        // set codec context params
+        unsafe {
+            let avctx = &mut *ctx.as_mut_ptr();
+            avctx.sample_fmt = ff::AVSampleFormat_AV_SAMPLE_FMT_FLTP;
+            avctx.sample_rate = params.sample_rate.try_into().expect("sample_rate too large");
+            avctx.channels = params.channels.try_into().expect("channels too large");
+            avctx.channel_layout = ff::av_get_default_channel_layout(avctx.channels) as u64;
+            avctx.bit_rate = params.bit_rate.try_into().expect("bit_rate too large");
+            avctx.time_base.num = 1;
+            avctx.time_base.den = avctx.sample_rate;
+            avctx.flags |= ff::AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+
+            // ffmpeg's own vorbis encoder is marked experimental
+            avctx.strict_std_compliance = ff::FF_COMPLIANCE_EXPERIMENTAL as c_int;
+        }
+
+        // open codec
+        let mut opts = AvDict::new();
+        let rc = unsafe { ff::avcodec_open2(ctx.as_mut_ptr(), codec, opts.as_mut() as *mut *mut _) };
+
+        if rc < 0 {
+            return Err(AvError(rc).into());
+        }
+
+        let frame_size = match unsafe { (*ctx.as_ptr()).frame_size } {
+            0 => DEFAULT_FRAME_SIZE,
+            frame_size => frame_size.try_into().expect("frame_size >= 0"),
+        };
+
+        Ok(AudioEncoder {
+            ctx,
+            sample_rate: params.sample_rate,
+            channels: params.channels,
+            frame_size,
+            pending: Vec::new(),
+            next_pts: 0,
+            packets: VecDeque::new(),
+        })
+    }
+
+    // codec headers, which for vorbis are the three header packets with Xiph
+    // lacing
+    pub fn extradata(&self) -> &[u8] {
+        unsafe {
+            let ctx = &*self.ctx.as_ptr();
+
+            if ctx.extradata.is_null() {
+                return &[];
+            }
+
+            slice::from_raw_parts(ctx.extradata,
+                ctx.extradata_size.try_into().expect("extradata_size >= 0"))
+        }
+    }
+
+    // takes interleaved samples, encoding as many whole frames as they make
+    pub fn send_audio(&mut self, samples: &[f32]) -> Result<(), AvError> {
+        self.pending.extend_from_slice(samples);
+
+        let frame_len = self.frame_size * self.channels;
+
+        while self.pending.len() >= frame_len {
+            let mut frame = AvFrame::<Audio>::planar_float(self.sample_rate, self.channels, &self.pending[0..frame_len]);
+            self.pending.drain(0..frame_len);
+
+            frame.set_presentation_timestamp(self.next_pts);
+            self.next_pts += self.frame_size as i64;
+
+            let rc = unsafe { ff::avcodec_send_frame(self.ctx.as_mut_ptr(), frame.as_ptr()) };
+
+            if rc < 0 {
+                return Err(AvError(rc));
+            }
+
+            // packets are taken straight away so that the encoder never
+            // refuses a frame for having too many waiting
+            loop {
+                match self.receive_packet() {
+                    Ok(packet) => self.packets.push_back(packet),
+                    Err(e) if e.again() => break,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn recv_packet(&mut self) -> Option<AvPacket> {
+        self.packets.pop_front()
+    }
+
+    fn receive_packet(&mut self) -> Result<AvPacket, AvError> {
+        unsafe {
+            let mut packet = MaybeUninit::<ff::AVPacket>::uninit();
+            ff::av_init_packet(packet.as_mut_ptr());
+
+            let rc = ff::avcodec_receive_packet(self.ctx.as_mut_ptr(), packet.as_mut_ptr());
+
+            if rc < 0 {
+                Err(AvError(rc))
+            } else {
+                Ok(AvPacket::new(packet.assume_init()))
+            }
+        }
+    }
+}
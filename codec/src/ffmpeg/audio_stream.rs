@@ -0,0 +1,207 @@
+use std::io::{self, Seek, SeekFrom};
+
+use derive_more::From;
+
+use crate::{AudioStream, PcmData, StreamRead, StreamError};
+use crate::ffmpeg::{AvError, AvFrame, AvIoError, AvIoReader, IoReader, InputContainer};
+use crate::ffmpeg::codec::{BuildError, CodecBuilder, Decode, OpenError, RecvFrameError};
+use crate::ffmpeg::media::Audio;
+
+/// Decodes the first audio stream of any container and codec ffmpeg can
+/// recognise, read from a reader which can only go forward.
+pub struct AudioDecodeStream<R: io::Read> {
+    container: InputContainer<SequentialReader<R>>,
+    decode: Decode<Audio>,
+    stream_index: usize,
+    codec_name: &'static str,
+    bitrate: usize,
+    sample_rate: usize,
+    channels: usize,
+    // the first frame is decoded up front to learn the sample rate and
+    // channel count, which raw streams like MP3 and ADTS only carry in
+    // their frames
+    first_frame: Option<PcmData>,
+}
+
+#[derive(Debug, From)]
+pub enum OpenAudioError {
+    Io(io::Error),
+    Av(AvError),
+    Build(BuildError),
+    Open(OpenError),
+    NoAudio,
+}
+
+impl<R: io::Read> From<AvIoError<SequentialReader<R>>> for OpenAudioError {
+    fn from(e: AvIoError<SequentialReader<R>>) -> Self {
+        match e {
+            AvIoError::Io(e) => OpenAudioError::Io(e),
+            AvIoError::Av(e) => OpenAudioError::Av(e),
+        }
+    }
+}
+
+impl<R: io::Read> AudioDecodeStream<R> {
+    pub fn open(reader: R) -> Result<Self, OpenAudioError> {
+        let io = AvIoReader::new(SequentialReader(reader)).unseekable();
+        let container = InputContainer::open(io)?;
+
+        let streams = container.streams();
+
+        let stream_index = streams.iter()
+            .position(|stream| stream.is_media::<Audio>())
+            .ok_or(OpenAudioError::NoAudio)?;
+
+        let stream = &streams[stream_index];
+        let codec_name = stream.codec_name().unwrap_or("Unknown");
+        let codec_params = stream.codec_parameters();
+        let bitrate = codec_params.bit_rate.max(0) as usize;
+
+        let decode = CodecBuilder::<Audio>::new(codec_params.codec_id, stream.time_base())?
+            .with_parameters(codec_params)
+            .open_decoder()?;
+
+        let mut audio = AudioDecodeStream {
+            container,
+            decode,
+            stream_index,
+            codec_name,
+            bitrate,
+            sample_rate: 0,
+            channels: 0,
+            first_frame: None,
+        };
+
+        loop {
+            match audio.next_frame() {
+                Ok(Some((frame, pcm))) => {
+                    audio.sample_rate = frame.sample_rate();
+                    audio.channels = frame.channel_count();
+                    audio.first_frame = Some(pcm);
+                    return Ok(audio);
+                }
+                // nothing decodable at all
+                Ok(None) => return Err(OpenAudioError::NoAudio),
+                Err(StreamError::BadPacket) => continue,
+                Err(StreamError::IoError(e)) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<Option<(AvFrame<Audio>, PcmData)>, StreamError> {
+        loop {
+            match self.decode.recv_frame() {
+                Ok(frame) => {
+                    let pcm = planar_pcm(&frame)?;
+                    return Ok(Some((frame, pcm)));
+                }
+                Err(RecvFrameError::NeedMoreInput) => {}
+                Err(RecvFrameError::Eof) => return Ok(None),
+                Err(RecvFrameError::Codec(_)) => return Err(StreamError::BadPacket),
+            }
+
+            match self.container.read_packet() {
+                Ok(Some(pkt)) if pkt.stream_index() as usize == self.stream_index => {
+                    self.decode.send_packet(&pkt)
+                        .map_err(|_| StreamError::BadPacket)?;
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    // drain whatever the decoder still holds, after which it
+                    // reports Eof
+                    let _ = self.decode.end_of_stream();
+                }
+                Err(AvIoError::Io(e)) => return Err(StreamError::IoError(e)),
+                Err(AvIoError::Av(_)) => return Err(StreamError::BadPacket),
+            }
+        }
+    }
+}
+
+impl<R: io::Read> AudioStream for AudioDecodeStream<R> {
+    fn codec_name(&self) -> &'static str {
+        self.codec_name
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn bitrate_nominal(&self) -> usize {
+        self.bitrate
+    }
+
+    fn read(&mut self) -> Result<Option<StreamRead>, StreamError> {
+        if let Some(pcm) = self.first_frame.take() {
+            return Ok(Some(StreamRead::Audio(pcm)));
+        }
+
+        Ok(self.next_frame()?.map(|(_, pcm)| StreamRead::Audio(pcm)))
+    }
+}
+
+fn planar_pcm(frame: &AvFrame<Audio>) -> Result<PcmData, StreamError> {
+    let mut interleaved = Vec::new();
+    frame.read_interleaved(&mut interleaved)
+        .map_err(|_| StreamError::BadPacket)?;
+
+    let channels = frame.channel_count().max(1);
+    let mut pcm = vec![Vec::with_capacity(frame.sample_count()); channels];
+
+    for samples in interleaved.chunks(channels) {
+        for (channel, sample) in pcm.iter_mut().zip(samples) {
+            channel.push((sample.max(-1.0).min(1.0) * i16::max_value() as f32) as i16);
+        }
+    }
+
+    Ok(pcm)
+}
+
+/// Adapts a plain reader for ffmpeg. Must be opened with
+/// `AvIoReader::unseekable` so that ffmpeg never tries to seek it.
+pub struct SequentialReader<R>(pub R);
+
+impl<R: io::Read> IoReader for SequentialReader<R> {
+    type Error = io::Error;
+    const BUFFER_SIZE: usize = 4096;
+
+    fn read(&mut self, out: &mut [u8]) -> Result<usize, Self::Error> {
+        io::Read::read(&mut self.0, out)
+    }
+
+    fn seek(&mut self, _: SeekFrom) -> Result<u64, Self::Error> {
+        Err(io::Error::new(io::ErrorKind::Other, "stream is not seekable"))
+    }
+
+    fn size(&mut self) -> Result<u64, Self::Error> {
+        Err(io::Error::new(io::ErrorKind::Other, "stream has no size"))
+    }
+}
+
+/// Adapts a plain reader which can seek, such as a file, for ffmpeg.
+/// Containers like MP4 often keep their index at the end and need this.
+pub struct SeekableReader<R>(pub R);
+
+impl<R: io::Read + io::Seek> IoReader for SeekableReader<R> {
+    type Error = io::Error;
+    const BUFFER_SIZE: usize = 4096;
+
+    fn read(&mut self, out: &mut [u8]) -> Result<usize, Self::Error> {
+        io::Read::read(&mut self.0, out)
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.0.seek(pos)
+    }
+
+    fn size(&mut self) -> Result<u64, Self::Error> {
+        let pos = self.0.seek(SeekFrom::Current(0))?;
+        let size = self.0.seek(SeekFrom::End(0))?;
+        self.0.seek(SeekFrom::Start(pos))?;
+        Ok(size)
+    }
+}
@@ -1,8 +1,9 @@
-use std::convert::TryFrom;
+use std::convert::{TryFrom, TryInto};
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::os::raw::c_int;
 use std::ptr;
+use std::slice;
 
 use derive_more::From;
 use ffmpeg_dev::sys as ff;
@@ -28,6 +29,16 @@ impl<'a> CodecBuilder<'a, Video> {
             Err(BuildError::CodecNotFound) => unreachable!(),
         }
     }
+
+    // unlike h264, hevc and av1 decoders are optional in ffmpeg builds:
+
+    pub fn hevc(time_base: TimeBase) -> Result<CodecBuilder<'a, Video>, BuildError> {
+        Self::new(ff::AVCodecID_AV_CODEC_ID_HEVC, time_base)
+    }
+
+    pub fn av1(time_base: TimeBase) -> Result<CodecBuilder<'a, Video>, BuildError> {
+        Self::new(ff::AVCodecID_AV_CODEC_ID_AV1, time_base)
+    }
 }
 
 #[derive(Debug)]
@@ -136,6 +147,19 @@ impl<'a> AvCodecParameters<'a> {
     pub unsafe fn from_raw(params: &'a ff::AVCodecParameters) -> Self {
         AvCodecParameters(params)
     }
+
+    // codec specific setup, such as the avcC record for h264 or the
+    // AudioSpecificConfig for aac
+    pub fn extradata(&self) -> &'a [u8] {
+        if self.0.extradata == ptr::null_mut() {
+            return &[];
+        }
+
+        unsafe {
+            slice::from_raw_parts(self.0.extradata,
+                self.0.extradata_size.try_into().expect("extradata_size >= 0"))
+        }
+    }
 }
 
 impl<'a> Deref for AvCodecParameters<'a> {
@@ -11,6 +11,7 @@ use mixlab_util::time::{MediaDuration, MediaTime, TimeBase};
 
 use crate::ffmpeg::{AvIoError, AvPacket, AvError, EOF};
 use crate::ffmpeg::codec::AvCodecParameters;
+use crate::ffmpeg::media::MediaType;
 use crate::ffmpeg::ioctx::{IoReader, AvIoReader};
 
 pub struct InputContainer<R: IoReader> {
@@ -48,6 +49,19 @@ impl<R: IoReader> InputContainer<R> {
         unsafe { &*(self.ctx.ptr as *const _) }
     }
 
+    // running time of the whole container, if the format records it
+    pub fn duration(&self) -> Option<MediaDuration> {
+        let duration = self.as_underlying().duration;
+
+        // negative when unknown (AV_NOPTS_VALUE), otherwise in AV_TIME_BASE
+        // units of microseconds
+        if duration < 0 {
+            None
+        } else {
+            Some(MediaDuration::new(duration, 1_000_000))
+        }
+    }
+
     pub fn streams(&self) -> &[InputStream] {
         let underlying = self.as_underlying();
 
@@ -72,9 +86,10 @@ impl<R: IoReader> InputContainer<R> {
             ff::avio_seek((*self.ctx.ptr).pb, 0, ff::SEEK_SET as i32) as i32
         })?;
 
-        // seek stream to start
+        // seek stream to the keyframe at or before time, so that decoding
+        // can resume from exactly time by discarding frames before it
         self.io.check_error(unsafe {
-            ff::av_seek_frame(self.ctx.ptr, stream_index as i32, ts, 0)
+            ff::av_seek_frame(self.ctx.ptr, stream_index as i32, ts, ff::AVSEEK_FLAG_BACKWARD as c_int)
         })?;
 
         Ok(())
@@ -125,6 +140,10 @@ impl InputStream {
         Some(long_name.to_str().expect("utf8 codec name"))
     }
 
+    pub fn is_media<Mt: MediaType>(&self) -> bool {
+        self.codec_parameters().codec_type == Mt::FFMPEG_MEDIA_TYPE
+    }
+
     pub fn duration(&self) -> MediaDuration {
         self.time_base().scale_duration(self.as_underlying().duration)
     }
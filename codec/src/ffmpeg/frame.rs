@@ -5,7 +5,7 @@ use std::ptr;
 
 use ffmpeg_dev::sys as ff;
 
-use crate::ffmpeg::media::{MediaType, Video};
+use crate::ffmpeg::media::{Audio, MediaType, Video};
 use crate::ffmpeg::{AvError, PixelFormat, ColorFormat};
 
 #[derive(Debug)]
@@ -70,6 +70,15 @@ impl<Mt: MediaType> AvFrame<Mt> {
     pub fn packet_duration(&self) -> i64 {
         self.as_underlying().pkt_duration
     }
+
+    // total size of the data buffers this frame references. buffers may be
+    // shared with other frames, so this can overcount
+    pub fn buffer_size(&self) -> usize {
+        self.as_underlying().buf.iter()
+            .filter(|buf| !buf.is_null())
+            .map(|buf| unsafe { (**buf).size as usize })
+            .sum()
+    }
 }
 
 impl AvFrame<Video> {
@@ -303,6 +312,121 @@ impl AvFrame<Video> {
     }
 }
 
+impl AvFrame<Audio> {
+    // planar float is the sample format every audio encoder we use accepts
+    pub fn planar_float(sample_rate: usize, channels: usize, interleaved: &[f32]) -> Self {
+        let mut frame = Self::new();
+        let samples = interleaved.len() / channels;
+
+        let underlying = frame.as_underlying_mut();
+        underlying.format = ff::AVSampleFormat_AV_SAMPLE_FMT_FLTP;
+        underlying.sample_rate = sample_rate.try_into().expect("sample_rate too large");
+        underlying.channels = channels.try_into().expect("channels too large");
+        underlying.channel_layout = unsafe { ff::av_get_default_channel_layout(underlying.channels) } as u64;
+        underlying.nb_samples = samples.try_into().expect("nb_samples too large");
+
+        let rc = unsafe { ff::av_frame_get_buffer(frame.as_mut_ptr(), 0) };
+
+        if rc != 0 {
+            panic!("av_frame_get_buffer: {:?}", AvError(rc));
+        }
+
+        let underlying = frame.as_underlying();
+
+        for chan in 0..channels {
+            unsafe {
+                let plane = *underlying.extended_data.add(chan) as *mut f32;
+
+                for idx in 0..samples {
+                    *plane.add(idx) = interleaved[idx * channels + chan];
+                }
+            }
+        }
+
+        frame
+    }
+
+    pub fn sample_rate(&self) -> usize {
+        self.as_underlying().sample_rate.try_into().expect("sample_rate >= 0")
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.as_underlying().channels.try_into().expect("channels >= 0")
+    }
+
+    // samples per channel
+    pub fn sample_count(&self) -> usize {
+        self.as_underlying().nb_samples.try_into().expect("nb_samples >= 0")
+    }
+
+    // appends the frame's samples to `out` as interleaved f32, whatever the
+    // sample format the decoder produced
+    pub fn read_interleaved(&self, out: &mut Vec<f32>) -> Result<(), UnsupportedSampleFormat> {
+        let underlying = self.as_underlying();
+        let format = underlying.format as ff::AVSampleFormat;
+
+        let (planar, read): (bool, unsafe fn(*const u8, usize) -> f32) = match format {
+            ff::AVSampleFormat_AV_SAMPLE_FMT_U8 => (false, read_u8),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_S16 => (false, read_s16),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_S32 => (false, read_s32),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_FLT => (false, read_flt),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_DBL => (false, read_dbl),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_U8P => (true, read_u8),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_S16P => (true, read_s16),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_S32P => (true, read_s32),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_FLTP => (true, read_flt),
+            ff::AVSampleFormat_AV_SAMPLE_FMT_DBLP => (true, read_dbl),
+            _ => return Err(UnsupportedSampleFormat(format)),
+        };
+
+        let channels = self.channel_count();
+        let samples = self.sample_count();
+
+        out.reserve(channels * samples);
+
+        // planar formats may have more channels than fit in data, so go
+        // through extended_data which is always valid for audio
+        for idx in 0..samples {
+            for chan in 0..channels {
+                let sample = unsafe {
+                    if planar {
+                        read(*underlying.extended_data.add(chan), idx)
+                    } else {
+                        read(*underlying.extended_data, idx * channels + chan)
+                    }
+                };
+
+                out.push(sample);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct UnsupportedSampleFormat(pub ff::AVSampleFormat);
+
+unsafe fn read_u8(data: *const u8, idx: usize) -> f32 {
+    (*data.add(idx) as f32 - 128.0) / 128.0
+}
+
+unsafe fn read_s16(data: *const u8, idx: usize) -> f32 {
+    *(data as *const i16).add(idx) as f32 / 32768.0
+}
+
+unsafe fn read_s32(data: *const u8, idx: usize) -> f32 {
+    *(data as *const i32).add(idx) as f32 / 2147483648.0
+}
+
+unsafe fn read_flt(data: *const u8, idx: usize) -> f32 {
+    *(data as *const f32).add(idx)
+}
+
+unsafe fn read_dbl(data: *const u8, idx: usize) -> f32 {
+    *(data as *const f64).add(idx) as f32
+}
+
 type PlanarData = [*mut u8; ff::AV_NUM_DATA_POINTERS as usize];
 type PlanarStride = [c_int; ff::AV_NUM_DATA_POINTERS as usize];
 
@@ -383,4 +507,12 @@ impl PictureSettings {
             pixel_format: PixelFormat::yuv420p(),
         }
     }
+
+    pub fn rgba(width: usize, height: usize) -> Self {
+        PictureSettings {
+            width,
+            height,
+            pixel_format: PixelFormat::rgba(),
+        }
+    }
 }
@@ -86,6 +86,13 @@ impl<R: IoReader> AvIoReader<R> {
         }
     }
 
+    // for readers that can only go forward, so that ffmpeg doesn't try to
+    // seek them looking for metadata
+    pub fn unseekable(self) -> Self {
+        unsafe { (*self.ctx.ptr).seekable = 0; }
+        self
+    }
+
     pub fn as_mut_ptr(&mut self) -> *mut ff::AVIOContext {
         self.ctx.ptr
     }
@@ -13,6 +13,11 @@ impl PixelFormat {
         PixelFormat(ff::AVPixelFormat_AV_PIX_FMT_YUV420P)
     }
 
+    /// Packed 8 bit RGB with straight (not premultiplied) alpha.
+    pub const fn rgba() -> Self {
+        PixelFormat(ff::AVPixelFormat_AV_PIX_FMT_RGBA)
+    }
+
     pub unsafe fn from_raw(pixfmt: ff::AVPixelFormat) -> Self {
         PixelFormat(pixfmt)
     }
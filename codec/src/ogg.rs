@@ -1,13 +1,49 @@
-use std::io;
+use std::io::{self, Read};
+use std::mem;
 
+use derive_more::From;
 pub use lewton::VorbisError;
 
-use ogg::{PacketReader, OggReadError};
+use ogg::{PacketReader, PacketWriter, PacketWriteEndInfo, OggReadError};
 use lewton::inside_ogg::read_headers;
 use lewton::audio::{read_audio_packet, PreviousWindowRight, AudioReadError};
 use lewton::header::{read_header_comment, IdentHeader, CommentHeader, SetupHeader};
 
 use crate::{AudioStream, StreamRead, StreamError, Metadata};
+use crate::ffmpeg::{AudioDecodeStream, OpenAudioError};
+
+// an Ogg page header is followed by its segment table, then the first
+// packet, whose first bytes name the codec
+const PAGE_HEADER_LEN: usize = 27;
+const CODEC_MAGIC_LEN: usize = 8;
+
+#[derive(Debug, From)]
+pub enum OggError {
+    Io(io::Error),
+    Vorbis(VorbisError),
+    Opus(OpenAudioError),
+}
+
+/// Opens an Ogg stream carrying either Vorbis or Opus
+pub fn open<T: io::Read + 'static>(mut io: T) -> Result<Box<dyn AudioStream>, OggError> {
+    let mut head = vec![0u8; PAGE_HEADER_LEN];
+    io.read_exact(&mut head)?;
+
+    let segments = head[PAGE_HEADER_LEN - 1] as usize;
+    head.resize(PAGE_HEADER_LEN + segments + CODEC_MAGIC_LEN, 0);
+    io.read_exact(&mut head[PAGE_HEADER_LEN..])?;
+
+    let opus = head.ends_with(b"OpusHead");
+
+    // the decoders read the stream from the start
+    let io = io::Cursor::new(head).chain(io);
+
+    if opus {
+        Ok(Box::new(OggOpusStream::new(io)?))
+    } else {
+        Ok(Box::new(OggStream::new(io)?))
+    }
+}
 
 struct NonSeekStream<T: io::Read> {
     stream: T,
@@ -119,3 +155,159 @@ impl<T: io::Read> AudioStream for OggStream<T> {
         }
     }
 }
+
+/// Opus in Ogg, decoded by ffmpeg. Opus always decodes at 48kHz
+pub struct OggOpusStream<T: io::Read> {
+    decode: AudioDecodeStream<T>,
+}
+
+impl<T: io::Read> OggOpusStream<T> {
+    pub fn new(io: T) -> Result<Self, OpenAudioError> {
+        Ok(OggOpusStream { decode: AudioDecodeStream::open(io)? })
+    }
+}
+
+impl<T: io::Read> AudioStream for OggOpusStream<T> {
+    fn codec_name(&self) -> &'static str {
+        "Opus"
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.decode.sample_rate()
+    }
+
+    fn channels(&self) -> usize {
+        self.decode.channels()
+    }
+
+    fn bitrate_nominal(&self) -> usize {
+        self.decode.bitrate_nominal()
+    }
+
+    fn read(&mut self) -> Result<Option<StreamRead>, StreamError> {
+        self.decode.read()
+    }
+}
+
+/// Muxes encoded Vorbis packets into an Ogg stream. Every stream starts with
+/// its own header pages and granule positions from zero, so a new one is made
+/// for each listener coming in part way through
+pub struct OggVorbisMux {
+    writer: PacketWriter<Vec<u8>>,
+    serial: u32,
+    // end of the packet last written, in samples
+    granule: u64,
+    // granule position of the last page ended
+    page_granule: u64,
+    // a page is ended once it holds this many samples, so that listeners
+    // aren't kept waiting on a full page
+    page_samples: u64,
+}
+
+impl OggVorbisMux {
+    /// Takes the three Vorbis headers with Xiph lacing, as ffmpeg gives them
+    /// in extradata. Returns the mux along with the stream's header pages
+    pub fn new(headers: &[u8], serial: u32, page_samples: u64) -> Result<(Self, Vec<u8>), io::Error> {
+        let headers = xiph_headers(headers)
+            .filter(|headers| headers.len() == 3)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "expected three vorbis headers"))?;
+
+        let mut mux = OggVorbisMux {
+            writer: PacketWriter::new(Vec::new()),
+            serial,
+            granule: 0,
+            page_granule: 0,
+            page_samples,
+        };
+
+        // the identification header has a page of its own, the rest of the
+        // headers finish before the first audio page
+        mux.write(headers[0], PacketWriteEndInfo::EndPage, 0)?;
+        mux.write(headers[1], PacketWriteEndInfo::NormalPacket, 0)?;
+        mux.write(headers[2], PacketWriteEndInfo::EndPage, 0)?;
+
+        let pages = mux.take();
+        Ok((mux, pages))
+    }
+
+    /// Writes a packet `samples` long, returning any pages it completed
+    pub fn packet(&mut self, data: &[u8], samples: u64) -> Result<Vec<u8>, io::Error> {
+        self.granule += samples;
+
+        let end = if self.granule - self.page_granule >= self.page_samples {
+            self.page_granule = self.granule;
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+
+        self.write(data, end, self.granule)?;
+        Ok(self.take())
+    }
+
+    fn write(&mut self, data: &[u8], end: PacketWriteEndInfo, granule: u64) -> Result<(), io::Error> {
+        self.writer.write_packet(data.to_vec().into_boxed_slice(), self.serial, end, granule)
+    }
+
+    fn take(&mut self) -> Vec<u8> {
+        mem::take(self.writer.inner_mut())
+    }
+}
+
+// Xiph lacing starts with the number of packets less one, then the length of
+// each but the last as bytes summed up to the first less than 255
+fn xiph_headers(data: &[u8]) -> Option<Vec<&[u8]>> {
+    let (count, mut rest) = data.split_first()?;
+    let mut lens = Vec::new();
+
+    for _ in 0..*count {
+        let mut len = 0;
+
+        loop {
+            let (byte, tail) = rest.split_first()?;
+            rest = tail;
+            len += *byte as usize;
+
+            if *byte < 255 {
+                break;
+            }
+        }
+
+        lens.push(len);
+    }
+
+    let mut packets = Vec::new();
+
+    for len in lens {
+        if rest.len() < len {
+            return None;
+        }
+
+        let (packet, tail) = rest.split_at(len);
+        packets.push(packet);
+        rest = tail;
+    }
+
+    packets.push(rest);
+    Some(packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_xiph_laced_headers() {
+        let mut data = vec![2, 255, 1, 3];
+        data.extend(vec![b'a'; 256]);
+        data.extend(b"bcd");
+        data.extend(b"efgh");
+
+        let headers = xiph_headers(&data).unwrap();
+
+        assert_eq!(3, headers.len());
+        assert_eq!(256, headers[0].len());
+        assert_eq!(b"bcd", headers[1]);
+        assert_eq!(b"efgh", headers[2]);
+    }
+}
@@ -3,6 +3,9 @@ use std::mem;
 use web_sys::MouseEvent;
 use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback, Children};
 
+use mixlab_protocol::MidiTarget;
+
+use crate::i18n::t;
 use crate::service::midi::{self, RangeSubscription, MidiRangeId, ConfigureTask};
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -21,11 +24,12 @@ pub struct MidiRangeTarget {
 pub enum MidiState {
     Unbound,
     Configure(ConfigureTask),
-    Bound(RangeSubscription),
+    Bound(MidiRangeId, RangeSubscription),
 }
 
 #[derive(Properties, Clone)]
 pub struct MidiTargetProps {
+    pub target: MidiTarget,
     pub ui_mode: MidiUiMode,
     pub onchange: Callback<f64>,
     #[prop_or_default]
@@ -35,6 +39,7 @@ pub struct MidiTargetProps {
 #[derive(Debug)]
 pub enum MidiTargetMsg {
     Configure,
+    ConfigureCancelled,
     Unbind,
     RangeConfigured(MidiRangeId, u8),
     RangeChanged(u8),
@@ -45,11 +50,14 @@ impl Component for MidiRangeTarget {
     type Message = MidiTargetMsg;
 
     fn create(props: MidiTargetProps, link: ComponentLink<Self>) -> Self {
-        MidiRangeTarget {
+        let mut target = MidiRangeTarget {
             props,
             link,
             state: MidiState::Unbound,
-        }
+        };
+
+        target.sync_mapping();
+        target
     }
 
     fn change(&mut self, mut props: MidiTargetProps) -> ShouldRender {
@@ -59,13 +67,16 @@ impl Component for MidiRangeTarget {
             match (&self.state, self.props.ui_mode) {
                 (MidiState::Configure(_), MidiUiMode::Normal) => {
                     // if we're still in configure state when the UI changes
-                    // back to normal mode, return to unbound mode:
+                    // back to normal mode, return to the persisted binding:
                     self.state = MidiState::Unbound;
                 }
                 _ => { /* otherwise do nothing */ }
             }
         }
 
+        // the mapping may have been changed by another session:
+        self.sync_mapping();
+
         true
     }
 
@@ -74,7 +85,7 @@ impl Component for MidiRangeTarget {
             MidiTargetMsg::Configure => {
                 let configure = midi::broker().configure_range(self.link.callback(|result| {
                     match result {
-                        None => MidiTargetMsg::Unbind,
+                        None => MidiTargetMsg::ConfigureCancelled,
                         Some((range_id, range_value)) =>
                             MidiTargetMsg::RangeConfigured(range_id, range_value),
                     }
@@ -82,18 +93,24 @@ impl Component for MidiRangeTarget {
                 self.state = MidiState::Configure(configure);
                 true
             }
+            MidiTargetMsg::ConfigureCancelled => {
+                self.state = MidiState::Unbound;
+                self.sync_mapping();
+                true
+            }
             MidiTargetMsg::Unbind => {
+                midi::broker().persist_mapping(self.props.target.clone(), None);
                 self.state = MidiState::Unbound;
                 true
             }
             MidiTargetMsg::RangeConfigured(range_id, range_value) => {
                 // only handle this message if we're still in configure state:
                 if let MidiState::Configure(_) = self.state {
-                    let subscription = midi::broker().subscribe_range(range_id,
-                        self.link.callback(MidiTargetMsg::RangeChanged));
+                    midi::broker().persist_mapping(self.props.target.clone(),
+                        Some(range_id.to_control()));
 
+                    self.bind(range_id);
                     self.props.onchange.emit(range_value as f64 / 127.0);
-                    self.state = MidiState::Bound(subscription);
                     true
                 } else {
                     false
@@ -109,10 +126,10 @@ impl Component for MidiRangeTarget {
     fn view(&self) -> Html {
         let overlay = match self.props.ui_mode {
             MidiUiMode::Normal => {
-                if let MidiState::Bound(_) = self.state {
+                if let MidiState::Bound(..) = self.state {
                     html! {
                         <div class="midi-target-overlay midi-target-overlay-bound">
-                            <span class="midi-target-overlay-label">{"MIDI"}</span>
+                            <span class="midi-target-overlay-label">{t("common.midi")}</span>
                         </div>
                     }
                 } else {
@@ -123,7 +140,7 @@ impl Component for MidiRangeTarget {
                 let class = match self.state {
                     MidiState::Unbound => "midi-target-overlay midi-target-cfg-overlay midi-target-cfg-overlay-unbound",
                     MidiState::Configure(_) => "midi-target-overlay midi-target-cfg-overlay midi-target-cfg-overlay-configure",
-                    MidiState::Bound(_) => "midi-target-overlay midi-target-cfg-overlay midi-target-cfg-overlay-bound",
+                    MidiState::Bound(..) => "midi-target-overlay midi-target-cfg-overlay midi-target-cfg-overlay-bound",
                 };
 
                 html! {
@@ -152,3 +169,37 @@ impl Component for MidiRangeTarget {
         }
     }
 }
+
+impl MidiRangeTarget {
+    fn bind(&mut self, range_id: MidiRangeId) {
+        let subscription = midi::broker().subscribe_range(range_id.clone(),
+            self.link.callback(MidiTargetMsg::RangeChanged));
+
+        self.state = MidiState::Bound(range_id, subscription);
+    }
+
+    // brings the state in line with the persisted mapping for this target,
+    // leaving it alone while the user is configuring it
+    fn sync_mapping(&mut self) {
+        if let MidiState::Configure(_) = self.state {
+            return;
+        }
+
+        let range_id = midi::broker().mapping(&self.props.target)
+            .map(|control| MidiRangeId::from_control(&control));
+
+        let current = match &self.state {
+            MidiState::Bound(current, _) => Some(current.clone()),
+            _ => None,
+        };
+
+        if current == range_id {
+            return;
+        }
+
+        match range_id {
+            Some(range_id) => self.bind(range_id),
+            None => self.state = MidiState::Unbound,
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! The page a remote guest opens from their guest link, `?guest=<name>`.
+//! It captures their camera and mic and sends them to the StreamInput
+//! listening under that name, so guests need nothing but a browser. The
+//! recording is sent as MediaRecorder produces it, in chunks over a
+//! websocket in place of WebRTC, for the reasons given in the server's
+//! guest module.
+
+use gloo_events::EventListener;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{BlobEvent, HtmlVideoElement, MediaRecorder, MediaRecorderOptions, MediaStream, MediaStreamConstraints, MediaStreamTrack, UrlSearchParams, WebSocket};
+use yew::{html, Component, ComponentLink, Html, ShouldRender, NodeRef};
+
+use crate::i18n::t;
+use crate::util;
+
+// how often the recorder hands over what it has, which bounds the latency
+// it adds
+const CHUNK_MILLIS: i32 = 250;
+
+// preferred recording formats, most preferred first. browsers without any
+// of these record in whatever they default to
+const MIME_TYPES: &[&str] = &[
+    "video/webm;codecs=vp8,opus",
+    "video/webm",
+    "video/mp4",
+];
+
+pub struct GuestPage {
+    link: ComponentLink<Self>,
+    guest: Option<GuestLink>,
+    state: GuestState,
+    video: NodeRef,
+    live: Option<Live>,
+}
+
+struct GuestLink {
+    name: String,
+    key: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum GuestState {
+    Ready,
+    Starting,
+    Live,
+    Failed(String),
+}
+
+// everything which must be kept alive while sending
+struct Live {
+    stream: MediaStream,
+    recorder: MediaRecorder,
+    socket: WebSocket,
+    _listeners: Vec<EventListener>,
+}
+
+pub enum GuestMsg {
+    Join,
+    Media(Result<MediaStream, JsValue>),
+    Disconnected,
+    Leave,
+}
+
+/// Whether this page was opened from a guest link.
+pub fn requested() -> bool {
+    guest_link().is_some()
+}
+
+fn guest_link() -> Option<GuestLink> {
+    let search = web_sys::window()?.location().search().ok()?;
+    let params = UrlSearchParams::new_with_str(&search).ok()?;
+
+    Some(GuestLink {
+        name: params.get("guest").filter(|name| !name.is_empty())?,
+        key: params.get("key").filter(|key| !key.is_empty()),
+    })
+}
+
+impl Component for GuestPage {
+    type Properties = ();
+    type Message = GuestMsg;
+
+    fn create(_: (), link: ComponentLink<Self>) -> Self {
+        GuestPage {
+            link,
+            guest: guest_link(),
+            state: GuestState::Ready,
+            video: NodeRef::default(),
+            live: None,
+        }
+    }
+
+    fn change(&mut self, _: ()) -> ShouldRender {
+        false
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            GuestMsg::Join => {
+                self.state = GuestState::Starting;
+
+                let link = self.link.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    link.send_message(GuestMsg::Media(user_media().await));
+                });
+            }
+            GuestMsg::Media(Ok(stream)) => {
+                match self.start(stream) {
+                    Ok(live) => {
+                        self.live = Some(live);
+                        self.state = GuestState::Live;
+                    }
+                    Err(e) => {
+                        crate::warn!("guest: could not start sending: {:?}", e);
+                        self.state = GuestState::Failed(t("guest.could-not-connect"));
+                    }
+                }
+            }
+            GuestMsg::Media(Err(e)) => {
+                crate::warn!("guest: could not get camera and mic: {:?}", e);
+                self.state = GuestState::Failed(t("guest.no-media"));
+            }
+            GuestMsg::Disconnected => {
+                self.stop();
+                self.state = GuestState::Failed(t("guest.disconnected"));
+            }
+            GuestMsg::Leave => {
+                self.stop();
+                self.state = GuestState::Ready;
+            }
+        }
+
+        true
+    }
+
+    fn view(&self) -> Html {
+        let guest = match &self.guest {
+            Some(guest) => guest,
+            None => return html! {},
+        };
+
+        html! {
+            <div class="guest">
+                <div class="guest-title">{"Mixlab"}</div>
+                <div class="guest-name">{&guest.name}</div>
+                <video class="guest-preview" playsinline=true ref={self.video.clone()} />
+                { match &self.state {
+                    GuestState::Ready => html! {
+                        <button class="guest-join" onclick={self.link.callback(|_| GuestMsg::Join)}>
+                            {t("guest.join")}
+                        </button>
+                    },
+                    GuestState::Starting => html! {
+                        <div class="guest-status">{t("guest.starting")}</div>
+                    },
+                    GuestState::Live => html! {
+                        <>
+                            <div class="guest-status guest-status-live">{t("status.live")}</div>
+                            <button class="guest-leave" onclick={self.link.callback(|_| GuestMsg::Leave)}>
+                                {t("guest.leave")}
+                            </button>
+                        </>
+                    },
+                    GuestState::Failed(reason) => html! {
+                        <>
+                            <div class="guest-status guest-status-failed">{reason}</div>
+                            <button class="guest-join" onclick={self.link.callback(|_| GuestMsg::Join)}>
+                                {t("guest.try-again")}
+                            </button>
+                        </>
+                    },
+                } }
+            </div>
+        }
+    }
+
+    fn destroy(&mut self) {
+        self.stop();
+    }
+}
+
+impl GuestPage {
+    fn start(&mut self, stream: MediaStream) -> Result<Live, JsValue> {
+        // guest is always Some once there's a join button to press
+        let guest = self.guest.as_ref().unwrap();
+
+        if let Some(video) = self.video.cast::<HtmlVideoElement>() {
+            // the guest hearing themselves back would only get in the way
+            video.set_muted(true);
+            video.set_src_object(Some(&stream));
+            let _ = video.play();
+        }
+
+        let recorder = match MIME_TYPES.iter().find(|mime_type| MediaRecorder::is_type_supported(mime_type)) {
+            Some(mime_type) => {
+                let mut options = MediaRecorderOptions::new();
+                options.mime_type(mime_type);
+                MediaRecorder::new_with_media_stream_and_media_recorder_options(&stream, &options)?
+            }
+            None => MediaRecorder::new_with_media_stream(&stream)?,
+        };
+
+        let mut url = format!("{}/_guest/{}", util::websocket_origin(),
+            String::from(js_sys::encode_uri_component(&guest.name)));
+
+        if let Some(key) = &guest.key {
+            url += &format!("?key={}", String::from(js_sys::encode_uri_component(key)));
+        }
+
+        let socket = WebSocket::new(&url)?;
+
+        let listeners = vec![
+            // start recording once there's somewhere to send it, so the
+            // server gets the recording's header first
+            EventListener::once(&socket, "open", {
+                let recorder = recorder.clone();
+                move |_| {
+                    if let Err(e) = recorder.start_with_time_slice(CHUNK_MILLIS) {
+                        crate::warn!("guest: could not start recorder: {:?}", e);
+                    }
+                }
+            }),
+            EventListener::new(&recorder, "dataavailable", {
+                let socket = socket.clone();
+                move |ev| {
+                    let ev = ev.dyn_ref::<BlobEvent>().expect("dyn_ref BlobEvent");
+
+                    if let Some(data) = ev.data() {
+                        if let Err(e) = socket.send_with_blob(&data) {
+                            crate::warn!("guest: could not send recording: {:?}", e);
+                        }
+                    }
+                }
+            }),
+            EventListener::once(&socket, "close", {
+                let link = self.link.clone();
+                move |_| link.send_message(GuestMsg::Disconnected)
+            }),
+        ];
+
+        Ok(Live {
+            stream,
+            recorder,
+            socket,
+            _listeners: listeners,
+        })
+    }
+
+    fn stop(&mut self) {
+        let live = match self.live.take() {
+            Some(live) => live,
+            None => return,
+        };
+
+        // dropping the listeners first means closing the socket here isn't
+        // taken for a disconnection
+        drop(live._listeners);
+
+        let _ = live.recorder.stop();
+        let _ = live.socket.close();
+
+        for track in live.stream.get_tracks().iter() {
+            if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+                track.stop();
+            }
+        }
+
+        if let Some(video) = self.video.cast::<HtmlVideoElement>() {
+            video.set_src_object(None);
+        }
+    }
+}
+
+async fn user_media() -> Result<MediaStream, JsValue> {
+    let media_devices = web_sys::window()
+        .expect("web_sys::window")
+        .navigator()
+        .media_devices()?;
+
+    let mut constraints = MediaStreamConstraints::new();
+    constraints.audio(&JsValue::TRUE);
+    constraints.video(&JsValue::TRUE);
+
+    JsFuture::from(media_devices.get_user_media_with_constraints(&constraints)?)
+        .await?
+        .dyn_into::<MediaStream>()
+}
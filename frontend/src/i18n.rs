@@ -0,0 +1,163 @@
+//! Message catalogs for the UI. English is built in, and the catalog for
+//! any other language is fetched from the server before the app starts.
+//! Messages missing from a catalog fall back to English.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use gloo_events::EventListener;
+use wasm_bindgen::JsValue;
+use web_sys::{Storage, XmlHttpRequest};
+
+use mixlab_protocol::ModuleParams;
+
+use crate::util;
+
+/// Languages the server has catalogs for, by code and name in that
+/// language.
+pub const LANGUAGES: &[(&str, &str)] = &[
+    ("en", "English"),
+    ("de", "Deutsch"),
+];
+
+const DEFAULT_LANGUAGE: &str = "en";
+
+const STORAGE_KEY: &str = "mixlab-language";
+
+const FALLBACK_CATALOG: &str = include_str!("../static/i18n/en.txt");
+
+type Catalog = HashMap<String, String>;
+
+struct Catalogs {
+    current: Catalog,
+    fallback: Catalog,
+}
+
+thread_local! {
+    static CATALOGS: RefCell<Catalogs> = RefCell::new(Catalogs {
+        current: Catalog::new(),
+        fallback: parse(FALLBACK_CATALOG),
+    });
+}
+
+/// Looks up a message in the current language. Keys missing from every
+/// catalog come back as they are, so that they stand out.
+pub fn t(key: &str) -> String {
+    CATALOGS.with(|catalogs| {
+        let catalogs = catalogs.borrow();
+
+        catalogs.current.get(key)
+            .or_else(|| catalogs.fallback.get(key))
+            .cloned()
+            .unwrap_or_else(|| key.to_owned())
+    })
+}
+
+/// As `t`, filling in `{name}` placeholders in the message.
+pub fn tf(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut message = t(key);
+
+    for (name, value) in args {
+        message = message.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+
+    message
+}
+
+pub fn module_name(params: &ModuleParams) -> String {
+    let variant = format!("{:?}", params).chars()
+        .take_while(|c| c.is_alphanumeric())
+        .collect::<String>();
+
+    t(&format!("module.{}", variant))
+}
+
+/// The language chosen in this browser, or the default if none has been.
+pub fn language() -> String {
+    storage()
+        .and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten())
+        .filter(|code| LANGUAGES.iter().any(|(known, _)| known == code))
+        .unwrap_or_else(|| DEFAULT_LANGUAGE.to_owned())
+}
+
+/// Saves the language choice and reloads the page in it. Messages are
+/// looked up as components render, so starting over is the only way to be
+/// sure none are left in the old language.
+pub fn set_language(code: &str) {
+    if let Some(storage) = storage() {
+        if let Err(e) = storage.set_item(STORAGE_KEY, code) {
+            crate::warn!("i18n: could not save language: {:?}", e);
+            return;
+        }
+    }
+
+    let _ = web_sys::window().unwrap().location().reload();
+}
+
+/// Fetches the catalog for the chosen language, calling `ready` once it is
+/// in place. If it can't be fetched the UI carries on in English.
+pub fn load(ready: impl FnOnce() + 'static) {
+    let language = language();
+
+    if language == DEFAULT_LANGUAGE {
+        ready();
+        return;
+    }
+
+    let xhr = match open(&language) {
+        Ok(xhr) => xhr,
+        Err(e) => {
+            crate::warn!("i18n: could not request catalog for {}: {:?}", language, e);
+            ready();
+            return;
+        }
+    };
+
+    EventListener::once(&xhr, "loadend", {
+        let xhr = xhr.clone();
+        move |_| {
+            match (xhr.status(), xhr.response_text()) {
+                (Ok(200), Ok(Some(text))) => {
+                    CATALOGS.with(|catalogs| {
+                        catalogs.borrow_mut().current = parse(&text);
+                    });
+                }
+                (status, _) => {
+                    crate::warn!("i18n: could not load catalog for {}: status {:?}", language, status);
+                }
+            }
+
+            ready();
+        }
+    }).forget();
+
+    if let Err(e) = xhr.send() {
+        crate::warn!("i18n: could not send catalog request: {:?}", e);
+    }
+}
+
+fn open(language: &str) -> Result<XmlHttpRequest, JsValue> {
+    let xhr = XmlHttpRequest::new()?;
+    xhr.open("GET", &(util::origin() + "/_i18n/" + language))?;
+    Ok(xhr)
+}
+
+fn storage() -> Option<Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+// catalogs are lines of `key = message`, with blank lines and lines starting
+// with `#` ignored
+fn parse(text: &str) -> Catalog {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next()?.trim();
+            let message = parts.next()?.trim();
+            Some((key.to_owned(), message.to_owned()))
+        })
+        .collect()
+}
@@ -2,40 +2,62 @@
 
 mod component;
 mod control;
+mod guest;
+mod i18n;
 mod library;
 mod module;
+mod mutate;
+mod reports;
 mod service;
 mod session;
 mod sidebar;
+mod snapshots;
+mod surface;
 mod util;
+mod viewer;
 mod workspace;
 
-use std::fmt::Display;
+use std::fmt::{self, Display};
 
-use derive_more::Display;
 use wasm_bindgen::prelude::*;
 use yew::{html, Component, ComponentLink, Html, ShouldRender, Callback, Properties};
 
 use mixlab_protocol::WorkspaceOp;
 
+use guest::GuestPage;
+use i18n::t;
 use library::MediaLibrary;
+use reports::StreamReports;
 use session::{Session, SessionRef};
 use sidebar::Sidebar;
+use surface::Surface;
 use util::{notify, Sequence};
+use viewer::ViewerPage;
 use workspace::Workspace;
 
 pub struct App {
     link: ComponentLink<Self>,
     session: SessionRef,
     selected_tab: Tab,
+    surface_only: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Display)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Tab {
-    #[display(fmt = "Workspace")]
     Workspace,
-    #[display(fmt = "Media Library")]
     MediaLibrary,
+    Reports,
+}
+
+impl Display for Tab {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Tab::Workspace => t("tab.workspace"),
+            Tab::MediaLibrary => t("tab.media-library"),
+            Tab::Reports => t("tab.reports"),
+        };
+        write!(f, "{}", name)
+    }
 }
 
 #[derive(Debug)]
@@ -53,6 +75,7 @@ impl Component for App {
             link,
             session: Session::new(),
             selected_tab: Tab::Workspace,
+            surface_only: surface_requested(),
         }
     }
 
@@ -74,6 +97,12 @@ impl Component for App {
     }
 
     fn view(&self) -> Html {
+        if self.surface_only {
+            return html! {
+                <SurfaceContainer session={self.session.clone()} />
+            };
+        }
+
         html! {
             <div class="app">
                 <SidebarContainer session={self.session.clone()} />
@@ -84,6 +113,7 @@ impl Component for App {
                         tabs={vec![
                             Tab::Workspace,
                             Tab::MediaLibrary,
+                            Tab::Reports,
                         ]}
                         onchange={self.link.callback(AppMsg::ChangeTab)}
                     />
@@ -98,6 +128,9 @@ impl Component for App {
                         Tab::MediaLibrary => html! {
                             <MediaLibrary session={self.session.clone()} />
                         },
+                        Tab::Reports => html! {
+                            <StreamReports session={self.session.clone()} />
+                        },
                     } }
                 </div>
             </div>
@@ -201,6 +234,62 @@ impl Component for SidebarContainer {
     }
 }
 
+pub struct SurfaceContainer {
+    _notify: notify::Handle,
+    props: SurfaceContainerProps,
+}
+
+#[derive(Properties, Clone)]
+pub struct SurfaceContainerProps {
+    session: SessionRef,
+}
+
+impl Component for SurfaceContainer {
+    type Message = ();
+    type Properties = SurfaceContainerProps;
+
+    fn create(props: SurfaceContainerProps, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_workspace(link.callback(|()| ()));
+
+        SurfaceContainer {
+            _notify: notify,
+            props,
+        }
+    }
+
+    fn change(&mut self, new_props: SurfaceContainerProps) -> ShouldRender {
+        self.props = new_props;
+        true
+    }
+
+    fn update(&mut self, _: ()) -> ShouldRender {
+        true
+    }
+
+    fn view(&self) -> Html {
+        if let Some(state) = self.props.session.workspace() {
+            html! {
+                <Surface
+                    session={self.props.session.clone()}
+                    workspace={state.clone()}
+                />
+            }
+        } else {
+            html! {}
+        }
+    }
+}
+
+// `?view=surface` opens the control surface on its own, without the
+// workspace or sidebar
+fn surface_requested() -> bool {
+    let search = web_sys::window().unwrap().location().search().unwrap_or_default();
+
+    search.trim_start_matches('?')
+        .split('&')
+        .any(|pair| pair == "view=surface")
+}
+
 #[derive(Properties, Clone, Debug)]
 pub struct TabBarProps<T: Clone> {
     current: T,
@@ -260,7 +349,18 @@ impl<T: Display + Clone + PartialEq + 'static> Component for TabBar<T> {
 pub fn start() {
     console_error_panic_hook::set_once();
 
-    yew::start_app::<App>();
+    // messages are looked up as the app renders, so its catalog needs to be
+    // in place before it starts
+    i18n::load(|| {
+        // viewer and guest links open on their own pages, without a session
+        if viewer::requested() {
+            yew::start_app::<ViewerPage>();
+        } else if guest::requested() {
+            yew::start_app::<GuestPage>();
+        } else {
+            yew::start_app::<App>();
+        }
+    });
 }
 
 #[wasm_bindgen]
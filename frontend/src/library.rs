@@ -10,6 +10,7 @@ use yew::{html, Callback, Component, ComponentLink, Html, ShouldRender, Properti
 
 use mixlab_protocol as protocol;
 
+use crate::i18n::t;
 use crate::session::SessionRef;
 use crate::util::{self, notify, Sequence};
 
@@ -109,7 +110,7 @@ impl Component for MediaLibrary {
                     html! {
                         <table class="media-library-table">
                             <tr class="table-heading">
-                                <th>{"Uploads"}</th>
+                                <th>{t("library.uploads")}</th>
                             </tr>
                             { for self.uploads.iter().map(|(id, item)| {
                                 html! {
@@ -145,9 +146,9 @@ impl Component for MediaLibrary {
                     html! {
                         <table class="media-library-table">
                             <tr class="table-heading">
-                                <th>{"Name"}</th>
-                                <th>{"Kind"}</th>
-                                <th>{"Size"}</th>
+                                <th>{t("library.name")}</th>
+                                <th>{t("library.kind")}</th>
+                                <th>{t("library.size")}</th>
                             </tr>
                             { for library.items.iter().map(|item| {
                                 html! {
@@ -247,7 +248,7 @@ impl Component for UploadButton {
         html! {
             <>
                 <label>
-                    <div class="media-library-main-button">{"+ Upload"}</div>
+                    <div class="media-library-main-button">{t("library.upload")}</div>
                     <input
                         type="file"
                         ref={self.input_ref.clone()}
@@ -0,0 +1,64 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, AmbisonicEncoderParams};
+
+use crate::component::midi_target::MidiUiMode;
+use crate::component::pure_module::{Pure, PureModule};
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type AmbisonicEncoder = Pure<AmbisonicEncoderParams>;
+
+impl PureModule for AmbisonicEncoderParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        let azimuth_id = format!("w{}-ambisonic-encoder-azimuth", id.0);
+        let elevation_id = format!("w{}-ambisonic-encoder-elevation", id.0);
+
+        html! {
+            <>
+                <label for={&azimuth_id}>{t("ambisonic-encoder.azimuth")}</label>
+                <input type="range"
+                    id={&azimuth_id}
+                    min={-180}
+                    max={180}
+                    step={1}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(azimuth_str) = ev {
+                                let azimuth = azimuth_str.parse().unwrap_or(params.azimuth);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::AmbisonicEncoder(AmbisonicEncoderParams { azimuth, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={self.azimuth}
+                />
+
+                <label for={&elevation_id}>{t("ambisonic-encoder.elevation")}</label>
+                <input type="range"
+                    id={&elevation_id}
+                    min={-90}
+                    max={90}
+                    step={1}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(elevation_str) = ev {
+                                let elevation = elevation_str.parse().unwrap_or(params.elevation);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::AmbisonicEncoder(AmbisonicEncoderParams { elevation, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={self.elevation}
+                />
+            </>
+        }
+    }
+}
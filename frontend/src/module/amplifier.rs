@@ -5,6 +5,7 @@ use mixlab_protocol::{ModuleId, ModuleParams, AmplifierParams};
 
 use crate::component::pure_module::{Pure, PureModule};
 use crate::component::midi_target::MidiUiMode;
+use crate::i18n::t;
 use crate::workspace::{Window, WindowMsg};
 
 pub type Amplifier = Pure<AmplifierParams>;
@@ -19,7 +20,7 @@ impl PureModule for AmplifierParams {
 
         html! {
             <>
-                <label for={&amp_id}>{"Volume"}</label>
+                <label for={&amp_id}>{t("amplifier.volume")}</label>
                 <input type="range"
                     id={&amp_id}
                     min={0}
@@ -37,7 +38,7 @@ impl PureModule for AmplifierParams {
                     })}
                     value={self.amplitude}
                 />
-                <label for={&mod_id}>{"Mod Depth"}</label>
+                <label for={&mod_id}>{t("amplifier.mod-depth")}</label>
                 <input type="range"
                     id={&mod_id}
                     min={0}
@@ -0,0 +1,43 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, BinauralDecoderParams};
+
+use crate::component::midi_target::MidiUiMode;
+use crate::component::pure_module::{Pure, PureModule};
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type BinauralDecoder = Pure<BinauralDecoderParams>;
+
+impl PureModule for BinauralDecoderParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        let yaw_id = format!("w{}-binaural-decoder-yaw", id.0);
+
+        html! {
+            <>
+                <label for={&yaw_id}>{t("binaural-decoder.yaw")}</label>
+                <input type="range"
+                    id={&yaw_id}
+                    min={-180}
+                    max={180}
+                    step={1}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(yaw_str) = ev {
+                                let yaw = yaw_str.parse().unwrap_or(params.yaw);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::BinauralDecoder(BinauralDecoderParams { yaw, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={self.yaw}
+                />
+                <div class="binaural-decoder-hint">{t("binaural-decoder.experimental")}</div>
+            </>
+        }
+    }
+}
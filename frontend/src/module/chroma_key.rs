@@ -0,0 +1,72 @@
+use yew::{html, ComponentLink, Html, ChangeData};
+
+use mixlab_protocol::{ModuleId, ModuleParams, ChromaKeyParams, Rgb};
+
+use crate::component::midi_target::MidiUiMode;
+use crate::component::pure_module::{Pure, PureModule};
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type ChromaKey = Pure<ChromaKeyParams>;
+
+impl PureModule for ChromaKeyParams {
+    fn view(&self, _: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        html! {
+            <>
+                <label class="form-field">
+                    <span class="form-field-label">{t("chroma-key.key")}</span>
+                    <input type="color"
+                        onchange={module.callback(
+                            update_params(self, move |params, ev| {
+                                let key = value(ev).and_then(|hex| Rgb::from_hex(&hex)).unwrap_or(params.key);
+                                ChromaKeyParams { key, ..params }
+                            }))}
+                        value={self.key.to_hex()}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("chroma-key.threshold")}</span>
+                    <input type="range"
+                        min={0}
+                        max={1}
+                        step={0.01}
+                        onchange={module.callback(
+                            update_params(self, move |params, ev| {
+                                let threshold = value(ev).and_then(|s| s.parse().ok()).unwrap_or(params.threshold);
+                                ChromaKeyParams { threshold, ..params }
+                            }))}
+                        value={self.threshold}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("chroma-key.softness")}</span>
+                    <input type="range"
+                        min={0}
+                        max={0.5}
+                        step={0.01}
+                        onchange={module.callback(
+                            update_params(self, move |params, ev| {
+                                let softness = value(ev).and_then(|s| s.parse().ok()).unwrap_or(params.softness);
+                                ChromaKeyParams { softness, ..params }
+                            }))}
+                        value={self.softness}
+                    />
+                </label>
+            </>
+        }
+    }
+}
+
+fn update_params<T>(params: &ChromaKeyParams, f: impl Fn(ChromaKeyParams, T) -> ChromaKeyParams) -> impl Fn(T) -> WindowMsg {
+    let params = params.clone();
+    move |arg| WindowMsg::UpdateParams(ModuleParams::ChromaKey(f(params.clone(), arg)))
+}
+
+fn value(event: ChangeData) -> Option<String> {
+    match event {
+        ChangeData::Value(value) => Some(value),
+        _ => None
+    }
+}
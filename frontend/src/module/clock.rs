@@ -0,0 +1,41 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ClockParams};
+
+use crate::component::pure_module::{Pure, PureModule};
+use crate::component::midi_target::MidiUiMode;
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type Clock = Pure<ClockParams>;
+
+impl PureModule for ClockParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        let bpm_id = format!("w{}-clock-bpm", id.0);
+
+        html! {
+            <>
+                <label for={&bpm_id}>{t("common.bpm")}</label>
+                <input type="number"
+                    id={&bpm_id}
+                    min={1}
+                    step={1}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(bpm_str) = ev {
+                                let bpm = bpm_str.parse().unwrap_or(params.bpm);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Clock(ClockParams { bpm, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={self.bpm}
+                />
+            </>
+        }
+    }
+}
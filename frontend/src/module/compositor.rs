@@ -0,0 +1,69 @@
+use yew::{html, ComponentLink, Html, ChangeData};
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, CompositorParams};
+
+use crate::component::midi_target::MidiUiMode;
+use crate::component::pure_module::{Pure, PureModule};
+use crate::i18n::t;
+use crate::module::text_overlay::SelectablePosition;
+use crate::workspace::{Window, WindowMsg};
+
+pub type Compositor = Pure<CompositorParams>;
+
+impl PureModule for CompositorParams {
+    fn view(&self, _: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        html! {
+            <>
+                <label class="form-field">
+                    <span class="form-field-label">{t("text-overlay.position")}</span>
+                    <Select<SelectablePosition>
+                        selected={SelectablePosition(self.position)}
+                        options={SelectablePosition::all()}
+                        on_change={module.callback(
+                            update_params(self, move |params, SelectablePosition(position)|
+                                CompositorParams { position, ..params }))}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("compositor.scale")}</span>
+                    <input type="range"
+                        min={0.01}
+                        max={1}
+                        step={0.01}
+                        onchange={module.callback(
+                            update_params(self, move |params, ev|
+                                CompositorParams { scale: float_value(ev).unwrap_or(params.scale), ..params }))}
+                        value={self.scale}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("compositor.opacity")}</span>
+                    <input type="range"
+                        min={0}
+                        max={1}
+                        step={0.01}
+                        onchange={module.callback(
+                            update_params(self, move |params, ev|
+                                CompositorParams { opacity: float_value(ev).unwrap_or(params.opacity), ..params }))}
+                        value={self.opacity}
+                    />
+                </label>
+            </>
+        }
+    }
+}
+
+fn update_params<T>(params: &CompositorParams, f: impl Fn(CompositorParams, T) -> CompositorParams) -> impl Fn(T) -> WindowMsg {
+    let params = params.clone();
+    move |arg| WindowMsg::UpdateParams(ModuleParams::Compositor(f(params.clone(), arg)))
+}
+
+fn float_value(event: ChangeData) -> Option<f64> {
+    match event {
+        ChangeData::Value(float_str) => float_str.parse().ok(),
+        _ => None
+    }
+}
@@ -0,0 +1,96 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, CompressorParams, Decibel};
+
+use crate::component::midi_target::MidiUiMode;
+use crate::component::pure_module::{Pure, PureModule};
+use crate::control::rotary::Rotary;
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type Compressor = Pure<CompressorParams>;
+
+impl PureModule for CompressorParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        html! {
+            <>
+                <div class="dynamics-rotaries">
+                    <div class="dynamics-rotary">
+                        <Rotary<Decibel>
+                            value={self.threshold}
+                            min={Decibel(-60.0)}
+                            max={Decibel(0.0)}
+                            default={Decibel(-18.0)}
+                            onchange={module.callback(update_params(self,
+                                |params, threshold| CompressorParams { threshold, ..params }))}
+                        />
+                        <div class="control-rotary-label">{t("compressor.threshold")}</div>
+                    </div>
+                    <div class="dynamics-rotary">
+                        <Rotary<f64>
+                            value={self.ratio}
+                            min={1.0}
+                            max={20.0}
+                            default={3.0}
+                            onchange={module.callback(update_params(self,
+                                |params, ratio| CompressorParams { ratio, ..params }))}
+                        />
+                        <div class="control-rotary-label">{t("compressor.ratio")}</div>
+                    </div>
+                    <div class="dynamics-rotary">
+                        <Rotary<Decibel>
+                            value={self.makeup}
+                            min={Decibel(0.0)}
+                            max={Decibel(24.0)}
+                            default={Decibel(0.0)}
+                            onchange={module.callback(update_params(self,
+                                |params, makeup| CompressorParams { makeup, ..params }))}
+                        />
+                        <div class="control-rotary-label">{t("compressor.makeup")}</div>
+                    </div>
+                </div>
+
+                {view_time(self, id, &module, "attack", self.attack, |params, attack| CompressorParams { attack, ..params })}
+                {view_time(self, id, &module, "release", self.release, |params, release| CompressorParams { release, ..params })}
+            </>
+        }
+    }
+}
+
+fn update_params<T>(params: &CompressorParams, f: impl Fn(CompressorParams, T) -> CompressorParams) -> impl Fn(T) -> WindowMsg {
+    let params = params.clone();
+    move |value| WindowMsg::UpdateParams(ModuleParams::Compressor(f(params.clone(), value)))
+}
+
+fn view_time(
+    params: &CompressorParams,
+    id: ModuleId,
+    module: &ComponentLink<Window>,
+    name: &str,
+    value: f64,
+    f: impl Fn(CompressorParams, f64) -> CompressorParams + 'static,
+) -> Html {
+    let input_id = format!("w{}-compressor-{}", id.0, name);
+    let params = params.clone();
+
+    html! {
+        <div class="dynamics-time">
+            <label for={&input_id}>{t(&format!("compressor.{}", name))}</label>
+            <input type="number"
+                id={&input_id}
+                min={0}
+                step={0.001}
+                onchange={module.callback(move |ev| {
+                    if let ChangeData::Value(time_str) = ev {
+                        let time = time_str.parse().unwrap_or(value);
+                        WindowMsg::UpdateParams(ModuleParams::Compressor(f(params.clone(), time)))
+                    } else {
+                        unreachable!()
+                    }
+                })}
+                value={value}
+            />
+        </div>
+    }
+}
@@ -0,0 +1,64 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, CrossoverParams};
+
+use crate::component::midi_target::MidiUiMode;
+use crate::component::pure_module::{Pure, PureModule};
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type Crossover = Pure<CrossoverParams>;
+
+impl PureModule for CrossoverParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        let low_id = format!("w{}-crossover-low", id.0);
+        let high_id = format!("w{}-crossover-high", id.0);
+
+        html! {
+            <>
+                <label for={&low_id}>{t("crossover.low-freq")}</label>
+                <input type="number"
+                    id={&low_id}
+                    min={20}
+                    max={20000}
+                    step={1}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(freq_str) = ev {
+                                let low_freq = freq_str.parse().unwrap_or(params.low_freq);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Crossover(CrossoverParams { low_freq, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={self.low_freq}
+                />
+
+                <label for={&high_id}>{t("crossover.high-freq")}</label>
+                <input type="number"
+                    id={&high_id}
+                    min={20}
+                    max={20000}
+                    step={1}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(freq_str) = ev {
+                                let high_freq = freq_str.parse().unwrap_or(params.high_freq);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Crossover(CrossoverParams { high_freq, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={self.high_freq}
+                />
+            </>
+        }
+    }
+}
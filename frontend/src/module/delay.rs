@@ -0,0 +1,123 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, DelayParams, MidiTarget};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::component::pure_module::{Pure, PureModule};
+use crate::control::rotary::Rotary;
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type Delay = Pure<DelayParams>;
+
+// matches the longest delay the server can hold
+const MAX_TIME: f64 = 4.0;
+
+impl PureModule for DelayParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
+        let time_id = format!("w{}-delay-time", id.0);
+        let clock_ratio_id = format!("w{}-delay-clock-ratio", id.0);
+
+        html! {
+            <>
+                <label for={&time_id}>{t("delay.time")}</label>
+                <MidiRangeTarget
+                    target={MidiTarget::new(id, "time")}
+                    ui_mode={midi_mode}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |value: f64| {
+                            WindowMsg::UpdateParams(
+                                ModuleParams::Delay(DelayParams { time: value * MAX_TIME, ..params.clone() }))
+                        }
+                    })}
+                >
+                    <input type="number"
+                        id={&time_id}
+                        min={0}
+                        max={MAX_TIME}
+                        step={0.01}
+                        onchange={module.callback({
+                            let params = self.clone();
+                            move |ev| {
+                                if let ChangeData::Value(time_str) = ev {
+                                    let time = time_str.parse().unwrap_or(params.time);
+                                    WindowMsg::UpdateParams(
+                                        ModuleParams::Delay(DelayParams { time, ..params.clone() }))
+                                } else {
+                                    unreachable!()
+                                }
+                            }
+                        })}
+                        value={self.time}
+                    />
+                </MidiRangeTarget>
+
+                <label for={&clock_ratio_id}>{t("delay.clock-ratio")}</label>
+                <input type="number"
+                    id={&clock_ratio_id}
+                    min={0}
+                    step={0.25}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(ratio_str) = ev {
+                                let clock_ratio = ratio_str.parse().unwrap_or(params.clock_ratio);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Delay(DelayParams { clock_ratio, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={self.clock_ratio}
+                />
+
+                <div>{t("delay.feedback")}</div>
+                <MidiRangeTarget
+                    target={MidiTarget::new(id, "feedback")}
+                    ui_mode={midi_mode}
+                    onchange={module.callback(update_feedback(self))}
+                >
+                    <Rotary<f64>
+                        value={self.feedback}
+                        min={0.0}
+                        max={1.0}
+                        default={0.4}
+                        onchange={module.callback(update_feedback(self))}
+                    />
+                </MidiRangeTarget>
+
+                <div>{t("common.mix")}</div>
+                <MidiRangeTarget
+                    target={MidiTarget::new(id, "mix")}
+                    ui_mode={midi_mode}
+                    onchange={module.callback(update_mix(self))}
+                >
+                    <Rotary<f64>
+                        value={self.mix}
+                        min={0.0}
+                        max={1.0}
+                        default={0.5}
+                        onchange={module.callback(update_mix(self))}
+                    />
+                </MidiRangeTarget>
+            </>
+        }
+    }
+}
+
+fn update_feedback(params: &DelayParams) -> impl Fn(f64) -> WindowMsg {
+    let params = params.clone();
+    move |feedback| {
+        WindowMsg::UpdateParams(ModuleParams::Delay(DelayParams { feedback, ..params.clone() }))
+    }
+}
+
+fn update_mix(params: &DelayParams) -> impl Fn(f64) -> WindowMsg {
+    let params = params.clone();
+    move |mix| {
+        WindowMsg::UpdateParams(ModuleParams::Delay(DelayParams { mix, ..params.clone() }))
+    }
+}
@@ -0,0 +1,111 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, EchoCancellerParams, EchoCancellerIndication};
+
+use crate::i18n::{t, tf};
+use crate::workspace::{Window, WindowMsg};
+
+// matches the limits the server holds to
+const MAX_DELAY_MS: f64 = 5000.0;
+const MIN_TAIL_MS: f64 = 20.0;
+const MAX_TAIL_MS: f64 = 1000.0;
+
+#[derive(Properties, Clone, Debug)]
+pub struct EchoCancellerProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: EchoCancellerParams,
+    pub indication: EchoCancellerIndication,
+}
+
+pub struct EchoCanceller {
+    props: EchoCancellerProps,
+}
+
+impl Component for EchoCanceller {
+    type Properties = EchoCancellerProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        EchoCanceller { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let params = &self.props.params;
+        let indication = &self.props.indication;
+
+        let delay_id = format!("w{}-echo-canceller-delay", self.props.id.0);
+        let tail_id = format!("w{}-echo-canceller-tail", self.props.id.0);
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={talk_class(indication.double_talk)}>{t("echo-canceller.talk")}</div>
+                </div>
+                <div class="echo-canceller-reduction">
+                    {tf("echo-canceller.reduction", &[("reduction", &indication.reduction_db)])}
+                </div>
+
+                <label for={&delay_id}>{t("echo-canceller.delay")}</label>
+                <input type="number"
+                    id={&delay_id}
+                    min={0}
+                    max={MAX_DELAY_MS}
+                    step={10}
+                    onchange={self.callback(move |value, params| {
+                        let delay_ms = value.parse().unwrap_or(params.delay_ms);
+                        EchoCancellerParams { delay_ms, ..params }
+                    })}
+                    value={params.delay_ms}
+                />
+
+                <label for={&tail_id}>{t("echo-canceller.tail")}</label>
+                <input type="number"
+                    id={&tail_id}
+                    min={MIN_TAIL_MS}
+                    max={MAX_TAIL_MS}
+                    step={10}
+                    onchange={self.callback(move |value, params| {
+                        let tail_ms = value.parse().unwrap_or(params.tail_ms);
+                        EchoCancellerParams { tail_ms, ..params }
+                    })}
+                    value={params.tail_ms}
+                />
+            </>
+        }
+    }
+}
+
+impl EchoCanceller {
+    fn callback(&self, f: impl Fn(String, EchoCancellerParams) -> EchoCancellerParams + 'static)
+        -> Callback<ChangeData>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            if let ChangeData::Value(value) = ev {
+                WindowMsg::UpdateParams(
+                    ModuleParams::EchoCanceller(f(value, params.clone())))
+            } else {
+                unreachable!()
+            }
+        })
+    }
+}
+
+fn talk_class(double_talk: bool) -> &'static str {
+    match double_talk {
+        false => "status-light",
+        true => "status-light status-light-green-active",
+    }
+}
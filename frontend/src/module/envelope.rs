@@ -1,8 +1,12 @@
+use std::fmt::{self, Display};
+
 use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
 use yew::events::ChangeData;
+use yew_components::Select;
 
-use mixlab_protocol::{ModuleId, ModuleParams, EnvelopeParams};
+use mixlab_protocol::{ModuleId, ModuleParams, EnvelopeParams, EnvelopeCurve, EnvelopeTrigger, MidiNoteBinding};
 
+use crate::i18n::t;
 use crate::workspace::{Window, WindowMsg};
 
 #[derive(Properties, Clone, Debug)]
@@ -46,9 +50,12 @@ impl Component for Envelope {
         let release_id = format!("w{}-release", self.props.id.0);
         let release_params = self.props.params.clone();
 
+        let midi_id = format!("w{}-midi", self.props.id.0);
+        let midi_params = self.props.params.clone();
+
         html! {
             <>
-                <label for={&attack_id}>{"Attack"}</label>
+                <label for={&attack_id}>{t("envelope.attack")}</label>
                 <input type="range"
                     id={&attack_id}
                     min={5}
@@ -61,7 +68,8 @@ impl Component for Envelope {
                     })}
                     value={self.props.params.attack_ms}
                 />
-                <label for={&decay_id}>{"Decay"}</label>
+                {self.view_curve(self.props.params.attack_curve, |params, attack_curve| EnvelopeParams { attack_curve, ..params })}
+                <label for={&decay_id}>{t("envelope.decay")}</label>
                 <input type="range"
                     id={&decay_id}
                     min={5}
@@ -74,7 +82,8 @@ impl Component for Envelope {
                     })}
                     value={self.props.params.decay_ms}
                 />
-                <label for={&sustain_id}>{"Sustain"}</label>
+                {self.view_curve(self.props.params.decay_curve, |params, decay_curve| EnvelopeParams { decay_curve, ..params })}
+                <label for={&sustain_id}>{t("envelope.sustain")}</label>
                 <input type="range"
                     id={&sustain_id}
                     min={0}
@@ -87,7 +96,7 @@ impl Component for Envelope {
                     })}
                     value={self.props.params.sustain_amplitude}
                 />
-                <label for={&release_id}>{"Release"}</label>
+                <label for={&release_id}>{t("envelope.release")}</label>
                 <input type="range"
                     id={&release_id}
                     min={5}
@@ -100,11 +109,138 @@ impl Component for Envelope {
                     })}
                     value={self.props.params.release_ms}
                 />
+                {self.view_curve(self.props.params.release_curve, |params, release_curve| EnvelopeParams { release_curve, ..params })}
+                {self.view_trigger()}
+                <label for={&midi_id}>{t("envelope.midi-note")}</label>
+                <input type="text"
+                    id={&midi_id}
+                    placeholder={t("envelope.off")}
+                    onchange={self.props.module.callback(move |ev| {
+                        let midi = extract_midi_binding(ev);
+                        let params = EnvelopeParams { midi, ..midi_params };
+                        WindowMsg::UpdateParams(ModuleParams::Envelope(params))
+                    })}
+                    value={format_midi_binding(self.props.params.midi)}
+                />
             </>
         }
     }
 }
 
+#[derive(PartialEq, Clone)]
+struct SelectableCurve(EnvelopeCurve);
+
+impl Display for SelectableCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableCurve(curve) = self;
+        let name = match curve {
+            EnvelopeCurve::Linear => t("envelope.linear"),
+            EnvelopeCurve::Exponential => t("envelope.exponential"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct SelectableTrigger(EnvelopeTrigger);
+
+impl Display for SelectableTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableTrigger(trigger) = self;
+        let name = match trigger {
+            EnvelopeTrigger::Retrigger => t("envelope.retrigger"),
+            EnvelopeTrigger::Legato => t("envelope.legato"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Envelope {
+    fn view_curve(&self, curve: EnvelopeCurve, f: impl Fn(EnvelopeParams, EnvelopeCurve) -> EnvelopeParams + 'static) -> Html {
+        let curves = vec![
+            SelectableCurve(EnvelopeCurve::Linear),
+            SelectableCurve(EnvelopeCurve::Exponential),
+        ];
+
+        let params = self.props.params.clone();
+
+        html! {
+            <Select<SelectableCurve>
+                selected={SelectableCurve(curve)}
+                options={curves}
+                on_change={self.props.module.callback(move |SelectableCurve(curve)| {
+                    WindowMsg::UpdateParams(ModuleParams::Envelope(f(params.clone(), curve)))
+                })}
+            />
+        }
+    }
+
+    fn view_trigger(&self) -> Html {
+        let triggers = vec![
+            SelectableTrigger(EnvelopeTrigger::Retrigger),
+            SelectableTrigger(EnvelopeTrigger::Legato),
+        ];
+
+        let params = self.props.params.clone();
+
+        html! {
+            <label>
+                <div>{t("envelope.trigger")}</div>
+                <Select<SelectableTrigger>
+                    selected={SelectableTrigger(self.props.params.trigger)}
+                    options={triggers}
+                    on_change={self.props.module.callback(move |SelectableTrigger(trigger)| {
+                        let params = EnvelopeParams { trigger, ..params.clone() };
+                        WindowMsg::UpdateParams(ModuleParams::Envelope(params))
+                    })}
+                />
+            </label>
+        }
+    }
+}
+
+// "" disables MIDI gating, "any" responds to every note, otherwise a note
+// number optionally prefixed by a 1-based channel, eg. "60" or "10:36"
+fn extract_midi_binding(event: ChangeData) -> Option<MidiNoteBinding> {
+    let value = match event {
+        ChangeData::Value(value) => value,
+        _ => return None,
+    };
+
+    let value = value.trim();
+
+    if value.is_empty() {
+        return None;
+    }
+
+    if value == "any" {
+        return Some(MidiNoteBinding::default());
+    }
+
+    let (channel, note) = match value.find(':') {
+        Some(idx) => {
+            let channel = value[..idx].trim().parse::<u8>().ok()
+                .filter(|ch| (1..=16).contains(ch))?;
+            (Some(channel - 1), value[idx + 1..].trim())
+        }
+        None => (None, value),
+    };
+
+    let note = note.parse::<u8>().ok().filter(|note| *note < 128)?;
+
+    Some(MidiNoteBinding { channel, note: Some(note) })
+}
+
+fn format_midi_binding(binding: Option<MidiNoteBinding>) -> String {
+    match binding {
+        None => String::new(),
+        Some(MidiNoteBinding { channel: None, note: None }) => "any".to_owned(),
+        Some(MidiNoteBinding { channel: Some(ch), note: Some(note) }) => format!("{}:{}", ch + 1, note),
+        Some(MidiNoteBinding { channel: Some(ch), note: None }) => format!("{}:any", ch + 1),
+        Some(MidiNoteBinding { channel: None, note: Some(note) }) => note.to_string(),
+    }
+}
+
 fn extract_float_value(event: ChangeData) -> Option<f64> {
     match event {
         ChangeData::Value(float_str) => float_str.parse().ok(),
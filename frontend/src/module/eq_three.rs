@@ -1,21 +1,23 @@
 use yew::{html, ComponentLink, Html};
 
-use mixlab_protocol::{ModuleId, ModuleParams, EqThreeParams, Decibel};
+use mixlab_protocol::{ModuleId, ModuleParams, EqThreeParams, Decibel, MidiTarget};
 
 use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
 use crate::component::pure_module::{Pure, PureModule};
 use crate::control::rotary::Rotary;
+use crate::i18n::t;
 use crate::workspace::{Window, WindowMsg};
 
 pub type EqThree = Pure<EqThreeParams>;
 
 impl PureModule for EqThreeParams {
-    fn view(&self, _: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
 
         html! {
             <>
-                <div>{"HI"}</div>
+                <div>{t("eq-three.hi")}</div>
                 <MidiRangeTarget
+                    target={MidiTarget::new(id, "gain-hi")}
                     ui_mode={midi_mode}
                     onchange={module.callback(wrap_decibel(update_params(self,
                         |params, value| EqThreeParams { gain_hi: value, ..params })))}
@@ -30,8 +32,9 @@ impl PureModule for EqThreeParams {
                     />
                 </MidiRangeTarget>
 
-                <div>{"MID"}</div>
+                <div>{t("eq-three.mid")}</div>
                 <MidiRangeTarget
+                    target={MidiTarget::new(id, "gain-mid")}
                     ui_mode={midi_mode}
                     onchange={module.callback(wrap_decibel(update_params(self,
                         |params, value| EqThreeParams { gain_mid: value, ..params })))}
@@ -46,8 +49,9 @@ impl PureModule for EqThreeParams {
                     />
                 </MidiRangeTarget>
 
-                <div>{"LO"}</div>
+                <div>{t("eq-three.lo")}</div>
                 <MidiRangeTarget
+                    target={MidiTarget::new(id, "gain-lo")}
                     ui_mode={midi_mode}
                     onchange={module.callback(wrap_decibel(update_params(self,
                         |params, value| EqThreeParams { gain_lo: value, ..params })))}
@@ -0,0 +1,173 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ModuleCommand, FeedbackSuppressorParams, FeedbackSuppressorIndication, FeedbackSuppressorCommand, FeedbackNotch};
+
+use crate::i18n::{t, tf};
+use crate::workspace::{Window, WindowMsg};
+
+// matches the limits the server holds to
+const MIN_SENSITIVITY_DB: f64 = 6.0;
+const MAX_SENSITIVITY_DB: f64 = 40.0;
+const MAX_NOTCHES: usize = 16;
+const MAX_DEPTH_DB: f64 = 40.0;
+
+#[derive(Properties, Clone, Debug)]
+pub struct FeedbackSuppressorProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: FeedbackSuppressorParams,
+    pub indication: FeedbackSuppressorIndication,
+}
+
+pub struct FeedbackSuppressor {
+    props: FeedbackSuppressorProps,
+}
+
+impl Component for FeedbackSuppressor {
+    type Properties = FeedbackSuppressorProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        FeedbackSuppressor { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let params = &self.props.params;
+        let notches = &self.props.indication.notches;
+
+        let sensitivity_id = format!("w{}-feedback-suppressor-sensitivity", self.props.id.0);
+        let notches_id = format!("w{}-feedback-suppressor-notches", self.props.id.0);
+        let depth_id = format!("w{}-feedback-suppressor-depth", self.props.id.0);
+        let release_id = format!("w{}-feedback-suppressor-release", self.props.id.0);
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={notch_class(!notches.is_empty())}>{t("feedback-suppressor.notch")}</div>
+                </div>
+
+                <div class="feedback-suppressor-notches">
+                    { if notches.is_empty() {
+                        html! { <div class="feedback-suppressor-none">{t("feedback-suppressor.no-notches")}</div> }
+                    } else {
+                        html! { <>{ for notches.iter().map(view_notch) }</> }
+                    } }
+                </div>
+
+                <button
+                    disabled={notches.is_empty()}
+                    onclick={self.command(FeedbackSuppressorCommand::ClearNotches)}
+                >
+                    {t("feedback-suppressor.clear")}
+                </button>
+
+                <label for={&sensitivity_id}>{t("feedback-suppressor.sensitivity")}</label>
+                <input type="number"
+                    id={&sensitivity_id}
+                    min={MIN_SENSITIVITY_DB}
+                    max={MAX_SENSITIVITY_DB}
+                    step={1}
+                    onchange={self.callback(text(move |value, params| {
+                        let sensitivity_db = value.parse().unwrap_or(params.sensitivity_db);
+                        FeedbackSuppressorParams { sensitivity_db, ..params }
+                    }))}
+                    value={params.sensitivity_db}
+                />
+
+                <label for={&notches_id}>{t("feedback-suppressor.max-notches")}</label>
+                <input type="number"
+                    id={&notches_id}
+                    min={0}
+                    max={MAX_NOTCHES}
+                    step={1}
+                    onchange={self.callback(text(move |value, params| {
+                        let max_notches = value.parse().unwrap_or(params.max_notches);
+                        FeedbackSuppressorParams { max_notches, ..params }
+                    }))}
+                    value={params.max_notches}
+                />
+
+                <label for={&depth_id}>{t("feedback-suppressor.max-depth")}</label>
+                <input type="number"
+                    id={&depth_id}
+                    min={0}
+                    max={MAX_DEPTH_DB}
+                    step={3}
+                    onchange={self.callback(text(move |value, params| {
+                        let max_depth_db = value.parse().unwrap_or(params.max_depth_db);
+                        FeedbackSuppressorParams { max_depth_db, ..params }
+                    }))}
+                    value={params.max_depth_db}
+                />
+
+                <label for={&release_id}>{t("feedback-suppressor.release")}</label>
+                <input type="number"
+                    id={&release_id}
+                    min={0}
+                    step={10}
+                    onchange={self.callback(text(move |value, params| {
+                        let release_secs = value.parse().unwrap_or(params.release_secs);
+                        FeedbackSuppressorParams { release_secs, ..params }
+                    }))}
+                    value={params.release_secs}
+                />
+            </>
+        }
+    }
+}
+
+impl FeedbackSuppressor {
+    fn callback<Ev>(&self, f: impl Fn(Ev, FeedbackSuppressorParams) -> FeedbackSuppressorParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::FeedbackSuppressor(f(ev, params.clone())))
+        })
+    }
+
+    fn command<Ev>(&self, command: FeedbackSuppressorCommand) -> Callback<Ev> {
+        self.props.module.callback(move |_| {
+            WindowMsg::Command(ModuleCommand::FeedbackSuppressor(command))
+        })
+    }
+}
+
+fn text<T>(f: impl Fn(String, FeedbackSuppressorParams) -> T)
+    -> impl Fn(ChangeData, FeedbackSuppressorParams) -> T
+{
+    move |change, params| {
+        if let ChangeData::Value(value) = change {
+            f(value, params)
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+fn view_notch(notch: &FeedbackNotch) -> Html {
+    html! {
+        <div class="feedback-suppressor-notch">
+            {tf("feedback-suppressor.notch-detail", &[("freq", &notch.freq_hz), ("depth", &notch.depth_db)])}
+        </div>
+    }
+}
+
+fn notch_class(notching: bool) -> &'static str {
+    match notching {
+        false => "status-light",
+        true => "status-light status-light-red-active",
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt::{self, Display};
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ModuleCommand, FileOutputParams, FileOutputIndication, FileOutputCommand, FileFormat};
+
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct FileOutputProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: FileOutputParams,
+    pub indication: FileOutputIndication,
+}
+
+pub struct FileOutput {
+    props: FileOutputProps,
+}
+
+#[derive(PartialEq, Clone)]
+struct SelectableFormat(FileFormat);
+
+impl Display for SelectableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableFormat(format) = self;
+        let name = match format {
+            FileFormat::Mp4 => t("file-output.mp4"),
+            FileFormat::Mkv => t("file-output.mkv"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Component for FileOutput {
+    type Properties = FileOutputProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let indication = &self.props.indication;
+
+        let formats = vec![
+            SelectableFormat(FileFormat::Mkv),
+            SelectableFormat(FileFormat::Mp4),
+        ];
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={recording_class(indication.recording)}>{t("status.rec")}</div>
+                    <div class={warning_class(indication.error)}>{t("status.error")}</div>
+                </div>
+
+                { if indication.recording {
+                    html! {
+                        <button onclick={self.command(FileOutputCommand::Stop)}>
+                            {t("file-output.stop")}
+                        </button>
+                    }
+                } else {
+                    html! {
+                        <button onclick={self.command(FileOutputCommand::Start)}>
+                            {t("file-output.start")}
+                        </button>
+                    }
+                } }
+
+                { if let Some(file) = &indication.file {
+                    html! {
+                        <div class="file-output-status">
+                            <div class="file-output-file">{file}</div>
+                            <div>{format_time(indication.elapsed_secs)}{" · "}{format_size(indication.bytes)}</div>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                } }
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("file-output.filename")}</span>
+                    <input type="text"
+                        onchange={self.callback(text(move |filename, params| {
+                            FileOutputParams { filename, ..params }
+                        }))}
+                        value={&self.props.params.filename}
+                        disabled={indication.recording}
+                    />
+                </label>
+
+                <div class="file-output-hint">{t("file-output.placeholders")}</div>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("file-output.format")}</span>
+                    <Select<SelectableFormat>
+                        selected={SelectableFormat(self.props.params.format)}
+                        options={formats}
+                        disabled={indication.recording}
+                        on_change={self.callback(move |SelectableFormat(format), params| {
+                            FileOutputParams { format, ..params }
+                        })}
+                    />
+                </label>
+            </>
+        }
+    }
+}
+
+impl FileOutput {
+    fn callback<Ev>(&self, f: impl Fn(Ev, FileOutputParams) -> FileOutputParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::FileOutput(f(ev, params.clone())))
+        })
+    }
+
+    fn command<Ev>(&self, command: FileOutputCommand) -> Callback<Ev> {
+        self.props.module.callback(move |_| {
+            WindowMsg::Command(ModuleCommand::FileOutput(command))
+        })
+    }
+}
+
+fn text<T>(f: impl Fn(String, FileOutputParams) -> T)
+    -> impl Fn(ChangeData, FileOutputParams) -> T
+{
+    move |change, params| {
+        if let ChangeData::Value(value) = change {
+            f(value, params)
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+fn format_time(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+fn format_size(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+    let bytes = bytes as f64;
+
+    if bytes >= GIB {
+        format!("{:.2} GiB", bytes / GIB)
+    } else {
+        format!("{:.1} MiB", bytes / MIB)
+    }
+}
+
+fn recording_class(recording: bool) -> &'static str {
+    match recording {
+        false => "status-light",
+        true => "status-light status-light-red-active",
+    }
+}
+
+fn warning_class(is_warning: bool) -> &'static str {
+    match is_warning {
+        false => "status-light",
+        true => "status-light status-light-red-active",
+    }
+}
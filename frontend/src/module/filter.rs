@@ -0,0 +1,141 @@
+use std::fmt::{self, Display};
+
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, FilterParams, FilterMode, MidiTarget};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::component::pure_module::{Pure, PureModule};
+use crate::control::rotary::Rotary;
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type Filter = Pure<FilterParams>;
+
+// range covered by MIDI control of cutoff, mapped logarithmically
+const MIDI_CUTOFF_MIN: f64 = 20.0;
+const MIDI_CUTOFF_MAX: f64 = 20000.0;
+
+#[derive(PartialEq, Clone)]
+struct SelectableMode(FilterMode);
+
+impl Display for SelectableMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableMode(mode) = self;
+        let name = match mode {
+            FilterMode::LowPass => t("filter.low-pass"),
+            FilterMode::HighPass => t("filter.high-pass"),
+            FilterMode::BandPass => t("filter.band-pass"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl PureModule for FilterParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
+        let cutoff_id = format!("w{}-filter-cutoff", id.0);
+        let cutoff_mod_id = format!("w{}-filter-cutoff-mod", id.0);
+
+        let modes = vec![
+            SelectableMode(FilterMode::LowPass),
+            SelectableMode(FilterMode::HighPass),
+            SelectableMode(FilterMode::BandPass),
+        ];
+
+        html! {
+            <>
+                <label>
+                    <div>{t("filter.mode")}</div>
+                    <Select<SelectableMode>
+                        selected={SelectableMode(self.mode)}
+                        options={modes}
+                        on_change={module.callback({
+                            let params = self.clone();
+                            move |SelectableMode(mode)| {
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Filter(FilterParams { mode, ..params.clone() }))
+                            }
+                        })}
+                    />
+                </label>
+
+                <label for={&cutoff_id}>{t("filter.cutoff")}</label>
+                <MidiRangeTarget
+                    target={MidiTarget::new(id, "cutoff")}
+                    ui_mode={midi_mode}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |value: f64| {
+                            let cutoff = MIDI_CUTOFF_MIN * (MIDI_CUTOFF_MAX / MIDI_CUTOFF_MIN).powf(value);
+                            WindowMsg::UpdateParams(
+                                ModuleParams::Filter(FilterParams { cutoff, ..params.clone() }))
+                        }
+                    })}
+                >
+                    <input type="number"
+                        id={&cutoff_id}
+                        min={0}
+                        step={1}
+                        onchange={module.callback({
+                            let params = self.clone();
+                            move |ev| {
+                                if let ChangeData::Value(cutoff_str) = ev {
+                                    let cutoff = cutoff_str.parse().unwrap_or(params.cutoff);
+                                    WindowMsg::UpdateParams(
+                                        ModuleParams::Filter(FilterParams { cutoff, ..params.clone() }))
+                                } else {
+                                    unreachable!()
+                                }
+                            }
+                        })}
+                        value={self.cutoff}
+                    />
+                </MidiRangeTarget>
+
+                <div>{t("filter.resonance")}</div>
+                <MidiRangeTarget
+                    target={MidiTarget::new(id, "resonance")}
+                    ui_mode={midi_mode}
+                    onchange={module.callback(update_resonance(self))}
+                >
+                    <Rotary<f64>
+                        value={self.resonance}
+                        min={0.0}
+                        max={1.0}
+                        default={0.0}
+                        onchange={module.callback(update_resonance(self))}
+                    />
+                </MidiRangeTarget>
+
+                <label for={&cutoff_mod_id}>{t("filter.cutoff-mod")}</label>
+                <input type="number"
+                    id={&cutoff_mod_id}
+                    min={0}
+                    step={0.1}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(mod_str) = ev {
+                                let cutoff_mod = mod_str.parse().unwrap_or(params.cutoff_mod);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Filter(FilterParams { cutoff_mod, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={self.cutoff_mod}
+                />
+            </>
+        }
+    }
+}
+
+fn update_resonance(params: &FilterParams) -> impl Fn(f64) -> WindowMsg {
+    let params = params.clone();
+    move |resonance| {
+        WindowMsg::UpdateParams(ModuleParams::Filter(FilterParams { resonance, ..params.clone() }))
+    }
+}
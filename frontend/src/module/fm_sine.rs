@@ -5,6 +5,7 @@ use mixlab_protocol::{ModuleId, ModuleParams, FmSineParams};
 
 use crate::component::pure_module::{Pure, PureModule};
 use crate::component::midi_target::MidiUiMode;
+use crate::i18n::t;
 use crate::workspace::{Window, WindowMsg};
 
 pub type FmSine = Pure<FmSineParams>;
@@ -17,7 +18,7 @@ impl PureModule for FmSineParams {
 
         html! {
             <>
-                <label for={&freq_lo_id}>{"Freq Lo"}</label>
+                <label for={&freq_lo_id}>{t("fm-sine.freq-lo")}</label>
                 <input type="number"
                     id={&freq_lo_id}
                     onchange={module.callback({
@@ -36,7 +37,7 @@ impl PureModule for FmSineParams {
                     value={self.freq_lo}
                 />
 
-                <label for={&freq_hi_id}>{"Freq Hi"}</label>
+                <label for={&freq_hi_id}>{t("fm-sine.freq-hi")}</label>
                 <input type="number"
                     id={&freq_hi_id}
                     onchange={module.callback({
@@ -0,0 +1,98 @@
+use std::f32;
+
+use plotters::prelude::*;
+use web_sys::HtmlCanvasElement;
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, NodeRef};
+
+use mixlab_protocol::{ModuleId, GoniometerIndication};
+
+use crate::i18n::tf;
+use crate::workspace::Window;
+
+// correlation below this would lose something in a mono downmix
+const PHASE_WARNING: f32 = 0.0;
+
+#[derive(Properties, Clone, Debug)]
+pub struct GoniometerProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub indication: GoniometerIndication,
+}
+
+pub struct Goniometer {
+    props: GoniometerProps,
+    canvas: NodeRef,
+}
+
+impl Component for Goniometer {
+    type Properties = GoniometerProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Goniometer {
+            props,
+            canvas: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+
+        if let Some(canvas) = self.canvas.cast::<HtmlCanvasElement>() {
+            render(canvas, &self.props.indication.points);
+        }
+
+        true
+    }
+
+    fn view(&self) -> Html {
+        let correlation = self.props.indication.correlation;
+
+        let class = if correlation < PHASE_WARNING {
+            "goniometer-correlation goniometer-correlation-warning"
+        } else {
+            "goniometer-correlation"
+        };
+
+        html! {
+            <>
+                <canvas ref={self.canvas.clone()} width={200} height={200} />
+                <div class={class}>
+                    <div class="goniometer-correlation-marker" style={format!("left:{}%", (correlation + 1.0) * 50.0)}></div>
+                </div>
+                <div class="goniometer-readout">
+                    {tf("goniometer.correlation", &[("correlation", &format!("{:+.2}", correlation))])}
+                </div>
+            </>
+        }
+    }
+}
+
+// mid up the screen and side across it, so mono is a vertical line, left
+// only leans to the left and out of phase audio lies flat
+fn render(canvas: HtmlCanvasElement, points: &[(f32, f32)]) {
+    let backend = CanvasBackend::with_canvas_object(canvas).unwrap();
+    let root = backend.into_drawing_area();
+    root.fill(&BLACK).unwrap();
+
+    let mut chart = ChartBuilder::on(&root)
+        .build_ranged(-1f32..1f32, -1f32..1f32).unwrap();
+
+    let guide = RGBColor(60, 60, 60);
+
+    for &(x, y) in &[(0.0, 1.0), (1.0, 0.0), (1.0, 1.0), (-1.0, 1.0)] {
+        chart.draw_series(LineSeries::new(vec![(-x, -y), (x, y)], &guide)).unwrap();
+    }
+
+    chart.draw_series(points.iter().map(|&(left, right)| {
+        let side = (right - left) / f32::consts::SQRT_2;
+        let mid = (left + right) / f32::consts::SQRT_2;
+        Circle::new((side, mid), 1, GREEN.filled())
+    })).unwrap();
+
+    root.present().unwrap();
+}
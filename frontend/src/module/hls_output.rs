@@ -0,0 +1,187 @@
+use std::fmt::{self, Display};
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, HlsOutputParams, HlsOutputIndication, HlsFormat};
+
+use crate::i18n::t;
+use crate::util;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct HlsOutputProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: HlsOutputParams,
+    pub indication: HlsOutputIndication,
+}
+
+pub struct HlsOutput {
+    props: HlsOutputProps,
+}
+
+#[derive(PartialEq, Clone)]
+struct SelectableFormat(HlsFormat);
+
+impl Display for SelectableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableFormat(format) = self;
+        let name = match format {
+            HlsFormat::Ts => t("hls-output.ts"),
+            HlsFormat::Fmp4 => t("hls-output.fmp4"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Component for HlsOutput {
+    type Properties = HlsOutputProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let formats = vec![
+            SelectableFormat(HlsFormat::Ts),
+            SelectableFormat(HlsFormat::Fmp4),
+        ];
+
+        let playlist_url = format!("{}/_hls/{}/index.m3u8", util::origin(), self.props.params.name);
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={live_class(self.props.indication.segments > 0)}>{t("status.live")}</div>
+                    <div class={warning_class(!self.props.indication.serving)}>{t("status.error")}</div>
+                </div>
+
+                { if self.props.params.private {
+                    html! {
+                        <div class="hls-output-playlist">{t("hls-output.links-only")}</div>
+                    }
+                } else if self.props.indication.serving {
+                    html! {
+                        <a class="hls-output-playlist" href={playlist_url.clone()} target="_blank">{playlist_url}</a>
+                    }
+                } else {
+                    html! {
+                        <div class="hls-output-playlist">{t("hls-output.name-taken")}</div>
+                    }
+                } }
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("hls-output.name")}</span>
+                    <input type="text"
+                        onchange={self.callback(text(move |name, params| {
+                            HlsOutputParams { name, ..params }
+                        }))}
+                        value={&self.props.params.name}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("hls-output.format")}</span>
+                    <Select<SelectableFormat>
+                        selected={SelectableFormat(self.props.params.format)}
+                        options={formats}
+                        on_change={self.callback(move |SelectableFormat(format), params| {
+                            HlsOutputParams { format, ..params }
+                        })}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("hls-output.segment-secs")}</span>
+                    <input type="number"
+                        min={1}
+                        max={10}
+                        step={1}
+                        onchange={self.callback(text(move |secs, params| {
+                            let segment_secs = secs.parse().unwrap_or(params.segment_secs);
+                            HlsOutputParams { segment_secs, ..params }
+                        }))}
+                        value={self.props.params.segment_secs}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("hls-output.playlist-length")}</span>
+                    <input type="number"
+                        min={2}
+                        max={20}
+                        step={1}
+                        onchange={self.callback(text(move |length, params| {
+                            let playlist_length = length.parse().unwrap_or(params.playlist_length);
+                            HlsOutputParams { playlist_length, ..params }
+                        }))}
+                        value={self.props.params.playlist_length}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <input type="checkbox"
+                        checked={self.props.params.private}
+                        onchange={self.callback(move |_, params| {
+                            HlsOutputParams { private: !params.private, ..params }
+                        })}
+                    />
+                    <span class="form-field-label">{t("hls-output.private")}</span>
+                </label>
+            </>
+        }
+    }
+}
+
+impl HlsOutput {
+    fn callback<Ev>(&self, f: impl Fn(Ev, HlsOutputParams) -> HlsOutputParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            let updated_params = f(ev, params.clone());
+
+            WindowMsg::UpdateParams(
+                ModuleParams::HlsOutput(updated_params))
+        })
+    }
+}
+
+fn text<T>(f: impl Fn(String, HlsOutputParams) -> T)
+    -> impl Fn(ChangeData, HlsOutputParams) -> T
+{
+    move |change, params| {
+        if let ChangeData::Value(value) = change {
+            f(value, params)
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+fn live_class(is_live: bool) -> &'static str {
+    match is_live {
+        false => "status-light",
+        true => "status-light status-light-green-active",
+    }
+}
+
+fn warning_class(is_warning: bool) -> &'static str {
+    match is_warning {
+        false => "status-light",
+        true => "status-light status-light-red-active",
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt::{self, Display};
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ModuleCommand, IcecastOutputCommand, IcecastOutputParams, IcecastOutputIndication, IcecastFormat, StreamOutputLiveStatus};
+
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct IcecastOutputProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: IcecastOutputParams,
+    pub indication: IcecastOutputIndication,
+}
+
+pub struct IcecastOutput {
+    props: IcecastOutputProps,
+}
+
+#[derive(PartialEq, Clone)]
+struct SelectableFormat(IcecastFormat);
+
+impl Display for SelectableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableFormat(format) = self;
+        let name = match format {
+            IcecastFormat::OggVorbis => t("icecast-output.ogg-vorbis"),
+            IcecastFormat::Mp3 => t("icecast-output.mp3"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Component for IcecastOutput {
+    type Properties = IcecastOutputProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let is_conn_active = match self.props.indication.live {
+            StreamOutputLiveStatus::Offline => false,
+            StreamOutputLiveStatus::Connecting | StreamOutputLiveStatus::Live => true,
+        };
+
+        let formats = vec![
+            SelectableFormat(IcecastFormat::OggVorbis),
+            SelectableFormat(IcecastFormat::Mp3),
+        ];
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={live_class(self.props.indication.live)}>{t("status.live")}</div>
+                    <div class={warning_class(self.props.indication.error)}>{t("status.error")}</div>
+                </div>
+
+                { if is_conn_active {
+                    html! {
+                        <button onclick={self.command(IcecastOutputCommand::Disconnect)}>
+                            {t("action.disconnect")}
+                        </button>
+                    }
+                } else {
+                    html! {
+                        <button onclick={self.command(IcecastOutputCommand::Connect)}>
+                            {t("action.connect")}
+                        </button>
+                    }
+                } }
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("icecast-output.url")}</span>
+                    <input type="text"
+                        placeholder={"http://host:8000/mountpoint"}
+                        onchange={self.callback(text(move |url, params| {
+                            IcecastOutputParams { url, ..params }
+                        }))}
+                        value={&self.props.params.url}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("icecast-output.source-password")}</span>
+                    <input type="password"
+                        onchange={self.callback(text(move |password, params| {
+                            IcecastOutputParams { password, ..params }
+                        }))}
+                        value={&self.props.params.password}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("icecast-output.format")}</span>
+                    <Select<SelectableFormat>
+                        selected={SelectableFormat(self.props.params.format)}
+                        options={formats}
+                        on_change={self.callback(move |SelectableFormat(format), params| {
+                            IcecastOutputParams { format, ..params }
+                        })}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("icecast-output.bitrate")}</span>
+                    <input type="number"
+                        min={32}
+                        max={320}
+                        step={32}
+                        onchange={self.callback(text(move |bitrate, params| {
+                            let bitrate_kbps = bitrate.parse().unwrap_or(params.bitrate_kbps);
+                            IcecastOutputParams { bitrate_kbps, ..params }
+                        }))}
+                        value={self.props.params.bitrate_kbps}
+                    />
+                </label>
+            </>
+        }
+    }
+}
+
+impl IcecastOutput {
+    fn callback<Ev>(&self, f: impl Fn(Ev, IcecastOutputParams) -> IcecastOutputParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::IcecastOutput(f(ev, params.clone())))
+        })
+    }
+
+    fn command<Ev>(&self, command: IcecastOutputCommand) -> Callback<Ev> {
+        self.props.module.callback(move |_| {
+            WindowMsg::Command(ModuleCommand::IcecastOutput(command))
+        })
+    }
+}
+
+fn text<T>(f: impl Fn(String, IcecastOutputParams) -> T)
+    -> impl Fn(ChangeData, IcecastOutputParams) -> T
+{
+    move |change, params| {
+        if let ChangeData::Value(value) = change {
+            f(value, params)
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+fn live_class(live_status: StreamOutputLiveStatus) -> &'static str {
+    match live_status {
+        StreamOutputLiveStatus::Offline => "status-light",
+        StreamOutputLiveStatus::Connecting => "status-light status-light-green",
+        StreamOutputLiveStatus::Live => "status-light status-light-green-active",
+    }
+}
+
+fn warning_class(is_warning: bool) -> &'static str {
+    match is_warning {
+        false => "status-light",
+        true => "status-light status-light-red-active",
+    }
+}
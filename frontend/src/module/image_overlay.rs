@@ -0,0 +1,165 @@
+use std::rc::Rc;
+
+use yew::{html, ChangeData, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ImageOverlayParams, MediaLibrary};
+
+use crate::i18n::t;
+use crate::module::media_source::MediaSourceItem;
+use crate::module::text_overlay::SelectablePosition;
+use crate::util::notify;
+use crate::session::SessionRef;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone)]
+pub struct ImageOverlayProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: ImageOverlayParams,
+    pub session: SessionRef,
+}
+
+pub struct ImageOverlay {
+    props: ImageOverlayProps,
+    link: ComponentLink<Self>,
+    library: Option<Rc<MediaLibrary>>,
+    _notify: notify::Handle,
+}
+
+pub enum ImageOverlayMsg {
+    MediaLibrary(Rc<MediaLibrary>),
+    ChangeImage(MediaSourceItem),
+    ChangePosition(SelectablePosition),
+    ChangeScale(f64),
+    ChangeOpacity(f64),
+}
+
+impl Component for ImageOverlay {
+    type Properties = ImageOverlayProps;
+    type Message = ImageOverlayMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_media(link.callback(ImageOverlayMsg::MediaLibrary));
+
+        Self {
+            props,
+            link,
+            library: None,
+            _notify: notify,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            ImageOverlayMsg::MediaLibrary(library) => {
+                self.library = Some(library);
+                true
+            }
+            ImageOverlayMsg::ChangeImage(item) => {
+                self.update_params(ImageOverlayParams { media_id: Some(item.id), ..self.props.params.clone() });
+                false
+            }
+            ImageOverlayMsg::ChangePosition(SelectablePosition(position)) => {
+                self.update_params(ImageOverlayParams { position, ..self.props.params.clone() });
+                false
+            }
+            ImageOverlayMsg::ChangeScale(scale) => {
+                self.update_params(ImageOverlayParams { scale, ..self.props.params.clone() });
+                false
+            }
+            ImageOverlayMsg::ChangeOpacity(opacity) => {
+                self.update_params(ImageOverlayParams { opacity, ..self.props.params.clone() });
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let options = self.library.iter()
+            .flat_map(|library| library.items.iter().cloned())
+            .map(|item| {
+                MediaSourceItem {
+                    id: item.id,
+                    name: item.name.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let selected = self.props.params.media_id.map(|id| {
+            MediaSourceItem {
+                id,
+                // name can be empty, we never display this item
+                name: String::new(),
+            }
+        });
+
+        let params = &self.props.params;
+
+        html! {
+            <>
+                <label class="form-field">
+                    <span class="form-field-label">{t("image-overlay.image")}</span>
+                    <Select<MediaSourceItem>
+                        options={options}
+                        selected={selected}
+                        on_change={self.link.callback(ImageOverlayMsg::ChangeImage)}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("text-overlay.position")}</span>
+                    <Select<SelectablePosition>
+                        selected={SelectablePosition(params.position)}
+                        options={SelectablePosition::all()}
+                        on_change={self.link.callback(ImageOverlayMsg::ChangePosition)}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("image-overlay.scale")}</span>
+                    <input type="range"
+                        min={0.01}
+                        max={1}
+                        step={0.01}
+                        onchange={self.link.callback(|ev| {
+                            ImageOverlayMsg::ChangeScale(extract_float_value(ev).unwrap_or(0.15))
+                        })}
+                        value={params.scale}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("image-overlay.opacity")}</span>
+                    <input type="range"
+                        min={0}
+                        max={1}
+                        step={0.01}
+                        onchange={self.link.callback(|ev| {
+                            ImageOverlayMsg::ChangeOpacity(extract_float_value(ev).unwrap_or(1.0))
+                        })}
+                        value={params.opacity}
+                    />
+                </label>
+            </>
+        }
+    }
+}
+
+impl ImageOverlay {
+    fn update_params(&self, params: ImageOverlayParams) {
+        self.props.module.send_message(WindowMsg::UpdateParams(ModuleParams::ImageOverlay(params)));
+    }
+}
+
+fn extract_float_value(event: ChangeData) -> Option<f64> {
+    match event {
+        ChangeData::Value(float_str) => float_str.parse().ok(),
+        _ => None
+    }
+}
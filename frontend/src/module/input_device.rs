@@ -0,0 +1,160 @@
+use std::fmt::{self, Display};
+use std::iter;
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, InputDeviceParams, InputDeviceIndication, TemporalWarningStatus};
+
+use crate::i18n::{t, tf};
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct InputDeviceProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: InputDeviceParams,
+    pub indication: InputDeviceIndication,
+}
+
+pub struct InputDevice {
+    props: InputDeviceProps,
+}
+
+impl Component for InputDevice {
+    type Properties = InputDeviceProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        #[derive(PartialEq, Clone)]
+        struct InputChannel(Option<usize>);
+
+        impl Display for InputChannel {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self.0 {
+                    Some(ch) => {
+                        // channels are 0-indexed internally, but 1-indexed in the UI:
+                        let display_channel_number = ch + 1;
+
+                        write!(f, "{}", tf("device.channel", &[("number", &display_channel_number)]))
+                    }
+                    None => {
+                        write!(f, "{}", t("device.none"))
+                    }
+                }
+            }
+        }
+
+        let devices = self.props.indication.devices.as_ref()
+            .map(|devices| devices.as_slice())
+            .unwrap_or(&[]);
+
+        let device_names = devices.iter()
+            .map(|(device_name, _)| device_name)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let channels = iter::once(None)
+            .chain(
+                devices.iter()
+                    .find(|(dev, _)| Some(dev) == self.props.params.device.as_ref())
+                    .into_iter()
+                    .flat_map(|(_, channel_count)| 0..*channel_count)
+                    .map(Some))
+            .map(InputChannel)
+            .collect::<Vec<_>>();
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={warning_class(self.props.indication.clip)}>{t("status.clip")}</div>
+                    <div class={warning_class(self.props.indication.lag)}>{t("status.lag")}</div>
+                </div>
+                <button
+                    onclick={self.props.module.callback({
+                        let device = self.props.indication.default_device.clone();
+
+                        let channel_count = devices.iter()
+                            .find(|(name, _)| Some(name) == device.as_ref())
+                            .map(|(_, channels)| channels);
+
+                        let left = Some(0).filter(|ch| channel_count >= Some(ch));
+                        let right = Some(1).filter(|ch| channel_count >= Some(ch));
+
+                        let params = InputDeviceParams {
+                            device,
+                            left,
+                            right,
+                            ..self.props.params.clone()
+                        };
+
+                        move |_| WindowMsg::UpdateParams(
+                            ModuleParams::InputDevice(params.clone()))
+                    })}
+                >
+                    {t("device.use-system-defaults")}
+                </button>
+
+                <label>{t("input-device.device")}</label>
+                <Select<String>
+                    selected={&self.props.params.device}
+                    options={device_names}
+                    on_change={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |device: String| {
+                            let params = InputDeviceParams { device: Some(device), ..params.clone() };
+                            WindowMsg::UpdateParams(ModuleParams::InputDevice(params))
+                        }
+                    })}
+                />
+
+                <label>{t("device.left-channel")}</label>
+                <Select<InputChannel>
+                    selected={InputChannel(self.props.params.left)}
+                    options={channels.clone()}
+                    on_change={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |chan: InputChannel| {
+                            let params = InputDeviceParams { left: chan.0, ..params.clone() };
+                            WindowMsg::UpdateParams(ModuleParams::InputDevice(params))
+                        }
+                    })}
+                />
+
+                <label>{t("device.right-channel")}</label>
+                <Select<InputChannel>
+                    selected={InputChannel(self.props.params.right)}
+                    options={channels}
+                    on_change={self.props.module.callback({
+                        let params = self.props.params.clone();
+                        move |chan: InputChannel| {
+                            let params = InputDeviceParams { right: chan.0, ..params.clone() };
+                            WindowMsg::UpdateParams(ModuleParams::InputDevice(params))
+                        }
+                    })}
+                />
+            </>
+        }
+    }
+}
+
+fn warning_class(warning_status: Option<TemporalWarningStatus>) -> &'static str {
+    match warning_status {
+        None => "status-light",
+        Some(TemporalWarningStatus::Active) => "status-light status-light-red-active",
+        Some(TemporalWarningStatus::Recent) => "status-light status-light-red",
+    }
+}
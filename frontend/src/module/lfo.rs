@@ -0,0 +1,107 @@
+use std::fmt::{self, Display};
+
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, LfoParams, Waveform};
+
+use crate::component::pure_module::{Pure, PureModule};
+use crate::component::midi_target::MidiUiMode;
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type Lfo = Pure<LfoParams>;
+
+#[derive(PartialEq, Clone)]
+struct SelectableWaveform(Waveform);
+
+impl Display for SelectableWaveform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableWaveform(waveform) = self;
+        let name = match waveform {
+            Waveform::Sine => t("waveform.sine"),
+            Waveform::Square => t("waveform.square"),
+            Waveform::Saw => t("waveform.sawtooth"),
+            Waveform::Triangle => t("waveform.triangle"),
+            Waveform::Pulse => t("waveform.pulse"),
+            Waveform::On => t("waveform.high"),
+            Waveform::Off => t("waveform.zero"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl PureModule for LfoParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        let freq_id = format!("w{}-lfo-freq", id.0);
+        let depth_id = format!("w{}-lfo-depth", id.0);
+
+        let waveforms = vec![
+            SelectableWaveform(Waveform::Sine),
+            SelectableWaveform(Waveform::Triangle),
+            SelectableWaveform(Waveform::Saw),
+            SelectableWaveform(Waveform::Square),
+        ];
+
+        html! {
+            <>
+                <label>
+                    <div>{t("waveform.label")}</div>
+                    <Select<SelectableWaveform>
+                        selected={SelectableWaveform(self.waveform)}
+                        options={waveforms}
+                        on_change={module.callback({
+                            let params = self.clone();
+                            move |SelectableWaveform(waveform)| {
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Lfo(LfoParams { waveform, ..params.clone() }))
+                            }
+                        })}
+                    />
+                </label>
+
+                <label for={&freq_id}>{t("lfo.rate")}</label>
+                <input type="number"
+                    id={&freq_id}
+                    min={0}
+                    step={0.01}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(freq_str) = ev {
+                                let freq = freq_str.parse().unwrap_or(0.0);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Lfo(LfoParams { freq, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={self.freq}
+                />
+
+                <label for={&depth_id}>{t("lfo.depth")}</label>
+                <input type="range"
+                    id={&depth_id}
+                    min={0}
+                    max={1}
+                    step={0.01}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            if let ChangeData::Value(depth_str) = ev {
+                                let depth = depth_str.parse().unwrap_or(0.0);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Lfo(LfoParams { depth, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={self.depth}
+                />
+            </>
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, LimiterParams, Decibel};
+
+use crate::component::midi_target::MidiUiMode;
+use crate::component::pure_module::{Pure, PureModule};
+use crate::control::rotary::Rotary;
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type Limiter = Pure<LimiterParams>;
+
+impl PureModule for LimiterParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        let release_id = format!("w{}-limiter-release", id.0);
+
+        html! {
+            <>
+                <div class="dynamics-rotaries">
+                    <div class="dynamics-rotary">
+                        <Rotary<Decibel>
+                            value={self.ceiling}
+                            min={Decibel(-24.0)}
+                            max={Decibel(0.0)}
+                            default={Decibel(-1.0)}
+                            onchange={module.callback({
+                                let params = self.clone();
+                                move |ceiling| WindowMsg::UpdateParams(
+                                    ModuleParams::Limiter(LimiterParams { ceiling, ..params.clone() }))
+                            })}
+                        />
+                        <div class="control-rotary-label">{t("limiter.ceiling")}</div>
+                    </div>
+                </div>
+
+                <div class="dynamics-time">
+                    <label for={&release_id}>{t("limiter.release")}</label>
+                    <input type="number"
+                        id={&release_id}
+                        min={0}
+                        step={0.001}
+                        onchange={module.callback({
+                            let params = self.clone();
+                            move |ev| {
+                                if let ChangeData::Value(time_str) = ev {
+                                    let release = time_str.parse().unwrap_or(params.release);
+                                    WindowMsg::UpdateParams(
+                                        ModuleParams::Limiter(LimiterParams { release, ..params.clone() }))
+                                } else {
+                                    unreachable!()
+                                }
+                            }
+                        })}
+                        value={self.release}
+                    />
+                </div>
+            </>
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, LooperParams, LooperIndication};
+
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct LooperProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: LooperParams,
+    pub indication: LooperIndication,
+}
+
+pub struct Looper {
+    props: LooperProps,
+}
+
+impl Component for Looper {
+    type Properties = LooperProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Looper { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let indication = &self.props.indication;
+
+        let state = if indication.recording {
+            t("looper.recording")
+        } else if indication.overdubbing {
+            t("looper.overdubbing")
+        } else if indication.playing {
+            t("looper.playing")
+        } else if indication.loops.is_empty() {
+            t("looper.empty")
+        } else {
+            t("looper.stopped")
+        };
+
+        let level_id = format!("w{}-looper-level", self.props.id.0);
+        let params = self.props.params.clone();
+
+        html! {
+            <>
+                <div class="looper-status">
+                    <span class={if indication.recording { "looper-state looper-state-recording" } else { "looper-state" }}>
+                        {state}
+                    </span>
+                    <span class="looper-loops">
+                        {format!("{} loops", indication.loops.len())}
+                    </span>
+                </div>
+                <div class="looper-position">
+                    <div class="looper-position-bar" style={format!("width:{}%", indication.position * 100.0)}></div>
+                </div>
+                <div class="looper-lengths">
+                    { for indication.loops.iter().map(|secs| html! {
+                        <span class="looper-length">{format!("{:.1}s", secs)}</span>
+                    }) }
+                </div>
+
+                <label for={&level_id}>{t("looper.level")}</label>
+                <input type="range"
+                    id={&level_id}
+                    min={0}
+                    max={1}
+                    step={0.01}
+                    onchange={self.props.module.callback(move |ev| {
+                        if let ChangeData::Value(level_str) = ev {
+                            let level = level_str.parse().unwrap_or(params.level);
+                            WindowMsg::UpdateParams(
+                                ModuleParams::Looper(LooperParams { level, ..params.clone() }))
+                        } else {
+                            unreachable!()
+                        }
+                    })}
+                    value={self.props.params.level}
+                />
+            </>
+        }
+    }
+}
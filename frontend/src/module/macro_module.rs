@@ -0,0 +1,72 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+
+use mixlab_protocol::{ModuleId, MacroParams};
+
+use crate::i18n::{self, t};
+use crate::session::SessionRef;
+use crate::workspace::Window;
+
+#[derive(Properties, Clone, Debug)]
+pub struct MacroProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: MacroParams,
+    pub session: SessionRef,
+    // saved macros are shared by the whole project, so only sessions
+    // without a permission group may save them
+    pub save: bool,
+}
+
+pub struct Macro {
+    props: MacroProps,
+    link: ComponentLink<Self>,
+}
+
+pub enum MacroMsg {
+    Save,
+}
+
+impl Component for Macro {
+    type Properties = MacroProps;
+    type Message = MacroMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self { props, link }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            MacroMsg::Save => {
+                self.props.session.save_macro(self.props.params.clone());
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <div class="macro">
+                <div class="macro-name">{&self.props.params.name}</div>
+                <div class="macro-modules">
+                    { for self.props.params.modules.iter().map(|module| html! {
+                        <div class="macro-module">{i18n::module_name(module)}</div>
+                    }) }
+                </div>
+                { if self.props.save {
+                    html! {
+                        <button onclick={self.link.callback(|_| MacroMsg::Save)}>
+                            {t("macro.save")}
+                        </button>
+                    }
+                } else {
+                    html! {}
+                } }
+            </div>
+        }
+    }
+}
@@ -1,11 +1,12 @@
 use std::fmt::{self, Display};
 use std::rc::Rc;
 
-use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::{html, ChangeData, Component, ComponentLink, Html, ShouldRender, Properties};
 use yew_components::Select;
 
-use mixlab_protocol::{ModuleId, ModuleParams, MediaSourceParams, MediaLibrary, MediaId};
+use mixlab_protocol::{ModuleId, ModuleParams, MediaSourceParams, MediaSourceIndication, MediaLibrary, MediaId};
 
+use crate::i18n::t;
 use crate::util::notify;
 use crate::session::SessionRef;
 use crate::workspace::{Window, WindowMsg};
@@ -15,6 +16,7 @@ pub struct MediaSourceProps {
     pub id: ModuleId,
     pub module: ComponentLink<Window>,
     pub params: MediaSourceParams,
+    pub indication: MediaSourceIndication,
     pub session: SessionRef,
 }
 
@@ -28,6 +30,9 @@ pub struct MediaSource {
 pub enum MediaSourceMsg {
     MediaLibrary(Rc<MediaLibrary>),
     ChangeSource(MediaSourceItem),
+    TogglePlaying,
+    ToggleLooping,
+    Seek(f64),
 }
 
 impl Component for MediaSource {
@@ -52,13 +57,33 @@ impl Component for MediaSource {
                 true
             }
             MediaSourceMsg::ChangeSource(source) => {
-                self.props.module.send_message(
-                    WindowMsg::UpdateParams(
-                        ModuleParams::MediaSource(
-                            MediaSourceParams {
-                                media_id: Some(source.id),
-                                ..self.props.params.clone()
-                            })));
+                self.update_params(MediaSourceParams {
+                    media_id: Some(source.id),
+                    position: 0.0,
+                    ..self.props.params.clone()
+                });
+                false
+            }
+            MediaSourceMsg::TogglePlaying => {
+                self.update_params(MediaSourceParams {
+                    playing: !self.props.params.playing,
+                    ..self.props.params.clone()
+                });
+                false
+            }
+            MediaSourceMsg::ToggleLooping => {
+                self.update_params(MediaSourceParams {
+                    looping: !self.props.params.looping,
+                    ..self.props.params.clone()
+                });
+                false
+            }
+            MediaSourceMsg::Seek(position) => {
+                self.update_params(MediaSourceParams {
+                    position,
+                    seek_seq: self.props.params.seek_seq + 1,
+                    ..self.props.params.clone()
+                });
                 false
             }
         }
@@ -88,20 +113,71 @@ impl Component for MediaSource {
             }
         });
 
+        let indication = &self.props.indication;
+        let duration = indication.duration.unwrap_or(0.0);
+
         html! {
-            <Select<MediaSourceItem>
-                options={options}
-                selected={selected}
-                on_change={self.link.callback(MediaSourceMsg::ChangeSource)}
-            />
+            <>
+                <Select<MediaSourceItem>
+                    options={options}
+                    selected={selected}
+                    on_change={self.link.callback(MediaSourceMsg::ChangeSource)}
+                />
+                <div class="media-source-transport">
+                    <button onclick={self.link.callback(|_| MediaSourceMsg::TogglePlaying)}>
+                        {if self.props.params.playing { t("media-source.pause") } else { t("media-source.play") }}
+                    </button>
+                    <label>
+                        <input type="checkbox"
+                            checked={self.props.params.looping}
+                            onchange={self.link.callback(|_| MediaSourceMsg::ToggleLooping)}
+                        />
+                        {t("media-source.loop")}
+                    </label>
+                    <span class="media-source-time">
+                        {format!("{} / {}", format_time(indication.position), format_time(duration))}
+                    </span>
+                </div>
+                <input type="range"
+                    class="media-source-scrub"
+                    min={0}
+                    max={duration}
+                    step={0.1}
+                    disabled={indication.duration.is_none()}
+                    value={indication.position}
+                    onchange={self.link.callback(|ev| {
+                        MediaSourceMsg::Seek(extract_float_value(ev).unwrap_or(0.0))
+                    })}
+                />
+            </>
         }
     }
 }
 
+impl MediaSource {
+    fn update_params(&self, params: MediaSourceParams) {
+        self.props.module.send_message(
+            WindowMsg::UpdateParams(
+                ModuleParams::MediaSource(params)));
+    }
+}
+
+fn format_time(secs: f64) -> String {
+    let secs = secs as u64;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn extract_float_value(event: ChangeData) -> Option<f64> {
+    match event {
+        ChangeData::Value(float_str) => float_str.parse().ok(),
+        _ => None
+    }
+}
+
 #[derive(Clone)]
 pub struct MediaSourceItem {
-    id: MediaId,
-    name: String,
+    pub id: MediaId,
+    pub name: String,
 }
 
 impl PartialEq for MediaSourceItem {
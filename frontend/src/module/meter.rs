@@ -0,0 +1,104 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+
+use mixlab_protocol::{ModuleId, ModuleCommand, MeterIndication, MeterLevel, MeterCommand};
+
+use crate::i18n::{t, tf};
+use crate::workspace::{Window, WindowMsg};
+
+// bottom of the meter scale. quieter signals are still measured, but there
+// is nothing useful to see down there when setting levels
+pub const SCALE_MIN_DB: f32 = -60.0;
+
+#[derive(Properties, Clone, Debug)]
+pub struct MeterProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub indication: MeterIndication,
+}
+
+pub struct Meter {
+    props: MeterProps,
+}
+
+impl Component for Meter {
+    type Properties = MeterProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Meter { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let channels = &self.props.indication.channels;
+        let clipped = channels.iter().any(|level| level.clipped);
+
+        let peak = channels.iter().map(|level| level.peak_db).fold(SCALE_MIN_DB, f32::max);
+        let rms = channels.iter().map(|level| level.rms_db).fold(SCALE_MIN_DB, f32::max);
+
+        html! {
+            <>
+                <div class="meter">
+                    { for channels.iter().map(view_channel) }
+                </div>
+
+                <div class="meter-readout">
+                    {tf("meter.readout", &[("peak", &format_db(peak)), ("rms", &format_db(rms))])}
+                </div>
+
+                <div class="status-light-bar">
+                    <div class={clip_class(clipped)}>{t("meter.clip")}</div>
+                    <button disabled={!clipped} onclick={self.command(MeterCommand::ResetClip)}>
+                        {t("meter.reset-clip")}
+                    </button>
+                </div>
+            </>
+        }
+    }
+}
+
+impl Meter {
+    fn command<Ev>(&self, command: MeterCommand) -> Callback<Ev> {
+        self.props.module.callback(move |_| {
+            WindowMsg::Command(ModuleCommand::Meter(command))
+        })
+    }
+}
+
+fn view_channel(level: &MeterLevel) -> Html {
+    html! {
+        <div class="meter-channel">
+            <div class="meter-rms" style={format!("width:{}%", position(level.rms_db))}></div>
+            <div class="meter-peak" style={format!("left:{}%", position(level.peak_db))}></div>
+            <div class="meter-hold" style={format!("left:{}%", position(level.hold_db))}></div>
+        </div>
+    }
+}
+
+// percentage along the meter scale
+pub fn position(db: f32) -> f32 {
+    ((db - SCALE_MIN_DB) / -SCALE_MIN_DB).max(0.0).min(1.0) * 100.0
+}
+
+fn format_db(db: f32) -> String {
+    if db <= SCALE_MIN_DB {
+        "-∞".to_owned()
+    } else {
+        format!("{:.1}", db)
+    }
+}
+
+fn clip_class(clipped: bool) -> &'static str {
+    match clipped {
+        false => "status-light",
+        true => "status-light status-light-red-active",
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt::{self, Display};
+
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, MidiNoteParams, VoiceAllocation};
+
+use crate::component::pure_module::{Pure, PureModule};
+use crate::component::midi_target::MidiUiMode;
+use crate::i18n::{t, tf};
+use crate::workspace::{Window, WindowMsg};
+
+pub type MidiNote = Pure<MidiNoteParams>;
+
+#[derive(PartialEq, Clone)]
+struct SelectableAllocation(VoiceAllocation);
+
+impl Display for SelectableAllocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableAllocation(allocation) = self;
+        let name = match allocation {
+            VoiceAllocation::RoundRobin => t("midi-note.round-robin"),
+            VoiceAllocation::LastNote => t("midi-note.last-note"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl PureModule for MidiNoteParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        let channel_id = format!("w{}-midi-note-channel", id.0);
+
+        let allocations = vec![
+            SelectableAllocation(VoiceAllocation::RoundRobin),
+            SelectableAllocation(VoiceAllocation::LastNote),
+        ];
+
+        html! {
+            <>
+                <div>{if self.voices == 1 { t("midi-note.one-voice") } else { tf("midi-note.voices", &[("voices", &self.voices)]) }}</div>
+
+                <label for={&channel_id}>{t("midi-note.channel")}</label>
+                <input type="text"
+                    id={&channel_id}
+                    placeholder={t("common.any")}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |ev| {
+                            let channel = extract_channel(ev);
+                            WindowMsg::UpdateParams(
+                                ModuleParams::MidiNote(MidiNoteParams { channel, ..params.clone() }))
+                        }
+                    })}
+                    value={self.channel.map(|ch| (ch + 1).to_string()).unwrap_or_default()}
+                />
+
+                <label>
+                    <div>{t("midi-note.allocation")}</div>
+                    <Select<SelectableAllocation>
+                        selected={SelectableAllocation(self.allocation)}
+                        options={allocations}
+                        on_change={module.callback({
+                            let params = self.clone();
+                            move |SelectableAllocation(allocation)| {
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::MidiNote(MidiNoteParams { allocation, ..params.clone() }))
+                            }
+                        })}
+                    />
+                </label>
+            </>
+        }
+    }
+}
+
+// 1-based channel number, anything else listens on every channel
+fn extract_channel(event: ChangeData) -> Option<u8> {
+    match event {
+        ChangeData::Value(value) => {
+            value.trim().parse::<u8>().ok()
+                .filter(|ch| (1..=16).contains(ch))
+                .map(|ch| ch - 1)
+        }
+        _ => None,
+    }
+}
@@ -1,9 +1,11 @@
 use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
 
-use mixlab_protocol::{ModuleId, MixerParams, MixerChannelParams, ModuleParams, Decibel};
+use mixlab_protocol::{ModuleId, MixerParams, MixerChannelParams, MixerIndication, MixerChannelLevels, ModuleParams, Decibel, MidiTarget, MIXER_MAX_CHANNELS};
 
 use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
 use crate::control::{Fader, Rotary};
+use crate::i18n::t;
+use crate::module::meter;
 use crate::workspace::{Window, WindowMsg};
 
 pub struct Mixer {
@@ -16,11 +18,15 @@ pub struct MixerProps {
     pub id: ModuleId,
     pub module: ComponentLink<Window>,
     pub params: MixerParams,
+    pub indication: MixerIndication,
     pub midi_mode: MidiUiMode,
 }
 
 pub enum MixerMsg {
     ChannelChanged(usize, MixerChannelParams),
+    CueDuckChanged(Decibel),
+    AddChannel,
+    RemoveChannel,
 }
 
 impl Component for Mixer {
@@ -46,10 +52,39 @@ impl Component for Mixer {
                         ModuleParams::Mixer(params)));
                 false
             }
+            MixerMsg::CueDuckChanged(cue_duck) => {
+                let params = MixerParams { cue_duck, ..self.props.params.clone() };
+                self.props.module.send_message(
+                    WindowMsg::UpdateParams(
+                        ModuleParams::Mixer(params)));
+                false
+            }
+            MixerMsg::AddChannel => {
+                let mut params = self.props.params.clone();
+                if params.channels.len() < MIXER_MAX_CHANNELS {
+                    params.channels.push(MixerChannelParams::default());
+                    self.props.module.send_message(
+                        WindowMsg::UpdateParams(
+                            ModuleParams::Mixer(params)));
+                }
+                false
+            }
+            MixerMsg::RemoveChannel => {
+                let mut params = self.props.params.clone();
+                if params.channels.len() > 1 {
+                    params.channels.pop();
+                    self.props.module.send_message(
+                        WindowMsg::UpdateParams(
+                            ModuleParams::Mixer(params)));
+                }
+                false
+            }
         }
     }
 
     fn view(&self) -> Html {
+        let channels = self.props.params.channels.len();
+
         html! {
             <div class="mixer-channels">
                 { for self.props.params.channels.iter()
@@ -57,7 +92,10 @@ impl Component for Mixer {
                     .map(|(idx, channel)| {
                         html! {
                             <Channel
+                                id={self.props.id}
+                                index={idx}
                                 params={channel}
+                                levels={self.props.indication.channels.get(idx).copied()}
                                 onchange={self.link.callback(move |params|
                                     MixerMsg::ChannelChanged(idx, params))}
                                 midi_mode={self.props.midi_mode}
@@ -65,6 +103,34 @@ impl Component for Mixer {
                         }
                     })
                 }
+                <div class="mixer-channel">
+                    <Rotary<Decibel>
+                        value={self.props.params.cue_duck}
+                        min={Decibel(-40.0)}
+                        max={Decibel(0.0)}
+                        default={Decibel(-20.0)}
+                        onchange={self.link.callback(MixerMsg::CueDuckChanged)}
+                    />
+                    <div class="control-rotary-label">{t("mixer.cue-dim")}</div>
+                    <div class="channel-count">
+                        <button
+                            class="channel-count-btn"
+                            title={t("mixer.remove-channel")}
+                            disabled={channels <= 1}
+                            onclick={self.link.callback(|_| MixerMsg::RemoveChannel)}
+                        >
+                            {"−"}
+                        </button>
+                        <button
+                            class="channel-count-btn"
+                            title={t("mixer.add-channel")}
+                            disabled={channels >= MIXER_MAX_CHANNELS}
+                            onclick={self.link.callback(|_| MixerMsg::AddChannel)}
+                        >
+                            {"+"}
+                        </button>
+                    </div>
+                </div>
             </div>
         }
     }
@@ -83,7 +149,11 @@ pub enum ChannelMsg {
 
 #[derive(Properties, Clone)]
 pub struct ChannelProps {
+    pub id: ModuleId,
+    pub index: usize,
     pub params: MixerChannelParams,
+    // missing until the server has metered a newly added channel
+    pub levels: Option<MixerChannelLevels>,
     pub onchange: Callback<MixerChannelParams>,
     pub midi_mode: MidiUiMode,
 }
@@ -138,6 +208,7 @@ impl Component for Channel {
         html! {
             <div class="mixer-channel">
                 <MidiRangeTarget
+                    target={self.midi_target("gain")}
                     ui_mode={self.props.midi_mode}
                     onchange={self.link.callback(|gain| {
                         ChannelMsg::GainChanged(Decibel(gain * 30.0 - 24.0))
@@ -152,17 +223,45 @@ impl Component for Channel {
                     />
                 </MidiRangeTarget>
                 <div class={cue_style} onclick={self.link.callback(|_| ChannelMsg::CueClick)}>
-                    {"CUE"}
+                    {t("mixer.cue")}
                 </div>
-                <MidiRangeTarget
-                    ui_mode={self.props.midi_mode}
-                    onchange={self.link.callback(ChannelMsg::FaderChanged)}
-                >
-                    <Fader
-                        value={self.props.params.fader}
+                <div class="mixer-channel-fader">
+                    <MidiRangeTarget
+                        target={self.midi_target("fader")}
+                        ui_mode={self.props.midi_mode}
                         onchange={self.link.callback(ChannelMsg::FaderChanged)}
-                    />
-                </MidiRangeTarget>
+                    >
+                        <Fader
+                            value={self.props.params.fader}
+                            onchange={self.link.callback(ChannelMsg::FaderChanged)}
+                        />
+                    </MidiRangeTarget>
+                    {self.view_levels()}
+                </div>
+            </div>
+        }
+    }
+}
+
+impl Channel {
+    fn midi_target(&self, control: &str) -> MidiTarget {
+        MidiTarget::new(self.props.id, format!("channel-{}/{}", self.props.index + 1, control))
+    }
+
+    fn view_levels(&self) -> Html {
+        let levels = self.props.levels.unwrap_or(MixerChannelLevels {
+            pre_fader_db: meter::SCALE_MIN_DB,
+            post_fader_db: meter::SCALE_MIN_DB,
+        });
+
+        html! {
+            <div class="mixer-channel-levels">
+                <div class="mixer-channel-level" title={t("mixer.pre-fader")}>
+                    <div class="mixer-channel-level-pre" style={format!("height:{}%", meter::position(levels.pre_fader_db))}></div>
+                </div>
+                <div class="mixer-channel-level" title={t("mixer.post-fader")}>
+                    <div class="mixer-channel-level-post" style={format!("height:{}%", meter::position(levels.post_fader_db))}></div>
+                </div>
             </div>
         }
     }
@@ -1,14 +1,52 @@
+pub mod ambisonic_encoder;
 pub mod amplifier;
+pub mod binaural_decoder;
+pub mod chroma_key;
+pub mod clock;
+pub mod compositor;
+pub mod compressor;
+pub mod crossover;
+pub mod delay;
+pub mod echo_canceller;
 pub mod envelope;
 pub mod eq_three;
+pub mod feedback_suppressor;
+pub mod file_output;
+pub mod filter;
 pub mod fm_sine;
+pub mod goniometer;
+pub mod hls_output;
+pub mod icecast_output;
+pub mod image_overlay;
+pub mod input_device;
+pub mod lfo;
+pub mod limiter;
+pub mod looper;
+pub mod macro_module;
 pub mod media_source;
+pub mod meter;
+pub mod midi_note;
 pub mod mixer;
 pub mod monitor;
+pub mod noise_gate;
 pub mod oscillator;
 pub mod output_device;
+pub mod pitch_shift;
 pub mod plotter;
+pub mod profanity_delay;
+pub mod recorder;
+pub mod reverb;
+pub mod sampler;
+pub mod sequencer;
+pub mod silence_detector;
+pub mod slate;
+pub mod spectrum_analyzer;
 pub mod stream_input;
 pub mod stream_output;
+pub mod sync_check;
+pub mod talkback;
+pub mod text_overlay;
 pub mod trigger;
+pub mod tuner;
 pub mod video_mixer;
+pub mod waveshaper;
@@ -7,21 +7,25 @@ use yew::services::websocket::{WebSocketService, WebSocketStatus, WebSocketTask}
 use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, NodeRef, Callback};
 
 use mixlab_mux::mp4::Mp4Mux;
-use mixlab_protocol::{ModuleId, MonitorIndication, MonitorTransportPacket};
+use mixlab_protocol::{MonitorIndication, MonitorTransportPacket};
 
 use crate::util;
 
 #[derive(Properties, Clone, Debug)]
 pub struct MonitorProps {
-    pub id: ModuleId,
-    pub indication: MonitorIndication,
+    pub socket_url: String,
+}
+
+/// Where the workspace's own monitors stream from. Viewer links stream the
+/// same thing from elsewhere.
+pub fn socket_url(indication: &MonitorIndication) -> String {
+    format!("{}/_monitor/{}", util::websocket_origin(), indication.socket_id)
 }
 
 pub struct Monitor {
     link: ComponentLink<Self>,
     props: MonitorProps,
     state: MonitorState,
-    socket_url: String,
     video_element: NodeRef,
     _source_open_event: Option<EventListener>,
 }
@@ -58,12 +62,9 @@ impl Component for Monitor {
     type Message = MonitorMsg;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let socket_url = format!("{}/_monitor/{}", util::websocket_origin(), props.indication.socket_id);
-
         Monitor {
             link,
             props,
-            socket_url,
             state: MonitorState::Stopped,
             video_element: NodeRef::default(),
             _source_open_event: None,
@@ -87,7 +88,7 @@ impl Component for Monitor {
 
                         let source_open_event = EventListener::new(&media_source, "sourceopen", {
                             let link = self.link.clone();
-                            let socket_url = self.socket_url.clone();
+                            let socket_url = self.props.socket_url.clone();
                             let media_source = media_source.clone();
 
                             move |_| {
@@ -0,0 +1,127 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, NoiseGateParams, GateState, Decibel};
+
+use crate::control::Rotary;
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct NoiseGateProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: NoiseGateParams,
+    pub indication: GateState,
+}
+
+pub struct NoiseGate {
+    props: NoiseGateProps,
+}
+
+impl Component for NoiseGate {
+    type Properties = NoiseGateProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        NoiseGate { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let params = &self.props.params;
+
+        let light_class = match self.props.indication {
+            GateState::Open => "status-light status-light-green-active",
+            GateState::Closed => "status-light",
+        };
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={light_class}>{t("noise-gate.open")}</div>
+                </div>
+
+                <div class="noise-gate-rotaries">
+                    <div class="noise-gate-rotary">
+                        <Rotary<Decibel>
+                            value={params.threshold}
+                            min={Decibel(-80.0)}
+                            max={Decibel(0.0)}
+                            default={Decibel(-40.0)}
+                            onchange={self.update(|params, threshold| NoiseGateParams { threshold, ..params })}
+                        />
+                        <div class="control-rotary-label">{t("noise-gate.threshold")}</div>
+                    </div>
+                    <div class="noise-gate-rotary">
+                        <Rotary<Decibel>
+                            value={params.hysteresis}
+                            min={Decibel(0.0)}
+                            max={Decibel(20.0)}
+                            default={Decibel(6.0)}
+                            onchange={self.update(|params, hysteresis| NoiseGateParams { hysteresis, ..params })}
+                        />
+                        <div class="control-rotary-label">{t("noise-gate.hysteresis")}</div>
+                    </div>
+                    <div class="noise-gate-rotary">
+                        <Rotary<Decibel>
+                            value={params.range}
+                            min={Decibel(-80.0)}
+                            max={Decibel(0.0)}
+                            default={Decibel(-60.0)}
+                            onchange={self.update(|params, range| NoiseGateParams { range, ..params })}
+                        />
+                        <div class="control-rotary-label">{t("noise-gate.range")}</div>
+                    </div>
+                </div>
+
+                {self.view_time("attack", params.attack, |params, attack| NoiseGateParams { attack, ..params })}
+                {self.view_time("hold", params.hold, |params, hold| NoiseGateParams { hold, ..params })}
+                {self.view_time("release", params.release, |params, release| NoiseGateParams { release, ..params })}
+            </>
+        }
+    }
+}
+
+impl NoiseGate {
+    fn update<T: 'static>(&self, f: impl Fn(NoiseGateParams, T) -> NoiseGateParams + 'static) -> Callback<T> {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |value| {
+            WindowMsg::UpdateParams(ModuleParams::NoiseGate(f(params.clone(), value)))
+        })
+    }
+
+    fn view_time(&self, name: &str, value: f64, f: impl Fn(NoiseGateParams, f64) -> NoiseGateParams + 'static) -> Html {
+        let id = format!("w{}-noise-gate-{}", self.props.id.0, name);
+        let params = self.props.params.clone();
+
+        html! {
+            <div class="noise-gate-time">
+                <label for={&id}>{t(&format!("noise-gate.{}", name))}</label>
+                <input type="number"
+                    id={&id}
+                    min={0}
+                    step={0.001}
+                    onchange={self.props.module.callback(move |ev| {
+                        if let ChangeData::Value(time_str) = ev {
+                            let time = time_str.parse().unwrap_or(value);
+                            WindowMsg::UpdateParams(ModuleParams::NoiseGate(f(params.clone(), time)))
+                        } else {
+                            unreachable!()
+                        }
+                    })}
+                    value={value}
+                />
+            </div>
+        }
+    }
+}
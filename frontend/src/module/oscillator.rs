@@ -4,8 +4,9 @@ use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
 use yew::events::ChangeData;
 use yew_components::Select;
 
-use mixlab_protocol::{ModuleId, ModuleParams, OscillatorParams, Waveform};
+use mixlab_protocol::{ModuleId, ModuleParams, OscillatorParams, Waveform, FreqScale};
 
+use crate::i18n::t;
 use crate::workspace::{Window, WindowMsg};
 
 #[derive(Properties, Clone, Debug)]
@@ -44,12 +45,13 @@ impl Component for Oscillator {
             fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
                 let SelectableWaveform(waveform) = self;
                 let name = match waveform {
-                    Waveform::Sine => "Sine",
-                    Waveform::Square => "Square",
-                    Waveform::Saw => "Sawtooth",
-                    Waveform::Triangle => "Triangle",
-                    Waveform::On => "High",
-                    Waveform::Off => "Zero",
+                    Waveform::Sine => t("waveform.sine"),
+                    Waveform::Square => t("waveform.square"),
+                    Waveform::Saw => t("waveform.sawtooth"),
+                    Waveform::Triangle => t("waveform.triangle"),
+                    Waveform::Pulse => t("waveform.pulse"),
+                    Waveform::On => t("waveform.high"),
+                    Waveform::Off => t("waveform.zero"),
                 };
                 write!(f, "{}", name)
             }
@@ -60,6 +62,7 @@ impl Component for Oscillator {
             SelectableWaveform(Waveform::Square),
             SelectableWaveform(Waveform::Saw),
             SelectableWaveform(Waveform::Triangle),
+            SelectableWaveform(Waveform::Pulse),
             SelectableWaveform(Waveform::On),
             SelectableWaveform(Waveform::Off),
         ];
@@ -69,7 +72,7 @@ impl Component for Oscillator {
         html! {
             <>
                 <label>
-                    <div>{"Waveform"}</div>
+                    <div>{t("waveform.label")}</div>
                     <Select<SelectableWaveform>
                         selected={SelectableWaveform(params.waveform.clone())}
                         options={waveforms}
@@ -87,7 +90,7 @@ impl Component for Oscillator {
                     />
                 </label>
                 <label>
-                    <div>{"Frequency"}</div>
+                    <div>{t("oscillator.frequency")}</div>
                     <input type="number"
                         onchange={self.props.module.callback(move |ev| {
                             if let ChangeData::Value(freq_str) = ev {
@@ -102,7 +105,108 @@ impl Component for Oscillator {
                         value={self.props.params.freq}
                     />
                 </label>
+                {self.view_freq_mod()}
+                {self.view_pulse_width()}
             </>
         }
     }
 }
+
+impl Oscillator {
+    fn view_freq_mod(&self) -> Html {
+        #[derive(PartialEq, Clone)]
+        struct SelectableScale(FreqScale);
+
+        impl Display for SelectableScale {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let SelectableScale(scale) = self;
+                let name = match scale {
+                    FreqScale::Hz => t("oscillator.scale-hz"),
+                    FreqScale::VoltPerOctave => t("oscillator.scale-volt-per-octave"),
+                };
+                write!(f, "{}", name)
+            }
+        }
+
+        let scales = vec![
+            SelectableScale(FreqScale::VoltPerOctave),
+            SelectableScale(FreqScale::Hz),
+        ];
+
+        let params = self.props.params.clone();
+
+        let mod_label = match params.freq_scale {
+            FreqScale::Hz => t("oscillator.freq-mod-hz"),
+            FreqScale::VoltPerOctave => t("oscillator.freq-mod-octaves"),
+        };
+
+        html! {
+            <>
+                <label>
+                    <div>{t("oscillator.freq-scale")}</div>
+                    <Select<SelectableScale>
+                        selected={SelectableScale(params.freq_scale)}
+                        options={scales}
+                        on_change={self.props.module.callback({
+                            let params = params.clone();
+                            move |SelectableScale(freq_scale)| {
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Oscillator(OscillatorParams {
+                                        freq_scale,
+                                        ..params.clone()
+                                    }))
+                            }
+                        })}
+                    />
+                </label>
+                <label>
+                    <div>{mod_label}</div>
+                    <input type="number"
+                        step={0.1}
+                        onchange={self.props.module.callback(move |ev| {
+                            if let ChangeData::Value(mod_str) = ev {
+                                let freq_mod = mod_str.parse().unwrap_or(params.freq_mod);
+                                let params = OscillatorParams { freq_mod, ..params.clone() };
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Oscillator(params))
+                            } else {
+                                unreachable!()
+                            }
+                        })}
+                        value={self.props.params.freq_mod}
+                    />
+                </label>
+            </>
+        }
+    }
+
+    fn view_pulse_width(&self) -> Html {
+        if self.props.params.waveform != Waveform::Pulse {
+            return html! {};
+        }
+
+        let params = self.props.params.clone();
+
+        html! {
+            <label>
+                <div>{t("oscillator.pulse-width")}</div>
+                <input type="number"
+                    min={0.01}
+                    max={0.99}
+                    step={0.01}
+                    onchange={self.props.module.callback(move |ev| {
+                        if let ChangeData::Value(width_str) = ev {
+                            let pulse_width = width_str.parse().unwrap_or(params.pulse_width);
+                            let params = OscillatorParams { pulse_width, ..params.clone() };
+                            WindowMsg::UpdateParams(
+                                ModuleParams::Oscillator(params))
+                        } else {
+                            unreachable!()
+                        }
+                    })}
+                    value={self.props.params.pulse_width}
+                />
+            </label>
+        }
+    }
+}
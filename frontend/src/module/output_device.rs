@@ -6,6 +6,7 @@ use yew_components::Select;
 
 use mixlab_protocol::{ModuleId, ModuleParams, OutputDeviceParams, OutputDeviceIndication, TemporalWarningStatus};
 
+use crate::i18n::{t, tf};
 use crate::workspace::{Window, WindowMsg};
 
 #[derive(Properties, Clone, Debug)]
@@ -48,10 +49,10 @@ impl Component for OutputDevice {
                         // channels are 0-indexed internally, but 1-indexed in the UI:
                         let display_channel_number = ch + 1;
 
-                        write!(f, "Channel #{}", display_channel_number)
+                        write!(f, "{}", tf("device.channel", &[("number", &display_channel_number)]))
                     }
                     None => {
-                        write!(f, "None")
+                        write!(f, "{}", t("device.none"))
                     }
                 }
             }
@@ -79,8 +80,8 @@ impl Component for OutputDevice {
         html! {
             <>
                 <div class="status-light-bar">
-                    <div class={warning_class(self.props.indication.clip)}>{"CLIP"}</div>
-                    <div class={warning_class(self.props.indication.lag)}>{"LAG"}</div>
+                    <div class={warning_class(self.props.indication.clip)}>{t("status.clip")}</div>
+                    <div class={warning_class(self.props.indication.lag)}>{t("status.lag")}</div>
                 </div>
                 <button
                     onclick={self.props.module.callback({
@@ -104,10 +105,10 @@ impl Component for OutputDevice {
                             ModuleParams::OutputDevice(params.clone()))
                     })}
                 >
-                    {"Use system defaults"}
+                    {t("device.use-system-defaults")}
                 </button>
 
-                <label>{"Output device"}</label>
+                <label>{t("output-device.device")}</label>
                 <Select<String>
                     selected={&self.props.params.device}
                     options={device_names}
@@ -120,7 +121,7 @@ impl Component for OutputDevice {
                     })}
                 />
 
-                <label>{"Left channel"}</label>
+                <label>{t("device.left-channel")}</label>
                 <Select<OutputChannel>
                     selected={OutputChannel(self.props.params.left)}
                     options={channels.clone()}
@@ -133,7 +134,7 @@ impl Component for OutputDevice {
                     })}
                 />
 
-                <label>{"Right channel"}</label>
+                <label>{t("device.right-channel")}</label>
                 <Select<OutputChannel>
                     selected={OutputChannel(self.props.params.right)}
                     options={channels}
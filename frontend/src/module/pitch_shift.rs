@@ -0,0 +1,73 @@
+use yew::{html, ComponentLink, Html};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, PitchShiftParams, MidiTarget, PITCH_SHIFT_MAX_SEMITONES};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::component::pure_module::{Pure, PureModule};
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type PitchShift = Pure<PitchShiftParams>;
+
+impl PureModule for PitchShiftParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
+        let semitones_id = format!("w{}-pitch-shift-semitones", id.0);
+
+        html! {
+            <>
+                <label for={&semitones_id}>{t("pitch-shift.semitones")}</label>
+                <MidiRangeTarget
+                    target={MidiTarget::new(id, "semitones")}
+                    ui_mode={midi_mode}
+                    onchange={module.callback({
+                        let params = self.clone();
+                        move |value: f64| {
+                            // whole semitones across the controller's travel
+                            let semitones = ((value * 2.0 - 1.0) * PITCH_SHIFT_MAX_SEMITONES).round();
+                            WindowMsg::UpdateParams(
+                                ModuleParams::PitchShift(PitchShiftParams { semitones, ..params.clone() }))
+                        }
+                    })}
+                >
+                    <input type="number"
+                        id={&semitones_id}
+                        min={-PITCH_SHIFT_MAX_SEMITONES}
+                        max={PITCH_SHIFT_MAX_SEMITONES}
+                        step={1}
+                        onchange={module.callback({
+                            let params = self.clone();
+                            move |ev| {
+                                if let ChangeData::Value(semitones_str) = ev {
+                                    let semitones = semitones_str.parse().unwrap_or(params.semitones);
+                                    WindowMsg::UpdateParams(
+                                        ModuleParams::PitchShift(PitchShiftParams { semitones, ..params.clone() }))
+                                } else {
+                                    unreachable!()
+                                }
+                            }
+                        })}
+                        value={self.semitones}
+                    />
+                </MidiRangeTarget>
+
+                <label class="form-field">
+                    <input type="checkbox"
+                        checked={self.preserve_formants}
+                        onchange={module.callback({
+                            let params = self.clone();
+                            move |_| {
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::PitchShift(PitchShiftParams {
+                                        preserve_formants: !params.preserve_formants,
+                                        ..params.clone()
+                                    }))
+                            }
+                        })}
+                    />
+                    <span class="form-field-label">{t("pitch-shift.preserve-formants")}</span>
+                </label>
+            </>
+        }
+    }
+}
@@ -4,6 +4,8 @@ use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, NodeRe
 
 use mixlab_protocol::{ModuleId, PlotterIndication};
 
+use crate::i18n::t;
+
 #[derive(Properties, Clone, Debug)]
 pub struct PlotterProps {
     pub id: ModuleId,
@@ -76,7 +78,7 @@ fn render_plot(canvas: HtmlCanvasElement, channel: &[f32]) {
         .x_labels(4)
         .x_label_formatter(&|x| format!("{:.1}ms", *x as f64 / 44.1))
         .y_labels(5)
-        .y_desc("Amplitude")
+        .y_desc(t("plotter.amplitude"))
         .draw()
         .unwrap();
 
@@ -0,0 +1,103 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ProfanityDelayParams, ProfanityDelayIndication};
+
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+// matches the longest delay the server can hold
+const MAX_DELAY: f64 = 40.0;
+
+#[derive(Properties, Clone, Debug)]
+pub struct ProfanityDelayProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: ProfanityDelayParams,
+    pub indication: ProfanityDelayIndication,
+}
+
+pub struct ProfanityDelay {
+    props: ProfanityDelayProps,
+}
+
+impl Component for ProfanityDelay {
+    type Properties = ProfanityDelayProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        ProfanityDelay { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let params = &self.props.params;
+        let held = self.props.indication.delay;
+
+        let progress = if params.delay > 0.0 {
+            (held / params.delay).min(1.0)
+        } else {
+            1.0
+        };
+
+        let delay_id = format!("w{}-profanity-delay-delay", self.props.id.0);
+
+        html! {
+            <>
+                <div class="profanity-delay-status">
+                    <span class="profanity-delay-held">
+                        {format!("{:.1}s / {:.1}s", held, params.delay)}
+                    </span>
+                    <button
+                        class="profanity-delay-dump"
+                        disabled={held == 0.0}
+                        onclick={self.props.module.callback({
+                            let params = params.clone();
+                            move |_| {
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::ProfanityDelay(ProfanityDelayParams {
+                                        dump_seq: params.dump_seq + 1,
+                                        ..params.clone()
+                                    }))
+                            }
+                        })}
+                    >
+                        {t("profanity-delay.dump")}
+                    </button>
+                </div>
+                <div class="profanity-delay-bar">
+                    <div class="profanity-delay-bar-fill" style={format!("width:{}%", progress * 100.0)}></div>
+                </div>
+
+                <label for={&delay_id}>{t("profanity-delay.delay")}</label>
+                <input type="number"
+                    id={&delay_id}
+                    min={0}
+                    max={MAX_DELAY}
+                    step={0.5}
+                    onchange={self.props.module.callback({
+                        let params = params.clone();
+                        move |ev| {
+                            if let ChangeData::Value(delay_str) = ev {
+                                let delay = delay_str.parse().unwrap_or(params.delay);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::ProfanityDelay(ProfanityDelayParams { delay, ..params.clone() }))
+                            } else {
+                                unreachable!()
+                            }
+                        }
+                    })}
+                    value={params.delay}
+                />
+            </>
+        }
+    }
+}
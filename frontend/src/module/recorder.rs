@@ -0,0 +1,197 @@
+use plotters::prelude::*;
+use web_sys::{HtmlCanvasElement, MouseEvent};
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, NodeRef};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, RecorderParams, RecorderMarker, RecorderIndication, WaveformOverview};
+
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+const OVERVIEW_WIDTH: u32 = 400;
+const OVERVIEW_HEIGHT: u32 = 80;
+
+#[derive(Properties, Clone, Debug)]
+pub struct RecorderProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: RecorderParams,
+    pub indication: RecorderIndication,
+}
+
+pub struct Recorder {
+    props: RecorderProps,
+    canvas: NodeRef,
+}
+
+impl Component for Recorder {
+    type Properties = RecorderProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Recorder {
+            props,
+            canvas: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn rendered(&mut self, _first_render: bool) {
+        if let Some(canvas) = self.canvas.cast::<HtmlCanvasElement>() {
+            render_overview(canvas, &self.props.indication.overview, &self.props.params.markers);
+        }
+    }
+
+    fn view(&self) -> Html {
+        let recording = self.props.params.recording;
+
+        let toggle = self.update_params(move |_: MouseEvent, params| {
+            RecorderParams { recording: !recording, ..params }
+        });
+
+        let duration = self.props.indication.overview.duration_secs;
+
+        // markers can be dropped anywhere in the show so far, the overview
+        // spans the full width of the canvas:
+        let add_marker = self.update_params(move |ev: MouseEvent, mut params| {
+            let time = ev.offset_x() as f64 / OVERVIEW_WIDTH as f64 * duration;
+
+            let label = web_sys::window().unwrap()
+                .prompt_with_message_and_default(&t("recorder.marker-prompt"), &format_time(time))
+                .ok()
+                .flatten();
+
+            if let Some(label) = label {
+                params.markers.push(RecorderMarker { time, label });
+                params.markers.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+            }
+
+            params
+        });
+
+        let indication = &self.props.indication;
+
+        let set_filename = self.update_params(|change: ChangeData, params| {
+            match change {
+                ChangeData::Value(filename) => RecorderParams { filename, ..params },
+                _ => unreachable!(),
+            }
+        });
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={recording_class(recording)}>{t("status.rec")}</div>
+                    <div class={warning_class(indication.error)}>{t("status.error")}</div>
+                </div>
+                <label class="form-field">
+                    <span class="form-field-label">{t("recorder.filename")}</span>
+                    <input type="text"
+                        onchange={set_filename}
+                        value={&self.props.params.filename}
+                        disabled={recording}
+                    />
+                </label>
+                <div class="recorder-transport">
+                    <button onclick={toggle}>
+                        { if recording { t("recorder.stop") } else { t("recorder.record") } }
+                    </button>
+                    <span class="recorder-duration">{format_time(duration)}</span>
+                    { if let Some(file) = &indication.file {
+                        html! { <span class="recorder-file">{file}</span> }
+                    } else {
+                        html! {}
+                    } }
+                </div>
+                <canvas
+                    class="recorder-overview"
+                    ref={self.canvas.clone()}
+                    width={OVERVIEW_WIDTH}
+                    height={OVERVIEW_HEIGHT}
+                    onclick={add_marker}
+                />
+                <div class="recorder-markers">
+                    { for self.props.params.markers.iter().enumerate().map(|(index, marker)| {
+                        let remove = self.update_params(move |_: MouseEvent, mut params| {
+                            params.markers.remove(index);
+                            params
+                        });
+
+                        html! {
+                            <div class="recorder-marker">
+                                <span class="recorder-marker-time">{format_time(marker.time)}</span>
+                                <span class="recorder-marker-label">{&marker.label}</span>
+                                <button onclick={remove}>{"×"}</button>
+                            </div>
+                        }
+                    }) }
+                </div>
+            </>
+        }
+    }
+}
+
+impl Recorder {
+    fn update_params<Ev>(&self, f: impl Fn(Ev, RecorderParams) -> RecorderParams + 'static)
+        -> yew::Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::Recorder(f(ev, params.clone())))
+        })
+    }
+}
+
+fn render_overview(canvas: HtmlCanvasElement, overview: &WaveformOverview, markers: &[RecorderMarker]) {
+    let backend = CanvasBackend::with_canvas_object(canvas).unwrap();
+    let root = backend.into_drawing_area();
+    root.fill(&WHITE).unwrap();
+
+    let duration = overview.duration_secs.max(1.0);
+
+    let mut chart = ChartBuilder::on(&root)
+        .build_ranged(0f64..duration, -1f64..1f64).unwrap();
+
+    let bucket_secs = overview.bucket_secs as f64;
+
+    chart.draw_series(overview.peaks.iter().enumerate().map(|(index, peak)| {
+        let peak = (*peak as f64).min(1.0);
+        let start = index as f64 * bucket_secs;
+        Rectangle::new([(start, -peak), (start + bucket_secs, peak)], RED.filled())
+    })).unwrap();
+
+    for marker in markers {
+        chart.draw_series(LineSeries::new(vec![(marker.time, -1.0), (marker.time, 1.0)], &BLUE)).unwrap();
+    }
+
+    root.present().unwrap();
+}
+
+fn format_time(secs: f64) -> String {
+    let secs = secs as u64;
+    format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+fn recording_class(recording: bool) -> &'static str {
+    match recording {
+        false => "status-light",
+        true => "status-light status-light-red-active",
+    }
+}
+
+fn warning_class(is_warning: bool) -> &'static str {
+    match is_warning {
+        false => "status-light",
+        true => "status-light status-light-red-active",
+    }
+}
@@ -0,0 +1,128 @@
+use std::rc::Rc;
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, ReverbParams, MediaLibrary, MidiTarget};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::control::rotary::Rotary;
+use crate::i18n::t;
+use crate::module::media_source::MediaSourceItem;
+use crate::util::notify;
+use crate::session::SessionRef;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone)]
+pub struct ReverbProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: ReverbParams,
+    pub session: SessionRef,
+    pub midi_mode: MidiUiMode,
+}
+
+pub struct Reverb {
+    props: ReverbProps,
+    link: ComponentLink<Self>,
+    library: Option<Rc<MediaLibrary>>,
+    _notify: notify::Handle,
+}
+
+pub enum ReverbMsg {
+    MediaLibrary(Rc<MediaLibrary>),
+    ChangeImpulse(MediaSourceItem),
+    ChangeMix(f64),
+}
+
+impl Component for Reverb {
+    type Properties = ReverbProps;
+    type Message = ReverbMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_media(link.callback(ReverbMsg::MediaLibrary));
+
+        Self {
+            props,
+            link,
+            library: None,
+            _notify: notify,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            ReverbMsg::MediaLibrary(library) => {
+                self.library = Some(library);
+                true
+            }
+            ReverbMsg::ChangeImpulse(item) => {
+                self.update_params(ReverbParams { impulse: Some(item.id), ..self.props.params.clone() });
+                false
+            }
+            ReverbMsg::ChangeMix(mix) => {
+                self.update_params(ReverbParams { mix, ..self.props.params.clone() });
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let options = self.library.iter()
+            .flat_map(|library| library.items.iter().cloned())
+            .map(|item| {
+                MediaSourceItem {
+                    id: item.id,
+                    name: item.name.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let selected = self.props.params.impulse.map(|id| {
+            MediaSourceItem {
+                id,
+                // name can be empty, we never display this item
+                name: String::new(),
+            }
+        });
+
+        html! {
+            <>
+                <label>
+                    <div>{t("reverb.impulse-response")}</div>
+                    <Select<MediaSourceItem>
+                        options={options}
+                        selected={selected}
+                        on_change={self.link.callback(ReverbMsg::ChangeImpulse)}
+                    />
+                </label>
+
+                <div>{t("common.mix")}</div>
+                <MidiRangeTarget
+                    target={MidiTarget::new(self.props.id, "mix")}
+                    ui_mode={self.props.midi_mode}
+                    onchange={self.link.callback(ReverbMsg::ChangeMix)}
+                >
+                    <Rotary<f64>
+                        value={self.props.params.mix}
+                        min={0.0}
+                        max={1.0}
+                        default={0.3}
+                        onchange={self.link.callback(ReverbMsg::ChangeMix)}
+                    />
+                </MidiRangeTarget>
+            </>
+        }
+    }
+}
+
+impl Reverb {
+    fn update_params(&self, params: ReverbParams) {
+        self.props.module.send_message(WindowMsg::UpdateParams(ModuleParams::Reverb(params)));
+    }
+}
@@ -0,0 +1,145 @@
+use std::rc::Rc;
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, SamplerParams, MediaLibrary, MidiTarget};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::control::rotary::Rotary;
+use crate::i18n::{t, tf};
+use crate::module::media_source::MediaSourceItem;
+use crate::util::notify;
+use crate::session::SessionRef;
+use crate::workspace::{Window, WindowMsg};
+
+// pitch range either side of the clip's own pitch, in semitones
+const PITCH_RANGE: f64 = 12.0;
+
+#[derive(Properties, Clone)]
+pub struct SamplerProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: SamplerParams,
+    pub session: SessionRef,
+    pub midi_mode: MidiUiMode,
+}
+
+pub struct Sampler {
+    props: SamplerProps,
+    link: ComponentLink<Self>,
+    library: Option<Rc<MediaLibrary>>,
+    _notify: notify::Handle,
+}
+
+pub enum SamplerMsg {
+    MediaLibrary(Rc<MediaLibrary>),
+    ChangeClip(MediaSourceItem),
+    ChangePitch(f64),
+    ChangeGain(f64),
+}
+
+impl Component for Sampler {
+    type Properties = SamplerProps;
+    type Message = SamplerMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_media(link.callback(SamplerMsg::MediaLibrary));
+
+        Self {
+            props,
+            link,
+            library: None,
+            _notify: notify,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            SamplerMsg::MediaLibrary(library) => {
+                self.library = Some(library);
+                true
+            }
+            SamplerMsg::ChangeClip(item) => {
+                self.update_params(SamplerParams { clip: Some(item.id), ..self.props.params.clone() });
+                false
+            }
+            SamplerMsg::ChangePitch(pitch) => {
+                self.update_params(SamplerParams { pitch, ..self.props.params.clone() });
+                false
+            }
+            SamplerMsg::ChangeGain(gain) => {
+                self.update_params(SamplerParams { gain, ..self.props.params.clone() });
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let options = self.library.iter()
+            .flat_map(|library| library.items.iter().cloned())
+            .map(|item| {
+                MediaSourceItem {
+                    id: item.id,
+                    name: item.name.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let selected = self.props.params.clip.map(|id| {
+            MediaSourceItem {
+                id,
+                // name can be empty, we never display this item
+                name: String::new(),
+            }
+        });
+
+        html! {
+            <>
+                <label>
+                    <div>{t("sampler.clip")}</div>
+                    <Select<MediaSourceItem>
+                        options={options}
+                        selected={selected}
+                        on_change={self.link.callback(SamplerMsg::ChangeClip)}
+                    />
+                </label>
+
+                <div>{tf("sampler.pitch", &[("pitch", &format!("{:+.1}", self.props.params.pitch))])}</div>
+                <Rotary<f64>
+                    value={self.props.params.pitch}
+                    min={-PITCH_RANGE}
+                    max={PITCH_RANGE}
+                    default={0.0}
+                    onchange={self.link.callback(SamplerMsg::ChangePitch)}
+                />
+
+                <div>{t("sampler.gain")}</div>
+                <MidiRangeTarget
+                    target={MidiTarget::new(self.props.id, "gain")}
+                    ui_mode={self.props.midi_mode}
+                    onchange={self.link.callback(SamplerMsg::ChangeGain)}
+                >
+                    <Rotary<f64>
+                        value={self.props.params.gain}
+                        min={0.0}
+                        max={1.0}
+                        default={1.0}
+                        onchange={self.link.callback(SamplerMsg::ChangeGain)}
+                    />
+                </MidiRangeTarget>
+            </>
+        }
+    }
+}
+
+impl Sampler {
+    fn update_params(&self, params: SamplerParams) {
+        self.props.module.send_message(WindowMsg::UpdateParams(ModuleParams::Sampler(params)));
+    }
+}
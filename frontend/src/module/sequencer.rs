@@ -0,0 +1,141 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, SequencerParams, SequencerIndication, SequencerStep};
+
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct SequencerProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: SequencerParams,
+    pub indication: SequencerIndication,
+}
+
+pub struct Sequencer {
+    props: SequencerProps,
+}
+
+impl Component for Sequencer {
+    type Properties = SequencerProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <>
+                <label class="form-field">
+                    <span class="form-field-label">{t("common.bpm")}</span>
+                    <input type="number"
+                        min={1}
+                        step={1}
+                        onchange={self.callback(move |ev, params: SequencerParams| {
+                            if let ChangeData::Value(bpm_str) = ev {
+                                let bpm = bpm_str.parse().unwrap_or(params.bpm);
+                                SequencerParams { bpm, ..params }
+                            } else {
+                                unreachable!()
+                            }
+                        })}
+                        value={self.props.params.bpm}
+                    />
+                </label>
+
+                <div class="sequencer-steps">
+                    { for self.props.params.steps.iter().enumerate().map(|(index, step)| self.view_step(index, step)) }
+                </div>
+            </>
+        }
+    }
+}
+
+impl Sequencer {
+    fn view_step(&self, index: usize, step: &SequencerStep) -> Html {
+        let class = if index == self.props.indication.step {
+            "sequencer-step sequencer-step-current"
+        } else {
+            "sequencer-step"
+        };
+
+        html! {
+            <div class={class}>
+                <input type="checkbox"
+                    title={t("sequencer.gate")}
+                    checked={step.gate}
+                    onchange={self.step_callback(index, |_, step| {
+                        SequencerStep { gate: !step.gate, ..step }
+                    })}
+                />
+                <input type="range"
+                    class="sequencer-slider"
+                    title={t("sequencer.value")}
+                    min={0}
+                    max={1}
+                    step={0.01}
+                    onchange={self.step_callback(index, range(|value, step| {
+                        SequencerStep { value, ..step }
+                    }))}
+                    value={step.value}
+                />
+                <input type="range"
+                    class="sequencer-slider"
+                    title={t("sequencer.length")}
+                    min={0}
+                    max={1}
+                    step={0.01}
+                    onchange={self.step_callback(index, range(|length, step| {
+                        SequencerStep { length, ..step }
+                    }))}
+                    value={step.length}
+                />
+            </div>
+        }
+    }
+
+    fn step_callback<Ev>(&self, index: usize, f: impl Fn(Ev, SequencerStep) -> SequencerStep + 'static)
+        -> Callback<Ev>
+    {
+        self.callback(move |ev, mut params| {
+            params.steps[index] = f(ev, params.steps[index].clone());
+            params
+        })
+    }
+
+    fn callback<Ev>(&self, f: impl Fn(Ev, SequencerParams) -> SequencerParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev|
+            WindowMsg::UpdateParams(
+                ModuleParams::Sequencer(
+                    f(ev, params.clone()))))
+    }
+}
+
+fn range(f: impl Fn(f64, SequencerStep) -> SequencerStep) -> impl Fn(ChangeData, SequencerStep) -> SequencerStep {
+    move |ev, step| {
+        if let ChangeData::Value(value_str) = ev {
+            match value_str.parse() {
+                Ok(value) => f(value, step),
+                Err(_) => step,
+            }
+        } else {
+            unreachable!()
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, SilenceDetectorParams, SilenceDetectorIndication, Decibel};
+
+use crate::control::Rotary;
+use crate::i18n::{t, tf};
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct SilenceDetectorProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: SilenceDetectorParams,
+    pub indication: SilenceDetectorIndication,
+}
+
+pub struct SilenceDetector {
+    props: SilenceDetectorProps,
+}
+
+impl Component for SilenceDetector {
+    type Properties = SilenceDetectorProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let alarm_class = if self.props.indication.alarm {
+            "status-light status-light-red-active"
+        } else {
+            "status-light"
+        };
+
+        let hold_id = format!("w{}-hold", self.props.id.0);
+
+        let threshold_params = self.props.params.clone();
+        let hold_params = self.props.params.clone();
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={alarm_class}>{t("silence-detector.silence")}</div>
+                </div>
+
+                <div>{t("silence-detector.threshold")}</div>
+                <Rotary<Decibel>
+                    value={self.props.params.threshold}
+                    min={Decibel(-80.0)}
+                    max={Decibel(-20.0)}
+                    default={Decibel(-50.0)}
+                    onchange={self.props.module.callback(move |threshold| {
+                        let params = SilenceDetectorParams { threshold, ..threshold_params.clone() };
+                        WindowMsg::UpdateParams(ModuleParams::SilenceDetector(params))
+                    })}
+                />
+
+                <label for={&hold_id}>{tf("silence-detector.hold", &[("seconds", &self.props.params.hold_secs)])}</label>
+                <input type="range"
+                    id={&hold_id}
+                    min={1}
+                    max={60}
+                    step={1}
+                    onchange={self.props.module.callback(move |ev| {
+                        let hold_secs = extract_float_value(ev).unwrap_or(10.0);
+                        let params = SilenceDetectorParams { hold_secs, ..hold_params.clone() };
+                        WindowMsg::UpdateParams(ModuleParams::SilenceDetector(params))
+                    })}
+                    value={self.props.params.hold_secs}
+                />
+            </>
+        }
+    }
+}
+
+fn extract_float_value(event: ChangeData) -> Option<f64> {
+    match event {
+        ChangeData::Value(float_str) => float_str.parse().ok(),
+        _ => None
+    }
+}
@@ -0,0 +1,77 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+
+use mixlab_protocol::{ModuleId, ModuleParams, SlateParams, SlateIndication};
+
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct SlateProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: SlateParams,
+    pub indication: SlateIndication,
+}
+
+pub struct Slate {
+    props: SlateProps,
+}
+
+impl Component for Slate {
+    type Properties = SlateProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let engaged = self.props.params.engaged;
+
+        let on_air_class = if engaged {
+            "status-light status-light-red-active"
+        } else {
+            "status-light"
+        };
+
+        let no_picture_class = if self.props.indication.picture {
+            "status-light"
+        } else {
+            "status-light status-light-red-active"
+        };
+
+        let button_class = if engaged {
+            "slate-button slate-button-engaged"
+        } else {
+            "slate-button"
+        };
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={on_air_class}>{t("slate.on-air")}</div>
+                    <div class={no_picture_class}>{t("slate.no-picture")}</div>
+                </div>
+
+                <button
+                    class={button_class}
+                    onclick={self.props.module.callback(move |_| {
+                        WindowMsg::UpdateParams(
+                            ModuleParams::Slate(SlateParams { engaged: !engaged }))
+                    })}
+                >
+                    {if engaged { t("slate.back-to-program") } else { t("slate.engage") }}
+                </button>
+            </>
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+
+use plotters::prelude::*;
+use web_sys::HtmlCanvasElement;
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, NodeRef};
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, SpectrumAnalyzerParams, SpectrumAnalyzerIndication, SpectrumDisplay};
+use mixlab_protocol::{SPECTRUM_BANDS, SPECTRUM_MIN_HZ, SPECTRUM_MAX_HZ, SPECTRUM_FLOOR_DB};
+
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+// analyses shown across the spectrogram, 10 seconds at the rate they come in
+const SPECTROGRAM_COLUMNS: usize = 100;
+
+#[derive(Properties, Clone, Debug)]
+pub struct SpectrumAnalyzerProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: SpectrumAnalyzerParams,
+    pub indication: SpectrumAnalyzerIndication,
+}
+
+pub struct SpectrumAnalyzer {
+    props: SpectrumAnalyzerProps,
+    canvas: NodeRef,
+    // recent analyses for the spectrogram, oldest first
+    history: VecDeque<Vec<f32>>,
+    seq: u64,
+}
+
+#[derive(PartialEq, Clone)]
+struct SelectableDisplay(SpectrumDisplay);
+
+impl Display for SelectableDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableDisplay(display) = self;
+        let name = match display {
+            SpectrumDisplay::Bars => t("spectrum-analyzer.bars"),
+            SpectrumDisplay::Spectrogram => t("spectrum-analyzer.spectrogram"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Component for SpectrumAnalyzer {
+    type Properties = SpectrumAnalyzerProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        SpectrumAnalyzer {
+            props,
+            canvas: NodeRef::default(),
+            history: VecDeque::with_capacity(SPECTROGRAM_COLUMNS),
+            seq: 0,
+        }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+
+        let indication = &self.props.indication;
+
+        // windows render again for all sorts of reasons, only new analyses
+        // move the spectrogram along
+        if indication.seq != self.seq && !indication.levels.is_empty() {
+            self.seq = indication.seq;
+
+            if self.history.len() == SPECTROGRAM_COLUMNS {
+                self.history.pop_front();
+            }
+
+            self.history.push_back(indication.levels.clone());
+        }
+
+        if let Some(canvas) = self.canvas.cast::<HtmlCanvasElement>() {
+            match self.props.params.display {
+                SpectrumDisplay::Bars => render_bars(canvas, &indication.levels),
+                SpectrumDisplay::Spectrogram => render_spectrogram(canvas, &self.history),
+            }
+        }
+
+        true
+    }
+
+    fn view(&self) -> Html {
+        let options = vec![
+            SelectableDisplay(SpectrumDisplay::Bars),
+            SelectableDisplay(SpectrumDisplay::Spectrogram),
+        ];
+
+        let params = self.props.params.clone();
+
+        html! {
+            <>
+                <Select<SelectableDisplay>
+                    selected={SelectableDisplay(self.props.params.display)}
+                    options={options}
+                    on_change={self.props.module.callback(move |SelectableDisplay(display)| {
+                        WindowMsg::UpdateParams(
+                            ModuleParams::SpectrumAnalyzer(SpectrumAnalyzerParams { display, ..params.clone() }))
+                    })}
+                />
+                <canvas ref={self.canvas.clone()} width={300} height={150} />
+            </>
+        }
+    }
+}
+
+fn band_label(band: usize) -> String {
+    let ratio = (SPECTRUM_MAX_HZ / SPECTRUM_MIN_HZ).powf(band as f64 / SPECTRUM_BANDS as f64);
+    let hz = SPECTRUM_MIN_HZ * ratio;
+
+    if hz >= 1000.0 {
+        format!("{:.0}k", hz / 1000.0)
+    } else {
+        format!("{:.0}", hz)
+    }
+}
+
+fn render_bars(canvas: HtmlCanvasElement, levels: &[f32]) {
+    let backend = CanvasBackend::with_canvas_object(canvas).unwrap();
+    let root = backend.into_drawing_area();
+    root.fill(&WHITE).unwrap();
+
+    let mut chart = ChartBuilder::on(&root)
+        .x_label_area_size(20)
+        .y_label_area_size(45)
+        .build_ranged(0f32..SPECTRUM_BANDS as f32, SPECTRUM_FLOOR_DB..0f32).unwrap();
+    chart.configure_mesh()
+        .x_labels(5)
+        .x_label_formatter(&|band| band_label(*band as usize))
+        .y_labels(4)
+        .y_desc(t("spectrum-analyzer.level"))
+        .draw()
+        .unwrap();
+
+    chart.draw_series(levels.iter().enumerate().map(|(band, level)| {
+        let band = band as f32;
+        Rectangle::new([(band + 0.1, SPECTRUM_FLOOR_DB), (band + 0.9, *level)], RED.filled())
+    })).unwrap();
+
+    root.present().unwrap();
+}
+
+fn render_spectrogram(canvas: HtmlCanvasElement, history: &VecDeque<Vec<f32>>) {
+    let backend = CanvasBackend::with_canvas_object(canvas).unwrap();
+    let root = backend.into_drawing_area();
+    root.fill(&BLACK).unwrap();
+
+    let mut chart = ChartBuilder::on(&root)
+        .y_label_area_size(45)
+        .build_ranged(0f32..SPECTROGRAM_COLUMNS as f32, 0f32..SPECTRUM_BANDS as f32).unwrap();
+    chart.configure_mesh()
+        .disable_x_mesh()
+        .disable_y_mesh()
+        .x_labels(0)
+        .y_labels(5)
+        .y_label_formatter(&|band| band_label(*band as usize))
+        .draw()
+        .unwrap();
+
+    // newest on the right, so a short history fills in from that side
+    let offset = SPECTROGRAM_COLUMNS - history.len();
+
+    for (column, levels) in history.iter().enumerate() {
+        let x = (offset + column) as f32;
+
+        chart.draw_series(levels.iter().enumerate().map(|(band, level)| {
+            let band = band as f32;
+            Rectangle::new([(x, band), (x + 1.0, band + 1.0)], heat(*level).filled())
+        })).unwrap();
+    }
+
+    root.present().unwrap();
+}
+
+// black through red to yellow as the level rises from the floor to 0dB
+fn heat(level: f32) -> RGBColor {
+    let heat = ((level - SPECTRUM_FLOOR_DB) / -SPECTRUM_FLOOR_DB).max(0.0).min(1.0);
+
+    let red = (heat * 2.0).min(1.0);
+    let green = (heat * 2.0 - 1.0).max(0.0);
+
+    RGBColor((red * 255.0) as u8, (green * 255.0) as u8, 0)
+}
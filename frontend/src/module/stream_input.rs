@@ -5,8 +5,10 @@ use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callba
 use yew_components::Select;
 use yew::events::ChangeData;
 
-use mixlab_protocol::{ModuleId, ModuleParams, StreamInputParams, StreamProtocol};
+use mixlab_protocol::{ModuleId, ModuleParams, StreamInputParams, StreamInputIndication, StreamLinkStats, StreamProtocol};
 
+use crate::i18n::{t, tf};
+use crate::util;
 use crate::workspace::{Window, WindowMsg};
 
 #[derive(Properties, Clone, Debug)]
@@ -14,6 +16,7 @@ pub struct StreamInputProps {
     pub id: ModuleId,
     pub module: ComponentLink<Window>,
     pub params: StreamInputParams,
+    pub indication: StreamInputIndication,
 }
 
 pub struct StreamInput {
@@ -38,15 +41,26 @@ impl Component for StreamInput {
     }
 
     fn view(&self) -> Html {
+        let mountpoint_label = match self.props.params.protocol {
+            Some(StreamProtocol::File) => t("stream-input.file-path"),
+            Some(StreamProtocol::MpegTsUdp) | Some(StreamProtocol::Srt) => t("stream-input.udp-address"),
+            Some(StreamProtocol::Guest) => t("stream-input.guest-name"),
+            _ => t("stream-input.mountpoint"),
+        };
+
         html! {
             <>
                 <label class="form-field">
-                    <span class="form-field-label">{"Protocol"}</span>
+                    <span class="form-field-label">{t("stream-input.protocol")}</span>
                     <Select<DisplayProtocol>
                         selected={self.props.params.protocol.map(DisplayProtocol)}
                         options={vec![
                             DisplayProtocol(StreamProtocol::Icecast),
                             DisplayProtocol(StreamProtocol::Rtmp),
+                            DisplayProtocol(StreamProtocol::File),
+                            DisplayProtocol(StreamProtocol::MpegTsUdp),
+                            DisplayProtocol(StreamProtocol::Srt),
+                            DisplayProtocol(StreamProtocol::Guest),
                         ]}
                         on_change={self.callback(move |protocol: DisplayProtocol, params| {
                             StreamInputParams { protocol: Some(protocol.0), ..params }
@@ -55,7 +69,7 @@ impl Component for StreamInput {
                 </label>
 
                 <label class="form-field">
-                    <span class="form-field-label">{"Mountpoint"}</span>
+                    <span class="form-field-label">{mountpoint_label}</span>
                     <input type="text"
                         onchange={self.callback(text(move |mountpoint, params| {
                             StreamInputParams {
@@ -66,6 +80,70 @@ impl Component for StreamInput {
                         value={self.props.params.mountpoint.as_ref().map(String::as_str).unwrap_or("")}
                     />
                 </label>
+
+                {if let Some(key_label) = stream_key_label(self.props.params.protocol) {
+                    html! {
+                        <label class="form-field">
+                            <span class="form-field-label">{key_label}</span>
+                            <input type="text"
+                                placeholder={t("common.any")}
+                                onchange={self.callback(text(move |stream_key, params| {
+                                    StreamInputParams {
+                                        stream_key: stream_key.map(str::to_owned),
+                                        ..params
+                                    }
+                                }))}
+                                value={self.props.params.stream_key.as_ref().map(String::as_str).unwrap_or("")}
+                            />
+                        </label>
+                    }
+                } else {
+                    html! {}
+                }}
+
+                {if let Some(link) = guest_link(&self.props.params) {
+                    html! {
+                        <div class="form-field">
+                            <span class="form-field-label">{t("stream-input.guest-link")}</span>
+                            <a class="stream-input-guest-link" href={link.clone()} target="_blank">{link}</a>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }}
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("stream-input.settle")}</span>
+                    <input type="number"
+                        min={0}
+                        step={0.5}
+                        onchange={self.callback(move |ev, params: StreamInputParams| {
+                            if let ChangeData::Value(settle_str) = ev {
+                                let settle_secs = settle_str.parse().unwrap_or(params.settle_secs);
+                                StreamInputParams { settle_secs, ..params }
+                            } else {
+                                unreachable!()
+                            }
+                        })}
+                        value={self.props.params.settle_secs}
+                    />
+                </label>
+
+                <div class="form-field">
+                    <span class="form-field-label">{t("stream-input.codecs")}</span>
+                    <span>{codec_summary(&self.props.indication)}</span>
+                </div>
+
+                {if let Some(link) = &self.props.indication.link {
+                    html! {
+                        <div class="form-field">
+                            <span class="form-field-label">{t("stream-input.link")}</span>
+                            <span>{link_summary(link)}</span>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }}
             </>
         }
     }
@@ -84,6 +162,55 @@ impl StreamInput {
     }
 }
 
+fn codec_summary(indication: &StreamInputIndication) -> String {
+    let audio_codec = indication.audio_codec.as_ref().map(|codec| {
+        match indication.audio_bitrate {
+            Some(bitrate) => format!("{} {} kbps", codec, bitrate / 1000),
+            None => codec.clone(),
+        }
+    });
+
+    let codecs = indication.video_codec.iter()
+        .chain(audio_codec.iter())
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+
+    if codecs.is_empty() {
+        t("stream-input.not-connected")
+    } else {
+        codecs.join(", ")
+    }
+}
+
+fn link_summary(link: &StreamLinkStats) -> String {
+    let recovered = link.recovered_fec + link.recovered_arq;
+
+    tf("stream-input.link-summary", &[
+        ("received", &link.received),
+        ("recovered", &recovered),
+        ("fec", &link.recovered_fec),
+        ("arq", &link.recovered_arq),
+        ("lost", &link.lost),
+    ])
+}
+
+// the page to send a remote guest, which carries the key so that they
+// don't need to be told it separately
+fn guest_link(params: &StreamInputParams) -> Option<String> {
+    if params.protocol != Some(StreamProtocol::Guest) {
+        return None;
+    }
+
+    let mut link = format!("{}/?guest={}", util::origin(),
+        String::from(js_sys::encode_uri_component(params.mountpoint.as_ref()?)));
+
+    if let Some(key) = &params.stream_key {
+        link += &format!("&key={}", String::from(js_sys::encode_uri_component(key)));
+    }
+
+    Some(link)
+}
+
 fn text<T>(f: impl Fn(Option<&str>, StreamInputParams) -> T)
     -> impl Fn(ChangeData, StreamInputParams) -> T
 {
@@ -109,6 +236,21 @@ impl Display for DisplayProtocol {
         match self.0 {
             StreamProtocol::Icecast => write!(f, "Icecast"),
             StreamProtocol::Rtmp => write!(f, "RTMP"),
+            StreamProtocol::File => write!(f, "{}", t("stream-input.file")),
+            StreamProtocol::MpegTsUdp => write!(f, "MPEG-TS (UDP)"),
+            StreamProtocol::Srt => write!(f, "SRT"),
+            StreamProtocol::Guest => write!(f, "{}", t("stream-input.guest")),
         }
     }
 }
+
+// sources on these protocols can be asked for a secret before connecting
+fn stream_key_label(protocol: Option<StreamProtocol>) -> Option<String> {
+    match protocol? {
+        StreamProtocol::Rtmp => Some(t("stream-input.stream-key")),
+        StreamProtocol::Icecast => Some(t("stream-input.source-password")),
+        StreamProtocol::Srt => Some(t("stream-input.stream-id")),
+        StreamProtocol::Guest => Some(t("stream-input.guest-key")),
+        StreamProtocol::File | StreamProtocol::MpegTsUdp => None,
+    }
+}
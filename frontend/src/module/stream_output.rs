@@ -1,8 +1,10 @@
 use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
 use yew::events::ChangeData;
 
-use mixlab_protocol::{ModuleId, ModuleParams, StreamOutputParams, StreamOutputLiveStatus, StreamOutputIndication};
+use mixlab_protocol::{ModuleId, ModuleParams, ModuleCommand, StreamOutputParams, StreamOutputCommand, StreamOutputLiveStatus, StreamOutputIndication, MonitorIndication, StreamDestination, StreamDestinationStatus};
 
+use crate::i18n::{t, tf};
+use crate::module::monitor::{self, Monitor};
 use crate::workspace::{Window, WindowMsg};
 
 #[derive(Properties, Clone, Debug)]
@@ -43,34 +45,27 @@ impl Component for StreamOutput {
         html! {
             <>
                 <div class="status-light-bar">
-                    <div class={live_class(self.props.indication.live)}>{"LIVE"}</div>
-                    <div class={warning_class(self.props.indication.error)}>{"ERROR"}</div>
+                    <div class={live_class(self.props.indication.live)}>{t("status.live")}</div>
+                    <div class={warning_class(self.props.indication.error)}>{t("status.error")}</div>
+                    <div class={backup_class(self.props.indication.backup)}>{t("status.backup")}</div>
                 </div>
 
                 { if is_conn_active {
                     html! {
-                        <button
-                            onclick={self.callback(move |_, params| {
-                                StreamOutputParams { disconnect_seq: params.seq, ..params }
-                            })}
-                        >
-                            {"Disconnect"}
+                        <button onclick={self.command(StreamOutputCommand::Disconnect)}>
+                            {t("action.disconnect")}
                         </button>
                     }
                 } else {
                     html! {
-                        <button
-                            onclick={self.callback(move |_, params| {
-                                StreamOutputParams { connect_seq: params.seq, ..params }
-                            })}
-                        >
-                            {"Connect"}
+                        <button onclick={self.command(StreamOutputCommand::Connect)}>
+                            {t("action.connect")}
                         </button>
                     }
                 } }
 
                 <label class="form-field">
-                    <span class="form-field-label">{"RTMP URL"}</span>
+                    <span class="form-field-label">{t("stream-output.url")}</span>
                     <input type="text"
                         onchange={self.callback(text(move |rtmp_url, params| {
                             StreamOutputParams { rtmp_url, ..params }
@@ -80,7 +75,7 @@ impl Component for StreamOutput {
                 </label>
 
                 <label class="form-field">
-                    <span class="form-field-label">{"Stream Key"}</span>
+                    <span class="form-field-label">{t("stream-output.stream-key")}</span>
                     <input type="text"
                         onchange={self.callback(text(move |rtmp_stream_key, params| {
                             StreamOutputParams { rtmp_stream_key, ..params }
@@ -88,26 +83,196 @@ impl Component for StreamOutput {
                         value={&self.props.params.rtmp_stream_key}
                     />
                 </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("stream-output.backup-url")}</span>
+                    <input type="text"
+                        onchange={self.callback(text(move |backup_rtmp_url, params| {
+                            StreamOutputParams { backup_rtmp_url, ..params }
+                        }))}
+                        value={&self.props.params.backup_rtmp_url}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("stream-output.backup-stream-key")}</span>
+                    <input type="text"
+                        onchange={self.callback(text(move |backup_rtmp_stream_key, params| {
+                            StreamOutputParams { backup_rtmp_stream_key, ..params }
+                        }))}
+                        value={&self.props.params.backup_rtmp_stream_key}
+                    />
+                </label>
+
+                <button
+                    disabled={self.props.indication.live != StreamOutputLiveStatus::Live
+                        || self.props.indication.backup
+                        || self.props.params.backup_rtmp_url.is_empty()}
+                    onclick={self.command(StreamOutputCommand::SwitchToBackup)}
+                >
+                    {t("stream-output.switch-to-backup")}
+                </button>
+
+                <div class="stream-output-destinations">
+                    <div class="stream-output-destinations-heading">{t("stream-output.destinations")}</div>
+                    { for (0..self.props.params.destinations.len()).map(|index| self.view_destination(index, is_conn_active)) }
+                    <button
+                        disabled={is_conn_active}
+                        onclick={self.callback(move |_, mut params| {
+                            params.destinations.push(StreamDestination { enabled: true, ..StreamDestination::default() });
+                            params
+                        })}
+                    >
+                        {t("stream-output.add-destination")}
+                    </button>
+                </div>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("stream-output.keyframe-interval")}</span>
+                    <input type="number"
+                        min={0.1}
+                        step={0.1}
+                        onchange={self.callback(text(move |interval, params| {
+                            let keyframe_interval = interval.parse().unwrap_or(params.keyframe_interval);
+                            StreamOutputParams { keyframe_interval, ..params }
+                        }))}
+                        value={self.props.params.keyframe_interval}
+                        disabled={is_conn_active}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("stream-output.audio-delay")}</span>
+                    <input type="number"
+                        step={1}
+                        onchange={self.callback(text(move |delay, params| {
+                            let audio_delay_ms = delay.parse().unwrap_or(params.audio_delay_ms);
+                            StreamOutputParams { audio_delay_ms, ..params }
+                        }))}
+                        value={self.props.params.audio_delay_ms}
+                        disabled={is_conn_active}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("stream-output.cue-name")}</span>
+                    <input type="text"
+                        placeholder={t("stream-output.cue-placeholder")}
+                        onchange={self.callback(text(move |cue_name, params| {
+                            StreamOutputParams { cue_name, ..params }
+                        }))}
+                        value={&self.props.params.cue_name}
+                    />
+                </label>
+
+                <button
+                    disabled={self.props.indication.live != StreamOutputLiveStatus::Live}
+                    onclick={self.command(StreamOutputCommand::Cue)}
+                >
+                    {t("stream-output.send-cue")}
+                </button>
+
+                <label class="form-field">
+                    <input type="checkbox"
+                        checked={self.props.params.passthrough}
+                        onchange={self.callback(move |_, params| {
+                            StreamOutputParams { passthrough: !params.passthrough, ..params }
+                        })}
+                    />
+                    <span class="form-field-label">{t("stream-output.passthrough")}</span>
+                </label>
+
+                <label class="form-field">
+                    <input type="checkbox"
+                        checked={self.props.params.preview}
+                        onchange={self.callback(move |_, params| {
+                            StreamOutputParams { preview: !params.preview, ..params }
+                        })}
+                    />
+                    <span class="form-field-label">{t("stream-output.preview")}</span>
+                </label>
+
+                { if let Some(socket_id) = self.props.indication.preview {
+                    html! {
+                        <Monitor socket_url={monitor::socket_url(&MonitorIndication { socket_id })} />
+                    }
+                } else {
+                    html! {}
+                } }
             </>
         }
     }
 }
 
 impl StreamOutput {
+    // the list can only change while offline, but destinations can be turned
+    // on and off while live
+    fn view_destination(&self, index: usize, is_conn_active: bool) -> Html {
+        let destination = &self.props.params.destinations[index];
+
+        let status = self.props.indication.destinations.get(index).copied()
+            .unwrap_or(StreamDestinationStatus::Disabled);
+
+        html! {
+            <div class="stream-output-destination">
+                <div class="stream-output-destination-header">
+                    <label>
+                        <input type="checkbox"
+                            checked={destination.enabled}
+                            onchange={self.callback(move |_, mut params| {
+                                params.destinations[index].enabled = !params.destinations[index].enabled;
+                                params
+                            })}
+                        />
+                        {tf("stream-output.destination", &[("number", &(index + 1))])}
+                    </label>
+                    <div class={destination_class(status)}>{t(destination_status_key(status))}</div>
+                </div>
+                <input type="text"
+                    placeholder={t("stream-output.url")}
+                    disabled={is_conn_active}
+                    onchange={self.callback(text(move |url, mut params| {
+                        params.destinations[index].url = url;
+                        params
+                    }))}
+                    value={&destination.url}
+                />
+                <input type="text"
+                    placeholder={t("stream-output.stream-key")}
+                    disabled={is_conn_active}
+                    onchange={self.callback(text(move |stream_key, mut params| {
+                        params.destinations[index].stream_key = stream_key;
+                        params
+                    }))}
+                    value={&destination.stream_key}
+                />
+                <button
+                    disabled={is_conn_active}
+                    onclick={self.callback(move |_, mut params| {
+                        params.destinations.remove(index);
+                        params
+                    })}
+                >
+                    {t("action.remove")}
+                </button>
+            </div>
+        }
+    }
+
     fn callback<Ev>(&self, f: impl Fn(Ev, StreamOutputParams) -> StreamOutputParams + 'static)
         -> Callback<Ev>
     {
         let params = self.props.params.clone();
 
         self.props.module.callback(move |ev| {
-            let updated_params = f(ev, {
-                let mut params = params.clone();
-                params.seq += 1;
-                params
-            });
-
             WindowMsg::UpdateParams(
-                ModuleParams::StreamOutput(updated_params))
+                ModuleParams::StreamOutput(f(ev, params.clone())))
+        })
+    }
+
+    fn command<Ev>(&self, command: StreamOutputCommand) -> Callback<Ev> {
+        self.props.module.callback(move |_| {
+            WindowMsg::Command(ModuleCommand::StreamOutput(command))
         })
     }
 }
@@ -132,6 +297,29 @@ fn live_class(live_status: StreamOutputLiveStatus) -> &'static str {
     }
 }
 
+fn destination_class(status: StreamDestinationStatus) -> &'static str {
+    match status {
+        StreamDestinationStatus::Disabled | StreamDestinationStatus::Offline => "status-light",
+        StreamDestinationStatus::Connecting => "status-light status-light-green",
+        StreamDestinationStatus::Live => "status-light status-light-green-active",
+        StreamDestinationStatus::Failed => "status-light status-light-red-active",
+    }
+}
+
+fn destination_status_key(status: StreamDestinationStatus) -> &'static str {
+    match status {
+        StreamDestinationStatus::Failed => "status.error",
+        _ => "status.live",
+    }
+}
+
+fn backup_class(is_backup: bool) -> &'static str {
+    match is_backup {
+        false => "status-light",
+        true => "status-light status-light-green-active",
+    }
+}
+
 fn warning_class(is_warning: bool) -> &'static str {
     match is_warning {
         false => "status-light",
@@ -0,0 +1,62 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+
+use mixlab_protocol::{ModuleId, SyncCheckIndication};
+
+use crate::i18n::{t, tf};
+
+
+#[derive(Properties, Clone, Debug)]
+pub struct SyncCheckProps {
+    pub id: ModuleId,
+    pub indication: SyncCheckIndication,
+}
+
+pub struct SyncCheck {
+    props: SyncCheckProps,
+}
+
+impl Component for SyncCheck {
+    type Properties = SyncCheckProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        match self.props.indication.offset_ms {
+            None => html! {
+                <div class="sync-check-status">{t("sync-check.waiting")}</div>
+            },
+            Some(offset_ms) => html! {
+                <>
+                    <div class="sync-check-offset">
+                        {describe_offset(offset_ms)}
+                    </div>
+                    <div class="sync-check-status">
+                        {tf("sync-check.correction", &[("delay", &-offset_ms)])}
+                    </div>
+                </>
+            },
+        }
+    }
+}
+
+fn describe_offset(offset_ms: f64) -> String {
+    if offset_ms > 0.0 {
+        tf("sync-check.late", &[("offset", &offset_ms)])
+    } else if offset_ms < 0.0 {
+        tf("sync-check.early", &[("offset", &-offset_ms)])
+    } else {
+        t("sync-check.in-sync")
+    }
+}
@@ -0,0 +1,58 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+
+use mixlab_protocol::{ModuleId, ModuleParams, GateState};
+
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct TalkbackProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: GateState,
+}
+
+pub struct Talkback {
+    props: TalkbackProps,
+}
+
+impl Component for Talkback {
+    type Properties = TalkbackProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let talk_class = match self.props.params {
+            GateState::Open => "status-light status-light-red-active",
+            GateState::Closed => "status-light",
+        };
+
+        html! {
+            <>
+                <div class="status-light-bar">
+                    <div class={talk_class}>{t("talkback.talk")}</div>
+                </div>
+                <button
+                    onmousedown={self.props.module.callback(move |_| {
+                        WindowMsg::UpdateParams(ModuleParams::Talkback(GateState::Open))
+                    })}
+                    onmouseup={self.props.module.callback(move |_| {
+                        WindowMsg::UpdateParams(ModuleParams::Talkback(GateState::Closed))
+                    })}
+                >{t("talkback.push-to-talk")}</button>
+            </>
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::fmt::{self, Display};
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties, Callback};
+use yew::events::ChangeData;
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, TextOverlayParams, TextPosition, Rgb};
+
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+#[derive(Properties, Clone, Debug)]
+pub struct TextOverlayProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: TextOverlayParams,
+}
+
+pub struct TextOverlay {
+    props: TextOverlayProps,
+}
+
+#[derive(PartialEq, Clone)]
+pub struct SelectablePosition(pub TextPosition);
+
+impl SelectablePosition {
+    pub fn all() -> Vec<SelectablePosition> {
+        vec![
+            SelectablePosition(TextPosition::TopLeft),
+            SelectablePosition(TextPosition::Top),
+            SelectablePosition(TextPosition::TopRight),
+            SelectablePosition(TextPosition::Center),
+            SelectablePosition(TextPosition::BottomLeft),
+            SelectablePosition(TextPosition::Bottom),
+            SelectablePosition(TextPosition::BottomRight),
+        ]
+    }
+}
+
+impl Display for SelectablePosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectablePosition(position) = self;
+        let name = match position {
+            TextPosition::TopLeft => t("text-overlay.top-left"),
+            TextPosition::Top => t("text-overlay.top"),
+            TextPosition::TopRight => t("text-overlay.top-right"),
+            TextPosition::Center => t("text-overlay.center"),
+            TextPosition::BottomLeft => t("text-overlay.bottom-left"),
+            TextPosition::Bottom => t("text-overlay.bottom"),
+            TextPosition::BottomRight => t("text-overlay.bottom-right"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Component for TextOverlay {
+    type Properties = TextOverlayProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <>
+                <label class="form-field">
+                    <span class="form-field-label">{t("text-overlay.text")}</span>
+                    <input type="text"
+                        onchange={self.callback(text(move |text, params| {
+                            TextOverlayParams { text, ..params }
+                        }))}
+                        value={&self.props.params.text}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("text-overlay.position")}</span>
+                    <Select<SelectablePosition>
+                        selected={SelectablePosition(self.props.params.position)}
+                        options={SelectablePosition::all()}
+                        on_change={self.callback(move |SelectablePosition(position), params| {
+                            TextOverlayParams { position, ..params }
+                        })}
+                    />
+                </label>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("text-overlay.font-size")}</span>
+                    <input type="number"
+                        min={7}
+                        max={200}
+                        step={7}
+                        onchange={self.callback(text(move |size, params| {
+                            let font_size = size.parse().unwrap_or(params.font_size);
+                            TextOverlayParams { font_size, ..params }
+                        }))}
+                        value={self.props.params.font_size}
+                    />
+                </label>
+
+                <div class="text-overlay-colors">
+                    <label class="form-field">
+                        <span class="form-field-label">{t("text-overlay.color")}</span>
+                        <input type="color"
+                            onchange={self.callback(text(move |hex, params| {
+                                let color = Rgb::from_hex(&hex).unwrap_or(params.color);
+                                TextOverlayParams { color, ..params }
+                            }))}
+                            value={self.props.params.color.to_hex()}
+                        />
+                    </label>
+
+                    <label class="form-field">
+                        <span class="form-field-label">{t("text-overlay.box-color")}</span>
+                        <input type="color"
+                            onchange={self.callback(text(move |hex, params| {
+                                let box_color = Rgb::from_hex(&hex).unwrap_or(params.box_color);
+                                TextOverlayParams { box_color, ..params }
+                            }))}
+                            value={self.props.params.box_color.to_hex()}
+                        />
+                    </label>
+                </div>
+
+                <label class="form-field">
+                    <span class="form-field-label">{t("text-overlay.box-opacity")}</span>
+                    <input type="range"
+                        min={0}
+                        max={1}
+                        step={0.01}
+                        onchange={self.callback(text(move |opacity, params| {
+                            let box_opacity = opacity.parse().unwrap_or(params.box_opacity);
+                            TextOverlayParams { box_opacity, ..params }
+                        }))}
+                        value={self.props.params.box_opacity}
+                    />
+                </label>
+            </>
+        }
+    }
+}
+
+impl TextOverlay {
+    fn callback<Ev>(&self, f: impl Fn(Ev, TextOverlayParams) -> TextOverlayParams + 'static)
+        -> Callback<Ev>
+    {
+        let params = self.props.params.clone();
+
+        self.props.module.callback(move |ev| {
+            WindowMsg::UpdateParams(
+                ModuleParams::TextOverlay(f(ev, params.clone())))
+        })
+    }
+}
+
+fn text<T>(f: impl Fn(String, TextOverlayParams) -> T)
+    -> impl Fn(ChangeData, TextOverlayParams) -> T
+{
+    move |change, params| {
+        if let ChangeData::Value(value) = change {
+            f(value, params)
+        } else {
+            unreachable!()
+        }
+    }
+}
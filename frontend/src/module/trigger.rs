@@ -2,6 +2,7 @@ use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
 
 use mixlab_protocol::{ModuleId, ModuleParams, GateState};
 
+use crate::i18n::t;
 use crate::workspace::{Window, WindowMsg};
 
 #[derive(Properties, Clone, Debug)]
@@ -42,7 +43,7 @@ impl Component for Trigger {
                     onmouseup={self.props.module.callback(move |ev| {
                         WindowMsg::UpdateParams(ModuleParams::Trigger(GateState::Closed))
                     })}
-                >{"Trigger"}</button>
+                >{t("trigger.trigger")}</button>
             </>
         }
     }
@@ -0,0 +1,115 @@
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, TunerParams, TunerIndication, TunerPitch};
+
+use crate::i18n::{t, tf};
+use crate::workspace::{Window, WindowMsg};
+
+const NOTE_NAMES: [&str; 12] = ["C", "C♯", "D", "D♯", "E", "F", "F♯", "G", "G♯", "A", "A♯", "B"];
+
+// close enough to the note to call in tune
+const IN_TUNE_CENTS: f32 = 5.0;
+
+#[derive(Properties, Clone, Debug)]
+pub struct TunerProps {
+    pub id: ModuleId,
+    pub module: ComponentLink<Window>,
+    pub params: TunerParams,
+    pub indication: TunerIndication,
+}
+
+pub struct Tuner {
+    props: TunerProps,
+}
+
+impl Component for Tuner {
+    type Properties = TunerProps;
+    type Message = ();
+
+    fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
+        Tuner { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let reference_id = format!("w{}-tuner-reference", self.props.id.0);
+        let params = self.props.params.clone();
+
+        html! {
+            <>
+                {match self.props.indication.pitch {
+                    Some(pitch) => view_pitch(pitch),
+                    None => view_no_pitch(),
+                }}
+
+                <div class="tuner-reference">
+                    <label for={&reference_id}>{t("tuner.reference")}</label>
+                    <input type="number"
+                        id={&reference_id}
+                        min={400}
+                        max={480}
+                        step={0.1}
+                        onchange={self.props.module.callback(move |ev| {
+                            if let ChangeData::Value(reference_str) = ev {
+                                let reference_hz = reference_str.parse().unwrap_or(params.reference_hz);
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Tuner(TunerParams { reference_hz }))
+                            } else {
+                                unreachable!()
+                            }
+                        })}
+                        value={self.props.params.reference_hz}
+                    />
+                </div>
+            </>
+        }
+    }
+}
+
+fn view_pitch(pitch: TunerPitch) -> Html {
+    let class = if pitch.cents.abs() <= IN_TUNE_CENTS {
+        "tuner-display tuner-in-tune"
+    } else {
+        "tuner-display"
+    };
+
+    // fades out as the input becomes less clearly pitched
+    let needle_style = format!("left:{}%;opacity:{}", pitch.cents + 50.0, pitch.confidence);
+
+    html! {
+        <div class={class}>
+            <div class="tuner-note">{note_name(pitch.note)}</div>
+            <div class="tuner-scale">
+                <div class="tuner-needle" style={needle_style}></div>
+            </div>
+            <div class="tuner-readout">
+                {tf("tuner.readout", &[("freq", &format!("{:.1}", pitch.freq)), ("cents", &format!("{:+.0}", pitch.cents))])}
+            </div>
+        </div>
+    }
+}
+
+fn view_no_pitch() -> Html {
+    html! {
+        <div class="tuner-display">
+            <div class="tuner-note">{"–"}</div>
+            <div class="tuner-scale"></div>
+            <div class="tuner-readout">{t("tuner.no-pitch")}</div>
+        </div>
+    }
+}
+
+// scientific pitch notation, where MIDI note 60 is C4
+fn note_name(note: u8) -> String {
+    let octave = note as i32 / 12 - 1;
+    format!("{}{}", NOTE_NAMES[note as usize % 12], octave)
+}
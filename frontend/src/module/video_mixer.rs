@@ -1,35 +1,37 @@
 use yew::{html, ComponentLink, Html, Callback, MouseEvent};
 
-use mixlab_protocol::{ModuleId, ModuleParams, VideoMixerParams, VIDEO_MIXER_CHANNELS};
+use mixlab_protocol::{ModuleId, ModuleParams, VideoMixerParams, MidiTarget, VIDEO_MIXER_MAX_CHANNELS};
 
 use crate::component::pure_module::{Pure, PureModule};
 use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
 use crate::control::Fader;
+use crate::i18n::t;
 use crate::workspace::{Window, WindowMsg};
 use crate::util::prevent_default;
 
 pub type VideoMixer = Pure<VideoMixerParams>;
 
 impl PureModule for VideoMixerParams {
-    fn view(&self, _: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
         html! {
             <>
                 <div class="video-mixer">
                     <div class="video-mixer-channels">
                         <div class="video-mixer-channel-row">
-                            {view_channel_row(Selector::A, self.a, module.callback(
+                            {view_channel_row(Selector::A, self.channels, self.a, module.callback(
                                 update_params(self, move |params, selection|
                                     VideoMixerParams { a: selection, ..params })))}
                         </div>
 
                         <div class="video-mixer-channel-row">
-                            {view_channel_row(Selector::B, self.b, module.callback(
+                            {view_channel_row(Selector::B, self.channels, self.b, module.callback(
                                 update_params(self, move |params, selection|
                                     VideoMixerParams { b: selection, ..params })))}
                         </div>
                     </div>
                     <div class="video-mixer-fader">
                         <MidiRangeTarget
+                            target={MidiTarget::new(id, "fader")}
                             ui_mode={midi_mode}
                             onchange={module.callback(
                                 update_params(self, move |params, fader|
@@ -44,6 +46,37 @@ impl PureModule for VideoMixerParams {
                         </MidiRangeTarget>
                     </div>
                 </div>
+                <label class="video-mixer-key-frame">
+                    <input type="checkbox"
+                        checked={self.cut_on_key_frame}
+                        onchange={module.callback(
+                            update_params(self, move |params, _|
+                                VideoMixerParams { cut_on_key_frame: !params.cut_on_key_frame, ..params }))}
+                    />
+                    {t("video-mixer.cut-on-key-frame")}
+                </label>
+                <div class="channel-count">
+                    <button
+                        class="channel-count-btn"
+                        title={t("video-mixer.remove-channel")}
+                        disabled={self.channels <= 1}
+                        onclick={module.callback(
+                            update_params(self, move |params, _|
+                                VideoMixerParams { channels: params.channels - 1, ..params }))}
+                    >
+                        {"−"}
+                    </button>
+                    <button
+                        class="channel-count-btn"
+                        title={t("video-mixer.add-channel")}
+                        disabled={self.channels >= VIDEO_MIXER_MAX_CHANNELS}
+                        onclick={module.callback(
+                            update_params(self, move |params, _|
+                                VideoMixerParams { channels: params.channels + 1, ..params }))}
+                    >
+                        {"+"}
+                    </button>
+                </div>
             </>
         }
     }
@@ -54,9 +87,9 @@ enum Selector {
     B,
 }
 
-fn view_channel_row(sel: Selector, current: Option<usize>, onchange: Callback<Option<usize>>) -> Html {
+fn view_channel_row(sel: Selector, channels: usize, current: Option<usize>, onchange: Callback<Option<usize>>) -> Html {
     html! {
-        {for (0..VIDEO_MIXER_CHANNELS).map(|i| {
+        {for (0..channels).map(|i| {
             let class = if Some(i) == current {
                 match sel {
                     Selector::A => "video-mixer-channel-select-btn video-mixer-channel-selected-a",
@@ -0,0 +1,85 @@
+use std::fmt::{self, Display};
+
+use yew::{html, ComponentLink, Html};
+use yew_components::Select;
+
+use mixlab_protocol::{ModuleId, ModuleParams, WaveshaperParams, WaveshaperMode, MidiTarget};
+
+use crate::component::midi_target::{MidiRangeTarget, MidiUiMode};
+use crate::component::pure_module::{Pure, PureModule};
+use crate::control::rotary::Rotary;
+use crate::i18n::t;
+use crate::workspace::{Window, WindowMsg};
+
+pub type Waveshaper = Pure<WaveshaperParams>;
+
+const MAX_DRIVE: f64 = 20.0;
+
+#[derive(PartialEq, Clone)]
+struct SelectableMode(WaveshaperMode);
+
+impl Display for SelectableMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableMode(mode) = self;
+        let name = match mode {
+            WaveshaperMode::RingMod => t("waveshaper.ring-mod"),
+            WaveshaperMode::Fold => t("waveshaper.fold"),
+            WaveshaperMode::SoftClip => t("waveshaper.soft-clip"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl PureModule for WaveshaperParams {
+    fn view(&self, id: ModuleId, module: ComponentLink<Window>, midi_mode: MidiUiMode) -> Html {
+        let modes = vec![
+            SelectableMode(WaveshaperMode::RingMod),
+            SelectableMode(WaveshaperMode::Fold),
+            SelectableMode(WaveshaperMode::SoftClip),
+        ];
+
+        html! {
+            <>
+                <label>
+                    <div>{t("waveshaper.mode")}</div>
+                    <Select<SelectableMode>
+                        selected={SelectableMode(self.mode)}
+                        options={modes}
+                        on_change={module.callback({
+                            let params = self.clone();
+                            move |SelectableMode(mode)| {
+                                WindowMsg::UpdateParams(
+                                    ModuleParams::Waveshaper(WaveshaperParams { mode, ..params.clone() }))
+                            }
+                        })}
+                    />
+                </label>
+
+                <div>{t("waveshaper.drive")}</div>
+                <MidiRangeTarget
+                    target={MidiTarget::new(id, "drive")}
+                    ui_mode={midi_mode}
+                    onchange={module.callback({
+                        let update = update_drive(self);
+                        move |value: f64| update(value * MAX_DRIVE)
+                    })}
+                >
+                    <Rotary<f64>
+                        value={self.drive}
+                        min={0.0}
+                        max={MAX_DRIVE}
+                        default={WaveshaperParams::default().drive}
+                        onchange={module.callback(update_drive(self))}
+                    />
+                </MidiRangeTarget>
+            </>
+        }
+    }
+}
+
+fn update_drive(params: &WaveshaperParams) -> impl Fn(f64) -> WindowMsg {
+    let params = params.clone();
+    move |drive| {
+        WindowMsg::UpdateParams(ModuleParams::Waveshaper(WaveshaperParams { drive, ..params.clone() }))
+    }
+}
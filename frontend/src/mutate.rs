@@ -0,0 +1,460 @@
+//! Randomises chosen parameters of chosen modules, for exploring synth and
+//! effect settings quickly. Each mutation can be undone, back through the
+//! last few, by sending the parameters each module had before it.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+
+use mixlab_protocol::{ModuleId, ModuleParams, WorkspaceOp, Decibel};
+
+use crate::i18n::{self, t};
+use crate::session::{SessionRef, WorkspaceStateRef};
+
+// mutations kept for undo
+const MAX_UNDO: usize = 20;
+
+/// A numeric parameter of a module, by the path naming it as in MIDI
+/// mappings, with the range it's randomised over unless changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumericParam {
+    pub path: &'static str,
+    pub min: f64,
+    pub max: f64,
+    // randomised evenly in octaves rather than value, for frequencies and
+    // times. min must be above zero
+    pub log: bool,
+}
+
+impl NumericParam {
+    const fn linear(path: &'static str, min: f64, max: f64) -> Self {
+        NumericParam { path, min, max, log: false }
+    }
+
+    const fn log(path: &'static str, min: f64, max: f64) -> Self {
+        NumericParam { path, min, max, log: true }
+    }
+}
+
+/// Parameters of a module which can be mutated. Ranges follow the module's
+/// own controls, a little inside them where the extremes are rarely wanted.
+pub fn numeric_params(params: &ModuleParams) -> Vec<NumericParam> {
+    match params {
+        ModuleParams::AmbisonicEncoder(_) => vec![
+            NumericParam::linear("azimuth", -180.0, 180.0),
+            NumericParam::linear("elevation", -90.0, 90.0),
+        ],
+        ModuleParams::Amplifier(_) => vec![
+            NumericParam::linear("amplitude", 0.0, 1.0),
+            NumericParam::linear("mod-depth", 0.0, 1.0),
+        ],
+        ModuleParams::Delay(_) => vec![
+            NumericParam::log("time", 0.01, 4.0),
+            // full feedback never dies away
+            NumericParam::linear("feedback", 0.0, 0.9),
+            NumericParam::linear("mix", 0.0, 1.0),
+        ],
+        ModuleParams::Envelope(_) => vec![
+            NumericParam::log("attack", 5.0, 500.0),
+            NumericParam::log("decay", 5.0, 1000.0),
+            NumericParam::linear("sustain", 0.0, 1.0),
+            NumericParam::log("release", 5.0, 5000.0),
+        ],
+        ModuleParams::EqThree(_) => vec![
+            NumericParam::linear("gain-lo", -24.0, 6.0),
+            NumericParam::linear("gain-mid", -24.0, 6.0),
+            NumericParam::linear("gain-hi", -24.0, 6.0),
+        ],
+        ModuleParams::Filter(_) => vec![
+            NumericParam::log("cutoff", 20.0, 20000.0),
+            // self-oscillation is loud
+            NumericParam::linear("resonance", 0.0, 0.9),
+            NumericParam::linear("cutoff-mod", 0.0, 4.0),
+        ],
+        ModuleParams::FmSine(_) => vec![
+            NumericParam::log("freq-lo", 20.0, 2000.0),
+            NumericParam::log("freq-hi", 20.0, 2000.0),
+        ],
+        ModuleParams::Lfo(_) => vec![
+            NumericParam::log("freq", 0.05, 20.0),
+            NumericParam::linear("depth", 0.0, 1.0),
+        ],
+        ModuleParams::Looper(_) => vec![
+            NumericParam::linear("level", 0.0, 1.0),
+        ],
+        ModuleParams::Oscillator(_) => vec![
+            NumericParam::log("freq", 20.0, 2000.0),
+        ],
+        ModuleParams::Reverb(_) => vec![
+            NumericParam::linear("mix", 0.0, 1.0),
+        ],
+        ModuleParams::Sampler(_) => vec![
+            NumericParam::linear("pitch", -12.0, 12.0),
+            NumericParam::linear("gain", 0.0, 1.0),
+        ],
+        ModuleParams::Waveshaper(_) => vec![
+            NumericParam::linear("drive", 0.0, 20.0),
+        ],
+        _ => vec![],
+    }
+}
+
+/// Current value of a numeric parameter.
+pub fn value(params: &ModuleParams, path: &str) -> Option<f64> {
+    match (params, path) {
+        (ModuleParams::AmbisonicEncoder(params), "azimuth") => Some(params.azimuth),
+        (ModuleParams::AmbisonicEncoder(params), "elevation") => Some(params.elevation),
+        (ModuleParams::Amplifier(params), "amplitude") => Some(params.amplitude),
+        (ModuleParams::Amplifier(params), "mod-depth") => Some(params.mod_depth),
+        (ModuleParams::Delay(params), "time") => Some(params.time),
+        (ModuleParams::Delay(params), "feedback") => Some(params.feedback),
+        (ModuleParams::Delay(params), "mix") => Some(params.mix),
+        (ModuleParams::Envelope(params), "attack") => Some(params.attack_ms),
+        (ModuleParams::Envelope(params), "decay") => Some(params.decay_ms),
+        (ModuleParams::Envelope(params), "sustain") => Some(params.sustain_amplitude),
+        (ModuleParams::Envelope(params), "release") => Some(params.release_ms),
+        (ModuleParams::EqThree(params), "gain-lo") => Some(params.gain_lo.0),
+        (ModuleParams::EqThree(params), "gain-mid") => Some(params.gain_mid.0),
+        (ModuleParams::EqThree(params), "gain-hi") => Some(params.gain_hi.0),
+        (ModuleParams::Filter(params), "cutoff") => Some(params.cutoff),
+        (ModuleParams::Filter(params), "resonance") => Some(params.resonance),
+        (ModuleParams::Filter(params), "cutoff-mod") => Some(params.cutoff_mod),
+        (ModuleParams::FmSine(params), "freq-lo") => Some(params.freq_lo),
+        (ModuleParams::FmSine(params), "freq-hi") => Some(params.freq_hi),
+        (ModuleParams::Lfo(params), "freq") => Some(params.freq),
+        (ModuleParams::Lfo(params), "depth") => Some(params.depth),
+        (ModuleParams::Looper(params), "level") => Some(params.level),
+        (ModuleParams::Oscillator(params), "freq") => Some(params.freq),
+        (ModuleParams::Reverb(params), "mix") => Some(params.mix),
+        (ModuleParams::Sampler(params), "pitch") => Some(params.pitch),
+        (ModuleParams::Sampler(params), "gain") => Some(params.gain),
+        (ModuleParams::Waveshaper(params), "drive") => Some(params.drive),
+        _ => None,
+    }
+}
+
+/// Params with a numeric parameter changed, or none if the module has no
+/// such parameter.
+pub fn with_value(params: &ModuleParams, path: &str, value: f64) -> Option<ModuleParams> {
+    let mut params = params.clone();
+
+    match (&mut params, path) {
+        (ModuleParams::AmbisonicEncoder(params), "azimuth") => { params.azimuth = value; }
+        (ModuleParams::AmbisonicEncoder(params), "elevation") => { params.elevation = value; }
+        (ModuleParams::Amplifier(params), "amplitude") => { params.amplitude = value; }
+        (ModuleParams::Amplifier(params), "mod-depth") => { params.mod_depth = value; }
+        (ModuleParams::Delay(params), "time") => { params.time = value; }
+        (ModuleParams::Delay(params), "feedback") => { params.feedback = value; }
+        (ModuleParams::Delay(params), "mix") => { params.mix = value; }
+        (ModuleParams::Envelope(params), "attack") => { params.attack_ms = value; }
+        (ModuleParams::Envelope(params), "decay") => { params.decay_ms = value; }
+        (ModuleParams::Envelope(params), "sustain") => { params.sustain_amplitude = value; }
+        (ModuleParams::Envelope(params), "release") => { params.release_ms = value; }
+        (ModuleParams::EqThree(params), "gain-lo") => { params.gain_lo = Decibel(value); }
+        (ModuleParams::EqThree(params), "gain-mid") => { params.gain_mid = Decibel(value); }
+        (ModuleParams::EqThree(params), "gain-hi") => { params.gain_hi = Decibel(value); }
+        (ModuleParams::Filter(params), "cutoff") => { params.cutoff = value; }
+        (ModuleParams::Filter(params), "resonance") => { params.resonance = value; }
+        (ModuleParams::Filter(params), "cutoff-mod") => { params.cutoff_mod = value; }
+        (ModuleParams::FmSine(params), "freq-lo") => { params.freq_lo = value; }
+        (ModuleParams::FmSine(params), "freq-hi") => { params.freq_hi = value; }
+        (ModuleParams::Lfo(params), "freq") => { params.freq = value; }
+        (ModuleParams::Lfo(params), "depth") => { params.depth = value; }
+        (ModuleParams::Looper(params), "level") => { params.level = value; }
+        (ModuleParams::Oscillator(params), "freq") => { params.freq = value; }
+        (ModuleParams::Reverb(params), "mix") => { params.mix = value; }
+        (ModuleParams::Sampler(params), "pitch") => { params.pitch = value; }
+        (ModuleParams::Sampler(params), "gain") => { params.gain = value; }
+        (ModuleParams::Waveshaper(params), "drive") => { params.drive = value; }
+        _ => return None,
+    }
+
+    Some(params)
+}
+
+// a random value in the param's range, moved towards from `current` by
+// `amount`, 0 leaving it alone and 1 replacing it
+fn mutate_value(param: &NumericParam, range: (f64, f64), current: f64, amount: f64) -> f64 {
+    let (min, max) = range;
+    let random = js_sys::Math::random();
+
+    if param.log && min > 0.0 && current > 0.0 {
+        let (min, max, current) = (min.ln(), max.ln(), current.ln());
+        let target = min + (max - min) * random;
+        (current + (target - current) * amount).exp()
+    } else {
+        let target = min + (max - min) * random;
+        current + (target - current) * amount
+    }
+}
+
+pub struct Mutate {
+    link: ComponentLink<Self>,
+    props: MutateProps,
+    // params chosen for mutation, by module
+    selected: BTreeMap<ModuleId, BTreeSet<&'static str>>,
+    // ranges changed from the param's own
+    ranges: BTreeMap<(ModuleId, &'static str), (f64, f64)>,
+    amount: f64,
+    // params of each module mutated, as they were before, latest last
+    undo: Vec<Vec<(ModuleId, ModuleParams)>>,
+}
+
+#[derive(Properties, Clone, Debug)]
+pub struct MutateProps {
+    pub session: SessionRef,
+    pub workspace: WorkspaceStateRef,
+}
+
+pub enum MutateMsg {
+    ToggleModule(ModuleId),
+    ToggleParam(ModuleId, &'static str),
+    SetMin(ModuleId, NumericParam, f64),
+    SetMax(ModuleId, NumericParam, f64),
+    SetAmount(f64),
+    Mutate,
+    Undo,
+}
+
+impl Component for Mutate {
+    type Properties = MutateProps;
+    type Message = MutateMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Mutate {
+            link,
+            props,
+            selected: BTreeMap::new(),
+            ranges: BTreeMap::new(),
+            amount: 1.0,
+            undo: Vec::new(),
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            MutateMsg::ToggleModule(module) => {
+                if self.selected.remove(&module).is_none() {
+                    self.selected.insert(module, BTreeSet::new());
+                }
+                true
+            }
+            MutateMsg::ToggleParam(module, path) => {
+                let params = self.selected.entry(module).or_default();
+
+                if !params.remove(path) {
+                    params.insert(path);
+                }
+                true
+            }
+            MutateMsg::SetMin(module, param, min) => {
+                let range = self.range(module, &param);
+                self.ranges.insert((module, param.path), (min, range.1));
+                true
+            }
+            MutateMsg::SetMax(module, param, max) => {
+                let range = self.range(module, &param);
+                self.ranges.insert((module, param.path), (range.0, max));
+                true
+            }
+            MutateMsg::SetAmount(amount) => {
+                self.amount = amount;
+                false
+            }
+            MutateMsg::Mutate => {
+                self.mutate();
+                true
+            }
+            MutateMsg::Undo => {
+                self.undo();
+                true
+            }
+        }
+    }
+
+    fn view(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        let modules = workspace.modules.iter()
+            .filter(|(id, _)| workspace.may_edit(**id))
+            .map(|(id, params)| (*id, params, numeric_params(params)))
+            .filter(|(_, _, numeric)| !numeric.is_empty())
+            .collect::<Vec<_>>();
+
+        if modules.is_empty() {
+            return html! {};
+        }
+
+        let anything_selected = self.selected.values().any(|params| !params.is_empty());
+
+        html! {
+            <div class="mutate">
+                <div class="mutate-heading">{t("mutate.title")}</div>
+                { for modules.into_iter().map(|(id, params, numeric)| {
+                    let selected = self.selected.get(&id);
+
+                    html! {
+                        <div class="mutate-module">
+                            <label class="mutate-module-name">
+                                <input type="checkbox"
+                                    checked={selected.is_some()}
+                                    onclick={self.link.callback(move |_| MutateMsg::ToggleModule(id))}
+                                />
+                                {format!("{} #{}", i18n::module_name(params), id.0)}
+                            </label>
+                            { match selected {
+                                Some(selected) => html! {
+                                    <>{ for numeric.into_iter().map(|param| self.view_param(id, param, selected.contains(param.path))) }</>
+                                },
+                                None => html! {},
+                            } }
+                        </div>
+                    }
+                }) }
+                <label class="mutate-amount">
+                    <span>{t("mutate.amount")}</span>
+                    <input type="range"
+                        min={0}
+                        max={1}
+                        step={0.05}
+                        value={self.amount}
+                        onchange={self.link.callback(|change| match change {
+                            ChangeData::Value(value) => MutateMsg::SetAmount(value.parse().unwrap_or(1.0)),
+                            _ => unreachable!(),
+                        })}
+                    />
+                </label>
+                <div class="mutate-actions">
+                    <button
+                        disabled={!anything_selected}
+                        onclick={self.link.callback(|_| MutateMsg::Mutate)}
+                    >
+                        {t("mutate.mutate")}
+                    </button>
+                    <button
+                        disabled={self.undo.is_empty()}
+                        onclick={self.link.callback(|_| MutateMsg::Undo)}
+                    >
+                        {t("mutate.undo")}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+}
+
+impl Mutate {
+    fn range(&self, module: ModuleId, param: &NumericParam) -> (f64, f64) {
+        self.ranges.get(&(module, param.path))
+            .copied()
+            .unwrap_or((param.min, param.max))
+    }
+
+    fn view_param(&self, module: ModuleId, param: NumericParam, selected: bool) -> Html {
+        let (min, max) = self.range(module, &param);
+        let path = param.path;
+
+        let parse = |value: ChangeData, fallback: f64| match value {
+            ChangeData::Value(value) => value.parse().unwrap_or(fallback),
+            _ => unreachable!(),
+        };
+
+        html! {
+            <div class="mutate-param">
+                <label class="mutate-param-name">
+                    <input type="checkbox"
+                        checked={selected}
+                        onclick={self.link.callback(move |_| MutateMsg::ToggleParam(module, path))}
+                    />
+                    {path}
+                </label>
+                <input type="number"
+                    class="mutate-param-range"
+                    step="any"
+                    value={min}
+                    onchange={self.link.callback(move |change| MutateMsg::SetMin(module, param, parse(change, min)))}
+                />
+                <input type="number"
+                    class="mutate-param-range"
+                    step="any"
+                    value={max}
+                    onchange={self.link.callback(move |change| MutateMsg::SetMax(module, param, parse(change, max)))}
+                />
+            </div>
+        }
+    }
+
+    fn mutate(&mut self) {
+        let mut previous = Vec::new();
+
+        {
+            let workspace = self.props.workspace.borrow();
+
+            for (module, paths) in &self.selected {
+                let params = match workspace.modules.get(module) {
+                    Some(params) if workspace.may_edit(*module) => params,
+                    _ => continue,
+                };
+
+                let mut mutated = params.clone();
+
+                for param in numeric_params(params) {
+                    if !paths.contains(param.path) {
+                        continue;
+                    }
+
+                    let current = match value(&mutated, param.path) {
+                        Some(current) => current,
+                        None => continue,
+                    };
+
+                    let (min, max) = self.range(*module, &param);
+                    let (min, max) = (min.min(max), min.max(max));
+                    let new = mutate_value(&param, (min, max), current, self.amount).max(min).min(max);
+
+                    if let Some(params) = with_value(&mutated, param.path, new) {
+                        mutated = params;
+                    }
+                }
+
+                previous.push((*module, params.clone()));
+
+                self.props.session.update_workspace(
+                    WorkspaceOp::UpdateModuleParams(*module, mutated));
+            }
+        }
+
+        if previous.is_empty() {
+            return;
+        }
+
+        self.undo.push(previous);
+
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+    }
+
+    // modules deleted since are skipped
+    fn undo(&mut self) {
+        let previous = match self.undo.pop() {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let workspace = self.props.workspace.borrow();
+
+        for (module, params) in previous {
+            if workspace.modules.contains_key(&module) && workspace.may_edit(module) {
+                self.props.session.update_workspace(
+                    WorkspaceOp::UpdateModuleParams(module, params));
+            }
+        }
+    }
+}
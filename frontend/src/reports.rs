@@ -0,0 +1,111 @@
+use std::rc::Rc;
+
+use wasm_bindgen::JsValue;
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+
+use mixlab_protocol as protocol;
+
+use crate::i18n::t;
+use crate::session::SessionRef;
+use crate::util::notify;
+
+pub struct StreamReports {
+    reports: Option<Rc<protocol::StreamReports>>,
+    _notify: notify::Handle,
+}
+
+#[derive(Properties, Clone)]
+pub struct StreamReportsProps {
+    pub session: SessionRef,
+}
+
+pub enum ReportsMsg {
+    Update(Rc<protocol::StreamReports>),
+}
+
+impl Component for StreamReports {
+    type Message = ReportsMsg;
+    type Properties = StreamReportsProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_reports(link.callback(ReportsMsg::Update));
+
+        StreamReports {
+            reports: None,
+            _notify: notify,
+        }
+    }
+
+    fn change(&mut self, _: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            ReportsMsg::Update(reports) => {
+                self.reports = Some(reports);
+                true
+            }
+        }
+    }
+
+    fn view(&self) -> Html {
+        let reports = match &self.reports {
+            Some(reports) => reports,
+            None => return html! {},
+        };
+
+        html! {
+            <div class="media-library">
+                <table class="media-library-table">
+                    <tr class="table-heading">
+                        <th>{t("reports.started")}</th>
+                        <th>{t("reports.destination")}</th>
+                        <th>{t("reports.duration")}</th>
+                        <th>{t("reports.average-bitrate")}</th>
+                        <th>{t("reports.peak-bitrate")}</th>
+                        <th>{t("reports.dropped-frames")}</th>
+                        <th>{t("reports.reconnects")}</th>
+                        <th>{t("reports.average-level")}</th>
+                        <th>{t("reports.peak-level")}</th>
+                        <th>{t("reports.export")}</th>
+                    </tr>
+                    { for reports.reports.iter().map(|report| {
+                        let summary = &report.summary;
+
+                        html! {
+                            <tr>
+                                <td>{format_timestamp(summary.started_at)}</td>
+                                <td>{&summary.destination}</td>
+                                <td>{format_duration(summary.duration_secs)}</td>
+                                <td>{format!("{:.0} kbps", summary.average_bitrate_kbps)}</td>
+                                <td>{format!("{:.0} kbps", summary.peak_bitrate_kbps)}</td>
+                                <td>{summary.dropped_frames}</td>
+                                <td>{summary.reconnects}</td>
+                                <td>{summary.loudness_average}</td>
+                                <td>{summary.loudness_peak}</td>
+                                <td>
+                                    <a href={format!("/_reports/{}/json", report.id.0)}>{"JSON"}</a>
+                                    {" "}
+                                    <a href={format!("/_reports/{}/csv", report.id.0)}>{"CSV"}</a>
+                                    {" "}
+                                    <a href={format!("/_reports/{}/loudness", report.id.0)}>{t("reports.loudness-log")}</a>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </table>
+            </div>
+        }
+    }
+}
+
+fn format_timestamp(unix_secs: i64) -> String {
+    let date = js_sys::Date::new(&JsValue::from(unix_secs as f64 * 1000.0));
+    String::from(date.to_locale_string("default", &JsValue::UNDEFINED))
+}
+
+fn format_duration(secs: f64) -> String {
+    let secs = secs as u64;
+    format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
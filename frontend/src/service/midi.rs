@@ -13,6 +13,8 @@ use wasm_bindgen_futures::JsFuture;
 use web_sys::{MidiInput, MidiMessageEvent, MidiConnectionEvent};
 use yew::Callback;
 
+use mixlab_protocol::{MidiTarget, MidiControl, MidiMapping};
+
 use crate::util::Sequence;
 
 struct MidiBroker {
@@ -22,16 +24,32 @@ struct MidiBroker {
     configuring: Option<ConfigureKind>,
     id_seq: Sequence,
     range_subscribers: BTreeMap<(MidiRangeId, SubscriptionId), Callback<u8>>,
+    // mirrors the project's persisted mapping table
+    mappings: HashMap<MidiTarget, MidiControl>,
+    persist: Option<Callback<(MidiTarget, Option<MidiControl>)>>,
 }
 
 #[derive(Clone)]
 pub struct MidiBrokerRef(Rc<RefCell<MidiBroker>>);
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub struct MidiRangeId(MidiInputId, u8);
+pub struct MidiRangeId(MidiDeviceName, u8);
+
+impl MidiRangeId {
+    pub fn from_control(control: &MidiControl) -> MidiRangeId {
+        MidiRangeId(Rc::new(control.device.clone()), control.controller)
+    }
+
+    pub fn to_control(&self) -> MidiControl {
+        MidiControl {
+            device: self.0.to_string(),
+            controller: self.1,
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub struct MidiNoteId(MidiInputId, u8);
+pub struct MidiNoteId(MidiDeviceName, u8);
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 struct SubscriptionId(NonZeroUsize);
@@ -48,6 +66,10 @@ impl SubscriptionId {
 
 type MidiInputId = Rc<String>;
 
+// ranges and notes are keyed by device name rather than input id so that
+// they can be persisted and matched again after the device reconnects
+type MidiDeviceName = Rc<String>;
+
 thread_local! {
     static BROKER: MidiBrokerRef = MidiBroker::new();
 }
@@ -83,12 +105,51 @@ impl MidiBrokerRef {
         }
     }
 
-    fn on_message(&self, input_id: MidiInputId, event: &MidiMessageEvent) {
+    pub fn mapping(&self, target: &MidiTarget) -> Option<MidiControl> {
+        self.0.borrow().mappings.get(target).cloned()
+    }
+
+    /// Replaces the mapping table with the one loaded from the server.
+    pub fn load_mappings(&self, mappings: Vec<MidiMapping>) {
+        self.0.borrow_mut().mappings = mappings.into_iter()
+            .map(|mapping| (mapping.target, mapping.control))
+            .collect();
+    }
+
+    /// Applies a mapping change received from the server.
+    pub fn set_mapping(&self, target: MidiTarget, control: Option<MidiControl>) {
+        let mut broker = self.0.borrow_mut();
+
+        match control {
+            Some(control) => { broker.mappings.insert(target, control); }
+            None => { broker.mappings.remove(&target); }
+        }
+    }
+
+    /// Called with every mapping change made locally so that it can be saved
+    /// to the project.
+    pub fn on_persist(&self, callback: Callback<(MidiTarget, Option<MidiControl>)>) {
+        self.0.borrow_mut().persist = Some(callback);
+    }
+
+    /// Binds or unbinds a target, updating the local table immediately rather
+    /// than waiting for the change to round trip through the server.
+    pub fn persist_mapping(&self, target: MidiTarget, control: Option<MidiControl>) {
+        self.set_mapping(target.clone(), control.clone());
+
+        let persist = self.0.borrow().persist.clone();
+
+        if let Some(persist) = persist {
+            persist.emit((target, control));
+        }
+    }
+
+    fn on_message(&self, device: MidiDeviceName, event: &MidiMessageEvent) {
         let data = event.data().expect("MidiMessageEvent::data");
 
         // MIDI controller (range) change message
         if data.len() == 3 && (data[0] & 0xf0) == 0xb0 {
-            let range_id = MidiRangeId(input_id, data[1] & 0x7f);
+            let range_id = MidiRangeId(device, data[1] & 0x7f);
             let value = data[2] & 0x7f;
 
             let min_key = (range_id.clone(), SubscriptionId::min());
@@ -130,6 +191,8 @@ impl MidiBroker {
             configuring: None,
             id_seq: Sequence::new(),
             range_subscribers: BTreeMap::new(),
+            mappings: HashMap::new(),
+            persist: None,
         })));
 
         wasm_bindgen_futures::spawn_local({
@@ -231,15 +294,15 @@ async fn request_midi_access() -> Result<web_sys::MidiAccess, MidiError> {
 
 fn setup_input(broker: MidiBrokerRef, input: MidiInput) {
     let input_id = Rc::new(input.id());
+    let device = Rc::new(input.name().unwrap_or_else(|| input.id()));
 
     let event_listener = EventListener::new(&input, "midimessage", {
-        let input_id = input_id.clone();
         let broker = broker.clone();
         move |ev| {
             let message = ev.dyn_ref::<MidiMessageEvent>()
                 .expect("dyn_into MidiMessageEvent");
 
-            broker.on_message(input_id.clone(), message);
+            broker.on_message(device.clone(), message);
         }
     });
 
@@ -1,13 +1,14 @@
 use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::rc::Rc;
 
 use yew::services::websocket::{WebSocketService, WebSocketStatus, WebSocketTask};
 use yew::format::Binary;
 use yew::Callback;
 
-use mixlab_protocol::{ServerMessage, ServerUpdate, ClientMessage, ClientSequence, ModuleId, ModuleParams, WindowGeometry, InputId, OutputId, Indication, Terminal, WorkspaceOp, WorkspaceMessage};
+use mixlab_protocol::{ServerMessage, ServerUpdate, ClientMessage, ClientSequence, ModuleId, ModuleParams, WindowGeometry, InputId, OutputId, ChannelAdapter, Indication, Terminal, WorkspaceOp, WorkspaceMessage, ModuleSafety, OverrunPolicy, SurfaceControl, ViewerLink, Freeze, MacroParams};
 
+use crate::service::midi;
 use crate::util;
 use crate::util::notify::{self, Notify};
 use crate::Sequence;
@@ -31,6 +32,10 @@ struct Notifiers {
     workspace: Notify<()>,
     performance: Notify<Rc<mixlab_protocol::PerformanceInfo>>,
     media: Notify<Rc<mixlab_protocol::MediaLibrary>>,
+    reports: Notify<Rc<mixlab_protocol::StreamReports>>,
+    midi_devices: Notify<Rc<mixlab_protocol::MidiDevices>>,
+    snapshots: Notify<Rc<mixlab_protocol::Snapshots>>,
+    macros: Notify<Rc<mixlab_protocol::Macros>>,
 }
 
 pub type SessionRef = Rc<Session>;
@@ -48,10 +53,17 @@ impl Session {
                 workspace: Notify::new(),
                 performance: Notify::new(),
                 media: Notify::new(),
+                reports: Notify::new(),
+                midi_devices: Notify::new(),
+                snapshots: Notify::new(),
+                macros: Notify::new(),
             },
         });
 
-        let websocket_url = util::websocket_origin() + "/session";
+        // pass the page's query string through to the session endpoint so
+        // that eg. `?group=audio` selects this session's permission group
+        let search = web_sys::window().unwrap().location().search().unwrap_or_default();
+        let websocket_url = util::websocket_origin() + "/session" + &search;
 
         let websocket = WebSocketService::connect_binary(&websocket_url,
             Callback::from({
@@ -77,6 +89,16 @@ impl Session {
 
         *session.websocket.borrow_mut() = Some(websocket);
 
+        // save MIDI mappings made in the browser to the project
+        midi::broker().on_persist(Callback::from({
+            let session = Rc::downgrade(&session);
+            move |(target, control)| {
+                if let Some(session) = session.upgrade() {
+                    session.update_workspace(WorkspaceOp::UpdateMidiMapping(target, control));
+                }
+            }
+        }));
+
         session
     }
 
@@ -110,6 +132,7 @@ impl Session {
     fn on_server_message(&self, msg: ServerMessage) {
         match msg {
             ServerMessage::WorkspaceState(state) => {
+                midi::broker().load_mappings(state.midi_mappings.clone());
                 *self.state.borrow_mut() = Some(Rc::new(RefCell::new(state.into())));
                 self.notify.workspace.broadcast(());
             }
@@ -138,9 +161,17 @@ impl Session {
                             state.inputs.insert(id, inputs);
                             state.outputs.insert(id, outputs);
                         }
-                        ServerUpdate::UpdateModuleParams(id, new_params) => {
+                        ServerUpdate::UpdateModuleParams { id, params: new_params, inputs, outputs } => {
                             if let Some(params) = state.modules.get_mut(&id) {
                                 *params = new_params;
+
+                                if let Some(inputs) = inputs {
+                                    state.inputs.insert(id, inputs);
+                                }
+
+                                if let Some(outputs) = outputs {
+                                    state.outputs.insert(id, outputs);
+                                }
                             }
                         }
                         ServerUpdate::UpdateWindowGeometry(id, new_geometry) => {
@@ -159,12 +190,63 @@ impl Session {
                             state.indications.remove(&id);
                             state.inputs.remove(&id);
                             state.outputs.remove(&id);
+                            state.safety.remove(&id);
+                            state.groups.remove(&id);
+                            state.bypassed.remove(&id);
                         }
                         ServerUpdate::CreateConnection(input, output) => {
                             state.connections.insert(input, output);
+                            state.adapters.remove(&input);
                         }
                         ServerUpdate::DeleteConnection(input) => {
                             state.connections.remove(&input);
+                            state.adapters.remove(&input);
+                        }
+                        ServerUpdate::UpdateModuleSafety(id, safety) => {
+                            state.safety.insert(id, safety);
+                        }
+                        ServerUpdate::UpdateModuleGroup(id, group) => {
+                            match group {
+                                Some(group) => { state.groups.insert(id, group); }
+                                None => { state.groups.remove(&id); }
+                            }
+                        }
+                        ServerUpdate::UpdateModuleBypass(id, bypassed) => {
+                            if bypassed {
+                                state.bypassed.insert(id);
+                            } else {
+                                state.bypassed.remove(&id);
+                            }
+                        }
+                        ServerUpdate::UpdateConnectionAdapter(input, adapter) => {
+                            if adapter == ChannelAdapter::default() {
+                                state.adapters.remove(&input);
+                            } else {
+                                state.adapters.insert(input, adapter);
+                            }
+                        }
+                        ServerUpdate::UpdateMidiMapping(target, control) => {
+                            midi::broker().set_mapping(target, control);
+                        }
+                        ServerUpdate::UpdateOverrunPolicy(policy) => {
+                            state.overrun_policy = policy;
+                        }
+                        ServerUpdate::UpdateSurface(surface) => {
+                            state.surface = surface;
+                        }
+                        ServerUpdate::UpdateViewerLinks(links) => {
+                            state.viewer_links = links;
+                        }
+                        ServerUpdate::UpdateFreezes(freezes) => {
+                            state.freezes = freezes;
+                        }
+                        ServerUpdate::RejectConnection { input, output, current, reason } => {
+                            crate::log!("connection from {:?} to {:?} rejected: {:?}", output, input, reason);
+
+                            match current {
+                                Some(current) => { state.connections.insert(input, current); }
+                                None => { state.connections.remove(&input); }
+                            }
                         }
                     }
                 }
@@ -183,6 +265,18 @@ impl Session {
                 crate::log!("Receiving media library!");
                 self.notify.media.broadcast(Rc::new(library));
             }
+            ServerMessage::StreamReports(reports) => {
+                self.notify.reports.broadcast(Rc::new(reports));
+            }
+            ServerMessage::MidiDevices(devices) => {
+                self.notify.midi_devices.broadcast(Rc::new(devices));
+            }
+            ServerMessage::Snapshots(snapshots) => {
+                self.notify.snapshots.broadcast(Rc::new(snapshots));
+            }
+            ServerMessage::Macros(macros) => {
+                self.notify.macros.broadcast(Rc::new(macros));
+            }
         }
     }
 
@@ -211,6 +305,44 @@ impl Session {
         self.notify.media.subscribe(callback)
     }
 
+    pub fn listen_reports(&self, callback: Callback<Rc<mixlab_protocol::StreamReports>>) -> notify::Handle {
+        self.notify.reports.subscribe(callback)
+    }
+
+    pub fn listen_midi_devices(&self, callback: Callback<Rc<mixlab_protocol::MidiDevices>>) -> notify::Handle {
+        self.notify.midi_devices.subscribe(callback)
+    }
+
+    pub fn listen_snapshots(&self, callback: Callback<Rc<mixlab_protocol::Snapshots>>) -> notify::Handle {
+        self.notify.snapshots.subscribe(callback)
+    }
+
+    pub fn save_snapshot(&self, name: String) {
+        self.send_message(ClientMessage::SaveSnapshot(name));
+    }
+
+    // the workspace changes arrive as ordinary server updates, as if from
+    // another session
+    pub fn apply_snapshot(&self, name: String) {
+        self.send_message(ClientMessage::ApplySnapshot(name));
+    }
+
+    pub fn delete_snapshot(&self, name: String) {
+        self.send_message(ClientMessage::DeleteSnapshot(name));
+    }
+
+    pub fn listen_macros(&self, callback: Callback<Rc<mixlab_protocol::Macros>>) -> notify::Handle {
+        self.notify.macros.subscribe(callback)
+    }
+
+    pub fn save_macro(&self, params: MacroParams) {
+        self.send_message(ClientMessage::SaveMacro(params));
+    }
+
+    pub fn delete_macro(&self, name: String) {
+        self.send_message(ClientMessage::DeleteMacro(name));
+    }
+
     fn send_message(&self, msg: ClientMessage) {
         let packet = bincode::serialize(&msg)
             .expect("bincode::serialize");
@@ -233,6 +365,67 @@ pub struct WorkspaceState {
     pub indications: HashMap<ModuleId, Indication>,
     pub inputs: HashMap<ModuleId, Vec<Terminal>>,
     pub outputs: HashMap<ModuleId, Vec<Terminal>>,
+    pub safety: HashMap<ModuleId, ModuleSafety>,
+    pub groups: HashMap<ModuleId, String>,
+    pub bypassed: HashSet<ModuleId>,
+    // only connections not using the default adapter
+    pub adapters: HashMap<InputId, ChannelAdapter>,
+    pub surface: Vec<SurfaceControl>,
+    pub viewer_links: Vec<ViewerLink>,
+    pub freezes: Vec<Freeze>,
+    pub overrun_policy: OverrunPolicy,
+    pub session_group: Option<String>,
+}
+
+impl WorkspaceState {
+    pub fn safety(&self, module: ModuleId) -> ModuleSafety {
+        self.safety.get(&module).copied().unwrap_or_default()
+    }
+
+    /// Mirrors the permission rules enforced by the engine: sessions without
+    /// a group may edit anything, otherwise only modules in the same group
+    pub fn may_edit(&self, module: ModuleId) -> bool {
+        match &self.session_group {
+            None => true,
+            Some(group) => self.groups.get(&module) == Some(group),
+        }
+    }
+
+    /// The adapter an input's connection converts between mono and stereo
+    /// with, or `None` if it isn't connected through one
+    pub fn adapter(&self, input: InputId) -> Option<ChannelAdapter> {
+        let output = self.connections.get(&input)?;
+        let output_type = self.outputs.get(&output.module_id())?.get(output.index())?.line_type();
+        let input_type = self.inputs.get(&input.module_id())?.get(input.index())?.line_type();
+
+        if output_type.adapts_to(input_type) {
+            Some(self.adapters.get(&input).copied().unwrap_or_default())
+        } else {
+            None
+        }
+    }
+
+    /// Mirrors the cycle check enforced by the engine: whether anything
+    /// `upstream` outputs reaches `downstream`, so that connecting an output
+    /// of `downstream` into `upstream` would close a feedback loop
+    pub fn feeds(&self, upstream: ModuleId, downstream: ModuleId) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack = vec![downstream];
+
+        while let Some(module_id) = stack.pop() {
+            if module_id == upstream {
+                return true;
+            }
+
+            if seen.insert(module_id) {
+                stack.extend(self.connections.iter()
+                    .filter(|(input, _)| input.module_id() == module_id)
+                    .map(|(_, output)| output.module_id()));
+            }
+        }
+
+        false
+    }
 }
 
 impl From<mixlab_protocol::WorkspaceState> for WorkspaceState {
@@ -244,6 +437,15 @@ impl From<mixlab_protocol::WorkspaceState> for WorkspaceState {
             connections: wstate.connections.into_iter().collect(),
             inputs: wstate.inputs.into_iter().collect(),
             outputs: wstate.outputs.into_iter().collect(),
+            safety: wstate.safety.into_iter().collect(),
+            groups: wstate.groups.into_iter().collect(),
+            bypassed: wstate.bypassed.into_iter().collect(),
+            adapters: wstate.adapters.into_iter().collect(),
+            surface: wstate.surface,
+            viewer_links: wstate.viewer_links,
+            freezes: wstate.freezes,
+            overrun_policy: wstate.overrun_policy,
+            session_group: wstate.session_group,
         }
     }
 }
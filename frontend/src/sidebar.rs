@@ -1,16 +1,31 @@
+use std::fmt::{self, Display};
 use std::rc::Rc;
 
+use wasm_bindgen::JsValue;
 use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+use yew_components::Select;
 
-use mixlab_protocol::{PerformanceInfo, PerformanceAccount, TemporalWarningStatus, ModuleId};
+use mixlab_protocol::{PerformanceInfo, PerformanceAccount, TemporalWarningStatus, ModuleId, ModuleParams, MixerChannelParams, RecorderParams, MidiDevices, Bytes, OverrunPolicy, WorkspaceOp, Template, ViewerLink};
 
+use crate::control::Fader;
+use crate::i18n::{self, t, tf};
+use crate::mutate::Mutate;
 use crate::session::{SessionRef, WorkspaceStateRef};
-use crate::util::notify;
+use crate::snapshots::Snapshots;
+use crate::util::{self, notify};
+
+// how long a new viewer link lasts, in seconds
+const LINK_DURATIONS: &[u64] = &[60 * 60, 24 * 60 * 60, 7 * 24 * 60 * 60];
 
 pub struct Sidebar {
+    link: ComponentLink<Self>,
     props: SidebarProps,
     perf_info: Option<Rc<PerformanceInfo>>,
+    midi_devices: Option<Rc<MidiDevices>>,
+    new_link: NewViewerLink,
     _perf_notify: notify::Handle,
+    _midi_notify: notify::Handle,
 }
 
 #[derive(Properties, Clone, Debug)]
@@ -21,6 +36,74 @@ pub struct SidebarProps {
 
 pub enum SidebarMsg {
     PerfInfo(Rc<PerformanceInfo>),
+    MidiDevices(Rc<MidiDevices>),
+    ChangeOverrunPolicy(OverrunPolicy),
+    UpdateModuleParams(ModuleId, ModuleParams),
+    ChangeLanguage(&'static str),
+    NewLinkModule(ModuleId),
+    NewLinkLabel(String),
+    NewLinkDuration(u64),
+    CreateViewerLink,
+    RevokeViewerLink(String),
+}
+
+// the viewer link being filled in, before it's created
+#[derive(Debug, Default)]
+struct NewViewerLink {
+    module: Option<ModuleId>,
+    label: String,
+    duration: Option<u64>,
+}
+
+#[derive(PartialEq, Clone)]
+struct SelectableModule(ModuleId, String);
+
+impl Display for SelectableModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableModule(_, name) = self;
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct SelectableDuration(u64);
+
+impl Display for SelectableDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableDuration(secs) = self;
+        let name = match secs / 3600 {
+            1 => t("sidebar.link-hour"),
+            24 => t("sidebar.link-day"),
+            hours => tf("sidebar.link-days", &[("days", &(hours / 24))]),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct SelectablePolicy(OverrunPolicy);
+
+impl Display for SelectablePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectablePolicy(policy) = self;
+        let name = match policy {
+            OverrunPolicy::Lag => t("overrun.lag"),
+            OverrunPolicy::AudioPriority => t("overrun.audio-priority"),
+            OverrunPolicy::SkipNonEssential => t("overrun.skip-non-essential"),
+            OverrunPolicy::SkipTick => t("overrun.skip-tick"),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct SelectableLanguage(&'static str, &'static str);
+
+impl Display for SelectableLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let SelectableLanguage(_, name) = self;
+        write!(f, "{}", name)
+    }
 }
 
 impl Component for Sidebar {
@@ -29,11 +112,16 @@ impl Component for Sidebar {
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let perf_notify = props.session.listen_performance(link.callback(SidebarMsg::PerfInfo));
+        let midi_notify = props.session.listen_midi_devices(link.callback(SidebarMsg::MidiDevices));
 
         Sidebar {
+            link,
             props,
             perf_info: None,
+            midi_devices: None,
+            new_link: NewViewerLink::default(),
             _perf_notify: perf_notify,
+            _midi_notify: midi_notify,
         }
     }
 
@@ -48,6 +136,50 @@ impl Component for Sidebar {
                 self.perf_info = Some(info);
                 true
             }
+            SidebarMsg::MidiDevices(devices) => {
+                self.midi_devices = Some(devices);
+                true
+            }
+            SidebarMsg::ChangeOverrunPolicy(policy) => {
+                self.props.session.update_workspace(WorkspaceOp::UpdateOverrunPolicy(policy));
+                false
+            }
+            SidebarMsg::UpdateModuleParams(module, params) => {
+                self.props.session.update_workspace(WorkspaceOp::UpdateModuleParams(module, params));
+                false
+            }
+            SidebarMsg::ChangeLanguage(code) => {
+                i18n::set_language(code);
+                false
+            }
+            SidebarMsg::NewLinkModule(module) => {
+                self.new_link.module = Some(module);
+                true
+            }
+            SidebarMsg::NewLinkLabel(label) => {
+                self.new_link.label = label;
+                false
+            }
+            SidebarMsg::NewLinkDuration(duration) => {
+                self.new_link.duration = Some(duration);
+                true
+            }
+            SidebarMsg::CreateViewerLink => {
+                let module = match self.new_link.module {
+                    Some(module) => module,
+                    None => return false,
+                };
+
+                let label = std::mem::take(&mut self.new_link.label);
+                let duration = self.new_link.duration.unwrap_or(LINK_DURATIONS[0]);
+
+                self.props.session.update_workspace(WorkspaceOp::CreateViewerLink(module, label, duration));
+                true
+            }
+            SidebarMsg::RevokeViewerLink(token) => {
+                self.props.session.update_workspace(WorkspaceOp::RevokeViewerLink(token));
+                false
+            }
         }
     }
 
@@ -55,7 +187,16 @@ impl Component for Sidebar {
         html! {
             <div class="sidebar">
                 <div class="sidebar-title">{"Mixlab"}</div>
+                {self.view_surface_link()}
+                {self.view_patch_export()}
+                {self.view_podcast()}
+                {self.view_viewer_links()}
+                <Mutate session={self.props.session.clone()} workspace={self.props.workspace.clone()} />
+                <Snapshots session={self.props.session.clone()} workspace={self.props.workspace.clone()} />
                 {self.view_perf_info()}
+                {self.view_overrun_policy()}
+                {self.view_midi_devices()}
+                {self.view_language()}
             </div>
         }
     }
@@ -65,10 +206,297 @@ impl Sidebar {
     fn module_name(&self, module_id: ModuleId) -> String {
         let workspace = self.props.workspace.borrow();
 
-        workspace.modules.get(&module_id).map(|module| {
-            format!("{:?}", module).chars()
-                .take_while(|c| c.is_alphanumeric()).collect::<String>()
-        }).unwrap_or("-".to_owned())
+        workspace.modules.get(&module_id)
+            .map(i18n::module_name)
+            .unwrap_or("-".to_owned())
+    }
+
+    // simplified controls for a podcast template, so that a show can be run
+    // without touching the patch itself
+    fn view_podcast(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        let podcast_modules = workspace.modules.iter()
+            .filter(|(id, _)| workspace.groups.get(*id).map(String::as_str) == Some(Template::PODCAST_GROUP))
+            .filter(|(id, _)| workspace.may_edit(**id))
+            .collect::<Vec<_>>();
+
+        let recorders = podcast_modules.iter()
+            .filter_map(|(id, params)| match params {
+                ModuleParams::Recorder(params) => Some((**id, params.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mixers = podcast_modules.iter()
+            .filter_map(|(id, params)| match params {
+                ModuleParams::Mixer(params) => Some((**id, params.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if recorders.is_empty() && mixers.is_empty() {
+            return html! {};
+        }
+
+        // every stem and the mixdown start and stop together
+        let recording = recorders.iter().any(|(_, params)| params.recording);
+
+        let toggle_recording = self.link.batch_callback(move |_| {
+            recorders.iter().map(|(id, params)| {
+                SidebarMsg::UpdateModuleParams(*id, ModuleParams::Recorder(RecorderParams {
+                    recording: !recording,
+                    ..params.clone()
+                }))
+            }).collect()
+        });
+
+        html! {
+            <div class="podcast">
+                <div class="podcast-heading">{t("sidebar.podcast")}</div>
+                <button class={if recording { "podcast-record podcast-record-active" } else { "podcast-record" }}
+                    onclick={toggle_recording}
+                >
+                    { if recording { t("sidebar.stop-recording") } else { t("sidebar.record") } }
+                </button>
+                <div class="podcast-channels">
+                    { for mixers.iter().flat_map(|(id, params)| {
+                        let id = *id;
+
+                        params.channels.iter().enumerate().map(move |(index, channel)| {
+                            let params = params.clone();
+
+                            html! {
+                                <div class="podcast-channel">
+                                    <Fader
+                                        value={channel.fader}
+                                        onchange={self.link.callback(move |fader| {
+                                            let mut params = params.clone();
+                                            params.channels[index] = MixerChannelParams { fader, ..params.channels[index].clone() };
+                                            SidebarMsg::UpdateModuleParams(id, ModuleParams::Mixer(params))
+                                        })}
+                                    />
+                                    <div class="podcast-channel-label">{tf("sidebar.mic", &[("number", &(index + 1))])}</div>
+                                </div>
+                            }
+                        })
+                    }) }
+                </div>
+            </div>
+        }
+    }
+
+    // the control surface is meant for a tablet alongside the workspace, so
+    // it opens separately, in the same permission group
+    fn view_surface_link(&self) -> Html {
+        let href = match &self.props.workspace.borrow().session_group {
+            Some(group) => format!("?view=surface&group={}", group),
+            None => "?view=surface".to_owned(),
+        };
+
+        html! {
+            <a class="sidebar-surface-link" href={href} target="_blank">{t("sidebar.open-surface")}</a>
+        }
+    }
+
+    // signal flow diagrams of the whole patch for show documentation, drawn
+    // by the server
+    fn view_patch_export(&self) -> Html {
+        html! {
+            <div class="sidebar-patch-export">
+                {t("sidebar.export-patch")}
+                <a href="/_patch/svg">{"SVG"}</a>
+                <a href="/_patch/dot">{"Graphviz"}</a>
+            </div>
+        }
+    }
+
+    // expiring links for sharing an HLS output or monitor with one viewer.
+    // only sessions without a group may hand these out
+    fn view_viewer_links(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        if workspace.session_group.is_some() {
+            return html! {};
+        }
+
+        let modules = workspace.modules.iter()
+            .filter(|(_, params)| match params {
+                ModuleParams::HlsOutput(_) | ModuleParams::Monitor(_) => true,
+                _ => false,
+            })
+            .map(|(id, params)| {
+                let name = match params {
+                    ModuleParams::HlsOutput(hls) => format!("{} ({})", i18n::module_name(params), hls.name),
+                    _ => i18n::module_name(params),
+                };
+                SelectableModule(*id, name)
+            })
+            .collect::<Vec<_>>();
+
+        if modules.is_empty() && workspace.viewer_links.is_empty() {
+            return html! {};
+        }
+
+        let selected_module = modules.iter()
+            .find(|SelectableModule(id, _)| Some(*id) == self.new_link.module)
+            .cloned();
+
+        let durations = LINK_DURATIONS.iter()
+            .map(|secs| SelectableDuration(*secs))
+            .collect::<Vec<_>>();
+
+        let now = (js_sys::Date::now() / 1000.0) as u64;
+
+        html! {
+            <div class="viewer-links">
+                <div class="viewer-links-heading">{t("sidebar.viewer-links")}</div>
+                { for workspace.viewer_links.iter().map(|link| {
+                    let url = viewer_url(link, workspace.modules.get(&link.module));
+                    let module = self.module_name(link.module);
+
+                    let expiry = if link.expires > now {
+                        tf("sidebar.link-expires", &[("time", &format_timestamp(link.expires))])
+                    } else {
+                        t("sidebar.link-expired")
+                    };
+
+                    html! {
+                        <div class="viewer-link">
+                            <div class="viewer-link-label">
+                                { if link.label.is_empty() { module } else { format!("{} - {}", link.label, module) } }
+                            </div>
+                            { match url {
+                                Some(url) if link.expires > now => html! {
+                                    <a class="viewer-link-url" href={url.clone()} target="_blank">{url}</a>
+                                },
+                                _ => html! {},
+                            } }
+                            <div class="viewer-link-expiry">{expiry}</div>
+                            <button class="viewer-link-revoke"
+                                onclick={self.link.callback({
+                                    let token = link.token.clone();
+                                    move |_| SidebarMsg::RevokeViewerLink(token.clone())
+                                })}
+                            >
+                                {t("sidebar.revoke-link")}
+                            </button>
+                        </div>
+                    }
+                }) }
+                { if !modules.is_empty() {
+                    html! {
+                        <div class="viewer-links-new">
+                            <Select<SelectableModule>
+                                selected={selected_module}
+                                options={modules}
+                                on_change={self.link.callback(|SelectableModule(id, _)| SidebarMsg::NewLinkModule(id))}
+                            />
+                            <input type="text"
+                                placeholder={t("sidebar.link-label")}
+                                value={&self.new_link.label}
+                                onchange={self.link.callback(|change| match change {
+                                    ChangeData::Value(label) => SidebarMsg::NewLinkLabel(label),
+                                    _ => unreachable!(),
+                                })}
+                            />
+                            <Select<SelectableDuration>
+                                selected={SelectableDuration(self.new_link.duration.unwrap_or(LINK_DURATIONS[0]))}
+                                options={durations}
+                                on_change={self.link.callback(|SelectableDuration(secs)| SidebarMsg::NewLinkDuration(secs))}
+                            />
+                            <button class="viewer-links-create"
+                                disabled={self.new_link.module.is_none()}
+                                onclick={self.link.callback(|_| SidebarMsg::CreateViewerLink)}
+                            >
+                                {t("sidebar.create-link")}
+                            </button>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                } }
+            </div>
+        }
+    }
+
+    fn view_overrun_policy(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        let policies = vec![
+            SelectablePolicy(OverrunPolicy::Lag),
+            SelectablePolicy(OverrunPolicy::AudioPriority),
+            SelectablePolicy(OverrunPolicy::SkipNonEssential),
+            SelectablePolicy(OverrunPolicy::SkipTick),
+        ];
+
+        let overruns = self.perf_info.iter()
+            .flat_map(|perf_info| perf_info.overruns.iter().rev());
+
+        html! {
+            <div class="overrun-policy">
+                <div class="overrun-policy-heading">{t("sidebar.on-overrun")}</div>
+                { if workspace.session_group.is_none() {
+                    html! {
+                        <Select<SelectablePolicy>
+                            selected={SelectablePolicy(workspace.overrun_policy)}
+                            options={policies}
+                            on_change={self.link.callback(|SelectablePolicy(policy)| SidebarMsg::ChangeOverrunPolicy(policy))}
+                        />
+                    }
+                } else {
+                    html! { <div>{SelectablePolicy(workspace.overrun_policy).to_string()}</div> }
+                } }
+                { for overruns.map(|overrun| html! {
+                    <div class="overrun-policy-event">
+                        {tf("sidebar.overrun-event", &[
+                            ("tick", &overrun.tick),
+                            ("elapsed", &overrun.elapsed.0),
+                            ("action", &SelectablePolicy(overrun.action)),
+                        ])}
+                    </div>
+                }) }
+            </div>
+        }
+    }
+
+    fn view_midi_devices(&self) -> Html {
+        match &self.midi_devices {
+            Some(devices) if !devices.inputs.is_empty() => html! {
+                <div class="midi-devices">
+                    <div class="midi-devices-heading">{t("sidebar.server-midi-inputs")}</div>
+                    { for devices.inputs.iter().map(|name| html! {
+                        <div class="midi-devices-device">{name}</div>
+                    }) }
+                </div>
+            },
+            _ => html! {},
+        }
+    }
+
+    // chosen per browser rather than per workspace, as operators sharing a
+    // workspace may not share a language
+    fn view_language(&self) -> Html {
+        let current = i18n::language();
+
+        let languages = i18n::LANGUAGES.iter()
+            .map(|&(code, name)| SelectableLanguage(code, name))
+            .collect::<Vec<_>>();
+
+        let selected = languages.iter()
+            .find(|SelectableLanguage(code, _)| *code == current)
+            .cloned();
+
+        html! {
+            <div class="language">
+                <div class="language-heading">{t("sidebar.language")}</div>
+                <Select<SelectableLanguage>
+                    selected={selected}
+                    options={languages}
+                    on_change={self.link.callback(|SelectableLanguage(code, _)| SidebarMsg::ChangeLanguage(code))}
+                />
+            </div>
+        }
     }
 
     fn view_perf_info(&self) -> Html {
@@ -100,12 +528,19 @@ impl Sidebar {
             html! {
                 <div class="perf-info">
                     <div class="status-light-bar">
-                        <div class={realtime_status_class}>{"REALTIME"}</div>
-                        <div class={lag_status_class}>{"LAG"}</div>
+                        <div class={realtime_status_class}>{t("sidebar.realtime")}</div>
+                        <div class={lag_status_class}>{t("status.lag")}</div>
                     </div>
                     <div class="perf-info-tick-util">
                         {format!("{:2.1}%", total_tick_percent)}
                     </div>
+                    <div class="perf-info-buffers">
+                        {tf("sidebar.buffer-pool", &[
+                            ("allocated", &perf_info.buffers.allocated),
+                            ("pooled", &perf_info.buffers.pooled),
+                            ("memory", &format_bytes(perf_info.buffers.memory)),
+                        ])}
+                    </div>
                     <table class="perf-info-accounts-table">
                         { for sorted_accounts.iter().map(|(account, metric)| {
                             let percent = (metric.last.0 as f64 / tick_budget) * 100.0;
@@ -114,13 +549,16 @@ impl Sidebar {
                                 <tr>
                                     { match account {
                                         PerformanceAccount::Engine => {
-                                            html! { <td class="perf-info-account perf-info-account-engine">{"Engine"}</td> }
+                                            html! { <td class="perf-info-account perf-info-account-engine">{t("sidebar.engine")}</td> }
                                         }
                                         PerformanceAccount::Module(id) => {
                                             let name = self.module_name(*id);
                                             html! { <td class="perf-info-account perf-info-account-module">{name}</td> }
                                         }
                                     } }
+                                    <td class="perf-info-metric perf-info-memory">
+                                        {metric.memory.map(format_bytes).unwrap_or_default()}
+                                    </td>
                                     <td class="perf-info-metric">{format!("{:2.1}%", percent)}</td>
                                 </tr>
                             }
@@ -133,3 +571,34 @@ impl Sidebar {
         }
     }
 }
+
+// HLS links are the playlist's own directory so that players can be given
+// them directly, monitor links open the viewer page
+fn viewer_url(link: &ViewerLink, module: Option<&ModuleParams>) -> Option<String> {
+    match module? {
+        ModuleParams::HlsOutput(_) => Some(format!("{}/_view/{}/index.m3u8", util::origin(), link.token)),
+        ModuleParams::Monitor(_) => Some(format!("{}/?watch={}", util::origin(), link.token)),
+        _ => None,
+    }
+}
+
+fn format_timestamp(unix_secs: u64) -> String {
+    let date = js_sys::Date::new(&JsValue::from(unix_secs as f64 * 1000.0));
+    String::from(date.to_locale_string("default", &JsValue::UNDEFINED))
+}
+
+fn format_bytes(bytes: Bytes) -> String {
+    let bytes = bytes.0 as f64;
+
+    if bytes >= 1024.0 * 1024.0 * 1024.0 {
+        format!("{:.1} GiB", bytes / (1024.0 * 1024.0 * 1024.0))
+    } else if bytes >= 1024.0 * 1024.0 {
+        format!("{:.1} MiB", bytes / (1024.0 * 1024.0))
+    } else if bytes >= 1024.0 {
+        format!("{:.0} KiB", bytes / 1024.0)
+    } else if bytes > 0.0 {
+        format!("{:.0} B", bytes)
+    } else {
+        String::new()
+    }
+}
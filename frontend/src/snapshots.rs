@@ -0,0 +1,160 @@
+//! Named copies of the whole workspace saved in the project, for switching
+//! between show configurations. Only sessions without a permission group may
+//! save or apply them, as applying one replaces every module.
+
+use std::rc::Rc;
+
+use wasm_bindgen::JsValue;
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::ChangeData;
+
+use mixlab_protocol as protocol;
+
+use crate::i18n::{t, tf};
+use crate::session::{SessionRef, WorkspaceStateRef};
+use crate::util::notify;
+
+pub struct Snapshots {
+    link: ComponentLink<Self>,
+    props: SnapshotsProps,
+    snapshots: Option<Rc<protocol::Snapshots>>,
+    name: String,
+    _notify: notify::Handle,
+}
+
+#[derive(Properties, Clone, Debug)]
+pub struct SnapshotsProps {
+    pub session: SessionRef,
+    pub workspace: WorkspaceStateRef,
+}
+
+pub enum SnapshotsMsg {
+    Update(Rc<protocol::Snapshots>),
+    SetName(String),
+    Save,
+    Apply(String),
+    Delete(String),
+}
+
+impl Component for Snapshots {
+    type Properties = SnapshotsProps;
+    type Message = SnapshotsMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_snapshots(link.callback(SnapshotsMsg::Update));
+
+        Snapshots {
+            link,
+            props,
+            snapshots: None,
+            name: String::new(),
+            _notify: notify,
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            SnapshotsMsg::Update(snapshots) => {
+                self.snapshots = Some(snapshots);
+                true
+            }
+            SnapshotsMsg::SetName(name) => {
+                self.name = name;
+                false
+            }
+            SnapshotsMsg::Save => {
+                let name = std::mem::take(&mut self.name);
+                let name = name.trim();
+
+                if name.is_empty() {
+                    return false;
+                }
+
+                self.props.session.save_snapshot(name.to_owned());
+                true
+            }
+            SnapshotsMsg::Apply(name) => {
+                self.props.session.apply_snapshot(name);
+                false
+            }
+            SnapshotsMsg::Delete(name) => {
+                self.props.session.delete_snapshot(name);
+                false
+            }
+        }
+    }
+
+    fn view(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        if workspace.session_group.is_some() {
+            return html! {};
+        }
+
+        // applying deletes every module, so protected ones must be armed
+        // first just as for deleting them one by one
+        let locked = workspace.safety.values().any(|safety| safety.is_locked());
+
+        let snapshots = self.snapshots.iter()
+            .flat_map(|snapshots| snapshots.snapshots.iter());
+
+        html! {
+            <div class="snapshots">
+                <div class="snapshots-heading">{t("snapshots.title")}</div>
+                { for snapshots.map(|snapshot| {
+                    let name = snapshot.name.clone();
+
+                    html! {
+                        <div class="snapshot">
+                            <div class="snapshot-name">{&snapshot.name}</div>
+                            <div class="snapshot-detail">
+                                {tf("snapshots.detail", &[
+                                    ("modules", &snapshot.module_count),
+                                    ("time", &format_timestamp(snapshot.saved_at)),
+                                ])}
+                            </div>
+                            <div class="snapshot-actions">
+                                <button
+                                    disabled={locked}
+                                    title={if locked { t("snapshots.arm-first") } else { String::new() }}
+                                    onclick={self.link.callback({
+                                        let name = name.clone();
+                                        move |_| SnapshotsMsg::Apply(name.clone())
+                                    })}
+                                >
+                                    {t("snapshots.apply")}
+                                </button>
+                                <button onclick={self.link.callback(move |_| SnapshotsMsg::Delete(name.clone()))}>
+                                    {t("snapshots.delete")}
+                                </button>
+                            </div>
+                        </div>
+                    }
+                }) }
+                <div class="snapshots-new">
+                    <input type="text"
+                        placeholder={t("snapshots.name")}
+                        value={&self.name}
+                        onchange={self.link.callback(|change| match change {
+                            ChangeData::Value(name) => SnapshotsMsg::SetName(name),
+                            _ => unreachable!(),
+                        })}
+                    />
+                    <button onclick={self.link.callback(|_| SnapshotsMsg::Save)}>
+                        {t("snapshots.save")}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+}
+
+fn format_timestamp(unix_secs: i64) -> String {
+    let date = js_sys::Date::new(&JsValue::from(unix_secs as f64 * 1000.0));
+    String::from(date.to_locale_string("default", &JsValue::UNDEFINED))
+}
@@ -0,0 +1,399 @@
+//! Touchscreen control surface, opened with `?view=surface`. It shows large
+//! faders and buttons for the parameters chosen in its layout editor, and
+//! nothing else, so a tablet can stand in for a physical console alongside
+//! the main workspace. The layout is kept with the workspace on the server.
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender, Properties};
+use yew::events::{ChangeData, InputData};
+
+use mixlab_protocol::{ModuleId, ModuleParams, MidiTarget, SurfaceControl, WorkspaceOp, GateState, MixerChannelParams};
+
+use crate::i18n::{self, t, tf};
+use crate::session::{SessionRef, WorkspaceStateRef};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlKind {
+    Fader,
+    Toggle,
+    // on only while held down
+    Momentary,
+}
+
+/// Parameters of a module which can be put on the surface, by the path
+/// naming them as in MIDI mappings.
+pub fn controls(params: &ModuleParams) -> Vec<(String, ControlKind)> {
+    match params {
+        ModuleParams::Mixer(params) => {
+            (1..=params.channels.len())
+                .flat_map(|channel| vec![
+                    (format!("channel-{}/fader", channel), ControlKind::Fader),
+                    (format!("channel-{}/cue", channel), ControlKind::Toggle),
+                ])
+                .collect()
+        }
+        ModuleParams::Delay(_) |
+        ModuleParams::Reverb(_) => vec![("mix".to_owned(), ControlKind::Fader)],
+        ModuleParams::VideoMixer(_) => vec![("fader".to_owned(), ControlKind::Fader)],
+        ModuleParams::Recorder(_) => vec![("recording".to_owned(), ControlKind::Toggle)],
+        ModuleParams::Slate(_) => vec![("engaged".to_owned(), ControlKind::Toggle)],
+        ModuleParams::Talkback(_) => vec![("talk".to_owned(), ControlKind::Momentary)],
+        _ => vec![],
+    }
+}
+
+fn kind(params: &ModuleParams, param: &str) -> Option<ControlKind> {
+    controls(params).into_iter()
+        .find(|(name, _)| name == param)
+        .map(|(_, kind)| kind)
+}
+
+// current value of a parameter, from 0 to 1. switches are 0 or 1
+fn value(params: &ModuleParams, param: &str) -> Option<f64> {
+    let switch = |on: bool| if on { 1.0 } else { 0.0 };
+
+    match (params, param) {
+        (ModuleParams::Mixer(params), _) => {
+            let (index, control) = mixer_channel(param)?;
+            let channel = params.channels.get(index)?;
+
+            match control {
+                "fader" => Some(channel.fader),
+                "cue" => Some(switch(channel.cue)),
+                _ => None,
+            }
+        }
+        (ModuleParams::Delay(params), "mix") => Some(params.mix),
+        (ModuleParams::Reverb(params), "mix") => Some(params.mix),
+        (ModuleParams::VideoMixer(params), "fader") => Some(params.fader),
+        (ModuleParams::Recorder(params), "recording") => Some(switch(params.recording)),
+        (ModuleParams::Slate(params), "engaged") => Some(switch(params.engaged)),
+        (ModuleParams::Talkback(gate), "talk") => Some(switch(*gate == GateState::Open)),
+        _ => None,
+    }
+}
+
+// params with a parameter changed, or none if the module has no such
+// parameter
+fn with_value(params: &ModuleParams, param: &str, value: f64) -> Option<ModuleParams> {
+    let on = value >= 0.5;
+    let mut params = params.clone();
+
+    match (&mut params, param) {
+        (ModuleParams::Mixer(params), _) => {
+            let (index, control) = mixer_channel(param)?;
+            let channel = params.channels.get_mut(index)?;
+
+            *channel = match control {
+                "fader" => MixerChannelParams { fader: value, ..channel.clone() },
+                "cue" => MixerChannelParams { cue: on, ..channel.clone() },
+                _ => return None,
+            };
+        }
+        (ModuleParams::Delay(params), "mix") => { params.mix = value; }
+        (ModuleParams::Reverb(params), "mix") => { params.mix = value; }
+        (ModuleParams::VideoMixer(params), "fader") => { params.fader = value; }
+        (ModuleParams::Recorder(params), "recording") => { params.recording = on; }
+        (ModuleParams::Slate(params), "engaged") => { params.engaged = on; }
+        (ModuleParams::Talkback(gate), "talk") => {
+            *gate = if on { GateState::Open } else { GateState::Closed };
+        }
+        _ => return None,
+    }
+
+    Some(params)
+}
+
+// splits a mixer parameter like `channel-2/fader` into the channel index and
+// control name
+fn mixer_channel(param: &str) -> Option<(usize, &str)> {
+    let mut parts = param.splitn(2, '/');
+    let channel = parts.next()?;
+    let control = parts.next()?;
+
+    if !channel.starts_with("channel-") {
+        return None;
+    }
+
+    let number = channel["channel-".len()..].parse::<usize>().ok()?;
+    Some((number.checked_sub(1)?, control))
+}
+
+pub struct Surface {
+    link: ComponentLink<Self>,
+    props: SurfaceProps,
+    editing: bool,
+}
+
+#[derive(Properties, Clone, Debug)]
+pub struct SurfaceProps {
+    pub session: SessionRef,
+    pub workspace: WorkspaceStateRef,
+}
+
+pub enum SurfaceMsg {
+    UpdateModuleParams(ModuleId, ModuleParams),
+    UpdateLayout(Vec<SurfaceControl>),
+    ToggleEditing,
+}
+
+impl Component for Surface {
+    type Properties = SurfaceProps;
+    type Message = SurfaceMsg;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Surface {
+            link,
+            props,
+            editing: false,
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            SurfaceMsg::UpdateModuleParams(module, params) => {
+                self.props.session.update_workspace(WorkspaceOp::UpdateModuleParams(module, params));
+                false
+            }
+            SurfaceMsg::UpdateLayout(surface) => {
+                self.props.session.update_workspace(WorkspaceOp::UpdateSurface(surface));
+                false
+            }
+            SurfaceMsg::ToggleEditing => {
+                self.editing = !self.editing;
+                true
+            }
+        }
+    }
+
+    fn view(&self) -> Html {
+        // the layout is shared by the whole workspace, so only sessions
+        // without a permission group may change it
+        let may_edit_layout = self.props.workspace.borrow().session_group.is_none();
+
+        html! {
+            <div class="surface">
+                <div class="surface-bar">
+                    <div class="surface-title">{t("surface.title")}</div>
+                    { if may_edit_layout {
+                        html! {
+                            <button class={if self.editing { "surface-edit surface-edit-active" } else { "surface-edit" }}
+                                onclick={self.link.callback(|_| SurfaceMsg::ToggleEditing)}
+                            >
+                                { if self.editing { t("surface.done") } else { t("surface.edit-layout") } }
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    } }
+                </div>
+                { if self.editing && may_edit_layout {
+                    self.view_editor()
+                } else {
+                    self.view_controls()
+                } }
+            </div>
+        }
+    }
+}
+
+impl Surface {
+    fn view_controls(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+
+        if workspace.surface.is_empty() {
+            return html! {
+                <div class="surface-empty">{t("surface.empty")}</div>
+            };
+        }
+
+        html! {
+            <div class="surface-controls">
+                { for workspace.surface.iter().filter_map(|control| {
+                    let module = control.target.module;
+                    let params = workspace.modules.get(&module)?;
+                    let kind = kind(params, &control.target.param)?;
+                    let current = value(params, &control.target.param)?;
+                    let disabled = !workspace.may_edit(module);
+
+                    let set = {
+                        let params = params.clone();
+                        let param = control.target.param.clone();
+
+                        move |value: f64| {
+                            // controls are only rendered for parameters the
+                            // module has:
+                            let params = with_value(&params, &param, value).unwrap();
+                            SurfaceMsg::UpdateModuleParams(module, params)
+                        }
+                    };
+
+                    let input = match kind {
+                        ControlKind::Fader => html! {
+                            <input type="range" class="surface-fader"
+                                min="0" max="1" step="0.001"
+                                disabled={disabled}
+                                value={current}
+                                oninput={self.link.callback(move |ev: InputData| {
+                                    set(ev.value.parse().unwrap_or(current))
+                                })}
+                            />
+                        },
+                        ControlKind::Toggle => html! {
+                            <button class={button_class(current)}
+                                disabled={disabled}
+                                onclick={self.link.callback(move |_| set(1.0 - current))}
+                            >
+                                {&control.label}
+                            </button>
+                        },
+                        ControlKind::Momentary => {
+                            let release = set.clone();
+
+                            html! {
+                                <button class={button_class(current)}
+                                    disabled={disabled}
+                                    onpointerdown={self.link.callback(move |_| set(1.0))}
+                                    onpointerup={self.link.callback({
+                                        let release = release.clone();
+                                        move |_| release(0.0)
+                                    })}
+                                    onpointerleave={self.link.callback(move |_| release(0.0))}
+                                >
+                                    {&control.label}
+                                </button>
+                            }
+                        }
+                    };
+
+                    Some(html! {
+                        <div class={format!("surface-control surface-control-{}", kind_class(kind))}>
+                            {input}
+                            { if kind == ControlKind::Fader {
+                                html! { <div class="surface-control-label">{&control.label}</div> }
+                            } else {
+                                html! {}
+                            } }
+                        </div>
+                    })
+                }) }
+            </div>
+        }
+    }
+
+    fn view_editor(&self) -> Html {
+        let workspace = self.props.workspace.borrow();
+        let surface = workspace.surface.clone();
+
+        let update_layout = {
+            let surface = surface.clone();
+            move |f: &dyn Fn(&mut Vec<SurfaceControl>)| {
+                let mut surface = surface.clone();
+                f(&mut surface);
+                SurfaceMsg::UpdateLayout(surface)
+            }
+        };
+
+        let available = workspace.modules.iter()
+            .flat_map(|(id, params)| {
+                controls(params).into_iter()
+                    .map(move |(param, _)| (MidiTarget::new(*id, param), params))
+            })
+            .filter(|(target, _)| !surface.iter().any(|control| &control.target == target))
+            .collect::<Vec<_>>();
+
+        html! {
+            <div class="surface-editor">
+                <div class="surface-editor-heading">{t("surface.on-surface")}</div>
+                { for surface.iter().enumerate().map(|(index, control)| {
+                    let module_name = workspace.modules.get(&control.target.module)
+                        .map(i18n::module_name)
+                        .unwrap_or_default();
+
+                    let last = index + 1 == surface.len();
+
+                    html! {
+                        <div class="surface-editor-control">
+                            <input type="text"
+                                value={&control.label}
+                                onchange={self.link.callback({
+                                    let update_layout = update_layout.clone();
+                                    move |ev| {
+                                        let label = match ev {
+                                            ChangeData::Value(value) => value,
+                                            _ => unreachable!(),
+                                        };
+
+                                        update_layout(&|surface| surface[index].label = label.clone())
+                                    }
+                                })}
+                            />
+                            <span class="surface-editor-target">
+                                {tf("surface.target", &[("module", &module_name), ("param", &control.target.param)])}
+                            </span>
+                            <button disabled={index == 0}
+                                onclick={self.link.callback({
+                                    let update_layout = update_layout.clone();
+                                    move |_| update_layout(&|surface| surface.swap(index - 1, index))
+                                })}
+                            >{"←"}</button>
+                            <button disabled={last}
+                                onclick={self.link.callback({
+                                    let update_layout = update_layout.clone();
+                                    move |_| update_layout(&|surface| surface.swap(index, index + 1))
+                                })}
+                            >{"→"}</button>
+                            <button
+                                onclick={self.link.callback({
+                                    let update_layout = update_layout.clone();
+                                    move |_| update_layout(&|surface| { surface.remove(index); })
+                                })}
+                            >{t("action.remove")}</button>
+                        </div>
+                    }
+                }) }
+
+                <div class="surface-editor-heading">{t("surface.available")}</div>
+                { for available.into_iter().map(|(target, params)| {
+                    let module_name = i18n::module_name(params);
+                    let label = tf("surface.target", &[("module", &module_name), ("param", &target.param)]);
+
+                    html! {
+                        <button class="surface-editor-add"
+                            onclick={self.link.callback({
+                                let update_layout = update_layout.clone();
+                                let label = label.clone();
+                                move |_| update_layout(&|surface| surface.push(SurfaceControl {
+                                    target: target.clone(),
+                                    label: label.clone(),
+                                }))
+                            })}
+                        >
+                            {tf("surface.add", &[("control", &label)])}
+                        </button>
+                    }
+                }) }
+            </div>
+        }
+    }
+}
+
+fn button_class(value: f64) -> &'static str {
+    if value >= 0.5 {
+        "surface-button surface-button-active"
+    } else {
+        "surface-button"
+    }
+}
+
+fn kind_class(kind: ControlKind) -> &'static str {
+    match kind {
+        ControlKind::Fader => "fader",
+        ControlKind::Toggle => "toggle",
+        ControlKind::Momentary => "momentary",
+    }
+}
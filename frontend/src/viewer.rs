@@ -0,0 +1,55 @@
+//! The page a viewer link to a Monitor opens, `?watch=<token>`. It shows
+//! only the monitor and never connects a session, so viewers get nothing
+//! else of the workspace.
+
+use yew::{html, Component, ComponentLink, Html, ShouldRender};
+
+use crate::module::monitor::Monitor;
+use crate::util;
+
+pub struct ViewerPage {
+    token: Option<String>,
+}
+
+/// Whether this page was opened from a viewer link.
+pub fn requested() -> bool {
+    watch_token().is_some()
+}
+
+fn watch_token() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+
+    search.trim_start_matches('?')
+        .split('&')
+        .filter(|pair| pair.starts_with("watch="))
+        .map(|pair| pair["watch=".len()..].to_owned())
+        .find(|token| !token.is_empty())
+}
+
+impl Component for ViewerPage {
+    type Properties = ();
+    type Message = ();
+
+    fn create(_: (), _: ComponentLink<Self>) -> Self {
+        ViewerPage { token: watch_token() }
+    }
+
+    fn change(&mut self, _: ()) -> ShouldRender {
+        false
+    }
+
+    fn update(&mut self, _: ()) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        match &self.token {
+            Some(token) => html! {
+                <div class="viewer">
+                    <Monitor socket_url={format!("{}/_view/{}", util::websocket_origin(), token)} />
+                </div>
+            },
+            None => html! {},
+        }
+    }
+}
@@ -1,28 +1,68 @@
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::mem;
+use std::rc::Rc;
 
 use wasm_bindgen::JsCast;
 use web_sys::{CanvasRenderingContext2d, HtmlElement, HtmlCanvasElement, MouseEvent, Element};
 use yew::{html, Callback, Component, ComponentLink, Html, ShouldRender, Properties, NodeRef};
 
-use mixlab_protocol::{ModuleId, TerminalId, InputId, OutputId, ModuleParams, OscillatorParams, Waveform, WorkspaceOp, WindowGeometry, Coords, Indication, OutputDeviceParams, FmSineParams, AmplifierParams, GateState, LineType, EnvelopeParams, MixerParams, StreamInputParams, EqThreeParams, StreamOutputParams, VideoMixerParams, MediaSourceParams};
+use mixlab_protocol::{ModuleId, TerminalId, InputId, OutputId, ChannelAdapter, ModuleParams, ModuleCommand, OscillatorParams, WorkspaceOp, WindowGeometry, Coords, Indication, OutputDeviceParams, PitchShiftParams, FmSineParams, HlsOutputParams, AmbisonicEncoderParams, AmplifierParams, BinauralDecoderParams, GateState, LineType, EnvelopeParams, MixerParams, StreamInputParams, EqThreeParams, CrossoverParams, NoiseGateParams, FeedbackSuppressorParams, FileOutputParams, StreamOutputParams, VideoMixerParams, MediaSourceParams, RecorderParams, SilenceDetectorParams, LfoParams, FilterParams, DelayParams, EchoCancellerParams, ReverbParams, LooperParams, SamplerParams, SequencerParams, ClockParams, MidiNoteParams, InputDeviceParams, IcecastOutputParams, ImageOverlayParams, CompositorParams, ChromaKeyParams, ProfanityDelayParams, SlateParams, SpectrumAnalyzerParams, TextOverlayParams, TunerParams, WaveshaperParams, CompressorParams, LimiterParams, ModuleSafety, Template, FreezeState, Macros};
 
 use crate::component::midi_target::MidiUiMode;
+use crate::i18n::{self, t, tf};
+use crate::module::ambisonic_encoder::AmbisonicEncoder;
 use crate::module::amplifier::Amplifier;
+use crate::module::binaural_decoder::BinauralDecoder;
+use crate::module::chroma_key::ChromaKey;
+use crate::module::clock::Clock;
+use crate::module::compositor::Compositor;
+use crate::module::compressor::Compressor;
+use crate::module::crossover::Crossover;
+use crate::module::delay::Delay;
+use crate::module::echo_canceller::EchoCanceller;
 use crate::module::envelope::Envelope;
 use crate::module::eq_three::EqThree;
+use crate::module::feedback_suppressor::FeedbackSuppressor;
+use crate::module::file_output::FileOutput;
+use crate::module::filter::Filter;
 use crate::module::fm_sine::FmSine;
+use crate::module::goniometer::Goniometer;
+use crate::module::hls_output::HlsOutput;
+use crate::module::icecast_output::IcecastOutput;
+use crate::module::image_overlay::ImageOverlay;
+use crate::module::input_device::InputDevice;
+use crate::module::lfo::Lfo;
+use crate::module::limiter::Limiter;
+use crate::module::looper::Looper;
+use crate::module::macro_module::Macro;
+use crate::module::meter::Meter;
+use crate::module::midi_note::MidiNote;
 use crate::module::media_source::MediaSource;
 use crate::module::mixer::Mixer;
-use crate::module::monitor::Monitor;
+use crate::module::monitor::{self, Monitor};
+use crate::module::noise_gate::NoiseGate;
 use crate::module::oscillator::Oscillator;
 use crate::module::output_device::OutputDevice;
+use crate::module::pitch_shift::PitchShift;
 use crate::module::plotter::Plotter;
+use crate::module::profanity_delay::ProfanityDelay;
+use crate::module::recorder::Recorder;
+use crate::module::reverb::Reverb;
+use crate::module::sampler::Sampler;
+use crate::module::sequencer::Sequencer;
+use crate::module::silence_detector::SilenceDetector;
+use crate::module::slate::Slate;
+use crate::module::spectrum_analyzer::SpectrumAnalyzer;
 use crate::module::stream_input::StreamInput;
 use crate::module::stream_output::StreamOutput;
+use crate::module::sync_check::SyncCheck;
+use crate::module::talkback::Talkback;
+use crate::module::text_overlay::TextOverlay;
 use crate::module::trigger::Trigger;
+use crate::module::tuner::Tuner;
 use crate::module::video_mixer::VideoMixer;
-use crate::util::{self, stop_propagation, prevent_default, Sequence};
+use crate::module::waveshaper::Waveshaper;
+use crate::util::{self, notify, stop_propagation, prevent_default, Sequence};
 use crate::session::{WorkspaceStateRef, WorkspaceState, SessionRef};
 use crate::{App, AppMsg};
 
@@ -33,6 +73,10 @@ pub struct Workspace {
     gen_z_index: Sequence,
     mouse: MouseMode,
     window_refs: BTreeMap<ModuleId, WindowRef>,
+    // windows shift-clicked for grouping into a macro
+    selected: BTreeSet<ModuleId>,
+    macros: Option<Rc<Macros>>,
+    _notify: notify::Handle,
 }
 
 #[derive(Properties, Clone)]
@@ -63,9 +107,21 @@ pub enum WorkspaceMsg {
     MouseMove(MouseEvent),
     SelectTerminal(TerminalId, TerminalRef),
     ClearTerminal(TerminalId),
+    CycleAdapter(InputId),
     DeleteWindow(ModuleId),
     UpdateModuleParams(ModuleId, ModuleParams),
+    SendModuleCommand(ModuleId, ModuleCommand),
+    UpdateModuleSafety(ModuleId, ModuleSafety),
+    UpdateModuleGroup(ModuleId, Option<String>),
+    UpdateModuleBypass(ModuleId, bool),
+    FreezeOutput(OutputId, f64),
+    ThawOutput(OutputId),
     CreateModule(ModuleParams, Coords),
+    CreateTemplate(Template, Coords),
+    ToggleSelected(ModuleId),
+    CreateMacro,
+    UpdateMacros(Rc<Macros>),
+    DeleteMacro(String),
 }
 
 impl Component for Workspace {
@@ -73,6 +129,8 @@ impl Component for Workspace {
     type Properties = WorkspaceProps;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let notify = props.session.listen_macros(link.callback(WorkspaceMsg::UpdateMacros));
+
         let mut workspace = Workspace {
             link,
             props,
@@ -80,6 +138,9 @@ impl Component for Workspace {
             gen_z_index: Sequence::new(),
             mouse: MouseMode::Normal,
             window_refs: BTreeMap::new(),
+            selected: BTreeSet::new(),
+            macros: None,
+            _notify: notify,
         };
 
         workspace.update_state();
@@ -101,6 +162,10 @@ impl Component for Workspace {
             WorkspaceMsg::DragStart(module, ev) => {
                 let mut state = self.props.state.borrow_mut();
 
+                if !state.may_edit(module) {
+                    return false;
+                }
+
                 if let Some(geom) = state.geometry.get_mut(&module) {
                     self.mouse = MouseMode::Drag(Drag {
                         module,
@@ -194,7 +259,29 @@ impl Component for Workspace {
                             (TerminalId::Output(output), TerminalId::Input(input)) => {
                                 let mut state = self.props.state.borrow_mut();
 
-                                if terminal_ref.line_type == other_terminal_ref.line_type {
+                                if !state.may_edit(input.module_id()) {
+                                    return false;
+                                }
+
+                                if state.connections.contains_key(&input) && state.safety(input.module_id()).is_locked() {
+                                    // replacing the connection would disconnect
+                                    // a protected module's input, it must be
+                                    // armed first
+                                    return false;
+                                }
+
+                                let (input_type, output_type) = match terminal_id {
+                                    TerminalId::Input(_) => (terminal_ref.line_type, other_terminal_ref.line_type),
+                                    TerminalId::Output(_) => (other_terminal_ref.line_type, terminal_ref.line_type),
+                                };
+
+                                if state.feeds(input.module_id(), output.module_id()) {
+                                    // would feed the module back into
+                                    // itself, which the engine refuses
+                                    return false;
+                                }
+
+                                if output_type.connects_to(input_type) {
                                     state.connections.insert(input, output);
 
                                     self.mouse = MouseMode::Normal;
@@ -222,9 +309,13 @@ impl Component for Workspace {
             WorkspaceMsg::ClearTerminal(terminal) => {
                 match terminal {
                     TerminalId::Input(input) => {
-                        self.props.state.borrow_mut()
-                            .connections
-                            .remove(&input);
+                        let mut state = self.props.state.borrow_mut();
+
+                        if state.safety(input.module_id()).is_locked() || !state.may_edit(input.module_id()) {
+                            return false;
+                        }
+
+                        state.connections.remove(&input);
 
                         self.props.app.send_message(
                             AppMsg::ClientUpdate(
@@ -235,24 +326,77 @@ impl Component for Workspace {
 
                         let mut state = self.props.state.borrow_mut();
 
+                        let mut deleted = Vec::new();
+
                         for (in_, out_) in &state.connections {
-                            if *out_ == output {
-                                msgs.push(AppMsg::ClientUpdate(
-                                    WorkspaceOp::DeleteConnection(*in_)));
+                            // leave cables into protected modules and modules
+                            // we may not edit alone
+                            if *out_ == output && !state.safety(in_.module_id()).is_locked() && state.may_edit(in_.module_id()) {
+                                deleted.push(*in_);
                             }
                         }
 
-                        // yeah, this is just doing the same loop as the loop above
-                        // but it's good enough for now
-                        state.connections.retain(|_, out| output != *out);
+                        for input in deleted {
+                            state.connections.remove(&input);
+                            msgs.push(AppMsg::ClientUpdate(
+                                WorkspaceOp::DeleteConnection(input)));
+                        }
 
                         self.props.app.send_message_batch(msgs);
                     }
                 }
                 true
             }
+            WorkspaceMsg::CycleAdapter(input) => {
+                let mut state = self.props.state.borrow_mut();
+
+                if state.safety(input.module_id()).is_locked() || !state.may_edit(input.module_id()) {
+                    return false;
+                }
+
+                let adapter = match state.adapter(input) {
+                    Some(ChannelAdapter::Both) => ChannelAdapter::Left,
+                    Some(ChannelAdapter::Left) => ChannelAdapter::Right,
+                    Some(ChannelAdapter::Right) => ChannelAdapter::Both,
+                    // not connected through an adapter
+                    None => return false,
+                };
+
+                if adapter == ChannelAdapter::default() {
+                    state.adapters.remove(&input);
+                } else {
+                    state.adapters.insert(input, adapter);
+                }
+
+                self.props.app.send_message(
+                    AppMsg::ClientUpdate(
+                        WorkspaceOp::UpdateConnectionAdapter(input, adapter)));
+
+                true
+            }
             WorkspaceMsg::DeleteWindow(module) => {
                 let mut state = self.props.state.borrow_mut();
+
+                // the server will refuse to delete a protected module, or one
+                // feeding into a protected module, unless it has been armed
+                let locked = state.safety(module).is_locked() ||
+                    state.connections.iter().any(|(input, output)| {
+                        output.module_id() == module && state.safety(input.module_id()).is_locked()
+                    });
+
+                let permitted = state.may_edit(module) &&
+                    state.connections.iter().all(|(input, output)| {
+                        output.module_id() != module || state.may_edit(input.module_id())
+                    });
+
+                if !permitted {
+                    return false;
+                }
+
+                if locked {
+                    return false;
+                }
+
                 state.modules.remove(&module);
                 state.geometry.remove(&module);
                 state.connections.retain(|input, output| {
@@ -268,6 +412,10 @@ impl Component for Workspace {
             WorkspaceMsg::UpdateModuleParams(module, params) => {
                 let mut state = self.props.state.borrow_mut();
 
+                if !state.may_edit(module) {
+                    return false;
+                }
+
                 if let Some(module_params) = state.modules.get_mut(&module) {
                     // verify that we're updating the module params with the
                     // same kind of module params:
@@ -286,6 +434,86 @@ impl Component for Workspace {
                     false
                 }
             }
+            WorkspaceMsg::SendModuleCommand(module, command) => {
+                if !self.props.state.borrow().may_edit(module) {
+                    return false;
+                }
+
+                self.props.app.send_message(
+                    AppMsg::ClientUpdate(
+                        WorkspaceOp::ModuleCommand(module, command)));
+
+                false
+            }
+            WorkspaceMsg::UpdateModuleSafety(module, safety) => {
+                let mut state = self.props.state.borrow_mut();
+
+                if !state.may_edit(module) {
+                    return false;
+                }
+
+                state.safety.insert(module, safety);
+
+                self.props.app.send_message(
+                    AppMsg::ClientUpdate(
+                        WorkspaceOp::UpdateModuleSafety(module, safety)));
+
+                true
+            }
+            WorkspaceMsg::UpdateModuleGroup(module, group) => {
+                let mut state = self.props.state.borrow_mut();
+
+                // only sessions without a group may reassign modules
+                if state.session_group.is_some() {
+                    return false;
+                }
+
+                match &group {
+                    Some(group) => { state.groups.insert(module, group.clone()); }
+                    None => { state.groups.remove(&module); }
+                }
+
+                self.props.app.send_message(
+                    AppMsg::ClientUpdate(
+                        WorkspaceOp::UpdateModuleGroup(module, group)));
+
+                true
+            }
+            WorkspaceMsg::UpdateModuleBypass(module, bypassed) => {
+                let mut state = self.props.state.borrow_mut();
+
+                if !state.may_edit(module) {
+                    return false;
+                }
+
+                if bypassed {
+                    state.bypassed.insert(module);
+                } else {
+                    state.bypassed.remove(&module);
+                }
+
+                self.props.app.send_message(
+                    AppMsg::ClientUpdate(
+                        WorkspaceOp::UpdateModuleBypass(module, bypassed)));
+
+                true
+            }
+            WorkspaceMsg::FreezeOutput(output, length_secs) => {
+                // the engine decides whether the chain can be frozen, the
+                // window updates once it replies
+                self.props.app.send_message(
+                    AppMsg::ClientUpdate(
+                        WorkspaceOp::FreezeOutput(output, length_secs)));
+
+                false
+            }
+            WorkspaceMsg::ThawOutput(output) => {
+                self.props.app.send_message(
+                    AppMsg::ClientUpdate(
+                        WorkspaceOp::ThawOutput(output)));
+
+                false
+            }
             WorkspaceMsg::CreateModule(module, coords) => {
                 self.mouse = MouseMode::Normal;
 
@@ -300,6 +528,56 @@ impl Component for Workspace {
 
                 true
             }
+            WorkspaceMsg::CreateTemplate(template, coords) => {
+                self.mouse = MouseMode::Normal;
+
+                let geometry = WindowGeometry {
+                    position: coords,
+                    z_index: self.gen_z_index.next().get(),
+                };
+
+                self.props.app.send_message(
+                    AppMsg::ClientUpdate(
+                        WorkspaceOp::CreateTemplate(template, geometry)));
+
+                true
+            }
+            WorkspaceMsg::ToggleSelected(module) => {
+                if !self.selected.remove(&module) {
+                    self.selected.insert(module);
+                }
+
+                true
+            }
+            WorkspaceMsg::CreateMacro => {
+                self.mouse = MouseMode::Normal;
+
+                let name = web_sys::window().unwrap()
+                    .prompt_with_message(&t("macro.name-prompt"))
+                    .ok()
+                    .flatten()
+                    .map(|name| name.trim().to_owned())
+                    .filter(|name| !name.is_empty());
+
+                // prompt returns None if cancelled
+                if let Some(name) = name {
+                    let modules = mem::take(&mut self.selected).into_iter().collect();
+
+                    self.props.app.send_message(
+                        AppMsg::ClientUpdate(
+                            WorkspaceOp::CreateMacro(name, modules)));
+                }
+
+                true
+            }
+            WorkspaceMsg::UpdateMacros(macros) => {
+                self.macros = Some(macros);
+                true
+            }
+            WorkspaceMsg::DeleteMacro(name) => {
+                self.props.session.delete_macro(name);
+                false
+            }
         };
 
         fn drag_event(state: &mut WorkspaceState, window_refs: &BTreeMap<ModuleId, WindowRef>, drag: &mut Drag, ev: MouseEvent) -> ShouldRender {
@@ -328,24 +606,28 @@ impl Component for Workspace {
     }
 
     fn view(&self) -> Html {
-        let mut connections: Vec<(Coords, Coords)> = vec![];
+        let mut connections: Vec<Cable> = vec![];
 
         for (input, output) in &self.props.state.borrow().connections {
             if let Some(input_coords) = self.screen_coords_for_terminal(TerminalId::Input(*input)) {
                 if let Some(output_coords) = self.screen_coords_for_terminal(TerminalId::Output(*output)) {
-                    connections.push((output_coords, input_coords));
+                    connections.push(Cable {
+                        from: output_coords,
+                        to: input_coords,
+                        adapted: self.props.state.borrow().adapter(*input).is_some(),
+                    });
                 }
             }
         }
 
         if let MouseMode::Connect(terminal_id, _, Some(to_coords)) = &self.mouse {
             if let Some(start_coords) = self.screen_coords_for_terminal(*terminal_id) {
-                let pair = match terminal_id {
+                let (from, to) = match terminal_id {
                     TerminalId::Input(_) => (*to_coords, start_coords),
                     TerminalId::Output(_) => (start_coords, *to_coords),
                 };
 
-                connections.push(pair);
+                connections.push(Cable { from, to, adapted: false });
             }
         }
 
@@ -366,9 +648,19 @@ impl Component for Workspace {
                     let geometry = state.geometry.get(id);
                     let workspace = self.link.clone();
                     let indication = state.indications.get(id);
+                    let safety = state.safety(*id);
+                    let editable = state.may_edit(*id);
+                    let group = state.groups.get(id).cloned();
+                    let assign_groups = state.session_group.is_none();
+                    let freeze = window_freeze(&state, *id);
+                    let selected = self.selected.contains(id);
+                    let bypass = window_bypass(&state, *id);
+                    let adapters = (0..refs.inputs.len())
+                        .map(|index| state.adapter(InputId(*id, index)))
+                        .collect::<Vec<_>>();
 
                     if let (Some(module), Some(geometry)) = (module, geometry) {
-                        let name = format!("{:?}", module).chars().take_while(|c| c.is_alphanumeric()).collect::<String>();
+                        let name = i18n::module_name(module);
                         html! { <Window
                             id={id}
                             module={module}
@@ -377,6 +669,14 @@ impl Component for Workspace {
                             workspace={workspace}
                             geometry={geometry}
                             indication={indication.cloned()}
+                            safety={safety}
+                            editable={editable}
+                            group={group}
+                            assign_groups={assign_groups}
+                            freeze={freeze}
+                            selected={selected}
+                            bypass={bypass}
+                            adapters={adapters}
                             session={self.props.session.clone()}
                         /> }
                     } else {
@@ -407,39 +707,59 @@ impl Workspace {
         let state = self.props.state.borrow();
 
         for id in state.modules.keys() {
-            if deleted_windows.remove(id) {
-                // cool, nothing changes with this module
-            } else {
-                // this module was not present before, create a window ref for it
-                let inputs = state.inputs.get(id);
-                let outputs = state.outputs.get(id);
-
-                if let (Some(inputs), Some(outputs)) = (inputs, outputs) {
-                    let refs = WindowRef {
-                        module: NodeRef::default(),
-                        inputs: make_terminal_refs(inputs, TerminalType::Input),
-                        outputs: make_terminal_refs(outputs, TerminalType::Output),
-                    };
+            deleted_windows.remove(id);
+
+            let inputs = state.inputs.get(id);
+            let outputs = state.outputs.get(id);
+
+            if let (Some(inputs), Some(outputs)) = (inputs, outputs) {
+                match self.window_refs.get_mut(id) {
+                    Some(refs) => {
+                        // terminals can come and go as a module's params
+                        // change, such as a mixer gaining channels
+                        if !same_terminals(&refs.inputs, inputs) {
+                            refs.inputs = make_terminal_refs(inputs, TerminalType::Input);
+                        }
 
-                    self.window_refs.insert(*id, refs);
-
-                    fn make_terminal_refs(terminals: &[mixlab_protocol::Terminal], terminal_type: TerminalType) -> Vec<TerminalRef> {
-                        terminals.iter()
-                            .cloned()
-                            .map(|terminal| TerminalRef {
-                                node: NodeRef::default(),
-                                label: terminal.label().map(String::from),
-                                line_type: terminal.line_type(),
-                                terminal_type,
-                            })
-                            .collect()
+                        if !same_terminals(&refs.outputs, outputs) {
+                            refs.outputs = make_terminal_refs(outputs, TerminalType::Output);
+                        }
+                    }
+                    None => {
+                        // this module was not present before, create a window ref for it
+                        let refs = WindowRef {
+                            module: NodeRef::default(),
+                            inputs: make_terminal_refs(inputs, TerminalType::Input),
+                            outputs: make_terminal_refs(outputs, TerminalType::Output),
+                        };
+
+                        self.window_refs.insert(*id, refs);
                     }
                 }
             }
         }
 
+        fn make_terminal_refs(terminals: &[mixlab_protocol::Terminal], terminal_type: TerminalType) -> Vec<TerminalRef> {
+            terminals.iter()
+                .cloned()
+                .map(|terminal| TerminalRef {
+                    node: NodeRef::default(),
+                    label: terminal.label().map(String::from),
+                    line_type: terminal.line_type(),
+                    terminal_type,
+                })
+                .collect()
+        }
+
+        fn same_terminals(refs: &[TerminalRef], terminals: &[mixlab_protocol::Terminal]) -> bool {
+            refs.len() == terminals.len() && refs.iter().zip(terminals).all(|(terminal_ref, terminal)| {
+                terminal_ref.label.as_deref() == terminal.label() && terminal_ref.line_type == terminal.line_type()
+            })
+        }
+
         for deleted_window in deleted_windows {
             self.window_refs.remove(&deleted_window);
+            self.selected.remove(&deleted_window);
         }
     }
 
@@ -465,33 +785,97 @@ impl Workspace {
             _ => return html! {},
         };
 
-        let items = &[
-            ("Oscillator", ModuleParams::Oscillator(OscillatorParams { freq: 100.0, waveform: Waveform::Sine })),
-            ("Mixer (2 channel)", ModuleParams::Mixer(MixerParams::with_channels(2))),
-            ("Mixer (4 channel)", ModuleParams::Mixer(MixerParams::with_channels(4))),
-            ("Mixer (8 channel)", ModuleParams::Mixer(MixerParams::with_channels(8))),
-            ("Output Device", ModuleParams::OutputDevice(OutputDeviceParams { device: None, left: None, right: None })),
-            ("Plotter", ModuleParams::Plotter(())),
-            ("FM Sine", ModuleParams::FmSine(FmSineParams { freq_lo: 90.0, freq_hi: 110.0 })),
-            ("Amplifier", ModuleParams::Amplifier(AmplifierParams { amplitude: 1.0, mod_depth: 0.5 })),
-            ("Trigger", ModuleParams::Trigger(GateState::Closed)),
-            ("Envelope", ModuleParams::Envelope(EnvelopeParams::default())),
-            ("Stereo Panner", ModuleParams::StereoPanner(())),
-            ("Stereo Splitter", ModuleParams::StereoSplitter(())),
-            ("Stream Input", ModuleParams::StreamInput(StreamInputParams::default())),
-            ("Stream Output", ModuleParams::StreamOutput(StreamOutputParams::default())),
-            ("EQ Three", ModuleParams::EqThree(EqThreeParams::default())),
-            ("Monitor", ModuleParams::Monitor(())),
-            ("Video Mixer", ModuleParams::VideoMixer(VideoMixerParams::default())),
-            ("Media Source", ModuleParams::MediaSource(MediaSourceParams::default())),
+        let items = vec![
+            (t("module.Oscillator"), ModuleParams::Oscillator(OscillatorParams::default())),
+            (tf("menu.mixer", &[("channels", &2)]), ModuleParams::Mixer(MixerParams::with_channels(2))),
+            (tf("menu.mixer", &[("channels", &4)]), ModuleParams::Mixer(MixerParams::with_channels(4))),
+            (tf("menu.mixer", &[("channels", &8)]), ModuleParams::Mixer(MixerParams::with_channels(8))),
+            (t("module.InputDevice"), ModuleParams::InputDevice(InputDeviceParams::default())),
+            (t("module.OutputDevice"), ModuleParams::OutputDevice(OutputDeviceParams { device: None, left: None, right: None })),
+            (t("module.Plotter"), ModuleParams::Plotter(())),
+            (t("module.Meter"), ModuleParams::Meter(())),
+            (t("module.SpectrumAnalyzer"), ModuleParams::SpectrumAnalyzer(SpectrumAnalyzerParams::default())),
+            (t("module.Goniometer"), ModuleParams::Goniometer(())),
+            (t("module.Tuner"), ModuleParams::Tuner(TunerParams::default())),
+            (t("module.FmSine"), ModuleParams::FmSine(FmSineParams { freq_lo: 90.0, freq_hi: 110.0 })),
+            (t("module.Amplifier"), ModuleParams::Amplifier(AmplifierParams { amplitude: 1.0, mod_depth: 0.5 })),
+            (t("module.Lfo"), ModuleParams::Lfo(LfoParams::default())),
+            (t("module.Trigger"), ModuleParams::Trigger(GateState::Closed)),
+            (t("module.Envelope"), ModuleParams::Envelope(EnvelopeParams::default())),
+            (t("module.StereoPanner"), ModuleParams::StereoPanner(())),
+            (t("module.StereoSplitter"), ModuleParams::StereoSplitter(())),
+            (t("module.AmbisonicEncoder"), ModuleParams::AmbisonicEncoder(AmbisonicEncoderParams::default())),
+            (t("module.BinauralDecoder"), ModuleParams::BinauralDecoder(BinauralDecoderParams::default())),
+            (t("module.StreamInput"), ModuleParams::StreamInput(StreamInputParams::default())),
+            (t("module.StreamOutput"), ModuleParams::StreamOutput(StreamOutputParams::default())),
+            (t("module.IcecastOutput"), ModuleParams::IcecastOutput(IcecastOutputParams::default())),
+            (t("module.HlsOutput"), ModuleParams::HlsOutput(HlsOutputParams::default())),
+            (t("module.FileOutput"), ModuleParams::FileOutput(FileOutputParams::default())),
+            (t("module.EqThree"), ModuleParams::EqThree(EqThreeParams::default())),
+            (t("module.NoiseGate"), ModuleParams::NoiseGate(NoiseGateParams::default())),
+            (t("module.Compressor"), ModuleParams::Compressor(CompressorParams::default())),
+            (t("module.Limiter"), ModuleParams::Limiter(LimiterParams::default())),
+            (t("module.Crossover"), ModuleParams::Crossover(CrossoverParams::default())),
+            (t("module.Monitor"), ModuleParams::Monitor(())),
+            (t("module.SyncCheck"), ModuleParams::SyncCheck(())),
+            (t("module.VideoMixer"), ModuleParams::VideoMixer(VideoMixerParams::default())),
+            (t("module.MediaSource"), ModuleParams::MediaSource(MediaSourceParams::default())),
+            (t("module.Recorder"), ModuleParams::Recorder(RecorderParams::default())),
+            (t("module.SilenceDetector"), ModuleParams::SilenceDetector(SilenceDetectorParams::default())),
+            (t("module.Slate"), ModuleParams::Slate(SlateParams::default())),
+            (t("module.Talkback"), ModuleParams::Talkback(GateState::Closed)),
+            (t("module.TextOverlay"), ModuleParams::TextOverlay(TextOverlayParams::default())),
+            (t("module.ImageOverlay"), ModuleParams::ImageOverlay(ImageOverlayParams::default())),
+            (t("module.Compositor"), ModuleParams::Compositor(CompositorParams::default())),
+            (t("module.ChromaKey"), ModuleParams::ChromaKey(ChromaKeyParams::default())),
+            (t("module.Filter"), ModuleParams::Filter(FilterParams::default())),
+            (t("module.Waveshaper"), ModuleParams::Waveshaper(WaveshaperParams::default())),
+            (t("module.Delay"), ModuleParams::Delay(DelayParams::default())),
+            (t("module.Reverb"), ModuleParams::Reverb(ReverbParams::default())),
+            (t("module.PitchShift"), ModuleParams::PitchShift(PitchShiftParams::default())),
+            (t("module.Looper"), ModuleParams::Looper(LooperParams::default())),
+            (t("module.ProfanityDelay"), ModuleParams::ProfanityDelay(ProfanityDelayParams::default())),
+            (t("module.EchoCanceller"), ModuleParams::EchoCanceller(EchoCancellerParams::default())),
+            (t("module.FeedbackSuppressor"), ModuleParams::FeedbackSuppressor(FeedbackSuppressorParams::default())),
+            (t("module.Sampler"), ModuleParams::Sampler(SamplerParams::default())),
+            (t("module.Sequencer"), ModuleParams::Sequencer(SequencerParams::default())),
+            (t("module.Clock"), ModuleParams::Clock(ClockParams::default())),
+            (t("menu.midi-note-mono"), ModuleParams::MidiNote(MidiNoteParams::with_voices(1))),
+            (tf("menu.midi-note", &[("voices", &4)]), ModuleParams::MidiNote(MidiNoteParams::with_voices(4))),
+            (tf("menu.midi-note", &[("voices", &8)]), ModuleParams::MidiNote(MidiNoteParams::with_voices(8))),
+            // `cargo xtask new-module` adds menu items above this line
+        ];
+
+        let templates = vec![
+            (tf("menu.podcast", &[("mics", &2)]), Template::Podcast { mics: 2 }),
+            (tf("menu.podcast", &[("mics", &4)]), Template::Podcast { mics: 4 }),
         ];
 
+        // grouping deletes the selected modules, and saved macros belong to
+        // the whole project, so both are for sessions without a group only
+        let manage_macros = self.props.state.borrow().session_group.is_none();
+
+        let macros = self.macros.iter()
+            .flat_map(|macros| macros.macros.iter())
+            .filter(|_| manage_macros);
+
         html! {
             <div class="context-menu"
                 style={format!("left:{}px; top:{}px;", coords.x, coords.y)}
                 onmousedown={stop_propagation()}
             >
-                <div class="context-menu-heading">{"Add module"}</div>
+                { if manage_macros && !self.selected.is_empty() {
+                    html! {
+                        <div class="context-menu-item"
+                            onmousedown={self.link.callback(|_| WorkspaceMsg::CreateMacro)}
+                        >
+                            {tf("menu.group-selected", &[("count", &self.selected.len())])}
+                        </div>
+                    }
+                } else {
+                    html! {}
+                } }
+                <div class="context-menu-heading">{t("menu.add-module")}</div>
                 { for items.iter().map(|(label, params)| {
                     let params = params.clone();
 
@@ -504,6 +888,45 @@ impl Workspace {
                         </div>
                     }
                 }) }
+                <div class="context-menu-heading">{t("menu.add-template")}</div>
+                { for templates.iter().map(|(label, template)| {
+                    let template = template.clone();
+
+                    html! {
+                        <div class="context-menu-item"
+                            onmousedown={self.link.callback(move |_|
+                                WorkspaceMsg::CreateTemplate(template.clone(), coords))}
+                        >
+                            {label}
+                        </div>
+                    }
+                }) }
+                { if manage_macros && self.macros.iter().any(|macros| !macros.macros.is_empty()) {
+                    html! { <div class="context-menu-heading">{t("menu.add-macro")}</div> }
+                } else {
+                    html! {}
+                } }
+                { for macros.map(|params| {
+                    let name = params.name.clone();
+                    let params = ModuleParams::Macro(params.clone());
+
+                    html! {
+                        <div class="context-menu-item context-menu-macro"
+                            onmousedown={self.link.callback(move |_|
+                                WorkspaceMsg::CreateModule(params.clone(), coords))}
+                        >
+                            {&name}
+                            <span class="context-menu-macro-delete"
+                                onmousedown={self.link.callback(move |ev: MouseEvent| {
+                                    ev.stop_propagation();
+                                    WorkspaceMsg::DeleteMacro(name.clone())
+                                })}
+                            >
+                                {"×"}
+                            </span>
+                        </div>
+                    }
+                }) }
             </div>
         }
     }
@@ -520,9 +943,72 @@ pub enum WindowMsg {
     TerminalMouseDown(MouseEvent, TerminalId, TerminalRef),
     Delete,
     UpdateParams(ModuleParams),
+    Command(ModuleCommand),
+    UpdateSafety(ModuleSafety),
+    AssignGroup,
+    Freeze(OutputId),
+    Thaw(OutputId),
+    Bypass(bool),
     SetMidiMode(MidiUiMode),
 }
 
+/// How a window takes part in freezing, see `WorkspaceOp::FreezeOutput`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WindowFreeze {
+    /// Not part of any freeze, with the output that could be frozen if the
+    /// module has a stereo output and the session may freeze
+    Unfrozen(Option<OutputId>),
+    Rendering(OutputId),
+    /// Part of the chain behind a frozen output
+    Frozen(OutputId),
+    /// The Media Source playing the render of a frozen output
+    StandIn(OutputId),
+}
+
+fn window_freeze(state: &WorkspaceState, module: ModuleId) -> WindowFreeze {
+    for freeze in &state.freezes {
+        match &freeze.state {
+            FreezeState::Rendering { .. } if freeze.output.module_id() == module => {
+                return WindowFreeze::Rendering(freeze.output);
+            }
+            FreezeState::Frozen { source, .. } if *source == module => {
+                return WindowFreeze::StandIn(freeze.output);
+            }
+            FreezeState::Frozen { modules, .. } if modules.contains(&module) => {
+                return WindowFreeze::Frozen(freeze.output);
+            }
+            _ => {}
+        }
+    }
+
+    // freezing reaches upstream into modules of any group
+    if state.session_group.is_some() {
+        return WindowFreeze::Unfrozen(None);
+    }
+
+    let output = state.outputs.get(&module)
+        .and_then(|outputs| outputs.iter().position(|output| output.line_type() == LineType::Stereo))
+        .map(|index| OutputId(module, index));
+
+    WindowFreeze::Unfrozen(output)
+}
+
+// whether a window is bypassed, or `None` if bypassing it would only
+// silence it as no output has an input of the same type to take
+fn window_bypass(state: &WorkspaceState, module: ModuleId) -> Option<bool> {
+    let inputs = state.inputs.get(&module)?;
+    let outputs = state.outputs.get(&module)?;
+
+    let passes_through = outputs.iter()
+        .any(|output| inputs.iter().any(|input| input.line_type() == output.line_type()));
+
+    if passes_through {
+        Some(state.bypassed.contains(&module))
+    } else {
+        None
+    }
+}
+
 #[derive(Properties, Clone, Debug)]
 pub struct WindowProps {
     pub id: ModuleId,
@@ -532,6 +1018,15 @@ pub struct WindowProps {
     pub workspace: ComponentLink<Workspace>,
     pub refs: WindowRef,
     pub indication: Option<Indication>,
+    pub safety: ModuleSafety,
+    pub editable: bool,
+    pub group: Option<String>,
+    pub assign_groups: bool,
+    pub freeze: WindowFreeze,
+    pub selected: bool,
+    pub bypass: Option<bool>,
+    // for each input, the adapter its connection goes through if any
+    pub adapters: Vec<Option<ChannelAdapter>>,
     pub session: SessionRef,
 }
 
@@ -580,8 +1075,13 @@ impl Component for Window {
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             WindowMsg::DragStart(ev) => {
-                self.props.workspace.send_message(
-                    WorkspaceMsg::DragStart(self.props.id, ev));
+                if ev.shift_key() {
+                    self.props.workspace.send_message(
+                        WorkspaceMsg::ToggleSelected(self.props.id));
+                } else {
+                    self.props.workspace.send_message(
+                        WorkspaceMsg::DragStart(self.props.id, ev));
+                }
 
                 false
             }
@@ -590,6 +1090,8 @@ impl Component for Window {
                     if (ev.buttons() & 2) != 0 {
                         // right click
                         WorkspaceMsg::ClearTerminal(terminal_id)
+                    } else if let (true, TerminalId::Input(input)) = (ev.alt_key(), terminal_id) {
+                        WorkspaceMsg::CycleAdapter(input)
                     } else {
                         WorkspaceMsg::SelectTerminal(terminal_id, terminal_ref)
                     };
@@ -610,6 +1112,63 @@ impl Component for Window {
 
                 false
             }
+            WindowMsg::Command(command) => {
+                self.props.workspace.send_message(
+                    WorkspaceMsg::SendModuleCommand(self.props.id, command));
+
+                false
+            }
+            WindowMsg::UpdateSafety(safety) => {
+                self.props.workspace.send_message(
+                    WorkspaceMsg::UpdateModuleSafety(self.props.id, safety));
+
+                false
+            }
+            WindowMsg::AssignGroup => {
+                let current = self.props.group.clone().unwrap_or_default();
+
+                let group = web_sys::window().unwrap()
+                    .prompt_with_message_and_default(&t("window.group-prompt"), &current)
+                    .ok()
+                    .flatten();
+
+                // prompt returns None if cancelled
+                if let Some(group) = group {
+                    let group = Some(group.trim().to_owned()).filter(|group| !group.is_empty());
+
+                    self.props.workspace.send_message(
+                        WorkspaceMsg::UpdateModuleGroup(self.props.id, group));
+                }
+
+                false
+            }
+            WindowMsg::Freeze(output) => {
+                let length = web_sys::window().unwrap()
+                    .prompt_with_message_and_default(&t("window.freeze-prompt"), "30")
+                    .ok()
+                    .flatten()
+                    .and_then(|length| length.trim().parse::<f64>().ok());
+
+                // prompt returns None if cancelled
+                if let Some(length) = length {
+                    self.props.workspace.send_message(
+                        WorkspaceMsg::FreezeOutput(output, length));
+                }
+
+                false
+            }
+            WindowMsg::Thaw(output) => {
+                self.props.workspace.send_message(
+                    WorkspaceMsg::ThawOutput(output));
+
+                false
+            }
+            WindowMsg::Bypass(bypassed) => {
+                self.props.workspace.send_message(
+                    WorkspaceMsg::UpdateModuleBypass(self.props.id, bypassed));
+
+                false
+            }
             WindowMsg::SetMidiMode(new_midi_mode) => {
                 self.midi_mode = new_midi_mode;
                 true
@@ -628,8 +1187,32 @@ impl Component for Window {
             self.props.geometry.position.y,
             self.props.geometry.z_index);
 
+        let delete_class = if self.props.safety.is_locked() {
+            "module-window-title-button module-window-title-delete module-window-title-delete-locked"
+        } else {
+            "module-window-title-button module-window-title-delete"
+        };
+
+        let mut window_class = if self.props.editable {
+            "module-window".to_owned()
+        } else {
+            "module-window module-window-readonly".to_owned()
+        };
+
+        if let WindowFreeze::Frozen(_) = self.props.freeze {
+            window_class.push_str(" module-window-frozen");
+        }
+
+        if self.props.selected {
+            window_class.push_str(" module-window-selected");
+        }
+
+        if self.props.bypass == Some(true) {
+            window_class.push_str(" module-window-bypassed");
+        }
+
         html! {
-            <div class="module-window"
+            <div class={window_class}
                 style={window_style}
                 ref={self.props.refs.module.clone()}
                 onmousedown={stop_propagation()}
@@ -643,7 +1226,11 @@ impl Component for Window {
                         {&self.props.name}
                     </div>
                     {self.view_custom_title_buttons()}
-                    <div class="module-window-title-button module-window-title-delete" onmousedown={self.link.callback(|_| WindowMsg::Delete)}>
+                    {self.view_bypass_title_button()}
+                    {self.view_freeze_title_button()}
+                    {self.view_group_title_button()}
+                    {self.view_safety_title_buttons()}
+                    <div class={delete_class} onmousedown={self.link.callback(|_| WindowMsg::Delete)}>
                         {"×"}
                     </div>
                 </div>
@@ -682,20 +1269,130 @@ impl Window {
 
                 html! {
                     <div class={class} onmousedown={self.link.callback(move |_| WindowMsg::SetMidiMode(new_midi_mode))}>
-                        {"MIDI"}
+                        {t("common.midi")}
+                    </div>
+                }
+            }
+            _ => html! {},
+        }
+    }
+
+    fn view_bypass_title_button(&self) -> Html {
+        match self.props.bypass {
+            Some(bypassed) if self.props.editable => {
+                let class = if bypassed {
+                    "module-window-title-button module-window-title-bypass-btn module-window-title-bypass-btn-active"
+                } else {
+                    "module-window-title-button module-window-title-bypass-btn"
+                };
+
+                html! {
+                    <div class={class} onmousedown={self.link.callback(move |_| WindowMsg::Bypass(!bypassed))}>
+                        {t("window.bypass")}
                     </div>
                 }
             }
             _ => html! {},
         }
     }
+
+    fn view_freeze_title_button(&self) -> Html {
+        match self.props.freeze {
+            WindowFreeze::Unfrozen(None) => html! {},
+            WindowFreeze::Unfrozen(Some(output)) => html! {
+                <div class="module-window-title-button module-window-title-freeze-btn"
+                    onmousedown={self.link.callback(move |_| WindowMsg::Freeze(output))}
+                >
+                    {t("window.freeze")}
+                </div>
+            },
+            WindowFreeze::Rendering(_) => html! {
+                <div class="module-window-title-freeze-label">{t("window.rendering")}</div>
+            },
+            WindowFreeze::Frozen(output) |
+            WindowFreeze::StandIn(output) => html! {
+                <div class="module-window-title-button module-window-title-freeze-btn module-window-title-freeze-btn-active"
+                    onmousedown={self.link.callback(move |_| WindowMsg::Thaw(output))}
+                >
+                    {t("window.thaw")}
+                </div>
+            },
+        }
+    }
+
+    fn view_group_title_button(&self) -> Html {
+        let label = self.props.group.clone().unwrap_or_else(|| t("window.group"));
+
+        if self.props.assign_groups {
+            html! {
+                <div class="module-window-title-button module-window-title-group-btn"
+                    onmousedown={self.link.callback(|_| WindowMsg::AssignGroup)}
+                >
+                    {label}
+                </div>
+            }
+        } else if self.props.group.is_some() {
+            html! {
+                <div class="module-window-title-group-label">{label}</div>
+            }
+        } else {
+            html! {}
+        }
+    }
+
+    fn view_safety_title_buttons(&self) -> Html {
+        // protection can only be removed once the module has been armed,
+        // mirroring the rules enforced by the engine
+        let (safe_class, safe_toggle) = match self.props.safety {
+            ModuleSafety::Unprotected =>
+                ("module-window-title-button module-window-title-safety-btn", Some(ModuleSafety::Safe)),
+            ModuleSafety::Safe =>
+                ("module-window-title-button module-window-title-safety-btn module-window-title-safety-btn-active", None),
+            ModuleSafety::Armed =>
+                ("module-window-title-button module-window-title-safety-btn module-window-title-safety-btn-active", Some(ModuleSafety::Unprotected)),
+        };
+
+        let arm_button = match self.props.safety {
+            ModuleSafety::Unprotected => html! {},
+            ModuleSafety::Safe => html! {
+                <div class="module-window-title-button module-window-title-safety-btn"
+                    onmousedown={self.link.callback(|_| WindowMsg::UpdateSafety(ModuleSafety::Armed))}
+                >
+                    {t("safety.arm")}
+                </div>
+            },
+            ModuleSafety::Armed => html! {
+                <div class="module-window-title-button module-window-title-safety-btn module-window-title-armed-btn"
+                    onmousedown={self.link.callback(|_| WindowMsg::UpdateSafety(ModuleSafety::Safe))}
+                >
+                    {t("safety.armed")}
+                </div>
+            },
+        };
+
+        html! {
+            <>
+                <div class={safe_class}
+                    onmousedown={self.link.batch_callback(move |_| {
+                        safe_toggle.map(WindowMsg::UpdateSafety).into_iter().collect::<Vec<_>>()
+                    })}
+                >
+                    {t("safety.safe")}
+                </div>
+                {arm_button}
+            </>
+        }
+    }
+
     fn view_inputs(&self) -> Html {
         self.view_terminals(
             self.props.refs.inputs.iter()
                 .cloned()
                 .enumerate()
-                .map(|(index, terminal_ref)|
-                    (TerminalId::Input(InputId(self.props.id, index)), terminal_ref)))
+                .map(|(index, terminal_ref)| {
+                    let adapter = self.props.adapters.get(index).copied().flatten();
+                    (TerminalId::Input(InputId(self.props.id, index)), terminal_ref, adapter)
+                }))
     }
 
     fn view_outputs(&self) -> Html {
@@ -704,15 +1401,16 @@ impl Window {
                 .cloned()
                 .enumerate()
                 .map(|(index, terminal_ref)|
-                    (TerminalId::Output(OutputId(self.props.id, index)), terminal_ref)))
+                    (TerminalId::Output(OutputId(self.props.id, index)), terminal_ref, None)))
     }
 
-    fn view_terminals(&self, terminals: impl Iterator<Item = (TerminalId, TerminalRef)>) -> Html {
+    fn view_terminals(&self, terminals: impl Iterator<Item = (TerminalId, TerminalRef, Option<ChannelAdapter>)>) -> Html {
         html! {
-            { for terminals.map(|(terminal_id, terminal_ref)| {
+            { for terminals.map(|(terminal_id, terminal_ref, adapter)| {
                 html! {
                     <Terminal
                         terminal={terminal_ref.clone()}
+                        adapter={adapter}
                         onmousedown={self.link.callback({
                             let terminal_ref = terminal_ref.clone();
                             move |ev| WindowMsg::TerminalMouseDown(ev, terminal_id, terminal_ref.clone())
@@ -732,6 +1430,13 @@ impl Window {
             ModuleParams::StereoSplitter(()) => {
                 html! {}
             }
+            ModuleParams::InputDevice(params) => {
+                if let Some(Indication::InputDevice(indication)) = &self.props.indication {
+                    html! { <InputDevice id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
             ModuleParams::OutputDevice(params) => {
                 if let Some(Indication::OutputDevice(indication)) = &self.props.indication {
                     html! { <OutputDevice id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
@@ -746,23 +1451,128 @@ impl Window {
                     unreachable!()
                 }
             }
+            ModuleParams::Meter(()) => {
+                if let Some(Indication::Meter(indication)) = &self.props.indication {
+                    html! { <Meter id={self.props.id} module={self.link.clone()} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::SpectrumAnalyzer(params) => {
+                if let Some(Indication::SpectrumAnalyzer(indication)) = &self.props.indication {
+                    html! { <SpectrumAnalyzer id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::Goniometer(()) => {
+                if let Some(Indication::Goniometer(indication)) = &self.props.indication {
+                    html! { <Goniometer id={self.props.id} module={self.link.clone()} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::Tuner(params) => {
+                if let Some(Indication::Tuner(indication)) = &self.props.indication {
+                    html! { <Tuner id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::Recorder(params) => {
+                if let Some(Indication::Recorder(indication)) = &self.props.indication {
+                    html! { <Recorder id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
             ModuleParams::FmSine(params) => {
                 html! { <FmSine id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
             }
+            ModuleParams::Clock(params) => {
+                html! { <Clock id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::Crossover(params) => {
+                html! { <Crossover id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::Lfo(params) => {
+                html! { <Lfo id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::Looper(params) => {
+                if let Some(Indication::Looper(indication)) = &self.props.indication {
+                    html! { <Looper id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::Macro(params) => {
+                html! { <Macro id={self.props.id} module={self.link.clone()} params={params} session={self.props.session.clone()} save={self.props.assign_groups} /> }
+            }
+            ModuleParams::EchoCanceller(params) => {
+                if let Some(Indication::EchoCanceller(indication)) = &self.props.indication {
+                    html! { <EchoCanceller id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::ProfanityDelay(params) => {
+                if let Some(Indication::ProfanityDelay(indication)) = &self.props.indication {
+                    html! { <ProfanityDelay id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
             ModuleParams::Amplifier(params) => {
                 html! { <Amplifier id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
             }
+            ModuleParams::AmbisonicEncoder(params) => {
+                html! { <AmbisonicEncoder id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::BinauralDecoder(params) => {
+                html! { <BinauralDecoder id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
             ModuleParams::Trigger(params) => {
                 html! { <Trigger id={self.props.id} module={self.link.clone()} params={params} /> }
             }
+            ModuleParams::Talkback(params) => {
+                html! { <Talkback id={self.props.id} module={self.link.clone()} params={params} /> }
+            }
+            ModuleParams::TextOverlay(params) => {
+                html! { <TextOverlay id={self.props.id} module={self.link.clone()} params={params} /> }
+            }
+            ModuleParams::SilenceDetector(params) => {
+                if let Some(Indication::SilenceDetector(indication)) = &self.props.indication {
+                    html! { <SilenceDetector id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::Slate(params) => {
+                if let Some(Indication::Slate(indication)) = &self.props.indication {
+                    html! { <Slate id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
             ModuleParams::Envelope(params) => {
                 html! { <Envelope id={self.props.id} module={self.link.clone()} params={params} /> }
             }
+            ModuleParams::MidiNote(params) => {
+                html! { <MidiNote id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
             ModuleParams::Mixer(params) => {
-                html! { <Mixer id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+                if let Some(Indication::Mixer(indication)) = &self.props.indication {
+                    html! { <Mixer id={self.props.id} module={self.link.clone()} params={params} indication={indication} midi_mode={self.midi_mode} /> }
+                } else {
+                    unreachable!()
+                }
             }
             ModuleParams::StreamInput(params) => {
-                html! { <StreamInput id={self.props.id} module={self.link.clone()} params={params} /> }
+                if let Some(Indication::StreamInput(indication)) = &self.props.indication {
+                    html! { <StreamInput id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
             }
             ModuleParams::StreamOutput(params) => {
                 if let Some(Indication::StreamOutput(indication)) = &self.props.indication {
@@ -771,12 +1581,63 @@ impl Window {
                     unreachable!()
                 }
             }
+            ModuleParams::IcecastOutput(params) => {
+                if let Some(Indication::IcecastOutput(indication)) = &self.props.indication {
+                    html! { <IcecastOutput id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::ImageOverlay(params) => {
+                html! { <ImageOverlay id={self.props.id} module={self.link.clone()} params={params} session={self.props.session.clone()} /> }
+            }
+            ModuleParams::Compositor(params) => {
+                html! { <Compositor id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::ChromaKey(params) => {
+                html! { <ChromaKey id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::FeedbackSuppressor(params) => {
+                if let Some(Indication::FeedbackSuppressor(indication)) = &self.props.indication {
+                    html! { <FeedbackSuppressor id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::FileOutput(params) => {
+                if let Some(Indication::FileOutput(indication)) = &self.props.indication {
+                    html! { <FileOutput id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::HlsOutput(params) => {
+                if let Some(Indication::HlsOutput(indication)) = &self.props.indication {
+                    html! { <HlsOutput id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
             ModuleParams::EqThree(params) => {
                 html! { <EqThree id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
             }
+            ModuleParams::NoiseGate(params) => {
+                if let Some(Indication::NoiseGate(indication)) = &self.props.indication {
+                    html! { <NoiseGate id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
             ModuleParams::Monitor(()) => {
                 if let Some(Indication::Monitor(indication)) = &self.props.indication {
-                    html! { <Monitor id={self.props.id} indication={indication} /> }
+                    html! { <Monitor socket_url={monitor::socket_url(indication)} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::SyncCheck(()) => {
+                if let Some(Indication::SyncCheck(indication)) = &self.props.indication {
+                    html! { <SyncCheck id={self.props.id} indication={indication} /> }
                 } else {
                     unreachable!()
                 }
@@ -784,9 +1645,45 @@ impl Window {
             ModuleParams::VideoMixer(params) => {
                 html! { <VideoMixer id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
             }
+            ModuleParams::Waveshaper(params) => {
+                html! { <Waveshaper id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::Filter(params) => {
+                html! { <Filter id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::Delay(params) => {
+                html! { <Delay id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::PitchShift(params) => {
+                html! { <PitchShift id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::Reverb(params) => {
+                html! { <Reverb id={self.props.id} module={self.link.clone()} params={params} session={self.props.session.clone()} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::Sampler(params) => {
+                html! { <Sampler id={self.props.id} module={self.link.clone()} params={params} session={self.props.session.clone()} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::Sequencer(params) => {
+                if let Some(Indication::Sequencer(indication)) = &self.props.indication {
+                    html! { <Sequencer id={self.props.id} module={self.link.clone()} params={params} indication={indication} /> }
+                } else {
+                    unreachable!()
+                }
+            }
             ModuleParams::MediaSource(params) => {
-                html! { <MediaSource id={self.props.id} module={self.link.clone()} params={params} session={self.props.session.clone()} /> }
+                if let Some(Indication::MediaSource(indication)) = &self.props.indication {
+                    html! { <MediaSource id={self.props.id} module={self.link.clone()} params={params} indication={indication} session={self.props.session.clone()} /> }
+                } else {
+                    unreachable!()
+                }
+            }
+            ModuleParams::Compressor(params) => {
+                html! { <Compressor id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+            ModuleParams::Limiter(params) => {
+                html! { <Limiter id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
             }
+            // `cargo xtask new-module` adds module views above this line
         }
     }
 }
@@ -800,6 +1697,8 @@ pub struct Terminal {
 #[derive(Properties, Clone, Debug)]
 pub struct TerminalProps {
     terminal: TerminalRef,
+    #[prop_or_default]
+    adapter: Option<ChannelAdapter>,
     onmousedown: Callback<MouseEvent>,
 }
 
@@ -839,6 +1738,8 @@ impl Component for Terminal {
                     {format!("{}", &self.props.terminal.label.as_ref().unwrap_or(&"".to_string()))}
                 </div>
 
+                {self.view_adapter()}
+
                 <svg width="16" height="16">
                     { match self.props.terminal.line_type {
                         LineType::Mono => html! {},
@@ -847,7 +1748,13 @@ impl Component for Terminal {
                         },
                         LineType::Video => html! {
                             <rect width="16" height="16" fill={ if self.hover { "#fef8e1" } else { "#fdf1bf" } } />
-                        }
+                        },
+                        LineType::Control => html! {
+                            <circle cx="8" cy="8" r="5" fill={ if self.hover { "#c9e4f6" } else { "#a9cfea" } } />
+                        },
+                        LineType::Ambisonic => html! {
+                            <polygon points="8,1 15,8 8,15 1,8" fill={ if self.hover { "#d4ecc8" } else { "#b8dca6" } } />
+                        },
                     } }
                 </svg>
             </div>
@@ -855,14 +1762,39 @@ impl Component for Terminal {
     }
 }
 
+impl Terminal {
+    fn view_adapter(&self) -> Html {
+        let (label, channels) = match self.props.adapter {
+            None => return html! {},
+            Some(ChannelAdapter::Both) => ("adapter.both", "adapter.title-both"),
+            Some(ChannelAdapter::Left) => ("adapter.left", "adapter.title-left"),
+            Some(ChannelAdapter::Right) => ("adapter.right", "adapter.title-right"),
+        };
+
+        html! {
+            <div class="terminal-adapter" title={tf("adapter.title", &[("channels", &t(channels))])}>
+                {t(label)}
+            </div>
+        }
+    }
+}
+
 pub struct Connections {
     canvas: NodeRef,
     props: ConnectionsProps,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Cable {
+    from: Coords,
+    to: Coords,
+    // converts between mono and stereo, drawn dashed
+    adapted: bool,
+}
+
 #[derive(Properties, Clone, PartialEq, Eq)]
 pub struct ConnectionsProps {
-    connections: Vec<(Coords, Coords)>,
+    connections: Vec<Cable>,
 }
 
 impl Component for Connections {
@@ -912,12 +1844,12 @@ impl Component for Connections {
 
             // plan multi-segment lines for all connections
             let lines = self.props.connections.iter()
-                .map(|(a, b)| plan_line_points(*a, *b))
+                .map(|cable| (plan_line_points(cable.from, cable.to), cable.adapted))
                 .collect::<Vec<_>>();
 
             // calculate required canvas size for all points
             let Coords { x: width, y: height } = lines.iter()
-                .flat_map(|segments| segments)
+                .flat_map(|(segments, _)| segments)
                 .fold(Coords { x: 0, y: 0 }, |area, point| {
                     Coords {
                         x: max(area.x, point.x),
@@ -931,7 +1863,12 @@ impl Component for Connections {
             // draw lines
             ctx.clear_rect(0f64, 0f64, width as f64, height as f64);
 
-            for points in lines {
+            let solid = js_sys::Array::new();
+            let dashed = js_sys::Array::of2(&6.into(), &4.into());
+
+            for (points, adapted) in lines {
+                let _ = ctx.set_line_dash(if adapted { &dashed } else { &solid });
+
                 ctx.begin_path();
 
                 ctx.move_to(points[0].x as f64, points[0].y as f64);
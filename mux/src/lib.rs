@@ -1 +1,3 @@
+pub mod mkv;
 pub mod mp4;
+pub mod ts;
@@ -0,0 +1,273 @@
+use std::borrow::Cow;
+
+// element ids, with their length marker bits as they're written
+const EBML: u32 = 0x1a45dfa3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42f7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42f2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42f3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x18538067;
+const INFO: u32 = 0x1549a966;
+const TIMESTAMP_SCALE: u32 = 0x2ad7b1;
+const MUXING_APP: u32 = 0x4d80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654ae6b;
+const TRACK_ENTRY: u32 = 0xae;
+const TRACK_NUMBER: u32 = 0xd7;
+const TRACK_UID: u32 = 0x73c5;
+const TRACK_TYPE: u32 = 0x83;
+const FLAG_LACING: u32 = 0x9c;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63a2;
+const VIDEO: u32 = 0xe0;
+const PIXEL_WIDTH: u32 = 0xb0;
+const PIXEL_HEIGHT: u32 = 0xba;
+const AUDIO: u32 = 0xe1;
+const SAMPLING_FREQUENCY: u32 = 0xb5;
+const CHANNELS: u32 = 0x9f;
+const CLUSTER: u32 = 0x1f43b675;
+const CLUSTER_TIMESTAMP: u32 = 0xe7;
+const SIMPLE_BLOCK: u32 = 0xa3;
+
+// the segment is written without knowing how long it will be, so that a
+// file cut short by a crash still plays up to its last cluster
+const UNKNOWN_SIZE: [u8; 8] = [0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+
+// timestamps are in milliseconds
+const TIMESTAMP_SCALE_NS: u64 = 1_000_000;
+
+const VIDEO_TRACK: u64 = 1;
+const AUDIO_TRACK: u64 = 2;
+
+const TRACK_TYPE_VIDEO: u64 = 1;
+const TRACK_TYPE_AUDIO: u64 = 2;
+
+// block timestamps are 16 bit offsets from their cluster's, a cluster is
+// closed well before running out of them even without a key frame
+const MAX_CLUSTER_MS: u64 = 30_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Track {
+    // H.264 with length prefixed NAL units, as in MP4
+    Video,
+    // raw AAC, without ADTS headers
+    Audio,
+}
+
+#[derive(Debug, Clone)]
+pub struct MkvParams<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub sample_rate: u32,
+    pub channels: u32,
+    // AVC decoder configuration record
+    pub dcr: Cow<'a, [u8]>,
+    // AAC audio specific config
+    pub asc: Cow<'a, [u8]>,
+}
+
+/// Muxes H.264 video and AAC audio into a Matroska file, written straight
+/// through from start to finish. Blocks are gathered into a cluster for each
+/// video key frame, and each cluster is written out whole once the next
+/// starts.
+#[derive(Debug)]
+pub struct MkvMux {
+    cluster: Option<Cluster>,
+}
+
+#[derive(Debug)]
+struct Cluster {
+    timestamp: u64,
+    blocks: Vec<u8>,
+}
+
+impl MkvMux {
+    /// Returns the muxer along with the start of the file, which must be
+    /// written before anything else.
+    pub fn new(params: MkvParams) -> (Self, Vec<u8>) {
+        let mut out = Vec::new();
+
+        let mut header = Vec::new();
+        write_uint(&mut header, EBML_VERSION, 1);
+        write_uint(&mut header, EBML_READ_VERSION, 1);
+        write_uint(&mut header, EBML_MAX_ID_LENGTH, 4);
+        write_uint(&mut header, EBML_MAX_SIZE_LENGTH, 8);
+        write_bytes(&mut header, DOC_TYPE, b"matroska");
+        write_uint(&mut header, DOC_TYPE_VERSION, 4);
+        write_uint(&mut header, DOC_TYPE_READ_VERSION, 2);
+        write_bytes(&mut out, EBML, &header);
+
+        write_id(&mut out, SEGMENT);
+        out.extend_from_slice(&UNKNOWN_SIZE);
+
+        let mut info = Vec::new();
+        write_uint(&mut info, TIMESTAMP_SCALE, TIMESTAMP_SCALE_NS);
+        write_bytes(&mut info, MUXING_APP, b"mixlab");
+        write_bytes(&mut info, WRITING_APP, b"mixlab");
+        write_bytes(&mut out, INFO, &info);
+
+        let mut video = Vec::new();
+        write_uint(&mut video, PIXEL_WIDTH, params.width.into());
+        write_uint(&mut video, PIXEL_HEIGHT, params.height.into());
+
+        let mut video_track = Vec::new();
+        write_uint(&mut video_track, TRACK_NUMBER, VIDEO_TRACK);
+        write_uint(&mut video_track, TRACK_UID, VIDEO_TRACK);
+        write_uint(&mut video_track, TRACK_TYPE, TRACK_TYPE_VIDEO);
+        write_uint(&mut video_track, FLAG_LACING, 0);
+        write_bytes(&mut video_track, CODEC_ID, b"V_MPEG4/ISO/AVC");
+        write_bytes(&mut video_track, CODEC_PRIVATE, &params.dcr);
+        write_bytes(&mut video_track, VIDEO, &video);
+
+        let mut audio = Vec::new();
+        write_float(&mut audio, SAMPLING_FREQUENCY, params.sample_rate.into());
+        write_uint(&mut audio, CHANNELS, params.channels.into());
+
+        let mut audio_track = Vec::new();
+        write_uint(&mut audio_track, TRACK_NUMBER, AUDIO_TRACK);
+        write_uint(&mut audio_track, TRACK_UID, AUDIO_TRACK);
+        write_uint(&mut audio_track, TRACK_TYPE, TRACK_TYPE_AUDIO);
+        write_uint(&mut audio_track, FLAG_LACING, 0);
+        write_bytes(&mut audio_track, CODEC_ID, b"A_AAC");
+        write_bytes(&mut audio_track, CODEC_PRIVATE, &params.asc);
+        write_bytes(&mut audio_track, AUDIO, &audio);
+
+        let mut tracks = Vec::new();
+        write_bytes(&mut tracks, TRACK_ENTRY, &video_track);
+        write_bytes(&mut tracks, TRACK_ENTRY, &audio_track);
+        write_bytes(&mut out, TRACKS, &tracks);
+
+        (MkvMux { cluster: None }, out)
+    }
+
+    /// Adds a frame with its presentation timestamp in milliseconds. Any
+    /// cluster this frame closes is written to `out`.
+    pub fn write(&mut self, out: &mut Vec<u8>, track: Track, timestamp: u64, key_frame: bool, data: &[u8]) {
+        let new_cluster = match &self.cluster {
+            None => true,
+            Some(cluster) => {
+                (track == Track::Video && key_frame)
+                    || timestamp > cluster.timestamp + MAX_CLUSTER_MS
+            }
+        };
+
+        if new_cluster {
+            self.finish(out);
+            self.cluster = Some(Cluster { timestamp, blocks: Vec::new() });
+        }
+
+        let cluster = self.cluster.as_mut().expect("cluster");
+
+        let track_number = match track {
+            Track::Video => VIDEO_TRACK,
+            Track::Audio => AUDIO_TRACK,
+        };
+
+        // audio can land a little before the key frame its cluster starts on
+        let offset = (timestamp as i64 - cluster.timestamp as i64)
+            .max(i16::min_value().into())
+            .min(i16::max_value().into()) as i16;
+
+        // every audio frame stands alone
+        let flags = if key_frame || track == Track::Audio { 0x80 } else { 0x00 };
+
+        let mut block = Vec::with_capacity(4 + data.len());
+        write_size(&mut block, track_number);
+        block.extend_from_slice(&offset.to_be_bytes());
+        block.push(flags);
+        block.extend_from_slice(data);
+
+        write_bytes(&mut cluster.blocks, SIMPLE_BLOCK, &block);
+    }
+
+    /// Writes out the cluster in progress, to be called at the end of the
+    /// file.
+    pub fn finish(&mut self, out: &mut Vec<u8>) {
+        if let Some(cluster) = self.cluster.take() {
+            let mut body = Vec::with_capacity(cluster.blocks.len() + 10);
+            write_uint(&mut body, CLUSTER_TIMESTAMP, cluster.timestamp);
+            body.extend_from_slice(&cluster.blocks);
+            write_bytes(out, CLUSTER, &body);
+        }
+    }
+}
+
+fn write_id(out: &mut Vec<u8>, id: u32) {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count();
+    out.extend_from_slice(&bytes[skip..]);
+}
+
+// the shortest variable length integer that holds the size. all ones is
+// reserved for unknown sizes at every length
+fn write_size(out: &mut Vec<u8>, size: u64) {
+    let len = (1..8).find(|len| size < (1 << (7 * len)) - 1).unwrap_or(8);
+    let marked = size | 1 << (7 * len);
+    out.extend_from_slice(&marked.to_be_bytes()[8 - len..]);
+}
+
+fn write_bytes(out: &mut Vec<u8>, id: u32, data: &[u8]) {
+    write_id(out, id);
+    write_size(out, data.len() as u64);
+    out.extend_from_slice(data);
+}
+
+fn write_uint(out: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count().min(7);
+    write_bytes(out, id, &bytes[skip..]);
+}
+
+fn write_float(out: &mut Vec<u8>, id: u32, value: f64) {
+    write_bytes(out, id, &value.to_bits().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_use_shortest_encoding() {
+        let encode = |size| {
+            let mut out = Vec::new();
+            write_size(&mut out, size);
+            out
+        };
+
+        assert_eq!(vec![0x80], encode(0));
+        assert_eq!(vec![0xfe], encode(126));
+        // 127 in one byte would read as unknown
+        assert_eq!(vec![0x40, 0x7f], encode(127));
+        assert_eq!(vec![0x20, 0x40, 0x00], encode(0x4000));
+    }
+
+    #[test]
+    fn clusters_start_on_video_key_frames() {
+        let (mut mux, header) = MkvMux::new(MkvParams {
+            width: 16,
+            height: 16,
+            sample_rate: 44100,
+            channels: 2,
+            dcr: Cow::Borrowed(&[1, 2, 3]),
+            asc: Cow::Borrowed(&[4, 5]),
+        });
+
+        assert_eq!(&[0x1a, 0x45, 0xdf, 0xa3], &header[..4]);
+
+        let mut out = Vec::new();
+        mux.write(&mut out, Track::Video, 0, true, &[0; 8]);
+        mux.write(&mut out, Track::Audio, 10, false, &[0; 8]);
+        mux.write(&mut out, Track::Video, 33, false, &[0; 8]);
+        assert!(out.is_empty());
+
+        mux.write(&mut out, Track::Video, 2000, true, &[0; 8]);
+        assert_eq!(&[0x1f, 0x43, 0xb6, 0x75], &out[..4]);
+
+        let first_len = out.len();
+        mux.finish(&mut out);
+        assert!(out.len() > first_len);
+    }
+}
@@ -0,0 +1,291 @@
+pub const PACKET_LEN: usize = 188;
+
+// all timestamps in a transport stream are in units of a 90kHz clock, and
+// the PCR in 27MHz
+pub const TIME_BASE: i64 = 90_000;
+
+const SYNC_BYTE: u8 = 0x47;
+
+const PAT_PID: u16 = 0x0000;
+const PMT_PID: u16 = 0x1000;
+const VIDEO_PID: u16 = 0x0100;
+const AUDIO_PID: u16 = 0x0101;
+
+const PROGRAM_NUMBER: u16 = 1;
+
+const STREAM_TYPE_H264: u8 = 0x1b;
+const STREAM_TYPE_AAC_ADTS: u8 = 0x0f;
+
+const STREAM_ID_VIDEO: u8 = 0xe0;
+const STREAM_ID_AUDIO: u8 = 0xc0;
+
+// how far the PCR runs behind video decode timestamps, giving decoders this
+// much buffer
+const PCR_DELAY: u64 = TIME_BASE as u64 / 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Track {
+    // H.264 in annex-b form
+    Video,
+    // AAC with ADTS headers
+    Audio,
+}
+
+/// Muxes one program of H.264 video and AAC audio into a transport stream,
+/// with the video carrying the PCR.
+#[derive(Debug, Default)]
+pub struct TsMux {
+    pat_continuity: u8,
+    pmt_continuity: u8,
+    video_continuity: u8,
+    audio_continuity: u8,
+}
+
+impl TsMux {
+    pub fn new() -> Self {
+        TsMux::default()
+    }
+
+    /// The PAT and PMT, which receivers need before they can make sense of
+    /// anything else. They should be sent ahead of each key frame.
+    pub fn tables(&mut self, out: &mut Vec<u8>) {
+        let mut pat = Vec::new();
+        pat.extend_from_slice(&PROGRAM_NUMBER.to_be_bytes());
+        pat.extend_from_slice(&(0xe000 | PMT_PID).to_be_bytes());
+
+        let pat = section(0x00, 1, &pat);
+        write_section(out, PAT_PID, &mut self.pat_continuity, &pat);
+
+        let mut pmt = Vec::new();
+        pmt.extend_from_slice(&(0xe000 | VIDEO_PID).to_be_bytes());
+        // no program descriptors
+        pmt.extend_from_slice(&0xf000u16.to_be_bytes());
+
+        for (stream_type, pid) in &[(STREAM_TYPE_H264, VIDEO_PID), (STREAM_TYPE_AAC_ADTS, AUDIO_PID)] {
+            pmt.push(*stream_type);
+            pmt.extend_from_slice(&(0xe000 | pid).to_be_bytes());
+            pmt.extend_from_slice(&0xf000u16.to_be_bytes());
+        }
+
+        let pmt = section(0x02, PROGRAM_NUMBER, &pmt);
+        write_section(out, PMT_PID, &mut self.pmt_continuity, &pmt);
+    }
+
+    /// Writes one access unit as a PES packet. Timestamps are in 90kHz units
+    /// and must be at least `TIME_BASE / 10`, to leave room for the PCR
+    /// behind them.
+    pub fn write(&mut self, out: &mut Vec<u8>, track: Track, pts: u64, dts: u64, key_frame: bool, data: &[u8]) {
+        let (pid, stream_id, continuity) = match track {
+            Track::Video => (VIDEO_PID, STREAM_ID_VIDEO, &mut self.video_continuity),
+            Track::Audio => (AUDIO_PID, STREAM_ID_AUDIO, &mut self.audio_continuity),
+        };
+
+        let mut pes = Vec::with_capacity(19 + data.len());
+        pes.extend_from_slice(&[0x00, 0x00, 0x01, stream_id]);
+
+        let header_len = if pts != dts { 10 } else { 5 };
+        let pes_len = 3 + header_len + data.len();
+
+        // video PES packets may be any length, signalled with zero
+        let pes_len = match track {
+            Track::Video if pes_len > 0xffff => 0,
+            _ => pes_len as u16,
+        };
+
+        pes.extend_from_slice(&pes_len.to_be_bytes());
+        // marker bits, then the data alignment indicator
+        pes.push(0x84);
+
+        if pts != dts {
+            pes.push(0xc0);
+            pes.push(header_len as u8);
+            write_timestamp(&mut pes, 0x3, pts);
+            write_timestamp(&mut pes, 0x1, dts);
+        } else {
+            pes.push(0x80);
+            pes.push(header_len as u8);
+            write_timestamp(&mut pes, 0x2, pts);
+        }
+
+        pes.extend_from_slice(data);
+
+        let pcr = match track {
+            Track::Video => Some(dts.saturating_sub(PCR_DELAY) * 300),
+            Track::Audio => None,
+        };
+
+        write_payload(out, pid, continuity, &pes, pcr, key_frame);
+    }
+}
+
+// splits a payload over as many packets as it takes, the first flagged as a
+// unit start. the last is padded out with adaptation field stuffing
+fn write_payload(out: &mut Vec<u8>, pid: u16, continuity: &mut u8, mut payload: &[u8], pcr: Option<u64>, random_access: bool) {
+    let mut first = true;
+
+    while !payload.is_empty() {
+        let mut adaptation = Vec::new();
+
+        if first && (pcr.is_some() || random_access) {
+            let mut flags = 0u8;
+
+            if random_access {
+                flags |= 0x40;
+            }
+
+            adaptation.push(0);
+
+            if let Some(pcr) = pcr {
+                flags |= 0x10;
+
+                let base = pcr / 300;
+                let ext = pcr % 300;
+
+                adaptation.push(flags);
+                adaptation.extend_from_slice(&[
+                    (base >> 25) as u8,
+                    (base >> 17) as u8,
+                    (base >> 9) as u8,
+                    (base >> 1) as u8,
+                    ((base & 1) << 7) as u8 | 0x7e | (ext >> 8) as u8,
+                    ext as u8,
+                ]);
+            } else {
+                adaptation.push(flags);
+            }
+        }
+
+        let space = PACKET_LEN - 4 - adaptation.len();
+
+        if payload.len() < space {
+            // stuff the adaptation field to fill the packet exactly
+            let stuffing = space - payload.len();
+
+            if adaptation.is_empty() {
+                if stuffing == 1 {
+                    // an adaptation field of length zero
+                    adaptation.push(0);
+                } else {
+                    adaptation.push(0);
+                    adaptation.push(0);
+                    adaptation.resize(stuffing, 0xff);
+                }
+            } else {
+                let len = adaptation.len() + stuffing;
+                adaptation.resize(len, 0xff);
+            }
+        }
+
+        if !adaptation.is_empty() {
+            adaptation[0] = (adaptation.len() - 1) as u8;
+        }
+
+        let take = PACKET_LEN - 4 - adaptation.len();
+        let (chunk, rest) = payload.split_at(take);
+
+        let control = if adaptation.is_empty() { 0x10 } else { 0x30 };
+
+        out.push(SYNC_BYTE);
+        out.push((if first { 0x40 } else { 0x00 }) | (pid >> 8) as u8 & 0x1f);
+        out.push(pid as u8);
+        out.push(control | (*continuity & 0x0f));
+        out.extend_from_slice(&adaptation);
+        out.extend_from_slice(chunk);
+
+        *continuity = continuity.wrapping_add(1);
+        payload = rest;
+        first = false;
+    }
+}
+
+fn write_section(out: &mut Vec<u8>, pid: u16, continuity: &mut u8, section: &[u8]) {
+    // sections start after a pointer field, and are padded with 0xff
+    let mut payload = Vec::with_capacity(PACKET_LEN - 4);
+    payload.push(0);
+    payload.extend_from_slice(section);
+    payload.resize(PACKET_LEN - 4, 0xff);
+
+    out.push(SYNC_BYTE);
+    out.push(0x40 | (pid >> 8) as u8 & 0x1f);
+    out.push(pid as u8);
+    out.push(0x10 | (*continuity & 0x0f));
+    out.extend_from_slice(&payload);
+
+    *continuity = continuity.wrapping_add(1);
+}
+
+// a PSI section with the long form header, ending in its CRC
+fn section(table_id: u8, id: u16, body: &[u8]) -> Vec<u8> {
+    // id, version and section numbers before the body, CRC after
+    let len = 5 + body.len() + 4;
+
+    let mut section = Vec::with_capacity(3 + len);
+    section.push(table_id);
+    section.extend_from_slice(&(0xb000 | len as u16).to_be_bytes());
+    section.extend_from_slice(&id.to_be_bytes());
+    // version 0, current
+    section.push(0xc1);
+    // section 0 of 0
+    section.push(0);
+    section.push(0);
+    section.extend_from_slice(body);
+
+    let crc = crc32(&section);
+    section.extend_from_slice(&crc.to_be_bytes());
+    section
+}
+
+fn write_timestamp(out: &mut Vec<u8>, prefix: u8, ts: u64) {
+    let ts = ts & 0x1_ffff_ffff;
+
+    out.push(prefix << 4 | ((ts >> 29) as u8 & 0x0e) | 1);
+    out.extend_from_slice(&(((ts >> 14) as u16 & 0xfffe) | 1).to_be_bytes());
+    out.extend_from_slice(&(((ts << 1) as u16 & 0xfffe) | 1).to_be_bytes());
+}
+
+// the MPEG-2 CRC, unreflected with no final xor
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+
+    for byte in data {
+        crc ^= (*byte as u32) << 24;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads_fill_whole_packets() {
+        let mut mux = TsMux::new();
+        let mut out = Vec::new();
+
+        mux.tables(&mut out);
+
+        for len in &[1, 170, 183, 184, 1000] {
+            mux.write(&mut out, Track::Video, 9000, 9000, true, &vec![0; *len]);
+            mux.write(&mut out, Track::Audio, 9000, 9000, false, &vec![0; *len]);
+        }
+
+        assert_eq!(0, out.len() % PACKET_LEN);
+        assert!(out.chunks(PACKET_LEN).all(|packet| packet[0] == SYNC_BYTE));
+    }
+
+    #[test]
+    fn pat_crc_matches_known_value() {
+        // the PAT every single program stream from ffmpeg starts with
+        let pat = section(0x00, 1, &[0x00, 0x01, 0xf0, 0x00]);
+        assert_eq!(&[0x2a, 0xb1, 0x04, 0xb2], &pat[pat.len() - 4..]);
+    }
+}
@@ -17,6 +17,10 @@ pub enum ServerMessage<'a> {
     Sync(ClientSequence),
     Performance(Cow<'a, PerformanceInfo>),
     MediaLibrary(MediaLibrary),
+    StreamReports(StreamReports),
+    MidiDevices(MidiDevices),
+    Snapshots(Snapshots),
+    Macros(Macros),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -27,6 +31,98 @@ pub struct WorkspaceState {
     pub connections: Vec<(InputId, OutputId)>,
     pub inputs: Vec<(ModuleId, Vec<Terminal>)>,
     pub outputs: Vec<(ModuleId, Vec<Terminal>)>,
+    pub safety: Vec<(ModuleId, ModuleSafety)>,
+    pub groups: Vec<(ModuleId, String)>,
+    /// Modules passing their inputs straight through instead of running
+    pub bypassed: Vec<ModuleId>,
+    /// Connections between mono and stereo terminals not using the default
+    /// adapter
+    pub adapters: Vec<(InputId, ChannelAdapter)>,
+    pub midi_mappings: Vec<MidiMapping>,
+    pub surface: Vec<SurfaceControl>,
+    pub viewer_links: Vec<ViewerLink>,
+    pub freezes: Vec<Freeze>,
+    pub overrun_policy: OverrunPolicy,
+    /// Permission group of the session receiving this state. Sessions
+    /// without a group may edit every module, sessions with a group may only
+    /// edit modules belonging to that group.
+    pub session_group: Option<String>,
+}
+
+/// MIDI devices connected to the server, as opposed to those connected to
+/// the browser.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MidiDevices {
+    pub inputs: Vec<String>,
+}
+
+/// A module parameter which can be bound to a MIDI controller. `param` is a
+/// path naming the control within the module, eg. `"channel-1/fader"`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MidiTarget {
+    pub module: ModuleId,
+    pub param: String,
+}
+
+impl MidiTarget {
+    pub fn new(module: ModuleId, param: impl Into<String>) -> MidiTarget {
+        MidiTarget { module, param: param.into() }
+    }
+}
+
+/// A control change source, identified by the name of the input device
+/// rather than its id so that bindings survive reconnecting the device.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MidiControl {
+    pub device: String,
+    pub controller: u8,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MidiMapping {
+    pub target: MidiTarget,
+    pub control: MidiControl,
+}
+
+/// A control on the touchscreen control surface, bound to a module parameter
+/// by the same path as MIDI mappings. Controls are laid out in order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SurfaceControl {
+    pub target: MidiTarget,
+    pub label: String,
+}
+
+/// An expiring link letting one viewer watch an HLS Output or Monitor
+/// without access to the rest of the workspace. The token is the link's
+/// secret, and identifies it for revocation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ViewerLink {
+    pub token: String,
+    pub module: ModuleId,
+    pub label: String,
+    /// Unix time in seconds at which the link stops working
+    pub expires: u64,
+}
+
+/// An output whose upstream chain is rendered to a media item and replaced
+/// by a Media Source playing it in a loop, so that the chain stops using CPU.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Freeze {
+    pub output: OutputId,
+    pub state: FreezeState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum FreezeState {
+    /// The output is being recorded in realtime
+    Rendering { length_secs: f64 },
+    Frozen {
+        /// Media Source standing in for the output
+        source: ModuleId,
+        media_id: MediaId,
+        /// Modules which don't run until thawed
+        modules: Vec<ModuleId>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -36,6 +132,49 @@ pub struct PerformanceInfo {
     pub tick_rate: usize,
     pub tick_budget: Microseconds,
     pub accounts: Vec<(PerformanceAccount, PerformanceMetric)>,
+    /// Recent ticks which ran over budget, oldest first
+    pub overruns: Vec<OverrunEvent>,
+    pub buffers: BufferPoolInfo,
+}
+
+/// Output buffers the engine keeps between ticks rather than allocating
+/// afresh for every module each tick
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct BufferPoolInfo {
+    /// Buffers allocated since the engine started, which stops growing once
+    /// the pool holds enough for a whole tick
+    pub allocated: u64,
+    /// Buffers waiting in the pool between ticks
+    pub pooled: u64,
+    pub memory: Bytes,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OverrunEvent {
+    pub tick: u64,
+    pub elapsed: Microseconds,
+    /// Policy in effect when the overrun happened, applied to the next tick
+    pub action: OverrunPolicy,
+}
+
+/// What the engine does to catch up after a tick runs over its time budget.
+/// The chosen policy applies to the tick immediately following an overrun.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverrunPolicy {
+    /// Run every module regardless and fall behind realtime
+    Lag,
+    /// Skip modules that only process video, so audio keeps up
+    AudioPriority,
+    /// Skip modules that only display signals to the operator, eg. plotters
+    SkipNonEssential,
+    /// Skip the next tick entirely, dropping a tick of audio and video
+    SkipTick,
+}
+
+impl Default for OverrunPolicy {
+    fn default() -> Self {
+        OverrunPolicy::Lag
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
@@ -53,11 +192,16 @@ pub enum PerformanceAccount {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PerformanceMetric {
     pub last: Microseconds,
+    // approximate, only reported for modules
+    pub memory: Option<Bytes>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
 pub struct Microseconds(pub u64);
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Default)]
+pub struct Bytes(pub u64);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MediaLibrary {
     pub items: Vec<MediaItem>,
@@ -74,21 +218,76 @@ pub struct MediaItem {
     pub size: usize,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamReports {
+    pub reports: Vec<StreamReport>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamReportId(pub i64);
+
+/// Summary of a StreamOutput session, generated when the stream ends
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamReport {
+    pub id: StreamReportId,
+    pub summary: StreamSummary,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StreamSummary {
+    pub destination: String,
+    /// Unix timestamp in seconds
+    pub started_at: i64,
+    pub duration_secs: f64,
+    pub average_bitrate_kbps: f64,
+    pub peak_bitrate_kbps: f64,
+    pub dropped_frames: u64,
+    pub reconnects: u64,
+    pub loudness_average: Decibel,
+    pub loudness_peak: Decibel,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshots {
+    pub snapshots: Vec<Snapshot>,
+}
+
+/// A named copy of the whole workspace saved in the project
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    pub name: String,
+    /// Unix timestamp in seconds
+    pub saved_at: i64,
+    pub module_count: usize,
+}
+
+/// Macros saved in the project for reuse, by name
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Macros {
+    pub macros: Vec<MacroParams>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ClientMessage {
     Workspace(WorkspaceMessage),
+    SaveSnapshot(String),
+    ApplySnapshot(String),
+    DeleteSnapshot(String),
+    // saving under an existing name replaces that macro
+    SaveMacro(MacroParams),
+    DeleteMacro(String),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ClientSequence(pub NonZeroUsize);
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WorkspaceMessage {
     pub sequence: ClientSequence,
     pub op: WorkspaceOp,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum WorkspaceOp {
     CreateModule(ModuleParams, WindowGeometry),
     UpdateModuleParams(ModuleId, ModuleParams),
@@ -96,6 +295,54 @@ pub enum WorkspaceOp {
     DeleteModule(ModuleId),
     CreateConnection(InputId, OutputId),
     DeleteConnection(InputId),
+    UpdateModuleSafety(ModuleId, ModuleSafety),
+    UpdateModuleGroup(ModuleId, Option<String>),
+    UpdateModuleBypass(ModuleId, bool),
+    UpdateConnectionAdapter(InputId, ChannelAdapter),
+    UpdateMidiMapping(MidiTarget, Option<MidiControl>),
+    UpdateOverrunPolicy(OverrunPolicy),
+    CreateTemplate(Template, WindowGeometry),
+    UpdateSurface(Vec<SurfaceControl>),
+    // module, label and seconds until expiry, the server picks the token
+    CreateViewerLink(ModuleId, String, u64),
+    RevokeViewerLink(String),
+    // a one-off action for a module, such as connecting a stream. unlike
+    // params these aren't kept, so each is acted on exactly once
+    ModuleCommand(ModuleId, ModuleCommand),
+    // output to freeze and seconds of it to render
+    FreezeOutput(OutputId, f64),
+    ThawOutput(OutputId),
+    // collapses modules into a single Macro module of the given name
+    CreateMacro(String, Vec<ModuleId>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ModuleCommand {
+    FeedbackSuppressor(FeedbackSuppressorCommand),
+    FileOutput(FileOutputCommand),
+    IcecastOutput(IcecastOutputCommand),
+    Meter(MeterCommand),
+    StreamOutput(StreamOutputCommand),
+}
+
+// a ready patched chain of modules, created together in one op
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Template {
+    // a mic channel per input with EQ and a stem recorder, mixed down into
+    // a recorder and the output device
+    Podcast { mics: usize },
+}
+
+impl Template {
+    pub const PODCAST_GROUP: &'static str = "podcast";
+
+    // modules created by a template are put in this group, unless the
+    // session creating them is already in one
+    pub fn group(&self) -> &'static str {
+        match self {
+            Template::Podcast { .. } => Template::PODCAST_GROUP,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -108,12 +355,74 @@ pub enum ServerUpdate {
         inputs: Vec<Terminal>,
         outputs: Vec<Terminal>,
     },
-    UpdateModuleParams(ModuleId, ModuleParams),
+    UpdateModuleParams {
+        id: ModuleId,
+        params: ModuleParams,
+        // the module's new terminals, only when the update changed them.
+        // connections to terminals which went away or moved are deleted
+        // before this update, and those which moved are created again after
+        inputs: Option<Vec<Terminal>>,
+        outputs: Option<Vec<Terminal>>,
+    },
     UpdateWindowGeometry(ModuleId, WindowGeometry),
     UpdateModuleIndication(ModuleId, Indication),
     DeleteModule(ModuleId),
     CreateConnection(InputId, OutputId),
     DeleteConnection(InputId),
+    UpdateModuleSafety(ModuleId, ModuleSafety),
+    UpdateModuleGroup(ModuleId, Option<String>),
+    UpdateModuleBypass(ModuleId, bool),
+    // connections start out with the default adapter, and go back to it
+    // whenever the input is connected again
+    UpdateConnectionAdapter(InputId, ChannelAdapter),
+    UpdateMidiMapping(MidiTarget, Option<MidiControl>),
+    UpdateOverrunPolicy(OverrunPolicy),
+    UpdateSurface(Vec<SurfaceControl>),
+    UpdateViewerLinks(Vec<ViewerLink>),
+    UpdateFreezes(Vec<Freeze>),
+    /// A connection the engine refused. The input stays connected to
+    /// `current`, which clients that connected it ahead of the engine should
+    /// put back
+    RejectConnection {
+        input: InputId,
+        output: OutputId,
+        current: Option<OutputId>,
+        reason: ConnectionError,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionError {
+    /// The output is fed, directly or through other modules, by the input's
+    /// own module
+    Cycle,
+}
+
+/// Protects designated modules (eg. an on-air StreamOutput) from destructive
+/// actions. A `Safe` module cannot be deleted and its inputs cannot be
+/// disconnected until it is explicitly `Armed`. Arming is consumed by the next
+/// destructive action, after which the module returns to `Safe`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModuleSafety {
+    Unprotected,
+    Safe,
+    Armed,
+}
+
+impl Default for ModuleSafety {
+    fn default() -> Self {
+        ModuleSafety::Unprotected
+    }
+}
+
+impl ModuleSafety {
+    pub fn is_protected(self) -> bool {
+        self != ModuleSafety::Unprotected
+    }
+
+    pub fn is_locked(self) -> bool {
+        self == ModuleSafety::Safe
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
@@ -173,14 +482,42 @@ impl Terminal {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LineType {
     Mono,
     Stereo,
     Video,
+    // modulation signal for module parameters, sampled at a fraction of the
+    // audio rate. values are unipolar, 0.0 to 1.0
+    Control,
+    // first order ambisonic B-format, four channels of audio in AmbiX
+    // order (W, Y, Z, X). experimental
+    Ambisonic,
 }
 
 impl LineType {
+    /// Whether an output of this type may be connected to an input of type
+    /// `input`. Control outputs may also feed mono inputs, the engine
+    /// upsamples them to audio rate. Mono and stereo connect either way
+    /// through a `ChannelAdapter`.
+    pub fn connects_to(self, input: LineType) -> bool {
+        match (self, input) {
+            (LineType::Control, LineType::Mono) => true,
+            (output, input) if output.adapts_to(input) => true,
+            (output, input) => output == input,
+        }
+    }
+
+    /// Whether a connection from an output of this type to an input of type
+    /// `input` goes through a `ChannelAdapter`.
+    pub fn adapts_to(self, input: LineType) -> bool {
+        match (self, input) {
+            (LineType::Mono, LineType::Stereo) |
+            (LineType::Stereo, LineType::Mono) => true,
+            _ => false,
+        }
+    }
+
     pub fn labeled(self, label: &str) -> Terminal {
         Terminal(Some(label.to_string()), self)
     }
@@ -190,44 +527,138 @@ impl LineType {
     }
 }
 
+/// How a connection between mono and stereo terminals carries its signal
+/// across. Mono into stereo is upmixed onto the chosen channels, and stereo
+/// into mono is downmixed from them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelAdapter {
+    // mono onto both channels at full level, or both channels averaged so
+    // that the same signal on each comes through unchanged
+    Both,
+    Left,
+    Right,
+}
+
+impl Default for ChannelAdapter {
+    fn default() -> Self {
+        ChannelAdapter::Both
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ModuleParams {
+    AmbisonicEncoder(AmbisonicEncoderParams),
     Amplifier(AmplifierParams),
+    BinauralDecoder(BinauralDecoderParams),
+    ChromaKey(ChromaKeyParams),
+    Clock(ClockParams),
+    Compositor(CompositorParams),
+    Compressor(CompressorParams),
+    Crossover(CrossoverParams),
+    Delay(DelayParams),
+    EchoCanceller(EchoCancellerParams),
     Envelope(EnvelopeParams),
     EqThree(EqThreeParams),
+    FeedbackSuppressor(FeedbackSuppressorParams),
+    FileOutput(FileOutputParams),
+    Filter(FilterParams),
     FmSine(FmSineParams),
+    Goniometer(()),
+    HlsOutput(HlsOutputParams),
+    IcecastOutput(IcecastOutputParams),
+    ImageOverlay(ImageOverlayParams),
+    InputDevice(InputDeviceParams),
+    Lfo(LfoParams),
+    Limiter(LimiterParams),
+    Looper(LooperParams),
+    Macro(MacroParams),
     MediaSource(MediaSourceParams),
+    Meter(()),
+    MidiNote(MidiNoteParams),
     Mixer(MixerParams),
     Monitor(()),
+    NoiseGate(NoiseGateParams),
     Oscillator(OscillatorParams),
     OutputDevice(OutputDeviceParams),
+    PitchShift(PitchShiftParams),
     Plotter(()),
+    ProfanityDelay(ProfanityDelayParams),
+    Recorder(RecorderParams),
+    Reverb(ReverbParams),
+    Sampler(SamplerParams),
+    Sequencer(SequencerParams),
+    SilenceDetector(SilenceDetectorParams),
+    Slate(SlateParams),
+    SpectrumAnalyzer(SpectrumAnalyzerParams),
     StereoPanner(()),
     StereoSplitter(()),
     StreamInput(StreamInputParams),
     StreamOutput(StreamOutputParams),
+    SyncCheck(()),
+    Talkback(GateState),
+    TextOverlay(TextOverlayParams),
     Trigger(GateState),
+    Tuner(TunerParams),
     VideoMixer(VideoMixerParams),
+    Waveshaper(WaveshaperParams),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum Indication {
+    AmbisonicEncoder(()),
     Amplifier(()),
+    BinauralDecoder(()),
+    ChromaKey(()),
+    Clock(()),
+    Compositor(()),
+    Compressor(()),
+    Crossover(()),
+    Delay(()),
+    EchoCanceller(EchoCancellerIndication),
     Envelope(()),
     EqThree(()),
+    FeedbackSuppressor(FeedbackSuppressorIndication),
+    FileOutput(FileOutputIndication),
+    Filter(()),
     FmSine(()),
-    MediaSource(()),
-    Mixer(()),
+    Goniometer(GoniometerIndication),
+    HlsOutput(HlsOutputIndication),
+    IcecastOutput(IcecastOutputIndication),
+    ImageOverlay(()),
+    InputDevice(InputDeviceIndication),
+    Lfo(()),
+    Limiter(()),
+    Looper(LooperIndication),
+    Macro(MacroIndication),
+    MediaSource(MediaSourceIndication),
+    Meter(MeterIndication),
+    MidiNote(()),
+    Mixer(MixerIndication),
     Monitor(MonitorIndication),
+    NoiseGate(GateState),
     Oscillator(()),
     OutputDevice(OutputDeviceIndication),
+    PitchShift(()),
     Plotter(PlotterIndication),
+    ProfanityDelay(ProfanityDelayIndication),
+    Recorder(RecorderIndication),
+    Reverb(()),
+    Sampler(()),
+    Sequencer(SequencerIndication),
+    SilenceDetector(SilenceDetectorIndication),
+    Slate(SlateIndication),
+    SpectrumAnalyzer(SpectrumAnalyzerIndication),
     StereoPanner(()),
     StereoSplitter(()),
-    StreamInput(()),
+    StreamInput(StreamInputIndication),
     StreamOutput(StreamOutputIndication),
+    SyncCheck(SyncCheckIndication),
+    Talkback(()),
+    TextOverlay(()),
     Trigger(()),
+    Tuner(TunerIndication),
     VideoMixer(()),
+    Waveshaper(()),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Copy, PartialEq)]
@@ -238,12 +669,87 @@ pub enum Waveform {
     Square,
     Triangle,
     Saw,
+    // square with a variable duty cycle, the oscillator's pulse width
+    Pulse,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct OscillatorParams {
     pub freq: f64,
     pub waveform: Waveform,
+    // fraction of each cycle a pulse is high for, 0.5 is a square wave
+    #[serde(default = "OscillatorParams::default_pulse_width")]
+    pub pulse_width: f64,
+    #[serde(default = "OscillatorParams::default_freq_scale")]
+    pub freq_scale: FreqScale,
+    // how far a full scale signal on the frequency input moves the
+    // frequency, in Hz or octaves according to the scale
+    #[serde(default = "OscillatorParams::default_freq_mod")]
+    pub freq_mod: f64,
+}
+
+impl Default for OscillatorParams {
+    fn default() -> Self {
+        OscillatorParams {
+            freq: 100.0,
+            waveform: Waveform::Sine,
+            pulse_width: OscillatorParams::default_pulse_width(),
+            freq_scale: OscillatorParams::default_freq_scale(),
+            freq_mod: OscillatorParams::default_freq_mod(),
+        }
+    }
+}
+
+impl OscillatorParams {
+    fn default_pulse_width() -> f64 {
+        0.5
+    }
+
+    fn default_freq_scale() -> FreqScale {
+        FreqScale::VoltPerOctave
+    }
+
+    fn default_freq_mod() -> f64 {
+        1.0
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FreqScale {
+    // frequency input added to the frequency
+    Hz,
+    // frequency input raises the frequency exponentially, by octaves
+    VoltPerOctave,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LfoParams {
+    pub freq: f64,
+    pub waveform: Waveform,
+    pub depth: f64,
+}
+
+impl Default for LfoParams {
+    fn default() -> Self {
+        LfoParams {
+            freq: 1.0,
+            waveform: Waveform::Sine,
+            depth: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ClockParams {
+    pub bpm: f64,
+}
+
+impl Default for ClockParams {
+    fn default() -> Self {
+        ClockParams {
+            bpm: 120.0,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -277,151 +783,1288 @@ pub struct OutputDeviceIndication {
     pub devices: Option<Vec<(String, usize)>>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InputDeviceParams {
+    pub device: Option<String>,
+    // device channels feeding the left and right of the output. a single
+    // channel selected on its own feeds both sides
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InputDeviceIndication {
+    pub clip: Option<TemporalWarningStatus>,
+    pub lag: Option<TemporalWarningStatus>,
+    pub default_device: Option<String>,
+    pub devices: Option<Vec<(String, usize)>>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PlotterIndication {
     pub inputs: Vec<Vec<Sample>>,
 }
 
+// quietest level meters show, anything below reads as this
+pub const METER_FLOOR_DB: f32 = -90.0;
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
-pub struct EqThreeParams {
-    pub gain_lo: Decibel,
-    pub gain_mid: Decibel,
-    pub gain_hi: Decibel,
+pub struct MeterIndication {
+    // left and right
+    pub channels: Vec<MeterLevel>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct FmSineParams {
-    pub freq_lo: f64,
-    pub freq_hi: f64,
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct MeterLevel {
+    // all in dBFS
+    pub rms_db: f32,
+    pub peak_db: f32,
+    // highest recent peak
+    pub hold_db: f32,
+    // reached full scale since the clip light was last reset
+    pub clipped: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct AmplifierParams {
-    pub amplitude: f64,
-    pub mod_depth: f64,
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeterCommand {
+    ResetClip,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub enum GateState {
-    Open,
-    Closed
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MixerIndication {
+    // in the same order as the params' channels
+    pub channels: Vec<MixerChannelLevels>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct EnvelopeParams {
-    pub attack_ms: f64,
-    pub decay_ms: f64,
-    pub sustain_amplitude: f64,
-    pub release_ms: f64,
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct MixerChannelLevels {
+    // peak levels in dBFS, louder of left and right
+    pub pre_fader_db: f32,
+    pub post_fader_db: f32,
 }
 
-impl Default for EnvelopeParams {
-    fn default() -> EnvelopeParams {
-        EnvelopeParams {
-            attack_ms: 25.0,
-            decay_ms: 500.0,
-            sustain_amplitude: 0.8,
-            release_ms: 200.0,
-        }
-    }
+// spectrum analyzer bands are spaced evenly in log frequency over this range
+pub const SPECTRUM_BANDS: usize = 32;
+pub const SPECTRUM_MIN_HZ: f64 = 20.0;
+pub const SPECTRUM_MAX_HZ: f64 = 20000.0;
+// quietest level reported, anything below reads as this
+pub const SPECTRUM_FLOOR_DB: f32 = -90.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SpectrumAnalyzerParams {
+    pub display: SpectrumDisplay,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct MixerParams {
-    pub channels: Vec<MixerChannelParams>
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpectrumDisplay {
+    Bars,
+    // scrolling history of analyses, newest on the right
+    Spectrogram,
 }
 
-impl MixerParams {
-    pub fn with_channels(n: usize) -> MixerParams {
-        MixerParams {
-            channels: vec![MixerChannelParams::default(); n]
-        }
+impl Default for SpectrumDisplay {
+    fn default() -> Self {
+        SpectrumDisplay::Bars
     }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
-pub struct MixerChannelParams {
-    pub gain: Decibel,
-    pub fader: f64,
-    pub cue: bool,
+pub struct SpectrumAnalyzerIndication {
+    // counts analyses, so the spectrogram can tell a new one from the same
+    // one rendered again
+    pub seq: u64,
+    // level of each band in dBFS, lowest band first. empty until the first
+    // analysis
+    pub levels: Vec<f32>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
-pub struct StreamInputParams {
-    pub protocol: Option<StreamProtocol>,
-    pub mountpoint: Option<String>,
+pub struct GoniometerIndication {
+    // left and right sample pairs since the last indication, thinned out
+    pub points: Vec<(f32, f32)>,
+    // from -1 with left and right out of phase, through 0 for unrelated
+    // channels, to 1 for mono
+    pub correlation: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TunerParams {
+    // pitch of A4 that notes are tuned against
+    pub reference_hz: f64,
+}
+
+impl Default for TunerParams {
+    fn default() -> Self {
+        TunerParams { reference_hz: 440.0 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TunerIndication {
+    // `None` while nothing pitched is playing
+    pub pitch: Option<TunerPitch>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
-pub enum StreamProtocol {
-    Icecast,
-    Rtmp,
+pub struct TunerPitch {
+    pub freq: f32,
+    // MIDI note number of the nearest note, 69 is A4
+    pub note: u8,
+    // how far the pitch is from that note, -50 to 50
+    pub cents: f32,
+    // from 0 to 1, how clearly periodic the input is
+    pub confidence: f32,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct StreamOutputParams {
-    // TODO this is an awful hack to encode one-time impulses into params
-    // figure out a nicer way of doing this
-    pub seq: u64,
-    pub connect_seq: u64,
-    pub disconnect_seq: u64,
-    pub rtmp_url: String,
-    pub rtmp_stream_key: String,
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecorderParams {
+    pub recording: bool,
+    pub markers: Vec<RecorderMarker>,
+    // name of the WAV file to record to, filled in as for FileOutputParams
+    #[serde(default = "RecorderParams::default_filename")]
+    pub filename: String,
 }
 
-impl Default for StreamOutputParams {
+impl RecorderParams {
+    fn default_filename() -> String {
+        "recording-{date}-{time}".to_owned()
+    }
+}
+
+impl Default for RecorderParams {
     fn default() -> Self {
-        Self {
-            seq: 1,
-            connect_seq: 0,
-            disconnect_seq: 0,
-            rtmp_url: "".to_owned(),
-            rtmp_stream_key: "".to_owned(),
+        RecorderParams {
+            recording: false,
+            markers: Vec::new(),
+            filename: RecorderParams::default_filename(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub struct StreamOutputIndication {
-    pub live: StreamOutputLiveStatus,
-    pub error: bool,
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecorderMarker {
+    // offset in seconds from the start of the recording:
+    pub time: f64,
+    pub label: String,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
-pub enum StreamOutputLiveStatus {
-    Offline,
-    Connecting,
-    Live,
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RecorderIndication {
+    pub overview: WaveformOverview,
+    // name of the file being written, or last written
+    pub file: Option<String>,
+    // set when the file couldn't be created or written
+    pub error: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
-pub struct StreamSource {
-    pub codec: String,
-    pub kbps: usize,
+pub struct WaveformOverview {
+    // number of seconds of audio each peak covers, doubles as the
+    // recording grows so the overview stays bounded in size:
+    pub bucket_secs: usize,
+    pub peaks: Vec<Sample>,
+    pub duration_secs: f64,
 }
 
-pub const VIDEO_MIXER_CHANNELS: usize = 4;
-
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct VideoMixerParams {
-    pub a: Option<usize>,
-    pub b: Option<usize>,
-    pub fader: f64,
+pub struct NoiseGateParams {
+    // opens when the input peaks above this
+    pub threshold: Decibel,
+    // and closes once it stays this far below the threshold for the hold
+    // time, so a level hovering around the threshold doesn't chatter
+    pub hysteresis: Decibel,
+    // gain while closed. a few dB makes it a gentle expander
+    pub range: Decibel,
+    // seconds
+    pub attack: f64,
+    pub hold: f64,
+    pub release: f64,
 }
 
-impl Default for VideoMixerParams {
+impl Default for NoiseGateParams {
     fn default() -> Self {
-        VideoMixerParams {
-            a: None,
-            b: None,
-            fader: 1.0, // start at A
+        NoiseGateParams {
+            threshold: Decibel(-40.0),
+            hysteresis: Decibel(6.0),
+            range: Decibel(-60.0),
+            attack: 0.001,
+            hold: 0.05,
+            release: 0.2,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CompressorParams {
+    // level above which gain is reduced
+    pub threshold: Decibel,
+    // input dB over the threshold for each dB of output over it
+    pub ratio: f64,
+    // seconds
+    pub attack: f64,
+    pub release: f64,
+    // gain after compression, to bring the level back up
+    pub makeup: Decibel,
+}
+
+impl Default for CompressorParams {
+    fn default() -> Self {
+        CompressorParams {
+            threshold: Decibel(-18.0),
+            ratio: 3.0,
+            attack: 0.005,
+            release: 0.15,
+            makeup: Decibel(0.0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LimiterParams {
+    // peak level the output never goes over
+    pub ceiling: Decibel,
+    // seconds
+    pub release: f64,
+}
+
+impl Default for LimiterParams {
+    fn default() -> Self {
+        LimiterParams {
+            ceiling: Decibel(-1.0),
+            release: 0.05,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct EqThreeParams {
+    pub gain_lo: Decibel,
+    pub gain_mid: Decibel,
+    pub gain_hi: Decibel,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CrossoverParams {
+    // Hz, between the low and mid bands and the mid and high bands
+    pub low_freq: f64,
+    pub high_freq: f64,
+}
+
+impl Default for CrossoverParams {
+    fn default() -> Self {
+        CrossoverParams {
+            low_freq: 200.0,
+            high_freq: 2000.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FilterParams {
+    pub mode: FilterMode,
+    // Hz
+    pub cutoff: f64,
+    // 0 to 1, self-oscillates near 1
+    pub resonance: f64,
+    // how far a full scale signal on the cutoff input moves the cutoff, in
+    // octaves
+    pub cutoff_mod: f64,
+}
+
+impl Default for FilterParams {
+    fn default() -> Self {
+        FilterParams {
+            mode: FilterMode::LowPass,
+            cutoff: 1000.0,
+            resonance: 0.0,
+            cutoff_mod: 4.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FilterMode {
+    LowPass,
+    HighPass,
+    BandPass,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WaveshaperParams {
+    pub mode: WaveshaperMode,
+    // gain into the shaper, 1 leaves a full scale input at full scale
+    pub drive: f64,
+}
+
+impl Default for WaveshaperParams {
+    fn default() -> Self {
+        WaveshaperParams {
+            mode: WaveshaperMode::Fold,
+            drive: 2.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum WaveshaperMode {
+    // input multiplied by the carrier input
+    RingMod,
+    // folds back on itself each time it passes full scale
+    Fold,
+    // rounds off smoothly towards full scale
+    SoftClip,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DelayParams {
+    // seconds, used when nothing is connected to the clock input
+    pub time: f64,
+    // delay time as a multiple of the clock period when the clock input is
+    // connected, eg. 0.75 for a dotted eighth against a quarter note clock
+    pub clock_ratio: f64,
+    // 0 to 1, proportion of the delayed signal fed back into the delay line
+    pub feedback: f64,
+    // 0 is fully dry, 1 fully wet
+    pub mix: f64,
+}
+
+impl Default for DelayParams {
+    fn default() -> Self {
+        DelayParams {
+            time: 0.5,
+            clock_ratio: 1.0,
+            feedback: 0.4,
+            mix: 0.5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EchoCancellerParams {
+    // milliseconds for the return feed to reach the guest and come back in
+    // their mic. the return is held back this long before it's compared
+    pub delay_ms: f64,
+    // milliseconds of echo beyond the delay that can be cancelled, longer
+    // for echoey rooms and uncertain delays but costlier
+    pub tail_ms: f64,
+}
+
+impl Default for EchoCancellerParams {
+    fn default() -> Self {
+        EchoCancellerParams {
+            delay_ms: 0.0,
+            tail_ms: 250.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct EchoCancellerIndication {
+    // how far below the guest's feed the echo in it has been brought, in
+    // whole decibels
+    pub reduction_db: i32,
+    // set while the guest talks over the return, which holds adaptation off
+    pub double_talk: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FmSineParams {
+    pub freq_lo: f64,
+    pub freq_hi: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AmplifierParams {
+    pub amplitude: f64,
+    pub mod_depth: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct AmbisonicEncoderParams {
+    // degrees anticlockwise from straight ahead, so positive is to the left
+    pub azimuth: f64,
+    // degrees above the horizon
+    pub elevation: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct BinauralDecoderParams {
+    // degrees the listener has turned to the left, for following a viewer
+    // looking around a 360 video
+    pub yaw: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SilenceDetectorParams {
+    pub threshold: Decibel,
+    pub hold_secs: f64,
+}
+
+impl Default for SilenceDetectorParams {
+    fn default() -> SilenceDetectorParams {
+        SilenceDetectorParams {
+            threshold: Decibel(-50.0),
+            hold_secs: 10.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SilenceDetectorIndication {
+    pub alarm: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SlateParams {
+    // program is replaced by the slate inputs while this is set
+    pub engaged: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SlateIndication {
+    // whether a slate picture has been received, so that engaging won't
+    // leave the output without video
+    pub picture: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum GateState {
+    Open,
+    Closed
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EnvelopeParams {
+    pub attack_ms: f64,
+    pub decay_ms: f64,
+    pub sustain_amplitude: f64,
+    pub release_ms: f64,
+    // gate the envelope from notes played on server MIDI inputs, in addition
+    // to its gate input
+    #[serde(default)]
+    pub midi: Option<MidiNoteBinding>,
+    #[serde(default = "EnvelopeParams::default_curve")]
+    pub attack_curve: EnvelopeCurve,
+    #[serde(default = "EnvelopeParams::default_curve")]
+    pub decay_curve: EnvelopeCurve,
+    #[serde(default = "EnvelopeParams::default_curve")]
+    pub release_curve: EnvelopeCurve,
+    #[serde(default = "EnvelopeParams::default_trigger")]
+    pub trigger: EnvelopeTrigger,
+}
+
+impl Default for EnvelopeParams {
+    fn default() -> EnvelopeParams {
+        EnvelopeParams {
+            attack_ms: 25.0,
+            decay_ms: 500.0,
+            sustain_amplitude: 0.8,
+            release_ms: 200.0,
+            midi: None,
+            attack_curve: EnvelopeParams::default_curve(),
+            decay_curve: EnvelopeParams::default_curve(),
+            release_curve: EnvelopeParams::default_curve(),
+            trigger: EnvelopeParams::default_trigger(),
+        }
+    }
+}
+
+impl EnvelopeParams {
+    fn default_curve() -> EnvelopeCurve {
+        EnvelopeCurve::Linear
+    }
+
+    fn default_trigger() -> EnvelopeTrigger {
+        EnvelopeTrigger::Retrigger
+    }
+}
+
+/// Shape of one stage of an envelope.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum EnvelopeCurve {
+    Linear,
+    // quick to move at first and slow to settle, like an analogue envelope
+    Exponential,
+}
+
+/// What a note played while an envelope is still sounding does.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum EnvelopeTrigger {
+    // start the attack again from the current level
+    Retrigger,
+    // carry on to the sustain level without a new attack
+    Legato,
+}
+
+/// Selects which MIDI notes an event applies to, `None` matches any.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct MidiNoteBinding {
+    pub channel: Option<u8>,
+    pub note: Option<u8>,
+}
+
+impl MidiNoteBinding {
+    pub fn matches(&self, channel: u8, note: u8) -> bool {
+        self.channel.map(|ch| ch == channel).unwrap_or(true)
+            && self.note.map(|n| n == note).unwrap_or(true)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MidiNoteParams {
+    // only notes on this channel are played, `None` plays every channel
+    pub channel: Option<u8>,
+    // fixed when the module is created, each voice has its own outputs
+    pub voices: usize,
+    pub allocation: VoiceAllocation,
+}
+
+impl MidiNoteParams {
+    pub fn with_voices(voices: usize) -> MidiNoteParams {
+        MidiNoteParams {
+            channel: None,
+            voices,
+            allocation: VoiceAllocation::RoundRobin,
+        }
+    }
+}
+
+/// How a new note picks its voice. Both prefer a voice that isn't playing,
+/// and differ in what they do when every voice is busy.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum VoiceAllocation {
+    // cycle through the voices, taking over the next one in turn
+    RoundRobin,
+    // take over the voice that has been held the longest, so the latest
+    // note always sounds
+    LastNote,
+}
+
+// most channels a mixer can be resized to
+pub const MIXER_MAX_CHANNELS: usize = 16;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MixerParams {
+    pub channels: Vec<MixerChannelParams>,
+    // amount to dim the cue output by while talkback or an alarm is active:
+    #[serde(default = "MixerParams::default_cue_duck")]
+    pub cue_duck: Decibel,
+}
+
+impl MixerParams {
+    pub fn with_channels(n: usize) -> MixerParams {
+        MixerParams {
+            channels: vec![MixerChannelParams::default(); n],
+            cue_duck: MixerParams::default_cue_duck(),
+        }
+    }
+
+    fn default_cue_duck() -> Decibel {
+        Decibel(-20.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MixerChannelParams {
+    pub gain: Decibel,
+    pub fader: f64,
+    pub cue: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StreamInputParams {
+    pub protocol: Option<StreamProtocol>,
+    pub mountpoint: Option<String>,
+    // seconds of a newly connected source to discard, to skip the black and
+    // silence some encoders start with. video is held back further until
+    // the first keyframe after the window
+    #[serde(default)]
+    pub settle_secs: f64,
+    // RTMP publishers must present this stream key, and Icecast sources
+    // this password, when set
+    #[serde(default)]
+    pub stream_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StreamInputIndication {
+    pub audio_codec: Option<String>,
+    pub audio_bitrate: Option<u32>,
+    pub video_codec: Option<String>,
+    // only present for inputs received over a link with loss recovery
+    pub link: Option<StreamLinkStats>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct StreamLinkStats {
+    pub received: u64,
+    pub lost: u64,
+    pub recovered_fec: u64,
+    pub recovered_arq: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum StreamProtocol {
+    Icecast,
+    Rtmp,
+    // plays a local FLV file on loop, mountpoint is the file path
+    File,
+    // MPEG-TS over UDP, mountpoint is the address to listen on
+    MpegTsUdp,
+    // MPEG-TS from an SRT caller, mountpoint is the address to listen on and
+    // stream key the stream id callers must send
+    Srt,
+    // a remote guest joining from the guest page in their browser,
+    // mountpoint names the guest link and stream key is its secret
+    Guest,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StreamOutputParams {
+    pub rtmp_url: String,
+    pub rtmp_stream_key: String,
+    // forward compressed video from an RTMP source routed here unmixed rather
+    // than re-encoding it. frames that have been mixed are still encoded.
+    // video only, audio reaches the output as samples and is always encoded.
+    // forwarding AAC from unmixed RTMP sources is a separate change
+    #[serde(default)]
+    pub passthrough: bool,
+    // encode a small preview of the output for watching in the browser
+    #[serde(default)]
+    pub preview: bool,
+    // seconds between key frames, most services ask for 2
+    #[serde(default = "StreamOutputParams::default_keyframe_interval")]
+    pub keyframe_interval: f64,
+    // name given to cue points sent with StreamOutputCommand::Cue
+    #[serde(default)]
+    pub cue_name: String,
+    // shifts audio against video to correct for sync drift upstream.
+    // positive delays audio, negative delays video
+    #[serde(default)]
+    pub audio_delay_ms: f64,
+    // destination promoted when the primary fails, or on
+    // StreamOutputCommand::SwitchToBackup
+    #[serde(default)]
+    pub backup_rtmp_url: String,
+    #[serde(default)]
+    pub backup_rtmp_stream_key: String,
+    // further destinations sent the same encoded stream as the primary.
+    // enabled flags can change while live, the list itself can't
+    #[serde(default)]
+    pub destinations: Vec<StreamDestination>,
+}
+
+impl Default for StreamOutputParams {
+    fn default() -> Self {
+        Self {
+            rtmp_url: "".to_owned(),
+            rtmp_stream_key: "".to_owned(),
+            passthrough: false,
+            preview: false,
+            keyframe_interval: Self::default_keyframe_interval(),
+            cue_name: "".to_owned(),
+            audio_delay_ms: 0.0,
+            backup_rtmp_url: "".to_owned(),
+            backup_rtmp_stream_key: "".to_owned(),
+            destinations: Vec::new(),
+        }
+    }
+}
+
+impl StreamOutputParams {
+    fn default_keyframe_interval() -> f64 {
+        2.0
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamOutputCommand {
+    Connect,
+    Disconnect,
+    // marks a cue point in the live stream, named by the cue_name param
+    Cue,
+    // moves a live stream over to the backup url
+    SwitchToBackup,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StreamOutputIndication {
+    pub live: StreamOutputLiveStatus,
+    pub error: bool,
+    // monitor socket streaming the preview, while enabled
+    pub preview: Option<Uuid>,
+    // set once the stream has been moved to the backup destination
+    #[serde(default)]
+    pub backup: bool,
+    // status of each of params.destinations, in the same order
+    #[serde(default)]
+    pub destinations: Vec<StreamDestinationStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct StreamDestination {
+    // RTMP or SRT, as with StreamOutputParams::rtmp_url
+    pub url: String,
+    pub stream_key: String,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamDestinationStatus {
+    Disabled,
+    Offline,
+    Connecting,
+    Live,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SyncCheckIndication {
+    // how far audio trails video in the returned test pattern, averaged over
+    // recent pulses. negative when audio is early
+    pub offset_ms: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamOutputLiveStatus {
+    Offline,
+    Connecting,
+    Live,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IcecastOutputParams {
+    // http://host:port/mountpoint. a user name other than "source" can be
+    // given in the url
+    pub url: String,
+    pub password: String,
+    pub format: IcecastFormat,
+    pub bitrate_kbps: usize,
+}
+
+impl Default for IcecastOutputParams {
+    fn default() -> Self {
+        IcecastOutputParams {
+            url: "".to_owned(),
+            password: "".to_owned(),
+            format: IcecastFormat::OggVorbis,
+            bitrate_kbps: 128,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IcecastOutputCommand {
+    Connect,
+    Disconnect,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IcecastFormat {
+    OggVorbis,
+    Mp3,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IcecastOutputIndication {
+    pub live: StreamOutputLiveStatus,
+    // set when connecting fails, and while a dropped connection is being
+    // retried
+    pub error: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FeedbackSuppressorParams {
+    // how far above the rest of the spectrum a peak must stand to be taken
+    // for feedback, in dB
+    pub sensitivity_db: f64,
+    pub max_notches: usize,
+    // deepest a notch is cut, in dB
+    pub max_depth_db: f64,
+    // notches not retriggered for this long are lifted, zero keeps them
+    pub release_secs: f64,
+}
+
+impl Default for FeedbackSuppressorParams {
+    fn default() -> Self {
+        FeedbackSuppressorParams {
+            sensitivity_db: 15.0,
+            max_notches: 8,
+            max_depth_db: 18.0,
+            release_secs: 60.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedbackSuppressorCommand {
+    ClearNotches,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct FeedbackSuppressorIndication {
+    // lowest frequency first
+    pub notches: Vec<FeedbackNotch>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeedbackNotch {
+    pub freq_hz: u32,
+    pub depth_db: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FileOutputParams {
+    // file name without extension, written to the project's recordings
+    // directory. {date}, {time} and {timestamp} are filled in from when
+    // recording starts, in UTC
+    pub filename: String,
+    pub format: FileFormat,
+}
+
+impl Default for FileOutputParams {
+    fn default() -> Self {
+        FileOutputParams {
+            filename: "program-{date}-{time}".to_owned(),
+            format: FileFormat::Mkv,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileOutputCommand {
+    Start,
+    Stop,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    Mp4,
+    Mkv,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct FileOutputIndication {
+    pub recording: bool,
+    // name of the file being written, or last written
+    pub file: Option<String>,
+    pub elapsed_secs: u64,
+    pub bytes: u64,
+    // set when the file couldn't be created or written
+    pub error: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HlsOutputParams {
+    // served at /_hls/<name>/index.m3u8
+    pub name: String,
+    pub format: HlsFormat,
+    // segments are cut at the first key frame after this long
+    pub segment_secs: f64,
+    // number of segments listed in the playlist
+    pub playlist_length: usize,
+    // only served through viewer links, not at /_hls/<name>
+    #[serde(default)]
+    pub private: bool,
+}
+
+impl Default for HlsOutputParams {
+    fn default() -> Self {
+        HlsOutputParams {
+            name: "live".to_owned(),
+            format: HlsFormat::Ts,
+            segment_secs: 4.0,
+            playlist_length: 5,
+            private: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HlsFormat {
+    Ts,
+    Fmp4,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct HlsOutputIndication {
+    // false when another HLS output is already serving under the name
+    pub serving: bool,
+    // segments available to viewers, the stream can be watched once the
+    // first is ready
+    pub segments: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StreamSource {
+    pub codec: String,
+    pub kbps: usize,
+}
+
+// channels a video mixer starts with, and the most it can be resized to
+pub const VIDEO_MIXER_CHANNELS: usize = 4;
+pub const VIDEO_MIXER_MAX_CHANNELS: usize = 8;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VideoMixerParams {
+    pub a: Option<usize>,
+    pub b: Option<usize>,
+    pub fader: f64,
+    // hold a cut to a new channel until that channel delivers a key frame,
+    // so compressed pass-through downstream never starts mid-GOP
+    #[serde(default)]
+    pub cut_on_key_frame: bool,
+    #[serde(default = "VideoMixerParams::default_channels")]
+    pub channels: usize,
+}
+
+impl VideoMixerParams {
+    fn default_channels() -> usize {
+        VIDEO_MIXER_CHANNELS
+    }
+}
+
+impl Default for VideoMixerParams {
+    fn default() -> Self {
+        VideoMixerParams {
+            a: None,
+            b: None,
+            fader: 1.0, // start at A
+            cut_on_key_frame: false,
+            channels: VIDEO_MIXER_CHANNELS,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TextOverlayParams {
+    pub text: String,
+    // height of capital letters in pixels, rounded to a whole number of font
+    // pixels
+    pub font_size: usize,
+    pub color: Rgb,
+    pub position: TextPosition,
+    // box drawn behind the text, hidden at 0.0
+    pub box_color: Rgb,
+    pub box_opacity: f64,
+}
+
+impl Default for TextOverlayParams {
+    fn default() -> Self {
+        TextOverlayParams {
+            text: String::new(),
+            font_size: 35,
+            color: Rgb(255, 255, 255),
+            position: TextPosition::BottomLeft,
+            box_color: Rgb(0, 0, 0),
+            box_opacity: 0.6,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    Center,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Formats as `#rrggbb`, as HTML colour inputs take.
+    pub fn to_hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+
+    pub fn from_hex(hex: &str) -> Option<Rgb> {
+        if hex.len() != 7 || !hex.starts_with('#') {
+            return None;
+        }
+
+        let component = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+
+        Some(Rgb(component(1)?, component(3)?, component(5)?))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ImageOverlayParams {
+    // PNG or JPEG image in the media library, with or without transparency
+    pub media_id: Option<MediaId>,
+    pub position: TextPosition,
+    // height of the image as a fraction of the picture height, keeping its
+    // aspect ratio
+    pub scale: f64,
+    pub opacity: f64,
+}
+
+impl Default for ImageOverlayParams {
+    fn default() -> Self {
+        ImageOverlayParams {
+            media_id: None,
+            position: TextPosition::TopRight,
+            scale: 0.15,
+            opacity: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CompositorParams {
+    pub position: TextPosition,
+    // height of the picture as a fraction of the background height, keeping
+    // its aspect ratio
+    pub scale: f64,
+    pub opacity: f64,
+}
+
+impl Default for CompositorParams {
+    fn default() -> Self {
+        CompositorParams {
+            position: TextPosition::BottomRight,
+            scale: 0.3,
+            opacity: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChromaKeyParams {
+    pub key: Rgb,
+    // distance in chroma from the key, 0 to 1, under which the foreground
+    // is fully keyed out
+    pub threshold: f64,
+    // distance past the threshold over which the foreground fades back in
+    pub softness: f64,
+}
+
+impl Default for ChromaKeyParams {
+    fn default() -> Self {
+        ChromaKeyParams {
+            key: Rgb(0, 177, 64),
+            threshold: 0.25,
+            softness: 0.1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MediaSourceParams {
     pub media_id: Option<MediaId>,
+    #[serde(default = "MediaSourceParams::default_playing")]
+    pub playing: bool,
+    #[serde(default = "MediaSourceParams::default_looping")]
+    pub looping: bool,
+    // seconds into the media to start playing from when opened, and the
+    // target of a seek whenever seek_seq changes
+    #[serde(default)]
+    pub position: f64,
+    #[serde(default)]
+    pub seek_seq: u64,
+}
+
+impl MediaSourceParams {
+    fn default_playing() -> bool {
+        true
+    }
+
+    fn default_looping() -> bool {
+        true
+    }
+}
+
+impl Default for MediaSourceParams {
+    fn default() -> Self {
+        MediaSourceParams {
+            media_id: None,
+            playing: MediaSourceParams::default_playing(),
+            looping: MediaSourceParams::default_looping(),
+            position: 0.0,
+            seek_seq: 0,
+        }
+    }
+}
+
+/// Several modules running as one, with some of their terminals exposed as
+/// the macro's own. Modules are referred to by their index in `modules`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MacroParams {
+    pub name: String,
+    pub modules: Vec<ModuleParams>,
+    pub connections: Vec<MacroConnection>,
+    pub inputs: Vec<MacroTerminal>,
+    pub outputs: Vec<MacroTerminal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct MacroConnection {
+    // (module, terminal) pairs
+    pub output: (usize, usize),
+    pub input: (usize, usize),
+    #[serde(default)]
+    pub adapter: ChannelAdapter,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct MacroTerminal {
+    pub module: usize,
+    pub terminal: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MacroIndication {
+    // the latest indication of each module
+    pub modules: Vec<Indication>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MediaSourceIndication {
+    // playhead in seconds from the start of the media
+    pub position: f64,
+    // None until the media has been opened, or if its length is unknown
+    pub duration: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LooperParams {
+    // gain applied to loop playback, the input always passes through
+    pub level: f64,
+}
+
+impl Default for LooperParams {
+    fn default() -> Self {
+        LooperParams {
+            level: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LooperIndication {
+    pub recording: bool,
+    pub overdubbing: bool,
+    pub playing: bool,
+    // length of each loop in seconds. the first loop sets the length all
+    // later loops are rounded to a multiple of
+    pub loops: Vec<f64>,
+    // 0 to 1, how far through the first loop playback is
+    pub position: f64,
+}
+
+// furthest the pitch shifter transposes, either way
+pub const PITCH_SHIFT_MAX_SEMITONES: f64 = 12.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PitchShiftParams {
+    pub semitones: f64,
+    // keep a voice's formants where they were as its pitch moves
+    pub preserve_formants: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReverbParams {
+    // impulse response, a WAV file in the media library
+    pub impulse: Option<MediaId>,
+    // 0 is fully dry, 1 fully wet
+    pub mix: f64,
+}
+
+impl Default for ReverbParams {
+    fn default() -> Self {
+        ReverbParams {
+            impulse: None,
+            mix: 0.3,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProfanityDelayParams {
+    // length of the delay in seconds
+    pub delay: f64,
+    // dumps the delay, jumping the program forward to live, whenever this
+    // changes
+    #[serde(default)]
+    pub dump_seq: u64,
+}
+
+impl Default for ProfanityDelayParams {
+    fn default() -> Self {
+        ProfanityDelayParams {
+            delay: 7.0,
+            dump_seq: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProfanityDelayIndication {
+    // seconds of delay currently held. drops to zero on a dump and grows back
+    // towards the set delay
+    pub delay: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SamplerParams {
+    // clip played on each trigger, any audio file in the media library
+    pub clip: Option<MediaId>,
+    // semitones, played faster or slower like a turntable
+    pub pitch: f64,
+    pub gain: f64,
+}
+
+impl Default for SamplerParams {
+    fn default() -> Self {
+        SamplerParams {
+            clip: None,
+            pitch: 0.0,
+            gain: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SequencerParams {
+    // tempo of the internal clock, which advances one step per sixteenth
+    // note. unused while the clock input is connected
+    pub bpm: f64,
+    pub steps: Vec<SequencerStep>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SequencerStep {
+    pub gate: bool,
+    // 0 to 1, sent on the pitch output for the length of the step
+    pub value: f64,
+    // 0 to 1, how much of the step the gate stays open for
+    pub length: f64,
+}
+
+impl Default for SequencerParams {
+    fn default() -> Self {
+        SequencerParams {
+            bpm: 120.0,
+            steps: (0..16)
+                .map(|i| SequencerStep {
+                    gate: i % 4 == 0,
+                    value: 0.5,
+                    length: 0.5,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SequencerIndication {
+    // step now playing, for highlighting
+    pub step: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -0,0 +1,252 @@
+use std::f64::consts::PI;
+
+/// Channels in a first order B-format signal, in ACN order with SN3D
+/// normalisation (AmbiX), as YouTube and most VR players expect.
+pub const CHANNELS: usize = 4;
+
+const W: usize = 0;
+const Y: usize = 1;
+const Z: usize = 2;
+const X: usize = 3;
+
+// spherical head model, see Brown & Duda, "A Structural Model for Binaural
+// Sound Synthesis" (1998)
+const HEAD_RADIUS: f64 = 0.0875;
+const SPEED_OF_SOUND: f64 = 343.0;
+const SHADOW_MIN_ALPHA: f64 = 0.1;
+const SHADOW_MIN_ANGLE: f64 = 150.0 * PI / 180.0;
+
+// virtual speakers on the corners of a cube, as azimuth and elevation in
+// degrees. the soundfield is decoded to these and each is rendered to the
+// ears from its direction
+const SPEAKERS: [(f64, f64); 8] = [
+    (45.0, 35.26), (135.0, 35.26), (-135.0, 35.26), (-45.0, 35.26),
+    (45.0, -35.26), (135.0, -35.26), (-135.0, -35.26), (-45.0, -35.26),
+];
+
+// max rE weighting of the first order components, trading a little
+// localisation for less of a source leaking into opposite speakers
+const FIRST_ORDER_WEIGHT: f64 = 0.577;
+
+// longer than the largest interaural delay, about 29 samples at 44.1kHz
+const HISTORY_LEN: usize = 64;
+
+/// B-format gains for a source at `azimuth` and `elevation` in radians.
+/// Azimuth is anticlockwise from straight ahead, so positive is to the
+/// left, and elevation is positive upwards.
+pub fn encode(azimuth: f64, elevation: f64) -> [f32; CHANNELS] {
+    let mut gains = [0.0; CHANNELS];
+    gains[W] = 1.0;
+    gains[Y] = (azimuth.sin() * elevation.cos()) as f32;
+    gains[Z] = elevation.sin() as f32;
+    gains[X] = (azimuth.cos() * elevation.cos()) as f32;
+    gains
+}
+
+// unit vector towards a direction, in the same axes as the B-format
+// channels: x ahead, y left, z up
+fn direction(azimuth: f64, elevation: f64) -> [f64; 3] {
+    [
+        azimuth.cos() * elevation.cos(),
+        azimuth.sin() * elevation.cos(),
+        elevation.sin(),
+    ]
+}
+
+// one virtual speaker as heard by one ear, a delay and a head shadow filter
+#[derive(Debug, Clone, Copy)]
+struct EarPath {
+    delay: f64,
+    b0: f32,
+    b1: f32,
+    a1: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl EarPath {
+    // `incidence` is the angle between the source and the ear's axis
+    fn new(incidence: f64, sample_rate: f64) -> Self {
+        let head_delay = HEAD_RADIUS / SPEED_OF_SOUND;
+
+        // sound reaches the near side of the head first, the far side after
+        // travelling around it. offset so that no delay is negative
+        let delay = if incidence < PI / 2.0 {
+            -head_delay * incidence.cos()
+        } else {
+            head_delay * (incidence - PI / 2.0)
+        } + head_delay;
+
+        // the head boosts highs on the near side and shadows them on the far
+        let alpha = (1.0 + SHADOW_MIN_ALPHA / 2.0)
+            + (1.0 - SHADOW_MIN_ALPHA / 2.0) * (incidence / SHADOW_MIN_ANGLE * PI).cos();
+
+        // bilinear transform of (1 + alpha s/2w0) / (1 + s/2w0)
+        let w0 = SPEED_OF_SOUND / HEAD_RADIUS;
+        let k = 2.0 * sample_rate / (2.0 * w0);
+        let a0 = 1.0 + k;
+
+        EarPath {
+            delay: delay * sample_rate,
+            b0: ((1.0 + alpha * k) / a0) as f32,
+            b1: ((1.0 - alpha * k) / a0) as f32,
+            a1: ((1.0 - k) / a0) as f32,
+            x1: 0.0,
+            y1: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 - self.a1 * self.y1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+}
+
+// Renders a first order soundfield for headphones. The soundfield is decoded
+// to a cube of virtual speakers, and each speaker reaches each ear through a
+// spherical head model giving it the time and level difference between the
+// ears for its direction.
+#[derive(Debug)]
+pub struct BinauralDecoder {
+    // per speaker decode weights for each B-format channel
+    decode: Vec<[f32; CHANNELS]>,
+    // per speaker, left ear then right
+    paths: Vec<[EarPath; 2]>,
+    // the last HISTORY_LEN feeds of each speaker
+    history: Vec<[f32; HISTORY_LEN]>,
+    pos: usize,
+}
+
+impl BinauralDecoder {
+    pub fn new(sample_rate: usize) -> Self {
+        let sample_rate = sample_rate as f64;
+        let scale = 1.0 / SPEAKERS.len() as f64;
+
+        let mut decode = Vec::with_capacity(SPEAKERS.len());
+        let mut paths = Vec::with_capacity(SPEAKERS.len());
+
+        for (azimuth, elevation) in SPEAKERS.iter() {
+            let [x, y, z] = direction(azimuth.to_radians(), elevation.to_radians());
+
+            let mut weights = [0.0; CHANNELS];
+            weights[W] = scale as f32;
+            weights[Y] = (scale * 3.0 * FIRST_ORDER_WEIGHT * y) as f32;
+            weights[Z] = (scale * 3.0 * FIRST_ORDER_WEIGHT * z) as f32;
+            weights[X] = (scale * 3.0 * FIRST_ORDER_WEIGHT * x) as f32;
+            decode.push(weights);
+
+            // the ears point straight out either side, along y
+            let left = y.acos();
+            let right = (-y).acos();
+            paths.push([EarPath::new(left, sample_rate), EarPath::new(right, sample_rate)]);
+        }
+
+        BinauralDecoder {
+            history: vec![[0.0; HISTORY_LEN]; SPEAKERS.len()],
+            decode,
+            paths,
+            pos: 0,
+        }
+    }
+
+    /// Renders interleaved B-format `input` to interleaved stereo `output`,
+    /// for a listener facing `yaw` radians anticlockwise from the front of
+    /// the soundfield.
+    pub fn process(&mut self, yaw: f64, input: &[f32], output: &mut [f32]) {
+        let (sin, cos) = (yaw.sin() as f32, yaw.cos() as f32);
+
+        for (frame, out) in input.chunks(CHANNELS).zip(output.chunks_mut(2)) {
+            // turning the head one way turns the soundfield the other
+            let mut frame = [frame[W], frame[Y], frame[Z], frame[X]];
+            let (x, y) = (frame[X], frame[Y]);
+            frame[X] = x * cos + y * sin;
+            frame[Y] = y * cos - x * sin;
+
+            self.pos = (self.pos + 1) % HISTORY_LEN;
+
+            let mut left = 0.0;
+            let mut right = 0.0;
+
+            for ((weights, history), paths) in self.decode.iter().zip(self.history.iter_mut()).zip(self.paths.iter_mut()) {
+                history[self.pos] = weights.iter().zip(frame.iter()).map(|(w, s)| w * s).sum();
+
+                left += paths[0].process(read_delayed(history, self.pos, paths[0].delay));
+                right += paths[1].process(read_delayed(history, self.pos, paths[1].delay));
+            }
+
+            out[0] = left;
+            out[1] = right;
+        }
+    }
+}
+
+// linearly interpolated read `delay` samples behind `pos`
+fn read_delayed(history: &[f32; HISTORY_LEN], pos: usize, delay: f64) -> f32 {
+    let whole = delay as usize;
+    let frac = (delay - whole as f64) as f32;
+
+    let a = history[(pos + HISTORY_LEN - whole) % HISTORY_LEN];
+    let b = history[(pos + HISTORY_LEN - whole - 1) % HISTORY_LEN];
+
+    a + (b - a) * frac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::noise;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    // decodes noise from a direction, returning the level at each ear
+    fn render(azimuth: f64, yaw: f64) -> (f32, f32) {
+        let source = noise(SAMPLE_RATE, 1);
+        let gains = encode(azimuth.to_radians(), 0.0);
+
+        let bformat = source.iter()
+            .flat_map(|sample| gains.iter().map(move |gain| gain * sample))
+            .collect::<Vec<_>>();
+
+        let mut output = vec![0.0; SAMPLE_RATE * 2];
+        BinauralDecoder::new(SAMPLE_RATE).process(yaw.to_radians(), &bformat, &mut output);
+
+        let level = |chan: usize| {
+            let sum = output.chunks(2).map(|frame| frame[chan] * frame[chan]).sum::<f32>();
+            (sum / SAMPLE_RATE as f32).sqrt()
+        };
+
+        (level(0), level(1))
+    }
+
+    #[test]
+    fn encodes_front_to_omni_and_x() {
+        let gains = encode(0.0, 0.0);
+        assert_eq!(1.0, gains[W]);
+        assert!(gains[Y].abs() < 1e-6);
+        assert!(gains[Z].abs() < 1e-6);
+        assert!((gains[X] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn front_is_centred() {
+        let (left, right) = render(0.0, 0.0);
+        assert!((left - right).abs() < left * 0.01, "left {} right {}", left, right);
+    }
+
+    #[test]
+    fn side_is_louder_in_near_ear() {
+        let (left, right) = render(90.0, 0.0);
+        assert!(left > right * 2.0, "left {} right {}", left, right);
+
+        let (left, right) = render(-90.0, 0.0);
+        assert!(right > left * 2.0, "left {} right {}", left, right);
+    }
+
+    #[test]
+    fn turning_left_moves_front_to_the_right() {
+        let (left, right) = render(0.0, 90.0);
+        assert!(right > left * 2.0, "left {} right {}", left, right);
+    }
+}
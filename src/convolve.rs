@@ -0,0 +1,169 @@
+use std::fmt::{self, Debug};
+use std::mem;
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use rustfft::{FFTplanner, FFT};
+
+// Uniformly partitioned overlap-save convolution. The impulse response is cut
+// into partitions of one block each and transformed up front. Each block of
+// input is transformed once, kept in a frequency domain delay line, and
+// multiplied against every partition, so the cost of a block grows with the
+// number of partitions rather than the length of the impulse in samples, and
+// output is produced with no latency beyond the block itself.
+pub struct Convolver {
+    block_len: usize,
+    forward: Arc<dyn FFT<f32>>,
+    inverse: Arc<dyn FFT<f32>>,
+    // spectra of each partition of the impulse, zero padded to two blocks
+    partitions: Vec<Vec<Complex<f32>>>,
+    // spectra of recent input windows. history[(newest + n) % len] is the
+    // window from n blocks ago
+    history: Vec<Vec<Complex<f32>>>,
+    newest: usize,
+    // the previous and current block of input
+    window: Vec<f32>,
+    scratch: Vec<Complex<f32>>,
+    accum: Vec<Complex<f32>>,
+}
+
+impl Debug for Convolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Convolver {{ block_len: {}, partitions: {} }}", self.block_len, self.partitions.len())
+    }
+}
+
+impl Convolver {
+    // transforming the impulse is expensive for long impulses, call this off
+    // the engine thread
+    pub fn new(impulse: &[f32], block_len: usize) -> Self {
+        let fft_len = block_len * 2;
+
+        let forward = FFTplanner::new(false).plan_fft(fft_len);
+        let inverse = FFTplanner::new(true).plan_fft(fft_len);
+
+        let mut scratch = vec![Complex::zero(); fft_len];
+
+        let partitions = impulse.chunks(block_len)
+            .map(|chunk| {
+                for (idx, value) in scratch.iter_mut().enumerate() {
+                    *value = Complex::new(chunk.get(idx).copied().unwrap_or(0.0), 0.0);
+                }
+
+                let mut spectrum = vec![Complex::zero(); fft_len];
+                forward.process(&mut scratch, &mut spectrum);
+                spectrum
+            })
+            .collect::<Vec<_>>();
+
+        // an empty impulse still needs one (silent) partition
+        let partitions = if partitions.is_empty() {
+            vec![vec![Complex::zero(); fft_len]]
+        } else {
+            partitions
+        };
+
+        Convolver {
+            block_len,
+            forward,
+            inverse,
+            history: vec![vec![Complex::zero(); fft_len]; partitions.len()],
+            partitions,
+            newest: 0,
+            window: vec![0.0; fft_len],
+            scratch,
+            accum: vec![Complex::zero(); fft_len],
+        }
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        let spectrum = self.block_len * 2 * mem::size_of::<Complex<f32>>();
+
+        // partitions and history, plus scratch and accum
+        spectrum * (self.partitions.len() + self.history.len() + 2)
+            + self.window.len() * mem::size_of::<f32>()
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        assert!(input.len() == self.block_len);
+        assert!(output.len() == self.block_len);
+
+        let block_len = self.block_len;
+        let fft_len = block_len * 2;
+        let partition_count = self.partitions.len();
+
+        // slide the input window along by one block
+        self.window.copy_within(block_len.., 0);
+        self.window[block_len..].copy_from_slice(input);
+
+        for (value, sample) in self.scratch.iter_mut().zip(&self.window) {
+            *value = Complex::new(*sample, 0.0);
+        }
+
+        // the oldest window's slot becomes the newest
+        self.newest = (self.newest + partition_count - 1) % partition_count;
+        self.forward.process(&mut self.scratch, &mut self.history[self.newest]);
+
+        for value in self.accum.iter_mut() {
+            *value = Complex::zero();
+        }
+
+        for (age, partition) in self.partitions.iter().enumerate() {
+            let window = &self.history[(self.newest + age) % partition_count];
+
+            for ((acc, x), h) in self.accum.iter_mut().zip(window).zip(partition) {
+                *acc += x * h;
+            }
+        }
+
+        self.inverse.process(&mut self.accum, &mut self.scratch);
+
+        // the first half is circular wraparound, only the second is valid
+        let scale = 1.0 / fft_len as f32;
+
+        for (out, value) in output.iter_mut().zip(&self.scratch[block_len..]) {
+            *out = value.re * scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::noise;
+
+    fn direct_convolve(signal: &[f32], impulse: &[f32]) -> Vec<f32> {
+        (0..signal.len())
+            .map(|n| {
+                (0..impulse.len())
+                    .filter(|k| *k <= n)
+                    .map(|k| signal[n - k] * impulse[k])
+                    .sum()
+            })
+            .collect()
+    }
+
+    // runs on noise, so that misaligned partitions can't cancel out
+    #[test]
+    fn matches_direct_convolution() {
+        let block_len = 64;
+
+        // spans several partitions with a partial one at the end
+        let impulse = noise(block_len * 5 / 2, 1);
+        let signal = noise(block_len * 8, 2);
+
+        let mut convolver = Convolver::new(&impulse, block_len);
+        let mut output = vec![0.0; signal.len()];
+
+        for (input, output) in signal.chunks(block_len).zip(output.chunks_mut(block_len)) {
+            convolver.process(input, output);
+        }
+
+        let expected = direct_convolve(&signal, &impulse);
+
+        for (idx, (expected, actual)) in expected.iter().zip(&output).enumerate() {
+            assert!((expected - actual).abs() < 1e-4, "sample {}: expected {}, got {}", idx, expected, actual);
+        }
+    }
+}
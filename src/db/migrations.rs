@@ -2,4 +2,8 @@ pub static MIGRATIONS: &'static [(i64, &'static str)] = &[
     (0, include_str!("migrations/0_init.sql")),
     (20200804, include_str!("migrations/20200804_create_media_tables.sql")),
     (20200805, include_str!("migrations/20200805_create_workspace_table.sql")),
+    (20200810, include_str!("migrations/20200810_create_stream_reports_table.sql")),
+    (20200811, include_str!("migrations/20200811_create_stream_loudness_table.sql")),
+    (20200812, include_str!("migrations/20200812_create_snapshots_table.sql")),
+    (20200813, include_str!("migrations/20200813_create_macros_table.sql")),
 ];
@@ -0,0 +1,12 @@
+CREATE TABLE stream_reports (
+    id INTEGER PRIMARY KEY NOT NULL,
+    destination TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    duration_secs REAL NOT NULL,
+    average_bitrate_kbps REAL NOT NULL,
+    peak_bitrate_kbps REAL NOT NULL,
+    dropped_frames INTEGER NOT NULL,
+    reconnects INTEGER NOT NULL,
+    loudness_average REAL NOT NULL,
+    loudness_peak REAL NOT NULL
+);
@@ -0,0 +1,6 @@
+CREATE TABLE stream_loudness (
+    report_id INTEGER NOT NULL REFERENCES stream_reports (id),
+    offset_secs INTEGER NOT NULL,
+    short_term REAL NOT NULL,
+    PRIMARY KEY (report_id, offset_secs)
+);
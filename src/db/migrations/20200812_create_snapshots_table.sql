@@ -0,0 +1,6 @@
+CREATE TABLE snapshots (
+    name TEXT PRIMARY KEY NOT NULL,
+    saved_at INTEGER NOT NULL,
+    module_count INTEGER NOT NULL,
+    serialized TEXT NOT NULL
+);
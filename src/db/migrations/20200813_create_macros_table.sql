@@ -0,0 +1,4 @@
+CREATE TABLE macros (
+    name TEXT PRIMARY KEY NOT NULL,
+    serialized TEXT NOT NULL
+);
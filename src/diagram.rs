@@ -0,0 +1,376 @@
+//! Signal flow diagrams of the current patch, for show documentation and
+//! handing a setup over to another operator. Graphviz DOT is laid out by
+//! Graphviz itself, the SVG keeps each module where its window is in the
+//! workspace so that it matches what operators see.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use mixlab_protocol::{LineType, ModuleId, ModuleParams, Terminal, WorkspaceState};
+
+// key params shown per module, the rest are left to the workspace itself
+const MAX_PARAMS: usize = 6;
+
+// longest param value shown before it is cut short, names and URLs can be
+// long enough to swamp the diagram
+const MAX_VALUE_LEN: usize = 32;
+
+const SVG_MARGIN: i32 = 20;
+const SVG_MODULE_WIDTH: i32 = 200;
+const SVG_TITLE_HEIGHT: i32 = 24;
+const SVG_ROW_HEIGHT: i32 = 16;
+
+struct Module<'a> {
+    id: ModuleId,
+    name: String,
+    params: Vec<(String, String)>,
+    group: Option<&'a str>,
+    inputs: &'a [Terminal],
+    outputs: &'a [Terminal],
+}
+
+fn modules(state: &WorkspaceState) -> Vec<Module> {
+    let inputs = state.inputs.iter().map(|(id, terminals)| (*id, terminals.as_slice())).collect::<HashMap<_, _>>();
+    let outputs = state.outputs.iter().map(|(id, terminals)| (*id, terminals.as_slice())).collect::<HashMap<_, _>>();
+    let groups = state.groups.iter().map(|(id, group)| (*id, group.as_str())).collect::<HashMap<_, _>>();
+
+    let mut modules = state.modules.iter()
+        .map(|(id, params)| Module {
+            id: *id,
+            name: module_name(params),
+            params: key_params(params),
+            group: groups.get(id).copied(),
+            inputs: inputs.get(id).copied().unwrap_or_default(),
+            outputs: outputs.get(id).copied().unwrap_or_default(),
+        })
+        .collect::<Vec<_>>();
+
+    modules.sort_by_key(|module| module.id);
+    modules
+}
+
+// the variant name, as the frontend uses to look up a module's title
+pub(crate) fn module_name(params: &ModuleParams) -> String {
+    format!("{:?}", params).chars()
+        .take_while(|c| c.is_alphanumeric())
+        .collect()
+}
+
+// top level fields of the params which are plain values. nested structures
+// such as mixer channels don't fit in a diagram
+fn key_params(params: &ModuleParams) -> Vec<(String, String)> {
+    let value = serde_json::to_value(params).expect("serde_json::to_value");
+
+    let fields = match value {
+        serde_json::Value::Object(variant) => variant.into_iter().next().map(|(_, fields)| fields),
+        _ => None,
+    };
+
+    let fields = match fields {
+        Some(serde_json::Value::Object(fields)) => fields,
+        Some(value) => return format_value(&value).map(|value| vec![("value".to_owned(), value)]).unwrap_or_default(),
+        None => return Vec::new(),
+    };
+
+    fields.iter()
+        .filter_map(|(name, value)| Some((name.clone(), format_value(value)?)))
+        .take(MAX_PARAMS)
+        .collect()
+}
+
+fn format_value(value: &serde_json::Value) -> Option<String> {
+    let value = match value {
+        serde_json::Value::Bool(value) => value.to_string(),
+        serde_json::Value::Number(value) => match value.as_f64() {
+            Some(float) if value.is_f64() => format!("{:.3}", float).trim_end_matches('0').trim_end_matches('.').to_owned(),
+            _ => value.to_string(),
+        },
+        serde_json::Value::String(value) if !value.is_empty() => value.clone(),
+        _ => return None,
+    };
+
+    if value.chars().count() > MAX_VALUE_LEN {
+        Some(value.chars().take(MAX_VALUE_LEN - 1).chain(Some('…')).collect())
+    } else {
+        Some(value)
+    }
+}
+
+fn terminal_label(terminal: &Terminal) -> String {
+    terminal.label()
+        .map(str::to_owned)
+        .unwrap_or_else(|| line_type_name(terminal.line_type()).to_owned())
+}
+
+fn line_type_name(line_type: LineType) -> &'static str {
+    match line_type {
+        LineType::Mono => "mono",
+        LineType::Stereo => "stereo",
+        LineType::Video => "video",
+        LineType::Control => "control",
+        LineType::Ambisonic => "ambisonic",
+    }
+}
+
+fn line_type_color(line_type: LineType) -> &'static str {
+    match line_type {
+        LineType::Mono => "#4a4870",
+        LineType::Stereo => "#2f7fc1",
+        LineType::Video => "#c1452f",
+        LineType::Control => "#3f9f56",
+        LineType::Ambisonic => "#9b4fc1",
+    }
+}
+
+// connections are typed by the output feeding them, as a control output
+// feeding a mono input still carries control
+fn connection_type(state: &WorkspaceState, module: ModuleId, index: usize) -> LineType {
+    state.outputs.iter()
+        .find(|(id, _)| *id == module)
+        .and_then(|(_, outputs)| outputs.get(index))
+        .map(Terminal::line_type)
+        .unwrap_or(LineType::Mono)
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn to_dot(state: &WorkspaceState) -> String {
+    let mut dot = String::new();
+
+    dot.push_str("digraph patch {\n");
+    dot.push_str("    rankdir=LR;\n");
+    dot.push_str("    node [shape=plaintext, fontname=\"sans-serif\", fontsize=10];\n");
+    dot.push_str("    edge [fontname=\"sans-serif\", fontsize=8];\n");
+
+    for module in modules(state) {
+        let rows = module.inputs.len().max(module.outputs.len()).max(1);
+
+        let mut body = format!("<b>{}</b>", escape_xml(&module.name));
+
+        for (name, value) in &module.params {
+            write!(body, "<br/>{}: {}", escape_xml(name), escape_xml(value)).unwrap();
+        }
+
+        if let Some(group) = module.group {
+            write!(body, "<br/><i>group: {}</i>", escape_xml(group)).unwrap();
+        }
+
+        writeln!(dot, "    m{} [label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\">", (module.id.0).get()).unwrap();
+
+        for row in 0..rows {
+            dot.push_str("        <tr>");
+
+            match module.inputs.get(row) {
+                Some(input) => write!(dot, "<td port=\"i{}\">{}</td>", row, escape_xml(&terminal_label(input))).unwrap(),
+                None => dot.push_str("<td border=\"0\"></td>"),
+            }
+
+            if row == 0 {
+                write!(dot, "<td rowspan=\"{}\">{}</td>", rows, body).unwrap();
+            }
+
+            match module.outputs.get(row) {
+                Some(output) => write!(dot, "<td port=\"o{}\">{}</td>", row, escape_xml(&terminal_label(output))).unwrap(),
+                None => dot.push_str("<td border=\"0\"></td>"),
+            }
+
+            dot.push_str("</tr>\n");
+        }
+
+        dot.push_str("    </table>>];\n");
+    }
+
+    let mut connections = state.connections.clone();
+    connections.sort();
+
+    for (input, output) in connections {
+        let line_type = connection_type(state, output.module_id(), output.index());
+
+        writeln!(dot, "    m{}:o{}:e -> m{}:i{}:w [color=\"{}\", fontcolor=\"{}\", label=\"{}\"];",
+            (output.module_id().0).get(), output.index(),
+            (input.module_id().0).get(), input.index(),
+            line_type_color(line_type), line_type_color(line_type), line_type_name(line_type),
+        ).unwrap();
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+pub fn to_svg(state: &WorkspaceState) -> String {
+    let modules = modules(state);
+
+    let positions = state.geometry.iter()
+        .map(|(id, geometry)| (*id, geometry.position))
+        .collect::<HashMap<_, _>>();
+
+    // boxes are laid out as in the workspace, shifted so that the top left
+    // module sits at the margin
+    let min_x = positions.values().map(|position| position.x).min().unwrap_or(0);
+    let min_y = positions.values().map(|position| position.y).min().unwrap_or(0);
+
+    struct Layout {
+        x: i32,
+        y: i32,
+        height: i32,
+    }
+
+    let layout = modules.iter()
+        .map(|module| {
+            let position = positions.get(&module.id).copied().unwrap_or_default();
+            let terminal_rows = module.inputs.len().max(module.outputs.len()) as i32;
+            let param_rows = module.params.len() as i32 + module.group.is_some() as i32;
+
+            (module.id, Layout {
+                x: position.x - min_x + SVG_MARGIN,
+                y: position.y - min_y + SVG_MARGIN,
+                height: SVG_TITLE_HEIGHT + (terminal_rows + param_rows).max(1) * SVG_ROW_HEIGHT + 4,
+            })
+        })
+        .collect::<HashMap<_, _>>();
+
+    let width = layout.values().map(|layout| layout.x + SVG_MODULE_WIDTH).max().unwrap_or(0) + SVG_MARGIN;
+    let height = layout.values().map(|layout| layout.y + layout.height).max().unwrap_or(0) + SVG_MARGIN;
+
+    let terminal_y = |layout: &Layout, index: usize| layout.y + SVG_TITLE_HEIGHT + index as i32 * SVG_ROW_HEIGHT + SVG_ROW_HEIGHT / 2;
+
+    let mut svg = String::new();
+
+    writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"sans-serif\" font-size=\"10\">",
+        width, height, width, height).unwrap();
+    writeln!(svg, "<rect width=\"{}\" height=\"{}\" fill=\"#f0f0f5\"/>", width, height).unwrap();
+
+    for module in &modules {
+        let layout = &layout[&module.id];
+
+        writeln!(svg, "<g id=\"module-{}\">", (module.id.0).get()).unwrap();
+        writeln!(svg, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#ffffff\" stroke=\"#8d8bb0\"/>",
+            layout.x, layout.y, SVG_MODULE_WIDTH, layout.height).unwrap();
+        writeln!(svg, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#8d8bb0\"/>",
+            layout.x, layout.y, SVG_MODULE_WIDTH, SVG_TITLE_HEIGHT).unwrap();
+        writeln!(svg, "<text x=\"{}\" y=\"{}\" fill=\"#ffffff\" font-size=\"12\" font-weight=\"bold\">{}</text>",
+            layout.x + 8, layout.y + 16, escape_xml(&module.name)).unwrap();
+
+        for (index, input) in module.inputs.iter().enumerate() {
+            let y = terminal_y(layout, index);
+            writeln!(svg, "<circle cx=\"{}\" cy=\"{}\" r=\"4\" fill=\"{}\"/>", layout.x, y, line_type_color(input.line_type())).unwrap();
+            writeln!(svg, "<text x=\"{}\" y=\"{}\" fill=\"#4a4870\">{}</text>", layout.x + 8, y + 3, escape_xml(&terminal_label(input))).unwrap();
+        }
+
+        for (index, output) in module.outputs.iter().enumerate() {
+            let x = layout.x + SVG_MODULE_WIDTH;
+            let y = terminal_y(layout, index);
+            writeln!(svg, "<circle cx=\"{}\" cy=\"{}\" r=\"4\" fill=\"{}\"/>", x, y, line_type_color(output.line_type())).unwrap();
+            writeln!(svg, "<text x=\"{}\" y=\"{}\" fill=\"#4a4870\" text-anchor=\"end\">{}</text>", x - 8, y + 3, escape_xml(&terminal_label(output))).unwrap();
+        }
+
+        let terminal_rows = module.inputs.len().max(module.outputs.len());
+
+        let params = module.params.iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .chain(module.group.map(|group| format!("group: {}", group)));
+
+        for (row, param) in params.enumerate() {
+            let y = terminal_y(layout, terminal_rows + row);
+            writeln!(svg, "<text x=\"{}\" y=\"{}\" fill=\"#8d8bb0\">{}</text>", layout.x + 8, y + 3, escape_xml(&param)).unwrap();
+        }
+
+        svg.push_str("</g>\n");
+    }
+
+    let mut connections = state.connections.clone();
+    connections.sort();
+
+    for (input, output) in connections {
+        let (from, to) = match (layout.get(&output.module_id()), layout.get(&input.module_id())) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+
+        let line_type = connection_type(state, output.module_id(), output.index());
+
+        let (x1, y1) = (from.x + SVG_MODULE_WIDTH, terminal_y(from, output.index()));
+        let (x2, y2) = (to.x, terminal_y(to, input.index()));
+        let bend = ((x2 - x1).abs() / 2).max(40);
+
+        writeln!(svg, "<path d=\"M {} {} C {} {}, {} {}, {} {}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"><title>{}</title></path>",
+            x1, y1, x1 + bend, y1, x2 - bend, y2, x2, y2,
+            line_type_color(line_type), line_type_name(line_type)).unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use mixlab_protocol::{Coords, InputId, OutputId, OscillatorParams, OverrunPolicy, WindowGeometry};
+
+    use super::*;
+
+    fn module(id: usize) -> ModuleId {
+        ModuleId(NonZeroUsize::new(id).unwrap())
+    }
+
+    fn geometry(x: i32, y: i32) -> WindowGeometry {
+        WindowGeometry { position: Coords { x, y }, z_index: 0 }
+    }
+
+    // oscillator(1) -> panner(2)
+    fn state() -> WorkspaceState {
+        WorkspaceState {
+            modules: vec![
+                (module(1), ModuleParams::Oscillator(OscillatorParams::default())),
+                (module(2), ModuleParams::StereoPanner(())),
+            ],
+            geometry: vec![(module(1), geometry(100, 100)), (module(2), geometry(400, 120))],
+            connections: vec![(InputId(module(2), 0), OutputId(module(1), 0))],
+            inputs: vec![(module(1), vec![]), (module(2), vec![LineType::Mono.unlabeled()])],
+            outputs: vec![(module(1), vec![LineType::Mono.labeled("<out>")]), (module(2), vec![LineType::Stereo.unlabeled()])],
+            indications: vec![],
+            safety: vec![],
+            groups: vec![(module(2), "talk & music".to_owned())],
+            bypassed: vec![],
+            adapters: vec![],
+            midi_mappings: vec![],
+            surface: vec![],
+            viewer_links: vec![],
+            freezes: vec![],
+            overrun_policy: OverrunPolicy::Lag,
+            session_group: None,
+        }
+    }
+
+    #[test]
+    fn key_params_are_plain_top_level_values() {
+        let params = key_params(&ModuleParams::Oscillator(OscillatorParams::default()));
+
+        assert_eq!(vec![("freq".to_owned(), "100".to_owned()), ("waveform".to_owned(), "Sine".to_owned())], params);
+    }
+
+    #[test]
+    fn dot_connects_terminal_ports_by_type() {
+        let dot = to_dot(&state());
+
+        assert!(dot.contains("m1:o0:e -> m2:i0:w [color=\"#4a4870\", fontcolor=\"#4a4870\", label=\"mono\"];"));
+        assert!(dot.contains("<td port=\"o0\">&lt;out&gt;</td>"));
+        assert!(dot.contains("group: talk &amp; music"));
+    }
+
+    #[test]
+    fn svg_keeps_workspace_layout() {
+        let svg = to_svg(&state());
+
+        // the top left module is moved to the margin, the rest keep their
+        // position relative to it
+        assert!(svg.contains("<g id=\"module-1\">\n<rect x=\"20\" y=\"20\""));
+        assert!(svg.contains("<g id=\"module-2\">\n<rect x=\"320\" y=\"40\""));
+    }
+}
@@ -0,0 +1,79 @@
+// Building blocks shared between audio modules.
+
+use std::mem;
+
+use crate::engine::{Sample, CHANNELS};
+
+/// One pole smoothing coefficient for a time constant in seconds, instant
+/// for zero.
+pub fn smoothing_coeff(secs: f64, sample_rate: f64) -> f64 {
+    if secs > 0.0 {
+        1.0 - (-1.0 / (secs * sample_rate)).exp()
+    } else {
+        1.0
+    }
+}
+
+/// Ring buffer of stereo frames which is read back at a fractional delay,
+/// interpolating between the frames either side.
+#[derive(Debug)]
+pub struct DelayLine {
+    frames: Vec<[Sample; CHANNELS]>,
+    write: usize,
+}
+
+impl DelayLine {
+    pub fn new(max_delay: usize) -> Self {
+        DelayLine {
+            // one extra frame for the frame just written, and one for
+            // interpolating at the maximum delay
+            frames: vec![[0.0; CHANNELS]; max_delay + 2],
+            write: 0,
+        }
+    }
+
+    pub fn write(&mut self, frame: &[Sample]) {
+        self.write = (self.write + 1) % self.frames.len();
+        self.frames[self.write].copy_from_slice(frame);
+    }
+
+    /// Reads the frame `delay` samples before the one last written.
+    pub fn read(&self, delay: f64) -> [Sample; CHANNELS] {
+        let len = self.frames.len();
+        let whole = delay.floor() as usize;
+        let frac = (delay - whole as f64) as Sample;
+
+        let newer = self.frames[(self.write + len - whole) % len];
+        let older = self.frames[(self.write + len - whole - 1) % len];
+
+        let mut frame = [0.0; CHANNELS];
+
+        for chan in 0..CHANNELS {
+            frame[chan] = newer[chan] + (older[chan] - newer[chan]) * frac;
+        }
+
+        frame
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.frames.len() * mem::size_of::<[Sample; CHANNELS]>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_frames_written_delay_samples_ago() {
+        let mut line = DelayLine::new(10);
+
+        for i in 0..20 {
+            line.write(&[i as Sample, -i as Sample]);
+        }
+
+        assert_eq!([19.0, -19.0], line.read(0.0));
+        assert_eq!([9.0, -9.0], line.read(10.0));
+        assert_eq!([14.5, -14.5], line.read(4.5));
+    }
+}
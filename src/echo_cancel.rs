@@ -0,0 +1,301 @@
+use std::fmt::{self, Debug};
+use std::mem;
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use rustfft::{FFTplanner, FFT};
+
+// overall adaptation step, shared out between partitions
+const STEP: f32 = 0.5;
+
+// smoothing of the per bin reference power the step is normalised by
+const POWER_SMOOTHING: f32 = 0.7;
+
+// keeps the step finite for bins the reference has next to nothing in. in
+// the units of an unnormalised transform, per sample of transform length
+const REGULARIZATION: f32 = 1e-6;
+
+// capture louder than this much of the recent reference peak can't be echo
+// alone, so the near end must be talking too (the Geigel test)
+const DOUBLE_TALK_THRESHOLD: f32 = 0.5;
+
+// blocks adaptation stays frozen for after double talk was last seen, as
+// speech has gaps much shorter than this
+const DOUBLE_TALK_HANGOVER: usize = 15;
+
+// reference quieter than this gives nothing useful to adapt to
+const SILENCE_PEAK: f32 = 1e-4;
+
+// smoothing of the echo reduction estimate, per block
+const REDUCTION_SMOOTHING: f32 = 0.95;
+
+// Removes echo of a reference signal from a capture of it, as when a remote
+// guest's microphone picks up the return feed from their speakers. A
+// partitioned block frequency domain adaptive filter, laid out as the
+// Convolver is, learns the path from reference to capture and its estimate of
+// the echo is subtracted. Adaptation is normalised per bin by reference power
+// and frozen while the near end is talking, so the filter doesn't learn to
+// cancel the guest themselves.
+pub struct EchoCanceller {
+    block_len: usize,
+    forward: Arc<dyn FFT<f32>>,
+    inverse: Arc<dyn FFT<f32>>,
+    // spectra of each partition of the estimated echo path
+    weights: Vec<Vec<Complex<f32>>>,
+    // spectra of recent reference windows, as in Convolver
+    history: Vec<Vec<Complex<f32>>>,
+    // peak level of the newest block of each reference window
+    peaks: Vec<f32>,
+    newest: usize,
+    power: Vec<f32>,
+    // the previous and current block of reference
+    window: Vec<f32>,
+    // partitions are constrained back to one block each in turn, rather
+    // than all of them every block
+    constrain_next: usize,
+    hangover: usize,
+    capture_power: f32,
+    error_power: f32,
+    scratch: Vec<Complex<f32>>,
+    accum: Vec<Complex<f32>>,
+    gradient: Vec<Complex<f32>>,
+}
+
+impl Debug for EchoCanceller {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EchoCanceller {{ block_len: {}, partitions: {} }}", self.block_len, self.weights.len())
+    }
+}
+
+impl EchoCanceller {
+    pub fn new(tail_len: usize, block_len: usize) -> Self {
+        let fft_len = block_len * 2;
+        let partition_count = ((tail_len + block_len - 1) / block_len).max(1);
+
+        EchoCanceller {
+            block_len,
+            forward: FFTplanner::new(false).plan_fft(fft_len),
+            inverse: FFTplanner::new(true).plan_fft(fft_len),
+            weights: vec![vec![Complex::zero(); fft_len]; partition_count],
+            history: vec![vec![Complex::zero(); fft_len]; partition_count],
+            peaks: vec![0.0; partition_count],
+            newest: 0,
+            power: vec![0.0; fft_len],
+            window: vec![0.0; fft_len],
+            constrain_next: 0,
+            hangover: 0,
+            capture_power: 0.0,
+            error_power: 0.0,
+            scratch: vec![Complex::zero(); fft_len],
+            accum: vec![Complex::zero(); fft_len],
+            gradient: vec![Complex::zero(); fft_len],
+        }
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        let spectrum = self.block_len * 2 * mem::size_of::<Complex<f32>>();
+
+        // weights and history, plus scratch, accum and gradient
+        spectrum * (self.weights.len() + self.history.len() + 3)
+            + (self.power.len() + self.window.len()) * mem::size_of::<f32>()
+    }
+
+    // whether the near end was heard talking over the reference in the last
+    // block, which holds adaptation off
+    pub fn double_talk(&self) -> bool {
+        self.hangover > 0
+    }
+
+    // how far below the capture its echo has been brought, smoothed over
+    // recent blocks
+    pub fn reduction_db(&self) -> f32 {
+        if self.capture_power <= 0.0 || self.error_power <= 0.0 {
+            return 0.0;
+        }
+
+        (10.0 * (self.capture_power / self.error_power).log10()).max(0.0)
+    }
+
+    pub fn process(&mut self, reference: &[f32], capture: &[f32], output: &mut [f32]) {
+        assert!(reference.len() == self.block_len);
+        assert!(capture.len() == self.block_len);
+        assert!(output.len() == self.block_len);
+
+        let block_len = self.block_len;
+        let fft_len = block_len * 2;
+        let partition_count = self.weights.len();
+        let scale = 1.0 / fft_len as f32;
+
+        // slide the reference window along by one block
+        self.window.copy_within(block_len.., 0);
+        self.window[block_len..].copy_from_slice(reference);
+
+        for (value, sample) in self.scratch.iter_mut().zip(&self.window) {
+            *value = Complex::new(*sample, 0.0);
+        }
+
+        self.newest = (self.newest + partition_count - 1) % partition_count;
+        self.forward.process(&mut self.scratch, &mut self.history[self.newest]);
+        self.peaks[self.newest] = peak(reference);
+
+        for (power, x) in self.power.iter_mut().zip(&self.history[self.newest]) {
+            *power = POWER_SMOOTHING * *power + (1.0 - POWER_SMOOTHING) * x.norm_sqr();
+        }
+
+        // estimate the echo by filtering the reference through the path
+        // learned so far
+        for value in self.accum.iter_mut() {
+            *value = Complex::zero();
+        }
+
+        for (age, weights) in self.weights.iter().enumerate() {
+            let window = &self.history[(self.newest + age) % partition_count];
+
+            for ((acc, x), w) in self.accum.iter_mut().zip(window).zip(weights) {
+                *acc += x * w;
+            }
+        }
+
+        self.inverse.process(&mut self.accum, &mut self.scratch);
+
+        let mut capture_energy = 0.0;
+        let mut error_energy = 0.0;
+
+        for ((out, sample), echo) in output.iter_mut().zip(capture).zip(&self.scratch[block_len..]) {
+            *out = sample - echo.re * scale;
+            capture_energy += sample * sample;
+            error_energy += *out * *out;
+        }
+
+        let reference_peak = self.peaks.iter().copied().fold(0.0, f32::max);
+
+        if peak(capture) > DOUBLE_TALK_THRESHOLD * reference_peak {
+            self.hangover = DOUBLE_TALK_HANGOVER;
+        } else if self.hangover > 0 {
+            self.hangover -= 1;
+        }
+
+        if self.hangover > 0 || reference_peak < SILENCE_PEAK {
+            return;
+        }
+
+        // the reduction is only meaningful while there's echo to reduce
+        self.capture_power = REDUCTION_SMOOTHING * self.capture_power + (1.0 - REDUCTION_SMOOTHING) * capture_energy;
+        self.error_power = REDUCTION_SMOOTHING * self.error_power + (1.0 - REDUCTION_SMOOTHING) * error_energy;
+
+        // error is the second half of the window, as the estimate was
+        for (idx, value) in self.scratch.iter_mut().enumerate() {
+            *value = match idx {
+                idx if idx < block_len => Complex::zero(),
+                idx => Complex::new(output[idx - block_len], 0.0),
+            };
+        }
+
+        self.forward.process(&mut self.scratch, &mut self.gradient);
+
+        let step = STEP / partition_count as f32;
+        let regularization = REGULARIZATION * fft_len as f32;
+
+        for (gradient, power) in self.gradient.iter_mut().zip(&self.power) {
+            *gradient = *gradient * (step / (power + regularization));
+        }
+
+        for (age, weights) in self.weights.iter_mut().enumerate() {
+            let window = &self.history[(self.newest + age) % partition_count];
+
+            for ((w, x), g) in weights.iter_mut().zip(window).zip(&self.gradient) {
+                *w += x.conj() * g;
+            }
+        }
+
+        // an unconstrained partition grows a circular tail that would wrap
+        // round into the estimate, so cut it back to one block
+        let weights = &mut self.weights[self.constrain_next];
+
+        self.inverse.process(weights, &mut self.scratch);
+
+        for (idx, value) in self.scratch.iter_mut().enumerate() {
+            *value = match idx {
+                idx if idx < block_len => *value * scale,
+                _ => Complex::zero(),
+            };
+        }
+
+        self.forward.process(&mut self.scratch, weights);
+        self.constrain_next = (self.constrain_next + 1) % partition_count;
+    }
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0, |peak, sample| peak.max(sample.abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::noise;
+
+    fn energy(samples: &[f32]) -> f32 {
+        samples.iter().map(|sample| sample * sample).sum()
+    }
+
+    // a quiet, decaying echo path arriving some way into the tail
+    fn echo_path(len: usize, delay: usize) -> Vec<f32> {
+        noise(len, 3).iter()
+            .enumerate()
+            .map(|(idx, tap)| match idx {
+                idx if idx < delay => 0.0,
+                idx => tap * 0.1 * (-((idx - delay) as f32) / 20.0).exp(),
+            })
+            .collect()
+    }
+
+    fn run(canceller: &mut EchoCanceller, reference: &[f32], capture: &[f32], block_len: usize) -> Vec<f32> {
+        let mut output = vec![0.0; capture.len()];
+
+        let blocks = reference.chunks(block_len)
+            .zip(capture.chunks(block_len))
+            .zip(output.chunks_mut(block_len));
+
+        for ((reference, capture), output) in blocks {
+            canceller.process(reference, capture, output);
+        }
+
+        output
+    }
+
+    #[test]
+    fn cancels_echo_of_reference() {
+        let block_len = 64;
+        let path = echo_path(block_len * 3, 40);
+
+        let reference = noise(block_len * 600, 1);
+
+        let capture = (0..reference.len())
+            .map(|n| (0..path.len()).filter(|k| *k <= n).map(|k| reference[n - k] * path[k]).sum())
+            .collect::<Vec<f32>>();
+
+        let mut canceller = EchoCanceller::new(block_len * 4, block_len);
+        let output = run(&mut canceller, &reference, &capture, block_len);
+
+        let tail = capture.len() - block_len * 50;
+        let reduction = 10.0 * (energy(&capture[tail..]) / energy(&output[tail..])).log10();
+
+        assert!(reduction > 20.0, "only reduced echo by {} dB", reduction);
+        assert!(!canceller.double_talk());
+    }
+
+    #[test]
+    fn passes_near_end_through() {
+        let block_len = 64;
+        let reference = vec![0.0; block_len * 20];
+        let capture = noise(reference.len(), 2);
+
+        let mut canceller = EchoCanceller::new(block_len * 4, block_len);
+        let output = run(&mut canceller, &reference, &capture, block_len);
+
+        assert_eq!(output, capture);
+        assert!(canceller.double_talk());
+    }
+}
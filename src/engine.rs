@@ -3,35 +3,59 @@ use std::collections::{HashMap, HashSet};
 use std::f32;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
-use std::sync::mpsc::{self, SyncSender, Receiver, RecvTimeoutError, TrySendError, TryRecvError};
+use std::sync::mpsc::{self, Sender, SyncSender, Receiver, RecvTimeoutError, TrySendError, TryRecvError};
 use std::thread;
 use std::time::{Instant, Duration};
 
 use futures::future;
 use futures::stream::{Stream, StreamExt};
+use serde::{Serialize, Deserialize};
 use tokio::runtime;
 use tokio::sync::{oneshot, broadcast, watch};
 
-use mixlab_protocol::{ModuleId, InputId, OutputId, WorkspaceState, ServerUpdate, Indication, ClientSequence, WorkspaceMessage, WorkspaceOp, PerformanceInfo};
+use mixlab_protocol::{ModuleId, ModuleParams, ChannelAdapter, InputId, OutputId, WindowGeometry, WorkspaceState, ServerUpdate, Indication, ClientSequence, WorkspaceMessage, WorkspaceOp, PerformanceInfo, ModuleSafety, MidiMapping, OverrunPolicy, ViewerLink, Freeze, FreezeState, MediaId, MediaSourceParams, LineType, Coords, ConnectionError};
 
+use crate::module::PriorityClass;
+use crate::persist;
 use crate::project::ProjectBaseRef;
 use crate::util::Sequence;
+use crate::viewer;
 
+mod buffers;
+mod collapse;
+mod conform;
+mod freeze;
 mod io;
 mod module;
+mod policy;
+mod pool;
+mod replay;
+mod run_order;
+mod schedule;
+mod smooth;
+mod template;
 mod timing;
 mod workspace;
 
+use buffers::BufferPool;
+use pool::WorkerPool;
+use replay::RecordedMessage;
+use schedule::{Schedule, Realtime, Offline, NotReady};
 use timing::{EngineStat, TickStat};
-use workspace::SyncWorkspace;
-
-pub use io::{InputRef, OutputRef, Output, VideoFrame};
-pub use module::{ModuleCtx, DynModuleHost};
+use workspace::{ConnectError, Retargeted, SyncWorkspace, Workspace};
+
+pub use conform::DEFAULT_FPS;
+pub use freeze::encode_wav;
+pub use io::{InputRef, OutputRef, Output, VideoFrame, ControlRef};
+pub use module::{ModuleCtx, ModuleHost, DynModuleHost, DynModuleHostT, host as host_module};
+pub use policy::{MonitorPolicy, DuckRequest};
+pub use replay::{Recorder, Recording, RecordingError, tap_audio, tap_video, tap_info};
+pub use smooth::{Smoothed, DEFAULT_SMOOTHING_MS};
 pub use workspace::WorkspaceEmbryo;
 
 pub type Sample = f32;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct SessionId(NonZeroUsize);
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -50,13 +74,22 @@ impl PartialOrd for OpClock {
 }
 
 pub const CHANNELS: usize = 2;
+// ambisonic lines carry first order B-format, see ambisonics::CHANNELS
+pub const AMBISONIC_CHANNELS: usize = crate::ambisonics::CHANNELS;
 pub const SAMPLE_RATE: usize = 44100;
 pub const TICKS_PER_SECOND: usize = 60;
 pub const SAMPLES_PER_TICK: usize = SAMPLE_RATE / TICKS_PER_SECOND;
+// control signals carry one value per this many sample frames
+pub const CONTROL_PERIOD: usize = 15;
+pub const CONTROL_SAMPLES_PER_TICK: usize = SAMPLES_PER_TICK / CONTROL_PERIOD;
 
 pub enum EngineMessage {
-    ConnectSession(oneshot::Sender<(SessionId, WorkspaceState, EngineEvents)>),
+    ConnectSession(Option<String>, oneshot::Sender<(SessionId, WorkspaceState, EngineEvents)>),
+    DisconnectSession(SessionId),
     Workspace(SessionId, WorkspaceMessage),
+    LoadWorkspace(SessionId, persist::Workspace),
+    // a render saved to the media library, or None if saving failed
+    FreezeRendered(OutputId, Option<MediaId>),
 }
 
 #[derive(Clone)]
@@ -70,7 +103,7 @@ pub struct EngineSession {
     cmd_tx: SyncSender<EngineMessage>,
 }
 
-pub fn start(tokio_runtime: runtime::Handle, workspace: WorkspaceEmbryo, base: ProjectBaseRef) -> EngineHandle {
+pub fn start(tokio_runtime: runtime::Handle, workspace: WorkspaceEmbryo, base: ProjectBaseRef, recorder: Option<Recorder>) -> EngineHandle {
     let (cmd_tx, cmd_rx) = mpsc::sync_channel(8);
     let (log_tx, _) = broadcast::channel(64);
     let (perf_tx, perf_rx) = watch::channel(None);
@@ -79,22 +112,113 @@ pub fn start(tokio_runtime: runtime::Handle, workspace: WorkspaceEmbryo, base: P
         // enter the tokio runtime context for the engine thread
         // this allows modules to spawn async tasks
         tokio_runtime.enter(|| {
-            let mut engine = Engine {
-                cmd_rx,
-                log_tx,
-                perf_tx,
-                session_seq: Sequence::new(),
-                workspace: workspace.spawn(base.clone()),
-                base,
-            };
+            let mut engine = Engine::new(cmd_rx, log_tx, perf_tx, workspace.spawn(base.clone()), base);
+
+            if let Some(recorder) = recorder {
+                replay::start_recording(recorder, &engine.workspace.borrow().to_persist());
+            }
 
-            engine.run();
+            // realtime ticks never wait on modules, so this only returns
+            // once every handle is gone
+            let _ = engine.run(Realtime::new());
         });
     });
 
     EngineHandle { cmd_tx, perf_rx }
 }
 
+/// Runs a recording through a fresh engine as fast as possible, returning
+/// every workspace update it produced along with the tick it happened in.
+pub async fn replay(tokio_runtime: runtime::Handle, mut recording: Recording, base: ProjectBaseRef) -> Vec<(u64, ServerUpdate)> {
+    let (result_tx, result_rx) = oneshot::channel();
+
+    thread::spawn(move || {
+        tokio_runtime.enter(|| {
+            // nothing is ever sent to a replaying engine, all of its input
+            // comes from the recording
+            let (_, cmd_rx) = mpsc::sync_channel(8);
+            let (log_tx, log_rx) = broadcast::channel(4096);
+            let (perf_tx, _) = watch::channel(None);
+            let (workspace, _) = WorkspaceEmbryo::new(recording.workspace.clone());
+
+            // sources must be replaying before modules are created
+            replay::start_replay(&mut recording);
+
+            let mut engine = Engine::new(cmd_rx, log_tx, perf_tx, workspace.spawn(base.clone()), base);
+
+            let _ = result_tx.send(engine.run_replay(recording, log_rx));
+        });
+    });
+
+    result_rx.await.expect("replay thread")
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    // no output was given and nothing feeds an Output Device
+    NoOutput,
+    NotStereo(OutputId),
+    // a module the render waited on too long, such as a Media Source whose
+    // file stopped decoding
+    NotReady(NotReady),
+}
+
+/// Renders a workspace offline, capturing `output` for as long as ticks take
+/// to compute rather than in realtime. Without an output, renders whatever
+/// feeds the first Output Device. Without a length, renders until every
+/// Media Source has played to its end. Returns interleaved stereo samples.
+pub async fn render(tokio_runtime: runtime::Handle, workspace: WorkspaceEmbryo, base: ProjectBaseRef, output: Option<OutputId>, length_secs: Option<f64>) -> Result<Vec<Sample>, RenderError> {
+    let (result_tx, result_rx) = oneshot::channel();
+
+    thread::spawn(move || {
+        tokio_runtime.enter(|| {
+            // nothing is sent to a rendering engine, but it stops once every
+            // sender is gone so one is held until the render is over
+            let (_cmd_tx, cmd_rx) = mpsc::sync_channel(8);
+            let (log_tx, _) = broadcast::channel(64);
+            let (perf_tx, _) = watch::channel(None);
+
+            let mut engine = Engine::new(cmd_rx, log_tx, perf_tx, workspace.spawn(base.clone()), base);
+
+            let output = match output.or_else(|| engine.program_output()) {
+                Some(output) => output,
+                None => {
+                    let _ = result_tx.send(Err(RenderError::NoOutput));
+                    return;
+                }
+            };
+
+            let stereo = engine.workspace.borrow().modules.get(&output.module_id())
+                .and_then(|module| module.outputs().get(output.index()))
+                .map(|terminal| terminal.line_type() == LineType::Stereo)
+                .unwrap_or(false);
+
+            if !stereo {
+                let _ = result_tx.send(Err(RenderError::NotStereo(output)));
+                return;
+            }
+
+            engine.capture = Some((output, Vec::new()));
+
+            // renders are held in memory until written out, as freezes are
+            let schedule = match length_secs {
+                Some(length_secs) => Offline::new(length_secs.min(freeze::MAX_LENGTH_SECS), false),
+                None => Offline::new(freeze::MAX_LENGTH_SECS, true),
+            };
+
+            if let Err(not_ready) = engine.run(schedule) {
+                let _ = result_tx.send(Err(RenderError::NotReady(not_ready)));
+                return;
+            }
+
+            let (_, samples) = engine.capture.take().expect("engine.capture");
+            let _ = result_tx.send(Ok(samples));
+        });
+    });
+
+    result_rx.await.expect("render thread")
+}
+
 #[derive(Debug)]
 pub enum EngineError {
     Stopped,
@@ -119,11 +243,13 @@ pub enum EngineEvent {
 }
 
 impl EngineHandle {
-    pub async fn connect(&self) -> Result<(WorkspaceState, EngineEvents, EngineSession), EngineError> {
+    /// Connects a new session to the engine. Sessions in a permission group
+    /// may only edit modules belonging to that group.
+    pub async fn connect(&self, group: Option<String>) -> Result<(WorkspaceState, EngineEvents, EngineSession), EngineError> {
         let cmd_tx = self.cmd_tx.clone();
 
         let (tx, rx) = oneshot::channel();
-        cmd_tx.try_send(EngineMessage::ConnectSession(tx))?;
+        cmd_tx.try_send(EngineMessage::ConnectSession(group, tx))?;
         let (session_id, state, log_rx) = rx.await.map_err(|_| EngineError::Stopped)?;
 
         Ok((state, log_rx, EngineSession {
@@ -147,59 +273,136 @@ impl EngineSession {
         self.send_message(EngineMessage::Workspace(self.session_id, msg))
     }
 
+    /// Replaces the entire workspace, as when applying a snapshot.
+    pub fn load_workspace(&self, workspace: persist::Workspace) -> Result<(), EngineError> {
+        self.send_message(EngineMessage::LoadWorkspace(self.session_id, workspace))
+    }
+
     fn send_message(&self, msg: EngineMessage) -> Result<(), EngineError> {
         Ok(self.cmd_tx.try_send(msg)?)
     }
 }
 
+impl Drop for EngineSession {
+    fn drop(&mut self) {
+        let _ = self.send_message(EngineMessage::DisconnectSession(self.session_id));
+    }
+}
+
 pub struct Engine {
     cmd_rx: Receiver<EngineMessage>,
+    // renders are saved off the engine thread and come back through here.
+    // not through cmd_rx, which must disconnect once every handle is gone
+    rendered_tx: Sender<(OutputId, Option<MediaId>)>,
+    rendered_rx: Receiver<(OutputId, Option<MediaId>)>,
     log_tx: broadcast::Sender<EngineEvent>,
     perf_tx: watch::Sender<Option<Arc<PerformanceInfo>>>,
     session_seq: Sequence,
+    sessions: HashMap<SessionId, Option<String>>,
     workspace: SyncWorkspace,
+    // stereo samples captured so far for each output being frozen
+    renders: HashMap<OutputId, Vec<Sample>>,
+    // stereo samples captured so far from the output being rendered offline
+    capture: Option<(OutputId, Vec<Sample>)>,
+    pool: WorkerPool,
+    buffer_pool: BufferPool,
     base: ProjectBaseRef,
 }
 
 impl Engine {
-    fn run(&mut self) {
-        let start = Instant::now();
+    fn new(
+        cmd_rx: Receiver<EngineMessage>,
+        log_tx: broadcast::Sender<EngineEvent>,
+        perf_tx: watch::Sender<Option<Arc<PerformanceInfo>>>,
+        workspace: SyncWorkspace,
+        base: ProjectBaseRef,
+    ) -> Self {
+        let (rendered_tx, rendered_rx) = mpsc::channel();
+
+        Engine {
+            cmd_rx,
+            rendered_tx,
+            rendered_rx,
+            log_tx,
+            perf_tx,
+            session_seq: Sequence::new(),
+            sessions: HashMap::new(),
+            workspace,
+            renders: HashMap::new(),
+            capture: None,
+            pool: WorkerPool::new(),
+            buffer_pool: BufferPool::new(),
+            base,
+        }
+    }
+
+    fn run(&mut self, mut schedule: impl Schedule) -> Result<(), NotReady> {
         let mut stat = EngineStat::new();
         let mut tick = 0;
 
+        // policy applied to this tick, anything but Lag only after an overrun
+        let mut shed = OverrunPolicy::Lag;
+
+        self.publish_viewer_links();
+
         loop {
             let this_tick = tick;
             tick += 1;
 
-            // we don't simply calculate `tick * TICK_BUDGET` here to prevent loss of precision over time:
-            let scheduled_tick_end = start + Duration::from_millis((tick * 1_000) / TICKS_PER_SECOND as u64);
+            let scheduled_tick_end = schedule.begin_tick(this_tick, self.workspace.borrow_mut_without_sync())?;
+
+            replay::set_tick(this_tick);
 
             // run tick
-            let indications = stat.record_tick(scheduled_tick_end,
-                |tick_stat| self.run_tick(this_tick, tick_stat));
+            if shed == OverrunPolicy::SkipTick {
+                shed = OverrunPolicy::Lag;
+            } else {
+                let indications = stat.record_tick(scheduled_tick_end.unwrap_or_else(Instant::now),
+                    |tick_stat| self.run_tick(this_tick, shed, scheduled_tick_end, tick_stat));
+
+                self.log_indications(indications);
+                self.finish_renders();
+
+                // only ticks with a deadline can overrun it
+                shed = if scheduled_tick_end.is_some() && stat.overran() {
+                    let policy = self.workspace.borrow().overrun_policy;
+                    stat.log_overrun(this_tick, policy);
+                    policy
+                } else {
+                    OverrunPolicy::Lag
+                };
+            }
 
-            // send out indication updates
-            for (module_id, indication) in indications {
-                self.workspace.indications_mut().insert(module_id, indication.clone());
-                self.log_op(ServerUpdate::UpdateModuleIndication(module_id, indication));
+            if !schedule.end_tick(this_tick, self.workspace.borrow()) {
+                return Ok(());
             }
 
             // send out performance metrics
             if (this_tick % (TICKS_PER_SECOND as u64 / 2)) == 0 {
+                for (module_id, module) in &self.workspace.borrow().modules {
+                    stat.record_memory(*module_id, module.memory_usage());
+                }
+
+                stat.record_buffers(self.buffer_pool.info());
+
                 let _ = self.perf_tx.broadcast(Some(Arc::new(stat.report())));
             }
 
+            while let Ok((output, media_id)) = self.rendered_rx.try_recv() {
+                self.process_message(EngineMessage::FreezeRendered(output, media_id), &mut stat);
+            }
+
             // process all waiting commands immediately
             loop {
                 match self.cmd_rx.try_recv() {
                     Ok(msg) => { self.process_message(msg, &mut stat); }
                     Err(TryRecvError::Empty) => { break; }
-                    Err(TryRecvError::Disconnected) => { return; }
+                    Err(TryRecvError::Disconnected) => { return Ok(()); }
                 }
             }
 
             // wait for next tick and process commands while waiting
-            loop {
+            while let Some(scheduled_tick_end) = scheduled_tick_end {
                 let now = Instant::now();
 
                 if now >= scheduled_tick_end {
@@ -209,31 +412,87 @@ impl Engine {
                 match self.cmd_rx.recv_timeout(scheduled_tick_end - now) {
                     Ok(msg) => { self.process_message(msg, &mut stat); }
                     Err(RecvTimeoutError::Timeout) => { break; }
-                    Err(RecvTimeoutError::Disconnected) => { return; }
+                    Err(RecvTimeoutError::Disconnected) => { return Ok(()); }
                 }
             }
         }
     }
 
+    fn run_replay(&mut self, mut recording: Recording, mut log_rx: EngineEvents) -> Vec<(u64, ServerUpdate)> {
+        let mut stat = EngineStat::new();
+        let mut log = Vec::new();
+
+        for tick in 0..=recording.end_tick() {
+            replay::set_tick(tick);
+
+            // overrun policies depend on wall clock time, so replays always
+            // run every module to stay deterministic
+            let indications = stat.record_tick(Instant::now(),
+                |tick_stat| self.run_tick(tick, OverrunPolicy::Lag, None, tick_stat));
+
+            self.log_indications(indications);
+            self.finish_renders();
+
+            // messages are processed after the tick they arrived in, as in
+            // the realtime loop
+            for msg in recording.take_messages(tick) {
+                match msg {
+                    RecordedMessage::ConnectSession(group) => { self.connect_session(group); }
+                    RecordedMessage::DisconnectSession(session_id) => { self.sessions.remove(&session_id); }
+                    RecordedMessage::Workspace(session_id, msg) => { self.client_update(session_id, msg, &mut stat); }
+                    RecordedMessage::LoadWorkspace(session_id, workspace) => { self.load_workspace(session_id, workspace, &mut stat); }
+                    RecordedMessage::FreezeRendered(output, media_id) => { self.complete_freeze(output, media_id); }
+                }
+            }
+
+            while let Ok(event) = log_rx.try_recv() {
+                if let EngineEvent::ServerUpdate(update) = event {
+                    log.push((tick, update));
+                }
+            }
+        }
+
+        log
+    }
+
+    fn log_indications(&mut self, indications: Vec<(ModuleId, Indication)>) {
+        for (module_id, indication) in indications {
+            self.workspace.indications_mut().insert(module_id, indication.clone());
+            self.log_op(ServerUpdate::UpdateModuleIndication(module_id, indication));
+        }
+    }
+
     fn process_message(&mut self, msg: EngineMessage, stat: &mut EngineStat) {
+        replay::record_message(&msg);
+
         match msg {
-            EngineMessage::ConnectSession(tx) => {
-                let _ = tx.send(self.connect_session());
+            EngineMessage::ConnectSession(group, tx) => {
+                let _ = tx.send(self.connect_session(group));
+            }
+            EngineMessage::DisconnectSession(session_id) => {
+                self.sessions.remove(&session_id);
             }
             EngineMessage::Workspace(session, msg) => {
                 self.client_update(session, msg, stat);
             }
+            EngineMessage::LoadWorkspace(session, workspace) => {
+                self.load_workspace(session, workspace, stat);
+            }
+            EngineMessage::FreezeRendered(output, media_id) => {
+                self.complete_freeze(output, media_id);
+            }
         }
     }
 
-    fn connect_session(&mut self) -> (SessionId, WorkspaceState, EngineEvents) {
+    fn connect_session(&mut self, group: Option<String>) -> (SessionId, WorkspaceState, EngineEvents) {
         let session_id = SessionId(self.session_seq.next());
         let log_rx = self.log_tx.subscribe();
-        let state = self.dump_state();
+        let state = self.dump_state(group.clone());
+        self.sessions.insert(session_id, group);
         (session_id, state, log_rx)
     }
 
-    fn dump_state(&self) -> WorkspaceState {
+    fn dump_state(&self, session_group: Option<String>) -> WorkspaceState {
         let mut state = WorkspaceState {
             modules: Vec::new(),
             geometry: Vec::new(),
@@ -241,6 +500,16 @@ impl Engine {
             connections: Vec::new(),
             inputs: Vec::new(),
             outputs: Vec::new(),
+            safety: Vec::new(),
+            groups: Vec::new(),
+            bypassed: Vec::new(),
+            adapters: Vec::new(),
+            midi_mappings: Vec::new(),
+            surface: self.workspace.borrow().surface.clone(),
+            viewer_links: self.workspace.borrow().viewer_links.clone(),
+            freezes: self.workspace.borrow().freezes.clone(),
+            overrun_policy: self.workspace.borrow().overrun_policy,
+            session_group,
         };
 
         let workspace = self.workspace.borrow();
@@ -263,6 +532,29 @@ impl Engine {
             state.connections.push((*input, *output));
         }
 
+        for (module_id, safety) in &workspace.safety {
+            state.safety.push((*module_id, *safety));
+        }
+
+        for (module_id, group) in &workspace.groups {
+            state.groups.push((*module_id, group.clone()));
+        }
+
+        for module_id in &workspace.bypassed {
+            state.bypassed.push(*module_id);
+        }
+
+        for (input, adapter) in &workspace.adapters {
+            state.adapters.push((*input, *adapter));
+        }
+
+        for (target, control) in &workspace.midi_mappings {
+            state.midi_mappings.push(MidiMapping {
+                target: target.clone(),
+                control: control.clone(),
+            });
+        }
+
         state
     }
 
@@ -274,49 +566,335 @@ impl Engine {
         let _ = self.log_tx.send(EngineEvent::Sync(clock));
     }
 
+    // returns the protected modules which would be affected by a destructive
+    // workspace op - the op must be rejected unless all of them are armed
+    fn protected_modules_affected(&self, op: &WorkspaceOp) -> Vec<ModuleId> {
+        let workspace = self.workspace.borrow();
+
+        let mut affected = Vec::new();
+
+        match op {
+            WorkspaceOp::DeleteModule(module_id) => {
+                affected.push(*module_id);
+
+                // deleting a module also disconnects all cables running from
+                // its outputs into other modules
+                for (input, output) in &workspace.connections {
+                    if output.module_id() == *module_id {
+                        affected.push(input.module_id());
+                    }
+                }
+            }
+            WorkspaceOp::CreateMacro(_, module_ids) => {
+                // collapsing deletes each module, and moves the cables out of
+                // them over to the macro
+                for module_id in module_ids {
+                    affected.push(*module_id);
+
+                    for (input, output) in &workspace.connections {
+                        if output.module_id() == *module_id {
+                            affected.push(input.module_id());
+                        }
+                    }
+                }
+            }
+            WorkspaceOp::CreateConnection(input, _) |
+            WorkspaceOp::DeleteConnection(input) |
+            WorkspaceOp::UpdateConnectionAdapter(input, _) => {
+                // creating a connection on an already connected input
+                // replaces the existing cable, and changing its adapter
+                // changes what the input hears
+                if workspace.connections.contains_key(input) {
+                    affected.push(input.module_id());
+                }
+            }
+            WorkspaceOp::FreezeOutput(output, _) => {
+                // freezing moves the output's cables over to the stand-in
+                for (input, from) in &workspace.connections {
+                    if from == output {
+                        affected.push(input.module_id());
+                    }
+                }
+            }
+            WorkspaceOp::ThawOutput(output) => {
+                // thawing deletes the stand-in and moves its cables back
+                let source = workspace.freezes.iter()
+                    .filter(|freeze| freeze.output == *output)
+                    .find_map(|freeze| match freeze.state {
+                        FreezeState::Rendering { .. } => None,
+                        FreezeState::Frozen { source, .. } => Some(source),
+                    });
+
+                if let Some(source) = source {
+                    affected.push(source);
+
+                    for (input, from) in &workspace.connections {
+                        if *from == OutputId(source, freeze::SOURCE_AUDIO_OUTPUT) {
+                            affected.push(input.module_id());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        affected.retain(|module_id| workspace.safety(*module_id).is_protected());
+        affected.sort();
+        affected.dedup();
+        affected
+    }
+
+    fn session_group(&self, session_id: SessionId) -> Option<String> {
+        self.sessions.get(&session_id).cloned().flatten()
+    }
+
+    // sessions without a permission group may do anything, sessions in a
+    // group may only touch modules in that group
+    fn session_permitted(&self, session_id: SessionId, op: &WorkspaceOp) -> bool {
+        let group = match self.sessions.get(&session_id) {
+            Some(Some(group)) => group.as_str(),
+            Some(None) => return true,
+            None => return false,
+        };
+
+        let workspace = self.workspace.borrow();
+        let owns = |module_id: ModuleId| workspace.group(module_id) == Some(group);
+
+        match op {
+            WorkspaceOp::CreateModule(..) |
+            WorkspaceOp::CreateTemplate(..) => true,
+            WorkspaceOp::UpdateModuleParams(module_id, _) |
+            WorkspaceOp::ModuleCommand(module_id, _) |
+            WorkspaceOp::UpdateWindowGeometry(module_id, _) |
+            WorkspaceOp::UpdateModuleSafety(module_id, _) |
+            WorkspaceOp::UpdateModuleBypass(module_id, _) => owns(*module_id),
+            WorkspaceOp::DeleteModule(module_id) => {
+                // deleting a module disconnects everything downstream of it,
+                // so all of those modules must be owned too
+                owns(*module_id) && workspace.connections.iter()
+                    .filter(|(_, output)| output.module_id() == *module_id)
+                    .all(|(input, _)| owns(input.module_id()))
+            }
+            WorkspaceOp::CreateConnection(input, _) |
+            WorkspaceOp::DeleteConnection(input) |
+            WorkspaceOp::UpdateConnectionAdapter(input, _) => owns(input.module_id()),
+            WorkspaceOp::UpdateModuleGroup(..) => false,
+            WorkspaceOp::UpdateMidiMapping(target, _) => owns(target.module),
+            WorkspaceOp::UpdateOverrunPolicy(..) => false,
+            WorkspaceOp::UpdateSurface(..) => false,
+            WorkspaceOp::CreateViewerLink(..) |
+            WorkspaceOp::RevokeViewerLink(..) => false,
+            // a freeze reaches upstream into modules of any group
+            WorkspaceOp::FreezeOutput(..) |
+            WorkspaceOp::ThawOutput(..) => false,
+            WorkspaceOp::CreateMacro(..) => false,
+        }
+    }
+
+    fn create_module(&mut self, params: ModuleParams, geometry: WindowGeometry, group: Option<String>) -> ModuleId {
+        // TODO - the audio engine is not actually concerned with
+        // window geometry and so should not own this data and force
+        // all accesses to it to go via the live audio thread
+        let (id, op) = {
+            let mut workspace = self.workspace.borrow_mut();
+            let id = ModuleId(workspace.module_seq.next());
+            let (module, indication) = module::host(params.clone(), self.base.clone());
+            let inputs = module.inputs().to_vec();
+            let outputs = module.outputs().to_vec();
+            workspace.insert_module(id, module);
+            workspace.geometry.insert(id, geometry.clone());
+            workspace.indications.insert(id, indication.clone());
+
+            // modules created by a session in a permission group
+            // belong to that group
+            workspace.set_group(id, group.clone());
+
+            (id, ServerUpdate::CreateModule {
+                id,
+                params,
+                geometry,
+                indication,
+                inputs,
+                outputs,
+            })
+        };
+
+        self.log_op(op);
+
+        if group.is_some() {
+            self.log_op(ServerUpdate::UpdateModuleGroup(id, group));
+        }
+
+        id
+    }
+
+    fn delete_module(&mut self, module_id: ModuleId, stat: &mut EngineStat) {
+        let mut operations = Vec::new();
+
+        {
+            let mut workspace = self.workspace.borrow_mut();
+
+            // find any connections connected to this module's inputs or
+            // outputs and delete them, generating oplog entries
+
+            let mut deleted_connections = Vec::new();
+
+            for (input, output) in &workspace.connections {
+                if input.module_id() == module_id || output.module_id() == module_id {
+                    deleted_connections.push(*input);
+                }
+            }
+
+            // keep the oplog independent of hash order so that
+            // replays are reproducible
+            deleted_connections.sort();
+
+            for deleted_connection in deleted_connections {
+                workspace.disconnect(deleted_connection);
+                operations.push(ServerUpdate::DeleteConnection(deleted_connection));
+            }
+
+            // finally, delete the module:
+
+            if workspace.modules.contains_key(&module_id) {
+                workspace.remove_module(module_id);
+                workspace.safety.remove(&module_id);
+                workspace.groups.remove(&module_id);
+                workspace.bypassed.remove(&module_id);
+
+                for target in workspace.remove_midi_mappings(module_id) {
+                    operations.push(ServerUpdate::UpdateMidiMapping(target, None));
+                }
+
+                if workspace.remove_surface_controls(module_id) {
+                    operations.push(ServerUpdate::UpdateSurface(workspace.surface.clone()));
+                }
+
+                if workspace.remove_viewer_links(module_id) {
+                    operations.push(ServerUpdate::UpdateViewerLinks(workspace.viewer_links.clone()));
+                }
+
+                if workspace.remove_freezes(module_id) {
+                    operations.push(ServerUpdate::UpdateFreezes(workspace.freezes.clone()));
+                }
+
+                operations.push(ServerUpdate::DeleteModule(module_id));
+            }
+        }
+
+        for op in operations {
+            self.log_op(op);
+        }
+
+        stat.remove_module(module_id);
+    }
+
     fn client_update(&mut self, session_id: SessionId, msg: WorkspaceMessage, stat: &mut EngineStat) {
         let clock = OpClock(session_id, msg.sequence);
 
+        if !self.session_permitted(session_id, &msg.op) {
+            // client should have guarded against this, just drop
+            eprintln!("engine: dropping {:?}, not permitted for session", msg.op);
+            return self.sync_log(clock);
+        }
+
+        let protected = self.protected_modules_affected(&msg.op);
+
+        let locked = {
+            let workspace = self.workspace.borrow();
+            protected.iter().any(|module_id| workspace.safety(*module_id).is_locked())
+        };
+
+        if locked {
+            // client should have guarded against this, just drop
+            eprintln!("engine: dropping {:?}, protected modules must be armed first", msg.op);
+            return self.sync_log(clock);
+        }
+
         match msg.op {
             WorkspaceOp::CreateModule(params, geometry) => {
-                // TODO - the audio engine is not actually concerned with
-                // window geometry and so should not own this data and force
-                // all accesses to it to go via the live audio thread
-                let op = {
-                    let mut workspace = self.workspace.borrow_mut();
-                    let id = ModuleId(workspace.module_seq.next());
-                    let (module, indication) = module::host(params.clone(), self.base.clone());
-                    let inputs = module.inputs().to_vec();
-                    let outputs = module.outputs().to_vec();
-                    workspace.modules.insert(id, module);
-                    workspace.geometry.insert(id, geometry.clone());
-                    workspace.indications.insert(id, indication.clone());
-
-                    ServerUpdate::CreateModule {
-                        id,
-                        params,
-                        geometry,
-                        indication,
-                        inputs,
-                        outputs,
-                    }
-                };
+                let group = self.session_group(session_id);
+                self.create_module(params, geometry, group);
+            }
+            WorkspaceOp::CreateTemplate(template, geometry) => {
+                let group = self.session_group(session_id)
+                    .or_else(|| Some(template.group().to_owned()));
+
+                let expansion = template::expand(&template);
+
+                let ids = expansion.modules.into_iter().map(|module| {
+                    let geometry = WindowGeometry {
+                        position: geometry.position.add(module.offset),
+                        z_index: geometry.z_index,
+                    };
+
+                    self.create_module(module.params, geometry, group.clone())
+                }).collect::<Vec<_>>();
 
-                self.log_op(op);
+                for conn in expansion.connections {
+                    let input_id = InputId(ids[conn.input.0], conn.input.1);
+                    let output_id = OutputId(ids[conn.output.0], conn.output.1);
+
+                    // freshly created inputs have nothing to replace, and a
+                    // template is only ever patched with matching line types
+                    if let Ok(_) = self.workspace.borrow_mut().connect(input_id, output_id) {
+                        self.log_op(ServerUpdate::CreateConnection(input_id, output_id));
+                    }
+                }
             }
             WorkspaceOp::UpdateModuleParams(module_id, params) => {
-                let op = {
+                let mut operations = Vec::new();
+                let mut indication = None;
+
+                {
                     let mut workspace = self.workspace.borrow_mut();
 
-                    workspace.modules.get_mut(&module_id).map(|module| {
-                        module.update(params.clone());
-                        ServerUpdate::UpdateModuleParams(module_id, module.params())
-                    })
-                };
+                    if let Some(module) = workspace.modules.get_mut(&module_id) {
+                        let old_inputs = module.inputs().to_vec();
+                        let old_outputs = module.outputs().to_vec();
 
-                if let Some(op) = op {
+                        indication = module.update(params);
+
+                        // some modules gain or lose terminals as their params
+                        // change, like a mixer resized in place
+                        let inputs = Some(module.inputs().to_vec()).filter(|inputs| *inputs != old_inputs);
+                        let outputs = Some(module.outputs().to_vec()).filter(|outputs| *outputs != old_outputs);
+                        let params = module.params();
+
+                        let retargeted = match inputs.is_some() || outputs.is_some() {
+                            true => workspace.retarget(module_id, &old_inputs, &old_outputs),
+                            false => Retargeted::default(),
+                        };
+
+                        operations.extend(retargeted.deleted.into_iter().map(ServerUpdate::DeleteConnection));
+                        operations.push(ServerUpdate::UpdateModuleParams { id: module_id, params, inputs, outputs });
+
+                        for (input, output) in retargeted.created {
+                            operations.push(ServerUpdate::CreateConnection(input, output));
+
+                            if let Some(adapter) = workspace.adapters.get(&input) {
+                                operations.push(ServerUpdate::UpdateConnectionAdapter(input, *adapter));
+                            }
+                        }
+                    }
+                }
+
+                for op in operations {
                     self.log_op(op);
                 }
+
+                // modules which reset on update, like a recorder starting
+                // over, show it straight away rather than on their next tick
+                if let Some(indication) = indication {
+                    self.log_indications(vec![(module_id, indication)]);
+                }
+            }
+            WorkspaceOp::ModuleCommand(module_id, command) => {
+                // commands change nothing clients hold, any effect shows in
+                // the module's indication
+                if let Some(module) = self.workspace.borrow_mut().modules.get_mut(&module_id) {
+                    module.command(command);
+                }
             }
             WorkspaceOp::UpdateWindowGeometry(module_id, geometry) => {
                 let op = {
@@ -333,179 +911,847 @@ impl Engine {
                 }
             }
             WorkspaceOp::DeleteModule(module_id) => {
-                let mut operations = Vec::new();
+                self.delete_module(module_id, stat);
+            }
+            WorkspaceOp::CreateConnection(input_id, output_id) => {
+                let previous = self.workspace.borrow_mut().connect(input_id, output_id);
 
-                {
+                match previous {
+                    Ok(old_output) => {
+                        if let Some(_) = old_output {
+                            self.log_op(ServerUpdate::DeleteConnection(input_id));
+                        }
+
+                        self.log_op(ServerUpdate::CreateConnection(input_id, output_id));
+                    }
+                    Err(ConnectError::Cycle) => {
+                        // clients can race each other into a cycle, so tell
+                        // them rather than leaving the connection drawn
+                        let current = self.workspace.borrow().connections.get(&input_id).copied();
+
+                        self.log_op(ServerUpdate::RejectConnection {
+                            input: input_id,
+                            output: output_id,
+                            current,
+                            reason: ConnectionError::Cycle,
+                        });
+                    }
+                    Err(_) => {
+                        // client should have guarded against a type mismatched
+                        // connection, just drop
+                    }
+                }
+            }
+            WorkspaceOp::DeleteConnection(input_id) => {
+                let previous = self.workspace.borrow_mut().disconnect(input_id);
+
+                if let Some(_) = previous {
+                    self.log_op(ServerUpdate::DeleteConnection(input_id));
+                }
+            }
+            WorkspaceOp::UpdateModuleGroup(module_id, group) => {
+                let op = {
                     let mut workspace = self.workspace.borrow_mut();
 
-                    // find any connections connected to this module's inputs or
-                    // outputs and delete them, generating oplog entries
+                    if workspace.modules.contains_key(&module_id) {
+                        workspace.set_group(module_id, group.clone());
+                        Some(ServerUpdate::UpdateModuleGroup(module_id, group))
+                    } else {
+                        None
+                    }
+                };
 
-                    let mut deleted_connections = Vec::new();
+                if let Some(op) = op {
+                    self.log_op(op);
+                }
+            }
+            WorkspaceOp::UpdateModuleBypass(module_id, bypassed) => {
+                let op = {
+                    let mut workspace = self.workspace.borrow_mut();
 
-                    for (input, output) in &workspace.connections {
-                        if input.module_id() == module_id || output.module_id() == module_id {
-                            deleted_connections.push(*input);
-                        }
+                    if workspace.modules.contains_key(&module_id) {
+                        workspace.set_bypassed(module_id, bypassed);
+                        Some(ServerUpdate::UpdateModuleBypass(module_id, bypassed))
+                    } else {
+                        None
                     }
+                };
 
-                    for deleted_connection in deleted_connections {
-                        workspace.connections.remove(&deleted_connection);
-                        operations.push(ServerUpdate::DeleteConnection(deleted_connection));
+                if let Some(op) = op {
+                    self.log_op(op);
+                }
+            }
+            WorkspaceOp::UpdateConnectionAdapter(input_id, adapter) => {
+                let updated = self.workspace.borrow_mut().set_adapter(input_id, adapter);
+
+                if updated {
+                    self.log_op(ServerUpdate::UpdateConnectionAdapter(input_id, adapter));
+                }
+            }
+            WorkspaceOp::UpdateMidiMapping(target, control) => {
+                let op = {
+                    let mut workspace = self.workspace.borrow_mut();
+
+                    if workspace.modules.contains_key(&target.module) {
+                        workspace.set_midi_mapping(target.clone(), control.clone());
+                        Some(ServerUpdate::UpdateMidiMapping(target, control))
+                    } else {
+                        None
                     }
+                };
 
-                    // finally, delete the module:
+                if let Some(op) = op {
+                    self.log_op(op);
+                }
+            }
+            WorkspaceOp::UpdateOverrunPolicy(policy) => {
+                self.workspace.borrow_mut().overrun_policy = policy;
+                self.log_op(ServerUpdate::UpdateOverrunPolicy(policy));
+            }
+            WorkspaceOp::UpdateSurface(surface) => {
+                let surface = self.workspace.borrow_mut().set_surface(surface).to_vec();
+                self.log_op(ServerUpdate::UpdateSurface(surface));
+            }
+            WorkspaceOp::CreateViewerLink(module_id, label, duration_secs) => {
+                let op = {
+                    let mut workspace = self.workspace.borrow_mut();
 
                     if workspace.modules.contains_key(&module_id) {
-                        workspace.modules.remove(&module_id);
-                        operations.push(ServerUpdate::DeleteModule(module_id));
+                        workspace.viewer_links.push(ViewerLink {
+                            token: viewer::new_token(),
+                            module: module_id,
+                            label,
+                            expires: viewer::unix_now().saturating_add(duration_secs),
+                        });
+
+                        Some(ServerUpdate::UpdateViewerLinks(workspace.viewer_links.clone()))
+                    } else {
+                        None
                     }
-                }
+                };
 
-                for op in operations {
+                if let Some(op) = op {
                     self.log_op(op);
                 }
+            }
+            WorkspaceOp::RevokeViewerLink(token) => {
+                let links = {
+                    let mut workspace = self.workspace.borrow_mut();
+                    workspace.viewer_links.retain(|link| link.token != token);
+                    workspace.viewer_links.clone()
+                };
 
-                stat.remove_module(module_id);
+                self.log_op(ServerUpdate::UpdateViewerLinks(links));
             }
-            WorkspaceOp::CreateConnection(input_id, output_id) => {
-                let previous = self.workspace.borrow_mut().connect(input_id, output_id);
+            WorkspaceOp::FreezeOutput(output, length_secs) => {
+                let permitted = {
+                    let workspace = self.workspace.borrow();
 
-                match previous {
-                    Ok(old_output) => {
-                        if let Some(_) = old_output {
-                            self.log_op(ServerUpdate::DeleteConnection(input_id));
-                        }
+                    let stereo = workspace.modules.get(&output.module_id())
+                        .and_then(|module| module.outputs().get(output.1))
+                        .map(|terminal| terminal.line_type() == LineType::Stereo)
+                        .unwrap_or(false);
 
-                        self.log_op(ServerUpdate::CreateConnection(input_id, output_id));
+                    let chain_free = freeze::chain(&workspace.connections, output)
+                        .map(|chain| chain.iter().all(|module_id| workspace.frozen_by(*module_id).is_none()))
+                        .unwrap_or(false);
+
+                    // also rejects NaN
+                    let length_ok = length_secs > 0.0 && length_secs <= freeze::MAX_LENGTH_SECS;
+
+                    stereo && chain_free && length_ok
+                };
+
+                if permitted {
+                    self.workspace.borrow_mut().freezes.push(Freeze {
+                        output,
+                        state: FreezeState::Rendering { length_secs },
+                    });
+
+                    self.renders.insert(output, Vec::new());
+                    self.log_freezes();
+                }
+            }
+            WorkspaceOp::ThawOutput(output) => {
+                let thawed = {
+                    let mut workspace = self.workspace.borrow_mut();
+
+                    workspace.freezes.iter()
+                        .position(|freeze| freeze.output == output)
+                        .map(|index| workspace.freezes.remove(index))
+                };
+
+                if let Some(thawed) = thawed {
+                    // thawing a render in progress cancels it
+                    self.renders.remove(&output);
+
+                    if let FreezeState::Frozen { source, .. } = thawed.state {
+                        self.reconnect(OutputId(source, freeze::SOURCE_AUDIO_OUTPUT), output);
+                        self.delete_module(source, stat);
                     }
-                    Err(_) => {
-                        // client should have guarded against a type mismatched
-                        // connection, just drop
+
+                    self.log_freezes();
+                }
+            }
+            WorkspaceOp::CreateMacro(name, module_ids) => {
+                self.create_macro(name, module_ids, stat);
+            }
+            WorkspaceOp::UpdateModuleSafety(module_id, safety) => {
+                let op = {
+                    let mut workspace = self.workspace.borrow_mut();
+
+                    let permitted = match (workspace.safety(module_id), safety) {
+                        // a safe module must be armed before its protection
+                        // can be removed
+                        (ModuleSafety::Safe, ModuleSafety::Unprotected) => false,
+                        _ => workspace.modules.contains_key(&module_id),
+                    };
+
+                    if permitted {
+                        workspace.set_safety(module_id, safety);
+                        Some(ServerUpdate::UpdateModuleSafety(module_id, safety))
+                    } else {
+                        None
                     }
+                };
+
+                if let Some(op) = op {
+                    self.log_op(op);
                 }
             }
-            WorkspaceOp::DeleteConnection(input_id) => {
-                let previous = self.workspace.borrow_mut().disconnect(input_id);
+        }
 
-                if let Some(_) = previous {
-                    self.log_op(ServerUpdate::DeleteConnection(input_id));
+        // arming a module only permits a single destructive action, return
+        // any armed modules that were affected to safe:
+        for module_id in protected {
+            let disarmed = {
+                let mut workspace = self.workspace.borrow_mut();
+
+                if workspace.modules.contains_key(&module_id) {
+                    workspace.set_safety(module_id, ModuleSafety::Safe);
+                    true
+                } else {
+                    false
                 }
+            };
+
+            if disarmed {
+                self.log_op(ServerUpdate::UpdateModuleSafety(module_id, ModuleSafety::Safe));
             }
         }
 
+        // any op may have changed what a link points to, eg. renaming an
+        // HLS output
+        self.publish_viewer_links();
+
         return self.sync_log(clock);
     }
 
-    fn run_tick(&mut self, tick: u64, stat: &mut TickStat) -> Vec<(ModuleId, Indication)> {
-        // tick is not allowed to update any persisted information such as
-        // module params or connections
-        let workspace = self.workspace.borrow_mut_without_sync();
+    fn create_macro(&mut self, name: String, mut module_ids: Vec<ModuleId>, stat: &mut EngineStat) {
+        module_ids.sort();
+        module_ids.dedup();
+
+        let (collapse, geometry, group, adapters) = {
+            let workspace = self.workspace.borrow();
+
+            // modules taking part in a freeze stay as they are, thawing
+            // relies on them
+            let permitted = !module_ids.is_empty() && module_ids.iter().all(|module_id| {
+                workspace.modules.contains_key(module_id) && workspace.frozen_by(*module_id).is_none()
+            });
+
+            if !permitted {
+                return;
+            }
+
+            let members = module_ids.iter()
+                .map(|module_id| {
+                    let module = &workspace.modules[module_id];
+
+                    collapse::Member {
+                        id: *module_id,
+                        params: module.params(),
+                        inputs: module.inputs().len(),
+                        outputs: module.outputs().len(),
+                    }
+                })
+                .collect();
 
-        // find terminal modules - modules which do not send their output to
-        // the input of any other module
+            let geometry = workspace.geometry.get(&module_ids[0]).cloned().unwrap_or_default();
 
-        let mut terminal_modules = HashSet::new();
+            // the macro stays in a permission group only if all of its
+            // modules were in it
+            let group = workspace.group(module_ids[0]).map(str::to_owned)
+                .filter(|group| module_ids.iter().all(|module_id| workspace.group(*module_id) == Some(group.as_str())));
 
-        for (id, _) in &workspace.modules {
-            terminal_modules.insert(*id);
+            let collapse = collapse::collapse(name, members, &workspace.connections, &workspace.adapters);
+
+            // deleting the modules disconnects them, taking their adapters
+            // with them
+            (collapse, geometry, group, workspace.adapters.clone())
+        };
+
+        for module_id in &module_ids {
+            self.delete_module(*module_id, stat);
         }
 
-        for (_, output) in &workspace.connections {
-            terminal_modules.remove(&output.module_id());
+        // each cable into the macro stands for one into a module inside it
+        let exposed = collapse.params.inputs.iter()
+            .map(|terminal| InputId(module_ids[terminal.module], terminal.terminal))
+            .collect::<Vec<_>>();
+
+        let macro_id = self.create_module(ModuleParams::Macro(collapse.params), geometry, group);
+
+        let inputs = collapse.inputs.into_iter()
+            .zip(exposed)
+            .enumerate()
+            .filter_map(|(index, (output, was))| output.map(|output| (InputId(macro_id, index), output, adapters.get(&was).copied())));
+
+        let outputs = collapse.outputs.into_iter()
+            .enumerate()
+            .flat_map(|(index, inputs)| inputs.into_iter().map(move |input| (input, OutputId(macro_id, index))))
+            .map(|(input, output)| (input, output, adapters.get(&input).copied()));
+
+        for (input, output, adapter) in inputs.chain(outputs).collect::<Vec<_>>() {
+            if let Ok(_) = self.workspace.borrow_mut().connect(input, output) {
+                self.log_op(ServerUpdate::CreateConnection(input, output));
+
+                if let Some(adapter) = adapter {
+                    if self.workspace.borrow_mut().set_adapter(input, adapter) {
+                        self.log_op(ServerUpdate::UpdateConnectionAdapter(input, adapter));
+                    }
+                }
+            }
+        }
+    }
+
+    fn load_workspace(&mut self, session_id: SessionId, save: persist::Workspace, stat: &mut EngineStat) {
+        // loading replaces every module, so is only for sessions which may
+        // touch all of them
+        if self.sessions.get(&session_id) != Some(&None) {
+            eprintln!("engine: dropping workspace load, not permitted for session");
+            return;
         }
 
-        // depth-first-search modules out via their inputs, starting from
-        // terminal modules
+        let mut module_ids = self.workspace.borrow().modules.keys().copied().collect::<Vec<_>>();
+        module_ids.sort();
 
-        let mut topsort = Topsort {
-            modules: &workspace.modules,
-            connections: &workspace.connections,
-            run_order: Vec::new(),
-            seen: HashSet::new(),
+        let locked = {
+            let workspace = self.workspace.borrow();
+            module_ids.iter().any(|module_id| workspace.safety(*module_id).is_locked())
         };
 
-        for id in terminal_modules.into_iter() {
-            traverse(id, &mut topsort);
+        if locked {
+            eprintln!("engine: dropping workspace load, protected modules must be armed first");
+            return;
+        }
+
+        for module_id in module_ids {
+            self.delete_module(module_id, stat);
+        }
+
+        *self.workspace.borrow_mut() = Workspace::from_persist(&save, self.base.clone());
+
+        let mut operations = Vec::new();
+
+        {
+            let workspace = self.workspace.borrow();
+
+            // as in delete_module, keep the oplog independent of hash order
+            let mut module_ids = workspace.modules.keys().copied().collect::<Vec<_>>();
+            module_ids.sort();
+
+            for module_id in module_ids {
+                let module = &workspace.modules[&module_id];
+
+                operations.push(ServerUpdate::CreateModule {
+                    id: module_id,
+                    params: module.params(),
+                    geometry: workspace.geometry.get(&module_id).cloned().unwrap_or_default(),
+                    indication: workspace.indications[&module_id].clone(),
+                    inputs: module.inputs().to_vec(),
+                    outputs: module.outputs().to_vec(),
+                });
+
+                if let Some(group) = workspace.groups.get(&module_id) {
+                    operations.push(ServerUpdate::UpdateModuleGroup(module_id, Some(group.clone())));
+                }
+
+                if let Some(safety) = workspace.safety.get(&module_id) {
+                    operations.push(ServerUpdate::UpdateModuleSafety(module_id, *safety));
+                }
+
+                if workspace.bypassed.contains(&module_id) {
+                    operations.push(ServerUpdate::UpdateModuleBypass(module_id, true));
+                }
+            }
+
+            let mut connections = workspace.connections.iter()
+                .map(|(input, output)| (*input, *output))
+                .collect::<Vec<_>>();
+            connections.sort();
+
+            for (input, output) in connections {
+                operations.push(ServerUpdate::CreateConnection(input, output));
+
+                if let Some(adapter) = workspace.adapters.get(&input) {
+                    operations.push(ServerUpdate::UpdateConnectionAdapter(input, *adapter));
+                }
+            }
+
+            for (target, control) in &workspace.midi_mappings {
+                operations.push(ServerUpdate::UpdateMidiMapping(target.clone(), Some(control.clone())));
+            }
+
+            operations.push(ServerUpdate::UpdateSurface(workspace.surface.clone()));
+            operations.push(ServerUpdate::UpdateViewerLinks(workspace.viewer_links.clone()));
+            operations.push(ServerUpdate::UpdateFreezes(workspace.freezes.clone()));
+            operations.push(ServerUpdate::UpdateOverrunPolicy(workspace.overrun_policy));
         }
 
-        struct Topsort<'a> {
-            modules: &'a HashMap<ModuleId, DynModuleHost>,
-            connections: &'a HashMap<InputId, OutputId>,
-            run_order: Vec<ModuleId>,
-            seen: HashSet<ModuleId>,
+        for op in operations {
+            self.log_op(op);
         }
 
-        fn traverse(module_id: ModuleId, state: &mut Topsort) {
-            if state.seen.contains(&module_id) {
+        self.publish_viewer_links();
+    }
+
+    fn log_freezes(&mut self) {
+        let freezes = self.workspace.borrow().freezes.clone();
+        self.log_op(ServerUpdate::UpdateFreezes(freezes));
+    }
+
+    // moves every cable plugged into one output over to another
+    fn reconnect(&mut self, from: OutputId, to: OutputId) {
+        let mut inputs = self.workspace.borrow().connections.iter()
+            .filter(|(_, output)| **output == from)
+            .map(|(input, _)| *input)
+            .collect::<Vec<_>>();
+
+        // as in delete_module, keep the oplog independent of hash order
+        inputs.sort();
+
+        for input in inputs {
+            if let Ok(_) = self.workspace.borrow_mut().connect(input, to) {
+                self.log_op(ServerUpdate::DeleteConnection(input));
+                self.log_op(ServerUpdate::CreateConnection(input, to));
+            }
+        }
+    }
+
+    // whatever feeds the first Output Device, which is what would be heard
+    fn program_output(&self) -> Option<OutputId> {
+        let workspace = self.workspace.borrow();
+
+        let mut devices = workspace.modules.iter()
+            .filter(|(_, module)| matches!(module.params(), ModuleParams::OutputDevice(_)))
+            .map(|(module_id, _)| *module_id)
+            .collect::<Vec<_>>();
+
+        devices.sort();
+
+        devices.into_iter()
+            .find_map(|module_id| workspace.connections.get(&InputId(module_id, 0)).copied())
+    }
+
+    // hands renders which have captured their full length off to be saved
+    fn finish_renders(&mut self) {
+        let rendering = self.workspace.borrow().freezes.iter()
+            .filter_map(|freeze| match freeze.state {
+                FreezeState::Rendering { length_secs } => Some((freeze.output, length_secs)),
+                FreezeState::Frozen { .. } => None,
+            })
+            .collect::<Vec<_>>();
+
+        // renders of thawed or deleted outputs are abandoned
+        self.renders.retain(|output, _| rendering.iter().any(|(rendering, _)| rendering == output));
+
+        for (output, length_secs) in rendering {
+            let length = (length_secs * SAMPLE_RATE as f64) as usize * CHANNELS;
+
+            let done = self.renders.get(&output)
+                .map(|samples| samples.len() >= length)
+                .unwrap_or(false);
+
+            if !done {
+                continue;
+            }
+
+            let mut samples = self.renders.remove(&output).expect("renders.remove");
+            samples.truncate(length);
+
+            // the recording already has the outcome of saving
+            if replay::is_replaying() {
+                continue;
+            }
+
+            let base = self.base.clone();
+            let rendered_tx = self.rendered_tx.clone();
+
+            tokio::spawn(async move {
+                let media_id = match freeze::save(base, output, samples).await {
+                    Ok(media_id) => Some(media_id),
+                    Err(e) => {
+                        eprintln!("engine: could not save freeze render: {:?}", e);
+                        None
+                    }
+                };
+
+                let _ = rendered_tx.send((output, media_id));
+            });
+        }
+    }
+
+    // swaps a rendered output over to a Media Source playing the render, and
+    // stops running the chain behind it
+    fn complete_freeze(&mut self, output: OutputId, media_id: Option<MediaId>) {
+        let (rendering, chain) = {
+            let workspace = self.workspace.borrow();
+
+            let rendering = workspace.freezes.iter()
+                .any(|freeze| freeze.output == output && match freeze.state {
+                    FreezeState::Rendering { .. } => true,
+                    FreezeState::Frozen { .. } => false,
+                });
+
+            // the patch may have changed while rendering
+            let chain = freeze::chain(&workspace.connections, output)
+                .filter(|chain| chain.iter().all(|module_id| {
+                    workspace.frozen_by(*module_id).map(|by| by == output).unwrap_or(true)
+                }));
+
+            (rendering, chain)
+        };
+
+        // thawed while the render was saving
+        if !rendering {
+            return;
+        }
+
+        let (media_id, chain) = match (media_id, chain) {
+            (Some(media_id), Some(chain)) => (media_id, chain),
+            _ => {
+                self.workspace.borrow_mut().freezes.retain(|freeze| freeze.output != output);
+                self.log_freezes();
                 return;
             }
+        };
+
+        let head = output.module_id();
+
+        let (geometry, group) = {
+            let workspace = self.workspace.borrow();
+            let geometry = workspace.geometry.get(&head).cloned().unwrap_or_default();
+            (geometry, workspace.group(head).map(str::to_owned))
+        };
+
+        let geometry = WindowGeometry {
+            position: geometry.position.add(Coords { x: 0, y: freeze::SOURCE_OFFSET_Y }),
+            z_index: geometry.z_index,
+        };
+
+        let params = ModuleParams::MediaSource(MediaSourceParams {
+            media_id: Some(media_id),
+            ..Default::default()
+        });
+
+        let source = self.create_module(params, geometry, group);
+
+        self.reconnect(output, OutputId(source, freeze::SOURCE_AUDIO_OUTPUT));
+
+        {
+            let mut workspace = self.workspace.borrow_mut();
+
+            for freeze in workspace.freezes.iter_mut() {
+                if freeze.output == output {
+                    freeze.state = FreezeState::Frozen { source, media_id, modules: chain.clone() };
+                }
+            }
+        }
+
+        self.log_freezes();
+    }
+
+    fn publish_viewer_links(&self) {
+        viewer::publish(self.workspace.borrow().viewer_grants());
+    }
 
-            state.seen.insert(module_id);
+    // ui feedback modules run only if there is time left before deadline.
+    // without a deadline every module runs, as for replays
+    fn run_tick(&mut self, tick: u64, shed: OverrunPolicy, deadline: Option<Instant>, stat: &mut TickStat) -> Vec<(ModuleId, Indication)> {
+        let run_order = self.workspace.run_order();
 
-            let module = &state.modules[&module_id];
+        // tick is not allowed to update any persisted information such as
+        // module params or connections
+        let workspace = self.workspace.borrow_mut_without_sync();
+
+        // run modules in dependency order. modules whose inputs are all ready
+        // can run at once, those which may on worker threads while the engine
+        // thread runs the rest
+
+        let mut buffers = self.buffer_pool.take_outputs();
+
+        // gathered by module and put in run order once all have run, so that
+        // they come out the same however modules were spread across threads
+        let mut tick_indications = HashMap::new();
+
+        // ui feedback modules wait until the rest of the tick is done, so
+        // they only ever use time left over from the program path
+        let mut deferred = Vec::new();
+
+        // frozen chains have been replaced by their renders
+        let frozen = workspace.frozen_modules();
+
+        let t = tick * SAMPLES_PER_TICK as u64;
+
+        let mut pending = run_order.modules.clone();
+        let mut finished = HashSet::new();
+        let mut running = 0;
+
+        loop {
+            let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter()
+                .partition(|module_id| run_order.depends_on[module_id].iter().all(|dep| finished.contains(dep)));
+
+            pending = waiting;
+
+            if ready.is_empty() {
+                if running == 0 {
+                    break;
+                }
+
+                let done = self.pool.wait();
+                running -= 1;
+
+                stat.record_module(done.module_id, done.elapsed);
+                workspace.modules.insert(done.module_id, DynModuleHost::Parallel(done.module));
+
+                for (_, input) in done.inputs {
+                    self.buffer_pool.give(input);
+                }
 
-            for i in 0..module.inputs().len() {
-                let terminal_id = InputId(module_id, i);
+                tick_indications.extend(done.indication.map(|indic| (done.module_id, indic)));
 
-                if let Some(output_id) = state.connections.get(&terminal_id) {
-                    traverse(output_id.module_id(), state);
+                for (i, output) in done.outputs.into_iter().enumerate() {
+                    buffers.insert(OutputId(done.module_id, i), output);
                 }
+
+                finished.insert(done.module_id);
+                continue;
             }
 
-            state.run_order.push(module_id);
+            let mut ready = ready.into_iter().peekable();
+
+            while let Some(module_id) = ready.next() {
+                let module = workspace.modules.get_mut(&module_id)
+                    .expect("module get_mut");
+
+                let is_frozen = frozen.contains(&module_id);
+                let defer = !is_frozen && deadline.is_some() && module.priority() == PriorityClass::UiFeedback;
+
+                if defer {
+                    deferred.push(module_id);
+                }
+
+                // shed, deferred and frozen modules still leave their (empty)
+                // outputs for downstream
+                let mut output_buffers = module.outputs().iter()
+                    .map(|output| self.buffer_pool.take(output.line_type()))
+                    .collect::<Vec<_>>();
+
+                if is_frozen || sheds(shed, module) || defer {
+                    // outputs are left empty
+                } else if workspace.bypassed.contains(&module_id) {
+                    bypass_module(module_id, module, &workspace.connections, &workspace.adapters, &buffers, &mut output_buffers);
+                } else if module.parallel() && running < self.pool.workers() && ready.peek().is_some() {
+                    // only worth handing off while there's something else
+                    // ready for this thread to run meanwhile
+                    let connections = (0..module.inputs().len())
+                        .map(|i| InputId(module_id, i))
+                        .filter_map(|input_id| workspace.connections.get(&input_id).map(|output_id| (input_id, *output_id)))
+                        .collect::<HashMap<_, _>>();
+
+                    let inputs = connections.values()
+                        .filter_map(|output_id| buffers.get(output_id).map(|buffer| (*output_id, self.buffer_pool.take_copy(buffer))))
+                        .collect();
+
+                    let adapters = connections.keys()
+                        .filter_map(|input_id| workspace.adapters.get(input_id).map(|adapter| (*input_id, *adapter)))
+                        .collect();
+
+                    let module = workspace.modules.remove(&module_id)
+                        .and_then(DynModuleHost::into_parallel)
+                        .expect("module remove");
+
+                    self.pool.submit(pool::Job {
+                        module_id,
+                        module,
+                        t,
+                        connections,
+                        adapters,
+                        inputs,
+                        outputs: output_buffers,
+                    });
+
+                    running += 1;
+                    continue;
+                } else {
+                    let (indication, elapsed) = run_module(module_id, &mut **module, t, &workspace.connections, &workspace.adapters, &buffers, &mut output_buffers);
+                    stat.record_module(module_id, elapsed);
+                    tick_indications.extend(indication.map(|indic| (module_id, indic)));
+                }
+
+                for (i, output) in output_buffers.into_iter().enumerate() {
+                    buffers.insert(OutputId(module_id, i), output);
+                }
+
+                finished.insert(module_id);
+            }
         }
 
-        // run modules in dependency order according to BFS above
+        let mut indications = run_order.modules.iter()
+            .filter_map(|module_id| tick_indications.remove(module_id).map(|indic| (*module_id, indic)))
+            .collect::<Vec<_>>();
 
-        let mut buffers = HashMap::<OutputId, Output>::new();
-        let mut indications = Vec::new();
+        // renders capture outputs as they are heard downstream, silence
+        // included if the module was shed
+        let capture = self.capture.as_mut().map(|(output, samples)| (&*output, samples));
+
+        for (output, samples) in self.renders.iter_mut().chain(capture) {
+            match buffers.get(output) {
+                Some(Output::Stereo(buff)) => samples.extend_from_slice(buff),
+                _ => samples.resize(samples.len() + SAMPLES_PER_TICK * CHANNELS, 0.0),
+            }
+        }
+
+        for module_id in deferred {
+            if deadline.map(|deadline| Instant::now() >= deadline).unwrap_or(false) {
+                break;
+            }
 
-        for module_id in topsort.run_order.iter() {
             let module = workspace.modules.get_mut(&module_id)
                 .expect("module get_mut");
 
-            let connections = &workspace.connections;
+            if sheds(shed, module) {
+                continue;
+            }
 
+            // anything downstream has already run with empty inputs, so the
+            // outputs are dropped
             let mut output_buffers = module.outputs().iter()
-                .map(|output| Output::from_line_type(output.line_type()))
+                .map(|output| self.buffer_pool.take(output.line_type()))
                 .collect::<Vec<_>>();
 
-            {
-                let input_refs = module.inputs().iter()
-                    .enumerate()
-                    .map(|(i, _ty)| InputId(*module_id, i))
-                    .map(|input_id| {
-                        connections.get(&input_id)
-                            .and_then(|output_id| buffers.get(output_id))
-                            .map(|output| output.as_input_ref())
-                            .unwrap_or(InputRef::Disconnected)
-                    })
-                    .collect::<Vec<_>>();
+            let (indication, elapsed) = run_module(module_id, &mut **module, t, &workspace.connections, &workspace.adapters, &buffers, &mut output_buffers);
+            stat.record_module(module_id, elapsed);
+            indications.extend(indication.map(|indic| (module_id, indic)));
 
-                let mut output_refs = output_buffers.iter_mut()
-                    .map(|output| output.as_output_ref())
-                    .collect::<Vec<_>>();
+            for output in output_buffers {
+                self.buffer_pool.give(output);
+            }
+        }
 
-                let t = tick * SAMPLES_PER_TICK as u64;
+        self.buffer_pool.give_outputs(buffers);
 
-                let result = stat.record_module(*module_id, || {
-                    module.run_tick(t, &input_refs, &mut output_refs)
-                });
+        indications
+    }
+}
 
-                match result {
-                    None => {}
-                    Some(indic) => {
-                        indications.push((*module_id, indic));
-                    }
-                }
-            }
+fn run_module(
+    module_id: ModuleId,
+    module: &mut dyn DynModuleHostT,
+    t: u64,
+    connections: &HashMap<InputId, OutputId>,
+    adapters: &HashMap<InputId, ChannelAdapter>,
+    buffers: &HashMap<OutputId, Output>,
+    output_buffers: &mut [Output],
+) -> (Option<Indication>, Duration) {
+    let connected = module.inputs().iter()
+        .enumerate()
+        .map(|(i, _ty)| InputId(module_id, i))
+        .map(|input_id| {
+            connections.get(&input_id)
+                .and_then(|output_id| buffers.get(output_id))
+        })
+        .collect::<Vec<_>>();
+
+    // outputs connected to inputs of a different line type are
+    // converted first, see LineType::connects_to
+    let converted = module.inputs().iter()
+        .zip(connected.iter())
+        .enumerate()
+        .map(|(i, (input, output))| {
+            let adapter = adapters.get(&InputId(module_id, i)).copied().unwrap_or_default();
+            output.and_then(|output| output.convert_to(input.line_type(), adapter))
+        })
+        .collect::<Vec<_>>();
+
+    let input_refs = connected.iter()
+        .zip(converted.iter())
+        .map(|(output, converted)| {
+            converted.as_ref()
+                .or(*output)
+                .map(|output| output.as_input_ref())
+                .unwrap_or(InputRef::Disconnected)
+        })
+        .collect::<Vec<_>>();
+
+    let mut output_refs = output_buffers.iter_mut()
+        .map(|output| output.as_output_ref())
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let indication = module.run_tick(t, &input_refs, &mut output_refs);
+    let elapsed = start.elapsed();
+
+    (indication, elapsed)
+}
+
+// passes a bypassed module's inputs straight through instead of running it.
+// each output takes the input of the same line type in the same position
+// among inputs of that type, eg. the first stereo output takes the first
+// stereo input. outputs without a matching input are left silent
+fn bypass_module(
+    module_id: ModuleId,
+    module: &DynModuleHost,
+    connections: &HashMap<InputId, OutputId>,
+    adapters: &HashMap<InputId, ChannelAdapter>,
+    buffers: &HashMap<OutputId, Output>,
+    outputs: &mut [Output],
+) {
+    let mut taken = vec![false; module.inputs().len()];
+
+    for output in outputs.iter_mut() {
+        let line_type = output.line_type();
+
+        let input = (0..taken.len())
+            .find(|i| !taken[*i] && module.inputs()[*i].line_type() == line_type);
+
+        let passed = input.and_then(|i| {
+            taken[i] = true;
+
+            let input_id = InputId(module_id, i);
+            let adapter = adapters.get(&input_id).copied().unwrap_or_default();
+
+            connections.get(&input_id)
+                .and_then(|output_id| buffers.get(output_id))
+                .map(|output| (output, adapter))
+        });
 
-            for (i, output) in output_buffers.into_iter().enumerate() {
-                buffers.insert(OutputId(*module_id, i), output);
+        if let Some((passed, adapter)) = passed {
+            if !output.copy_from(passed) {
+                if let Some(converted) = passed.convert_to(line_type, adapter) {
+                    *output = converted;
+                }
             }
         }
+    }
+}
 
-        indications
+// whether an overrun policy skips running a module for a tick
+fn sheds(policy: OverrunPolicy, module: &DynModuleHost) -> bool {
+    match policy {
+        OverrunPolicy::Lag | OverrunPolicy::SkipTick => false,
+        OverrunPolicy::AudioPriority => module.priority() == PriorityClass::Video,
+        OverrunPolicy::SkipNonEssential => module.priority() == PriorityClass::UiFeedback,
     }
 }
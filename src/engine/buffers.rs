@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::mem;
+
+use mixlab_protocol::{BufferPoolInfo, Bytes, LineType, OutputId};
+
+use crate::engine::Output;
+
+/// Output buffers kept from one tick to the next, so that the engine isn't
+/// allocating and freeing every module's outputs sixty times a second.
+/// Buffers are handed out empty, just as newly allocated ones would be.
+pub struct BufferPool {
+    free: HashMap<LineType, Vec<Output>>,
+    // last tick's outputs by id, emptied and kept for its capacity
+    outputs: HashMap<OutputId, Output>,
+    allocated: u64,
+}
+
+impl BufferPool {
+    pub fn new() -> Self {
+        BufferPool {
+            free: HashMap::new(),
+            outputs: HashMap::new(),
+            allocated: 0,
+        }
+    }
+
+    pub fn take(&mut self, line_type: LineType) -> Output {
+        match self.free.get_mut(&line_type).and_then(Vec::pop) {
+            Some(mut output) => {
+                output.clear();
+                output
+            }
+            None => {
+                self.allocated += 1;
+                Output::from_line_type(line_type)
+            }
+        }
+    }
+
+    pub fn take_copy(&mut self, from: &Output) -> Output {
+        let mut output = self.take(from.line_type());
+        output.copy_from(from);
+        output
+    }
+
+    pub fn give(&mut self, output: Output) {
+        self.free.entry(output.line_type())
+            .or_insert_with(Vec::new)
+            .push(output);
+    }
+
+    /// A map to hold a tick's outputs in.
+    pub fn take_outputs(&mut self) -> HashMap<OutputId, Output> {
+        mem::take(&mut self.outputs)
+    }
+
+    /// Returns the map from `take_outputs` along with every output in it.
+    pub fn give_outputs(&mut self, mut outputs: HashMap<OutputId, Output>) {
+        for (_, output) in outputs.drain() {
+            self.give(output);
+        }
+
+        self.outputs = outputs;
+    }
+
+    pub fn info(&self) -> BufferPoolInfo {
+        let pooled = self.free.values().flatten();
+
+        BufferPoolInfo {
+            allocated: self.allocated,
+            pooled: pooled.clone().count() as u64,
+            memory: Bytes(pooled.map(|output| output.size() as u64).sum()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_buffers_once_given_back() {
+        let mut pool = BufferPool::new();
+
+        let mut output = pool.take(LineType::Mono);
+        if let Output::Mono(buff) = &mut output {
+            buff[0] = 1.0;
+        }
+
+        pool.give(output);
+
+        match pool.take(LineType::Mono) {
+            Output::Mono(buff) => assert!(buff.iter().all(|sample| *sample == 0.0)),
+            _ => panic!("expected mono output"),
+        }
+
+        pool.take(LineType::Stereo);
+
+        assert_eq!(2, pool.info().allocated);
+    }
+}
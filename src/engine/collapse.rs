@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use mixlab_protocol::{ChannelAdapter, InputId, MacroConnection, MacroParams, MacroTerminal, ModuleId, ModuleParams, OutputId};
+
+/// A module being collapsed into a macro, along with how many terminals it has.
+pub struct Member {
+    pub id: ModuleId,
+    pub params: ModuleParams,
+    pub inputs: usize,
+    pub outputs: usize,
+}
+
+pub struct Collapse {
+    pub params: MacroParams,
+    /// For each of the macro's inputs, the output outside it which fed the
+    /// module input it stands for
+    pub inputs: Vec<Option<OutputId>>,
+    /// For each of the macro's outputs, the inputs outside it which it feeds
+    pub outputs: Vec<Vec<InputId>>,
+}
+
+/// Builds a macro from `members`, in the order given. Cables between members
+/// go inside the macro. Every input not fed from inside is exposed, as is
+/// every output which is used outside or not at all, so that nothing which
+/// could be patched before is lost. Cables going inside keep their adapters.
+pub fn collapse(name: String, members: Vec<Member>, connections: &HashMap<InputId, OutputId>, adapters: &HashMap<InputId, ChannelAdapter>) -> Collapse {
+    let index = members.iter()
+        .enumerate()
+        .map(|(index, member)| (member.id, index))
+        .collect::<HashMap<_, _>>();
+
+    let mut macro_connections = Vec::new();
+    let mut exposed_inputs = Vec::new();
+    let mut inputs = Vec::new();
+
+    for (module, member) in members.iter().enumerate() {
+        for terminal in 0..member.inputs {
+            let input = InputId(member.id, terminal);
+            let from = connections.get(&input).copied();
+
+            match from.and_then(|from| index.get(&from.module_id()).map(|from_module| (*from_module, from.index()))) {
+                Some(output) => {
+                    let adapter = adapters.get(&input).copied().unwrap_or_default();
+                    macro_connections.push(MacroConnection { output, input: (module, terminal), adapter });
+                }
+                None => {
+                    exposed_inputs.push(MacroTerminal { module, terminal });
+                    inputs.push(from);
+                }
+            }
+        }
+    }
+
+    let mut exposed_outputs = Vec::new();
+    let mut outputs = Vec::new();
+
+    for (module, member) in members.iter().enumerate() {
+        for terminal in 0..member.outputs {
+            let output = OutputId(member.id, terminal);
+
+            let used_inside = macro_connections.iter()
+                .any(|conn| conn.output == (module, terminal));
+
+            let mut used_outside = connections.iter()
+                .filter(|(input, from)| **from == output && !index.contains_key(&input.module_id()))
+                .map(|(input, _)| *input)
+                .collect::<Vec<_>>();
+
+            // keep the order independent of hash order so that replays are
+            // reproducible
+            used_outside.sort();
+
+            if !used_inside || !used_outside.is_empty() {
+                exposed_outputs.push(MacroTerminal { module, terminal });
+                outputs.push(used_outside);
+            }
+        }
+    }
+
+    let params = MacroParams {
+        name,
+        modules: members.into_iter().map(|member| member.params).collect(),
+        connections: macro_connections,
+        inputs: exposed_inputs,
+        outputs: exposed_outputs,
+    };
+
+    Collapse { params, inputs, outputs }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::*;
+
+    fn module(id: usize) -> ModuleId {
+        ModuleId(NonZeroUsize::new(id).unwrap())
+    }
+
+    fn member(id: usize, inputs: usize, outputs: usize) -> Member {
+        Member { id: module(id), params: ModuleParams::StereoPanner(()), inputs, outputs }
+    }
+
+    // source(1) -> filter(2) -> amp(3) -> output(4), with the filter also
+    // feeding a meter(5). filter and amp are collapsed
+    fn connections() -> HashMap<InputId, OutputId> {
+        let mut connections = HashMap::new();
+        connections.insert(InputId(module(2), 0), OutputId(module(1), 0));
+        connections.insert(InputId(module(3), 0), OutputId(module(2), 0));
+        connections.insert(InputId(module(4), 0), OutputId(module(3), 0));
+        connections.insert(InputId(module(5), 0), OutputId(module(2), 0));
+        connections
+    }
+
+    #[test]
+    fn cables_between_members_go_inside() {
+        let mut adapters = HashMap::new();
+        adapters.insert(InputId(module(3), 0), ChannelAdapter::Left);
+
+        let collapse = collapse("fx".to_owned(), vec![member(2, 2, 1), member(3, 1, 1)], &connections(), &adapters);
+
+        assert_eq!(vec![MacroConnection { output: (0, 0), input: (1, 0), adapter: ChannelAdapter::Left }], collapse.params.connections);
+    }
+
+    #[test]
+    fn unfed_inputs_are_exposed_with_what_fed_them() {
+        let collapse = collapse("fx".to_owned(), vec![member(2, 2, 1), member(3, 1, 1)], &connections(), &HashMap::new());
+
+        assert_eq!(vec![MacroTerminal { module: 0, terminal: 0 }, MacroTerminal { module: 0, terminal: 1 }], collapse.params.inputs);
+        assert_eq!(vec![Some(OutputId(module(1), 0)), None], collapse.inputs);
+    }
+
+    #[test]
+    fn outputs_used_outside_or_not_at_all_are_exposed() {
+        let mut connections = connections();
+        connections.remove(&InputId(module(4), 0));
+
+        let collapse = collapse("fx".to_owned(), vec![member(2, 2, 1), member(3, 1, 1)], &connections, &HashMap::new());
+
+        // the filter feeds both the amp and the meter, the amp nothing
+        assert_eq!(vec![MacroTerminal { module: 0, terminal: 0 }, MacroTerminal { module: 1, terminal: 0 }], collapse.params.outputs);
+        assert_eq!(vec![vec![InputId(module(5), 0)], vec![]], collapse.outputs);
+    }
+}
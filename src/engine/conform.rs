@@ -0,0 +1,164 @@
+use mixlab_util::time::{MediaDuration, MediaTime};
+
+use crate::engine::{InputRef, VideoFrame, SAMPLE_RATE, SAMPLES_PER_TICK, TICKS_PER_SECOND};
+use crate::video;
+
+/// Frame rate video modules take their inputs at unless they have reason to
+/// pick their own. Matches what stream outputs advertise to servers.
+pub const DEFAULT_FPS: u32 = 30;
+
+/// Conforms the video inputs of a module to the frame rate it asks for, so
+/// that it sees one frame every frame interval whatever rate its sources run
+/// at. Frames are repeated while a source is slower and dropped while it is
+/// faster. Frame intervals are counted from the start of the engine clock, so
+/// inputs conformed to the same rate line up with each other.
+#[derive(Debug, Default)]
+pub struct Conform {
+    fps: Option<u32>,
+    inputs: Vec<ConformInput>,
+}
+
+#[derive(Debug)]
+struct ConformInput {
+    // arrived since the last frame interval. a newer arrival replaces it,
+    // dropping it
+    pending: Option<video::Frame>,
+    // passed on at the last frame interval, repeated until something newer
+    // arrives
+    last: Option<video::Frame>,
+    // when the last frame to arrive runs out by its duration hint. repeats
+    // stop there, so that a source which goes away doesn't freeze on its
+    // last picture
+    held_until: MediaTime,
+}
+
+impl Conform {
+    pub fn new() -> Self {
+        Conform::default()
+    }
+
+    /// Conforms each video input to `fps` for the tick starting at sample
+    /// `t`. Returns `None` when the module takes its inputs as they are.
+    pub fn run(&mut self, fps: Option<u32>, t: u64, inputs: &[InputRef]) -> Option<Vec<Option<VideoFrame>>> {
+        if fps != self.fps {
+            self.fps = fps;
+            self.inputs.clear();
+        }
+
+        // one tick carries at most one frame
+        let fps = fps?.max(1).min(TICKS_PER_SECOND as u32);
+
+        self.inputs.resize_with(inputs.len(), || ConformInput {
+            pending: None,
+            last: None,
+            held_until: MediaTime::zero(),
+        });
+
+        let interval = interval_in_tick(fps, t);
+
+        let frames = inputs.iter()
+            .zip(self.inputs.iter_mut())
+            .map(|(input, conform)| match input {
+                InputRef::Video(frame) => conform.run(fps, t, *frame, interval),
+                _ => None,
+            })
+            .collect();
+
+        Some(frames)
+    }
+}
+
+impl ConformInput {
+    fn run(&mut self, fps: u32, t: u64, frame: Option<&VideoFrame>, interval: Option<u64>) -> Option<VideoFrame> {
+        let tick_start = MediaTime::new(t as i64, SAMPLE_RATE as i64);
+
+        if let Some(frame) = frame {
+            self.held_until = tick_start + frame.tick_offset + frame.data.duration_hint;
+            self.pending = Some(frame.data.clone());
+        }
+
+        let interval = interval?;
+        let interval_start = MediaTime::new(interval as i64, fps as i64);
+
+        let data = match self.pending.take() {
+            Some(frame) => {
+                self.last = Some(frame.clone());
+                frame
+            }
+            None if interval_start < self.held_until => {
+                // repeats carry no source packets, those went out with the
+                // first showing
+                video::Frame {
+                    passthrough: None,
+                    ..self.last.clone()?
+                }
+            }
+            None => {
+                self.last = None;
+                return None;
+            }
+        };
+
+        Some(VideoFrame {
+            data: video::Frame {
+                duration_hint: MediaDuration::new(1, fps as i64),
+                ..data
+            },
+            tick_offset: interval_start - tick_start,
+        })
+    }
+}
+
+// the frame interval starting within the tick starting at sample `t`, if
+// any. there is never more than one as frame rates are capped at the tick rate
+fn interval_in_tick(fps: u32, t: u64) -> Option<u64> {
+    let fps = fps as u64;
+    let rate = SAMPLE_RATE as u64;
+
+    let interval = (t * fps + rate - 1) / rate;
+
+    if interval * rate < (t + SAMPLES_PER_TICK as u64) * fps {
+        Some(interval)
+    } else {
+        None
+    }
+}
+
+/// Swaps a module's video inputs for the frames conformed for it.
+pub fn input_refs<'a>(inputs: &[InputRef<'a>], frames: &'a [Option<VideoFrame>]) -> Vec<InputRef<'a>> {
+    inputs.iter()
+        .zip(frames)
+        .map(|(input, frame)| match input {
+            InputRef::Video(_) => InputRef::Video(frame.as_ref()),
+            input => *input,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intervals(fps: u32, seconds: u64) -> Vec<u64> {
+        (0..seconds * TICKS_PER_SECOND as u64)
+            .filter_map(|tick| interval_in_tick(fps, tick * SAMPLES_PER_TICK as u64))
+            .collect()
+    }
+
+    #[test]
+    fn every_interval_falls_in_exactly_one_tick() {
+        for fps in &[1, 24, 25, 30, 50, TICKS_PER_SECOND as u32] {
+            let expected = (0..2 * *fps as u64).collect::<Vec<_>>();
+            assert_eq!(expected, intervals(*fps, 2));
+        }
+    }
+
+    #[test]
+    fn intervals_at_half_the_tick_rate_fall_on_every_other_tick() {
+        let ticks = (0..6)
+            .filter(|tick| interval_in_tick(TICKS_PER_SECOND as u32 / 2, tick * SAMPLES_PER_TICK as u64).is_some())
+            .collect::<Vec<u64>>();
+
+        assert_eq!(vec![0, 2, 4], ticks);
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{BTreeSet, HashMap};
+
+use mixlab_protocol::{InputId, MediaId, ModuleId, OutputId};
+
+use crate::engine::{Sample, CHANNELS, SAMPLE_RATE};
+use crate::project::ProjectBaseRef;
+use crate::project::media::{MediaUpload, UploadError, UploadInfo};
+
+// longest render accepted, renders are held in memory until written out
+pub const MAX_LENGTH_SECS: f64 = 600.0;
+
+// the stand-in Media Source's stereo output, after its video output
+pub const SOURCE_AUDIO_OUTPUT: usize = 1;
+
+// how far below the frozen module its stand-in is placed
+pub const SOURCE_OFFSET_Y: i32 = 40;
+
+/// Saves a render to the media library. Renders stay there after thawing,
+/// as snapshots saved while frozen may still play them.
+pub async fn save(base: ProjectBaseRef, output: OutputId, samples: Vec<Sample>) -> Result<MediaId, UploadError> {
+    let info = UploadInfo {
+        name: format!("freeze-{}-{}.wav", (output.0).0, output.1),
+        kind: "audio/wav".to_owned(),
+    };
+
+    let mut upload = MediaUpload::new(base, info).await?;
+    upload.receive_bytes(&encode_wav(&samples)).await?;
+    upload.finalize().await
+}
+
+/// Modules which can stop running once `output` is frozen: its own module
+/// and everything upstream feeding nothing but the chain. Returns `None` if
+/// the module's other outputs are still needed elsewhere, in which case
+/// freezing would save nothing.
+pub fn chain(connections: &HashMap<InputId, OutputId>, output: OutputId) -> Option<Vec<ModuleId>> {
+    let head = output.module_id();
+
+    let mut chain = BTreeSet::new();
+    chain.insert(head);
+
+    let head_needed = connections.iter()
+        .any(|(input, from)| from.module_id() == head && *from != output && input.module_id() != head);
+
+    if head_needed {
+        return None;
+    }
+
+    loop {
+        let upstream = connections.iter()
+            .filter(|(input, from)| chain.contains(&input.module_id()) && !chain.contains(&from.module_id()))
+            .map(|(_, from)| from.module_id())
+            .collect::<BTreeSet<_>>();
+
+        let mut grew = false;
+
+        for module_id in upstream {
+            let feeds_only_chain = connections.iter()
+                .filter(|(_, from)| from.module_id() == module_id)
+                .all(|(input, _)| chain.contains(&input.module_id()));
+
+            if feeds_only_chain {
+                chain.insert(module_id);
+                grew = true;
+            }
+        }
+
+        if !grew {
+            return Some(chain.into_iter().collect());
+        }
+    }
+}
+
+/// Interleaved stereo samples as a 32 bit float WAV file.
+pub fn encode_wav(samples: &[Sample]) -> Vec<u8> {
+    const BYTES_PER_SAMPLE: usize = 4;
+    // WAVE_FORMAT_IEEE_FLOAT
+    const FORMAT_FLOAT: u16 = 3;
+
+    let data_len = (samples.len() * BYTES_PER_SAMPLE) as u32;
+    let block_align = (CHANNELS * BYTES_PER_SAMPLE) as u16;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+
+    wav.extend(b"RIFF");
+    wav.extend(&(36 + data_len).to_le_bytes());
+    wav.extend(b"WAVE");
+
+    wav.extend(b"fmt ");
+    wav.extend(&16u32.to_le_bytes());
+    wav.extend(&FORMAT_FLOAT.to_le_bytes());
+    wav.extend(&(CHANNELS as u16).to_le_bytes());
+    wav.extend(&(SAMPLE_RATE as u32).to_le_bytes());
+    wav.extend(&(SAMPLE_RATE as u32 * block_align as u32).to_le_bytes());
+    wav.extend(&block_align.to_le_bytes());
+    wav.extend(&(BYTES_PER_SAMPLE as u16 * 8).to_le_bytes());
+
+    wav.extend(b"data");
+    wav.extend(&data_len.to_le_bytes());
+
+    for sample in samples {
+        wav.extend(&sample.to_le_bytes());
+    }
+
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::*;
+
+    fn module(id: usize) -> ModuleId {
+        ModuleId(NonZeroUsize::new(id).unwrap())
+    }
+
+    // osc(1) -> filter(2) -> mixer(3), with lfo(4) modulating both the
+    // filter and another module(5) outside the chain
+    fn connections() -> HashMap<InputId, OutputId> {
+        let mut connections = HashMap::new();
+        connections.insert(InputId(module(2), 0), OutputId(module(1), 0));
+        connections.insert(InputId(module(3), 0), OutputId(module(2), 0));
+        connections.insert(InputId(module(2), 1), OutputId(module(4), 0));
+        connections.insert(InputId(module(5), 0), OutputId(module(4), 0));
+        connections
+    }
+
+    #[test]
+    fn chain_stops_at_modules_needed_elsewhere() {
+        let chain = chain(&connections(), OutputId(module(2), 0));
+        assert_eq!(Some(vec![module(1), module(2)]), chain);
+    }
+
+    #[test]
+    fn chain_includes_everything_feeding_only_the_chain() {
+        let mut connections = connections();
+        connections.remove(&InputId(module(5), 0));
+
+        let chain = chain(&connections, OutputId(module(2), 0));
+        assert_eq!(Some(vec![module(1), module(2), module(4)]), chain);
+    }
+
+    #[test]
+    fn cannot_freeze_a_module_with_other_outputs_in_use() {
+        let mut connections = connections();
+        connections.insert(InputId(module(5), 1), OutputId(module(2), 1));
+
+        assert_eq!(None, chain(&connections, OutputId(module(2), 0)));
+    }
+
+    #[test]
+    fn wav_header_describes_float_stereo() {
+        let wav = encode_wav(&[0.0; 8]);
+
+        assert_eq!(44 + 8 * 4, wav.len());
+        assert_eq!(b"RIFF", &wav[0..4]);
+        assert_eq!(b"WAVE", &wav[8..12]);
+        assert_eq!(3, u16::from_le_bytes([wav[20], wav[21]]));
+        assert_eq!(CHANNELS as u16, u16::from_le_bytes([wav[22], wav[23]]));
+        assert_eq!(SAMPLE_RATE as u32, u32::from_le_bytes([wav[24], wav[25], wav[26], wav[27]]));
+        assert_eq!(32, u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]));
+    }
+}
@@ -1,12 +1,15 @@
-use mixlab_protocol::LineType;
+use std::mem;
+
+use mixlab_protocol::{ChannelAdapter, LineType};
 use mixlab_util::time::MediaDuration;
 
-use crate::engine::{CHANNELS, SAMPLES_PER_TICK};
+use crate::engine::{CHANNELS, AMBISONIC_CHANNELS, SAMPLES_PER_TICK, CONTROL_PERIOD, CONTROL_SAMPLES_PER_TICK};
 use crate::engine::Sample;
 use crate::video;
 
 pub static ZERO_BUFFER_STEREO: [Sample; SAMPLES_PER_TICK * CHANNELS] = [0.0; SAMPLES_PER_TICK * CHANNELS];
 pub static ZERO_BUFFER_MONO: [Sample; SAMPLES_PER_TICK] = [0.0; SAMPLES_PER_TICK];
+pub static ZERO_BUFFER_AMBISONIC: [Sample; SAMPLES_PER_TICK * AMBISONIC_CHANNELS] = [0.0; SAMPLES_PER_TICK * AMBISONIC_CHANNELS];
 
 #[derive(Debug, Clone)]
 pub struct VideoFrame {
@@ -16,11 +19,14 @@ pub struct VideoFrame {
     pub tick_offset: MediaDuration,
 }
 
+#[derive(Clone, Copy)]
 pub enum InputRef<'a> {
     Disconnected,
     Mono(&'a [Sample]),
     Stereo(&'a [Sample]),
     Video(Option<&'a VideoFrame>),
+    Control(&'a [Sample]),
+    Ambisonic(&'a [Sample]),
 }
 
 impl<'a> InputRef<'a> {
@@ -29,7 +35,9 @@ impl<'a> InputRef<'a> {
             InputRef::Disconnected => false,
             InputRef::Mono(_) |
             InputRef::Stereo(_) |
-            InputRef::Video(_) => true,
+            InputRef::Video(_) |
+            InputRef::Control(_) |
+            InputRef::Ambisonic(_) => true,
         }
     }
 
@@ -39,6 +47,8 @@ impl<'a> InputRef<'a> {
             InputRef::Mono(buff) => buff,
             InputRef::Stereo(_) => panic!("expected mono input, got stereo"),
             InputRef::Video(_) => panic!("expected mono input, got avc"),
+            InputRef::Control(_) => panic!("expected mono input, got control"),
+            InputRef::Ambisonic(_) => panic!("expected mono input, got ambisonic"),
         }
     }
 
@@ -48,6 +58,8 @@ impl<'a> InputRef<'a> {
             InputRef::Stereo(buff) => buff,
             InputRef::Mono(_) => panic!("expected stereo input, got mono"),
             InputRef::Video(_) => panic!("expected stereo input, got avc"),
+            InputRef::Control(_) => panic!("expected stereo input, got control"),
+            InputRef::Ambisonic(_) => panic!("expected stereo input, got ambisonic"),
         }
     }
 
@@ -57,14 +69,69 @@ impl<'a> InputRef<'a> {
             InputRef::Stereo(_) => panic!("expected stereo input, got stereo"),
             InputRef::Mono(_) => panic!("expected stereo input, got mono"),
             InputRef::Video(frame) => *frame,
+            InputRef::Control(_) => panic!("expected video input, got control"),
+            InputRef::Ambisonic(_) => panic!("expected video input, got ambisonic"),
+        }
+    }
+
+    // control inputs are usually optional modulation of a parameter, so
+    // disconnected inputs are reported as None rather than a zero buffer
+    pub fn expect_control(&self) -> Option<ControlRef<'a>> {
+        match self {
+            InputRef::Disconnected => None,
+            InputRef::Control(buff) => Some(ControlRef(buff)),
+            InputRef::Mono(_) => panic!("expected control input, got mono"),
+            InputRef::Stereo(_) => panic!("expected control input, got stereo"),
+            InputRef::Video(_) => panic!("expected control input, got video"),
+            InputRef::Ambisonic(_) => panic!("expected control input, got ambisonic"),
+        }
+    }
+
+    // interleaved B-format, AMBISONIC_CHANNELS samples to a frame
+    pub fn expect_ambisonic(&self) -> &'a [Sample] {
+        match self {
+            InputRef::Disconnected => &ZERO_BUFFER_AMBISONIC,
+            InputRef::Ambisonic(buff) => buff,
+            InputRef::Mono(_) => panic!("expected ambisonic input, got mono"),
+            InputRef::Stereo(_) => panic!("expected ambisonic input, got stereo"),
+            InputRef::Video(_) => panic!("expected ambisonic input, got video"),
+            InputRef::Control(_) => panic!("expected ambisonic input, got control"),
         }
     }
 }
 
+#[derive(Clone, Copy)]
+pub struct ControlRef<'a>(&'a [Sample]);
+
+impl<'a> ControlRef<'a> {
+    /// Value of the control signal at a sample frame within the tick,
+    /// linearly interpolated between control points.
+    pub fn at(&self, frame: usize) -> Sample {
+        let index = frame / CONTROL_PERIOD;
+        let frac = (frame % CONTROL_PERIOD) as Sample / CONTROL_PERIOD as Sample;
+
+        match (self.0.get(index), self.0.get(index + 1)) {
+            (Some(a), Some(b)) => a + (b - a) * frac,
+            (Some(a), None) => *a,
+            // past the end of the buffer, hold the last value
+            (None, _) => self.0.last().cloned().unwrap_or(0.0),
+        }
+    }
+
+    pub fn upsample(&self, out: &mut [Sample]) {
+        for (frame, sample) in out.iter_mut().enumerate() {
+            *sample = self.at(frame);
+        }
+    }
+}
+
+#[derive(Clone)]
 pub enum Output {
     Mono(Vec<Sample>),
     Stereo(Vec<Sample>),
     Video(Option<VideoFrame>),
+    Control(Vec<Sample>),
+    Ambisonic(Vec<Sample>),
 }
 
 impl Output {
@@ -73,6 +140,102 @@ impl Output {
             LineType::Mono => Output::Mono(vec![0.0; SAMPLES_PER_TICK]),
             LineType::Stereo => Output::Stereo(vec![0.0; SAMPLES_PER_TICK * CHANNELS]),
             LineType::Video => Output::Video(None),
+            LineType::Control => Output::Control(vec![0.0; CONTROL_SAMPLES_PER_TICK]),
+            LineType::Ambisonic => Output::Ambisonic(vec![0.0; SAMPLES_PER_TICK * AMBISONIC_CHANNELS]),
+        }
+    }
+
+    pub fn line_type(&self) -> LineType {
+        match self {
+            Output::Mono(_) => LineType::Mono,
+            Output::Stereo(_) => LineType::Stereo,
+            Output::Video(_) => LineType::Video,
+            Output::Control(_) => LineType::Control,
+            Output::Ambisonic(_) => LineType::Ambisonic,
+        }
+    }
+
+    /// Empties this output for reuse, leaving it as `from_line_type` would.
+    /// Video frames are shared rather than copied, so are simply dropped.
+    pub fn clear(&mut self) {
+        match self {
+            Output::Mono(buff) |
+            Output::Stereo(buff) |
+            Output::Control(buff) |
+            Output::Ambisonic(buff) => {
+                for sample in buff.iter_mut() {
+                    *sample = 0.0;
+                }
+            }
+            Output::Video(frame) => { *frame = None; }
+        }
+    }
+
+    /// Copies another output of the same line type into this one, returns
+    /// false if the line types differ.
+    pub fn copy_from(&mut self, other: &Output) -> bool {
+        match (self, other) {
+            (Output::Mono(to), Output::Mono(from)) |
+            (Output::Stereo(to), Output::Stereo(from)) |
+            (Output::Control(to), Output::Control(from)) |
+            (Output::Ambisonic(to), Output::Ambisonic(from)) => {
+                to.copy_from_slice(from);
+                true
+            }
+            (Output::Video(to), Output::Video(from)) => {
+                *to = from.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // bytes of sample data held, for reporting on pooled buffers
+    pub fn size(&self) -> usize {
+        match self {
+            Output::Mono(buff) |
+            Output::Stereo(buff) |
+            Output::Control(buff) |
+            Output::Ambisonic(buff) => buff.capacity() * mem::size_of::<Sample>(),
+            Output::Video(_) => 0,
+        }
+    }
+
+    /// Converts this output for use by an input of a different line type,
+    /// returns `None` if no conversion is necessary. `adapter` chooses the
+    /// channels for conversions between mono and stereo.
+    pub fn convert_to(&self, line_type: LineType, adapter: ChannelAdapter) -> Option<Output> {
+        match (self, line_type) {
+            (Output::Control(buff), LineType::Mono) => {
+                let mut mono = vec![0.0; SAMPLES_PER_TICK];
+                ControlRef(buff).upsample(&mut mono);
+                Some(Output::Mono(mono))
+            }
+            (Output::Mono(buff), LineType::Stereo) => {
+                let mut stereo = vec![0.0; SAMPLES_PER_TICK * CHANNELS];
+
+                for (frame, sample) in stereo.chunks_mut(CHANNELS).zip(buff) {
+                    match adapter {
+                        ChannelAdapter::Both => { frame[0] = *sample; frame[1] = *sample; }
+                        ChannelAdapter::Left => { frame[0] = *sample; }
+                        ChannelAdapter::Right => { frame[1] = *sample; }
+                    }
+                }
+
+                Some(Output::Stereo(stereo))
+            }
+            (Output::Stereo(buff), LineType::Mono) => {
+                let mono = buff.chunks(CHANNELS)
+                    .map(|frame| match adapter {
+                        ChannelAdapter::Both => (frame[0] + frame[1]) / 2.0,
+                        ChannelAdapter::Left => frame[0],
+                        ChannelAdapter::Right => frame[1],
+                    })
+                    .collect();
+
+                Some(Output::Mono(mono))
+            }
+            _ => None,
         }
     }
 
@@ -81,6 +244,8 @@ impl Output {
             Output::Mono(buff) => InputRef::Mono(buff),
             Output::Stereo(buff) => InputRef::Stereo(buff),
             Output::Video(packet) => InputRef::Video(packet.as_ref()),
+            Output::Control(buff) => InputRef::Control(buff),
+            Output::Ambisonic(buff) => InputRef::Ambisonic(buff),
         }
     }
 
@@ -89,6 +254,8 @@ impl Output {
             Output::Mono(buff) => OutputRef::Mono(buff),
             Output::Stereo(buff) => OutputRef::Stereo(buff),
             Output::Video(frame) => OutputRef::Video(frame),
+            Output::Control(buff) => OutputRef::Control(buff),
+            Output::Ambisonic(buff) => OutputRef::Ambisonic(buff),
         }
     }
 }
@@ -96,7 +263,9 @@ impl Output {
 pub enum OutputRef<'a> {
     Mono(&'a mut [Sample]),
     Stereo(&'a mut [Sample]),
-    Video(&'a mut Option<VideoFrame>)
+    Video(&'a mut Option<VideoFrame>),
+    Control(&'a mut [Sample]),
+    Ambisonic(&'a mut [Sample]),
 }
 
 impl<'a> OutputRef<'a> {
@@ -105,6 +274,8 @@ impl<'a> OutputRef<'a> {
             OutputRef::Mono(buff) => buff,
             OutputRef::Stereo(_) => panic!("expected mono output, got stereo"),
             OutputRef::Video(_) => panic!("expected mono output, got video"),
+            OutputRef::Control(_) => panic!("expected mono output, got control"),
+            OutputRef::Ambisonic(_) => panic!("expected mono output, got ambisonic"),
         }
     }
 
@@ -113,6 +284,8 @@ impl<'a> OutputRef<'a> {
             OutputRef::Stereo(buff) => buff,
             OutputRef::Mono(_) => panic!("expected stereo output, got mono"),
             OutputRef::Video(_) => panic!("expected mono output, got video"),
+            OutputRef::Control(_) => panic!("expected stereo output, got control"),
+            OutputRef::Ambisonic(_) => panic!("expected stereo output, got ambisonic"),
         }
     }
 
@@ -121,6 +294,78 @@ impl<'a> OutputRef<'a> {
             OutputRef::Stereo(_) => panic!("expected stereo output, got video"),
             OutputRef::Mono(_) => panic!("expected mono input, got video"),
             OutputRef::Video(frame) => *frame,
+            OutputRef::Control(_) => panic!("expected video output, got control"),
+            OutputRef::Ambisonic(_) => panic!("expected video output, got ambisonic"),
+        }
+    }
+
+    pub fn expect_control(&mut self) -> &mut [Sample] {
+        match self {
+            OutputRef::Control(buff) => buff,
+            OutputRef::Mono(_) => panic!("expected control output, got mono"),
+            OutputRef::Stereo(_) => panic!("expected control output, got stereo"),
+            OutputRef::Video(_) => panic!("expected control output, got video"),
+            OutputRef::Ambisonic(_) => panic!("expected control output, got ambisonic"),
         }
     }
+
+    pub fn expect_ambisonic(&mut self) -> &mut [Sample] {
+        match self {
+            OutputRef::Ambisonic(buff) => buff,
+            OutputRef::Mono(_) => panic!("expected ambisonic output, got mono"),
+            OutputRef::Stereo(_) => panic!("expected ambisonic output, got stereo"),
+            OutputRef::Video(_) => panic!("expected ambisonic output, got video"),
+            OutputRef::Control(_) => panic!("expected ambisonic output, got control"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_upsamples_to_mono() {
+        let control = Output::Control((0..CONTROL_SAMPLES_PER_TICK).map(|i| i as Sample).collect());
+
+        let mono = match control.convert_to(LineType::Mono, ChannelAdapter::default()) {
+            Some(Output::Mono(mono)) => mono,
+            _ => panic!("expected mono output"),
+        };
+
+        assert_eq!(SAMPLES_PER_TICK, mono.len());
+        assert_eq!(0.0, mono[0]);
+        assert_eq!(1.0, mono[CONTROL_PERIOD]);
+        assert_eq!(1.0 + (CONTROL_PERIOD / 2) as Sample / CONTROL_PERIOD as Sample, mono[CONTROL_PERIOD + CONTROL_PERIOD / 2]);
+    }
+
+    #[test]
+    fn mono_upmixes_to_stereo() {
+        let mono = Output::Mono(vec![0.5; SAMPLES_PER_TICK]);
+
+        let stereo = |adapter| match mono.convert_to(LineType::Stereo, adapter) {
+            Some(Output::Stereo(stereo)) => stereo,
+            _ => panic!("expected stereo output"),
+        };
+
+        assert_eq!(&[0.5, 0.5], &stereo(ChannelAdapter::Both)[0..2]);
+        assert_eq!(&[0.5, 0.0], &stereo(ChannelAdapter::Left)[0..2]);
+        assert_eq!(&[0.0, 0.5], &stereo(ChannelAdapter::Right)[0..2]);
+        assert_eq!(SAMPLES_PER_TICK * CHANNELS, stereo(ChannelAdapter::Both).len());
+    }
+
+    #[test]
+    fn stereo_downmixes_to_mono() {
+        let stereo = Output::Stereo((0..SAMPLES_PER_TICK).flat_map(|_| vec![0.5, -0.25]).collect());
+
+        let mono = |adapter| match stereo.convert_to(LineType::Mono, adapter) {
+            Some(Output::Mono(mono)) => mono,
+            _ => panic!("expected mono output"),
+        };
+
+        assert_eq!(0.125, mono(ChannelAdapter::Both)[0]);
+        assert_eq!(0.5, mono(ChannelAdapter::Left)[0]);
+        assert_eq!(-0.25, mono(ChannelAdapter::Right)[0]);
+        assert_eq!(SAMPLES_PER_TICK, mono(ChannelAdapter::Both).len());
+    }
 }
@@ -1,13 +1,16 @@
 use std::fmt;
 use std::future::Future;
+use std::ops::{Deref, DerefMut};
 
 use tokio::runtime;
 use tokio::sync::mpsc;
 
-use mixlab_protocol::{ModuleParams, Indication, Terminal};
+use mixlab_protocol::{ModuleParams, ModuleCommand, Indication, Terminal};
 
-use crate::engine::{InputRef, OutputRef};
-use crate::module::{self, ModuleT};
+use crate::engine::{InputRef, OutputRef, MonitorPolicy};
+use crate::engine::conform::{self, Conform};
+use crate::midi::MidiSubscription;
+use crate::module::{self, ModuleT, PriorityClass};
 use crate::project::ProjectBaseRef;
 
 #[derive(Debug)]
@@ -22,6 +25,14 @@ impl<M: ModuleT> ModuleCtx<M> {
         self.base.clone()
     }
 
+    pub fn monitor_policy(&self) -> MonitorPolicy {
+        self.base.monitor_policy().clone()
+    }
+
+    pub fn subscribe_midi(&self) -> MidiSubscription {
+        self.base.midi().subscribe()
+    }
+
     pub fn link(&self) -> ModuleLink<M> {
         self.link.clone()
     }
@@ -62,6 +73,7 @@ impl<M: ModuleT> fmt::Debug for ModuleLink<M> {
 pub struct ModuleHost<M: ModuleT> {
     module: M,
     events: mpsc::Receiver<M::Event>,
+    conform: Conform,
 }
 
 impl<M: ModuleT> ModuleHost<M> {
@@ -79,6 +91,7 @@ impl<M: ModuleT> ModuleHost<M> {
         let host = ModuleHost {
             module,
             events: events_rx,
+            conform: Conform::new(),
         };
 
         (host, indication)
@@ -88,9 +101,14 @@ impl<M: ModuleT> ModuleHost<M> {
 pub trait DynModuleHostT {
     fn params(&self) -> ModuleParams;
     fn update(&mut self, new_params: ModuleParams) -> Option<Indication>;
+    fn command(&mut self, command: ModuleCommand);
     fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Indication>;
     fn inputs(&self) -> &[Terminal];
     fn outputs(&self) -> &[Terminal];
+    fn memory_usage(&self) -> usize;
+    fn priority(&self) -> PriorityClass;
+    fn ready(&mut self) -> bool;
+    fn finished(&self) -> bool;
 }
 
 macro_rules! gen_dyn_module_impls {
@@ -109,13 +127,24 @@ macro_rules! gen_dyn_module_impls {
                     }
                 }
 
+                fn command(&mut self, command: ModuleCommand) {
+                    match <module::$mod_name::$module as ModuleT>::command_event(command.clone()) {
+                        Some(ev) => self.module.receive_event(ev),
+                        None => eprintln!("module ignoring command: {:?}", command),
+                    }
+                }
+
                 fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Indication> {
                     if let Some(ev) = self.events.try_recv().ok() {
                         self.module.receive_event(ev);
                     }
 
-                    self.module.run_tick(t, inputs, outputs)
-                        .map(Indication::$module)
+                    let indication = match self.conform.run(self.module.frame_rate(), t, inputs) {
+                        Some(frames) => self.module.run_tick(t, &conform::input_refs(inputs, &frames), outputs),
+                        None => self.module.run_tick(t, inputs, outputs),
+                    };
+
+                    indication.map(Indication::$module)
                 }
 
                 fn inputs(&self) -> &[Terminal] {
@@ -125,6 +154,26 @@ macro_rules! gen_dyn_module_impls {
                 fn outputs(&self) -> &[Terminal] {
                     self.module.outputs()
                 }
+
+                fn memory_usage(&self) -> usize {
+                    self.module.memory_usage()
+                }
+
+                fn priority(&self) -> PriorityClass {
+                    self.module.priority()
+                }
+
+                fn ready(&mut self) -> bool {
+                    while let Ok(ev) = self.events.try_recv() {
+                        self.module.receive_event(ev);
+                    }
+
+                    self.module.ready()
+                }
+
+                fn finished(&self) -> bool {
+                    self.module.finished()
+                }
             }
         )*
     }
@@ -137,7 +186,7 @@ macro_rules! gen_host_fn {
                 $(
                     ModuleParams::$module(params) => {
                         let (host, indication) = ModuleHost::<module::$mod_name::$module>::new(params, base);
-                        (Box::new(host) as DynModuleHost, Indication::$module(indication))
+                        (<module::$mod_name::$module as ModuleT>::host(host), Indication::$module(indication))
                     }
                 )*
             }
@@ -148,4 +197,42 @@ macro_rules! gen_host_fn {
 crate::enumerate_modules!{then gen_dyn_module_impls!}
 crate::enumerate_modules!{then gen_host_fn!}
 
-pub type DynModuleHost = Box<dyn DynModuleHostT>;
+/// A hosted module. Those which may run on an engine worker thread are kept
+/// boxed as Send, which `module::parallel` proves as they opt in.
+pub enum DynModuleHost {
+    Local(Box<dyn DynModuleHostT>),
+    Parallel(Box<dyn DynModuleHostT + Send>),
+}
+
+impl DynModuleHost {
+    pub fn parallel(&self) -> bool {
+        matches!(self, DynModuleHost::Parallel(_))
+    }
+
+    pub fn into_parallel(self) -> Option<Box<dyn DynModuleHostT + Send>> {
+        match self {
+            DynModuleHost::Local(_) => None,
+            DynModuleHost::Parallel(host) => Some(host),
+        }
+    }
+}
+
+impl Deref for DynModuleHost {
+    type Target = dyn DynModuleHostT;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            DynModuleHost::Local(host) => &**host,
+            DynModuleHost::Parallel(host) => &**host,
+        }
+    }
+}
+
+impl DerefMut for DynModuleHost {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            DynModuleHost::Local(host) => &mut **host,
+            DynModuleHost::Parallel(host) => &mut **host,
+        }
+    }
+}
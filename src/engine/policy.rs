@@ -0,0 +1,53 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Monitoring policy shared by all modules in a project. Alarm sources (eg. the
+// silence detector) and talkback raise a duck request while active, and
+// monitor bus modules dim the operator's cue output while any are raised.
+#[derive(Debug, Clone, Default)]
+pub struct MonitorPolicy {
+    ducks: Arc<AtomicUsize>,
+}
+
+impl MonitorPolicy {
+    pub fn should_duck(&self) -> bool {
+        self.ducks.load(Ordering::Relaxed) > 0
+    }
+
+    pub fn duck_request(&self) -> DuckRequest {
+        DuckRequest {
+            policy: self.clone(),
+            active: false,
+        }
+    }
+}
+
+// released automatically when dropped, so a deleted module can never leave
+// the cue bus ducked
+#[derive(Debug)]
+pub struct DuckRequest {
+    policy: MonitorPolicy,
+    active: bool,
+}
+
+impl DuckRequest {
+    pub fn set_active(&mut self, active: bool) {
+        if active == self.active {
+            return;
+        }
+
+        if active {
+            self.policy.ducks.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.policy.ducks.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        self.active = active;
+    }
+}
+
+impl Drop for DuckRequest {
+    fn drop(&mut self) {
+        self.set_active(false);
+    }
+}
@@ -0,0 +1,102 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use mixlab_protocol::{ChannelAdapter, Indication, InputId, ModuleId, OutputId};
+
+use crate::engine::{self, DynModuleHostT, Output};
+
+// kept small, as the engine thread runs modules too and media decoding and
+// encoding go on alongside the engine
+const WORKERS: usize = 2;
+
+/// Worker threads for running modules in independent branches of the graph
+/// alongside the engine thread. Only modules hosted as Send are ever handed
+/// to a worker, and each is handed back once its tick is done.
+pub struct WorkerPool {
+    jobs_tx: Sender<Job>,
+    done_rx: Receiver<Result<Done, Box<dyn Any + Send>>>,
+}
+
+/// A module to run for one tick, along with copies of what it takes input
+/// from and empty buffers for its outputs.
+pub struct Job {
+    pub module_id: ModuleId,
+    pub module: Box<dyn DynModuleHostT + Send>,
+    pub t: u64,
+    pub connections: HashMap<InputId, OutputId>,
+    pub adapters: HashMap<InputId, ChannelAdapter>,
+    pub inputs: HashMap<OutputId, Output>,
+    pub outputs: Vec<Output>,
+}
+
+/// A module handed back after its tick, along with its input copies so the
+/// buffers can be reused.
+pub struct Done {
+    pub module_id: ModuleId,
+    pub module: Box<dyn DynModuleHostT + Send>,
+    pub inputs: HashMap<OutputId, Output>,
+    pub outputs: Vec<Output>,
+    pub indication: Option<Indication>,
+    pub elapsed: Duration,
+}
+
+impl WorkerPool {
+    pub fn new() -> Self {
+        let (jobs_tx, jobs_rx) = mpsc::channel::<Job>();
+        let (done_tx, done_rx) = mpsc::channel();
+        let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+
+        for _ in 0..WORKERS {
+            let jobs_rx = jobs_rx.clone();
+            let done_tx = done_tx.clone();
+
+            thread::spawn(move || {
+                loop {
+                    // exits once the pool is dropped along with the engine
+                    let job = match jobs_rx.lock().expect("lock jobs_rx").recv() {
+                        Ok(job) => job,
+                        Err(_) => return,
+                    };
+
+                    let done = panic::catch_unwind(AssertUnwindSafe(|| run_job(job)));
+
+                    if done_tx.send(done).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+
+        WorkerPool { jobs_tx, done_rx }
+    }
+
+    pub fn workers(&self) -> usize {
+        WORKERS
+    }
+
+    pub fn submit(&self, job: Job) {
+        self.jobs_tx.send(job).expect("worker pool stopped");
+    }
+
+    /// Waits for the next module to finish. A module which panicked on its
+    /// worker panics the engine, as it would have on the engine thread.
+    pub fn wait(&self) -> Done {
+        match self.done_rx.recv().expect("worker pool stopped") {
+            Ok(done) => done,
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    }
+}
+
+fn run_job(job: Job) -> Done {
+    let Job { module_id, mut module, t, connections, adapters, inputs, mut outputs } = job;
+
+    let (indication, elapsed) = engine::run_module(module_id, &mut *module, t, &connections, &adapters, &inputs, &mut outputs);
+
+    Done { module_id, module, inputs, outputs, indication, elapsed }
+}
@@ -0,0 +1,488 @@
+// Recording and deterministic replay of engine sessions.
+//
+// A recording captures everything fed into the engine from outside, stamped
+// with the tick it arrived in: session messages, and the source frames and
+// info read by modules. Replaying it runs the same ticks as fast as possible
+// with those inputs presented at exactly the same points, so that timing
+// dependent behaviour becomes reproducible.
+//
+// Source taps are thread local because modules read their sources from the
+// engine thread, deep inside run_tick. Video frames are recorded as their
+// picture size and timing only and come back as blank frames on replay.
+// MIDI input is not recorded.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender, TryRecvError};
+use std::thread;
+
+use serde::{Serialize, Deserialize};
+
+use mixlab_codec::ffmpeg::{AvFrame, PictureSettings};
+use mixlab_protocol::{MediaId, OutputId, WorkspaceMessage};
+use mixlab_util::time::{MediaTime, MediaDuration};
+
+use crate::engine::{EngineMessage, SessionId};
+use crate::persist;
+use crate::source::{Frame, AudioData, VideoData, SourceId, SourceInfo};
+use crate::video;
+
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    // persist::Workspace is only guaranteed to round trip through json
+    workspace: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    tick: u64,
+    event: Event,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Event {
+    ConnectSession(Option<String>),
+    DisconnectSession(SessionId),
+    Workspace(SessionId, WorkspaceMessage),
+    Audio(String, RecordedFrame<AudioData>),
+    Video(String, RecordedFrame<RecordedVideo>),
+    Info(String, SourceInfo),
+    // persist::Workspace as json, see Header
+    LoadWorkspace(SessionId, Vec<u8>),
+    FreezeRendered(OutputId, Option<MediaId>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedFrame<T> {
+    source_id: SourceId,
+    source_time: MediaTime,
+    data: T,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedVideo {
+    width: usize,
+    height: usize,
+    duration_hint: MediaDuration,
+    // absent from recordings made before key frames were tracked
+    #[serde(default = "default_key_frame")]
+    key_frame: bool,
+}
+
+fn default_key_frame() -> bool {
+    true
+}
+
+/// A session message as the engine processed it, for replay.
+pub enum RecordedMessage {
+    ConnectSession(Option<String>),
+    DisconnectSession(SessionId),
+    Workspace(SessionId, WorkspaceMessage),
+    LoadWorkspace(SessionId, persist::Workspace),
+    FreezeRendered(OutputId, Option<MediaId>),
+}
+
+/// Writes a recording to disk from a background thread, so that the engine
+/// thread never blocks on file IO.
+pub struct Recorder {
+    tx: Sender<Vec<u8>>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Recorder> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+
+        thread::spawn(move || {
+            let result = (|| -> io::Result<()> {
+                loop {
+                    let bytes = match rx.try_recv() {
+                        Ok(bytes) => bytes,
+                        Err(TryRecvError::Empty) => {
+                            // flush while idle so that a recording survives
+                            // the server being killed
+                            file.flush()?;
+
+                            match rx.recv() {
+                                Ok(bytes) => bytes,
+                                Err(_) => break,
+                            }
+                        }
+                        Err(TryRecvError::Disconnected) => break,
+                    };
+
+                    file.write_all(&bytes)?;
+                }
+
+                file.flush()
+            })();
+
+            if let Err(e) = result {
+                eprintln!("engine: could not write recording: {:?}", e);
+            }
+        });
+
+        Ok(Recorder { tx })
+    }
+
+    fn write(&self, value: &impl Serialize) {
+        let bytes = bincode::serialize(value).expect("bincode::serialize");
+        // nothing we can do if the writer thread has gone away
+        let _ = self.tx.send(bytes);
+    }
+}
+
+/// A recording read back from disk.
+pub struct Recording {
+    pub workspace: persist::Workspace,
+    messages: VecDeque<(u64, RecordedMessage)>,
+    player: Player,
+    end_tick: u64,
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    Decode(bincode::Error),
+    Workspace(serde_json::Error),
+    UnsupportedVersion(u32),
+}
+
+impl Recording {
+    pub fn open(path: &Path) -> Result<Recording, RecordingError> {
+        let mut file = BufReader::new(File::open(path).map_err(RecordingError::Io)?);
+
+        let header: Header = bincode::deserialize_from(&mut file)
+            .map_err(RecordingError::Decode)?;
+
+        if header.version != VERSION {
+            return Err(RecordingError::UnsupportedVersion(header.version));
+        }
+
+        let workspace = serde_json::from_slice(&header.workspace)
+            .map_err(RecordingError::Workspace)?;
+
+        let mut recording = Recording {
+            workspace,
+            messages: VecDeque::new(),
+            player: Player::default(),
+            end_tick: 0,
+        };
+
+        loop {
+            let entry: Entry = match bincode::deserialize_from(&mut file) {
+                Ok(entry) => entry,
+                Err(e) => match *e {
+                    // a recording cut short by the server being killed may
+                    // end partway through an entry, keep what we have
+                    bincode::ErrorKind::Io(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                    _ => return Err(RecordingError::Decode(e)),
+                }
+            };
+
+            recording.end_tick = entry.tick;
+
+            let player = &mut recording.player;
+
+            match entry.event {
+                Event::ConnectSession(group) => {
+                    recording.messages.push_back((entry.tick, RecordedMessage::ConnectSession(group)));
+                }
+                Event::DisconnectSession(session_id) => {
+                    recording.messages.push_back((entry.tick, RecordedMessage::DisconnectSession(session_id)));
+                }
+                Event::Workspace(session_id, msg) => {
+                    recording.messages.push_back((entry.tick, RecordedMessage::Workspace(session_id, msg)));
+                }
+                Event::Audio(channel, frame) => {
+                    player.audio.entry(channel).or_default().push_back((entry.tick, frame));
+                }
+                Event::Video(channel, frame) => {
+                    player.video.entry(channel).or_default().push_back((entry.tick, frame));
+                }
+                Event::Info(channel, info) => {
+                    player.info_changes.entry(channel).or_default().push_back((entry.tick, info));
+                }
+                Event::LoadWorkspace(session_id, workspace) => {
+                    let workspace = serde_json::from_slice(&workspace)
+                        .map_err(RecordingError::Workspace)?;
+
+                    recording.messages.push_back((entry.tick, RecordedMessage::LoadWorkspace(session_id, workspace)));
+                }
+                Event::FreezeRendered(output, media_id) => {
+                    recording.messages.push_back((entry.tick, RecordedMessage::FreezeRendered(output, media_id)));
+                }
+            }
+        }
+
+        Ok(recording)
+    }
+
+    /// The last tick with anything recorded in it.
+    pub fn end_tick(&self) -> u64 {
+        self.end_tick
+    }
+
+    /// Takes the messages which were processed during the given tick.
+    pub fn take_messages(&mut self, tick: u64) -> Vec<RecordedMessage> {
+        let mut messages = Vec::new();
+
+        while self.messages.front().map(|(msg_tick, _)| *msg_tick <= tick).unwrap_or(false) {
+            messages.extend(self.messages.pop_front().map(|(_, msg)| msg));
+        }
+
+        messages
+    }
+}
+
+#[derive(Default)]
+struct Player {
+    tick: u64,
+    audio: HashMap<String, VecDeque<(u64, RecordedFrame<AudioData>)>>,
+    video: HashMap<String, VecDeque<(u64, RecordedFrame<RecordedVideo>)>>,
+    info_changes: HashMap<String, VecDeque<(u64, SourceInfo)>>,
+    info: HashMap<String, SourceInfo>,
+}
+
+// pops the next frame recorded in the current tick. frames from earlier ticks
+// were not read on replay, which can only happen if replay has diverged, and
+// are dropped
+fn pop_due<T>(queue: Option<&mut VecDeque<(u64, T)>>, tick: u64) -> Option<T> {
+    let queue = queue?;
+
+    while let Some((frame_tick, _)) = queue.front() {
+        if *frame_tick > tick {
+            return None;
+        }
+
+        let (frame_tick, frame) = queue.pop_front().unwrap();
+
+        if frame_tick == tick {
+            return Some(frame);
+        }
+    }
+
+    None
+}
+
+impl Player {
+    fn read_audio(&mut self, channel: &str) -> Option<Frame<AudioData>> {
+        pop_due(self.audio.get_mut(channel), self.tick).map(|frame| Frame {
+            source_id: frame.source_id,
+            source_time: frame.source_time,
+            data: frame.data,
+        })
+    }
+
+    fn read_video(&mut self, channel: &str) -> Option<Frame<VideoData>> {
+        pop_due(self.video.get_mut(channel), self.tick).map(|frame| Frame {
+            source_id: frame.source_id,
+            source_time: frame.source_time,
+            data: video::Frame {
+                decoded: AvFrame::blank(&PictureSettings::yuv420p(frame.data.width, frame.data.height)),
+                duration_hint: frame.data.duration_hint,
+                key_frame: frame.data.key_frame,
+                passthrough: None,
+            },
+        })
+    }
+
+    fn read_info(&mut self, channel: &str) -> SourceInfo {
+        if let Some(changes) = self.info_changes.get_mut(channel) {
+            while changes.front().map(|(tick, _)| *tick <= self.tick).unwrap_or(false) {
+                let (_, info) = changes.pop_front().unwrap();
+                self.info.insert(channel.to_owned(), info);
+            }
+        }
+
+        self.info.get(channel).cloned().unwrap_or_default()
+    }
+}
+
+enum Tape {
+    Record {
+        recorder: Recorder,
+        tick: u64,
+        info: HashMap<String, SourceInfo>,
+    },
+    Replay(Player),
+}
+
+thread_local! {
+    static TAPE: RefCell<Option<Tape>> = RefCell::new(None);
+}
+
+/// Starts recording on the current thread, which must be the engine thread.
+pub fn start_recording(recorder: Recorder, workspace: &persist::Workspace) {
+    recorder.write(&Header {
+        version: VERSION,
+        workspace: serde_json::to_vec(workspace).expect("serde_json::to_vec"),
+    });
+
+    TAPE.with(|tape| {
+        *tape.borrow_mut() = Some(Tape::Record { recorder, tick: 0, info: HashMap::new() });
+    });
+}
+
+/// Starts replaying source reads on the current thread. Takes the player out
+/// of the recording, leaving only its messages.
+pub fn start_replay(recording: &mut Recording) {
+    let player = std::mem::replace(&mut recording.player, Player::default());
+
+    TAPE.with(|tape| {
+        *tape.borrow_mut() = Some(Tape::Replay(player));
+    });
+}
+
+// work whose result comes back into the engine as a message, such as saving
+// a freeze render, is skipped on replay as the recording already has it
+pub fn is_replaying() -> bool {
+    TAPE.with(|tape| {
+        match tape.borrow().as_ref() {
+            Some(Tape::Replay(_)) => true,
+            _ => false,
+        }
+    })
+}
+
+pub fn set_tick(new_tick: u64) {
+    TAPE.with(|tape| {
+        match tape.borrow_mut().as_mut() {
+            Some(Tape::Record { tick, .. }) => *tick = new_tick,
+            Some(Tape::Replay(player)) => player.tick = new_tick,
+            None => {}
+        }
+    })
+}
+
+pub fn record_message(msg: &EngineMessage) {
+    with_recorder(|recorder, tick| {
+        let event = match msg {
+            EngineMessage::ConnectSession(group, _) => Event::ConnectSession(group.clone()),
+            EngineMessage::DisconnectSession(session_id) => Event::DisconnectSession(*session_id),
+            EngineMessage::Workspace(session_id, msg) => Event::Workspace(*session_id, msg.clone()),
+            EngineMessage::LoadWorkspace(session_id, workspace) => {
+                Event::LoadWorkspace(*session_id, serde_json::to_vec(workspace).expect("serde_json::to_vec"))
+            }
+            EngineMessage::FreezeRendered(output, media_id) => Event::FreezeRendered(*output, *media_id),
+        };
+
+        recorder.write(&Entry { tick, event });
+    });
+}
+
+fn with_recorder(f: impl FnOnce(&Recorder, u64)) {
+    TAPE.with(|tape| {
+        if let Some(Tape::Record { recorder, tick, .. }) = tape.borrow().as_ref() {
+            f(recorder, *tick);
+        }
+    })
+}
+
+/// Passes a source audio read through the tape.
+pub fn tap_audio(channel: &str, read: impl FnOnce() -> Option<Frame<AudioData>>) -> Option<Frame<AudioData>> {
+    TAPE.with(|tape| {
+        match tape.borrow_mut().as_mut() {
+            Some(Tape::Replay(player)) => player.read_audio(channel),
+            Some(Tape::Record { recorder, tick, .. }) => {
+                let frame = read();
+
+                if let Some(frame) = &frame {
+                    recorder.write(&Entry {
+                        tick: *tick,
+                        event: Event::Audio(channel.to_owned(), RecordedFrame {
+                            source_id: frame.source_id,
+                            source_time: frame.source_time,
+                            data: frame.data.clone(),
+                        }),
+                    });
+                }
+
+                frame
+            }
+            None => read(),
+        }
+    })
+}
+
+/// Passes a source video read through the tape.
+pub fn tap_video(channel: &str, read: impl FnOnce() -> Option<Frame<VideoData>>) -> Option<Frame<VideoData>> {
+    TAPE.with(|tape| {
+        match tape.borrow_mut().as_mut() {
+            Some(Tape::Replay(player)) => player.read_video(channel),
+            Some(Tape::Record { recorder, tick, .. }) => {
+                let frame = read();
+
+                if let Some(frame) = &frame {
+                    let picture = frame.data.decoded.picture_settings();
+
+                    recorder.write(&Entry {
+                        tick: *tick,
+                        event: Event::Video(channel.to_owned(), RecordedFrame {
+                            source_id: frame.source_id,
+                            source_time: frame.source_time,
+                            data: RecordedVideo {
+                                width: picture.width,
+                                height: picture.height,
+                                duration_hint: frame.data.duration_hint,
+                                key_frame: frame.data.key_frame,
+                            },
+                        }),
+                    });
+                }
+
+                frame
+            }
+            None => read(),
+        }
+    })
+}
+
+/// Passes a source info read through the tape. Only changes are recorded.
+pub fn tap_info(channel: &str, read: impl FnOnce() -> SourceInfo) -> SourceInfo {
+    TAPE.with(|tape| {
+        match tape.borrow_mut().as_mut() {
+            Some(Tape::Replay(player)) => player.read_info(channel),
+            Some(Tape::Record { recorder, tick, info: last_info }) => {
+                let info = read();
+
+                if last_info.get(channel) != Some(&info) {
+                    last_info.insert(channel.to_owned(), info.clone());
+
+                    recorder.write(&Entry {
+                        tick: *tick,
+                        event: Event::Info(channel.to_owned(), info.clone()),
+                    });
+                }
+
+                info
+            }
+            None => read(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pops_frames_due_in_tick() {
+        let mut queue = vec![(1, 'a'), (1, 'b'), (2, 'c'), (4, 'd')].into_iter().collect::<VecDeque<_>>();
+
+        assert_eq!(None, pop_due(Some(&mut queue), 0));
+        assert_eq!(Some('a'), pop_due(Some(&mut queue), 1));
+        assert_eq!(Some('b'), pop_due(Some(&mut queue), 1));
+        assert_eq!(None, pop_due(Some(&mut queue), 1));
+
+        // 'c' was due in tick 2 but never read, so is dropped
+        assert_eq!(None, pop_due(Some(&mut queue), 3));
+        assert_eq!(Some('d'), pop_due(Some(&mut queue), 4));
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::{HashMap, HashSet};
+
+use mixlab_protocol::{InputId, ModuleId, OutputId};
+
+/// The order modules run in each tick, which only changes when modules or
+/// connections do.
+#[derive(Debug)]
+pub struct RunOrder {
+    pub modules: Vec<ModuleId>,
+    /// The modules feeding each module which come earlier in the run order,
+    /// and so must have run before it. Later ones only feed it through a
+    /// cycle, and it sees them as disconnected
+    pub depends_on: HashMap<ModuleId, Vec<ModuleId>>,
+}
+
+impl RunOrder {
+    /// Works out the run order from the number of inputs each module has and
+    /// the connections between them.
+    pub fn new(inputs: &HashMap<ModuleId, usize>, connections: &HashMap<InputId, OutputId>) -> Self {
+        // find terminal modules - modules which do not send their output to
+        // the input of any other module
+
+        let mut terminal_modules = inputs.keys().copied().collect::<HashSet<_>>();
+
+        for (_, output) in connections {
+            terminal_modules.remove(&output.module_id());
+        }
+
+        // run order, and so the order of indications, must not depend on
+        // hash order for replays to be reproducible
+        let mut terminal_modules = terminal_modules.into_iter().collect::<Vec<_>>();
+        terminal_modules.sort();
+
+        // depth-first-search modules out via their inputs, starting from
+        // terminal modules
+
+        let mut topsort = Topsort {
+            inputs,
+            connections,
+            run_order: Vec::new(),
+            seen: HashSet::new(),
+        };
+
+        for id in terminal_modules.into_iter() {
+            traverse(id, &mut topsort);
+        }
+
+        let modules = topsort.run_order;
+
+        let position = modules.iter()
+            .enumerate()
+            .map(|(index, module_id)| (*module_id, index))
+            .collect::<HashMap<_, _>>();
+
+        let depends_on = modules.iter()
+            .map(|module_id| {
+                let deps = (0..inputs[module_id])
+                    .filter_map(|i| connections.get(&InputId(*module_id, i)))
+                    .map(|output_id| output_id.module_id())
+                    .filter(|dep| position[dep] < position[module_id])
+                    .collect::<Vec<_>>();
+
+                (*module_id, deps)
+            })
+            .collect();
+
+        RunOrder { modules, depends_on }
+    }
+}
+
+/// Whether anything `upstream` outputs reaches `downstream`, directly or
+/// through other modules. Connecting an output of `downstream` into
+/// `upstream` would close a cycle if so.
+pub fn feeds(connections: &HashMap<InputId, OutputId>, upstream: ModuleId, downstream: ModuleId) -> bool {
+    let mut feeders = HashMap::<ModuleId, Vec<ModuleId>>::new();
+
+    for (input, output) in connections {
+        feeders.entry(input.module_id()).or_default().push(output.module_id());
+    }
+
+    let mut seen = HashSet::new();
+    let mut stack = vec![downstream];
+
+    while let Some(module_id) = stack.pop() {
+        if module_id == upstream {
+            return true;
+        }
+
+        if seen.insert(module_id) {
+            if let Some(feeders) = feeders.get(&module_id) {
+                stack.extend(feeders);
+            }
+        }
+    }
+
+    false
+}
+
+struct Topsort<'a> {
+    inputs: &'a HashMap<ModuleId, usize>,
+    connections: &'a HashMap<InputId, OutputId>,
+    run_order: Vec<ModuleId>,
+    seen: HashSet<ModuleId>,
+}
+
+fn traverse(module_id: ModuleId, state: &mut Topsort) {
+    if state.seen.contains(&module_id) {
+        return;
+    }
+
+    state.seen.insert(module_id);
+
+    for i in 0..state.inputs[&module_id] {
+        let terminal_id = InputId(module_id, i);
+
+        if let Some(output_id) = state.connections.get(&terminal_id) {
+            traverse(output_id.module_id(), state);
+        }
+    }
+
+    state.run_order.push(module_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::*;
+
+    fn module(id: usize) -> ModuleId {
+        ModuleId(NonZeroUsize::new(id).unwrap())
+    }
+
+    #[test]
+    fn sources_run_before_what_they_feed() {
+        // 1 -> 2 -> 3, and 4 on its own
+        let inputs = vec![(module(1), 0), (module(2), 1), (module(3), 1), (module(4), 1)]
+            .into_iter().collect();
+
+        let mut connections = HashMap::new();
+        connections.insert(InputId(module(2), 0), OutputId(module(1), 0));
+        connections.insert(InputId(module(3), 0), OutputId(module(2), 0));
+
+        let order = RunOrder::new(&inputs, &connections);
+
+        assert_eq!(vec![module(1), module(2), module(3), module(4)], order.modules);
+        assert_eq!(vec![module(2)], order.depends_on[&module(3)]);
+        assert!(order.depends_on[&module(4)].is_empty());
+    }
+
+    #[test]
+    fn cycles_depend_only_on_earlier_modules() {
+        // 1 -> 2 -> 1, feeding 3
+        let inputs = vec![(module(1), 1), (module(2), 1), (module(3), 1)]
+            .into_iter().collect();
+
+        let mut connections = HashMap::new();
+        connections.insert(InputId(module(1), 0), OutputId(module(2), 0));
+        connections.insert(InputId(module(2), 0), OutputId(module(1), 0));
+        connections.insert(InputId(module(3), 0), OutputId(module(2), 0));
+
+        let order = RunOrder::new(&inputs, &connections);
+
+        assert_eq!(vec![module(1), module(2), module(3)], order.modules);
+        assert!(order.depends_on[&module(1)].is_empty());
+        assert_eq!(vec![module(1)], order.depends_on[&module(2)]);
+    }
+
+    #[test]
+    fn feeds_follows_connections_downstream() {
+        // 1 -> 2 -> 3, and 4 on its own
+        let mut connections = HashMap::new();
+        connections.insert(InputId(module(2), 0), OutputId(module(1), 0));
+        connections.insert(InputId(module(3), 0), OutputId(module(2), 0));
+
+        assert!(feeds(&connections, module(1), module(3)));
+        assert!(feeds(&connections, module(2), module(2)));
+        assert!(!feeds(&connections, module(3), module(1)));
+        assert!(!feeds(&connections, module(4), module(3)));
+    }
+}
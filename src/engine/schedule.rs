@@ -0,0 +1,114 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mixlab_protocol::ModuleId;
+
+use crate::diagram;
+use crate::engine::{SAMPLE_RATE, SAMPLES_PER_TICK, TICKS_PER_SECOND};
+use crate::engine::workspace::Workspace;
+
+// how long offline renders sleep between checks on modules not yet ready
+const READY_POLL: Duration = Duration::from_millis(1);
+
+// how long offline renders wait on a module before giving up on it, well
+// beyond any decode or image load keeping up with a render
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A module which an offline render gave up waiting on, by id and kind.
+#[derive(Debug)]
+pub struct NotReady(pub ModuleId, pub String);
+
+/// Paces the engine loop. The realtime schedule keeps ticks in step with the
+/// wall clock, others may run them as fast as they can be computed.
+pub trait Schedule {
+    /// Called before tick `tick` runs. Returns when the tick is due to end, or
+    /// `None` to run it without a deadline and go straight on to the next.
+    /// Fails to stop the engine if a module it waited on never became ready.
+    fn begin_tick(&mut self, tick: u64, workspace: &mut Workspace) -> Result<Option<Instant>, NotReady>;
+
+    /// Called after tick `tick` has run. Returns false to stop the engine.
+    fn end_tick(&mut self, tick: u64, workspace: &Workspace) -> bool;
+}
+
+/// Runs ticks in step with the wall clock until every handle to the engine
+/// is gone, shedding work according to the workspace's overrun policy.
+pub struct Realtime {
+    start: Instant,
+}
+
+impl Realtime {
+    pub fn new() -> Self {
+        Realtime { start: Instant::now() }
+    }
+}
+
+impl Schedule for Realtime {
+    fn begin_tick(&mut self, tick: u64, _: &mut Workspace) -> Result<Option<Instant>, NotReady> {
+        // we don't simply calculate `tick * TICK_BUDGET` here to prevent loss of precision over time:
+        Ok(Some(self.start + Duration::from_millis(((tick + 1) * 1_000) / TICKS_PER_SECOND as u64)))
+    }
+
+    fn end_tick(&mut self, _: u64, _: &Workspace) -> bool {
+        true
+    }
+}
+
+/// Runs ticks back to back, for rendering to a file. Nothing is shed and
+/// each tick waits for modules which aren't ready rather than playing out
+/// silence, so a render comes out the same however fast the machine is.
+pub struct Offline {
+    end_tick: u64,
+    until_finished: bool,
+}
+
+impl Offline {
+    /// Stops after `length_secs`, or sooner if `until_finished` and every
+    /// module has played all it has to play.
+    pub fn new(length_secs: f64, until_finished: bool) -> Self {
+        let end_tick = (length_secs.max(0.0) * SAMPLE_RATE as f64 / SAMPLES_PER_TICK as f64).ceil() as u64;
+
+        Offline { end_tick, until_finished }
+    }
+}
+
+impl Schedule for Offline {
+    fn begin_tick(&mut self, _: u64, workspace: &mut Workspace) -> Result<Option<Instant>, NotReady> {
+        let start = Instant::now();
+
+        loop {
+            let unready = workspace.modules.iter_mut()
+                .find_map(|(module_id, module)| if module.ready() { None } else { Some((*module_id, module)) });
+
+            let (module_id, module) = match unready {
+                Some(unready) => unready,
+                None => return Ok(None),
+            };
+
+            if start.elapsed() >= READY_TIMEOUT {
+                return Err(NotReady(module_id, diagram::module_name(&module.params())));
+            }
+
+            thread::sleep(READY_POLL);
+        }
+    }
+
+    fn end_tick(&mut self, tick: u64, workspace: &Workspace) -> bool {
+        if tick + 1 >= self.end_tick {
+            return false;
+        }
+
+        !(self.until_finished && workspace.modules.values().all(|module| module.finished()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_length_rounds_up_to_whole_ticks() {
+        assert_eq!(TICKS_PER_SECOND as u64, Offline::new(1.0, false).end_tick);
+        assert_eq!(1, Offline::new(0.001, false).end_tick);
+        assert_eq!(0, Offline::new(-1.0, false).end_tick);
+    }
+}
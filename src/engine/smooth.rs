@@ -0,0 +1,93 @@
+use crate::engine::SAMPLE_RATE;
+
+/// Time constant modules glide params over unless they have reason to pick
+/// their own. Long enough that fader moves don't click, short enough that
+/// they still feel immediate.
+pub const DEFAULT_SMOOTHING_MS: f64 = 20.0;
+
+// close enough to the target to stop gliding, well below anything audible
+const SETTLED: f64 = 1e-6;
+
+/// A param which glides towards new values rather than jumping, since params
+/// change between ticks and a step at a tick boundary can click. Glides are
+/// exponential, advancing one step per sample frame.
+#[derive(Debug, Clone)]
+pub struct Smoothed {
+    current: f64,
+    target: f64,
+    coeff: f64,
+}
+
+impl Smoothed {
+    /// Starts settled at `value`, so that a new module doesn't glide in from
+    /// nothing.
+    pub fn new(value: f64, time_constant_ms: f64) -> Self {
+        let coeff = 1.0 - f64::exp(-1.0 / (time_constant_ms / 1000.0 * SAMPLE_RATE as f64));
+
+        Smoothed {
+            current: value,
+            target: value,
+            coeff,
+        }
+    }
+
+    pub fn with_default(value: f64) -> Self {
+        Smoothed::new(value, DEFAULT_SMOOTHING_MS)
+    }
+
+    pub fn set(&mut self, target: f64) {
+        self.target = target;
+    }
+
+    pub fn target(&self) -> f64 {
+        self.target
+    }
+
+    /// Advances one sample frame, returning the value for that frame.
+    pub fn next(&mut self) -> f64 {
+        if self.is_settled() {
+            self.current = self.target;
+        } else {
+            self.current += (self.target - self.current) * self.coeff;
+        }
+
+        self.current
+    }
+
+    pub fn is_settled(&self) -> bool {
+        (self.target - self.current).abs() < SETTLED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_settled() {
+        let mut gain = Smoothed::with_default(0.5);
+        assert!(gain.is_settled());
+        assert_eq!(0.5, gain.next());
+    }
+
+    #[test]
+    fn glides_towards_target_without_overshooting() {
+        let mut gain = Smoothed::new(0.0, 10.0);
+        gain.set(1.0);
+
+        let first = gain.next();
+        assert!(first > 0.0 && first < 0.01);
+
+        // one time constant gets within 1/e of the target
+        let frames = SAMPLE_RATE / 100;
+        let value = (1..frames).map(|_| gain.next()).last().unwrap();
+        assert!((value - (1.0 - f64::exp(-1.0))).abs() < 0.01);
+
+        for _ in 0..SAMPLE_RATE {
+            assert!(gain.next() <= 1.0);
+        }
+
+        assert!(gain.is_settled());
+        assert_eq!(1.0, gain.next());
+    }
+}
@@ -0,0 +1,172 @@
+use mixlab_protocol::{Coords, ModuleParams, Template, InputDeviceParams, OutputDeviceParams, EqThreeParams, MixerParams, NoiseGateParams, CompressorParams, LimiterParams, RecorderParams};
+
+// modules of a template are laid out in a grid from where it was placed
+const COLUMN_WIDTH: i32 = 280;
+const ROW_HEIGHT: i32 = 220;
+
+#[derive(Debug)]
+pub struct Expansion {
+    pub modules: Vec<TemplateModule>,
+    pub connections: Vec<TemplateConnection>,
+}
+
+#[derive(Debug)]
+pub struct TemplateModule {
+    pub params: ModuleParams,
+    pub offset: Coords,
+}
+
+// modules are referred to by their index in the expansion, since they have
+// no ids until created
+#[derive(Debug, PartialEq)]
+pub struct TemplateConnection {
+    pub output: (usize, usize),
+    pub input: (usize, usize),
+}
+
+impl Expansion {
+    fn new() -> Self {
+        Expansion { modules: Vec::new(), connections: Vec::new() }
+    }
+
+    fn add(&mut self, params: ModuleParams, column: i32, row: i32) -> usize {
+        self.modules.push(TemplateModule {
+            params,
+            offset: Coords { x: column * COLUMN_WIDTH, y: row * ROW_HEIGHT },
+        });
+
+        self.modules.len() - 1
+    }
+
+    fn connect(&mut self, output: (usize, usize), input: (usize, usize)) {
+        self.connections.push(TemplateConnection { output, input });
+    }
+}
+
+pub fn expand(template: &Template) -> Expansion {
+    match template {
+        Template::Podcast { mics } => podcast(*mics),
+    }
+}
+
+// a gated, EQ'd chain per mic, each recorded to its own stem before the
+// mixer, with the mix recorded alongside. files are named for the time they
+// started and the mic they came from, so a session's stems sort together
+fn podcast(mics: usize) -> Expansion {
+    let mics = mics.max(1);
+    let mut expansion = Expansion::new();
+
+    let mixer = expansion.add(ModuleParams::Mixer(MixerParams::with_channels(mics)), 7, 0);
+
+    for mic in 0..mics {
+        let row = mic as i32;
+
+        let input = expansion.add(ModuleParams::InputDevice(InputDeviceParams::default()), 0, row);
+        let splitter = expansion.add(ModuleParams::StereoSplitter(()), 1, row);
+        let eq = expansion.add(ModuleParams::EqThree(EqThreeParams::default()), 2, row);
+        let panner = expansion.add(ModuleParams::StereoPanner(()), 3, row);
+        let gate = expansion.add(ModuleParams::NoiseGate(NoiseGateParams::default()), 4, row);
+        let compressor = expansion.add(ModuleParams::Compressor(CompressorParams::default()), 5, row);
+        let stem = expansion.add(ModuleParams::Recorder(RecorderParams {
+            filename: format!("{{date}}-{{time}}-mic-{}", mic + 1),
+            ..RecorderParams::default()
+        }), 6, row);
+
+        // mics are mono, only the left channel of the device is used and
+        // centered again after EQ
+        expansion.connect((input, 0), (splitter, 0));
+        expansion.connect((splitter, 0), (eq, 0));
+        expansion.connect((eq, 0), (panner, 0));
+        expansion.connect((eq, 0), (panner, 1));
+        expansion.connect((panner, 0), (gate, 0));
+        expansion.connect((gate, 0), (compressor, 0));
+        expansion.connect((compressor, 0), (stem, 0));
+        expansion.connect((stem, 0), (mixer, mic));
+    }
+
+    let limiter = expansion.add(ModuleParams::Limiter(LimiterParams::default()), 8, 0);
+    let mixdown = expansion.add(ModuleParams::Recorder(RecorderParams {
+        filename: "{date}-{time}-mix".to_owned(),
+        ..RecorderParams::default()
+    }), 9, 0);
+    let output = expansion.add(ModuleParams::OutputDevice(OutputDeviceParams { device: None, left: None, right: None }), 10, 0);
+
+    expansion.connect((mixer, 0), (limiter, 0));
+    expansion.connect((limiter, 0), (mixdown, 0));
+    expansion.connect((mixdown, 0), (output, 0));
+
+    expansion
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn podcast_feeds_every_mic_into_the_mixer() {
+        let expansion = expand(&Template::Podcast { mics: 3 });
+
+        let mixer = expansion.modules.iter()
+            .position(|module| matches!(module.params, ModuleParams::Mixer(_)))
+            .unwrap();
+
+        let mut channels = expansion.connections.iter()
+            .filter(|conn| conn.input.0 == mixer)
+            .map(|conn| conn.input.1)
+            .collect::<Vec<_>>();
+
+        channels.sort();
+        assert_eq!(vec![0, 1, 2], channels);
+
+        let recorders = expansion.modules.iter()
+            .filter(|module| matches!(module.params, ModuleParams::Recorder(_)))
+            .count();
+
+        // a stem per mic plus the mixdown
+        assert_eq!(4, recorders);
+    }
+
+    #[test]
+    fn podcast_recorders_write_separate_files() {
+        let expansion = expand(&Template::Podcast { mics: 2 });
+
+        let filenames = expansion.modules.iter()
+            .filter_map(|module| match &module.params {
+                ModuleParams::Recorder(params) => Some(params.filename.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["{date}-{time}-mic-1", "{date}-{time}-mic-2", "{date}-{time}-mix"], filenames);
+    }
+
+    #[test]
+    fn podcast_compresses_each_mic_and_limits_the_bus() {
+        let expansion = expand(&Template::Podcast { mics: 2 });
+
+        let index = |f: fn(&ModuleParams) -> bool| expansion.modules.iter()
+            .enumerate()
+            .filter(|(_, module)| f(&module.params))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        let feeds = |output: usize| expansion.connections.iter()
+            .filter(|conn| conn.output.0 == output)
+            .map(|conn| &expansion.modules[conn.input.0].params)
+            .collect::<Vec<_>>();
+
+        for gate in index(|params| matches!(params, ModuleParams::NoiseGate(_))) {
+            assert!(matches!(feeds(gate)[..], [ModuleParams::Compressor(_)]));
+        }
+
+        for compressor in index(|params| matches!(params, ModuleParams::Compressor(_))) {
+            assert!(matches!(feeds(compressor)[..], [ModuleParams::Recorder(_)]));
+        }
+
+        let mixer = index(|params| matches!(params, ModuleParams::Mixer(_)))[0];
+        assert!(matches!(feeds(mixer)[..], [ModuleParams::Limiter(_)]));
+
+        let limiter = index(|params| matches!(params, ModuleParams::Limiter(_)))[0];
+        assert!(matches!(feeds(limiter)[..], [ModuleParams::Recorder(params)] if params.filename == "{date}-{time}-mix"));
+    }
+}
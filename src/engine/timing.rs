@@ -1,17 +1,24 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::time::{Instant, Duration};
 
-use mixlab_protocol::{ModuleId, PerformanceInfo, PerformanceAccount, PerformanceMetric, Microseconds};
+use mixlab_protocol::{BufferPoolInfo, ModuleId, PerformanceInfo, PerformanceAccount, PerformanceMetric, Microseconds, Bytes, OverrunEvent, OverrunPolicy};
 
 use crate::engine;
 use crate::util;
 
 pub const TICK_BUDGET: Duration = Duration::from_micros(1_000_000 / engine::TICKS_PER_SECOND as u64);
 
+// number of recent overruns kept for the performance report
+const OVERRUN_LOG_LEN: usize = 16;
+
 pub struct EngineStat {
     is_realtime: bool,
     last_lagged: Option<Instant>,
+    last_tick: Duration,
     accounts: HashMap<PerformanceAccount, Stat>,
+    memory: HashMap<ModuleId, usize>,
+    overruns: VecDeque<OverrunEvent>,
+    buffers: BufferPoolInfo,
 }
 
 impl EngineStat {
@@ -19,7 +26,11 @@ impl EngineStat {
         EngineStat {
             is_realtime: false,
             last_lagged: None,
+            last_tick: Duration::from_micros(0),
             accounts: HashMap::new(),
+            memory: HashMap::new(),
+            overruns: VecDeque::new(),
+            buffers: BufferPoolInfo::default(),
         }
     }
 
@@ -32,13 +43,18 @@ impl EngineStat {
         tick.stat.is_realtime = end < scheduled_tick_end;
 
         let tick_time = end - start;
+        tick.stat.last_tick = tick_time;
 
         if tick_time > TICK_BUDGET {
             tick.stat.last_lagged = Some(Instant::now());
             eprintln!("WARNING: tick ran over time! elapsed: {} us, budget: {} us", tick_time.as_micros(), TICK_BUDGET.as_micros());
         }
 
-        tick.stat.add_sample(PerformanceAccount::Engine, tick_time - tick.modules_accounted_for);
+        // modules running in parallel can account for more than the tick took
+        let engine_time = tick_time.checked_sub(tick.modules_accounted_for)
+            .unwrap_or(Duration::from_micros(0));
+
+        tick.stat.add_sample(PerformanceAccount::Engine, engine_time);
 
         retn
     }
@@ -52,15 +68,51 @@ impl EngineStat {
             tick_rate: engine::TICKS_PER_SECOND,
             tick_budget: Microseconds(TICK_BUDGET.as_micros() as u64),
             accounts: self.accounts.iter().map(|(account, stat)| {
+                let memory = match account {
+                    PerformanceAccount::Engine => None,
+                    PerformanceAccount::Module(module_id) => {
+                        self.memory.get(module_id).map(|bytes| Bytes(*bytes as u64))
+                    }
+                };
+
                 (*account, PerformanceMetric {
                     last: Microseconds(stat.last().as_micros() as u64),
+                    memory,
                 })
-            }).collect()
+            }).collect(),
+            overruns: self.overruns.iter().cloned().collect(),
+            buffers: self.buffers,
+        }
+    }
+
+    // whether the last recorded tick ran over budget
+    pub fn overran(&self) -> bool {
+        self.last_tick > TICK_BUDGET
+    }
+
+    pub fn log_overrun(&mut self, tick: u64, action: OverrunPolicy) {
+        if self.overruns.len() == OVERRUN_LOG_LEN {
+            self.overruns.pop_front();
         }
+
+        self.overruns.push_back(OverrunEvent {
+            tick,
+            elapsed: Microseconds(self.last_tick.as_micros() as u64),
+            action,
+        });
+    }
+
+    pub fn record_memory(&mut self, module_id: ModuleId, bytes: usize) {
+        self.memory.insert(module_id, bytes);
+    }
+
+    pub fn record_buffers(&mut self, info: BufferPoolInfo) {
+        self.buffers = info;
     }
 
     pub fn remove_module(&mut self, module_id: ModuleId) {
         self.accounts.remove(&PerformanceAccount::Module(module_id));
+        self.memory.remove(&module_id);
     }
 
     fn add_sample(&mut self, account: PerformanceAccount, sample: Duration) {
@@ -83,14 +135,10 @@ impl<'a> TickStat<'a> {
         }
     }
 
-    pub fn record_module<T>(&mut self, module_id: ModuleId, f: impl FnOnce() -> T) -> T {
-        let start = Instant::now();
-        let retn = f();
-        let end = Instant::now();
-        let elapsed_time = end - start;
+    // modules are timed where they run, which may be on a worker thread
+    pub fn record_module(&mut self, module_id: ModuleId, elapsed_time: Duration) {
         self.modules_accounted_for += elapsed_time;
         self.stat.add_sample(PerformanceAccount::Module(module_id), elapsed_time);
-        retn
     }
 }
 
@@ -1,14 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
 
 use tokio::sync::watch;
 
-use mixlab_protocol::{ModuleId, InputId, OutputId, TerminalId, WindowGeometry, Indication, LineType};
+use mixlab_protocol::{ModuleId, ModuleParams, ChannelAdapter, InputId, OutputId, Terminal, TerminalId, WindowGeometry, Indication, LineType, ModuleSafety, MidiTarget, MidiControl, MidiMapping, OverrunPolicy, SurfaceControl, ViewerLink, Freeze, FreezeState};
 
 use crate::engine::module::{self, DynModuleHost};
+use crate::engine::run_order::{self, RunOrder};
 use crate::persist;
 use crate::project::ProjectBaseRef;
 use crate::util::Sequence;
+use crate::viewer;
 
 pub struct Workspace {
     pub(in crate::engine) module_seq: Sequence,
@@ -16,6 +19,19 @@ pub struct Workspace {
     pub(in crate::engine) geometry: HashMap<ModuleId, WindowGeometry>,
     pub(in crate::engine) connections: HashMap<InputId, OutputId>,
     pub(in crate::engine) indications: HashMap<ModuleId, Indication>,
+    pub(in crate::engine) safety: HashMap<ModuleId, ModuleSafety>,
+    pub(in crate::engine) groups: HashMap<ModuleId, String>,
+    pub(in crate::engine) bypassed: HashSet<ModuleId>,
+    // only connections not using the default adapter
+    pub(in crate::engine) adapters: HashMap<InputId, ChannelAdapter>,
+    pub(in crate::engine) midi_mappings: BTreeMap<MidiTarget, MidiControl>,
+    pub(in crate::engine) surface: Vec<SurfaceControl>,
+    pub(in crate::engine) viewer_links: Vec<ViewerLink>,
+    pub(in crate::engine) freezes: Vec<Freeze>,
+    pub(in crate::engine) overrun_policy: OverrunPolicy,
+    // modules or connections have changed since the run order was last
+    // worked out
+    topology_changed: bool,
 }
 
 impl Workspace {
@@ -23,6 +39,9 @@ impl Workspace {
         let mut modules = HashMap::new();
         let mut geometry = HashMap::new();
         let mut indications = HashMap::new();
+        let mut safety = HashMap::new();
+        let mut groups = HashMap::new();
+        let mut bypassed = HashSet::new();
 
         // load modules and geometry
         for (module_id, saved_module) in &save.modules {
@@ -30,14 +49,68 @@ impl Workspace {
             modules.insert(*module_id, module);
             geometry.insert(*module_id, saved_module.geometry.clone());
             indications.insert(*module_id, indication);
+
+            // modules always come back safe, arming is never persisted
+            if saved_module.protected {
+                safety.insert(*module_id, ModuleSafety::Safe);
+            }
+
+            if let Some(group) = &saved_module.group {
+                groups.insert(*module_id, group.clone());
+            }
+
+            if saved_module.bypassed {
+                bypassed.insert(*module_id);
+            }
         }
 
+        // drop any mappings left behind by modules which no longer exist
+        let midi_mappings = save.midi_mappings.iter()
+            .filter(|mapping| modules.contains_key(&mapping.target.module))
+            .map(|mapping| (mapping.target.clone(), mapping.control.clone()))
+            .collect();
+
+        // and likewise any surface controls
+        let surface = save.surface.iter()
+            .filter(|control| modules.contains_key(&control.target.module))
+            .cloned()
+            .collect();
+
+        // expired viewer links would only ever be revoked, so they go too
+        let now = viewer::unix_now();
+
+        let viewer_links = save.viewer_links.iter()
+            .filter(|link| modules.contains_key(&link.module) && link.expires > now)
+            .cloned()
+            .collect();
+
+        // a render in progress is lost, its chain was never frozen
+        let freezes = save.freezes.iter()
+            .filter(|freeze| match &freeze.state {
+                FreezeState::Rendering { .. } => false,
+                FreezeState::Frozen { source, modules: chain, .. } => {
+                    chain.iter().chain(Some(source)).all(|module_id| modules.contains_key(module_id))
+                }
+            })
+            .cloned()
+            .collect();
+
         let mut workspace = Workspace {
             module_seq: save.module_seq.clone(),
             modules,
             geometry,
             connections: HashMap::new(),
             indications,
+            safety,
+            groups,
+            bypassed,
+            adapters: HashMap::new(),
+            midi_mappings,
+            surface,
+            viewer_links,
+            freezes,
+            overrun_policy: save.overrun_policy,
+            topology_changed: true,
         };
 
         // load connections after loading all modules
@@ -50,6 +123,11 @@ impl Workspace {
                     let _ = workspace.connect(input_id, *output_id);
                 }
             }
+
+            // connecting resets adapters, so they are set once connected
+            for (input_idx, adapter) in &saved_module.adapters {
+                workspace.set_adapter(InputId(*module_id, *input_idx), *adapter);
+            }
         }
 
         workspace
@@ -71,14 +149,186 @@ impl Workspace {
                         .map(|input_id| self.connections.get(&input_id).cloned())
                         .collect();
 
+                    let protected = self.safety(*module_id).is_protected();
+                    let group = self.groups.get(module_id).cloned();
+                    let bypassed = self.bypassed.contains(module_id);
+
+                    let adapters = (0..module.inputs().len())
+                        .filter_map(|idx| self.adapters.get(&InputId(*module_id, idx)).map(|adapter| (idx, *adapter)))
+                        .collect();
+
                     (*module_id, persist::Module {
                         params,
                         geometry,
                         inputs,
+                        protected,
+                        group,
+                        bypassed,
+                        adapters,
                     })
                 })
-                .collect()
+                .collect(),
+            midi_mappings: self.midi_mappings.iter()
+                .map(|(target, control)| MidiMapping {
+                    target: target.clone(),
+                    control: control.clone(),
+                })
+                .collect(),
+            surface: self.surface.clone(),
+            viewer_links: self.viewer_links.clone(),
+            freezes: self.freezes.iter()
+                .filter(|freeze| match freeze.state {
+                    FreezeState::Rendering { .. } => false,
+                    FreezeState::Frozen { .. } => true,
+                })
+                .cloned()
+                .collect(),
+            overrun_policy: self.overrun_policy,
+        }
+    }
+
+    pub fn safety(&self, module_id: ModuleId) -> ModuleSafety {
+        self.safety.get(&module_id).copied().unwrap_or_default()
+    }
+
+    pub fn set_safety(&mut self, module_id: ModuleId, safety: ModuleSafety) {
+        if safety.is_protected() {
+            self.safety.insert(module_id, safety);
+        } else {
+            self.safety.remove(&module_id);
+        }
+    }
+
+    pub fn group(&self, module_id: ModuleId) -> Option<&str> {
+        self.groups.get(&module_id).map(String::as_str)
+    }
+
+    pub fn set_group(&mut self, module_id: ModuleId, group: Option<String>) {
+        match group {
+            Some(group) => { self.groups.insert(module_id, group); }
+            None => { self.groups.remove(&module_id); }
+        }
+    }
+
+    pub fn set_bypassed(&mut self, module_id: ModuleId, bypassed: bool) {
+        if bypassed {
+            self.bypassed.insert(module_id);
+        } else {
+            self.bypassed.remove(&module_id);
+        }
+    }
+
+    pub fn adapter(&self, input_id: InputId) -> ChannelAdapter {
+        self.adapters.get(&input_id).copied().unwrap_or_default()
+    }
+
+    /// Sets how a connection converts between mono and stereo. Returns false
+    /// if the input isn't connected through an adapter.
+    pub fn set_adapter(&mut self, input_id: InputId, adapter: ChannelAdapter) -> bool {
+        let output_type = self.connections.get(&input_id)
+            .and_then(|output_id| self.terminal_type(TerminalId::Output(*output_id)));
+
+        let input_type = self.terminal_type(TerminalId::Input(input_id));
+
+        match (output_type, input_type) {
+            (Some(output_type), Some(input_type)) if output_type.adapts_to(input_type) => {}
+            _ => return false,
         }
+
+        if adapter == ChannelAdapter::default() {
+            self.adapters.remove(&input_id);
+        } else {
+            self.adapters.insert(input_id, adapter);
+        }
+
+        true
+    }
+
+    pub fn set_midi_mapping(&mut self, target: MidiTarget, control: Option<MidiControl>) {
+        match control {
+            Some(control) => { self.midi_mappings.insert(target, control); }
+            None => { self.midi_mappings.remove(&target); }
+        }
+    }
+
+    // removes all mappings targeting a module, returning the removed targets
+    pub fn remove_midi_mappings(&mut self, module_id: ModuleId) -> Vec<MidiTarget> {
+        let targets = self.midi_mappings.keys()
+            .filter(|target| target.module == module_id)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for target in &targets {
+            self.midi_mappings.remove(target);
+        }
+
+        targets
+    }
+
+    // replaces the surface layout, dropping controls for modules which don't
+    // exist
+    pub fn set_surface(&mut self, mut surface: Vec<SurfaceControl>) -> &[SurfaceControl] {
+        surface.retain(|control| self.modules.contains_key(&control.target.module));
+        self.surface = surface;
+        &self.surface
+    }
+
+    // removes all surface controls for a module, returning whether there were
+    // any
+    pub fn remove_surface_controls(&mut self, module_id: ModuleId) -> bool {
+        let len = self.surface.len();
+        self.surface.retain(|control| control.target.module != module_id);
+        self.surface.len() != len
+    }
+
+    // removes all viewer links to a module, returning whether there were any
+    pub fn remove_viewer_links(&mut self, module_id: ModuleId) -> bool {
+        let len = self.viewer_links.len();
+        self.viewer_links.retain(|link| link.module != module_id);
+        self.viewer_links.len() != len
+    }
+
+    // modules in frozen chains, which are not run
+    pub fn frozen_modules(&self) -> HashSet<ModuleId> {
+        self.freezes.iter()
+            .flat_map(|freeze| match &freeze.state {
+                FreezeState::Rendering { .. } => &[][..],
+                FreezeState::Frozen { modules, .. } => &modules[..],
+            })
+            .copied()
+            .collect()
+    }
+
+    // the output of the freeze a module is part of, if any. a module may
+    // only be part of one freeze at a time
+    pub fn frozen_by(&self, module_id: ModuleId) -> Option<OutputId> {
+        self.freezes.iter()
+            .find(|freeze| freeze_involves(freeze, module_id))
+            .map(|freeze| freeze.output)
+    }
+
+    // a freeze involving a deleted module can't be thawed, so it goes and
+    // its chain runs again. returns whether there were any
+    pub fn remove_freezes(&mut self, module_id: ModuleId) -> bool {
+        let len = self.freezes.len();
+        self.freezes.retain(|freeze| !freeze_involves(freeze, module_id));
+        self.freezes.len() != len
+    }
+
+    // what each viewer link currently points to. links to modules which
+    // can't be watched are left out, and so never work
+    pub fn viewer_grants(&self) -> HashMap<String, viewer::Grant> {
+        self.viewer_links.iter()
+            .filter_map(|link| {
+                let target = match (self.modules.get(&link.module)?.params(), self.indications.get(&link.module)?) {
+                    (ModuleParams::HlsOutput(params), _) => viewer::Target::Hls(params.name),
+                    (_, Indication::Monitor(indication)) => viewer::Target::Monitor(indication.socket_id),
+                    _ => return None,
+                };
+
+                Some((link.token.clone(), viewer::Grant { target, expires: link.expires }))
+            })
+            .collect()
     }
 
     fn terminal_type(&self, terminal: TerminalId) -> Option<LineType> {
@@ -94,6 +344,16 @@ impl Workspace {
         })
     }
 
+    pub fn insert_module(&mut self, module_id: ModuleId, module: DynModuleHost) {
+        self.modules.insert(module_id, module);
+        self.topology_changed = true;
+    }
+
+    pub fn remove_module(&mut self, module_id: ModuleId) -> Option<DynModuleHost> {
+        self.topology_changed = true;
+        self.modules.remove(&module_id)
+    }
+
     pub fn connect(&mut self, input_id: InputId, output_id: OutputId) -> Result<Option<OutputId>, ConnectError> {
         let input_type = match self.terminal_type(TerminalId::Input(input_id)) {
             Some(ty) => ty,
@@ -105,23 +365,116 @@ impl Workspace {
             None => return Err(ConnectError::NoOutput),
         };
 
-        if input_type == output_type {
-            Ok(self.connections.insert(input_id, output_id))
-        } else {
+        if !output_type.connects_to(input_type) {
             // type mismatch, don't connect
-            Err(ConnectError::TypeMismatch)
+            return Err(ConnectError::TypeMismatch);
         }
+
+        // feedback loops would leave the run order to decide which module in
+        // the loop hears silence, so aren't allowed at all
+        if run_order::feeds(&self.connections, input_id.module_id(), output_id.module_id()) {
+            return Err(ConnectError::Cycle);
+        }
+
+        self.topology_changed = true;
+        self.adapters.remove(&input_id);
+        Ok(self.connections.insert(input_id, output_id))
     }
 
     pub fn disconnect(&mut self, input_id: InputId) -> Option<OutputId> {
+        self.topology_changed = true;
+        self.adapters.remove(&input_id);
         self.connections.remove(&input_id)
     }
+
+    /// Moves connections to and from a module whose terminals have just
+    /// changed so that they stay with the same terminals, which are followed
+    /// by label. Connections to terminals which have gone are dropped.
+    pub fn retarget(&mut self, module_id: ModuleId, old_inputs: &[Terminal], old_outputs: &[Terminal]) -> Retargeted {
+        let (new_inputs, new_outputs) = match self.modules.get(&module_id) {
+            Some(module) => (module.inputs().to_vec(), module.outputs().to_vec()),
+            None => return Retargeted::default(),
+        };
+
+        let mut affected = self.connections.iter()
+            .filter(|(input, output)| input.module_id() == module_id || output.module_id() == module_id)
+            .map(|(input, output)| (*input, *output))
+            .collect::<Vec<_>>();
+
+        // keep the oplog independent of hash order so that replays are
+        // reproducible
+        affected.sort();
+
+        let mut retargeted = Retargeted::default();
+        let mut moved = Vec::new();
+
+        for (input, output) in affected {
+            let new_input = match input.module_id() == module_id {
+                true => follow_terminal(old_inputs, &new_inputs, input.index()).map(|index| InputId(module_id, index)),
+                false => Some(input),
+            };
+
+            let new_output = match output.module_id() == module_id {
+                true => follow_terminal(old_outputs, &new_outputs, output.index()).map(|index| OutputId(module_id, index)),
+                false => Some(output),
+            };
+
+            if new_input == Some(input) && new_output == Some(output) {
+                continue;
+            }
+
+            self.connections.remove(&input);
+            let adapter = self.adapters.remove(&input);
+            retargeted.deleted.push(input);
+
+            if let (Some(input), Some(output)) = (new_input, new_output) {
+                moved.push((input, output, adapter));
+            }
+        }
+
+        // terminals are followed whole, so line types still match and the
+        // same modules are connected as before, without any new cycle
+        for (input, output, adapter) in moved {
+            if !self.connections.contains_key(&input) {
+                self.connections.insert(input, output);
+                retargeted.created.push((input, output));
+
+                if let Some(adapter) = adapter {
+                    self.adapters.insert(input, adapter);
+                }
+            }
+        }
+
+        self.topology_changed = true;
+        retargeted
+    }
 }
 
 pub enum ConnectError {
     NoInput,
     NoOutput,
     TypeMismatch,
+    Cycle,
+}
+
+/// Connections changed by `Workspace::retarget`. Every connection which
+/// moved is deleted from where it was and created again where it is now,
+/// keeping its adapter.
+#[derive(Debug, Default)]
+pub struct Retargeted {
+    pub deleted: Vec<InputId>,
+    pub created: Vec<(InputId, OutputId)>,
+}
+
+// where a terminal is after a module's terminals change. labelled terminals
+// are found by label wherever they are, unlabelled ones only where they were
+fn follow_terminal(old: &[Terminal], new: &[Terminal], index: usize) -> Option<usize> {
+    let terminal = old.get(index)?;
+
+    match terminal.label() {
+        Some(_) => new.iter().position(|new| new == terminal),
+        None => new.get(index).filter(|new| *new == terminal).map(|_| index),
+    }
 }
 
 pub struct WorkspaceEmbryo {
@@ -141,6 +494,7 @@ impl WorkspaceEmbryo {
         SyncWorkspace {
             workspace,
             persist_tx: self.persist_tx,
+            run_order: None,
         }
     }
 }
@@ -148,6 +502,7 @@ impl WorkspaceEmbryo {
 pub struct SyncWorkspace {
     workspace: Workspace,
     persist_tx: watch::Sender<persist::Workspace>,
+    run_order: Option<Rc<RunOrder>>,
 }
 
 impl SyncWorkspace {
@@ -160,6 +515,21 @@ impl SyncWorkspace {
         &self.workspace
     }
 
+    /// The order modules run in, worked out again only once modules or
+    /// connections have changed rather than every tick.
+    pub fn run_order(&mut self) -> Rc<RunOrder> {
+        if self.workspace.topology_changed || self.run_order.is_none() {
+            let inputs = self.workspace.modules.iter()
+                .map(|(module_id, module)| (*module_id, module.inputs().len()))
+                .collect();
+
+            self.run_order = Some(Rc::new(RunOrder::new(&inputs, &self.workspace.connections)));
+            self.workspace.topology_changed = false;
+        }
+
+        self.run_order.clone().expect("run_order")
+    }
+
     pub fn borrow_mut<'a>(&'a mut self) -> WorkspaceBorrowMut<'a> {
         WorkspaceBorrowMut { sync: self }
     }
@@ -194,3 +564,14 @@ impl<'a> DerefMut for WorkspaceBorrowMut<'a> {
         &mut self.sync.workspace
     }
 }
+
+fn freeze_involves(freeze: &Freeze, module_id: ModuleId) -> bool {
+    if freeze.output.module_id() == module_id {
+        return true;
+    }
+
+    match &freeze.state {
+        FreezeState::Rendering { .. } => false,
+        FreezeState::Frozen { source, modules, .. } => *source == module_id || modules.contains(&module_id),
+    }
+}
@@ -0,0 +1,434 @@
+use std::f64;
+use std::fmt::{self, Debug};
+use std::mem;
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use rustfft::{FFTplanner, FFT};
+
+// analysis window, about 93ms and 11Hz per bin at 44.1kHz
+const FFT_LEN: usize = 4096;
+
+// feedback outside this range is rare, and mains hum is not feedback
+const MIN_FREQ: f64 = 80.0;
+const MAX_FREQ: f64 = 16000.0;
+
+// peaks quieter than this, in dBFS, aren't loud enough to be howling
+const MIN_LEVEL_DB: f64 = -50.0;
+
+// a voice or instrument has harmonics close to its fundamental in level,
+// feedback is nearly a pure tone
+const HARMONIC_REJECT_DB: f64 = 20.0;
+
+// how long a peak must hold at the same frequency before it's notched, and
+// then before the notch is deepened
+const PERSIST_SECS: f64 = 0.15;
+
+// notches start at this depth and deepen by it each time they retrigger
+const DEPTH_STEP_DB: f64 = 6.0;
+
+// about a twentieth of an octave wide, narrow enough to leave the voice
+// alone around it
+const NOTCH_Q: f64 = 30.0;
+
+// a peak this close to an existing notch, as a frequency ratio, is the same
+// feedback it was placed for
+const NOTCH_MATCH_RATIO: f64 = 1.03;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    // how far a peak must stand above the average of the spectrum
+    pub sensitivity_db: f64,
+    pub max_notches: usize,
+    pub max_depth_db: f64,
+    // notches not retriggered for this long are lifted, zero keeps them
+    pub release_secs: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Notch {
+    pub freq: f64,
+    pub depth_db: f64,
+    biquad: Biquad,
+    // sample clock when the notch was last placed or deepened
+    triggered_at: u64,
+}
+
+// Detects feedback ringing in a signal and cuts it with narrow notch filters.
+// The notched output is analysed, so feedback that keeps ringing through a
+// notch deepens it. A ringing peak is told apart from speech by standing far
+// above the rest of the spectrum, without strong harmonics, at the same
+// frequency for a while.
+pub struct FeedbackSuppressor {
+    sample_rate: f64,
+    settings: Settings,
+    fft: Arc<dyn FFT<f32>>,
+    window: Vec<f32>,
+    // the last FFT_LEN samples of output, oldest first
+    history: Vec<f32>,
+    scratch: Vec<Complex<f32>>,
+    spectrum: Vec<Complex<f32>>,
+    power: Vec<f32>,
+    // the bin a peak is standing at, and since when
+    candidate: Option<(usize, u64)>,
+    notches: Vec<Notch>,
+    clock: u64,
+}
+
+impl Debug for FeedbackSuppressor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FeedbackSuppressor {{ notches: {:?} }}", self.notches)
+    }
+}
+
+impl FeedbackSuppressor {
+    pub fn new(sample_rate: usize, settings: Settings) -> Self {
+        // hann window
+        let window = (0..FFT_LEN)
+            .map(|i| 0.5 - 0.5 * (2.0 * f64::consts::PI * i as f64 / FFT_LEN as f64).cos())
+            .map(|w| w as f32)
+            .collect();
+
+        FeedbackSuppressor {
+            sample_rate: sample_rate as f64,
+            settings,
+            fft: FFTplanner::new(false).plan_fft(FFT_LEN),
+            window,
+            history: vec![0.0; FFT_LEN],
+            scratch: vec![Complex::zero(); FFT_LEN],
+            spectrum: vec![Complex::zero(); FFT_LEN],
+            power: vec![0.0; FFT_LEN / 2],
+            candidate: None,
+            notches: Vec::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        (self.window.len() + self.history.len() + self.power.len()) * mem::size_of::<f32>()
+            + (self.scratch.len() + self.spectrum.len()) * mem::size_of::<Complex<f32>>()
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+
+        // drop the oldest notches beyond the new limit, and bring the rest
+        // up to the new depth limit
+        while self.notches.len() > settings.max_notches {
+            self.remove_oldest();
+        }
+
+        let sample_rate = self.sample_rate;
+
+        for notch in &mut self.notches {
+            if notch.depth_db > settings.max_depth_db {
+                notch.depth_db = settings.max_depth_db;
+                notch.biquad.set_notch(notch.freq, notch.depth_db, sample_rate);
+            }
+        }
+    }
+
+    pub fn notches(&self) -> &[Notch] {
+        &self.notches
+    }
+
+    pub fn clear(&mut self) {
+        self.notches.clear();
+        self.candidate = None;
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        assert!(input.len() == output.len());
+
+        for (out, sample) in output.iter_mut().zip(input) {
+            let mut value = *sample as f64;
+
+            for notch in &mut self.notches {
+                value = notch.biquad.pump(value);
+            }
+
+            *out = value as f32;
+        }
+
+        let len = output.len().min(FFT_LEN);
+        self.history.copy_within(len.., 0);
+        self.history[FFT_LEN - len..].copy_from_slice(&output[output.len() - len..]);
+
+        self.clock += output.len() as u64;
+
+        self.analyse();
+        self.release();
+    }
+
+    fn analyse(&mut self) {
+        for ((value, sample), w) in self.scratch.iter_mut().zip(&self.history).zip(&self.window) {
+            *value = Complex::new(sample * w, 0.0);
+        }
+
+        self.fft.process(&mut self.scratch, &mut self.spectrum);
+
+        for (power, bin) in self.power.iter_mut().zip(&self.spectrum) {
+            *power = bin.norm_sqr();
+        }
+
+        let min_bin = self.freq_to_bin(MIN_FREQ).max(1);
+        let max_bin = self.freq_to_bin(MAX_FREQ).min(self.power.len() - 2);
+
+        let (peak_bin, peak_power) = (min_bin..=max_bin)
+            .map(|bin| (bin, self.power[bin]))
+            .fold((min_bin, 0.0), |peak, bin| if bin.1 > peak.1 { bin } else { peak });
+
+        let mean_power = self.power[min_bin..=max_bin].iter().sum::<f32>() / (max_bin - min_bin + 1) as f32;
+
+        if !self.is_feedback(peak_bin, peak_power, mean_power) {
+            self.candidate = None;
+            return;
+        }
+
+        let since = match self.candidate {
+            // peaks wander by a bin as the window slides over them
+            Some((bin, since)) if (bin as isize - peak_bin as isize).abs() <= 1 => since,
+            _ => self.clock,
+        };
+
+        self.candidate = Some((peak_bin, since));
+
+        if (self.clock - since) as f64 >= PERSIST_SECS * self.sample_rate {
+            let freq = self.interpolate_peak(peak_bin);
+            self.trigger(freq);
+
+            // the notch must be given time to take effect before deepening
+            self.candidate = Some((peak_bin, self.clock));
+        }
+    }
+
+    fn is_feedback(&self, peak_bin: usize, peak_power: f32, mean_power: f32) -> bool {
+        if peak_power <= 0.0 || mean_power <= 0.0 {
+            return false;
+        }
+
+        // a sine of amplitude A peaks at A * N / 4 through a hann window
+        let amplitude = 4.0 * (peak_power as f64).sqrt() / FFT_LEN as f64;
+
+        if 20.0 * amplitude.log10() < MIN_LEVEL_DB {
+            return false;
+        }
+
+        if 10.0 * (peak_power as f64 / mean_power as f64).log10() < self.settings.sensitivity_db {
+            return false;
+        }
+
+        // the loudest peak of a voice can be its second harmonic rather than
+        // its fundamental, so look an octave either side. harmonics wander
+        // with the fundamental, so take the loudest bin around where each
+        // should be
+        let octaves = [peak_bin / 2, peak_bin * 2];
+
+        for &bin in octaves.iter().filter(|bin| **bin >= 1 && **bin + 1 < self.power.len()) {
+            let power = self.power[bin - 1..=bin + 1].iter().copied().fold(0.0, f32::max);
+
+            if power > 0.0 && 10.0 * (peak_power as f64 / power as f64).log10() < HARMONIC_REJECT_DB {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // the frequency between bins a peak is centred on, from a parabola
+    // through the log power of its bin and the bins either side
+    fn interpolate_peak(&self, bin: usize) -> f64 {
+        let db = |bin: usize| 10.0 * (self.power[bin] as f64).max(1e-20).log10();
+
+        let (a, b, c) = (db(bin - 1), db(bin), db(bin + 1));
+        let denom = a - 2.0 * b + c;
+
+        let offset = if denom.abs() > f64::EPSILON {
+            (0.5 * (a - c) / denom).max(-0.5).min(0.5)
+        } else {
+            0.0
+        };
+
+        (bin as f64 + offset) * self.sample_rate / FFT_LEN as f64
+    }
+
+    fn trigger(&mut self, freq: f64) {
+        let sample_rate = self.sample_rate;
+        let clock = self.clock;
+        let max_depth_db = self.settings.max_depth_db;
+
+        let existing = self.notches.iter_mut()
+            .find(|notch| (notch.freq / freq).max(freq / notch.freq) < NOTCH_MATCH_RATIO);
+
+        if let Some(notch) = existing {
+            notch.depth_db = (notch.depth_db + DEPTH_STEP_DB).min(max_depth_db);
+            notch.biquad.set_notch(notch.freq, notch.depth_db, sample_rate);
+            notch.triggered_at = clock;
+            return;
+        }
+
+        if self.settings.max_notches == 0 {
+            return;
+        }
+
+        if self.notches.len() >= self.settings.max_notches {
+            self.remove_oldest();
+        }
+
+        let depth_db = DEPTH_STEP_DB.min(max_depth_db);
+        let mut biquad = Biquad::default();
+        biquad.set_notch(freq, depth_db, sample_rate);
+
+        self.notches.push(Notch { freq, depth_db, biquad, triggered_at: clock });
+    }
+
+    fn release(&mut self) {
+        if self.settings.release_secs <= 0.0 {
+            return;
+        }
+
+        let release = (self.settings.release_secs * self.sample_rate) as u64;
+        let clock = self.clock;
+
+        self.notches.retain(|notch| clock - notch.triggered_at < release);
+    }
+
+    fn remove_oldest(&mut self) {
+        let oldest = self.notches.iter()
+            .enumerate()
+            .min_by_key(|(_, notch)| notch.triggered_at)
+            .map(|(index, _)| index);
+
+        if let Some(index) = oldest {
+            self.notches.remove(index);
+        }
+    }
+
+    fn freq_to_bin(&self, freq: f64) -> usize {
+        (freq * FFT_LEN as f64 / self.sample_rate).round() as usize
+    }
+}
+
+// peaking filter cutting by the notch depth, after the RBJ audio EQ cookbook
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    // transposed direct form II state
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    fn set_notch(&mut self, freq: f64, depth_db: f64, sample_rate: f64) {
+        let a = 10f64.powf(-depth_db / 40.0);
+        let w0 = 2.0 * f64::consts::PI * freq / sample_rate;
+        let alpha = w0.sin() / (2.0 * NOTCH_Q);
+        let cos_w0 = w0.cos();
+
+        let a0 = 1.0 + alpha / a;
+
+        self.b0 = (1.0 + alpha * a) / a0;
+        self.b1 = -2.0 * cos_w0 / a0;
+        self.b2 = (1.0 - alpha * a) / a0;
+        self.a1 = -2.0 * cos_w0 / a0;
+        self.a2 = (1.0 - alpha / a) / a0;
+    }
+
+    fn pump(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::noise;
+
+    const SAMPLE_RATE: usize = 44100;
+    const BLOCK_LEN: usize = 735;
+
+    fn settings() -> Settings {
+        Settings {
+            sensitivity_db: 15.0,
+            max_notches: 8,
+            max_depth_db: 18.0,
+            release_secs: 0.0,
+        }
+    }
+
+    fn tone(len: usize, freq: f64, amplitude: f64) -> Vec<f32> {
+        (0..len)
+            .map(|i| (amplitude * (2.0 * f64::consts::PI * freq * i as f64 / SAMPLE_RATE as f64).sin()) as f32)
+            .collect()
+    }
+
+    fn run(suppressor: &mut FeedbackSuppressor, input: &[f32]) -> Vec<f32> {
+        let mut output = vec![0.0; input.len()];
+
+        for (input, output) in input.chunks(BLOCK_LEN).zip(output.chunks_mut(BLOCK_LEN)) {
+            suppressor.process(input, output);
+        }
+
+        output
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, sample| peak.max(sample.abs()))
+    }
+
+    #[test]
+    fn notches_ringing_tone() {
+        let len = SAMPLE_RATE * 2;
+        let ringing = tone(len, 1234.0, 0.5);
+
+        let input = ringing.iter().zip(noise(len, 1))
+            .map(|(tone, noise)| tone + noise * 0.02)
+            .collect::<Vec<_>>();
+
+        let mut suppressor = FeedbackSuppressor::new(SAMPLE_RATE, settings());
+        let output = run(&mut suppressor, &input);
+
+        let notches = suppressor.notches();
+        assert_eq!(1, notches.len());
+        assert!((notches[0].freq - 1234.0).abs() < 5.0, "notched at {} Hz", notches[0].freq);
+        assert_eq!(18.0, notches[0].depth_db);
+
+        // once the notch is at full depth the tone comes through well down
+        let tail = peak(&output[len - SAMPLE_RATE / 4..]);
+        assert!(tail < 0.1, "tone still peaks at {}", tail);
+    }
+
+    #[test]
+    fn leaves_noise_alone() {
+        let input = noise(SAMPLE_RATE * 2, 2);
+
+        let mut suppressor = FeedbackSuppressor::new(SAMPLE_RATE, settings());
+        let output = run(&mut suppressor, &input);
+
+        assert!(suppressor.notches().is_empty());
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn leaves_harmonic_tones_alone() {
+        let len = SAMPLE_RATE * 2;
+
+        // louder at the second harmonic than the fundamental, as voices can be
+        let input = tone(len, 220.0, 0.2).iter().zip(tone(len, 440.0, 0.4))
+            .map(|(fundamental, harmonic)| fundamental + harmonic)
+            .collect::<Vec<_>>();
+
+        let mut suppressor = FeedbackSuppressor::new(SAMPLE_RATE, settings());
+        run(&mut suppressor, &input);
+
+        assert!(suppressor.notches().is_empty());
+    }
+}
@@ -0,0 +1,316 @@
+//! Ingest for remote guests, who join from the guest page in their browser
+//! rather than running an encoder. The page records the guest's camera and
+//! mic with MediaRecorder and sends the recording over a websocket as it
+//! goes, which is decoded here and fed to a StreamInput set to Guest.
+//!
+//! This stands in for WebRTC, which mixlab has no ingest for: there is no
+//! WebRTC stack among its dependencies to negotiate sessions, and a STUN or
+//! TURN server would be needed to reach guests behind NAT. A websocket goes
+//! over the same HTTP port as everything else, and MediaRecorder output is
+//! WebM that ffmpeg already decodes. The cost is latency: MediaRecorder
+//! delivers in timeslices rather than per frame, and TCP stalls on loss
+//! rather than dropping late packets, so guests sit a little further behind
+//! than they would over WebRTC.
+
+use std::io;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use derive_more::From;
+use futures::stream::StreamExt;
+use warp::ws::WebSocket;
+
+use mixlab_codec::ffmpeg::codec::{self, CodecBuilder, Decode, RecvFrameError};
+use mixlab_codec::ffmpeg::media::{Audio, Video};
+use mixlab_codec::ffmpeg::{AvError, AvIoError, AvIoReader, AvPacket, InputContainer, SequentialReader, UnsupportedSampleFormat};
+use mixlab_util::time::{MediaTime, MediaDuration, TimeBase};
+
+use crate::engine::{Sample, CHANNELS, SAMPLE_RATE};
+use crate::module::media_source::read_stereo;
+use crate::resample::Resampler;
+use crate::source::{Registry, SourceRecv, SourceSend};
+use crate::video;
+
+// browsers don't always say how long a recorded frame lasts, so until the
+// next frame tells us, assume a typical webcam rate
+const DEFAULT_FRAME_RATE: i64 = 30;
+
+lazy_static::lazy_static! {
+    static ref MOUNTPOINTS: Registry = Registry::new();
+}
+
+pub fn listen(mountpoint: &str, key: Option<String>) -> SourceRecv {
+    let recv = MOUNTPOINTS.listen(mountpoint);
+    recv.set_stream_key(key);
+    recv
+}
+
+pub async fn accept(mountpoint: String, key: Option<String>, mut websocket: WebSocket) {
+    // closing the websocket is all the guest page needs to know that it
+    // was turned away
+    let send = match MOUNTPOINTS.connect(&mountpoint, key.as_deref()) {
+        Ok(send) => send,
+        Err(e) => {
+            eprintln!("guest: could not connect to mountpoint {:?}: {:?}", mountpoint, e);
+            return;
+        }
+    };
+
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        match run_decode_thread(send, ChannelReader::new(rx)) {
+            Ok(()) => {}
+            Err(e) => {
+                eprintln!("guest: error in decode thread: {:?}", e);
+            }
+        }
+    });
+
+    while let Some(message) = websocket.next().await {
+        match message {
+            Ok(message) if message.is_binary() => {
+                // the decode thread has gone away
+                if tx.send(message.as_bytes().to_vec()).is_err() {
+                    break;
+                }
+            }
+            Ok(message) if message.is_close() => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("guest: websocket error: {:?}", e);
+                break;
+            }
+        }
+    }
+}
+
+// reads the recording as its chunks arrive, ending when the guest leaves
+struct ChannelReader {
+    rx: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    fn new(rx: Receiver<Vec<u8>>) -> Self {
+        ChannelReader { rx, chunk: Vec::new(), pos: 0 }
+    }
+}
+
+impl io::Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+type GuestStream = SequentialReader<ChannelReader>;
+
+#[derive(Debug, From)]
+enum DecodeError {
+    CodecBuild(codec::BuildError),
+    CodecOpen(codec::OpenError),
+    NoStreams,
+    RecvFrame(RecvFrameError),
+    SampleFormat(UnsupportedSampleFormat),
+    Av(AvError),
+    Io(io::Error),
+    ListenerDisconnected,
+}
+
+impl From<AvIoError<GuestStream>> for DecodeError {
+    fn from(e: AvIoError<GuestStream>) -> DecodeError {
+        match e {
+            AvIoError::Av(e) => DecodeError::Av(e),
+            AvIoError::Io(e) => DecodeError::Io(e),
+        }
+    }
+}
+
+struct VideoTrack {
+    index: usize,
+    decode: Decode<Video>,
+    time_base: TimeBase,
+    last_pts: Option<MediaTime>,
+}
+
+struct AudioTrack {
+    index: usize,
+    decode: Decode<Audio>,
+    time_base: TimeBase,
+    resample: Option<AudioResample>,
+    // scratch space for decoded samples
+    samples: Vec<Sample>,
+}
+
+struct AudioResample {
+    resampler: Resampler,
+    source_rate: usize,
+    // pts of the first sample fed to the resampler
+    start: MediaTime,
+    // sample frames the resampler has produced so far
+    produced: i64,
+}
+
+fn run_decode_thread(mut send: SourceSend, reader: ChannelReader) -> Result<(), DecodeError> {
+    let io = AvIoReader::new(SequentialReader(reader)).unseekable();
+    let mut container = InputContainer::open(io)?;
+
+    let streams = container.streams();
+
+    let mut video = match streams.iter().position(|stream| stream.is_media::<Video>()) {
+        Some(index) => {
+            let time_base = streams[index].time_base();
+            let codec_params = streams[index].codec_parameters();
+
+            let decode = CodecBuilder::<Video>::new(codec_params.codec_id, time_base)?
+                .with_parameters(codec_params)
+                .open_decoder()?;
+
+            Some(VideoTrack { index, decode, time_base, last_pts: None })
+        }
+        None => None,
+    };
+
+    let mut audio = match streams.iter().position(|stream| stream.is_media::<Audio>()) {
+        Some(index) => {
+            let time_base = streams[index].time_base();
+            let codec_params = streams[index].codec_parameters();
+
+            let decode = CodecBuilder::<Audio>::new(codec_params.codec_id, time_base)?
+                .with_parameters(codec_params)
+                .open_decoder()?;
+
+            Some(AudioTrack { index, decode, time_base, resample: None, samples: Vec::new() })
+        }
+        None => None,
+    };
+
+    if video.is_none() && audio.is_none() {
+        return Err(DecodeError::NoStreams);
+    }
+
+    let video_codec = video.as_ref().and_then(|track| streams[track.index].codec_name());
+    let audio_codec = audio.as_ref().and_then(|track| streams[track.index].codec_name());
+
+    send.set_info(|info| {
+        info.video_codec = video_codec.map(str::to_owned);
+        info.audio_codec = audio_codec.map(str::to_owned);
+    });
+
+    while let Some(pkt) = container.read_packet()? {
+        decode_packet(&mut send, video.as_mut(), audio.as_mut(), &pkt)?;
+    }
+
+    Ok(())
+}
+
+fn decode_packet(send: &mut SourceSend, video: Option<&mut VideoTrack>, audio: Option<&mut AudioTrack>, pkt: &AvPacket)
+    -> Result<(), DecodeError>
+{
+    let index = pkt.stream_index() as usize;
+
+    match (video, audio) {
+        (Some(video), _) if video.index == index => {
+            video.decode.send_packet(pkt)?;
+            recv_video(send, video)
+        }
+        (_, Some(audio)) if audio.index == index => {
+            audio.decode.send_packet(pkt)?;
+            recv_audio(send, audio)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn recv_video(send: &mut SourceSend, track: &mut VideoTrack) -> Result<(), DecodeError> {
+    loop {
+        let decoded = match track.decode.recv_frame() {
+            Ok(decoded) => decoded,
+            Err(RecvFrameError::NeedMoreInput) | Err(RecvFrameError::Eof) => {
+                return Ok(());
+            }
+            Err(e) => { return Err(e.into()); }
+        };
+
+        let pts = track.time_base.scale_timestamp(decoded.presentation_timestamp());
+
+        let duration = match (decoded.packet_duration(), track.last_pts) {
+            (duration, _) if duration > 0 => track.time_base.scale_duration(duration),
+            (_, Some(last_pts)) if pts > last_pts => pts - last_pts,
+            _ => MediaDuration::new(1, DEFAULT_FRAME_RATE),
+        };
+
+        track.last_pts = Some(pts);
+
+        let frame = video::Frame {
+            key_frame: decoded.is_key_frame(),
+            decoded,
+            duration_hint: duration,
+            passthrough: None,
+        };
+
+        send.write_video(pts, frame)
+            .map_err(|()| DecodeError::ListenerDisconnected)?;
+    }
+}
+
+fn recv_audio(send: &mut SourceSend, track: &mut AudioTrack) -> Result<(), DecodeError> {
+    loop {
+        let decoded = match track.decode.recv_frame() {
+            Ok(decoded) => decoded,
+            Err(RecvFrameError::NeedMoreInput) | Err(RecvFrameError::Eof) => {
+                return Ok(());
+            }
+            Err(e) => { return Err(e.into()); }
+        };
+
+        let source_rate = decoded.sample_rate();
+
+        // browsers record Opus at 48kHz, so this nearly always resamples
+        if track.resample.as_ref().map(|resample| resample.source_rate) != Some(source_rate) {
+            track.resample = Some(AudioResample {
+                resampler: Resampler::new(source_rate, SAMPLE_RATE, CHANNELS),
+                source_rate,
+                start: track.time_base.scale_timestamp(decoded.presentation_timestamp()),
+                produced: 0,
+            });
+        }
+
+        track.samples.clear();
+        read_stereo(&decoded, &mut track.samples)?;
+
+        let resample = track.resample.as_mut().unwrap();
+        let mut samples = Vec::new();
+        resample.resampler.process(&track.samples, &mut samples);
+
+        if samples.is_empty() {
+            continue;
+        }
+
+        // timestamps follow the resampler's output so that chunks stay
+        // contiguous
+        let pts = resample.start + MediaDuration::new(resample.produced, SAMPLE_RATE as i64);
+        resample.produced += (samples.len() / CHANNELS) as i64;
+
+        let pcm = samples.iter()
+            .map(|sample| (sample.max(-1.0).min(1.0) * i16::max_value() as Sample) as i16)
+            .collect();
+
+        send.write_audio(pts, pcm)
+            .map_err(|()| DecodeError::ListenerDisconnected)?;
+    }
+}
@@ -0,0 +1,95 @@
+use derive_more::From;
+use httparse::{Response, Status, EMPTY_HEADER};
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::icecast::http;
+use crate::listen::WriteQueue;
+
+// user name sent when the url doesn't give one, which is what Icecast
+// expects of sources by default
+const DEFAULT_USER: &str = "source";
+
+// longest response to the source request we'll read
+const MAX_RESPONSE_LEN: usize = 8192;
+
+#[derive(Debug, From)]
+pub enum ConnectError {
+    Url(url::ParseError),
+    UnsupportedScheme,
+    MissingHost,
+    Io(io::Error),
+    BadResponse,
+    // the server refused the source with this status, 401 for a wrong
+    // password or 403 for a mountpoint already in use
+    Refused(u16),
+}
+
+/// Connects to an Icecast server as a source for the mountpoint in `url`,
+/// returning a queue to write the stream to.
+pub async fn connect(url: &str, password: &str, content_type: &str) -> Result<WriteQueue, ConnectError> {
+    let url = url::Url::parse(url)?;
+
+    if url.scheme() != "http" {
+        return Err(ConnectError::UnsupportedScheme);
+    }
+
+    let hostname = url.host_str().ok_or(ConnectError::MissingHost)?;
+    let port = url.port().unwrap_or(8000);
+
+    let user = match url.username() {
+        "" => DEFAULT_USER,
+        user => user,
+    };
+
+    let mut conn = TcpStream::connect((hostname, port)).await?;
+    conn.set_nodelay(true)?;
+
+    // Icecast 2.4 and later take sources with PUT, answering 100 Continue
+    // before the stream is sent
+    let request = format!(concat!(
+            "PUT {} HTTP/1.1\r\n",
+            "Host: {}:{}\r\n",
+            "Authorization: {}\r\n",
+            "User-Agent: mixlab\r\n",
+            "Content-Type: {}\r\n",
+            "Ice-Public: 0\r\n",
+            "Expect: 100-continue\r\n",
+            "\r\n"),
+        url.path(), hostname, port, http::basic_auth(user, password), content_type);
+
+    conn.write_all(request.as_bytes()).await?;
+
+    let status = read_response_status(&mut conn).await?;
+
+    match status {
+        100 | 200 => Ok(WriteQueue::spawn(conn)),
+        status => Err(ConnectError::Refused(status)),
+    }
+}
+
+async fn read_response_status(conn: &mut TcpStream) -> Result<u16, ConnectError> {
+    let mut buff = Vec::new();
+
+    loop {
+        let mut headers = [EMPTY_HEADER; 16];
+        let mut response = Response::new(&mut headers);
+
+        if let Status::Complete(_) = response.parse(&buff).map_err(|_| ConnectError::BadResponse)? {
+            return response.code.ok_or(ConnectError::BadResponse);
+        }
+
+        if buff.len() >= MAX_RESPONSE_LEN {
+            return Err(ConnectError::BadResponse);
+        }
+
+        let mut chunk = [0u8; 1024];
+        let len = conn.read(&mut chunk).await?;
+
+        if len == 0 {
+            return Err(ConnectError::BadResponse);
+        }
+
+        buff.extend_from_slice(&chunk[0..len]);
+    }
+}
@@ -13,18 +13,22 @@ pub enum Error {
     Eof,
     HeadersTooLong,
     NoPath,
-    NoContentType,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ContentType {
     Ogg,
+    Aac,
+    Mp3,
 }
 
 #[derive(Debug)]
 pub struct RequestInfo {
     pub path: String,
     pub content_type: Option<ContentType>,
+    // from HTTP basic auth. source clients send a user name too, usually
+    // "source", which is ignored
+    pub password: Option<String>,
     pub stream_data: Vec<u8>,
 }
 
@@ -58,21 +62,272 @@ pub async fn parse(stream: &mut PeekTcpStream) -> Result<RequestInfo, Error> {
     }
 
     fn on_complete(request: &Request, stream_data: &[u8]) -> Result<RequestInfo, Error> {
-        let content_type_hdr = request.headers.iter()
+        // source clients don't always send a content type, or send the
+        // wrong one. the stream payload is probed too, see `probe`
+        let content_type = request.headers.iter()
             .find(|header| header.name.eq_ignore_ascii_case("content-type"))
             .and_then(|header| str::from_utf8(header.value).ok())
-            .ok_or(Error::NoContentType)?;
+            .and_then(|content_type| match content_type {
+                "application/ogg" | "audio/ogg" => Some(ContentType::Ogg),
+                "audio/aac" | "audio/aacp" | "audio/x-aac" => Some(ContentType::Aac),
+                "audio/mpeg" | "audio/mp3" => Some(ContentType::Mp3),
+                _ => None,
+            });
 
-        let content_type = match content_type_hdr {
-            "application/ogg" | "audio/ogg" => Some(ContentType::Ogg),
-            _ => None,
-        };
+        let password = request.headers.iter()
+            .find(|header| header.name.eq_ignore_ascii_case("authorization"))
+            .and_then(|header| str::from_utf8(header.value).ok())
+            .and_then(basic_auth_password);
 
         Ok(RequestInfo {
             path: request.path.ok_or(Error::NoPath)?.to_owned(),
             content_type,
+            password,
             stream_data: stream_data.to_vec(),
         })
     }
 }
 
+fn basic_auth_password(authorization: &str) -> Option<String> {
+    let mut parts = authorization.trim().splitn(2, ' ');
+
+    if !parts.next()?.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+
+    let credentials = decode_base64(parts.next()?.trim())?;
+    let credentials = String::from_utf8(credentials).ok()?;
+    let (_user, password) = credentials.split_at(credentials.find(':')?);
+
+    Some(password[1..].to_owned())
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+
+    for chunk in encoded.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+
+        let mut bits = 0u32;
+
+        for (i, c) in chunk.iter().enumerate() {
+            bits |= value(*c)? << (18 - 6 * i);
+        }
+
+        // every character past the first carries another byte, less the
+        // bits still to come
+        for i in 0..(chunk.len() - 1) {
+            decoded.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+// credentials for basic auth when connecting out to an Icecast server
+pub fn basic_auth(user: &str, password: &str) -> String {
+    format!("Basic {}", encode_base64(format!("{}:{}", user, password).as_bytes()))
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity((data.len() + 2) / 3 * 4);
+
+    for chunk in data.chunks(3) {
+        let mut bits = 0u32;
+
+        for (i, byte) in chunk.iter().enumerate() {
+            bits |= (*byte as u32) << (16 - 8 * i);
+        }
+
+        // a character per 6 bits of input, padded out to 4
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    pub content_type: ContentType,
+    pub codec: &'static str,
+    // bits per second, if known
+    pub bitrate: Option<u32>,
+}
+
+/// Detects the format of a stream from the first bytes of its payload.
+/// Returns `None` if the format is not recognised, or if more data is needed.
+pub fn probe(data: &[u8]) -> Option<Probe> {
+    if data.starts_with(b"OggS") {
+        return probe_ogg(data);
+    }
+
+    // MPEG audio and ADTS share a sync word, skip to the first one that
+    // begins a plausible frame header
+    (0..data.len()).find_map(|offset| {
+        let data = &data[offset..];
+
+        if data.len() < 7 || data[0] != 0xff || (data[1] & 0xe0) != 0xe0 {
+            return None;
+        }
+
+        // layer bits are always zero for ADTS
+        if (data[1] & 0xf6) == 0xf0 {
+            probe_adts(data)
+        } else {
+            probe_mpeg_audio(data)
+        }
+    })
+}
+
+fn probe_ogg(data: &[u8]) -> Option<Probe> {
+    // page header is 27 bytes followed by the segment table
+    let segments = *data.get(26)? as usize;
+    let packet = data.get((27 + segments)..)?;
+
+    if packet.starts_with(b"\x01vorbis") {
+        // nominal bitrate follows version, channels, sample rate and
+        // maximum bitrate in the identification header
+        let nominal = packet.get(20..24)?;
+        let bitrate = i32::from_le_bytes([nominal[0], nominal[1], nominal[2], nominal[3]]);
+
+        Some(Probe {
+            content_type: ContentType::Ogg,
+            codec: "Vorbis",
+            bitrate: if bitrate > 0 { Some(bitrate as u32) } else { None },
+        })
+    } else if packet.starts_with(b"OpusHead") {
+        Some(Probe { content_type: ContentType::Ogg, codec: "Opus", bitrate: None })
+    } else if packet.len() >= 8 {
+        Some(Probe { content_type: ContentType::Ogg, codec: "Ogg", bitrate: None })
+    } else {
+        None
+    }
+}
+
+fn probe_adts(data: &[u8]) -> Option<Probe> {
+    const SAMPLE_RATES: [u32; 13] = [
+        96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+    ];
+
+    let sample_rate = *SAMPLE_RATES.get(((data[2] >> 2) & 0x0f) as usize)?;
+
+    let frame_len = ((data[3] as usize & 0x03) << 11)
+        | ((data[4] as usize) << 3)
+        | ((data[5] as usize) >> 5);
+
+    if frame_len < 7 {
+        return None;
+    }
+
+    // a single sync word is easily found by chance in other formats, so
+    // require the next frame to follow on if we have it
+    if let Some(next) = data.get(frame_len..(frame_len + 2)) {
+        if next[0] != 0xff || (next[1] & 0xf6) != 0xf0 {
+            return None;
+        }
+    }
+
+    // each frame carries 1024 samples per channel
+    let bitrate = frame_len as u32 * 8 * sample_rate / 1024;
+
+    Some(Probe { content_type: ContentType::Aac, codec: "AAC", bitrate: Some(bitrate) })
+}
+
+fn probe_mpeg_audio(data: &[u8]) -> Option<Probe> {
+    const MPEG1_LAYER1: [u32; 15] = [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448];
+    const MPEG1_LAYER2: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384];
+    const MPEG1_LAYER3: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const MPEG2_LAYER1: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256];
+    const MPEG2_LAYER23: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+    let version = (data[1] >> 3) & 0x03;
+    let layer = (data[1] >> 1) & 0x03;
+    let bitrate_index = (data[2] >> 4) as usize;
+    let sample_rate_index = (data[2] >> 2) & 0x03;
+
+    // reserved values, free format bitrate is not supported either
+    if version == 1 || layer == 0 || bitrate_index == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+        return None;
+    }
+
+    let mpeg1 = version == 3;
+
+    let table = match (mpeg1, layer) {
+        (true, 3) => &MPEG1_LAYER1,
+        (true, 2) => &MPEG1_LAYER2,
+        (true, _) => &MPEG1_LAYER3,
+        (false, 3) => &MPEG2_LAYER1,
+        (false, _) => &MPEG2_LAYER23,
+    };
+
+    let codec = match layer {
+        3 => "MP1",
+        2 => "MP2",
+        _ => "MP3",
+    };
+
+    Some(Probe {
+        content_type: ContentType::Mp3,
+        codec,
+        bitrate: Some(table[bitrate_index] * 1000),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_basic_auth_password() {
+        // source:hackme
+        assert_eq!(Some("hackme".to_owned()), basic_auth_password("Basic c291cmNlOmhhY2ttZQ=="));
+        assert_eq!(None, basic_auth_password("Bearer c291cmNlOmhhY2ttZQ=="));
+        assert_eq!(None, basic_auth_password("Basic not*base64"));
+    }
+
+    #[test]
+    fn encodes_basic_auth() {
+        assert_eq!("Basic c291cmNlOmhhY2ttZQ==", basic_auth("source", "hackme"));
+        assert_eq!(Some("hackme!".to_owned()), basic_auth_password(&basic_auth("source", "hackme!")));
+    }
+
+    #[test]
+    fn probes_mp3_frame_header() {
+        // MPEG 1 layer 3, 128 kbps, 44.1 kHz
+        let probe = probe(&[0xff, 0xfb, 0x90, 0x64, 0x00, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(ContentType::Mp3, probe.content_type);
+        assert_eq!(Some(128_000), probe.bitrate);
+    }
+
+    #[test]
+    fn probes_adts_frame_header() {
+        // AAC LC, 44.1 kHz, stereo, 371 byte frame
+        let header = [0xff, 0xf1, 0x50, 0x80, 0x2e, 0x7f, 0xfc];
+        let probe = probe(&header).unwrap();
+        assert_eq!(ContentType::Aac, probe.content_type);
+        assert_eq!(Some(371 * 8 * 44100 / 1024), probe.bitrate);
+    }
+}
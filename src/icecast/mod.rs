@@ -1,3 +1,4 @@
+pub mod client;
 pub mod http;
 
 use std::fmt::Debug;
@@ -5,19 +6,25 @@ use std::io::{self, Read};
 use std::thread;
 
 use derive_more::From;
-use tokio::io::AsyncWriteExt;
+use tokio::io::AsyncReadExt;
 
-use mixlab_codec::ogg::{self, OggStream};
+use mixlab_codec::ffmpeg::{AudioDecodeStream, OpenAudioError};
+use mixlab_codec::ogg;
 use mixlab_codec::{AudioStream, StreamRead, StreamError};
 use mixlab_util::time::{MediaTime, MediaDuration};
 
-use crate::engine::SAMPLE_RATE;
-use crate::listen::PeekTcpStream;
-use crate::source::{Registry, ListenError, SourceRecv, SourceSend};
+use crate::engine::{Sample, CHANNELS, SAMPLE_RATE};
+use crate::listen::{write_all_timeout, PeekTcpStream};
+use crate::resample::Resampler;
+use crate::source::{ConnectError, Registry, SourceRecv, SourceSend};
 use crate::throttle::AudioThrottle;
 use crate::util::SyncRead;
 
-use self::http::ContentType;
+use self::http::{ContentType, Probe};
+
+// how much of the stream payload to read looking for a recognisable format
+// before giving up and trusting the content type header
+const PROBE_LEN: usize = 16384;
 
 lazy_static::lazy_static! {
     static ref MOUNTPOINTS: Registry = Registry::new();
@@ -29,26 +36,58 @@ pub async fn accept(mut stream: PeekTcpStream) {
         Err(_) => { return; }
     };
 
-    // any partial stream data which we might have caught in the http::parse above
-    let stream_data = req.stream_data;
-
-    let content_type = if let Some(ty) = req.content_type {
-        ty
-    } else {
-        // unknown content type
-        return;
-    };
-
-    let send = match MOUNTPOINTS.connect(&req.path) {
+    // sources with the wrong password are turned away before any of their
+    // stream is read
+    let mut send = match MOUNTPOINTS.connect(&req.path, req.password.as_deref()) {
         Ok(send) => send,
+        Err(ConnectError::Unauthorized) => {
+            eprintln!("icecast: rejected source for {:?}, incorrect password", req.path);
+            let _ = write_all_timeout(&mut stream,
+                b"HTTP/1.0 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"mixlab\"\r\n\r\n").await;
+            return;
+        }
         Err(e) => {
             eprintln!("could not connect to icecast mountpoint: {:?}", e);
             return;
         }
     };
 
-    stream.write_all(b"HTTP/1.0 200 OK\r\n\r\n").await
-        .expect("stream.write_all");
+    // any partial stream data which we might have caught in the http::parse above
+    let mut stream_data = req.stream_data;
+
+    let probe = match probe_stream(&mut stream, &mut stream_data).await {
+        Ok(probe) => probe,
+        Err(e) => {
+            eprintln!("icecast: error reading stream: {:?}", e);
+            return;
+        }
+    };
+
+    if let (Some(probe), Some(content_type)) = (&probe, req.content_type) {
+        if probe.content_type != content_type {
+            eprintln!("icecast: content type header says {:?} but stream looks like {:?}, trusting stream",
+                content_type, probe.content_type);
+        }
+    }
+
+    let content_type = probe.as_ref().map(|probe| probe.content_type).or(req.content_type);
+
+    // report what we found even if we can't decode it, so the user can see
+    // why a source isn't producing any audio
+    send.set_info(|info| {
+        info.audio_codec = Some(match (&probe, content_type) {
+            (Some(probe), _) => probe.codec.to_owned(),
+            (None, Some(content_type)) => format!("{:?}", content_type),
+            (None, None) => "Unrecognised format".to_owned(),
+        });
+
+        info.audio_bitrate = probe.as_ref().and_then(|probe| probe.bitrate);
+    });
+
+    if let Err(e) = write_all_timeout(&mut stream, b"HTTP/1.0 200 OK\r\n\r\n").await {
+        eprintln!("icecast: could not write response: {:?}", e);
+        return;
+    }
 
     thread::spawn(move || {
         let stream = stream_data.chain(SyncRead(stream));
@@ -62,24 +101,71 @@ pub async fn accept(mut stream: PeekTcpStream) {
     });
 }
 
-pub fn listen(mountpoint: &str) -> Result<SourceRecv, ListenError> {
-    MOUNTPOINTS.listen(mountpoint)
+async fn probe_stream(stream: &mut PeekTcpStream, stream_data: &mut Vec<u8>) -> Result<Option<Probe>, io::Error> {
+    let mut buff = [0u8; 4096];
+
+    loop {
+        if let Some(probe) = http::probe(stream_data) {
+            return Ok(Some(probe));
+        }
+
+        if stream_data.len() >= PROBE_LEN {
+            return Ok(None);
+        }
+
+        let bytes = stream.read(&mut buff).await?;
+
+        if bytes == 0 {
+            return Ok(None);
+        }
+
+        stream_data.extend_from_slice(&buff[0..bytes]);
+    }
+}
+
+pub fn listen(mountpoint: &str, password: Option<String>) -> SourceRecv {
+    let recv = MOUNTPOINTS.listen(mountpoint);
+    recv.set_stream_key(password);
+    recv
 }
 
 #[derive(From, Debug)]
 enum DecodeThreadError {
     ListenerDisconnected,
-    Ogg(ogg::VorbisError),
+    Ogg(ogg::OggError),
+    Decode(OpenAudioError),
     Io(io::Error),
 }
 
-fn run_decode_thread(mut send: SourceSend, stream: impl io::Read, content_type: ContentType)
+fn resample(resampler: &mut Resampler, pcm: &[i16]) -> Vec<i16> {
+    // i16::min_value is a greater absolute distance away from 0 than
+    // max_value, so divide by that to stay within -1.0 to 1.0
+    let input = pcm.iter()
+        .map(|sample| *sample as Sample / -(i16::min_value() as Sample))
+        .collect::<Vec<_>>();
+
+    let mut output = Vec::new();
+    resampler.process(&input, &mut output);
+
+    output.into_iter()
+        .map(|sample| (sample.max(-1.0).min(1.0) * i16::max_value() as Sample) as i16)
+        .collect()
+}
+
+fn run_decode_thread(mut send: SourceSend, mut stream: impl io::Read + 'static, content_type: Option<ContentType>)
     -> Result<(), DecodeThreadError>
 {
     let mut audio = match content_type {
-        ContentType::Ogg => {
-            let ogg = OggStream::new(stream)?;
-            Box::new(ogg) as Box<dyn AudioStream>
+        Some(ContentType::Ogg) => {
+            ogg::open(stream)?
+        }
+        Some(ContentType::Aac) | Some(ContentType::Mp3) => {
+            let decode = AudioDecodeStream::open(stream)?;
+            Box::new(decode) as Box<dyn AudioStream>
+        }
+        None => {
+            eprintln!("icecast: no decoder for {:?} stream, discarding", content_type);
+            return discard(&send, &mut stream);
         }
     };
 
@@ -91,10 +177,12 @@ fn run_decode_thread(mut send: SourceSend, stream: impl io::Read, content_type:
         return Ok(());
     }
 
-    if audio.sample_rate() != SAMPLE_RATE {
-        // not much we can do for now. TODO implement resampling
-        return Ok(());
-    }
+    // MP3 and AAC sources are often 48kHz
+    let mut resampler = if audio.sample_rate() == SAMPLE_RATE {
+        None
+    } else {
+        Some(Resampler::new(audio.sample_rate(), SAMPLE_RATE, CHANNELS))
+    };
 
     let mut timestamp = MediaTime::zero();
     let mut throttle = AudioThrottle::new();
@@ -122,10 +210,19 @@ fn run_decode_thread(mut send: SourceSend, stream: impl io::Read, content_type:
                     }
                 }
 
+                let samples = match &mut resampler {
+                    Some(resampler) => resample(resampler, &samples),
+                    None => samples,
+                };
+
+                // resampled audio is shorter at first, as the resampler
+                // looks ahead
+                let sample_count = samples.len() / CHANNELS;
+
                 send.write_audio(timestamp, samples)
                     .map_err(|()| DecodeThreadError::ListenerDisconnected)?;
 
-                timestamp += MediaDuration::new(sample_count as i64, audio.sample_rate() as i64);
+                timestamp += MediaDuration::new(sample_count as i64, SAMPLE_RATE as i64);
                 throttle.send_samples(sample_count);
             }
             Ok(StreamRead::Metadata(_)) => {
@@ -144,3 +241,17 @@ fn run_decode_thread(mut send: SourceSend, stream: impl io::Read, content_type:
 
     Ok(())
 }
+
+// keeps an undecodable source connected so its format stays visible in the
+// StreamInput indication, rather than the client reconnecting in a loop
+fn discard(send: &SourceSend, stream: &mut impl io::Read) -> Result<(), DecodeThreadError> {
+    let mut buff = [0u8; 4096];
+
+    while send.connected() {
+        if stream.read(&mut buff)? == 0 {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+pub mod ambisonics;
+pub mod convolve;
+pub mod db;
+pub mod diagram;
+pub mod dsp;
+pub mod echo_cancel;
+pub mod engine;
+pub mod feedback;
+pub mod guest;
+pub mod icecast;
+pub mod listen;
+pub mod meter;
+pub mod midi;
+pub mod mpegts;
+pub mod output_file;
+pub mod persist;
+pub mod pitch_shift;
+pub mod project;
+pub mod render;
+pub mod reorder;
+pub mod replay;
+pub mod resample;
+pub mod rtmp;
+pub mod server;
+pub mod source;
+pub mod throttle;
+pub mod util;
+pub mod video;
+pub mod viewer;
+
+#[cfg(test)]
+mod test_support;
+
+#[macro_use]
+pub mod module;
@@ -2,11 +2,23 @@ use std::cmp;
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::task::{Poll, Context};
+use std::time::Duration;
 
 use futures::stream::{self, StreamExt};
-use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::mpsc::error::TrySendError;
+
+pub mod srt;
+
+// a client that hasn't accepted any of a write for this long is considered
+// stalled and is disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// maximum number of writes waiting on a slow client before it is considered
+// stalled
+const WRITE_QUEUE_LEN: usize = 64;
 
 pub struct Listener {
     pub local_addr: SocketAddr,
@@ -161,3 +173,50 @@ impl AsyncWrite for PeekTcpStream {
         unsafe { self.map_unchecked_mut(|stream| &mut stream.conn) }.poll_shutdown(cx)
     }
 }
+
+pub async fn write_all_timeout<W: AsyncWrite + Unpin>(stream: &mut W, bytes: &[u8]) -> Result<(), io::Error> {
+    match tokio::time::timeout(WRITE_TIMEOUT, stream.write_all(bytes)).await {
+        Ok(result) => result,
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "write timed out")),
+    }
+}
+
+#[derive(Debug)]
+pub enum WriteQueueError {
+    // the client has fallen too far behind reading what we send it
+    Full,
+    // a previous write failed or timed out
+    Closed,
+}
+
+/// Writes to a connection from a task of its own, so that threads producing
+/// writes for a slow client never block on it.
+#[derive(Debug)]
+pub struct WriteQueue {
+    tx: Sender<Vec<u8>>,
+}
+
+impl WriteQueue {
+    /// Must be called from within the tokio runtime.
+    pub fn spawn(mut writer: impl AsyncWrite + Unpin + Send + 'static) -> Self {
+        let (tx, mut rx) = mpsc::channel::<Vec<u8>>(WRITE_QUEUE_LEN);
+
+        tokio::spawn(async move {
+            while let Some(bytes) = rx.recv().await {
+                if let Err(e) = write_all_timeout(&mut writer, &bytes).await {
+                    eprintln!("listen: write to client failed: {:?}", e);
+                    break;
+                }
+            }
+        });
+
+        WriteQueue { tx }
+    }
+
+    pub fn write(&mut self, bytes: Vec<u8>) -> Result<(), WriteQueueError> {
+        self.tx.try_send(bytes).map_err(|e| match e {
+            TrySendError::Full(_) => WriteQueueError::Full,
+            TrySendError::Closed(_) => WriteQueueError::Closed,
+        })
+    }
+}
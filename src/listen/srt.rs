@@ -0,0 +1,1162 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use derive_more::From;
+use uuid::Uuid;
+
+use mixlab_protocol::StreamLinkStats;
+
+use crate::reorder::ReorderBuffer;
+
+// SRT version advertised in handshake extensions, 1.4.1
+const SRT_VERSION: u32 = 0x0001_0401;
+
+// extension field of a version 5 induction response, telling the caller we
+// speak HSv5
+const HS_MAGIC: u16 = 0x4a17;
+
+// extension field of a version 4 induction request, the UDT socket type
+const UDT_DGRAM: u16 = 2;
+
+const HS_INDUCTION: u32 = 1;
+const HS_CONCLUSION: u32 = 0xffff_ffff;
+
+// handshake types from here on reject the connection, offset by a reason
+const HS_REJECT_BASE: u32 = 1000;
+const REJECT_PEER: u32 = 2;
+const REJECT_BAD_SECRET: u32 = 10;
+
+// extension field flags and extension block types
+const HS_EXT_HSREQ: u16 = 0x1;
+const HS_EXT_CONFIG: u16 = 0x4;
+const EXT_HSREQ: u16 = 1;
+const EXT_HSRSP: u16 = 2;
+const EXT_SID: u16 = 5;
+
+// timestamp based packet delivery both ways, dropping what's too late,
+// periodic NAKs and retransmission flags. no encryption
+const SRT_FLAGS: u32 = 0x01 | 0x02 | 0x08 | 0x10 | 0x20;
+
+const CTRL_HANDSHAKE: u16 = 0;
+const CTRL_KEEPALIVE: u16 = 1;
+const CTRL_ACK: u16 = 2;
+const CTRL_NAK: u16 = 3;
+const CTRL_SHUTDOWN: u16 = 5;
+const CTRL_ACKACK: u16 = 6;
+
+const HEADER_LEN: usize = 16;
+const HANDSHAKE_LEN: usize = 48;
+const MTU: u32 = 1500;
+const FLOW_WINDOW: u32 = 8192;
+
+// data packets carry seven transport stream packets, as srt-live-transmit
+// sends them
+pub const PAYLOAD_LEN: usize = 1316;
+
+// sequence and message numbers are 31 and 26 bits
+const SEQ_MASK: u32 = 0x7fff_ffff;
+const MSG_MASK: u32 = 0x03ff_ffff;
+
+// data packet flags: a whole message in one packet, and a retransmission
+const PACKET_SOLO: u32 = 0b11 << 30;
+const PACKET_RETRANSMIT: u32 = 1 << 26;
+
+// how long packets are waited on for retransmission before being given up
+// on, unless the peer asks for longer in its handshake. SRT's default
+pub const DEFAULT_LATENCY: Duration = Duration::from_millis(120);
+
+const ACK_INTERVAL: Duration = Duration::from_millis(10);
+const NAK_INTERVAL: Duration = Duration::from_millis(40);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+// a connected peer silent for this long has gone away
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+// handshake packets are sent again at this interval until answered
+const HANDSHAKE_RETRY: Duration = Duration::from_millis(250);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// same as the UDP link, so the receive thread notices an unused listener
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// a jump in sequence number larger than this is treated as the sender
+// restarting rather than as loss
+const MAX_GAP: u64 = 8192;
+
+#[derive(Debug, From)]
+pub enum ConnectError {
+    Io(io::Error),
+    BadAddress,
+    TimedOut,
+    // the listener turned us away with this reason
+    Rejected(u32),
+}
+
+/// Listens for SRT callers on a UDP address, receiving one at a time. Reads
+/// give the payload of data packets in order, with lost packets recovered
+/// by retransmission where possible. Callers are turned away unless
+/// `authorize` accepts the stream id they send.
+pub struct SrtReceiver {
+    socket: UdpSocket,
+    local_id: u32,
+    authorize: Box<dyn Fn(Option<&str>) -> bool + Send>,
+    // random secret for syn cookies, so that only callers which received
+    // our induction response can conclude a handshake
+    secret: u64,
+    peer: Option<Peer>,
+    buff: Vec<u8>,
+    ready: VecDeque<Vec<u8>>,
+    current: Vec<u8>,
+    pos: usize,
+    last_activity: Instant,
+    stats: StreamLinkStats,
+    shared_stats: Arc<Mutex<Option<StreamLinkStats>>>,
+}
+
+// the caller connected to a receiver
+struct Peer {
+    addr: SocketAddr,
+    socket_id: u32,
+    start: Instant,
+    latency: Duration,
+    recv: ReorderBuffer,
+    ack_number: u32,
+    last_ack: Instant,
+    last_nak: Instant,
+    last_keepalive: Instant,
+    last_heard: Instant,
+}
+
+impl SrtReceiver {
+    pub fn bind(addr: SocketAddr, authorize: Box<dyn Fn(Option<&str>) -> bool + Send>) -> io::Result<SrtReceiver> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        Ok(SrtReceiver {
+            socket,
+            local_id: random_u32() & SEQ_MASK,
+            authorize,
+            secret: Uuid::new_v4().as_u128() as u64,
+            peer: None,
+            buff: vec![0u8; 65536],
+            ready: VecDeque::new(),
+            current: Vec::new(),
+            pos: 0,
+            last_activity: Instant::now(),
+            stats: StreamLinkStats::default(),
+            shared_stats: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Loss and recovery stats for the connected caller, shared as with the
+    /// UDP link.
+    pub fn stats(&self) -> Arc<Mutex<Option<StreamLinkStats>>> {
+        self.shared_stats.clone()
+    }
+
+    fn poll(&mut self) -> io::Result<()> {
+        match self.socket.recv_from(&mut self.buff) {
+            Ok((len, from)) => {
+                let packet = Packet::parse(&self.buff[0..len]);
+
+                if let Some(packet) = packet {
+                    self.receive(packet, from)?;
+                }
+            }
+            Err(e) if is_timeout(&e) => {}
+            Err(e) => { return Err(e); }
+        }
+
+        let now = Instant::now();
+
+        let peer = match &mut self.peer {
+            Some(peer) => peer,
+            None => return Ok(()),
+        };
+
+        if now.duration_since(peer.last_heard) >= PEER_TIMEOUT {
+            eprintln!("srt: caller {} timed out", peer.addr);
+            self.peer = None;
+            return Ok(());
+        }
+
+        peer.recv.release(now, peer.latency, &mut self.ready, &mut self.stats);
+
+        if now.duration_since(peer.last_ack) >= ACK_INTERVAL {
+            peer.last_ack = now;
+            peer.ack_number = peer.ack_number.wrapping_add(1);
+
+            let ack = ack_seq(&peer.recv);
+            let buffered = peer.recv.len() as u32;
+
+            let mut cif = Vec::with_capacity(28);
+            cif.extend_from_slice(&ack.to_be_bytes());
+            // rtt and its variance in microseconds, which we don't measure,
+            // then available buffer in packets and rates we don't estimate
+            cif.extend_from_slice(&100_000u32.to_be_bytes());
+            cif.extend_from_slice(&50_000u32.to_be_bytes());
+            cif.extend_from_slice(&FLOW_WINDOW.saturating_sub(buffered).to_be_bytes());
+            cif.extend_from_slice(&0u32.to_be_bytes());
+            cif.extend_from_slice(&0u32.to_be_bytes());
+            cif.extend_from_slice(&0u32.to_be_bytes());
+
+            let packet = peer.control(CTRL_ACK, peer.ack_number, cif);
+            send(&self.socket, peer.addr, &packet);
+        }
+
+        if now.duration_since(peer.last_nak) >= NAK_INTERVAL {
+            peer.last_nak = now;
+
+            let missing = wrapped(&peer.recv, peer.recv.missing());
+
+            if !missing.is_empty() {
+                let packet = peer.control(CTRL_NAK, 0, loss_list(&missing));
+                send(&self.socket, peer.addr, &packet);
+            }
+        }
+
+        if now.duration_since(peer.last_keepalive) >= KEEPALIVE_INTERVAL {
+            peer.last_keepalive = now;
+            let packet = peer.control(CTRL_KEEPALIVE, 0, Vec::new());
+            send(&self.socket, peer.addr, &packet);
+        }
+
+        *self.shared_stats.lock().expect("link stats lock") = Some(self.stats.clone());
+
+        Ok(())
+    }
+
+    fn receive(&mut self, packet: Packet, from: SocketAddr) -> io::Result<()> {
+        let is_peer = self.peer.as_ref().map(|peer| peer.addr == from).unwrap_or(false);
+
+        match packet {
+            Packet::Control(control) if control.kind == CTRL_HANDSHAKE => {
+                if let Some(handshake) = Handshake::parse(&control.cif) {
+                    self.handshake(handshake, from)?;
+                }
+            }
+            Packet::Control(control) if is_peer && control.kind == CTRL_SHUTDOWN => {
+                eprintln!("srt: caller {} disconnected", from);
+                self.peer = None;
+            }
+            Packet::Control(_) if is_peer => {
+                // keepalives and ACKACKs only tell us the caller is alive
+                self.peer.as_mut().unwrap().last_heard = Instant::now();
+            }
+            Packet::Data(data) if is_peer => {
+                let peer = self.peer.as_mut().unwrap();
+                let now = Instant::now();
+
+                peer.last_heard = now;
+                self.last_activity = now;
+                self.stats.received += 1;
+
+                let seq = peer.recv.extend(data.seq as u64);
+                let missing = peer.recv.insert(seq, data.payload, now, &mut self.stats, true);
+                let missing = wrapped(&peer.recv, missing.into_iter());
+
+                if !missing.is_empty() {
+                    let packet = peer.control(CTRL_NAK, 0, loss_list(&missing));
+                    send(&self.socket, peer.addr, &packet);
+                }
+            }
+            // packets from anyone we're not connected to
+            Packet::Control(_) | Packet::Data(_) => {}
+        }
+
+        Ok(())
+    }
+
+    fn handshake(&mut self, request: Handshake, from: SocketAddr) -> io::Result<()> {
+        let cookie = self.cookie(from);
+
+        let response = match request.kind {
+            HS_INDUCTION => Handshake {
+                version: 5,
+                encryption: 0,
+                extension: HS_MAGIC,
+                initial_seq: request.initial_seq,
+                mtu: MTU,
+                flow_window: FLOW_WINDOW,
+                kind: HS_INDUCTION,
+                socket_id: self.local_id,
+                cookie,
+                peer_ip: peer_ip(from.ip()),
+                extensions: Vec::new(),
+            },
+            HS_CONCLUSION if request.cookie == cookie => {
+                let reject = if request.version != 5 {
+                    Some(REJECT_PEER)
+                } else if !(self.authorize)(request.stream_id().as_deref()) {
+                    Some(REJECT_BAD_SECRET)
+                } else if self.peer.as_ref().map(|peer| peer.addr != from).unwrap_or(false) {
+                    // one caller at a time
+                    Some(REJECT_PEER)
+                } else {
+                    None
+                };
+
+                let kind = match reject {
+                    Some(reason) => HS_REJECT_BASE + reason,
+                    None => HS_CONCLUSION,
+                };
+
+                let peer_delay = request.hs_req()
+                    .map(|req| Duration::from_millis(req.send_delay.into()))
+                    .unwrap_or(DEFAULT_LATENCY);
+
+                let latency = std::cmp::max(peer_delay, DEFAULT_LATENCY);
+
+                if reject.is_none() && self.peer.is_none() {
+                    eprintln!("srt: caller {} connected with {}ms latency", from, latency.as_millis());
+
+                    let now = Instant::now();
+
+                    self.peer = Some(Peer {
+                        addr: from,
+                        socket_id: request.socket_id,
+                        start: now,
+                        latency,
+                        recv: ReorderBuffer::starting_at("srt", 31, MAX_GAP, request.initial_seq as u64),
+                        ack_number: 0,
+                        last_ack: now,
+                        last_nak: now,
+                        last_keepalive: now,
+                        last_heard: now,
+                    });
+
+                    self.stats = StreamLinkStats::default();
+                }
+
+                let delay = latency.as_millis().try_into().unwrap_or(u16::max_value());
+
+                Handshake {
+                    version: 5,
+                    encryption: 0,
+                    extension: HS_EXT_HSREQ,
+                    initial_seq: request.initial_seq,
+                    mtu: MTU,
+                    flow_window: FLOW_WINDOW,
+                    kind,
+                    socket_id: self.local_id,
+                    cookie,
+                    peer_ip: peer_ip(from.ip()),
+                    extensions: vec![(EXT_HSRSP, hs_ext(delay, delay))],
+                }
+            }
+            // stale cookie or a handshake type we don't answer, the caller
+            // will try again
+            _ => return Ok(()),
+        };
+
+        let packet = Packet::Control(ControlPacket {
+            kind: CTRL_HANDSHAKE,
+            info: 0,
+            timestamp: 0,
+            dest: request.socket_id,
+            cif: response.to_bytes(),
+        });
+
+        send(&self.socket, from, &packet);
+        Ok(())
+    }
+
+    fn cookie(&self, addr: SocketAddr) -> u32 {
+        let mut hasher = DefaultHasher::new();
+        self.secret.hash(&mut hasher);
+        addr.hash(&mut hasher);
+        hasher.finish() as u32
+    }
+}
+
+impl io::Read for SrtReceiver {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.pos < self.current.len() {
+                let len = std::cmp::min(out.len(), self.current.len() - self.pos);
+                out[0..len].copy_from_slice(&self.current[self.pos..(self.pos + len)]);
+                self.pos += len;
+                return Ok(len);
+            }
+
+            if let Some(payload) = self.ready.pop_front() {
+                self.current = payload;
+                self.pos = 0;
+                continue;
+            }
+
+            if self.last_activity.elapsed() >= IDLE_TIMEOUT {
+                self.last_activity = Instant::now();
+                return Ok(0);
+            }
+
+            self.poll()?;
+        }
+    }
+}
+
+impl Peer {
+    fn control(&self, kind: u16, info: u32, cif: Vec<u8>) -> Packet {
+        Packet::Control(ControlPacket {
+            kind,
+            info,
+            timestamp: timestamp(self.start),
+            dest: self.socket_id,
+            cif,
+        })
+    }
+}
+
+/// The sending end of an SRT connection made as a caller. Sent packets are
+/// kept for retransmission until acknowledged or too late to be of use.
+pub struct SrtSender {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    peer_id: u32,
+    start: Instant,
+    latency: Duration,
+    next_seq: u32,
+    next_msg: u32,
+    // sent and not yet acknowledged, by sequence number
+    unacked: VecDeque<SentPacket>,
+    last_heard: Instant,
+    buff: Vec<u8>,
+}
+
+struct SentPacket {
+    seq: u32,
+    sent: Instant,
+    packet: DataPacket,
+}
+
+impl SrtSender {
+    /// Connects to an SRT listener at `addr` of the form `host:port`,
+    /// optionally prefixed with `srt://`. Blocks until connected.
+    pub fn connect(addr: &str, stream_id: &str, latency: Duration) -> Result<SrtSender, ConnectError> {
+        use std::net::ToSocketAddrs;
+
+        let peer = addr.trim_start_matches("srt://")
+            .split('?').next().unwrap_or("")
+            .to_socket_addrs()
+            .map_err(|_| ConnectError::BadAddress)?
+            .next()
+            .ok_or(ConnectError::BadAddress)?;
+
+        let local: SocketAddr = match peer {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+
+        let socket = UdpSocket::bind(local)?;
+        socket.set_read_timeout(Some(HANDSHAKE_RETRY))?;
+
+        let local_id = random_u32() & SEQ_MASK;
+        let initial_seq = random_u32() & SEQ_MASK;
+        let delay = latency.as_millis().try_into().unwrap_or(u16::max_value());
+
+        let mut request = Handshake {
+            version: 4,
+            encryption: 0,
+            extension: UDT_DGRAM,
+            initial_seq,
+            mtu: MTU,
+            flow_window: FLOW_WINDOW,
+            kind: HS_INDUCTION,
+            socket_id: local_id,
+            cookie: 0,
+            peer_ip: peer_ip(peer.ip()),
+            extensions: Vec::new(),
+        };
+
+        let mut buff = vec![0u8; 65536];
+        let started = Instant::now();
+
+        let response = loop {
+            if started.elapsed() >= HANDSHAKE_TIMEOUT {
+                return Err(ConnectError::TimedOut);
+            }
+
+            send(&socket, peer, &Packet::Control(ControlPacket {
+                kind: CTRL_HANDSHAKE,
+                info: 0,
+                timestamp: 0,
+                dest: 0,
+                cif: request.to_bytes(),
+            }));
+
+            let len = match socket.recv_from(&mut buff) {
+                Ok((len, from)) if from == peer => len,
+                Ok(_) => continue,
+                Err(e) if is_timeout(&e) => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            let response = match Packet::parse(&buff[0..len]) {
+                Some(Packet::Control(control)) if control.kind == CTRL_HANDSHAKE => {
+                    match Handshake::parse(&control.cif) {
+                        Some(response) => response,
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+
+            match response.kind {
+                HS_INDUCTION if request.kind == HS_INDUCTION => {
+                    // conclude with the cookie we were given
+                    let mut extensions = vec![(EXT_HSREQ, hs_ext(delay, delay))];
+                    let mut extension = HS_EXT_HSREQ;
+
+                    if !stream_id.is_empty() {
+                        extensions.push((EXT_SID, encode_stream_id(stream_id)));
+                        extension |= HS_EXT_CONFIG;
+                    }
+
+                    request = Handshake {
+                        version: 5,
+                        extension,
+                        kind: HS_CONCLUSION,
+                        cookie: response.cookie,
+                        extensions,
+                        ..request
+                    };
+                }
+                HS_CONCLUSION if request.kind == HS_CONCLUSION => {
+                    break response;
+                }
+                kind if kind >= HS_REJECT_BASE && kind < HS_REJECT_BASE + 1000 => {
+                    return Err(ConnectError::Rejected(kind - HS_REJECT_BASE));
+                }
+                _ => {}
+            }
+        };
+
+        let latency = response.hs_req()
+            .map(|rsp| Duration::from_millis(rsp.recv_delay.into()))
+            .map(|peer_delay| std::cmp::max(peer_delay, latency))
+            .unwrap_or(latency);
+
+        socket.set_nonblocking(true)?;
+
+        Ok(SrtSender {
+            socket,
+            peer,
+            peer_id: response.socket_id,
+            start: Instant::now(),
+            latency,
+            next_seq: initial_seq,
+            next_msg: 1,
+            unacked: VecDeque::new(),
+            last_heard: Instant::now(),
+            buff,
+        })
+    }
+
+    /// Sends one payload of at most `PAYLOAD_LEN` bytes, failing once the
+    /// listener has disconnected or stopped answering.
+    pub fn send(&mut self, payload: &[u8]) -> io::Result<()> {
+        self.poll()?;
+
+        let packet = DataPacket {
+            seq: self.next_seq,
+            flags: PACKET_SOLO | self.next_msg,
+            timestamp: timestamp(self.start),
+            dest: self.peer_id,
+            payload: payload.to_vec(),
+        };
+
+        self.next_seq = (self.next_seq + 1) & SEQ_MASK;
+        self.next_msg = (self.next_msg + 1) & MSG_MASK;
+
+        self.socket.send_to(&packet.to_bytes(), self.peer)?;
+
+        self.unacked.push_back(SentPacket {
+            seq: packet.seq,
+            sent: Instant::now(),
+            packet,
+        });
+
+        Ok(())
+    }
+
+    fn poll(&mut self) -> io::Result<()> {
+        loop {
+            let len = match self.socket.recv_from(&mut self.buff) {
+                Ok((len, from)) if from == self.peer => len,
+                Ok(_) => continue,
+                Err(e) if is_timeout(&e) => break,
+                Err(e) => return Err(e),
+            };
+
+            self.last_heard = Instant::now();
+
+            let control = match Packet::parse(&self.buff[0..len]) {
+                Some(Packet::Control(control)) => control,
+                _ => continue,
+            };
+
+            match control.kind {
+                CTRL_ACK if control.cif.len() >= 4 => {
+                    let ack = u32::from_be_bytes(control.cif[0..4].try_into().unwrap());
+
+                    while let Some(sent) = self.unacked.front() {
+                        if seq_distance(sent.seq, ack) <= 0 {
+                            break;
+                        }
+
+                        self.unacked.pop_front();
+                    }
+
+                    let ackack = self.control(CTRL_ACKACK, control.info, Vec::new());
+                    send(&self.socket, self.peer, &ackack);
+                }
+                CTRL_NAK => {
+                    for seq in parse_loss_list(&control.cif) {
+                        self.retransmit(seq);
+                    }
+                }
+                CTRL_SHUTDOWN => {
+                    return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "srt listener disconnected"));
+                }
+                _ => {}
+            }
+        }
+
+        // what the receiver hasn't got by now it will have given up on
+        let now = Instant::now();
+        let latency = self.latency;
+
+        while let Some(sent) = self.unacked.front() {
+            if now.duration_since(sent.sent) < latency * 2 {
+                break;
+            }
+
+            self.unacked.pop_front();
+        }
+
+        if self.last_heard.elapsed() >= PEER_TIMEOUT {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "srt listener stopped answering"));
+        }
+
+        Ok(())
+    }
+
+    fn retransmit(&mut self, seq: u32) {
+        let sent = self.unacked.iter()
+            .find(|sent| sent.seq == seq);
+
+        if let Some(sent) = sent {
+            let packet = DataPacket {
+                flags: sent.packet.flags | PACKET_RETRANSMIT,
+                payload: sent.packet.payload.clone(),
+                ..sent.packet
+            };
+
+            send(&self.socket, self.peer, &Packet::Data(packet));
+        }
+    }
+
+    fn control(&self, kind: u16, info: u32, cif: Vec<u8>) -> Packet {
+        Packet::Control(ControlPacket {
+            kind,
+            info,
+            timestamp: timestamp(self.start),
+            dest: self.peer_id,
+            cif,
+        })
+    }
+}
+
+impl Drop for SrtSender {
+    fn drop(&mut self) {
+        let shutdown = self.control(CTRL_SHUTDOWN, 0, vec![0; 4]);
+        send(&self.socket, self.peer, &shutdown);
+    }
+}
+
+// the sequence number acknowledged, which is the first not yet received
+fn ack_seq(recv: &ReorderBuffer) -> u32 {
+    let next = recv.missing().next()
+        .or_else(|| recv.highest().map(|highest| highest + 1))
+        .unwrap_or(0);
+
+    recv.wrap(next) as u32
+}
+
+fn wrapped(recv: &ReorderBuffer, seqs: impl Iterator<Item = u64>) -> Vec<u32> {
+    seqs.map(|seq| recv.wrap(seq) as u32).collect()
+}
+
+// signed distance from sequence number a to b, allowing for wrapping
+fn seq_distance(a: u32, b: u32) -> i64 {
+    let diff = (b.wrapping_sub(a) & SEQ_MASK) as i64;
+
+    if diff > (SEQ_MASK as i64) / 2 {
+        diff - (SEQ_MASK as i64 + 1)
+    } else {
+        diff
+    }
+}
+
+// compresses sorted sequence numbers into an SRT loss list, where a range
+// is its first number with the top bit set followed by its last
+fn loss_list(missing: &[u32]) -> Vec<u8> {
+    let mut list = Vec::new();
+    let mut iter = missing.iter().cloned().peekable();
+
+    while let Some(first) = iter.next() {
+        let mut last = first;
+
+        while iter.peek() == Some(&((last + 1) & SEQ_MASK)) {
+            last = iter.next().unwrap();
+        }
+
+        if first == last {
+            list.extend_from_slice(&first.to_be_bytes());
+        } else {
+            list.extend_from_slice(&(first | !SEQ_MASK).to_be_bytes());
+            list.extend_from_slice(&last.to_be_bytes());
+        }
+    }
+
+    list
+}
+
+fn parse_loss_list(cif: &[u8]) -> Vec<u32> {
+    let mut seqs = Vec::new();
+    let mut words = cif.chunks_exact(4)
+        .map(|word| u32::from_be_bytes(word.try_into().unwrap()));
+
+    while let Some(word) = words.next() {
+        if word & !SEQ_MASK == 0 {
+            seqs.push(word);
+            continue;
+        }
+
+        let first = word & SEQ_MASK;
+        let last = match words.next() {
+            Some(last) => last & SEQ_MASK,
+            None => break,
+        };
+
+        // a bogus range could have us retransmitting forever
+        let len = seq_distance(first, last);
+
+        if len < 0 || len as u32 > FLOW_WINDOW {
+            continue;
+        }
+
+        seqs.extend((0..=len as u32).map(|offset| (first + offset) & SEQ_MASK));
+    }
+
+    seqs
+}
+
+enum Packet {
+    Data(DataPacket),
+    Control(ControlPacket),
+}
+
+struct DataPacket {
+    seq: u32,
+    // position, ordering, encryption and retransmission flags, followed by
+    // the message number
+    flags: u32,
+    timestamp: u32,
+    dest: u32,
+    payload: Vec<u8>,
+}
+
+struct ControlPacket {
+    kind: u16,
+    info: u32,
+    timestamp: u32,
+    dest: u32,
+    cif: Vec<u8>,
+}
+
+impl Packet {
+    fn parse(bytes: &[u8]) -> Option<Packet> {
+        if bytes.len() < HEADER_LEN {
+            return None;
+        }
+
+        let word = |i: usize| u32::from_be_bytes(bytes[i * 4..(i + 1) * 4].try_into().unwrap());
+        let body = bytes[HEADER_LEN..].to_vec();
+
+        if word(0) & !SEQ_MASK == 0 {
+            Some(Packet::Data(DataPacket {
+                seq: word(0),
+                flags: word(1),
+                timestamp: word(2),
+                dest: word(3),
+                payload: body,
+            }))
+        } else {
+            Some(Packet::Control(ControlPacket {
+                kind: ((word(0) >> 16) & 0x7fff) as u16,
+                info: word(1),
+                timestamp: word(2),
+                dest: word(3),
+                cif: body,
+            }))
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Packet::Data(data) => data.to_bytes(),
+            Packet::Control(control) => {
+                let mut bytes = Vec::with_capacity(HEADER_LEN + control.cif.len());
+                bytes.extend_from_slice(&((1 << 31) | (control.kind as u32) << 16).to_be_bytes());
+                bytes.extend_from_slice(&control.info.to_be_bytes());
+                bytes.extend_from_slice(&control.timestamp.to_be_bytes());
+                bytes.extend_from_slice(&control.dest.to_be_bytes());
+                bytes.extend_from_slice(&control.cif);
+                bytes
+            }
+        }
+    }
+}
+
+impl DataPacket {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        bytes.extend_from_slice(&self.seq.to_be_bytes());
+        bytes.extend_from_slice(&self.flags.to_be_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes.extend_from_slice(&self.dest.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+}
+
+struct Handshake {
+    version: u32,
+    encryption: u16,
+    extension: u16,
+    initial_seq: u32,
+    mtu: u32,
+    flow_window: u32,
+    kind: u32,
+    socket_id: u32,
+    cookie: u32,
+    peer_ip: [u8; 16],
+    // extension blocks by type, each a whole number of 32 bit words
+    extensions: Vec<(u16, Vec<u8>)>,
+}
+
+// contents of an HSREQ or HSRSP extension block
+struct HsExt {
+    recv_delay: u16,
+    send_delay: u16,
+}
+
+impl Handshake {
+    fn parse(cif: &[u8]) -> Option<Handshake> {
+        if cif.len() < HANDSHAKE_LEN {
+            return None;
+        }
+
+        let word = |i: usize| u32::from_be_bytes(cif[i * 4..(i + 1) * 4].try_into().unwrap());
+
+        let mut extensions = Vec::new();
+        let mut rest = &cif[HANDSHAKE_LEN..];
+
+        while rest.len() >= 4 {
+            let kind = u16::from_be_bytes([rest[0], rest[1]]);
+            let len = u16::from_be_bytes([rest[2], rest[3]]) as usize * 4;
+
+            if rest.len() < 4 + len {
+                break;
+            }
+
+            extensions.push((kind, rest[4..(4 + len)].to_vec()));
+            rest = &rest[(4 + len)..];
+        }
+
+        Some(Handshake {
+            version: word(0),
+            encryption: (word(1) >> 16) as u16,
+            extension: word(1) as u16,
+            initial_seq: word(2),
+            mtu: word(3),
+            flow_window: word(4),
+            kind: word(5),
+            socket_id: word(6),
+            cookie: word(7),
+            peer_ip: cif[32..48].try_into().unwrap(),
+            extensions,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HANDSHAKE_LEN);
+        bytes.extend_from_slice(&self.version.to_be_bytes());
+        bytes.extend_from_slice(&self.encryption.to_be_bytes());
+        bytes.extend_from_slice(&self.extension.to_be_bytes());
+        bytes.extend_from_slice(&self.initial_seq.to_be_bytes());
+        bytes.extend_from_slice(&self.mtu.to_be_bytes());
+        bytes.extend_from_slice(&self.flow_window.to_be_bytes());
+        bytes.extend_from_slice(&self.kind.to_be_bytes());
+        bytes.extend_from_slice(&self.socket_id.to_be_bytes());
+        bytes.extend_from_slice(&self.cookie.to_be_bytes());
+        bytes.extend_from_slice(&self.peer_ip);
+
+        for (kind, content) in &self.extensions {
+            bytes.extend_from_slice(&kind.to_be_bytes());
+            bytes.extend_from_slice(&((content.len() / 4) as u16).to_be_bytes());
+            bytes.extend_from_slice(content);
+        }
+
+        bytes
+    }
+
+    // latencies asked for in an HSREQ or HSRSP block
+    fn hs_req(&self) -> Option<HsExt> {
+        self.extensions.iter()
+            .find(|(kind, content)| (*kind == EXT_HSREQ || *kind == EXT_HSRSP) && content.len() >= 12)
+            .map(|(_, content)| HsExt {
+                recv_delay: u16::from_be_bytes([content[8], content[9]]),
+                send_delay: u16::from_be_bytes([content[10], content[11]]),
+            })
+    }
+
+    fn stream_id(&self) -> Option<String> {
+        self.extensions.iter()
+            .find(|(kind, _)| *kind == EXT_SID)
+            .map(|(_, content)| decode_stream_id(content))
+    }
+}
+
+fn hs_ext(recv_delay: u16, send_delay: u16) -> Vec<u8> {
+    let mut content = Vec::with_capacity(12);
+    content.extend_from_slice(&SRT_VERSION.to_be_bytes());
+    content.extend_from_slice(&SRT_FLAGS.to_be_bytes());
+    content.extend_from_slice(&recv_delay.to_be_bytes());
+    content.extend_from_slice(&send_delay.to_be_bytes());
+    content
+}
+
+// stream ids are sent with the bytes of each 32 bit word reversed, padded
+// out with nuls
+fn encode_stream_id(stream_id: &str) -> Vec<u8> {
+    let mut bytes = stream_id.as_bytes().to_vec();
+
+    while bytes.len() % 4 != 0 {
+        bytes.push(0);
+    }
+
+    for word in bytes.chunks_mut(4) {
+        word.reverse();
+    }
+
+    bytes
+}
+
+fn decode_stream_id(content: &[u8]) -> String {
+    let mut bytes = content.to_vec();
+
+    for word in bytes.chunks_mut(4) {
+        word.reverse();
+    }
+
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn peer_ip(ip: IpAddr) -> [u8; 16] {
+    let mut bytes = [0u8; 16];
+
+    match ip {
+        // an IPv4 address goes in the first word, little endian
+        IpAddr::V4(ip) => {
+            let mut octets = ip.octets();
+            octets.reverse();
+            bytes[0..4].copy_from_slice(&octets);
+        }
+        IpAddr::V6(ip) => bytes.copy_from_slice(&ip.octets()),
+    }
+
+    bytes
+}
+
+// microseconds since the connection started, which wraps after about an
+// hour and a quarter
+fn timestamp(start: Instant) -> u32 {
+    start.elapsed().as_micros() as u32
+}
+
+fn random_u32() -> u32 {
+    Uuid::new_v4().as_u128() as u32
+}
+
+fn send(socket: &UdpSocket, addr: SocketAddr, packet: &Packet) {
+    if let Err(e) = socket.send_to(&packet.to_bytes(), addr) {
+        if !is_timeout(&e) {
+            eprintln!("srt: could not send to {}: {:?}", addr, e);
+        }
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loss_list_round_trips_ranges_across_wrap() {
+        let missing = vec![5, 7, 8, 9, SEQ_MASK - 1, SEQ_MASK, 0, 1];
+        assert_eq!(missing, parse_loss_list(&loss_list(&missing)));
+    }
+
+    #[test]
+    fn stream_id_round_trips() {
+        let encoded = encode_stream_id("#!::r=live");
+        assert_eq!(12, encoded.len());
+        assert_eq!("#!::r=live", decode_stream_id(&encoded));
+    }
+
+    use std::io::Read;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn receiver(stream_id: &'static str) -> (SrtReceiver, SocketAddr) {
+        let receiver = SrtReceiver::bind("127.0.0.1:0".parse().unwrap(),
+            Box::new(move |sid| sid == Some(stream_id))).unwrap();
+
+        let addr = receiver.socket.local_addr().unwrap();
+        (receiver, addr)
+    }
+
+    // forwards packets between a caller and `to`, dropping the first data
+    // packet carrying `drop_payload` on its way there
+    fn lossy_proxy(to: SocketAddr, drop_payload: u8, stop: Arc<AtomicBool>) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(POLL_INTERVAL)).unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut buff = vec![0u8; 65536];
+            let mut caller = None;
+            let mut dropped = false;
+
+            while !stop.load(Ordering::SeqCst) {
+                let (len, from) = match socket.recv_from(&mut buff) {
+                    Ok(received) => received,
+                    Err(_) => continue,
+                };
+
+                if from == to {
+                    if let Some(caller) = caller {
+                        let _ = socket.send_to(&buff[0..len], caller);
+                    }
+
+                    continue;
+                }
+
+                caller = Some(from);
+
+                if let Some(Packet::Data(data)) = Packet::parse(&buff[0..len]) {
+                    if !dropped && data.payload.first() == Some(&drop_payload) {
+                        dropped = true;
+                        continue;
+                    }
+                }
+
+                let _ = socket.send_to(&buff[0..len], to);
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn lost_packets_are_retransmitted() {
+        let (mut receiver, addr) = receiver("live");
+        let stats = receiver.stats();
+        let stop = Arc::new(AtomicBool::new(false));
+        let proxy = lossy_proxy(addr, 2, stop.clone());
+
+        let payloads = (0..5u8).map(|i| vec![i; 188]).collect::<Vec<_>>();
+        let expected = payloads.concat();
+        let expected_len = expected.len();
+
+        let (received_tx, received_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let started = Instant::now();
+            let mut received = Vec::new();
+            let mut buff = [0u8; 1024];
+
+            while received.len() < expected_len && started.elapsed() < Duration::from_secs(5) {
+                let len = receiver.read(&mut buff).unwrap();
+                received.extend_from_slice(&buff[0..len]);
+            }
+
+            let _ = received_tx.send(received);
+        });
+
+        let mut sender = SrtSender::connect(&proxy.to_string(), "live", DEFAULT_LATENCY).unwrap();
+
+        for payload in &payloads {
+            sender.send(payload).unwrap();
+        }
+
+        // the sender answers NAKs as it polls
+        let received = loop {
+            if let Ok(received) = received_rx.try_recv() {
+                break received;
+            }
+
+            sender.poll().unwrap();
+            thread::sleep(Duration::from_millis(5));
+        };
+
+        stop.store(true, Ordering::SeqCst);
+
+        assert_eq!(expected, received);
+
+        let stats = stats.lock().unwrap().clone().unwrap();
+        assert_eq!(1, stats.recovered_arq);
+        assert_eq!(0, stats.lost);
+    }
+
+    #[test]
+    fn callers_with_the_wrong_stream_id_are_rejected() {
+        let (mut receiver, addr) = receiver("live");
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let listener = thread::spawn({
+            let stop = stop.clone();
+
+            move || {
+                while !stop.load(Ordering::SeqCst) {
+                    receiver.poll().unwrap();
+                }
+
+                receiver.peer.is_none()
+            }
+        });
+
+        let result = SrtSender::connect(&addr.to_string(), "other", DEFAULT_LATENCY);
+        stop.store(true, Ordering::SeqCst);
+
+        assert!(matches!(result, Err(ConnectError::Rejected(REJECT_BAD_SECRET))), "{:?}", result.err());
+        assert!(listener.join().unwrap(), "rejected caller was connected");
+    }
+}
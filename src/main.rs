@@ -1,25 +1,29 @@
-mod db;
-mod engine;
-mod icecast;
-mod listen;
-mod persist;
-mod project;
-mod rtmp;
-mod server;
-mod source;
-mod throttle;
-mod util;
-mod video;
-
-#[macro_use]
-mod module;
-
 use structopt::StructOpt;
+use structopt::clap::AppSettings;
+
+use mixlab::{render, replay, rtmp, server};
 
 #[derive(StructOpt)]
+#[structopt(
+    setting = AppSettings::SubcommandsNegateReqs,
+    setting = AppSettings::ArgsNegateSubcommands,
+)]
 struct Opts {
     #[structopt(flatten)]
     run: server::RunOpts,
+    #[structopt(subcommand)]
+    tool: Option<Tool>,
+}
+
+// development tools, run in place of the server
+#[derive(StructOpt)]
+enum Tool {
+    /// Publish a synthetic stream with A/V sync markers to an RTMP server
+    SimulatePublish(rtmp::simulate::SimulateOpts),
+    /// Replay a recorded session headlessly, printing each workspace update
+    Replay(replay::ReplayOpts),
+    /// Render a project's output to a WAV file, faster than realtime
+    Render(render::RenderOpts),
 }
 
 fn main() {
@@ -33,5 +37,25 @@ fn main() {
         .build()
         .unwrap();
 
-    runtime.block_on(server::run(opts.run));
+    match opts.tool {
+        None => runtime.block_on(server::run(opts.run)),
+        Some(Tool::SimulatePublish(opts)) => {
+            if let Err(e) = runtime.block_on(rtmp::simulate::run(opts)) {
+                eprintln!("simulate-publish: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Tool::Replay(opts)) => {
+            if let Err(e) = runtime.block_on(replay::run(opts)) {
+                eprintln!("replay: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(Tool::Render(opts)) => {
+            if let Err(e) = runtime.block_on(render::run(opts)) {
+                eprintln!("render: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 }
@@ -0,0 +1,168 @@
+use mixlab_protocol::{MeterLevel, METER_FLOOR_DB};
+
+use crate::engine::Sample;
+
+// rms is averaged over about this long, the integration time of a VU meter
+const RMS_SECS: f64 = 0.3;
+
+// after a transient the peak falls back at this rate, about as fast as a
+// PPM's
+const PEAK_FALL_DB_PER_SEC: f64 = 12.0;
+
+// the highest recent peak is held this long before falling with the peak
+const PEAK_HOLD_SECS: f64 = 2.0;
+
+// samples at full scale or over have clipped, or will once they leave
+const CLIP_LEVEL: Sample = 1.0;
+
+// Level meter with ballistics, for interleaved audio of any channel count.
+// RMS shows the loudness an operator hears, the peak how close transients
+// come to full scale. Clipping is held until reset, so that a clip is seen
+// even by an operator who looked away when it happened.
+#[derive(Debug)]
+pub struct Meter {
+    sample_rate: f64,
+    channels: Vec<ChannelMeter>,
+}
+
+#[derive(Debug, Clone)]
+struct ChannelMeter {
+    mean_square: f64,
+    peak_db: f64,
+    hold_db: f64,
+    // samples since the held peak was reached
+    held_for: usize,
+    clipped: bool,
+}
+
+impl Meter {
+    pub fn new(sample_rate: usize, channels: usize) -> Self {
+        let channel = ChannelMeter {
+            mean_square: 0.0,
+            peak_db: METER_FLOOR_DB as f64,
+            hold_db: METER_FLOOR_DB as f64,
+            held_for: 0,
+            clipped: false,
+        };
+
+        Meter {
+            sample_rate: sample_rate as f64,
+            channels: vec![channel; channels],
+        }
+    }
+
+    pub fn process(&mut self, interleaved: &[Sample]) {
+        let channel_count = self.channels.len();
+        let frames = interleaved.len() / channel_count;
+        let secs = frames as f64 / self.sample_rate;
+
+        let rms_coeff = 1.0 - (-1.0 / (RMS_SECS * self.sample_rate)).exp();
+        let hold_samples = (PEAK_HOLD_SECS * self.sample_rate) as usize;
+
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            let mut peak: Sample = 0.0;
+
+            for sample in interleaved.iter().skip(index).step_by(channel_count) {
+                let value = *sample as f64;
+                channel.mean_square += (value * value - channel.mean_square) * rms_coeff;
+                peak = peak.max(sample.abs());
+            }
+
+            channel.clipped |= peak >= CLIP_LEVEL;
+
+            let falling = channel.peak_db - PEAK_FALL_DB_PER_SEC * secs;
+            channel.peak_db = falling.max(to_db(peak as f64)).max(METER_FLOOR_DB as f64);
+
+            if channel.peak_db >= channel.hold_db {
+                channel.hold_db = channel.peak_db;
+                channel.held_for = 0;
+            } else {
+                channel.held_for += frames;
+
+                if channel.held_for >= hold_samples {
+                    channel.hold_db = channel.peak_db;
+                }
+            }
+        }
+    }
+
+    pub fn reset_clip(&mut self) {
+        for channel in &mut self.channels {
+            channel.clipped = false;
+        }
+    }
+
+    /// Levels of each channel, rounded to a tenth of a dB so that they only
+    /// change when the difference would show.
+    pub fn levels(&self) -> Vec<MeterLevel> {
+        self.channels.iter()
+            .map(|channel| MeterLevel {
+                rms_db: round_db(to_db(channel.mean_square.sqrt())),
+                peak_db: round_db(channel.peak_db),
+                hold_db: round_db(channel.hold_db),
+                clipped: channel.clipped,
+            })
+            .collect()
+    }
+}
+
+fn to_db(amplitude: f64) -> f64 {
+    (20.0 * amplitude.log10()).max(METER_FLOOR_DB as f64)
+}
+
+fn round_db(db: f64) -> f32 {
+    ((db * 10.0).round() / 10.0) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 44100;
+
+    fn run(meter: &mut Meter, secs: f64, level: Sample) {
+        let block = vec![level; 2 * 441];
+
+        for _ in 0..(secs * 100.0) as usize {
+            meter.process(&block);
+        }
+    }
+
+    #[test]
+    fn rms_settles_on_the_signal_level() {
+        let mut meter = Meter::new(SAMPLE_RATE, 2);
+        run(&mut meter, 2.0, 0.5);
+
+        let level = &meter.levels()[0];
+        assert_eq!(-6.0, level.rms_db);
+        assert_eq!(-6.0, level.peak_db);
+    }
+
+    #[test]
+    fn peaks_hold_then_fall() {
+        let mut meter = Meter::new(SAMPLE_RATE, 2);
+        run(&mut meter, 0.01, 0.5);
+        run(&mut meter, 1.0, 0.0);
+
+        let level = &meter.levels()[0];
+        assert_eq!(-18.0, level.peak_db);
+        assert_eq!(-6.0, level.hold_db);
+
+        run(&mut meter, 1.5, 0.0);
+
+        let level = &meter.levels()[0];
+        assert_eq!(level.peak_db, level.hold_db);
+    }
+
+    #[test]
+    fn clips_hold_until_reset() {
+        let mut meter = Meter::new(SAMPLE_RATE, 2);
+        run(&mut meter, 0.01, 1.0);
+        run(&mut meter, 5.0, 0.0);
+
+        assert!(meter.levels()[0].clipped);
+
+        meter.reset_clip();
+        assert!(!meter.levels()[0].clipped);
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Instant;
+
+use midir::MidiInput;
+
+use crate::engine::SAMPLES_PER_TICK;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiEvent {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+}
+
+impl MidiEvent {
+    pub fn parse(message: &[u8]) -> Option<MidiEvent> {
+        let status = *message.get(0)?;
+        let channel = status & 0x0f;
+
+        match (status & 0xf0, message.get(1).cloned(), message.get(2).cloned()) {
+            (0x80, Some(note), Some(_)) => Some(MidiEvent::NoteOff { channel, note }),
+            // note on with zero velocity is note off, used for running status
+            (0x90, Some(note), Some(0)) => Some(MidiEvent::NoteOff { channel, note }),
+            (0x90, Some(note), Some(velocity)) => Some(MidiEvent::NoteOn { channel, note, velocity }),
+            (0xb0, Some(controller), Some(value)) => Some(MidiEvent::ControlChange { channel, controller, value }),
+            _ => None,
+        }
+    }
+}
+
+type TimedEvent = (Instant, MidiEvent);
+
+/// MIDI input devices connected to the server. All inputs present at startup
+/// are opened and their events merged for subscribers.
+#[derive(Clone)]
+pub struct MidiInputs {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    devices: Mutex<Vec<String>>,
+    subscribers: Mutex<Vec<Sender<TimedEvent>>>,
+}
+
+impl Shared {
+    fn dispatch(&self, event: MidiEvent) {
+        let event = (Instant::now(), event);
+
+        self.subscribers.lock().expect("midi subscribers lock")
+            .retain(|tx| tx.send(event).is_ok());
+    }
+}
+
+impl MidiInputs {
+    pub fn open() -> MidiInputs {
+        let shared = Arc::new(Shared {
+            devices: Mutex::new(Vec::new()),
+            subscribers: Mutex::new(Vec::new()),
+        });
+
+        thread::spawn({
+            let shared = shared.clone();
+            move || run_input_thread(shared)
+        });
+
+        MidiInputs { shared }
+    }
+
+    pub fn devices(&self) -> Vec<String> {
+        self.shared.devices.lock().expect("midi devices lock").clone()
+    }
+
+    pub fn subscribe(&self) -> MidiSubscription {
+        let (tx, rx) = mpsc::channel();
+        self.shared.subscribers.lock().expect("midi subscribers lock").push(tx);
+
+        MidiSubscription {
+            rx,
+            last_tick: None,
+        }
+    }
+}
+
+// TODO - see if we can open devices as they are plugged in
+fn run_input_thread(shared: Arc<Shared>) {
+    let port_count = match MidiInput::new("mixlab") {
+        Ok(input) => input.port_count(),
+        Err(e) => {
+            eprintln!("midi: could not initialise midi input: {:?}", e);
+            return;
+        }
+    };
+
+    let mut connections = Vec::new();
+
+    // connecting consumes the MidiInput, so each port needs its own
+    for index in 0..port_count {
+        let input = match MidiInput::new("mixlab") {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("midi: could not initialise midi input: {:?}", e);
+                continue;
+            }
+        };
+
+        let port = match input.ports().into_iter().nth(index) {
+            Some(port) => port,
+            None => continue,
+        };
+
+        let name = input.port_name(&port).unwrap_or_else(|_| format!("MIDI Input {}", index + 1));
+
+        let connection = input.connect(&port, "mixlab-input", {
+            let shared = shared.clone();
+            move |_, message, _| {
+                if let Some(event) = MidiEvent::parse(message) {
+                    shared.dispatch(event);
+                }
+            }
+        }, ());
+
+        match connection {
+            Ok(connection) => {
+                println!("midi: opened input {:?}", name);
+                shared.devices.lock().expect("midi devices lock").push(name);
+                connections.push(connection);
+            }
+            Err(e) => {
+                eprintln!("midi: could not open input {:?}: {:?}", name, e);
+            }
+        }
+    }
+
+    // events are delivered on midir's own threads, this thread only needs to
+    // keep the connections open
+    loop {
+        thread::park();
+    }
+}
+
+/// Receives all MIDI events for a single module.
+#[derive(Debug)]
+pub struct MidiSubscription {
+    rx: Receiver<TimedEvent>,
+    last_tick: Option<Instant>,
+}
+
+impl MidiSubscription {
+    /// Drains events received since the previous tick, each paired with the
+    /// offset in sample frames at which it should take effect. Events are
+    /// delayed by one tick so that their relative timing is preserved.
+    /// Must be called every tick, even if the events are not wanted.
+    pub fn tick_events(&mut self) -> Vec<(usize, MidiEvent)> {
+        let now = Instant::now();
+        let start = self.last_tick.replace(now);
+        let events = self.rx.try_iter();
+
+        let start = match start {
+            Some(start) => start,
+            None => return events.map(|(_, event)| (0, event)).collect(),
+        };
+
+        let span = now.duration_since(start).as_secs_f64();
+
+        events.map(|(time, event)| {
+            let position = if span > 0.0 {
+                time.saturating_duration_since(start).as_secs_f64() / span
+            } else {
+                0.0
+            };
+
+            let offset = ((position * SAMPLES_PER_TICK as f64) as usize).min(SAMPLES_PER_TICK - 1);
+
+            (offset, event)
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_note_on_with_zero_velocity_as_note_off() {
+        assert_eq!(Some(MidiEvent::NoteOff { channel: 2, note: 60 }), MidiEvent::parse(&[0x92, 60, 0]));
+        assert_eq!(Some(MidiEvent::NoteOn { channel: 0, note: 60, velocity: 100 }), MidiEvent::parse(&[0x90, 60, 100]));
+        assert_eq!(Some(MidiEvent::ControlChange { channel: 15, controller: 7, value: 127 }), MidiEvent::parse(&[0xbf, 7, 127]));
+        assert_eq!(None, MidiEvent::parse(&[0xf8]));
+    }
+}
@@ -0,0 +1,93 @@
+use mixlab_protocol::AmbisonicEncoderParams;
+
+use crate::ambisonics;
+use crate::engine::{self, InputRef, OutputRef, AMBISONIC_CHANNELS};
+use crate::module::{ModuleT, LineType, Terminal};
+
+#[derive(Debug)]
+pub struct AmbisonicEncoder {
+    params: AmbisonicEncoderParams,
+    // gains at the end of the last tick, ramped from so that moving the
+    // source doesn't click
+    gains: [f32; AMBISONIC_CHANNELS],
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for AmbisonicEncoder {
+    type Params = AmbisonicEncoderParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        (Self {
+            gains: gains(&params, 0.0),
+            params,
+            inputs: vec![
+                LineType::Mono.labeled("Input"),
+                LineType::Control.labeled("Azimuth"),
+                // another encoder's soundfield, mixed in so that encoders can
+                // be chained to place several sources
+                LineType::Ambisonic.labeled("Chain"),
+            ],
+            outputs: vec![LineType::Ambisonic.unlabeled()],
+        }, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_mono();
+        let azimuth_mod = inputs[1].expect_control();
+        let chain = inputs[2].expect_ambisonic();
+        let output = outputs[0].expect_ambisonic();
+
+        let start = self.gains;
+        let end = gains(&self.params, 0.0);
+
+        let frames = output.chunks_mut(AMBISONIC_CHANNELS).zip(chain.chunks(AMBISONIC_CHANNELS));
+
+        for (i, (sample, (frame, chained))) in input.iter().zip(frames).enumerate() {
+            // the control input turns the source a full circle from the
+            // azimuth set
+            let gains = match azimuth_mod {
+                Some(ctl) => gains(&self.params, ctl.at(i) as f64 * 360.0),
+                None => {
+                    let ramp = (i + 1) as f32 / input.len() as f32;
+                    let mut gains = start;
+                    for (gain, end) in gains.iter_mut().zip(end.iter()) {
+                        *gain += (end - *gain) * ramp;
+                    }
+                    gains
+                }
+            };
+
+            for ((out, chained), gain) in frame.iter_mut().zip(chained.iter()).zip(gains.iter()) {
+                *out = chained + sample * gain;
+            }
+
+            self.gains = gains;
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+}
+
+fn gains(params: &AmbisonicEncoderParams, turn: f64) -> [f32; AMBISONIC_CHANNELS] {
+    ambisonics::encode((params.azimuth + turn).to_radians(), params.elevation.to_radians())
+}
@@ -1,11 +1,13 @@
-use crate::engine::{self, Sample, InputRef, OutputRef};
-use crate::module::{ModuleT, LineType, Terminal};
+use crate::engine::{self, Sample, InputRef, OutputRef, Smoothed, CHANNELS, ModuleHost, DynModuleHost};
+use crate::module::{self, ModuleT, LineType, Terminal};
 
 use mixlab_protocol::AmplifierParams;
 
 #[derive(Debug)]
 pub struct Amplifier {
     params: AmplifierParams,
+    amplitude: Smoothed,
+    mod_depth: Smoothed,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
 }
@@ -17,10 +19,13 @@ impl ModuleT for Amplifier {
 
     fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
         (Self {
+            amplitude: Smoothed::with_default(params.amplitude),
+            mod_depth: Smoothed::with_default(params.mod_depth),
             params,
             inputs: vec![
                 LineType::Stereo.labeled("Input"),
-                LineType::Mono.labeled("Control")
+                LineType::Mono.labeled("Control"),
+                LineType::Control.labeled("Amplitude"),
             ],
             outputs: vec![LineType::Stereo.unlabeled()]
         }, ())
@@ -31,13 +36,13 @@ impl ModuleT for Amplifier {
     }
 
     fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.amplitude.set(params.amplitude);
+        self.mod_depth.set(params.mod_depth);
         self.params = params;
         None
     }
 
     fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
-        let AmplifierParams {mod_depth, amplitude} = self.params;
-
         let input = inputs[0].expect_stereo();
         let mod_input = if inputs[1].connected() {
             Some(inputs[1].expect_mono())
@@ -45,15 +50,21 @@ impl ModuleT for Amplifier {
             None
         };
 
+        let amplitude_mod = inputs[2].expect_control();
+
         let output = outputs[0].expect_stereo();
 
-        let len = input.len();
+        for (frame, (input, output)) in input.chunks(CHANNELS).zip(output.chunks_mut(CHANNELS)).enumerate() {
+            let amplitude = self.amplitude.next();
+            let mod_depth = self.mod_depth.next();
 
-        for i in 0..len {
-            // mod input is a mono channel and so half the length:
-            let mod_value = mod_input.map(|buff| buff[i / 2] as f64).unwrap_or(1.0);
+            // mod input is a mono channel and so one sample per frame:
+            let mod_value = mod_input.map(|buff| buff[frame] as f64).unwrap_or(1.0);
+            let amplitude = amplitude_mod.map(|ctl| ctl.at(frame) as f64 * amplitude).unwrap_or(amplitude);
 
-            output[i] = (input[i] as f64 * depth(mod_value, mod_depth) * amplitude) as Sample;
+            for (input, output) in input.iter().zip(output.iter_mut()) {
+                *output = (*input as f64 * depth(mod_value, mod_depth) * amplitude) as Sample;
+            }
         }
 
         None
@@ -66,6 +77,10 @@ impl ModuleT for Amplifier {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn host(host: ModuleHost<Self>) -> DynModuleHost {
+        module::parallel(host)
+    }
 }
 
 pub fn depth(value: f64, depth: f64) -> f64 {
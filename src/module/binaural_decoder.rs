@@ -0,0 +1,54 @@
+use mixlab_protocol::BinauralDecoderParams;
+
+use crate::ambisonics;
+use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
+use crate::module::{ModuleT, LineType, Terminal};
+
+#[derive(Debug)]
+pub struct BinauralDecoder {
+    params: BinauralDecoderParams,
+    decoder: ambisonics::BinauralDecoder,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for BinauralDecoder {
+    type Params = BinauralDecoderParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        (Self {
+            params,
+            decoder: ambisonics::BinauralDecoder::new(SAMPLE_RATE),
+            inputs: vec![LineType::Ambisonic.unlabeled()],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        }, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_ambisonic();
+        let output = outputs[0].expect_stereo();
+
+        self.decoder.process(self.params.yaw.to_radians(), input, output);
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+}
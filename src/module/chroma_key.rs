@@ -0,0 +1,209 @@
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::{AvFrame, PictureSettings};
+use mixlab_protocol::{ChromaKeyParams, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef};
+use crate::module::ModuleT;
+use crate::video;
+use crate::video::composite;
+use crate::video::encode::DynamicScaler;
+
+#[derive(Debug)]
+pub struct ChromaKey {
+    params: ChromaKeyParams,
+    // converts the background to yuv420p if it isn't already
+    background_scaler: Option<DynamicScaler>,
+    // converts the foreground to the background's picture settings
+    foreground_scaler: Option<DynamicScaler>,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for ChromaKey {
+    type Params = ChromaKeyParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = ChromaKey {
+            params,
+            background_scaler: None,
+            foreground_scaler: None,
+            inputs: vec![
+                LineType::Video.labeled("Foreground"),
+                LineType::Video.labeled("Background"),
+            ],
+            outputs: vec![LineType::Video.unlabeled()],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let output = outputs[0].expect_video();
+
+        // with only one input connected there is nothing to key, so it
+        // passes through as it is
+        let (foreground, background) = match (inputs[0].expect_video(), inputs[1].expect_video()) {
+            (Some(foreground), Some(background)) => (foreground, background),
+            (foreground, background) => {
+                *output = foreground.or(background).cloned();
+                return None;
+            }
+        };
+
+        let mut frame = background.data.decoded.clone();
+        let target = composite::yuv420p(&frame.picture_settings());
+
+        if frame.picture_settings() != target {
+            frame = scale(&mut self.background_scaler, target.clone(), &mut frame);
+        }
+
+        let mut foreground = foreground.data.decoded.clone();
+
+        if foreground.picture_settings() != target {
+            foreground = scale(&mut self.foreground_scaler, target.clone(), &mut foreground);
+        }
+
+        let mask = key_mask(&foreground, &self.params);
+        let mask_width = target.width / 2;
+
+        composite::blend_picture(&mut frame, &foreground, 0, 0, |x, y| mask[y * mask_width + x]);
+
+        *output = Some(engine::VideoFrame {
+            data: video::Frame {
+                decoded: frame,
+                // the picture has changed, so the background's packets no
+                // longer describe it
+                passthrough: None,
+                ..background.data.clone()
+            },
+            tick_offset: background.tick_offset,
+        });
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+fn scale(scaler: &mut Option<DynamicScaler>, target: PictureSettings, frame: &mut AvFrame<Video>) -> AvFrame<Video> {
+    if scaler.as_ref().map(|scaler| scaler.output()) != Some(&target) {
+        *scaler = Some(DynamicScaler::new(target));
+    }
+
+    scaler.as_mut().unwrap().scale(frame).clone()
+}
+
+// the alpha, out of 256, of each 2x2 block of a yuv420p foreground. keying
+// looks at chroma only, so the key colour is removed however brightly it is
+// lit, shadows on the screen included
+fn key_mask(foreground: &AvFrame<Video>, params: &ChromaKeyParams) -> Vec<u16> {
+    let picture = foreground.picture_settings();
+    let (width, height) = (picture.width / 2, picture.height / 2);
+
+    let [_, key_u, key_v] = composite::yuv(params.key);
+    let threshold = params.threshold.max(0.0);
+    let softness = params.softness.max(0.0);
+
+    let data = foreground.frame_data();
+    let mut mask = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let (u, v) = unsafe {
+                (*data.data(1).add(y * data.stride(1) + x), *data.data(2).add(y * data.stride(2) + x))
+            };
+
+            let du = u as f64 - key_u as f64;
+            let dv = v as f64 - key_v as f64;
+            let distance = (du * du + dv * dv).sqrt() / 128.0;
+
+            let alpha = if distance <= threshold {
+                0.0
+            } else if distance >= threshold + softness {
+                1.0
+            } else {
+                (distance - threshold) / softness
+            };
+
+            mask.push((alpha * 256.0) as u16);
+        }
+    }
+
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mixlab_protocol::Rgb;
+    use crate::video::golden;
+
+    fn video_frame(seed: usize) -> engine::VideoFrame {
+        golden::video_frame(golden::test_card(&PictureSettings::yuv420p(128, 72), seed))
+    }
+
+    fn key(params: ChromaKeyParams, foreground: Option<usize>, background: Option<usize>) -> Option<AvFrame<Video>> {
+        let mut chroma_key = ChromaKey {
+            params,
+            background_scaler: None,
+            foreground_scaler: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        };
+
+        let foreground = foreground.map(video_frame);
+        let background = background.map(video_frame);
+
+        let mut output = None;
+
+        chroma_key.run_tick(0,
+            &[InputRef::Video(foreground.as_ref()), InputRef::Video(background.as_ref())],
+            &mut [OutputRef::Video(&mut output)]);
+
+        output.map(|output| output.data.decoded)
+    }
+
+    // a key in the middle of the test card's chroma ramps, so that the
+    // output has keyed, soft and opaque regions
+    fn params() -> ChromaKeyParams {
+        ChromaKeyParams { key: Rgb(100, 100, 180), threshold: 0.2, softness: 0.3 }
+    }
+
+    #[test]
+    fn key_colour_shows_the_background() {
+        let frame = key(params(), Some(1), Some(2)).expect("chroma key output");
+        golden::assert_matches("chroma_key", &frame, 1);
+    }
+
+    #[test]
+    fn hard_key_has_no_soft_edge() {
+        let params = ChromaKeyParams { softness: 0.0, ..params() };
+        let frame = key(params, Some(1), Some(2)).expect("chroma key output");
+        golden::assert_matches("chroma_key_hard", &frame, 1);
+    }
+
+    #[test]
+    fn single_input_passes_through() {
+        let frame = key(params(), None, Some(2)).expect("chroma key output");
+        golden::assert_matches("chroma_key_background_only", &frame, 0);
+
+        assert!(key(params(), None, None).is_none());
+    }
+}
@@ -0,0 +1,94 @@
+use mixlab_protocol::{ClockParams, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::module::ModuleT;
+
+// run input counts as high above this level
+const RUN_THRESHOLD: Sample = 0.5;
+
+// subdivisions of a beat sent on each output, in output order
+const DIVISIONS: [f64; 3] = [1.0, 2.0, 4.0];
+
+#[derive(Debug)]
+pub struct Clock {
+    params: ClockParams,
+    // position within the current beat, 0.0 to 1.0
+    phase: f64,
+    running: bool,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Clock {
+    type Params = ClockParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        (Self {
+            params,
+            phase: 0.0,
+            running: false,
+            inputs: vec![LineType::Mono.labeled("Run")],
+            outputs: vec![
+                LineType::Mono.labeled("1/4"),
+                LineType::Mono.labeled("1/8"),
+                LineType::Mono.labeled("1/16"),
+            ],
+        }, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        // free running unless something is connected to the run input
+        let run = if inputs[0].connected() {
+            Some(inputs[0].expect_mono())
+        } else {
+            None
+        };
+
+        let increment = self.params.bpm.max(0.0) / 60.0 / SAMPLE_RATE as f64;
+
+        for i in 0..SAMPLES_PER_TICK {
+            let running = run.map(|run| run[i] > RUN_THRESHOLD).unwrap_or(true);
+
+            // starting always begins on the beat, so that everything clocked
+            // from here starts together
+            if running && !self.running {
+                self.phase = 0.0;
+            }
+
+            self.running = running;
+
+            if !running {
+                continue;
+            }
+
+            for (output, division) in outputs.iter_mut().zip(DIVISIONS.iter()) {
+                // pulses are high for the first half of each division
+                let high = (self.phase * division).fract() < 0.5;
+                output.expect_mono()[i] = if high { 1.0 } else { 0.0 };
+            }
+
+            self.phase = (self.phase + increment).fract();
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+}
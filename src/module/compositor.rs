@@ -0,0 +1,179 @@
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::{AvFrame, PictureSettings};
+use mixlab_protocol::{CompositorParams, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef};
+use crate::module::ModuleT;
+use crate::video;
+use crate::video::composite;
+use crate::video::encode::DynamicScaler;
+
+#[derive(Debug)]
+pub struct Compositor {
+    params: CompositorParams,
+    // converts the background to yuv420p if it isn't already
+    background_scaler: Option<DynamicScaler>,
+    // scales the picture to its size over the background
+    picture_scaler: Option<DynamicScaler>,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Compositor {
+    type Params = CompositorParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Compositor {
+            params,
+            background_scaler: None,
+            picture_scaler: None,
+            inputs: vec![
+                LineType::Video.labeled("Background"),
+                LineType::Video.labeled("Picture"),
+            ],
+            outputs: vec![LineType::Video.unlabeled()],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let output = outputs[0].expect_video();
+
+        let background = match inputs[0].expect_video() {
+            Some(background) => background,
+            None => {
+                *output = None;
+                return None;
+            }
+        };
+
+        let picture = match inputs[1].expect_video() {
+            Some(picture) if self.params.opacity > 0.0 => picture,
+            _ => {
+                *output = Some(background.clone());
+                return None;
+            }
+        };
+
+        let mut frame = background.data.decoded.clone();
+        let target = composite::yuv420p(&frame.picture_settings());
+
+        if frame.picture_settings() != target {
+            frame = scale(&mut self.background_scaler, target.clone(), &mut frame);
+        }
+
+        let size = picture_size(&picture.data.decoded.picture_settings(), target.height, self.params.scale);
+        let picture_target = PictureSettings::yuv420p(size.0, size.1);
+
+        let mut picture = picture.data.decoded.clone();
+
+        if picture.picture_settings() != picture_target {
+            picture = scale(&mut self.picture_scaler, picture_target, &mut picture);
+        }
+
+        let alpha = (self.params.opacity.max(0.0).min(1.0) * 256.0) as u16;
+
+        let (x, y) = composite::place(self.params.position, size, (target.width, target.height));
+        composite::blend_picture(&mut frame, &picture, x, y, |_, _| alpha);
+
+        *output = Some(engine::VideoFrame {
+            data: video::Frame {
+                decoded: frame,
+                // the picture has changed, so the background's packets no
+                // longer describe it
+                passthrough: None,
+                ..background.data.clone()
+            },
+            tick_offset: background.tick_offset,
+        });
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+fn scale(scaler: &mut Option<DynamicScaler>, target: PictureSettings, frame: &mut AvFrame<Video>) -> AvFrame<Video> {
+    if scaler.as_ref().map(|scaler| scaler.output()) != Some(&target) {
+        *scaler = Some(DynamicScaler::new(target));
+    }
+
+    scaler.as_mut().unwrap().scale(frame).clone()
+}
+
+// the size of the picture at `scale` times the background height, keeping
+// its aspect ratio, rounded to even for yuv420p
+fn picture_size(picture: &PictureSettings, background_height: usize, scale: f64) -> (usize, usize) {
+    let height = (background_height as f64 * scale.max(0.0).min(1.0) / 2.0).round().max(1.0);
+    let width = (picture.width as f64 * height / picture.height as f64).round().max(1.0);
+
+    (width as usize * 2, height as usize * 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mixlab_protocol::TextPosition;
+    use crate::video::golden;
+
+    // output of one tick with a 128x72 background and a 64x36 picture, which
+    // a scale of 0.5 places without scaling
+    fn composite(params: CompositorParams) -> AvFrame<Video> {
+        let mut compositor = Compositor {
+            params,
+            background_scaler: None,
+            picture_scaler: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        };
+
+        let background = golden::video_frame(golden::test_card(&PictureSettings::yuv420p(128, 72), 1));
+        let picture = golden::video_frame(golden::test_card(&PictureSettings::yuv420p(64, 36), 2));
+
+        let mut output = None;
+
+        compositor.run_tick(0,
+            &[InputRef::Video(Some(&background)), InputRef::Video(Some(&picture))],
+            &mut [OutputRef::Video(&mut output)]);
+
+        output.expect("compositor output").data.decoded
+    }
+
+    #[test]
+    fn opaque_picture_replaces_the_background() {
+        let params = CompositorParams { position: TextPosition::TopLeft, scale: 0.5, opacity: 1.0 };
+        golden::assert_matches("compositor_opaque", &composite(params), 1);
+    }
+
+    #[test]
+    fn translucent_picture_blends_with_the_background() {
+        let params = CompositorParams { position: TextPosition::BottomRight, scale: 0.5, opacity: 0.5 };
+        golden::assert_matches("compositor_translucent", &composite(params), 1);
+    }
+
+    #[test]
+    fn pictures_keep_their_aspect_ratio() {
+        let picture = PictureSettings::yuv420p(1920, 1080);
+
+        assert_eq!((384, 216), picture_size(&picture, 720, 0.3));
+        assert_eq!((4, 2), picture_size(&picture, 720, 0.0));
+    }
+}
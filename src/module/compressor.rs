@@ -0,0 +1,176 @@
+use mixlab_protocol::{CompressorParams, LineType, Terminal};
+
+use crate::dsp::smoothing_coeff;
+use crate::engine::{self, InputRef, OutputRef, Sample, CHANNELS, SAMPLE_RATE};
+use crate::module::ModuleT;
+
+// quieter than anything that reaches the threshold, keeps log10 finite on
+// silence
+const FLOOR_DB: f64 = -120.0;
+
+#[derive(Debug)]
+pub struct Compressor {
+    params: CompressorParams,
+    dynamics: Dynamics,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Compressor {
+    type Params = CompressorParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Compressor {
+            dynamics: Dynamics::new(&params, SAMPLE_RATE),
+            params,
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.dynamics.configure(&params, SAMPLE_RATE);
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let output = outputs[0].expect_stereo();
+
+        self.dynamics.process(input, output);
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+// Feed forward compression of the louder channel's peak level, worked out in
+// dB. Both channels get the same gain so the stereo image holds still, as
+// with the noise gate.
+#[derive(Debug)]
+struct Dynamics {
+    threshold_db: f64,
+    // dB of reduction for each dB over the threshold
+    slope: f64,
+    makeup_db: f64,
+    attack_coeff: f64,
+    release_coeff: f64,
+    // current gain reduction in dB, positive
+    reduction_db: f64,
+}
+
+impl Dynamics {
+    fn new(params: &CompressorParams, sample_rate: usize) -> Self {
+        let mut dynamics = Dynamics {
+            threshold_db: 0.0,
+            slope: 0.0,
+            makeup_db: 0.0,
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+            reduction_db: 0.0,
+        };
+
+        dynamics.configure(params, sample_rate);
+        dynamics
+    }
+
+    fn configure(&mut self, params: &CompressorParams, sample_rate: usize) {
+        let sample_rate = sample_rate as f64;
+
+        self.threshold_db = params.threshold.0;
+        self.slope = 1.0 - 1.0 / params.ratio.max(1.0);
+        self.makeup_db = params.makeup.0;
+        self.attack_coeff = smoothing_coeff(params.attack, sample_rate);
+        self.release_coeff = smoothing_coeff(params.release, sample_rate);
+    }
+
+    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        for (input, output) in input.chunks(CHANNELS).zip(output.chunks_mut(CHANNELS)) {
+            let peak = input.iter().fold(0.0, |peak: f64, sample| peak.max(sample.abs() as f64));
+            let level_db = (peak.log10() * 20.0).max(FLOOR_DB);
+
+            let target = (level_db - self.threshold_db).max(0.0) * self.slope;
+
+            let coeff = match target > self.reduction_db {
+                true => self.attack_coeff,
+                false => self.release_coeff,
+            };
+
+            self.reduction_db += (target - self.reduction_db) * coeff;
+
+            let gain = f64::powf(10.0, (self.makeup_db - self.reduction_db) / 20.0);
+
+            for (input, output) in input.iter().zip(output) {
+                *output = (*input as f64 * gain) as Sample;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mixlab_protocol::Decibel;
+
+    fn run(dynamics: &mut Dynamics, secs: f64, level: Sample) -> Vec<Sample> {
+        // alternating sign, so that it's a signal rather than a DC offset
+        let input = (0..(secs * SAMPLE_RATE as f64) as usize * CHANNELS)
+            .map(|i| if (i / CHANNELS) % 2 == 0 { level } else { -level })
+            .collect::<Vec<_>>();
+
+        let mut output = vec![0.0; input.len()];
+        dynamics.process(&input, &mut output);
+        output
+    }
+
+    fn level_db(output: &[Sample]) -> f64 {
+        Decibel::from_linear(output.last().unwrap().abs() as f64).0
+    }
+
+    #[test]
+    fn reduces_by_the_ratio_over_threshold() {
+        let params = CompressorParams { threshold: Decibel(-20.0), ratio: 4.0, ..CompressorParams::default() };
+        let mut dynamics = Dynamics::new(&params, SAMPLE_RATE);
+
+        // 12dB over comes out 3dB over
+        let output = run(&mut dynamics, 1.0, Decibel(-8.0).to_linear() as Sample);
+        assert!((level_db(&output) - -17.0).abs() < 0.1, "{}", level_db(&output));
+    }
+
+    #[test]
+    fn leaves_quieter_signals_alone_but_for_makeup() {
+        let params = CompressorParams { makeup: Decibel(6.0), ..CompressorParams::default() };
+        let mut dynamics = Dynamics::new(&params, SAMPLE_RATE);
+
+        let output = run(&mut dynamics, 0.5, Decibel(-30.0).to_linear() as Sample);
+        assert!((level_db(&output) - -24.0).abs() < 0.01, "{}", level_db(&output));
+    }
+
+    #[test]
+    fn recovers_after_release() {
+        let params = CompressorParams::default();
+        let mut dynamics = Dynamics::new(&params, SAMPLE_RATE);
+
+        run(&mut dynamics, 0.5, 1.0);
+        assert!(dynamics.reduction_db > 10.0);
+
+        run(&mut dynamics, 2.0, Decibel(-40.0).to_linear() as Sample);
+        assert!(dynamics.reduction_db < 0.01);
+    }
+}
@@ -0,0 +1,236 @@
+use std::f64;
+
+use mixlab_protocol::{CrossoverParams, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, CHANNELS, SAMPLE_RATE};
+use crate::module::ModuleT;
+
+const MIN_FREQ: f64 = 20.0;
+// comfortably short of nyquist, where the filters stop behaving
+const MAX_FREQ: f64 = 20000.0;
+
+#[derive(Debug)]
+pub struct Crossover {
+    params: CrossoverParams,
+    channels: [Splitter; CHANNELS],
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Crossover {
+    type Params = CrossoverParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut module = Crossover {
+            params: params.clone(),
+            channels: [Splitter::default(), Splitter::default()],
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![
+                LineType::Stereo.labeled("Low"),
+                LineType::Stereo.labeled("Mid"),
+                LineType::Stereo.labeled("High"),
+            ],
+        };
+
+        module.update(params);
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        // the bands can meet but never cross
+        let low_freq = params.low_freq.max(MIN_FREQ).min(MAX_FREQ);
+        let high_freq = params.high_freq.max(low_freq).min(MAX_FREQ);
+
+        for splitter in &mut self.channels {
+            splitter.set(low_freq, high_freq, SAMPLE_RATE as f64);
+        }
+
+        self.params = CrossoverParams { low_freq, high_freq };
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+
+        let (low, mid, high) = match outputs {
+            [low, mid, high] => (low.expect_stereo(), mid.expect_stereo(), high.expect_stereo()),
+            _ => unreachable!(),
+        };
+
+        for (i, sample) in input.iter().enumerate() {
+            let (lo, mi, hi) = self.channels[i % CHANNELS].split(*sample as f64);
+            low[i] = lo as Sample;
+            mid[i] = mi as Sample;
+            high[i] = hi as Sample;
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+// Three way split of one channel. The low band also goes through the high
+// crossover with its outputs summed back together, an allpass which delays
+// it in phase as much as the mid and high bands have been, so that the three
+// bands sum back to the input with a flat response.
+#[derive(Debug, Default)]
+struct Splitter {
+    low: LinkwitzRiley,
+    high: LinkwitzRiley,
+    low_allpass: LinkwitzRiley,
+}
+
+impl Splitter {
+    fn set(&mut self, low_freq: f64, high_freq: f64, sample_rate: f64) {
+        self.low.set(low_freq, sample_rate);
+        self.high.set(high_freq, sample_rate);
+        self.low_allpass.set(high_freq, sample_rate);
+    }
+
+    fn split(&mut self, x: f64) -> (f64, f64, f64) {
+        let (low, rest) = self.low.split(x);
+        let (mid, high) = self.high.split(rest);
+        let (low_lo, low_hi) = self.low_allpass.split(low);
+
+        (low_lo + low_hi, mid, high)
+    }
+}
+
+// Fourth order Linkwitz-Riley crossover, each side two second order
+// Butterworth filters in series. Both sides are 6dB down at the crossover
+// and in phase with each other at every frequency, so they sum flat.
+#[derive(Debug, Default)]
+struct LinkwitzRiley {
+    low_pass: [Biquad; 2],
+    high_pass: [Biquad; 2],
+}
+
+impl LinkwitzRiley {
+    fn set(&mut self, freq: f64, sample_rate: f64) {
+        for biquad in &mut self.low_pass {
+            biquad.set_butterworth(freq, sample_rate, false);
+        }
+
+        for biquad in &mut self.high_pass {
+            biquad.set_butterworth(freq, sample_rate, true);
+        }
+    }
+
+    fn split(&mut self, x: f64) -> (f64, f64) {
+        let low = self.low_pass.iter_mut().fold(x, |x, biquad| biquad.pump(x));
+        let high = self.high_pass.iter_mut().fold(x, |x, biquad| biquad.pump(x));
+        (low, high)
+    }
+}
+
+// after the RBJ audio EQ cookbook
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    // transposed direct form II state
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    fn set_butterworth(&mut self, freq: f64, sample_rate: f64, high_pass: bool) {
+        let w0 = 2.0 * f64::consts::PI * freq / sample_rate;
+        let alpha = w0.sin() / (2.0 * f64::consts::FRAC_1_SQRT_2);
+        let cos_w0 = w0.cos();
+
+        let a0 = 1.0 + alpha;
+
+        let (b0, b1) = match high_pass {
+            false => ((1.0 - cos_w0) / 2.0, 1.0 - cos_w0),
+            true => ((1.0 + cos_w0) / 2.0, -(1.0 + cos_w0)),
+        };
+
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b0 / a0;
+        self.a1 = -2.0 * cos_w0 / a0;
+        self.a2 = (1.0 - alpha) / a0;
+    }
+
+    fn pump(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn splitter() -> Splitter {
+        let mut splitter = Splitter::default();
+        splitter.set(200.0, 2000.0, SAMPLE_RATE as f64);
+        splitter
+    }
+
+    // peak level of each band, and of them summed, once a sine has settled
+    fn levels(freq: f64) -> [f64; 4] {
+        let mut splitter = splitter();
+        let mut peaks = [0.0; 4];
+
+        for i in 0..SAMPLE_RATE {
+            let x = (2.0 * f64::consts::PI * freq * i as f64 / SAMPLE_RATE as f64).sin();
+            let (low, mid, high) = splitter.split(x);
+
+            if i >= SAMPLE_RATE / 2 {
+                for (peak, value) in peaks.iter_mut().zip(&[low, mid, high, low + mid + high]) {
+                    *peak = f64::max(*peak, value.abs());
+                }
+            }
+        }
+
+        peaks
+    }
+
+    #[test]
+    fn bands_sum_flat() {
+        for &freq in &[50.0, 200.0, 700.0, 2000.0, 8000.0] {
+            let [_, _, _, sum] = levels(freq);
+            assert!((sum - 1.0).abs() < 0.01, "{}Hz sums to {}", freq, sum);
+        }
+    }
+
+    #[test]
+    fn tones_land_in_their_band() {
+        let [low, mid, high, _] = levels(50.0);
+        assert!(low > 0.99 && mid < 0.05 && high < 0.01, "{} {} {}", low, mid, high);
+
+        let [low, mid, high, _] = levels(630.0);
+        assert!(mid > 0.8 && low < 0.1 && high < 0.1, "{} {} {}", low, mid, high);
+
+        let [low, mid, high, _] = levels(10000.0);
+        assert!(high > 0.99 && mid < 0.05 && low < 0.01, "{} {} {}", low, mid, high);
+    }
+
+    #[test]
+    fn crossover_points_are_6db_down() {
+        let [low, mid, _, _] = levels(200.0);
+        assert!((low - 0.5).abs() < 0.02, "low: {}", low);
+        assert!((mid - 0.5).abs() < 0.05, "mid: {}", mid);
+    }
+}
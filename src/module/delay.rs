@@ -0,0 +1,228 @@
+use mixlab_protocol::{DelayParams, LineType, Terminal};
+
+use crate::dsp::DelayLine;
+use crate::engine::{self, InputRef, OutputRef, Sample, CHANNELS, SAMPLE_RATE, ModuleHost, DynModuleHost};
+use crate::module::{self, ModuleT};
+
+// longest delay the ring buffer can hold, in seconds. also bounds clock
+// synced delays, so a slow clock or large ratio saturates here
+const MAX_DELAY: usize = 4;
+const MAX_DELAY_SAMPLES: usize = MAX_DELAY * SAMPLE_RATE;
+
+// time constant for the delay time to glide to a new value. jumping the read
+// position straight to the new time clicks, gliding bends the pitch of the
+// echoes instead, like a tape delay
+const GLIDE_SECONDS: f64 = 0.1;
+
+// clock input counts as high above this level
+const CLOCK_THRESHOLD: Sample = 0.5;
+
+#[derive(Debug)]
+pub struct Delay {
+    params: DelayParams,
+    line: DelayLine,
+    clock: ClockTracker,
+    // current delay time in samples, gliding towards the target. None until
+    // the first tick so that the delay starts at its set time
+    delay: Option<f64>,
+    // values reached at the end of the last tick, ramped towards the new
+    // params across the next tick so that changes don't zipper
+    feedback: f64,
+    mix: f64,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Delay {
+    type Params = DelayParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Delay {
+            line: DelayLine::new(MAX_DELAY_SAMPLES),
+            clock: ClockTracker::default(),
+            delay: None,
+            feedback: clamp_unit(params.feedback),
+            mix: clamp_unit(params.mix),
+            params,
+            inputs: vec![
+                LineType::Stereo.labeled("Input"),
+                LineType::Mono.labeled("Clock"),
+            ],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+
+        let clock = if inputs[1].connected() {
+            Some(inputs[1].expect_mono())
+        } else {
+            // forget the old period so reconnecting doesn't pick up a stale
+            // tempo before the first full clock cycle
+            self.clock = ClockTracker::default();
+            None
+        };
+
+        let output = outputs[0].expect_stereo();
+
+        let len = input.len() / CHANNELS;
+        let glide = 1.0 - f64::exp(-1.0 / (GLIDE_SECONDS * SAMPLE_RATE as f64));
+
+        let feedback_from = self.feedback;
+        let feedback_to = clamp_unit(self.params.feedback);
+        let mix_from = self.mix;
+        let mix_to = clamp_unit(self.params.mix);
+
+        for i in 0..len {
+            if let Some(clock) = clock {
+                self.clock.observe(t + i as u64, clock[i]);
+            }
+
+            let target = self.target_delay();
+            let delay = match self.delay {
+                Some(delay) => delay + (target - delay) * glide,
+                None => target,
+            };
+            self.delay = Some(delay);
+
+            let progress = (i + 1) as f64 / len as f64;
+            let feedback = feedback_from + (feedback_to - feedback_from) * progress;
+            let mix = mix_from + (mix_to - mix_from) * progress;
+
+            let frame = [input[i * CHANNELS], input[i * CHANNELS + 1]];
+            let wet = echo(&mut self.line, frame, delay, feedback);
+
+            for chan in 0..CHANNELS {
+                output[i * CHANNELS + chan] = (frame[chan] as f64 * (1.0 - mix) + wet[chan] as f64 * mix) as Sample;
+            }
+        }
+
+        self.feedback = feedback_to;
+        self.mix = mix_to;
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn host(host: ModuleHost<Self>) -> DynModuleHost {
+        module::parallel(host)
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.line.memory_usage()
+    }
+}
+
+impl Delay {
+    // delay time in samples that the current delay should glide towards
+    fn target_delay(&self) -> f64 {
+        let samples = match self.clock.period {
+            Some(period) => period as f64 * self.params.clock_ratio,
+            None => self.params.time * SAMPLE_RATE as f64,
+        };
+
+        // at least one sample, the current input is written after reading
+        samples.max(1.0).min(MAX_DELAY_SAMPLES as f64)
+    }
+}
+
+fn clamp_unit(value: f64) -> f64 {
+    value.max(0.0).min(1.0)
+}
+
+// reads the frame `delay` samples ago, then writes the input mixed with
+// `feedback` of the delayed signal. returns the delayed frame
+fn echo(line: &mut DelayLine, input: [Sample; CHANNELS], delay: f64, feedback: f64) -> [Sample; CHANNELS] {
+    // the input is written after reading, so `delay` samples before it is
+    // one fewer before the frame last written
+    let delayed = line.read(delay - 1.0);
+
+    let mut frame = [0.0; CHANNELS];
+
+    for chan in 0..CHANNELS {
+        frame[chan] = (input[chan] as f64 + delayed[chan] as f64 * feedback) as Sample;
+    }
+
+    line.write(&frame);
+
+    delayed
+}
+
+// measures the period of a clock signal from the interval between rising
+// edges
+#[derive(Debug, Default)]
+struct ClockTracker {
+    high: bool,
+    last_edge: Option<u64>,
+    period: Option<u64>,
+}
+
+impl ClockTracker {
+    fn observe(&mut self, sample_seq: u64, value: Sample) {
+        let high = value > CLOCK_THRESHOLD;
+
+        if high && !self.high {
+            if let Some(last_edge) = self.last_edge {
+                self.period = Some(sample_seq - last_edge);
+            }
+
+            self.last_edge = Some(sample_seq);
+        }
+
+        self.high = high;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echoes_decay_by_feedback() {
+        let mut line = DelayLine::new(100);
+
+        let out = (0..25)
+            .map(|i| {
+                let input = if i == 0 { [1.0, -1.0] } else { [0.0, 0.0] };
+                echo(&mut line, input, 10.0, 0.5)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!([1.0, -1.0], out[10]);
+        assert_eq!([0.5, -0.5], out[20]);
+        assert_eq!([0.0, 0.0], out[15]);
+    }
+
+    #[test]
+    fn measures_clock_period() {
+        let mut clock = ClockTracker::default();
+
+        for seq in 0..1000 {
+            // high for the first 10 samples of every 300
+            clock.observe(seq, if seq % 300 < 10 { 1.0 } else { 0.0 });
+        }
+
+        assert_eq!(Some(300), clock.period);
+    }
+}
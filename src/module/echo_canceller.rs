@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use mixlab_protocol::{LineType, Terminal, EchoCancellerParams, EchoCancellerIndication};
+
+use crate::echo_cancel::EchoCanceller as Canceller;
+use crate::engine::{self, InputRef, OutputRef, Sample, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::module::ModuleT;
+
+// longest round trip and tail allowed, in milliseconds. the cost of every
+// tick grows with the tail, the delay only costs memory
+const MAX_DELAY_MS: f64 = 5000.0;
+const MAX_TAIL_MS: f64 = 1000.0;
+const MIN_TAIL_MS: f64 = 20.0;
+
+#[derive(Debug)]
+pub struct EchoCanceller {
+    params: EchoCancellerParams,
+    canceller: Canceller,
+    // mono return feed held back by the round trip delay
+    delay_line: VecDeque<Sample>,
+    // mono downmixes of one tick, and the guest with echo removed
+    reference: Vec<Sample>,
+    capture: Vec<Sample>,
+    cleaned: Vec<Sample>,
+    indication: EchoCancellerIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for EchoCanceller {
+    type Params = EchoCancellerParams;
+    type Indication = EchoCancellerIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = EchoCancellerIndication::default();
+
+        let module = EchoCanceller {
+            canceller: new_canceller(&params),
+            delay_line: new_delay_line(&params),
+            params,
+            reference: vec![0.0; SAMPLES_PER_TICK],
+            capture: vec![0.0; SAMPLES_PER_TICK],
+            cleaned: vec![0.0; SAMPLES_PER_TICK],
+            indication: indication.clone(),
+            inputs: vec![
+                LineType::Stereo.labeled("Guest"),
+                LineType::Stereo.labeled("Return"),
+            ],
+            outputs: vec![LineType::Stereo.labeled("Guest")],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        // the learned echo path belongs to the old delay, so either change
+        // starts learning over
+        if new_params != self.params {
+            self.canceller = new_canceller(&new_params);
+            self.delay_line = new_delay_line(&new_params);
+        }
+
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let guest = inputs[0].expect_stereo();
+        let ret = inputs[1].expect_stereo();
+        let output = outputs[0].expect_stereo();
+
+        // a guest's mic arrives as the same signal on both channels, and
+        // their speakers mix the return down as far as the mic can tell
+        downmix(guest, &mut self.capture);
+
+        for frame in ret.chunks(CHANNELS) {
+            self.delay_line.push_back(frame.iter().sum::<Sample>() / CHANNELS as Sample);
+        }
+
+        for sample in self.reference.iter_mut() {
+            *sample = self.delay_line.pop_front().unwrap_or(0.0);
+        }
+
+        self.canceller.process(&self.reference, &self.capture, &mut self.cleaned);
+
+        for (frame, sample) in output.chunks_mut(CHANNELS).zip(&self.cleaned) {
+            for out in frame {
+                *out = *sample;
+            }
+        }
+
+        let indication = EchoCancellerIndication {
+            reduction_db: self.canceller.reduction_db().round() as i32,
+            double_talk: self.canceller.double_talk(),
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.canceller.memory_usage() + self.delay_line.len() * mem::size_of::<Sample>()
+    }
+}
+
+fn new_canceller(params: &EchoCancellerParams) -> Canceller {
+    let tail_ms = params.tail_ms.max(MIN_TAIL_MS).min(MAX_TAIL_MS);
+    Canceller::new(ms_to_samples(tail_ms), SAMPLES_PER_TICK)
+}
+
+fn new_delay_line(params: &EchoCancellerParams) -> VecDeque<Sample> {
+    let delay = ms_to_samples(params.delay_ms.max(0.0).min(MAX_DELAY_MS));
+    let mut delay_line = VecDeque::with_capacity(delay + SAMPLES_PER_TICK);
+    delay_line.resize(delay, 0.0);
+    delay_line
+}
+
+fn ms_to_samples(ms: f64) -> usize {
+    (ms * SAMPLE_RATE as f64 / 1000.0).round() as usize
+}
+
+fn downmix(input: &[Sample], output: &mut [Sample]) {
+    for (frame, sample) in input.chunks(CHANNELS).zip(output.iter_mut()) {
+        *sample = frame.iter().sum::<Sample>() / CHANNELS as Sample;
+    }
+}
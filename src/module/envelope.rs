@@ -1,15 +1,24 @@
 use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
+use crate::midi::{MidiEvent, MidiSubscription};
 use crate::module::{ModuleT, LineType, Terminal};
 
-use mixlab_protocol::EnvelopeParams;
+use mixlab_protocol::{EnvelopeParams, EnvelopeCurve, EnvelopeTrigger};
 
 type SampleSeq = u64;
 
+// how sharply exponential stages bend, higher is quicker to start and slower
+// to finish
+const EXPONENTIAL_STEEPNESS: f64 = 5.0;
+
+// Amplitudes within states are already scaled by the velocity of the note
+// that started the envelope, which peaks at the velocity rather than 1.
 #[derive(Debug)]
 enum EnvelopeState {
     Initial,
-    TriggerOn {on: SampleSeq},
-    TriggerOff {off: SampleSeq, off_amplitude: f64},
+    // attack from where the envelope was, unless legato in which case it goes
+    // straight from there to the sustain level over the decay time
+    TriggerOn {on: SampleSeq, from: f64, velocity: f64, attack: bool},
+    TriggerOff {off: SampleSeq, off_amplitude: f64, velocity: f64},
 }
 
 type Ms = f64;
@@ -27,32 +36,50 @@ fn clamp(x: f64) -> f64 {
     }
 }
 
-fn invert(x: f64) -> f64 {
-    1.0 - x
+// how far through a stage of some duration, from 0 to 1
+fn progress(ms: Ms, duration: Ms) -> f64 {
+    if ms >= duration {
+        1.0
+    } else {
+        clamp(ms / duration)
+    }
+}
+
+// level partway through a stage moving from one amplitude to another
+fn stage(from: f64, to: f64, progress: f64, curve: EnvelopeCurve) -> f64 {
+    let shaped = match curve {
+        EnvelopeCurve::Linear => progress,
+        // a capacitor charging, scaled to arrive at the end of the stage
+        EnvelopeCurve::Exponential => {
+            (1.0 - (-EXPONENTIAL_STEEPNESS * progress).exp()) / (1.0 - (-EXPONENTIAL_STEEPNESS).exp())
+        }
+    };
+
+    from + (to - from) * shaped
 }
 
 fn amplitude(params: &EnvelopeParams, state: &EnvelopeState, t: SampleSeq) -> f64 {
-    match state {
+    match *state {
         EnvelopeState::Initial => 0.0,
-        EnvelopeState::TriggerOn {on} => {
-            let ms_since_on = sample_seq_duration_ms(*on, t);
+        EnvelopeState::TriggerOn {on, from, velocity, attack} => {
+            let ms_since_on = sample_seq_duration_ms(on, t);
+            let sustain = params.sustain_amplitude * velocity;
 
-            if ms_since_on < params.attack_ms {
+            if !attack {
+                // Legato, heading for sustain from wherever it was
+                stage(from, sustain, progress(ms_since_on, params.decay_ms), params.decay_curve)
+            } else if ms_since_on < params.attack_ms {
                 // Currently in attack phase
-                1.0 / params.attack_ms * ms_since_on
+                stage(from, velocity, progress(ms_since_on, params.attack_ms), params.attack_curve)
             } else {
                 // In decay/sustain phase
                 let ms_since_decay_started = ms_since_on - params.attack_ms;
-                let decay_amplitude = invert(clamp(1.0 / params.decay_ms * ms_since_decay_started));
-
-                params.sustain_amplitude + ((1.0 - params.sustain_amplitude) * decay_amplitude)
+                stage(velocity, sustain, progress(ms_since_decay_started, params.decay_ms), params.decay_curve)
             }
         }
-        EnvelopeState::TriggerOff {off, off_amplitude} => {
-            let ms_since_off = sample_seq_duration_ms(*off, t);
-            let release_amplitude = invert(clamp(1.0 / params.release_ms * ms_since_off));
-
-            off_amplitude * release_amplitude
+        EnvelopeState::TriggerOff {off, off_amplitude, ..} => {
+            let ms_since_off = sample_seq_duration_ms(off, t);
+            stage(off_amplitude, 0.0, progress(ms_since_off, params.release_ms), params.release_curve)
         }
     }
 }
@@ -61,6 +88,9 @@ fn amplitude(params: &EnvelopeParams, state: &EnvelopeState, t: SampleSeq) -> f6
 pub struct Envelope {
     params: EnvelopeParams,
     state: EnvelopeState,
+    midi: MidiSubscription,
+    // number of bound MIDI notes currently held
+    midi_notes: usize,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
 }
@@ -70,11 +100,16 @@ impl ModuleT for Envelope {
     type Indication = ();
     type Event = ();
 
-    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
         (Self {
             params,
             state: EnvelopeState::Initial,
-            inputs: vec![LineType::Mono.unlabeled()],
+            midi: ctx.subscribe_midi(),
+            midi_notes: 0,
+            inputs: vec![
+                LineType::Mono.unlabeled(),
+                LineType::Mono.labeled("Velocity"),
+            ],
             outputs: vec![LineType::Mono.unlabeled()],
         }, ())
     }
@@ -84,31 +119,100 @@ impl ModuleT for Envelope {
     }
 
     fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        if self.params.midi != params.midi {
+            self.midi_notes = 0;
+        }
+
         self.params = params;
         None
     }
 
     fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
         let input = inputs[0].expect_mono();
+
+        let velocity_input = if inputs[1].connected() {
+            Some(inputs[1].expect_mono())
+        } else {
+            None
+        };
+
         let output = outputs[0].expect_mono();
 
+        let mut midi_events = self.midi.tick_events().into_iter().peekable();
+
         let len = input.len();
         for i in 0..len {
             let sample_seq = t + i as u64;
 
+            // velocity of any bound MIDI note played at this sample
+            let mut note_on = None;
+
+            // Apply any MIDI notes due at this sample
+            while let Some((_, event)) = midi_events.peek().filter(|(offset, _)| *offset <= i) {
+                if let Some(binding) = &self.params.midi {
+                    match *event {
+                        MidiEvent::NoteOn { channel, note, velocity } if binding.matches(channel, note) => {
+                            self.midi_notes += 1;
+                            note_on = Some(velocity);
+                        }
+                        MidiEvent::NoteOff { channel, note } if binding.matches(channel, note) => {
+                            self.midi_notes = self.midi_notes.saturating_sub(1);
+                        }
+                        _ => {}
+                    }
+                }
+
+                midi_events.next();
+            }
+
+            let gate_on = input[i] == 1.0 || self.midi_notes > 0;
+            let gate_off = input[i] == 0.0 && self.midi_notes == 0;
+
+            // the velocity input wins over MIDI, and a gate alone plays at
+            // full velocity
+            let note_velocity = velocity_input.map(|velocity| clamp(velocity[i] as f64))
+                .or_else(|| note_on.map(|velocity| velocity as f64 / 127.0))
+                .unwrap_or(1.0);
+
+            let current = amplitude(&self.params, &self.state, sample_seq);
+
+            let attack = EnvelopeState::TriggerOn {
+                on: sample_seq,
+                from: current,
+                velocity: note_velocity,
+                attack: true,
+            };
+
             // First, process input
             match self.state {
-                EnvelopeState::Initial | EnvelopeState::TriggerOff { .. } => {
-                    if input[i] == 1.0 {
-                        self.state = EnvelopeState::TriggerOn { on: sample_seq };
+                EnvelopeState::Initial => {
+                    if gate_on {
+                        self.state = attack;
                     }
                 }
-                EnvelopeState::TriggerOn {..} => {
-                    if input[i] == 0.0 {
+                EnvelopeState::TriggerOff { velocity, .. } => {
+                    if gate_on {
+                        self.state = match self.params.trigger {
+                            EnvelopeTrigger::Legato if current > 0.0 => EnvelopeState::TriggerOn {
+                                on: sample_seq,
+                                from: current,
+                                velocity,
+                                attack: false,
+                            },
+                            _ => attack,
+                        };
+                    }
+                }
+                EnvelopeState::TriggerOn { velocity, .. } => {
+                    if gate_off {
                         self.state = EnvelopeState::TriggerOff {
                             off: sample_seq,
-                            off_amplitude: amplitude(&self.params, &self.state, sample_seq)
+                            off_amplitude: current,
+                            velocity,
                         };
+                    } else if note_on.is_some() && self.params.trigger == EnvelopeTrigger::Retrigger {
+                        // a new note played over a held one
+                        self.state = attack;
                     }
                 }
             }
@@ -127,3 +231,44 @@ impl ModuleT for Envelope {
         &self.outputs
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: f64) -> SampleSeq {
+        (ms / 1000.0 * SAMPLE_RATE as f64) as SampleSeq
+    }
+
+    #[test]
+    fn stages_arrive_whatever_their_curve() {
+        for &curve in &[EnvelopeCurve::Linear, EnvelopeCurve::Exponential] {
+            assert_eq!(0.2, stage(0.2, 0.9, 0.0, curve));
+            assert!((stage(0.2, 0.9, 1.0, curve) - 0.9).abs() < 1e-9);
+        }
+
+        // exponential stages cover most of their distance early on
+        assert!(stage(0.0, 1.0, 0.25, EnvelopeCurve::Exponential) > 0.7);
+        assert!(stage(1.0, 0.0, 0.25, EnvelopeCurve::Exponential) < 0.3);
+    }
+
+    #[test]
+    fn velocity_scales_peak_and_sustain() {
+        let params = EnvelopeParams::default();
+        let state = EnvelopeState::TriggerOn { on: 0, from: 0.0, velocity: 0.5, attack: true };
+
+        assert!((amplitude(&params, &state, ms(params.attack_ms)) - 0.5).abs() < 1e-3);
+        assert!((amplitude(&params, &state, ms(2000.0)) - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn legato_skips_the_attack() {
+        let params = EnvelopeParams { trigger: EnvelopeTrigger::Legato, ..EnvelopeParams::default() };
+        let state = EnvelopeState::TriggerOn { on: 0, from: 0.6, velocity: 1.0, attack: false };
+
+        // straight from where it was towards sustain, never up to the peak
+        let amplitudes = (0..ms(1000.0)).map(|t| amplitude(&params, &state, t)).collect::<Vec<_>>();
+        assert!(amplitudes.iter().all(|amplitude| *amplitude >= 0.6 && *amplitude < 0.8 + 1e-9));
+        assert!((amplitudes.last().unwrap() - 0.8).abs() < 1e-9);
+    }
+}
@@ -2,8 +2,8 @@ use std::f64;
 
 use mixlab_protocol::EqThreeParams;
 
-use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
-use crate::module::{ModuleT, LineType, Terminal};
+use crate::engine::{self, InputRef, OutputRef, Smoothed, SAMPLE_RATE, ModuleHost, DynModuleHost};
+use crate::module::{self, ModuleT, LineType, Terminal};
 
 const FREQ_LO: f64 = 420.0;
 const FREQ_HI: f64 = 2700.0;
@@ -13,6 +13,9 @@ const VSA: f64 = 1.0 / 4294967295.0; // Very small amount (Denormal Fix)
 #[derive(Debug)]
 pub struct EqThree {
     params: EqThreeParams,
+    gain_lo: Smoothed,
+    gain_mid: Smoothed,
+    gain_hi: Smoothed,
 
     // filter 1 (low band)
     lo: LowPass,
@@ -35,6 +38,9 @@ impl ModuleT for EqThree {
         let hi = LowPass::new(FREQ_HI);
 
         let eq_three = Self {
+            gain_lo: Smoothed::with_default(params.gain_lo.to_linear()),
+            gain_mid: Smoothed::with_default(params.gain_mid.to_linear()),
+            gain_hi: Smoothed::with_default(params.gain_hi.to_linear()),
             params,
             lo,
             hi,
@@ -51,6 +57,9 @@ impl ModuleT for EqThree {
     }
 
     fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.gain_lo.set(params.gain_lo.to_linear());
+        self.gain_mid.set(params.gain_mid.to_linear());
+        self.gain_hi.set(params.gain_hi.to_linear());
         self.params = params;
         None
     }
@@ -59,10 +68,6 @@ impl ModuleT for EqThree {
         let input = inputs[0].expect_mono();
         let output = outputs[0].expect_mono();
 
-        let gain_lo = self.params.gain_lo.to_linear();
-        let gain_mid = self.params.gain_mid.to_linear();
-        let gain_hi = self.params.gain_hi.to_linear();
-
         for (input, output) in input.iter().copied().zip(output.iter_mut()) {
             let sample = input as f64;
 
@@ -78,9 +83,9 @@ impl ModuleT for EqThree {
 
             // apply gain
 
-            let lo = lo * gain_lo;
-            let mid = mid * gain_mid;
-            let hi = hi * gain_hi;
+            let lo = lo * self.gain_lo.next();
+            let mid = mid * self.gain_mid.next();
+            let hi = hi * self.gain_hi.next();
 
             *output = (lo + mid + hi) as f32;
         }
@@ -95,6 +100,10 @@ impl ModuleT for EqThree {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn host(host: ModuleHost<Self>) -> DynModuleHost {
+        module::parallel(host)
+    }
 }
 
 #[derive(Debug)]
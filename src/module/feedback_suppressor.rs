@@ -0,0 +1,116 @@
+use mixlab_protocol::{LineType, Terminal, FeedbackSuppressorParams, FeedbackSuppressorIndication, FeedbackSuppressorCommand, FeedbackNotch, ModuleCommand};
+
+use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
+use crate::feedback::{FeedbackSuppressor as Suppressor, Settings};
+use crate::module::ModuleT;
+
+// every notch costs a biquad per sample, so the count is held to this
+const MAX_NOTCHES: usize = 16;
+
+const MIN_SENSITIVITY_DB: f64 = 6.0;
+const MAX_SENSITIVITY_DB: f64 = 40.0;
+const MAX_DEPTH_DB: f64 = 40.0;
+
+#[derive(Debug)]
+pub struct FeedbackSuppressor {
+    params: FeedbackSuppressorParams,
+    suppressor: Suppressor,
+    indication: FeedbackSuppressorIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for FeedbackSuppressor {
+    type Params = FeedbackSuppressorParams;
+    type Indication = FeedbackSuppressorIndication;
+    type Event = FeedbackSuppressorCommand;
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = FeedbackSuppressorIndication::default();
+
+        let module = FeedbackSuppressor {
+            suppressor: Suppressor::new(SAMPLE_RATE, settings(&params)),
+            params,
+            indication: indication.clone(),
+            inputs: vec![LineType::Mono.unlabeled()],
+            outputs: vec![LineType::Mono.unlabeled()],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.suppressor.set_settings(settings(&new_params));
+        self.params = new_params;
+        self.indicate()
+    }
+
+    fn command_event(command: ModuleCommand) -> Option<FeedbackSuppressorCommand> {
+        match command {
+            ModuleCommand::FeedbackSuppressor(command) => Some(command),
+            _ => None,
+        }
+    }
+
+    fn receive_event(&mut self, command: FeedbackSuppressorCommand) {
+        match command {
+            FeedbackSuppressorCommand::ClearNotches => self.suppressor.clear(),
+        }
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_mono();
+        let output = outputs[0].expect_mono();
+
+        self.suppressor.process(input, output);
+
+        self.indicate()
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.suppressor.memory_usage()
+    }
+}
+
+impl FeedbackSuppressor {
+    fn indicate(&mut self) -> Option<FeedbackSuppressorIndication> {
+        let mut notches = self.suppressor.notches().iter()
+            .map(|notch| FeedbackNotch {
+                freq_hz: notch.freq.round() as u32,
+                depth_db: notch.depth_db.round() as u32,
+            })
+            .collect::<Vec<_>>();
+
+        notches.sort_by_key(|notch| notch.freq_hz);
+
+        let indication = FeedbackSuppressorIndication { notches };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+}
+
+fn settings(params: &FeedbackSuppressorParams) -> Settings {
+    Settings {
+        sensitivity_db: params.sensitivity_db.max(MIN_SENSITIVITY_DB).min(MAX_SENSITIVITY_DB),
+        max_notches: params.max_notches.min(MAX_NOTCHES),
+        max_depth_db: params.max_depth_db.max(0.0).min(MAX_DEPTH_DB),
+        release_secs: params.release_secs.max(0.0),
+    }
+}
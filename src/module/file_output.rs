@@ -0,0 +1,376 @@
+use std::borrow::Cow;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use fdk_aac::enc as aac;
+
+use mixlab_codec::ffmpeg::PictureSettings;
+use mixlab_mux::mkv::{self, MkvMux, MkvParams};
+use mixlab_mux::mp4::{Mp4Mux, Mp4Params, TrackData, AdtsFrame};
+use mixlab_protocol::{LineType, Terminal, FileOutputParams, FileOutputIndication, FileOutputCommand, FileFormat, ModuleCommand};
+use mixlab_util::time::{MediaTime, MediaDuration};
+
+use crate::engine::{self, InputRef, OutputRef, CHANNELS, SAMPLE_RATE, TICKS_PER_SECOND};
+use crate::module::ModuleT;
+use crate::module::monitor::Tick;
+use crate::output_file::{expand_filename, unix_time, unused_path};
+use crate::video::encode::{EncodeStream, AudioCtx, AudioParams, VideoCtx, VideoParams, StreamSegment, Profile};
+
+const OUTPUT_WIDTH: usize = 1280;
+const OUTPUT_HEIGHT: usize = 720;
+
+// key frames every couple of seconds keep seeking around the file quick
+const KEYFRAME_INTERVAL_SECS: i64 = 2;
+
+#[derive(Debug)]
+pub struct FileOutput {
+    params: FileOutputParams,
+    dir: PathBuf,
+    recording: Option<Recording>,
+    // name of the file being written, or last written
+    file: Option<String>,
+    error: bool,
+    indication: FileOutputIndication,
+    inputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+struct Recording {
+    writer: Writer,
+    epoch: Option<MediaTime>,
+    ticks: u64,
+}
+
+impl ModuleT for FileOutput {
+    type Params = FileOutputParams;
+    type Indication = FileOutputIndication;
+    type Event = FileOutputCommand;
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = FileOutputIndication::default();
+
+        let module = FileOutput {
+            params,
+            dir: ctx.project().recordings_dir(),
+            recording: None,
+            file: None,
+            error: false,
+            indication: indication.clone(),
+            inputs: vec![
+                LineType::Video.labeled("Video"),
+                LineType::Stereo.labeled("Audio"),
+            ],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn command_event(command: ModuleCommand) -> Option<FileOutputCommand> {
+        match command {
+            ModuleCommand::FileOutput(command) => Some(command),
+            _ => None,
+        }
+    }
+
+    fn receive_event(&mut self, command: FileOutputCommand) {
+        match command {
+            FileOutputCommand::Start => {
+                if self.recording.is_none() {
+                    self.start();
+                }
+            }
+            FileOutputCommand::Stop => {
+                // the writer finishes off the file once it has drained
+                self.recording = None;
+            }
+        }
+    }
+
+    fn run_tick(&mut self, time: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        let (video, audio) = match inputs {
+            [video, audio] => (video.expect_video(), audio.expect_stereo()),
+            _ => unreachable!()
+        };
+
+        if let Some(recording) = &mut self.recording {
+            let absolute_timestamp = MediaTime::new(time as i64, SAMPLE_RATE as i64);
+            let epoch = *recording.epoch.get_or_insert(absolute_timestamp);
+
+            let alive = recording.writer.send(Tick {
+                timestamp: absolute_timestamp.remove_epoch(epoch),
+                audio: audio.to_vec(),
+                video: video.cloned(),
+            });
+
+            recording.ticks += 1;
+
+            if !alive {
+                self.recording = None;
+                self.error = true;
+            }
+        }
+
+        self.indicate()
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &[]
+    }
+
+    fn frame_rate(&self) -> Option<u32> {
+        Some(engine::DEFAULT_FPS)
+    }
+}
+
+impl FileOutput {
+    fn start(&mut self) {
+        let params = &self.params;
+
+        let extension = match params.format {
+            FileFormat::Mp4 => "mp4",
+            FileFormat::Mkv => "mkv",
+        };
+
+        let name = expand_filename(&params.filename, unix_time());
+        let path = unused_path(&self.dir, &name, extension);
+
+        let writer = Writer::start(path.clone(), params.format);
+
+        self.file = path.file_name().map(|name| name.to_string_lossy().into_owned());
+        self.error = false;
+
+        self.recording = Some(Recording {
+            writer,
+            epoch: None,
+            ticks: 0,
+        });
+    }
+
+    fn indicate(&mut self) -> Option<FileOutputIndication> {
+        let (elapsed_secs, bytes) = match &self.recording {
+            Some(recording) => (recording.ticks / TICKS_PER_SECOND as u64, recording.writer.bytes()),
+            None => (self.indication.elapsed_secs, self.indication.bytes),
+        };
+
+        let indication = FileOutputIndication {
+            recording: self.recording.is_some(),
+            file: self.file.clone(),
+            elapsed_secs,
+            bytes,
+            error: self.error,
+        };
+
+        // the file grows nearly every tick, but the size is only worth
+        // sending along with the elapsed time
+        let unchanged = FileOutputIndication { bytes: self.indication.bytes, ..indication.clone() } == self.indication;
+
+        if unchanged {
+            None
+        } else {
+            self.indication = indication.clone();
+            Some(indication)
+        }
+    }
+}
+
+// encodes and writes ticks to a file on its own thread, which finishes the
+// file and stops when dropped
+#[derive(Debug)]
+struct Writer {
+    tx: mpsc::SyncSender<Tick>,
+    bytes: Arc<AtomicU64>,
+}
+
+impl Writer {
+    fn start(path: PathBuf, format: FileFormat) -> Self {
+        let (tx, rx) = mpsc::sync_channel(2);
+        let bytes = Arc::new(AtomicU64::new(0));
+
+        thread::spawn({
+            let bytes = bytes.clone();
+            move || {
+                if let Err(e) = run_writer_thread(&path, format, &bytes, rx) {
+                    eprintln!("FileOutput: could not write {}: {}", path.display(), e);
+                }
+            }
+        });
+
+        Writer { tx, bytes }
+    }
+
+    fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    // returns false once the writer thread has given up
+    fn send(&mut self, tick: Tick) -> bool {
+        use mpsc::TrySendError;
+
+        match self.tx.try_send(tick) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                println!("FileOutput: writer not keeping up, dropping tick");
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+enum Muxer {
+    Mp4(Mp4Mux),
+    Mkv(MkvMux),
+}
+
+fn run_writer_thread(path: &Path, format: FileFormat, bytes: &AtomicU64, rx: mpsc::Receiver<Tick>) -> Result<(), io::Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    let mut file = BufWriter::new(file);
+
+    // the mp4 muxer strips ADTS headers, matroska wants raw frames
+    let transport = match format {
+        FileFormat::Mp4 => aac::Transport::Adts,
+        FileFormat::Mkv => aac::Transport::Raw,
+    };
+
+    let audio_ctx = AudioCtx::new(AudioParams {
+        bit_rate: aac::BitRate::Cbr(192000),
+        sample_rate: SAMPLE_RATE,
+        transport,
+    });
+
+    let picture = PictureSettings::yuv420p(OUTPUT_WIDTH, OUTPUT_HEIGHT);
+
+    let video_ctx = VideoCtx::new(VideoParams {
+        picture: picture.clone(),
+        time_base: SAMPLE_RATE,
+        profile: Profile::Stream,
+        keyframe_interval: Some(MediaDuration::new(KEYFRAME_INTERVAL_SECS, 1)),
+    });
+
+    let mut dcr = vec![];
+    video_ctx.decoder_configuration_record().write_to(&mut dcr);
+
+    let (mut muxer, header) = match format {
+        FileFormat::Mp4 => {
+            let (mux, init) = Mp4Mux::new(Mp4Params {
+                timescale: SAMPLE_RATE as u32,
+                width: picture.width as u32,
+                height: picture.height as u32,
+                dcr: Cow::Owned(dcr),
+            });
+
+            (Muxer::Mp4(mux), init.to_vec())
+        }
+        FileFormat::Mkv => {
+            let (mux, header) = MkvMux::new(MkvParams {
+                width: picture.width as u32,
+                height: picture.height as u32,
+                sample_rate: SAMPLE_RATE as u32,
+                channels: CHANNELS as u32,
+                dcr: Cow::Owned(dcr),
+                asc: Cow::Owned(audio_ctx.configuration_data().to_vec()),
+            });
+
+            (Muxer::Mkv(mux), header)
+        }
+    };
+
+    file.write_all(&header)?;
+    bytes.fetch_add(header.len() as u64, Ordering::Relaxed);
+
+    let mut encode = EncodeStream::new(audio_ctx, video_ctx);
+
+    // decode timestamp of the first key frame, which the file starts from
+    let mut start: Option<MediaTime> = None;
+    let mut out = Vec::new();
+
+    while let Ok(tick) = rx.recv() {
+        encode.send_audio(&tick.audio);
+
+        if let Some(video_frame) = tick.video {
+            let frame_timestamp = tick.timestamp + video_frame.tick_offset;
+            let frame = video_frame.data.decoded.clone();
+
+            encode.send_video(frame_timestamp, video_frame.data.duration_hint, frame);
+        }
+
+        encode.barrier(tick.timestamp);
+
+        while let Some(segment) = encode.recv_segment() {
+            if let StreamSegment::Video(video) = &segment {
+                if video.frame.is_key_frame && start.is_none() {
+                    start = Some(video.decode_timestamp);
+                }
+            }
+
+            // nothing before the first key frame can be decoded
+            let start = match start {
+                Some(start) => start,
+                None => continue,
+            };
+
+            match &mut muxer {
+                Muxer::Mp4(mux) => {
+                    let (duration, track_data) = match segment {
+                        StreamSegment::Audio(audio) => {
+                            (audio.duration, TrackData::Audio(AdtsFrame(audio.frame)))
+                        }
+                        StreamSegment::Video(video) => {
+                            (video.duration, TrackData::Video(video.frame))
+                        }
+                    };
+
+                    out.extend_from_slice(&mux.write_track(duration, &track_data));
+                }
+                Muxer::Mkv(mux) => {
+                    let millis = |timestamp: MediaTime| (timestamp - start).round_to_base(1000).max(0) as u64;
+
+                    match segment {
+                        StreamSegment::Audio(audio) => {
+                            mux.write(&mut out, mkv::Track::Audio, millis(audio.decode_timestamp), true, &audio.frame);
+                        }
+                        StreamSegment::Video(video) => {
+                            let timestamp = millis(video.decode_timestamp + video.frame.composition_time);
+                            mux.write(&mut out, mkv::Track::Video, timestamp, video.frame.is_key_frame, &video.frame.data);
+                        }
+                    }
+                }
+            }
+
+            if !out.is_empty() {
+                file.write_all(&out)?;
+                bytes.fetch_add(out.len() as u64, Ordering::Relaxed);
+                out.clear();
+            }
+        }
+    }
+
+    if let Muxer::Mkv(mux) = &mut muxer {
+        mux.finish(&mut out);
+        file.write_all(&out)?;
+        bytes.fetch_add(out.len() as u64, Ordering::Relaxed);
+    }
+
+    file.flush()
+}
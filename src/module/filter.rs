@@ -0,0 +1,206 @@
+use std::f64;
+
+use mixlab_protocol::{FilterParams, FilterMode, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, SAMPLE_RATE, ModuleHost, DynModuleHost};
+use crate::module::{self, ModuleT};
+
+const MIN_CUTOFF: f64 = 20.0;
+// just short of nyquist, tan() blows up beyond it
+const MAX_CUTOFF: f64 = SAMPLE_RATE as f64 * 0.49;
+
+// keeps some damping at full resonance so the filter rings rather than
+// running away
+const MAX_RESONANCE: f64 = 0.99;
+
+#[derive(Debug)]
+pub struct Filter {
+    params: FilterParams,
+    svf: StateVariable,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Filter {
+    type Params = FilterParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Filter {
+            params,
+            svf: StateVariable::default(),
+            inputs: vec![
+                LineType::Mono.labeled("Input"),
+                LineType::Mono.labeled("Cutoff"),
+                LineType::Mono.labeled("Resonance"),
+            ],
+            outputs: vec![LineType::Mono.unlabeled()],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_mono();
+
+        let cutoff_mod = if inputs[1].connected() {
+            Some(inputs[1].expect_mono())
+        } else {
+            None
+        };
+
+        let resonance_mod = if inputs[2].connected() {
+            Some(inputs[2].expect_mono())
+        } else {
+            None
+        };
+
+        let output = outputs[0].expect_mono();
+
+        let FilterParams { mode, cutoff, resonance, cutoff_mod: cutoff_mod_depth } = self.params;
+
+        // coefficients only need recalculating per sample when modulated
+        let mut coeffs = Coefficients::new(cutoff, resonance);
+        let modulated = cutoff_mod.is_some() || resonance_mod.is_some();
+
+        for i in 0..input.len() {
+            if modulated {
+                let cutoff = cutoff_mod
+                    .map(|cv| cutoff * 2f64.powf(cv[i] as f64 * cutoff_mod_depth))
+                    .unwrap_or(cutoff);
+
+                let resonance = resonance_mod
+                    .map(|cv| resonance + cv[i] as f64)
+                    .unwrap_or(resonance);
+
+                coeffs = Coefficients::new(cutoff, resonance);
+            }
+
+            let out = self.svf.pump(&coeffs, input[i] as f64);
+
+            output[i] = match mode {
+                FilterMode::LowPass => out.low,
+                FilterMode::HighPass => out.high,
+                FilterMode::BandPass => out.band,
+            } as Sample;
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn host(host: ModuleHost<Self>) -> DynModuleHost {
+        module::parallel(host)
+    }
+}
+
+#[derive(Debug)]
+struct Coefficients {
+    k: f64,
+    a1: f64,
+    a2: f64,
+    a3: f64,
+}
+
+impl Coefficients {
+    fn new(cutoff: f64, resonance: f64) -> Self {
+        let cutoff = cutoff.max(MIN_CUTOFF).min(MAX_CUTOFF);
+        let resonance = resonance.max(0.0).min(MAX_RESONANCE);
+
+        let g = f64::tan(f64::consts::PI * cutoff / SAMPLE_RATE as f64);
+        let k = 2.0 * (1.0 - resonance);
+
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        Coefficients { k, a1, a2, a3 }
+    }
+}
+
+struct FilterOutput {
+    low: f64,
+    band: f64,
+    high: f64,
+}
+
+// trapezoidal integrated state variable filter, stable under fast modulation
+// of cutoff. see Andrew Simper, "Linear Trapezoidal Integrated State Variable
+// Filter With Low Noise Optimisation"
+#[derive(Debug, Default)]
+struct StateVariable {
+    ic1eq: f64,
+    ic2eq: f64,
+}
+
+impl StateVariable {
+    fn pump(&mut self, c: &Coefficients, v0: f64) -> FilterOutput {
+        let v3 = v0 - self.ic2eq;
+        let v1 = c.a1 * self.ic1eq + c.a2 * v3;
+        let v2 = self.ic2eq + c.a2 * self.ic1eq + c.a3 * v3;
+
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        FilterOutput {
+            low: v2,
+            // normalised to unity gain at the cutoff frequency
+            band: c.k * v1,
+            high: v0 - c.k * v1 - v2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // peak output amplitude of a sine at `freq` once the filter has settled
+    fn response(mode: FilterMode, cutoff: f64, freq: f64) -> f64 {
+        let coeffs = Coefficients::new(cutoff, 0.0);
+        let mut svf = StateVariable::default();
+
+        (0..SAMPLE_RATE)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let out = svf.pump(&coeffs, f64::sin(2.0 * f64::consts::PI * freq * t));
+
+                match mode {
+                    FilterMode::LowPass => out.low,
+                    FilterMode::HighPass => out.high,
+                    FilterMode::BandPass => out.band,
+                }
+            })
+            .skip(SAMPLE_RATE / 2)
+            .fold(0.0, |peak: f64, sample| peak.max(sample.abs()))
+    }
+
+    #[test]
+    fn passes_and_attenuates_bands() {
+        assert!(response(FilterMode::LowPass, 1000.0, 100.0) > 0.9);
+        assert!(response(FilterMode::LowPass, 1000.0, 10000.0) < 0.05);
+
+        assert!(response(FilterMode::HighPass, 1000.0, 10000.0) > 0.9);
+        assert!(response(FilterMode::HighPass, 1000.0, 100.0) < 0.05);
+
+        assert!(response(FilterMode::BandPass, 1000.0, 1000.0) > 0.9);
+        assert!(response(FilterMode::BandPass, 1000.0, 100.0) < 0.25);
+    }
+}
@@ -2,8 +2,8 @@ use std::f64;
 
 use mixlab_protocol::{FmSineParams, LineType, Terminal};
 
-use crate::engine::{self, Sample, InputRef, OutputRef, SAMPLE_RATE, CHANNELS};
-use crate::module::ModuleT;
+use crate::engine::{self, Sample, InputRef, OutputRef, SAMPLE_RATE, CHANNELS, ModuleHost, DynModuleHost};
+use crate::module::{self, ModuleT};
 
 #[derive(Debug)]
 pub struct FmSine {
@@ -62,4 +62,8 @@ impl ModuleT for FmSine {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn host(host: ModuleHost<Self>) -> DynModuleHost {
+        module::parallel(host)
+    }
 }
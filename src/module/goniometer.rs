@@ -0,0 +1,192 @@
+use std::mem;
+
+use mixlab_protocol::{GoniometerIndication, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::module::{ModuleT, PriorityClass};
+
+// ticks between indications, the same rate the plotter redraws at
+const INDICATION_TICKS: u64 = 6;
+
+// most points sent in an indication, plenty to draw a trace without sending
+// every sample
+const POINTS: usize = 512;
+
+// correlation is averaged over about this long, as a correlation meter's is
+const CORRELATION_SECS: f64 = 0.3;
+
+// below this power, about -80dBFS, there is nothing to correlate and the
+// correlation reads 0
+const SILENCE: f64 = 1e-8;
+
+#[derive(Debug)]
+pub struct Goniometer {
+    correlation: Correlation,
+    // every frame since the last indication
+    frames: Vec<(Sample, Sample)>,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Goniometer {
+    type Params = ();
+    type Indication = GoniometerIndication;
+    type Event = ();
+
+    fn create(_: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Goniometer {
+            correlation: Correlation::new(SAMPLE_RATE),
+            frames: Vec::with_capacity(SAMPLES_PER_TICK * INDICATION_TICKS as usize),
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![],
+        };
+
+        (module, GoniometerIndication::default())
+    }
+
+    fn params(&self) -> Self::Params {
+        ()
+    }
+
+    fn update(&mut self, _: Self::Params) -> Option<Self::Indication> {
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+
+        self.correlation.process(input);
+        self.frames.extend(input.chunks(2).map(|frame| (frame[0], frame[1])));
+
+        if (t / SAMPLES_PER_TICK as u64) % INDICATION_TICKS != 0 {
+            return None;
+        }
+
+        let indication = GoniometerIndication {
+            points: thin(&self.frames),
+            correlation: self.correlation.coefficient(),
+        };
+
+        self.frames.clear();
+
+        Some(indication)
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.frames.capacity() * mem::size_of::<(Sample, Sample)>()
+    }
+
+    fn priority(&self) -> PriorityClass {
+        PriorityClass::UiFeedback
+    }
+}
+
+// Pearson correlation of left with right over a moving window. It stays
+// close to 1 for mono and near 0 for wide stereo, and goes negative when the
+// channels are out of phase and would cancel in a mono downmix.
+#[derive(Debug)]
+struct Correlation {
+    coeff: f64,
+    left_right: f64,
+    left_square: f64,
+    right_square: f64,
+}
+
+impl Correlation {
+    fn new(sample_rate: usize) -> Self {
+        Correlation {
+            coeff: 1.0 - (-1.0 / (CORRELATION_SECS * sample_rate as f64)).exp(),
+            left_right: 0.0,
+            left_square: 0.0,
+            right_square: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: &[Sample]) {
+        for frame in input.chunks(2) {
+            let left = frame[0] as f64;
+            let right = frame[1] as f64;
+
+            self.left_right += (left * right - self.left_right) * self.coeff;
+            self.left_square += (left * left - self.left_square) * self.coeff;
+            self.right_square += (right * right - self.right_square) * self.coeff;
+        }
+    }
+
+    fn coefficient(&self) -> f32 {
+        let power = (self.left_square * self.right_square).sqrt();
+
+        if power < SILENCE {
+            return 0.0;
+        }
+
+        (self.left_right / power).max(-1.0).min(1.0) as f32
+    }
+}
+
+// every nth frame, for at most POINTS of them
+fn thin(frames: &[(Sample, Sample)]) -> Vec<(Sample, Sample)> {
+    let step = (frames.len() + POINTS - 1) / POINTS;
+
+    frames.iter()
+        .step_by(step.max(1))
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64;
+
+    use super::*;
+
+    fn sine(secs: f64, right_gain: f64) -> Vec<Sample> {
+        (0..(secs * SAMPLE_RATE as f64) as usize)
+            .flat_map(|i| {
+                let value = 0.5 * (2.0 * f64::consts::PI * 440.0 * i as f64 / SAMPLE_RATE as f64).sin();
+                vec![value as Sample, (value * right_gain) as Sample]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mono_correlates() {
+        let mut correlation = Correlation::new(SAMPLE_RATE);
+        correlation.process(&sine(1.0, 0.5));
+
+        assert!(correlation.coefficient() > 0.99, "correlation: {}", correlation.coefficient());
+    }
+
+    #[test]
+    fn inverted_channel_anticorrelates() {
+        let mut correlation = Correlation::new(SAMPLE_RATE);
+        correlation.process(&sine(1.0, -1.0));
+
+        assert!(correlation.coefficient() < -0.99, "correlation: {}", correlation.coefficient());
+    }
+
+    #[test]
+    fn silence_reads_zero() {
+        let mut correlation = Correlation::new(SAMPLE_RATE);
+        correlation.process(&vec![0.0; 2 * SAMPLES_PER_TICK]);
+
+        assert_eq!(0.0, correlation.coefficient());
+    }
+
+    #[test]
+    fn thinning_keeps_at_most_points() {
+        let frames = vec![(0.0, 0.0); SAMPLES_PER_TICK * INDICATION_TICKS as usize];
+        let points = thin(&frames);
+
+        assert!(points.len() <= POINTS && points.len() > POINTS / 2);
+        assert!(thin(&[]).is_empty());
+    }
+}
@@ -0,0 +1,531 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::mem;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use bytes::Bytes;
+use fdk_aac::enc as aac;
+
+use mixlab_codec::ffmpeg::PictureSettings;
+use mixlab_mux::mp4::{Mp4Mux, Mp4Params, TrackData, AdtsFrame};
+use mixlab_protocol::{LineType, Terminal, HlsOutputParams, HlsOutputIndication, HlsFormat};
+use mixlab_util::time::{MediaTime, MediaDuration};
+
+use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
+use crate::module::ModuleT;
+use crate::module::monitor::Tick;
+use crate::mpegts::write::TsWriter;
+use crate::video::encode::{EncodeStream, AudioCtx, AudioParams, VideoCtx, VideoParams, StreamSegment, Profile};
+
+const OUTPUT_WIDTH: usize = 1280;
+const OUTPUT_HEIGHT: usize = 720;
+
+// segments kept after dropping off the end of the playlist, for viewers
+// still fetching them
+const SEGMENTS_RETAINED: usize = 3;
+
+lazy_static::lazy_static! {
+    static ref PLAYLISTS: Mutex<HashMap<String, PlaylistRef>> = Mutex::new(HashMap::new());
+}
+
+type PlaylistRef = Arc<Mutex<Playlist>>;
+
+/// Serves a file of the named stream, returning its content type and data.
+/// Private streams are only served to viewers with a link.
+pub fn serve(name: &str, file: &str, via_link: bool) -> Option<(&'static str, Bytes)> {
+    let playlist = PLAYLISTS.lock().unwrap().get(name)?.clone();
+    let playlist = playlist.lock().unwrap();
+
+    if playlist.private && !via_link {
+        return None;
+    }
+
+    if file == "index.m3u8" {
+        return Some(("application/vnd.apple.mpegurl", Bytes::from(playlist.render())));
+    }
+
+    if file == "init.mp4" {
+        return playlist.init.clone().map(|init| ("video/mp4", init));
+    }
+
+    let (sequence, extension) = {
+        let mut parts = file.splitn(2, '.');
+        (parts.next()?.parse::<u64>().ok()?, parts.next()?)
+    };
+
+    if extension != playlist.format.extension() {
+        return None;
+    }
+
+    let content_type = match playlist.format {
+        HlsFormat::Ts => "video/mp2t",
+        HlsFormat::Fmp4 => "video/iso.segment",
+    };
+
+    playlist.segments.iter()
+        .find(|segment| segment.sequence == sequence)
+        .map(|segment| (content_type, segment.data.clone()))
+}
+
+#[derive(Debug)]
+struct Playlist {
+    format: HlsFormat,
+    private: bool,
+    // number of segments listed, the rest of `segments` are only retained
+    length: usize,
+    // fMP4 initialization segment, which segments are appended to
+    init: Option<Bytes>,
+    segments: VecDeque<Segment>,
+    next_sequence: u64,
+    // discontinuities which have dropped off the playlist
+    discontinuity_sequence: u64,
+    // set when the encoder restarts, and taken by the next segment
+    discontinuity: bool,
+    // bumped on restart so that a replaced codec thread finishing up can't
+    // add to the new playlist
+    generation: u64,
+}
+
+#[derive(Debug)]
+struct Segment {
+    sequence: u64,
+    duration: MediaDuration,
+    discontinuity: bool,
+    data: Bytes,
+}
+
+impl Playlist {
+    fn new(params: &HlsOutputParams) -> Self {
+        Playlist {
+            format: params.format,
+            private: params.private,
+            length: params.playlist_length.max(1),
+            init: None,
+            segments: VecDeque::new(),
+            next_sequence: 0,
+            discontinuity_sequence: 0,
+            discontinuity: false,
+            generation: 0,
+        }
+    }
+
+    // starts over for a new encoder, which viewers can only follow across a
+    // discontinuity. sequence numbers carry on so that segment urls are
+    // never reused
+    fn restart(&mut self, params: &HlsOutputParams) {
+        if self.listed().any(|segment| segment.discontinuity) {
+            self.discontinuity_sequence += 1;
+        }
+
+        self.format = params.format;
+        self.length = params.playlist_length.max(1);
+        self.init = None;
+        self.segments.clear();
+        self.discontinuity = self.next_sequence > 0;
+        self.generation += 1;
+    }
+
+    fn push(&mut self, generation: u64, duration: MediaDuration, data: Bytes) {
+        if generation != self.generation {
+            return;
+        }
+
+        self.segments.push_back(Segment {
+            sequence: self.next_sequence,
+            duration,
+            discontinuity: mem::replace(&mut self.discontinuity, false),
+            data,
+        });
+
+        self.next_sequence += 1;
+
+        if self.segments.len() > self.length {
+            let dropped = &self.segments[self.segments.len() - self.length - 1];
+
+            if dropped.discontinuity {
+                self.discontinuity_sequence += 1;
+            }
+        }
+
+        while self.segments.len() > self.length + SEGMENTS_RETAINED {
+            self.segments.pop_front();
+        }
+    }
+
+    fn listed(&self) -> impl Iterator<Item = &Segment> {
+        self.segments.iter().skip(self.segments.len().saturating_sub(self.length))
+    }
+
+    fn listed_count(&self) -> usize {
+        self.segments.len().min(self.length)
+    }
+
+    fn render(&self) -> String {
+        let mut m3u8 = String::new();
+
+        let first_sequence = self.listed().next()
+            .map(|segment| segment.sequence)
+            .unwrap_or(self.next_sequence);
+
+        let target_duration = self.listed()
+            .map(|segment| segment.duration.round_to_base(1000))
+            .max()
+            .map(|millis| (millis + 999) / 1000)
+            .unwrap_or(1);
+
+        // the version needed for EXT-X-MAP without I-frame playlists
+        let version = match self.format {
+            HlsFormat::Ts => 3,
+            HlsFormat::Fmp4 => 6,
+        };
+
+        // writing to a string never fails
+        writeln!(m3u8, "#EXTM3U").unwrap();
+        writeln!(m3u8, "#EXT-X-VERSION:{}", version).unwrap();
+        writeln!(m3u8, "#EXT-X-TARGETDURATION:{}", target_duration).unwrap();
+        writeln!(m3u8, "#EXT-X-MEDIA-SEQUENCE:{}", first_sequence).unwrap();
+        writeln!(m3u8, "#EXT-X-DISCONTINUITY-SEQUENCE:{}", self.discontinuity_sequence).unwrap();
+
+        if let HlsFormat::Fmp4 = self.format {
+            writeln!(m3u8, "#EXT-X-MAP:URI=\"init.mp4\"").unwrap();
+        }
+
+        for segment in self.listed() {
+            if segment.discontinuity {
+                writeln!(m3u8, "#EXT-X-DISCONTINUITY").unwrap();
+            }
+
+            writeln!(m3u8, "#EXTINF:{:.3},", segment.duration.round_to_base(1000) as f64 / 1000.0).unwrap();
+            writeln!(m3u8, "{}.{}", segment.sequence, self.format.extension()).unwrap();
+        }
+
+        m3u8
+    }
+}
+
+trait FormatExt {
+    fn extension(&self) -> &'static str;
+}
+
+impl FormatExt for HlsFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            HlsFormat::Ts => "ts",
+            HlsFormat::Fmp4 => "m4s",
+        }
+    }
+}
+
+// holds a name in the playlist registry for as long as it lives
+#[derive(Debug)]
+struct Registration {
+    name: String,
+    playlist: PlaylistRef,
+}
+
+impl Registration {
+    fn claim(name: &str, playlist: PlaylistRef) -> Option<Self> {
+        let mut playlists = PLAYLISTS.lock().unwrap();
+
+        if playlists.contains_key(name) {
+            return None;
+        }
+
+        playlists.insert(name.to_owned(), playlist.clone());
+        Some(Registration { name: name.to_owned(), playlist })
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut playlists = PLAYLISTS.lock().unwrap();
+
+        // only remove the entry if it's still ours
+        if playlists.get(&self.name).map(|playlist| Arc::ptr_eq(playlist, &self.playlist)).unwrap_or(false) {
+            playlists.remove(&self.name);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HlsOutput {
+    params: HlsOutputParams,
+    epoch: Option<MediaTime>,
+    playlist: PlaylistRef,
+    registration: Option<Registration>,
+    codec: Codec,
+    indication: HlsOutputIndication,
+    inputs: Vec<Terminal>,
+}
+
+impl ModuleT for HlsOutput {
+    type Params = HlsOutputParams;
+    type Indication = HlsOutputIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let playlist = Arc::new(Mutex::new(Playlist::new(&params)));
+        let registration = Registration::claim(&params.name, playlist.clone());
+        let codec = Codec::start(&params, playlist.clone());
+
+        let indication = HlsOutputIndication {
+            serving: registration.is_some(),
+            segments: 0,
+        };
+
+        let module = HlsOutput {
+            params,
+            epoch: None,
+            playlist,
+            registration,
+            codec,
+            indication: indication.clone(),
+            inputs: vec![
+                LineType::Video.labeled("Video"),
+                LineType::Stereo.labeled("Audio"),
+            ],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        if new_params.name != self.params.name || self.registration.is_none() {
+            // let go of the old name before claiming the new one, in case
+            // they're the same
+            self.registration = None;
+            self.registration = Registration::claim(&new_params.name, self.playlist.clone());
+        }
+
+        if new_params.format != self.params.format
+            || new_params.segment_secs != self.params.segment_secs
+            || new_params.playlist_length != self.params.playlist_length
+        {
+            self.playlist.lock().unwrap().restart(&new_params);
+            self.codec = Codec::start(&new_params, self.playlist.clone());
+            self.epoch = None;
+        }
+
+        self.playlist.lock().unwrap().private = new_params.private;
+
+        self.params = new_params;
+        self.indicate()
+    }
+
+    fn run_tick(&mut self, time: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        let (video, audio) = match inputs {
+            [video, audio] => (video.expect_video(), audio.expect_stereo()),
+            _ => unreachable!()
+        };
+
+        let absolute_timestamp = MediaTime::new(time as i64, SAMPLE_RATE as i64);
+        let epoch = *self.epoch.get_or_insert(absolute_timestamp);
+
+        self.codec.send(Tick {
+            timestamp: absolute_timestamp.remove_epoch(epoch),
+            audio: audio.to_vec(),
+            video: video.cloned(),
+        });
+
+        self.indicate()
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &[]
+    }
+
+    fn frame_rate(&self) -> Option<u32> {
+        Some(engine::DEFAULT_FPS)
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.playlist.lock().unwrap().segments.iter()
+            .map(|segment| segment.data.len())
+            .sum()
+    }
+}
+
+impl HlsOutput {
+    fn indicate(&mut self) -> Option<HlsOutputIndication> {
+        let indication = HlsOutputIndication {
+            serving: self.registration.is_some(),
+            segments: self.playlist.lock().unwrap().listed_count(),
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+}
+
+// encodes and segments ticks on its own thread, stopping when dropped
+#[derive(Debug)]
+struct Codec {
+    tx: mpsc::SyncSender<Tick>,
+}
+
+impl Codec {
+    fn start(params: &HlsOutputParams, playlist: PlaylistRef) -> Self {
+        let (tx, rx) = mpsc::sync_channel(2);
+        let format = params.format;
+        let segment_duration = MediaDuration::new((params.segment_secs.max(1.0) * 1000.0) as i64, 1000);
+
+        let generation = playlist.lock().unwrap().generation;
+
+        thread::spawn(move || run_codec_thread(format, segment_duration, generation, playlist, rx));
+
+        Codec { tx }
+    }
+
+    fn send(&mut self, tick: Tick) {
+        use mpsc::TrySendError;
+
+        match self.tx.try_send(tick) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                println!("HlsOutput: codec not keeping up, dropping tick");
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                // TODO handle gracefully
+                panic!("HlsOutput: codec thread died");
+            }
+        }
+    }
+}
+
+enum Muxer {
+    Ts(TsWriter),
+    Fmp4(Mp4Mux),
+}
+
+impl Muxer {
+    fn write(&mut self, out: &mut Vec<u8>, segment: StreamSegment) {
+        match self {
+            Muxer::Ts(writer) => writer.write_segment(out, segment),
+            Muxer::Fmp4(mux) => {
+                let (duration, track_data) = match segment {
+                    StreamSegment::Audio(audio) => {
+                        (audio.duration, TrackData::Audio(AdtsFrame(audio.frame)))
+                    }
+                    StreamSegment::Video(video) => {
+                        (video.duration, TrackData::Video(video.frame))
+                    }
+                };
+
+                out.extend_from_slice(&mux.write_track(duration, &track_data));
+            }
+        }
+    }
+}
+
+fn run_codec_thread(format: HlsFormat, segment_duration: MediaDuration, generation: u64, playlist: PlaylistRef, rx: mpsc::Receiver<Tick>) {
+    // the mp4 muxer strips ADTS headers, the transport stream writer adds
+    // its own
+    let transport = match format {
+        HlsFormat::Ts => aac::Transport::Raw,
+        HlsFormat::Fmp4 => aac::Transport::Adts,
+    };
+
+    let audio_ctx = AudioCtx::new(AudioParams {
+        bit_rate: aac::BitRate::Cbr(128000),
+        sample_rate: SAMPLE_RATE,
+        transport,
+    });
+
+    let picture = PictureSettings::yuv420p(OUTPUT_WIDTH, OUTPUT_HEIGHT);
+
+    // key frames at the segment duration let segments be cut close to it
+    let video_ctx = VideoCtx::new(VideoParams {
+        picture: picture.clone(),
+        time_base: SAMPLE_RATE,
+        profile: Profile::Stream,
+        keyframe_interval: Some(segment_duration),
+    });
+
+    let mut muxer = match format {
+        HlsFormat::Ts => {
+            let mut writer = TsWriter::new();
+            writer.set_sequence_headers(&audio_ctx, &video_ctx);
+            Muxer::Ts(writer)
+        }
+        HlsFormat::Fmp4 => {
+            let dcr = video_ctx.decoder_configuration_record();
+            let mut dcr_bytes = vec![];
+            dcr.write_to(&mut dcr_bytes);
+
+            let (mux, init) = Mp4Mux::new(Mp4Params {
+                timescale: SAMPLE_RATE as u32,
+                width: picture.width as u32,
+                height: picture.height as u32,
+                dcr: Cow::Owned(dcr_bytes),
+            });
+
+            let mut playlist = playlist.lock().unwrap();
+
+            if playlist.generation == generation {
+                playlist.init = Some(init);
+            }
+
+            Muxer::Fmp4(mux)
+        }
+    };
+
+    let mut encode = EncodeStream::new(audio_ctx, video_ctx);
+
+    let mut current = Vec::new();
+    // decode timestamp of the key frame starting the current segment
+    let mut segment_start: Option<MediaTime> = None;
+
+    while let Ok(tick) = rx.recv() {
+        encode.send_audio(&tick.audio);
+
+        if let Some(video_frame) = tick.video {
+            let frame_timestamp = tick.timestamp + video_frame.tick_offset;
+            let frame = video_frame.data.decoded.clone();
+
+            encode.send_video(frame_timestamp, video_frame.data.duration_hint, frame);
+        }
+
+        encode.barrier(tick.timestamp);
+
+        while let Some(segment) = encode.recv_segment() {
+            if let StreamSegment::Video(video) = &segment {
+                if video.frame.is_key_frame {
+                    match segment_start {
+                        Some(start) if video.decode_timestamp - start >= segment_duration => {
+                            let duration = video.decode_timestamp - start;
+                            let data = Bytes::from(mem::take(&mut current));
+                            playlist.lock().unwrap().push(generation, duration, data);
+                            segment_start = Some(video.decode_timestamp);
+                        }
+                        Some(_) => {}
+                        None => {
+                            segment_start = Some(video.decode_timestamp);
+                        }
+                    }
+                }
+            }
+
+            // segments must start on a key frame, so anything before the
+            // first is no use
+            if segment_start.is_none() {
+                continue;
+            }
+
+            muxer.write(&mut current, segment);
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use std::io;
+use std::mem;
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use derive_more::From;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use mixlab_codec::ffmpeg::{AudioEncoder, AudioEncodeParams, AudioEncodeError, AudioEncoding};
+use mixlab_codec::ogg::OggVorbisMux;
+use mixlab_protocol::{IcecastOutputParams, IcecastOutputIndication, IcecastOutputCommand, IcecastFormat, ModuleCommand, LineType, Terminal, StreamOutputLiveStatus};
+
+use crate::engine::{self, InputRef, OutputRef, CHANNELS, SAMPLE_RATE};
+use crate::icecast::client::{self, ConnectError};
+use crate::listen::{WriteQueue, WriteQueueError};
+use crate::module::ModuleT;
+
+// wait between attempts to reconnect after the connection drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+// ogg pages are ended at least this often, in samples, so that listeners
+// aren't kept waiting on a full page
+const PAGE_SAMPLES: u64 = SAMPLE_RATE as u64 / 2;
+
+#[derive(Debug)]
+pub struct IcecastOutput {
+    params: IcecastOutputParams,
+    connection: Connection,
+    inputs: Vec<Terminal>,
+    indication: IcecastOutputIndication,
+}
+
+impl ModuleT for IcecastOutput {
+    type Params = IcecastOutputParams;
+    type Indication = IcecastOutputIndication;
+    type Event = IcecastOutputCommand;
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let indication = IcecastOutputIndication {
+            live: StreamOutputLiveStatus::Offline,
+            error: false,
+        };
+
+        let module = IcecastOutput {
+            params,
+            connection: Connection::Offline,
+            inputs: vec![LineType::Stereo.labeled("Audio")],
+            indication: indication.clone(),
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        // nothing can change on a live output
+        if !self.connection.is_active() {
+            self.params = new_params;
+        }
+
+        self.indicate()
+    }
+
+    fn command_event(command: ModuleCommand) -> Option<IcecastOutputCommand> {
+        match command {
+            ModuleCommand::IcecastOutput(command) => Some(command),
+            _ => None,
+        }
+    }
+
+    fn receive_event(&mut self, command: IcecastOutputCommand) {
+        match command {
+            IcecastOutputCommand::Connect if !self.connection.is_active() => {
+                self.connection = Connection::Connecting(connect(&self.params));
+            }
+            IcecastOutputCommand::Disconnect if self.connection.is_active() => {
+                self.connection = Connection::Offline;
+            }
+            // stale commands from a client that hadn't yet seen the
+            // connection change
+            _ => {}
+        }
+    }
+
+    fn run_tick(&mut self, _: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        let audio = inputs[0].expect_stereo();
+
+        match &mut self.connection {
+            Connection::Offline | Connection::Failed => {}
+            Connection::Connecting(completion) => {
+                use oneshot::error::TryRecvError;
+
+                match completion.try_recv() {
+                    Ok(Ok(queue)) => {
+                        match LiveOutputTask::start(&self.params, queue) {
+                            Ok(live) => {
+                                self.connection = Connection::Live(live, Reconnect::Connected);
+                            }
+                            Err(e) => {
+                                eprintln!("IcecastOutput: could not start encoder: {:?}", e);
+                                self.connection = Connection::Failed;
+                            }
+                        }
+                    }
+                    Ok(Err(e)) => {
+                        eprintln!("IcecastOutput failed to connect: {:?}", e);
+                        self.connection = Connection::Failed;
+                    }
+                    Err(TryRecvError::Empty) => {}
+                    Err(TryRecvError::Closed) => {
+                        self.connection = Connection::Failed;
+                    }
+                }
+            }
+            Connection::Live(live, reconnect) => {
+                live.send(audio);
+                reconnect.poll(live, &self.params);
+            }
+        }
+
+        self.indicate()
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &[]
+    }
+}
+
+impl IcecastOutput {
+    fn indicate(&mut self) -> Option<IcecastOutputIndication> {
+        let (live, error) = match &self.connection {
+            Connection::Offline => (StreamOutputLiveStatus::Offline, false),
+            Connection::Failed => (StreamOutputLiveStatus::Offline, true),
+            Connection::Connecting(_) => (StreamOutputLiveStatus::Connecting, false),
+            Connection::Live(_, Reconnect::Connected) |
+            Connection::Live(_, Reconnect::Ready(_)) => (StreamOutputLiveStatus::Live, false),
+            Connection::Live(_, Reconnect::Waiting(_)) |
+            Connection::Live(_, Reconnect::Connecting(_)) => (StreamOutputLiveStatus::Connecting, true),
+        };
+
+        let new_indication = IcecastOutputIndication { live, error };
+
+        if new_indication == self.indication {
+            None
+        } else {
+            self.indication = new_indication.clone();
+            Some(new_indication)
+        }
+    }
+}
+
+fn connect(params: &IcecastOutputParams) -> oneshot::Receiver<Result<WriteQueue, ConnectError>> {
+    let (completion_tx, completion_rx) = oneshot::channel();
+
+    tokio::spawn({
+        let url = params.url.clone();
+        let password = params.password.clone();
+        let content_type = content_type(params.format);
+        async move {
+            let _ = completion_tx.send(client::connect(&url, &password, content_type).await);
+        }
+    });
+
+    completion_rx
+}
+
+fn content_type(format: IcecastFormat) -> &'static str {
+    match format {
+        IcecastFormat::OggVorbis => "application/ogg",
+        IcecastFormat::Mp3 => "audio/mpeg",
+    }
+}
+
+#[derive(Debug)]
+enum Connection {
+    Offline,
+    Failed,
+    Connecting(oneshot::Receiver<Result<WriteQueue, ConnectError>>),
+    Live(LiveOutputTask, Reconnect),
+}
+
+impl Connection {
+    fn is_active(&self) -> bool {
+        match self {
+            Connection::Offline => false,
+            Connection::Failed => false,
+            Connection::Connecting(_) => true,
+            Connection::Live(..) => true,
+        }
+    }
+}
+
+// a live output keeps encoding while a dropped connection is retried, so
+// that the stream carries on from where it is once back
+#[derive(Debug)]
+enum Reconnect {
+    Connected,
+    Waiting(Instant),
+    Connecting(oneshot::Receiver<Result<WriteQueue, ConnectError>>),
+    // connected, waiting to be handed to the live output
+    Ready(WriteQueue),
+}
+
+impl Reconnect {
+    fn poll(&mut self, live: &mut LiveOutputTask, params: &IcecastOutputParams) {
+        match self {
+            Reconnect::Connected => {
+                if live.connection_failed() {
+                    *self = Reconnect::Waiting(Instant::now() + RECONNECT_DELAY);
+                }
+            }
+            Reconnect::Waiting(retry_at) => {
+                if Instant::now() >= *retry_at {
+                    *self = Reconnect::Connecting(connect(params));
+                }
+            }
+            Reconnect::Connecting(completion) => {
+                use oneshot::error::TryRecvError;
+
+                match completion.try_recv() {
+                    Ok(Ok(queue)) => {
+                        *self = Reconnect::Ready(queue);
+                    }
+                    Ok(Err(e)) => {
+                        eprintln!("IcecastOutput failed to reconnect: {:?}", e);
+                        *self = Reconnect::Waiting(Instant::now() + RECONNECT_DELAY);
+                    }
+                    Err(TryRecvError::Empty) => {}
+                    Err(TryRecvError::Closed) => {
+                        *self = Reconnect::Waiting(Instant::now() + RECONNECT_DELAY);
+                    }
+                }
+            }
+            Reconnect::Ready(_) => {}
+        }
+
+        if let Reconnect::Ready(_) = self {
+            if let Reconnect::Ready(queue) = mem::replace(self, Reconnect::Connected) {
+                if let Some(queue) = live.reconnect(queue) {
+                    // live output is lagging, try again next tick
+                    *self = Reconnect::Ready(queue);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, From)]
+enum StartError {
+    Encode(AudioEncodeError),
+    Mux(io::Error),
+}
+
+#[derive(Debug)]
+struct LiveOutputTask {
+    tx: mpsc::SyncSender<LiveOutputMsg>,
+    // set by the live output once the server stops accepting data
+    failed: Arc<AtomicBool>,
+}
+
+enum LiveOutputMsg {
+    Tick(Vec<engine::Sample>),
+    Reconnect(WriteQueue),
+}
+
+impl LiveOutputTask {
+    fn start(params: &IcecastOutputParams, queue: WriteQueue) -> Result<Self, StartError> {
+        let encoder = AudioEncoder::new(AudioEncodeParams {
+            encoding: match params.format {
+                IcecastFormat::OggVorbis => AudioEncoding::Vorbis,
+                IcecastFormat::Mp3 => AudioEncoding::Mp3,
+            },
+            sample_rate: SAMPLE_RATE,
+            channels: CHANNELS,
+            bit_rate: params.bitrate_kbps * 1000,
+        })?;
+
+        let mut live = LiveOutput {
+            encoder,
+            format: params.format,
+            mux: None,
+            queue: None,
+            failed: Arc::new(AtomicBool::new(false)),
+        };
+
+        live.connect(queue)?;
+
+        let (tx, rx) = mpsc::sync_channel(100);
+        let failed = live.failed.clone();
+
+        thread::spawn(move || {
+            while let Ok(msg) = rx.recv() {
+                match msg {
+                    LiveOutputMsg::Tick(audio) => {
+                        live.tick(&audio);
+                    }
+                    LiveOutputMsg::Reconnect(queue) => {
+                        if let Err(e) = live.connect(queue) {
+                            eprintln!("IcecastOutput: could not restart stream: {:?}", e);
+                            live.failed.store(true, Ordering::Relaxed);
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(LiveOutputTask { tx, failed })
+    }
+
+    fn connection_failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    fn send(&mut self, audio: &[engine::Sample]) {
+        // if the encoder thread is lagging this tick is dropped, as with
+        // StreamOutput
+        let _ = self.tx.try_send(LiveOutputMsg::Tick(audio.to_vec()));
+    }
+
+    // hands the live output a new connection, giving it back if the live
+    // output is too busy to take it now
+    fn reconnect(&mut self, queue: WriteQueue) -> Option<WriteQueue> {
+        use mpsc::TrySendError;
+
+        match self.tx.try_send(LiveOutputMsg::Reconnect(queue)) {
+            Ok(()) => {
+                self.failed.store(false, Ordering::Relaxed);
+                None
+            }
+            Err(TrySendError::Full(LiveOutputMsg::Reconnect(queue))) => Some(queue),
+            Err(_) => None,
+        }
+    }
+}
+
+struct LiveOutput {
+    encoder: AudioEncoder,
+    format: IcecastFormat,
+    // ogg streams are muxed afresh for every connection
+    mux: Option<OggVorbisMux>,
+    queue: Option<WriteQueue>,
+    failed: Arc<AtomicBool>,
+}
+
+impl LiveOutput {
+    // starts the stream from its headers on a new connection
+    fn connect(&mut self, queue: WriteQueue) -> Result<(), io::Error> {
+        self.queue = Some(queue);
+
+        if let IcecastFormat::OggVorbis = self.format {
+            let serial = Uuid::new_v4().as_u128() as u32;
+            let (mux, headers) = OggVorbisMux::new(self.encoder.extradata(), serial, PAGE_SAMPLES)?;
+            self.mux = Some(mux);
+            self.write(headers);
+        }
+
+        Ok(())
+    }
+
+    fn tick(&mut self, audio: &[engine::Sample]) {
+        if let Err(e) = self.encoder.send_audio(audio) {
+            eprintln!("IcecastOutput: could not encode audio: {:?}", e);
+        }
+
+        while let Some(packet) = self.encoder.recv_packet() {
+            let data = match &mut self.mux {
+                Some(mux) => {
+                    let samples = packet.duration().unwrap_or(0).max(0) as u64;
+
+                    match mux.packet(packet.data(), samples) {
+                        Ok(pages) => pages,
+                        Err(e) => {
+                            eprintln!("IcecastOutput: could not mux packet: {:?}", e);
+                            continue;
+                        }
+                    }
+                }
+                // mp3 frames are sent as they are
+                None => packet.data().to_vec(),
+            };
+
+            self.write(data);
+        }
+    }
+
+    fn write(&mut self, data: Vec<u8>) {
+        if data.is_empty() {
+            return;
+        }
+
+        if let Some(queue) = &mut self.queue {
+            match queue.write(data) {
+                Ok(()) => {}
+                // the server is not keeping up, nothing to do but drop data
+                Err(WriteQueueError::Full) => {}
+                Err(WriteQueueError::Closed) => {
+                    eprintln!("IcecastOutput: server disconnected");
+                    self.queue = None;
+                    self.failed.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use derive_more::From;
+use tokio::task;
+
+use mixlab_codec::ffmpeg::codec::{self, CodecBuilder, RecvFrameError};
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::{AvError, AvFrame, AvIoError, AvIoReader, IoReader, InputContainer, PictureSettings, SwsContext};
+use mixlab_protocol::{ImageOverlayParams, MediaId, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, ModuleCtx};
+use crate::module::ModuleT;
+use crate::project::media;
+use crate::project::ProjectBaseRef;
+use crate::project::stream::ReadStream;
+use crate::video;
+use crate::video::composite::{self, Overlay};
+use crate::video::encode::DynamicScaler;
+
+#[derive(Debug)]
+pub struct ImageOverlay {
+    ctx: ModuleCtx<Self>,
+    params: ImageOverlayParams,
+    // rgba, as decoded from the media library
+    image: Option<AvFrame<Video>>,
+    // image is being decoded in the background
+    loading: bool,
+    // image scaled for the pictures coming in, made again when their size
+    // or the scale change
+    overlay: Option<Overlay>,
+    scaler: Option<DynamicScaler>,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+pub enum ImageOverlayEvent {
+    SetImage(MediaId, Option<AvFrame<Video>>),
+}
+
+impl ModuleT for ImageOverlay {
+    type Params = ImageOverlayParams;
+    type Indication = ();
+    type Event = ImageOverlayEvent;
+
+    fn create(params: Self::Params, ctx: ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut module = ImageOverlay {
+            ctx,
+            params: ImageOverlayParams { media_id: None, ..params.clone() },
+            image: None,
+            loading: false,
+            overlay: None,
+            scaler: None,
+            inputs: vec![LineType::Video.unlabeled()],
+            outputs: vec![LineType::Video.unlabeled()],
+        };
+
+        module.update(params);
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        if self.params.media_id != params.media_id {
+            self.image = None;
+            self.overlay = None;
+            self.loading = false;
+
+            if let Some(media_id) = params.media_id {
+                let project = self.ctx.project();
+
+                self.loading = true;
+                self.ctx.spawn_async(async move {
+                    ImageOverlayEvent::SetImage(media_id, load_image(project, media_id).await)
+                });
+            }
+        }
+
+        if self.params.scale != params.scale {
+            self.overlay = None;
+        }
+
+        self.params = params;
+        None
+    }
+
+    fn receive_event(&mut self, event: ImageOverlayEvent) {
+        match event {
+            ImageOverlayEvent::SetImage(media_id, image) => {
+                // the image may have been changed again while loading
+                if self.params.media_id == Some(media_id) {
+                    self.image = image;
+                    self.overlay = None;
+                    self.loading = false;
+                }
+            }
+        }
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let output = outputs[0].expect_video();
+
+        let input = match inputs[0].expect_video() {
+            Some(input) => input,
+            None => {
+                *output = None;
+                return None;
+            }
+        };
+
+        let image = match &self.image {
+            Some(image) if self.params.opacity > 0.0 => image,
+            _ => {
+                *output = Some(input.clone());
+                return None;
+            }
+        };
+
+        // images are composited in yuv420p, other pictures are converted
+        // first
+        let mut frame = input.data.decoded.clone();
+        let target = composite::yuv420p(&frame.picture_settings());
+
+        if frame.picture_settings() != target {
+            if self.scaler.as_ref().map(|scaler| scaler.output()) != Some(&target) {
+                self.scaler = Some(DynamicScaler::new(target.clone()));
+            }
+
+            let scaler = self.scaler.as_mut().unwrap();
+            frame = scaler.scale(&mut frame).clone();
+        }
+
+        let size = overlay_size(&image.picture_settings(), target.height, self.params.scale);
+
+        if self.overlay.as_ref().map(|overlay| (overlay.width(), overlay.height())) != Some(size) {
+            self.overlay = Some(scale_image(image, size));
+        }
+
+        let overlay = self.overlay.as_ref().unwrap();
+
+        let (x, y) = composite::place(self.params.position, size, (target.width, target.height));
+        overlay.composite(&mut frame, x, y, self.params.opacity);
+
+        *output = Some(engine::VideoFrame {
+            data: video::Frame {
+                decoded: frame,
+                // the picture has changed, so the source's packets no longer
+                // describe it
+                passthrough: None,
+                ..input.data.clone()
+            },
+            tick_offset: input.tick_offset,
+        });
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.image.as_ref()
+            .map(|image| image.buffer_size())
+            .unwrap_or(0)
+    }
+
+    fn ready(&mut self) -> bool {
+        // offline renders wait for the image rather than render without it
+        !self.loading
+    }
+}
+
+// the size of the image at `scale` times the picture height, keeping its
+// aspect ratio
+fn overlay_size(image: &PictureSettings, picture_height: usize, scale: f64) -> (usize, usize) {
+    let height = (picture_height as f64 * scale.max(0.0).min(1.0)).round().max(1.0);
+    let width = (image.width as f64 * height / image.height as f64).round().max(1.0);
+
+    (width as usize, height as usize)
+}
+
+fn scale_image(image: &AvFrame<Video>, (width, height): (usize, usize)) -> Overlay {
+    let output = PictureSettings::rgba(width, height);
+    let mut scaled = AvFrame::blank(&output);
+
+    let mut sws = SwsContext::new(image.picture_settings(), output);
+    sws.process(&image.frame_data(), &mut scaled.frame_data_mut());
+
+    Overlay::new(&scaled)
+}
+
+#[derive(Debug, From)]
+enum ImageError {
+    CodecBuild(codec::BuildError),
+    CodecOpen(codec::OpenError),
+    NoVideo,
+    NoFrames,
+    RecvFrame(RecvFrameError),
+    Av(AvError),
+    Io(<ReadStream as IoReader>::Error),
+}
+
+impl From<AvIoError<ReadStream>> for ImageError {
+    fn from(e: AvIoError<ReadStream>) -> ImageError {
+        match e {
+            AvIoError::Av(e) => ImageError::Av(e),
+            AvIoError::Io(e) => ImageError::Io(e),
+        }
+    }
+}
+
+async fn load_image(project: ProjectBaseRef, media_id: MediaId) -> Option<AvFrame<Video>> {
+    let stream = match media::open(project, media_id).await {
+        Ok(Some(stream)) => stream,
+        Ok(None) => return None,
+        Err(e) => {
+            eprintln!("image_overlay: could not open {:?}: {:?}", media_id, e);
+            return None;
+        }
+    };
+
+    let result = task::spawn_blocking(move || decode_image(stream))
+        .await
+        .expect("join image thread");
+
+    match result {
+        Ok(image) => Some(image),
+        Err(e) => {
+            eprintln!("image_overlay: could not decode image {:?}: {:?}", media_id, e);
+            None
+        }
+    }
+}
+
+// decodes the first picture in the media, converted to rgba so that
+// transparency survives whatever format it was stored in
+fn decode_image(stream: ReadStream) -> Result<AvFrame<Video>, ImageError> {
+    let mut container = InputContainer::open(AvIoReader::new(stream))?;
+
+    let streams = container.streams();
+
+    let index = streams.iter()
+        .position(|stream| stream.is_media::<Video>())
+        .ok_or(ImageError::NoVideo)?;
+
+    let time_base = streams[index].time_base();
+    let codec_params = streams[index].codec_parameters();
+
+    let mut decode = CodecBuilder::<Video>::new(codec_params.codec_id, time_base)?
+        .with_parameters(codec_params)
+        .open_decoder()?;
+
+    loop {
+        let pkt = container.read_packet()?;
+
+        match &pkt {
+            Some(pkt) if pkt.stream_index() as usize == index => { decode.send_packet(pkt)?; }
+            Some(_) => { continue; }
+            None => { decode.end_of_stream()?; }
+        }
+
+        match decode.recv_frame() {
+            Ok(frame) => {
+                let picture = frame.picture_settings();
+                let output = PictureSettings::rgba(picture.width, picture.height);
+                let mut image = AvFrame::blank(&output);
+
+                let mut sws = SwsContext::new(picture, output);
+                sws.process(&frame.frame_data(), &mut image.frame_data_mut());
+
+                return Ok(image);
+            }
+            Err(RecvFrameError::NeedMoreInput) if pkt.is_some() => { continue; }
+            Err(RecvFrameError::NeedMoreInput) | Err(RecvFrameError::Eof) => {
+                return Err(ImageError::NoFrames);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlays_keep_the_image_aspect_ratio() {
+        let image = PictureSettings::rgba(400, 100);
+
+        assert_eq!((432, 108), overlay_size(&image, 720, 0.15));
+        assert_eq!((4, 1), overlay_size(&image, 720, 0.0));
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use cpal::traits::{HostTrait, DeviceTrait, StreamTrait};
+use ringbuf::{RingBuffer, Consumer};
+
+use mixlab_protocol::{InputDeviceParams, InputDeviceIndication, LineType, Terminal};
+
+use crate::engine::{self, Sample, InputRef, OutputRef, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::module::ModuleT;
+use crate::resample::Resampler;
+use crate::util;
+
+// audio held back from the device to absorb jitter between its callbacks and
+// engine ticks, in frames
+const TARGET_BUFFERED: usize = SAMPLES_PER_TICK * 2;
+
+// more than this buffered means the device clock is running fast of the
+// engine's, and frames are dropped to catch up
+const MAX_BUFFERED: usize = TARGET_BUFFERED + SAMPLES_PER_TICK;
+
+// frames skipped per tick while catching up. small enough not to be heard
+const DRIFT_FRAMES_PER_TICK: usize = 4;
+
+pub struct InputDevice {
+    params: InputDeviceParams,
+    host: cpal::Host,
+    stream: Option<InputStream>,
+    last_clip: Option<Instant>,
+    last_lag: Option<Instant>,
+    indication: InputDeviceIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+struct InputStream {
+    rx: Consumer<f32>,
+    channels: usize,
+    resampler: Option<Resampler>,
+    scratch: Vec<Sample>,
+    stereo: Vec<Sample>,
+    resampled: Vec<Sample>,
+    // captured stereo audio at the engine sample rate, waiting to be played
+    buffered: VecDeque<Sample>,
+    // set until the buffer first fills to its target, and again after it
+    // runs dry
+    priming: bool,
+    overflow_flag: Arc<AtomicBool>,
+    // this field is never used directly but must not be dropped for the
+    // stream to continue capturing:
+    _stream: cpal::Stream,
+}
+
+impl Debug for InputDevice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "InputDevice {{ params: {:?}, .. }}", self.params)
+    }
+}
+
+impl ModuleT for InputDevice {
+    type Params = InputDeviceParams;
+    type Indication = InputDeviceIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let host = cpal::default_host();
+
+        // TODO - see if we can update devices as they are added/removed from host
+        let devices = Some(host.input_devices()
+            .map(|devices| devices
+                .flat_map(|device| -> Option<_> {
+                    let name = device.name().ok()?;
+                    let config = device.default_input_config().ok()?;
+                    Some((name, config.channels() as usize))
+                })
+                .collect())
+            .unwrap_or(Vec::new()));
+
+        let default_device = host.default_input_device()
+            .and_then(|dev| dev.name().ok());
+
+        let indication = InputDeviceIndication {
+            default_device,
+            devices,
+            clip: None,
+            lag: None,
+        };
+
+        let mut device = InputDevice {
+            params: InputDeviceParams::default(),
+            host,
+            stream: None,
+            last_clip: None,
+            last_lag: None,
+            inputs: vec![],
+            outputs: vec![LineType::Stereo.unlabeled()],
+            indication: indication.clone(),
+        };
+
+        device.update(params);
+
+        (device, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        let InputDeviceParams { device, left, right } = new_params;
+
+        if self.params.device != device {
+            self.stream = device.as_ref().and_then(|name| self.open_stream(name));
+            self.params.device = device;
+        }
+
+        // assign left and right channels, validating that they are within range:
+        let channels = self.stream.as_ref().map(|stream| stream.channels).unwrap_or(0);
+        self.params.left = left.filter(|left| *left < channels);
+        self.params.right = right.filter(|right| *right < channels);
+
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let output = outputs[0].expect_stereo();
+
+        let mut clip = false;
+        let mut lag = false;
+
+        if let Some(stream) = &mut self.stream {
+            stream.receive(self.params.left, self.params.right);
+
+            if stream.overflow_flag.swap(false, Ordering::Relaxed) {
+                lag = true;
+            }
+
+            let buffered_frames = stream.buffered.len() / CHANNELS;
+
+            if stream.priming && buffered_frames >= TARGET_BUFFERED {
+                stream.priming = false;
+            }
+
+            if !stream.priming {
+                if buffered_frames > MAX_BUFFERED * 2 {
+                    // too far behind to catch up gradually, jump back to the
+                    // target
+                    let excess = buffered_frames - TARGET_BUFFERED;
+                    stream.buffered.drain(0..excess * CHANNELS);
+                    lag = true;
+                } else if buffered_frames > MAX_BUFFERED {
+                    stream.buffered.drain(0..DRIFT_FRAMES_PER_TICK * CHANNELS);
+                }
+
+                let available = stream.buffered.len().min(output.len());
+
+                for (out, sample) in output.iter_mut().zip(stream.buffered.drain(0..available)) {
+                    if sample < -1.0 || sample > 1.0 {
+                        clip = true;
+                    }
+
+                    *out = sample;
+                }
+
+                if available < output.len() {
+                    // device clock is running slow of the engine's or the
+                    // device has stopped, wait for the buffer to fill again
+                    stream.priming = true;
+                    lag = true;
+                }
+            }
+        }
+
+        let now = Instant::now();
+
+        if clip {
+            self.last_clip = Some(now);
+        }
+
+        if lag {
+            self.last_lag = Some(now);
+        }
+
+        let mut indication_changed = false;
+
+        let new_clip_status = util::temporal_warning(
+            self.last_clip.map(|time| now - time));
+
+        if self.indication.clip != new_clip_status {
+            self.indication.clip = new_clip_status;
+            indication_changed = true;
+        }
+
+        let new_lag_status = util::temporal_warning(
+            self.last_lag.map(|time| now - time));
+
+        if self.indication.lag != new_lag_status {
+            self.indication.lag = new_lag_status;
+            indication_changed = true;
+        }
+
+        if indication_changed {
+            Some(self.indication.clone())
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl InputDevice {
+    fn open_stream(&self, name: &str) -> Option<InputStream> {
+        let input_device = self.host.input_devices()
+            .ok()?
+            .find(|dev| dev.name().map(|dev| dev == name).unwrap_or(false))?;
+
+        let config = match input_device.default_input_config() {
+            Ok(config) => config.config(),
+            Err(e) => {
+                eprintln!("input device: could not get config for {:?}: {:?}", name, e);
+                return None;
+            }
+        };
+
+        let (mut tx, rx) = RingBuffer::<f32>::new(65536).split();
+        let overflow_flag = Arc::new(AtomicBool::new(false));
+        let channels = config.channels as usize;
+
+        let stream = input_device.build_input_stream(
+                &config,
+                {
+                    let overflow_flag = overflow_flag.clone();
+                    move |data: &[f32], _info| {
+                        // only whole frames are pushed so that channels stay
+                        // aligned when the buffer is full
+                        let space = tx.remaining() / channels * channels;
+
+                        if space < data.len() {
+                            overflow_flag.store(true, Ordering::Relaxed);
+                        }
+
+                        tx.push_slice(&data[0..space.min(data.len())]);
+                    }
+                },
+                |err| {
+                    eprintln!("input stream error! {:?}", err);
+                });
+
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("input device: could not open {:?}: {:?}", name, e);
+                return None;
+            }
+        };
+
+        if let Err(e) = stream.play() {
+            eprintln!("input device: could not start {:?}: {:?}", name, e);
+            return None;
+        }
+
+        let device_rate = config.sample_rate.0 as usize;
+
+        let resampler = if device_rate == SAMPLE_RATE {
+            None
+        } else {
+            Some(Resampler::new(device_rate, SAMPLE_RATE, CHANNELS))
+        };
+
+        Some(InputStream {
+            rx,
+            channels,
+            resampler,
+            scratch: Vec::new(),
+            stereo: Vec::new(),
+            resampled: Vec::new(),
+            buffered: VecDeque::new(),
+            priming: true,
+            overflow_flag,
+            _stream: stream,
+        })
+    }
+}
+
+impl InputStream {
+    // takes everything captured since the last tick into the buffer as
+    // stereo at the engine sample rate
+    fn receive(&mut self, left: Option<usize>, right: Option<usize>) {
+        self.scratch.resize(self.rx.len() / self.channels * self.channels, 0.0);
+        let len = self.rx.pop_slice(&mut self.scratch);
+        self.scratch.truncate(len);
+
+        // a channel selected on its own feeds both sides
+        let (left, right) = match (left, right) {
+            (Some(left), None) => (Some(left), Some(left)),
+            (None, Some(right)) => (Some(right), Some(right)),
+            channels => channels,
+        };
+
+        self.stereo.clear();
+
+        for frame in self.scratch.chunks_exact(self.channels) {
+            self.stereo.push(left.map(|ch| frame[ch]).unwrap_or(0.0));
+            self.stereo.push(right.map(|ch| frame[ch]).unwrap_or(0.0));
+        }
+
+        match &mut self.resampler {
+            Some(resampler) => {
+                self.resampled.clear();
+                resampler.process(&self.stereo, &mut self.resampled);
+                self.buffered.extend(self.resampled.iter().copied());
+            }
+            None => {
+                self.buffered.extend(self.stereo.iter().copied());
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::f64;
+
+use mixlab_protocol::{LfoParams, Waveform, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, SAMPLE_RATE, CONTROL_PERIOD, ModuleHost, DynModuleHost};
+use crate::module::{self, ModuleT};
+use crate::module::amplifier::depth;
+
+#[derive(Debug)]
+pub struct Lfo {
+    params: LfoParams,
+    // position within the current cycle, 0.0 to 1.0. accumulated rather than
+    // derived from the tick count so that rate changes don't jump the output
+    phase: f64,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+// unipolar waveforms, 0.0 to 1.0
+fn wave(waveform: Waveform, phase: f64) -> f64 {
+    match waveform {
+        Waveform::Sine => 0.5 - 0.5 * f64::cos(phase * 2.0 * f64::consts::PI),
+        // without a pulse width, a pulse is square
+        Waveform::Square | Waveform::Pulse => if phase < 0.5 { 1.0 } else { 0.0 },
+        Waveform::Saw => phase,
+        Waveform::Triangle => 1.0 - (2.0 * phase - 1.0).abs(),
+        Waveform::On => 1.0,
+        Waveform::Off => 0.0,
+    }
+}
+
+impl ModuleT for Lfo {
+    type Params = LfoParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        (Self {
+            params,
+            phase: 0.0,
+            inputs: vec![],
+            outputs: vec![LineType::Control.unlabeled()],
+        }, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let output = outputs[0].expect_control();
+
+        let increment = self.params.freq.max(0.0) * CONTROL_PERIOD as f64 / SAMPLE_RATE as f64;
+        let mod_depth = self.params.depth.max(0.0).min(1.0);
+
+        for sample in output.iter_mut() {
+            *sample = depth(wave(self.params.waveform, self.phase), mod_depth) as Sample;
+            self.phase = (self.phase + increment).fract();
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+
+    fn host(host: ModuleHost<Self>) -> DynModuleHost {
+        module::parallel(host)
+    }
+}
@@ -0,0 +1,136 @@
+use mixlab_protocol::{LimiterParams, LineType, Terminal};
+
+use crate::dsp::smoothing_coeff;
+use crate::engine::{self, InputRef, OutputRef, Sample, CHANNELS, SAMPLE_RATE};
+use crate::module::ModuleT;
+
+#[derive(Debug)]
+pub struct Limiter {
+    params: LimiterParams,
+    ceiling: f64,
+    release_coeff: f64,
+    gain: f64,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Limiter {
+    type Params = LimiterParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut module = Limiter {
+            params: params.clone(),
+            ceiling: 1.0,
+            release_coeff: 1.0,
+            gain: 1.0,
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        };
+
+        module.update(params);
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.ceiling = params.ceiling.to_linear();
+        self.release_coeff = smoothing_coeff(params.release, SAMPLE_RATE as f64);
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let output = outputs[0].expect_stereo();
+
+        self.process(input, output);
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl Limiter {
+    // gain drops at once to keep any peak under the ceiling, with no look
+    // ahead, and recovers over the release time. both channels get the same
+    // gain so the stereo image holds still
+    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        for (input, output) in input.chunks(CHANNELS).zip(output.chunks_mut(CHANNELS)) {
+            let peak = input.iter().fold(0.0, |peak: f64, sample| peak.max(sample.abs() as f64));
+
+            self.gain += (1.0 - self.gain) * self.release_coeff;
+
+            if peak * self.gain > self.ceiling {
+                self.gain = self.ceiling / peak;
+            }
+
+            for (input, output) in input.iter().zip(output) {
+                *output = (*input as f64 * self.gain) as Sample;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mixlab_protocol::Decibel;
+
+    fn limiter(params: LimiterParams) -> Limiter {
+        Limiter {
+            params,
+            ceiling: 1.0,
+            release_coeff: 1.0,
+            gain: 1.0,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    fn run(limiter: &mut Limiter, input: &[Sample]) -> Vec<Sample> {
+        let mut output = vec![0.0; input.len()];
+        limiter.process(input, &mut output);
+        output
+    }
+
+    #[test]
+    fn peaks_never_pass_the_ceiling() {
+        let params = LimiterParams::default();
+        let ceiling = params.ceiling.to_linear() as Sample;
+        let mut limiter = limiter(params.clone());
+        limiter.update(params);
+
+        // a sudden peak on one channel only, then louder still
+        let input = [0.1, 0.1, 0.1, 2.0, -3.0, 0.5, 0.2, 0.2];
+        let output = run(&mut limiter, &input);
+
+        assert!(output.iter().all(|sample| sample.abs() <= ceiling * 1.0001), "{:?}", output);
+        assert_eq!(0.1, output[0]);
+    }
+
+    #[test]
+    fn recovers_after_release() {
+        let params = LimiterParams { ceiling: Decibel(-6.0), release: 0.05 };
+        let mut limiter = limiter(params.clone());
+        limiter.update(params);
+
+        run(&mut limiter, &[1.0, 1.0]);
+        assert!(limiter.gain < 0.6);
+
+        run(&mut limiter, &vec![0.1; SAMPLE_RATE * CHANNELS]);
+        assert!(limiter.gain > 0.9999);
+    }
+}
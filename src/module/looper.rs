@@ -0,0 +1,383 @@
+use std::mem;
+
+use mixlab_protocol::{LooperParams, LooperIndication, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::module::ModuleT;
+
+// recording stops by itself after this long, so a forgotten record gate
+// can't eat all the memory on the machine
+const MAX_RECORD_SECONDS: usize = 120;
+const MAX_RECORD_SAMPLES: usize = MAX_RECORD_SECONDS * SAMPLE_RATE;
+
+// gate inputs count as high above this level
+const GATE_THRESHOLD: Sample = 0.5;
+
+// ticks between playback position indications
+const INDICATION_TICKS: u64 = 6;
+
+#[derive(Debug)]
+pub struct Looper {
+    params: LooperParams,
+    loops: Loops,
+    record: Gate,
+    overdub: Gate,
+    play: Gate,
+    clear: Gate,
+    indication: LooperIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Looper {
+    type Params = LooperParams;
+    type Indication = LooperIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Looper {
+            params,
+            loops: Loops::default(),
+            record: Gate::default(),
+            overdub: Gate::default(),
+            play: Gate::default(),
+            clear: Gate::default(),
+            indication: LooperIndication::default(),
+            inputs: vec![
+                LineType::Stereo.labeled("Input"),
+                LineType::Mono.labeled("Record"),
+                LineType::Mono.labeled("Overdub"),
+                LineType::Mono.labeled("Play"),
+                LineType::Mono.labeled("Clear"),
+            ],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        };
+
+        (module, LooperIndication::default())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let record = inputs[1].expect_mono();
+        let overdub = inputs[2].expect_mono();
+        let play = inputs[3].expect_mono();
+        let clear = inputs[4].expect_mono();
+
+        let output = outputs[0].expect_stereo();
+
+        let level = self.params.level as Sample;
+
+        for i in 0..SAMPLES_PER_TICK {
+            if self.clear.rising(clear[i]) {
+                self.loops.clear();
+            }
+
+            if self.record.rising(record[i]) {
+                self.loops.toggle_record();
+            }
+
+            if self.overdub.rising(overdub[i]) {
+                self.loops.toggle_overdub();
+            }
+
+            if self.play.rising(play[i]) {
+                self.loops.toggle_play();
+            }
+
+            let frame = [input[i * CHANNELS], input[i * CHANNELS + 1]];
+            let looped = self.loops.process(frame);
+
+            for chan in 0..CHANNELS {
+                output[i * CHANNELS + chan] = frame[chan] + looped[chan] * level;
+            }
+        }
+
+        let indication = LooperIndication {
+            recording: self.loops.recording.is_some(),
+            overdubbing: self.loops.overdubbing,
+            playing: self.loops.playing,
+            loops: self.loops.loops.iter()
+                .map(|samples| frames(samples) as f64 / SAMPLE_RATE as f64)
+                .collect(),
+            // only refreshed every few ticks, state changes go out straight
+            // away
+            position: if (t / SAMPLES_PER_TICK as u64) % INDICATION_TICKS == 0 {
+                self.loops.position_fraction()
+            } else {
+                self.indication.position
+            },
+        };
+
+        if indication == self.indication {
+            return None;
+        }
+
+        self.indication = indication.clone();
+        Some(indication)
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        let loops = self.loops.loops.iter()
+            .map(|samples| samples.capacity())
+            .sum::<usize>();
+
+        let recording = self.loops.recording.as_ref()
+            .map(|recording| recording.samples.capacity())
+            .unwrap_or(0);
+
+        (loops + recording) * mem::size_of::<Sample>()
+    }
+}
+
+// detects rising edges on a gate input
+#[derive(Debug, Default)]
+struct Gate {
+    high: bool,
+}
+
+impl Gate {
+    fn rising(&mut self, value: Sample) -> bool {
+        let high = value > GATE_THRESHOLD;
+        let rising = high && !self.high;
+        self.high = high;
+        rising
+    }
+}
+
+#[derive(Debug, Default)]
+struct Loops {
+    // interleaved stereo. the first loop sets the base length, every later
+    // loop is a whole multiple of it so they all stay in time
+    loops: Vec<Vec<Sample>>,
+    recording: Option<Recording>,
+    overdubbing: bool,
+    playing: bool,
+    // sample frames since the first loop started recording. every loop plays
+    // from this position modulo its own length
+    position: u64,
+}
+
+#[derive(Debug)]
+struct Recording {
+    // position the recording started at
+    start: u64,
+    samples: Vec<Sample>,
+}
+
+impl Loops {
+    fn base_frames(&self) -> Option<usize> {
+        self.loops.first().map(|samples| frames(samples))
+    }
+
+    fn position_fraction(&self) -> f64 {
+        match self.base_frames() {
+            Some(base) => (self.position % base as u64) as f64 / base as f64,
+            None => 0.0,
+        }
+    }
+
+    fn toggle_record(&mut self) {
+        if self.recording.is_some() {
+            self.finish_recording();
+            return;
+        }
+
+        if self.loops.is_empty() {
+            self.position = 0;
+        } else {
+            // recording another loop on top carries on playing the others
+            self.playing = true;
+        }
+
+        self.recording = Some(Recording {
+            start: self.position,
+            samples: Vec::new(),
+        });
+    }
+
+    fn finish_recording(&mut self) {
+        let recording = match self.recording.take() {
+            Some(recording) => recording,
+            None => return,
+        };
+
+        let recorded_frames = frames(&recording.samples);
+
+        if recorded_frames == 0 {
+            return;
+        }
+
+        let base = match self.base_frames() {
+            Some(base) => base,
+            None => {
+                // the first loop starts playing back straight away, and since
+                // position has counted up its length it wraps to the start
+                self.loops.push(recording.samples);
+                self.playing = true;
+                return;
+            }
+        };
+
+        // round to the nearest whole number of base loops
+        let multiple = ((recorded_frames as f64 / base as f64).round() as usize).max(1);
+        let len = multiple * base;
+
+        // lay the recording into the loop at the positions it was recorded
+        // at, so it lines up with the others. anything recorded past the loop
+        // length wraps around and sums with the start
+        let mut samples = vec![0.0; len * CHANNELS];
+
+        for (offset, frame) in recording.samples.chunks(CHANNELS).enumerate() {
+            let index = ((recording.start + offset as u64) % len as u64) as usize;
+
+            for chan in 0..CHANNELS {
+                samples[index * CHANNELS + chan] += frame[chan];
+            }
+        }
+
+        self.loops.push(samples);
+    }
+
+    fn toggle_overdub(&mut self) {
+        if self.loops.is_empty() {
+            return;
+        }
+
+        self.overdubbing = !self.overdubbing;
+
+        if self.overdubbing {
+            self.playing = true;
+        }
+    }
+
+    fn toggle_play(&mut self) {
+        if self.playing || self.recording.is_some() {
+            self.finish_recording();
+            self.overdubbing = false;
+            self.playing = false;
+
+            // playback starts again from the top
+            self.position = 0;
+        } else if !self.loops.is_empty() {
+            self.playing = true;
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Loops::default();
+    }
+
+    // records and overdubs the input frame, returning the loops' playback
+    // for this frame
+    fn process(&mut self, input: [Sample; CHANNELS]) -> [Sample; CHANNELS] {
+        let mut output = [0.0; CHANNELS];
+
+        if self.playing {
+            let position = self.position;
+            let last = self.loops.len() - 1;
+
+            for (index, samples) in self.loops.iter_mut().enumerate() {
+                let frame = (position % frames(samples) as u64) as usize * CHANNELS;
+
+                for chan in 0..CHANNELS {
+                    output[chan] += samples[frame + chan];
+
+                    // overdubbing adds to the most recent loop, after reading
+                    // it so the input isn't heard twice
+                    if self.overdubbing && index == last {
+                        samples[frame + chan] += input[chan];
+                    }
+                }
+            }
+        }
+
+        if let Some(recording) = &mut self.recording {
+            recording.samples.extend_from_slice(&input);
+
+            if frames(&recording.samples) >= MAX_RECORD_SAMPLES {
+                self.finish_recording();
+            }
+        }
+
+        if self.playing || self.recording.is_some() {
+            self.position += 1;
+        }
+
+        output
+    }
+}
+
+fn frames(samples: &[Sample]) -> usize {
+    samples.len() / CHANNELS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(loops: &mut Loops, frames: usize, value: Sample) {
+        loops.toggle_record();
+
+        for _ in 0..frames {
+            loops.process([value, -value]);
+        }
+
+        loops.toggle_record();
+    }
+
+    #[test]
+    fn later_loops_are_multiples_of_the_first() {
+        let mut loops = Loops::default();
+
+        record(&mut loops, 100, 1.0);
+        record(&mut loops, 230, 0.5);
+        record(&mut loops, 20, 0.25);
+
+        let lengths = loops.loops.iter().map(|samples| frames(samples)).collect::<Vec<_>>();
+        assert_eq!(vec![100, 200, 100], lengths);
+    }
+
+    #[test]
+    fn loops_play_back_in_time() {
+        let mut loops = Loops::default();
+
+        record(&mut loops, 100, 1.0);
+
+        // second loop starts half way through the first
+        for _ in 0..50 {
+            loops.process([0.0, 0.0]);
+        }
+
+        record(&mut loops, 50, 0.5);
+
+        // stop and play again from the top
+        loops.toggle_play();
+        loops.toggle_play();
+
+        let out = (0..100)
+            .map(|_| loops.process([0.0, 0.0]))
+            .collect::<Vec<_>>();
+
+        assert_eq!([1.0, -1.0], out[0]);
+        assert_eq!([1.0, -1.0], out[49]);
+        assert_eq!([1.5, -1.5], out[50]);
+        assert_eq!([1.5, -1.5], out[99]);
+    }
+}
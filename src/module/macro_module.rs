@@ -0,0 +1,344 @@
+use std::fmt;
+
+use mixlab_protocol::{ChannelAdapter, Indication, MacroConnection, MacroIndication, MacroParams, MacroTerminal, ModuleParams};
+
+use crate::engine::{self, host_module, DynModuleHost, InputRef, Output, OutputRef};
+use crate::module::{ModuleT, Terminal, PriorityClass};
+
+// where each input of an inner module is fed from
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    Disconnected,
+    // one of the macro's own inputs
+    Exposed(usize),
+    // an output of an earlier module in the macro
+    Module(usize, usize),
+}
+
+pub struct Macro {
+    name: String,
+    modules: Vec<DynModuleHost>,
+    connections: Vec<MacroConnection>,
+    exposed_inputs: Vec<MacroTerminal>,
+    exposed_outputs: Vec<MacroTerminal>,
+    indications: Vec<Indication>,
+    sources: Vec<Vec<Source>>,
+    // how each input fed from inside converts between mono and stereo
+    adapters: Vec<Vec<ChannelAdapter>>,
+    run_order: Vec<usize>,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl fmt::Debug for Macro {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Macro({:?}, {} modules)", self.name, self.modules.len())
+    }
+}
+
+impl ModuleT for Macro {
+    type Params = MacroParams;
+    type Indication = MacroIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let (modules, indications): (Vec<_>, Vec<_>) = params.modules.into_iter()
+            .map(|params| host_module(params, ctx.project()))
+            .unzip();
+
+        // params come from clients, anything not referring to a terminal of
+        // the right type is dropped rather than trusted
+        let mut sources = modules.iter()
+            .map(|module| vec![Source::Disconnected; module.inputs().len()])
+            .collect::<Vec<_>>();
+
+        let mut adapters = modules.iter()
+            .map(|module| vec![ChannelAdapter::default(); module.inputs().len()])
+            .collect::<Vec<_>>();
+
+        let connections = params.connections.into_iter()
+            .filter(|conn| {
+                let output = terminal(&modules, conn.output, true);
+                let input = terminal(&modules, conn.input, false);
+
+                match (output, input) {
+                    (Some(output), Some(input)) => output.line_type().connects_to(input.line_type()),
+                    _ => false,
+                }
+            })
+            .filter(|conn| {
+                // each input takes one cable only
+                let source = &mut sources[conn.input.0][conn.input.1];
+
+                match source {
+                    Source::Disconnected => {
+                        *source = Source::Module(conn.output.0, conn.output.1);
+                        adapters[conn.input.0][conn.input.1] = conn.adapter;
+                        true
+                    }
+                    _ => false,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut exposed_inputs = Vec::new();
+        let mut inputs = Vec::new();
+
+        for exposed in params.inputs {
+            let input = match terminal(&modules, (exposed.module, exposed.terminal), false) {
+                Some(input) => input,
+                None => continue,
+            };
+
+            let source = &mut sources[exposed.module][exposed.terminal];
+
+            // inputs fed from inside the macro can't also be fed from outside
+            if let Source::Disconnected = source {
+                *source = Source::Exposed(exposed_inputs.len());
+                exposed_inputs.push(exposed);
+                inputs.push(input.clone());
+            }
+        }
+
+        let mut exposed_outputs = Vec::new();
+        let mut outputs = Vec::new();
+
+        for exposed in params.outputs {
+            if let Some(output) = terminal(&modules, (exposed.module, exposed.terminal), true) {
+                exposed_outputs.push(exposed);
+                outputs.push(output.clone());
+            }
+        }
+
+        let run_order = run_order(&sources);
+
+        let module = Macro {
+            name: params.name,
+            modules,
+            connections,
+            exposed_inputs,
+            exposed_outputs,
+            indications: indications.clone(),
+            sources,
+            adapters,
+            run_order,
+            inputs,
+            outputs,
+        };
+
+        (module, MacroIndication { modules: indications })
+    }
+
+    fn params(&self) -> Self::Params {
+        MacroParams {
+            name: self.name.clone(),
+            modules: self.modules.iter().map(|module| module.params()).collect(),
+            connections: self.connections.clone(),
+            inputs: self.exposed_inputs.clone(),
+            outputs: self.exposed_outputs.clone(),
+        }
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.name = new_params.name;
+
+        // the modules and cables inside a macro are fixed once created, only
+        // the params of each module can change
+        let mut changed = false;
+
+        for (index, (module, params)) in self.modules.iter_mut().zip(new_params.modules).enumerate() {
+            if !same_kind(&module.params(), &params) {
+                continue;
+            }
+
+            if let Some(indication) = module.update(params) {
+                self.indications[index] = indication;
+                changed = true;
+            }
+        }
+
+        if changed {
+            Some(MacroIndication { modules: self.indications.clone() })
+        } else {
+            None
+        }
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let mut buffers = self.modules.iter()
+            .map(|_| None)
+            .collect::<Vec<Option<Vec<Output>>>>();
+
+        let mut changed = false;
+
+        for &index in &self.run_order {
+            let module = &mut self.modules[index];
+            let sources = &self.sources[index];
+            let adapters = &self.adapters[index];
+
+            let connected = sources.iter()
+                .map(|source| match *source {
+                    Source::Module(module, output) => buffers[module].as_ref().and_then(|buffers| buffers.get(output)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            // as in the engine, converted where line types differ
+            let converted = module.inputs().iter()
+                .zip(connected.iter().zip(adapters))
+                .map(|(input, (output, adapter))| output.and_then(|output| output.convert_to(input.line_type(), *adapter)))
+                .collect::<Vec<_>>();
+
+            let input_refs = sources.iter()
+                .zip(connected.iter().zip(converted.iter()))
+                .map(|(source, (output, converted))| match *source {
+                    Source::Exposed(exposed) => inputs.get(exposed).copied().unwrap_or(InputRef::Disconnected),
+                    _ => converted.as_ref()
+                        .or(*output)
+                        .map(|output| output.as_input_ref())
+                        .unwrap_or(InputRef::Disconnected),
+                })
+                .collect::<Vec<_>>();
+
+            let mut output_buffers = module.outputs().iter()
+                .map(|output| Output::from_line_type(output.line_type()))
+                .collect::<Vec<_>>();
+
+            let mut output_refs = output_buffers.iter_mut()
+                .map(|output| output.as_output_ref())
+                .collect::<Vec<_>>();
+
+            if let Some(indication) = module.run_tick(t, &input_refs, &mut output_refs) {
+                self.indications[index] = indication;
+                changed = true;
+            }
+
+            buffers[index] = Some(output_buffers);
+        }
+
+        for (exposed, output) in self.exposed_outputs.iter().zip(outputs.iter_mut()) {
+            let buffer = buffers[exposed.module].as_ref()
+                .and_then(|buffers| buffers.get(exposed.terminal));
+
+            if let Some(buffer) = buffer {
+                copy_output(buffer, output);
+            }
+        }
+
+        if changed {
+            Some(MacroIndication { modules: self.indications.clone() })
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.modules.iter().map(|module| module.memory_usage()).sum()
+    }
+
+    // a macro is as important as the most important module inside it
+    fn priority(&self) -> PriorityClass {
+        let priorities = self.modules.iter().map(|module| module.priority()).collect::<Vec<_>>();
+
+        if priorities.iter().all(|priority| *priority == PriorityClass::UiFeedback) && !priorities.is_empty() {
+            PriorityClass::UiFeedback
+        } else if priorities.iter().all(|priority| *priority != PriorityClass::RealtimeAudio) && !priorities.is_empty() {
+            PriorityClass::Video
+        } else {
+            PriorityClass::RealtimeAudio
+        }
+    }
+
+    fn ready(&mut self) -> bool {
+        self.modules.iter_mut().all(|module| module.ready())
+    }
+
+    fn finished(&self) -> bool {
+        self.modules.iter().all(|module| module.finished())
+    }
+}
+
+fn terminal(modules: &[DynModuleHost], (module, terminal): (usize, usize), output: bool) -> Option<&Terminal> {
+    let module = modules.get(module)?;
+    let terminals = if output { module.outputs() } else { module.inputs() };
+    terminals.get(terminal)
+}
+
+// depth first from every module through its inputs, as the engine orders a
+// tick. modules in a cycle see the other's output as disconnected
+fn run_order(sources: &[Vec<Source>]) -> Vec<usize> {
+    fn traverse(index: usize, sources: &[Vec<Source>], seen: &mut Vec<bool>, order: &mut Vec<usize>) {
+        if seen[index] {
+            return;
+        }
+
+        seen[index] = true;
+
+        for source in &sources[index] {
+            if let Source::Module(module, _) = *source {
+                traverse(module, sources, seen, order);
+            }
+        }
+
+        order.push(index);
+    }
+
+    let mut seen = vec![false; sources.len()];
+    let mut order = Vec::with_capacity(sources.len());
+
+    for index in 0..sources.len() {
+        traverse(index, sources, &mut seen, &mut order);
+    }
+
+    order
+}
+
+fn same_kind(a: &ModuleParams, b: &ModuleParams) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+fn copy_output(from: &Output, to: &mut OutputRef) {
+    match (from, to) {
+        (Output::Mono(from), OutputRef::Mono(to)) |
+        (Output::Stereo(from), OutputRef::Stereo(to)) |
+        (Output::Control(from), OutputRef::Control(to)) |
+        (Output::Ambisonic(from), OutputRef::Ambisonic(to)) => to.copy_from_slice(from),
+        (Output::Video(from), OutputRef::Video(to)) => **to = from.clone(),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_order_puts_sources_first() {
+        // 0 <- 2 <- 1
+        let sources = vec![
+            vec![Source::Module(2, 0)],
+            vec![Source::Exposed(0)],
+            vec![Source::Module(1, 0), Source::Disconnected],
+        ];
+
+        assert_eq!(vec![1, 2, 0], run_order(&sources));
+    }
+
+    #[test]
+    fn run_order_survives_cycles() {
+        let sources = vec![
+            vec![Source::Module(1, 0)],
+            vec![Source::Module(0, 0)],
+        ];
+
+        assert_eq!(vec![1, 0], run_order(&sources));
+    }
+}
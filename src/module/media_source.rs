@@ -1,63 +1,318 @@
+use std::cmp;
 use std::collections::VecDeque;
-use std::sync::mpsc::{self, SyncSender, Receiver, TryRecvError};
+use std::mem;
+use std::sync::mpsc::{self, Sender, SyncSender, Receiver, TryRecvError};
 use std::thread;
 
 use derive_more::From;
-use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::media::{Audio, Video};
 use mixlab_codec::ffmpeg::codec::{self, CodecBuilder, RecvFrameError, Decode};
-use mixlab_codec::ffmpeg::{AvError, AvIoError, AvIoReader, IoReader, InputContainer};
-use mixlab_protocol::{MediaId, MediaSourceParams};
+use mixlab_codec::ffmpeg::{AvError, AvFrame, AvIoError, AvIoReader, AvPacket, IoReader, InputContainer, UnsupportedSampleFormat};
+use mixlab_protocol::{MediaId, MediaSourceParams, MediaSourceIndication};
 use mixlab_util::time::{MediaTime, MediaDuration, TimeBase};
 
-use crate::engine::{InputRef, OutputRef, VideoFrame, ModuleCtx, SAMPLE_RATE, TICKS_PER_SECOND};
+use crate::engine::{InputRef, OutputRef, Sample, VideoFrame, ModuleCtx, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK};
 use crate::module::{ModuleT, LineType, Terminal};
 use crate::project::media;
 use crate::project::ProjectBaseRef;
 use crate::project::stream::ReadStream;
-use crate::throttle::MediaThrottle;
+use crate::resample::Resampler;
 use crate::video;
 
+// the decode thread runs ahead of the playhead by at most this many decoded
+// frames. audio frames are small, so this is mostly audio
+const DECODE_AHEAD: usize = 16;
+
+// ticks between playhead position indications
+const INDICATION_TICKS: u64 = 6;
+
 #[derive(Debug)]
 pub struct MediaSource {
     ctx: ModuleCtx<Self>,
     params: MediaSourceParams,
     media: Option<OpenMedia>,
+    // media is being opened in the background
+    opening: bool,
+    indication: MediaSourceIndication,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
 }
 
 #[derive(Debug)]
 pub enum MediaSourceEvent {
-    SetMedia(Option<OpenMedia>),
+    SetMedia(Option<MediaId>, Option<OpenMedia>),
 }
 
+// Media is played by the engine pulling decoded frames along its own
+// playhead, rather than the decode thread pushing them in realtime. The
+// playhead only advances while there is enough decoded to cover a whole
+// tick, so media never plays faster than it can be decoded, and pausing is
+// simply not advancing it.
+//
+// Times here are on the decode thread's timeline, in sample frames. This
+// matches media time until playback loops, after which the timeline carries
+// on counting from the end of the previous pass.
 #[derive(Debug)]
 pub struct OpenMedia {
     media_id: MediaId,
-    rx: Receiver<Frame>,
-    epoch: Option<MediaTime>,
+    rx: Receiver<Decoded>,
+    commands: Sender<DecodeCommand>,
+    looping: bool,
+    // None until the decode thread has opened the media
+    tracks: Option<Tracks>,
+    // seek the decode thread has not acknowledged yet. anything received
+    // before the acknowledgement was decoded from before the seek
+    pending_seek: Option<u64>,
+    playhead: i64,
+    // timeline position the current pass through the media started at
+    media_start: i64,
+    // timeline positions of passes yet to start
+    loops: VecDeque<i64>,
+    // decode thread has reached the end of the media and is not looping
+    ended: bool,
     video_buffer: VecDeque<Frame>,
+    // last frame sent, repeated while paused
+    held: Option<HeldFrame>,
+    // interleaved stereo samples waiting to be played
+    audio_buffer: VecDeque<Sample>,
+    // sample frame that the front of audio_buffer plays at
+    audio_position: i64,
+}
+
+#[derive(Debug)]
+struct HeldFrame {
+    frame: video::Frame,
+    // engine time at which to send the frame again
+    repeat_at: u64,
+}
+
+impl OpenMedia {
+    fn position(&self) -> f64 {
+        (self.playhead - self.media_start) as f64 / SAMPLE_RATE as f64
+    }
+
+    fn duration(&self) -> Option<f64> {
+        self.tracks.as_ref()
+            .and_then(|tracks| tracks.duration)
+            .map(|duration| duration.round_to_base(1_000_000) as f64 / 1_000_000.0)
+    }
+
+    fn seek(&mut self, seq: u64, position: f64) {
+        let position = match self.duration() {
+            Some(duration) => position.min(duration),
+            None => position,
+        }.max(0.0);
+
+        let target = (position * SAMPLE_RATE as f64) as i64;
+
+        if self.commands.send(DecodeCommand::Seek(seq, MediaTime::new(target, SAMPLE_RATE as i64))).is_err() {
+            // decode thread is gone, nothing to seek
+            return;
+        }
+
+        // after seeking, the decode thread's timeline is media time again
+        self.pending_seek = Some(seq);
+        self.playhead = target;
+        self.media_start = 0;
+        self.loops.clear();
+        self.ended = false;
+        self.video_buffer.clear();
+        self.audio_buffer.clear();
+    }
+
+    fn set_looping(&mut self, looping: bool) {
+        if self.looping != looping {
+            self.looping = looping;
+            let _ = self.commands.send(DecodeCommand::SetLooping(looping));
+        }
+    }
+
+    // pulls decoded frames until the next tick is covered or the decode
+    // thread has nothing more for us yet
+    fn receive(&mut self) {
+        while self.pending_seek.is_some() || !self.ready() {
+            match self.rx.try_recv() {
+                Ok(decoded) => self.receive_decoded(decoded),
+                Err(TryRecvError::Empty) => { break; }
+                Err(TryRecvError::Disconnected) => {
+                    eprintln!("media_source: decode thread died");
+                    // nothing more is coming, play out what we have
+                    self.ended = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn receive_decoded(&mut self, decoded: Decoded) {
+        if let Decoded::Tracks(tracks) = decoded {
+            self.tracks = Some(tracks);
+            return;
+        }
+
+        if let Some(seq) = self.pending_seek {
+            if let Decoded::Seeked(acknowledged) = decoded {
+                if acknowledged == seq {
+                    self.pending_seek = None;
+                }
+            }
+
+            return;
+        }
+
+        match decoded {
+            Decoded::Tracks(_) => unreachable!(),
+            Decoded::Video(frame) => { self.video_buffer.push_back(frame); }
+            Decoded::Audio(chunk) => { self.receive_audio(chunk.pts, chunk.samples); }
+            Decoded::Loop(start) => { self.loops.push_back(samples(start)); }
+            Decoded::End => { self.ended = true; }
+            // acknowledgement of a seek since superseded by another
+            Decoded::Seeked(_) => {}
+        }
+    }
+
+    fn receive_audio(&mut self, pts: MediaTime, samples: Vec<Sample>) {
+        // chunks from the decode thread are contiguous, so only take the
+        // timestamp when starting from empty. this keeps rounding from
+        // opening up gaps between chunks
+        if self.audio_buffer.is_empty() {
+            self.audio_position = pts.round_to_base(SAMPLE_RATE as i64);
+        }
+
+        self.audio_buffer.extend(samples);
+    }
+
+    // whether the playhead can advance by a tick
+    fn ready(&self) -> bool {
+        let tracks = match &self.tracks {
+            Some(tracks) => tracks,
+            None => return false,
+        };
+
+        if self.ended {
+            // play out whatever is left
+            return !self.video_buffer.is_empty() || !self.audio_buffer.is_empty();
+        }
+
+        let end_of_tick = self.playhead + SAMPLES_PER_TICK as i64;
+
+        let audio_ready = !tracks.audio ||
+            self.audio_position + (self.audio_buffer.len() / CHANNELS) as i64 >= end_of_tick;
+
+        let video_ready = !tracks.video ||
+            self.video_buffer.back().map(|frame| samples(frame.pts) >= end_of_tick).unwrap_or(false);
+
+        audio_ready && video_ready
+    }
+
+    fn play_video(&mut self, t: u64, output: &mut Option<VideoFrame>) {
+        let end_of_tick = self.playhead + SAMPLES_PER_TICK as i64;
+
+        // if frames come faster than ticks, show only the latest of them
+        let mut shown = None;
+
+        while let Some(frame) = self.video_buffer.front() {
+            if samples(frame.pts) >= end_of_tick {
+                break;
+            }
+
+            shown = self.video_buffer.pop_front();
+        }
+
+        if let Some(frame) = shown {
+            let tick_offset = frame.pts - MediaTime::new(self.playhead, SAMPLE_RATE as i64);
+
+            *output = Some(VideoFrame {
+                data: frame.frame.clone(),
+                tick_offset,
+            });
+
+            let repeat_after = (tick_offset + frame.frame.duration_hint).round_to_base(SAMPLE_RATE as i64);
+
+            self.held = Some(HeldFrame {
+                frame: frame.frame,
+                repeat_at: t + cmp::max(repeat_after, 0) as u64,
+            });
+        }
+    }
+
+    // while not advancing, keep repeating the last frame so downstream
+    // modules hold the picture rather than dropping it
+    fn hold_video(&mut self, t: u64, output: &mut Option<VideoFrame>) {
+        if let Some(held) = &mut self.held {
+            if t >= held.repeat_at {
+                *output = Some(VideoFrame {
+                    data: held.frame.clone(),
+                    tick_offset: MediaDuration::zero(),
+                });
+
+                let duration = held.frame.duration_hint.round_to_base(SAMPLE_RATE as i64);
+                held.repeat_at = t + cmp::max(duration, 1) as u64;
+            }
+        }
+    }
+
+    fn play_audio(&mut self, output: &mut [Sample]) {
+        let t = self.playhead;
+
+        // drop anything that arrived too late to play
+        if self.audio_position < t {
+            let late = cmp::min((t - self.audio_position) as usize * CHANNELS, self.audio_buffer.len());
+            self.audio_buffer.drain(..late);
+            self.audio_position += (late / CHANNELS) as i64;
+        }
+
+        let offset = (self.audio_position - t) as usize;
+
+        if offset >= SAMPLES_PER_TICK {
+            return;
+        }
+
+        let count = cmp::min((SAMPLES_PER_TICK - offset) * CHANNELS, self.audio_buffer.len());
+
+        for (out, sample) in output[offset * CHANNELS..].iter_mut().zip(self.audio_buffer.drain(..count)) {
+            *out = sample;
+        }
+
+        self.audio_position += (count / CHANNELS) as i64;
+    }
+
+    fn advance(&mut self) {
+        self.playhead += SAMPLES_PER_TICK as i64;
+
+        while let Some(start) = self.loops.front() {
+            if *start > self.playhead {
+                break;
+            }
+
+            self.media_start = *start;
+            self.loops.pop_front();
+        }
+    }
 }
 
 impl ModuleT for MediaSource {
     type Params = MediaSourceParams;
-    type Indication = ();
+    type Indication = MediaSourceIndication;
     type Event = MediaSourceEvent;
 
     fn create(params: Self::Params, ctx: ModuleCtx<Self>) -> (Self, Self::Indication) {
         let mut module = Self {
             ctx,
-            params: MediaSourceParams::default(),
+            params: MediaSourceParams { media_id: None, ..params.clone() },
             media: None,
+            opening: false,
+            indication: MediaSourceIndication::default(),
             inputs: vec![],
             outputs: vec![
-                LineType::Video.unlabeled(),
+                LineType::Video.labeled("Video"),
+                LineType::Stereo.labeled("Audio"),
             ],
         };
 
         module.update(params);
 
-        (module, ())
+        (module, MediaSourceIndication::default())
     }
 
     fn params(&self) -> Self::Params {
@@ -66,63 +321,81 @@ impl ModuleT for MediaSource {
 
     fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
         if self.params.media_id != params.media_id {
-            self.params.media_id = params.media_id;
-
             let project = self.ctx.project();
+            let media_id = params.media_id;
+            let start = params.position;
+            let looping = params.looping;
 
+            self.opening = true;
             self.ctx.spawn_async(async move {
-                let media = match params.media_id {
-                    Some(media_id) => open_media(project, media_id).await,
+                let media = match media_id {
+                    Some(media_id) => open_media(project, media_id, start, looping).await,
                     None => None,
                 };
 
-                MediaSourceEvent::SetMedia(media)
+                MediaSourceEvent::SetMedia(media_id, media)
             });
+        } else if let Some(media) = &mut self.media {
+            if self.params.seek_seq != params.seek_seq {
+                media.seek(params.seek_seq, params.position);
+            }
+
+            media.set_looping(params.looping);
         }
+
+        self.params = params;
         None
     }
 
     fn receive_event(&mut self, event: MediaSourceEvent) {
         match event {
-            MediaSourceEvent::SetMedia(media) => {
+            MediaSourceEvent::SetMedia(media_id, mut media) => {
+                // media may have changed again while this was opening
+                if media_id != self.params.media_id {
+                    return;
+                }
+
+                if let Some(media) = &mut media {
+                    media.set_looping(self.params.looping);
+                }
+
                 self.media = media;
+                self.opening = false;
             }
         }
     }
 
     fn run_tick(&mut self, t: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
-        let start_of_frame = MediaTime::new(t as i64, SAMPLE_RATE as i64);
-        let end_of_frame = start_of_frame + MediaDuration::new(1, TICKS_PER_SECOND as i64);
+        let media = match &mut self.media {
+            Some(media) => media,
+            None => return None,
+        };
 
-        if let Some(media) = &mut self.media {
-            match media.rx.try_recv() {
-                Err(TryRecvError::Empty) => {}
-                Err(TryRecvError::Disconnected) => {
-                    eprintln!("media_source: decode thread died");
-                }
-                Ok(frame) => {
-                    let epoch = *media.epoch.get_or_insert(start_of_frame);
+        media.receive();
 
-                    media.video_buffer.push_back(Frame {
-                        pts: frame.pts.add_epoch(epoch),
-                        frame: frame.frame,
-                    });
-                }
-            }
+        if self.params.playing && media.pending_seek.is_none() && media.ready() {
+            media.play_video(t, outputs[0].expect_video());
+            media.play_audio(outputs[1].expect_stereo());
+            media.advance();
+        } else {
+            media.hold_video(t, outputs[0].expect_video());
+        }
 
-            if let Some(frame) = media.video_buffer.front() {
-                if frame.pts < end_of_frame {
-                    *outputs[0].expect_video() = Some(VideoFrame {
-                        data: frame.frame.clone(),
-                        tick_offset: frame.pts - start_of_frame,
-                    });
+        if (t / SAMPLES_PER_TICK as u64) % INDICATION_TICKS != 0 {
+            return None;
+        }
 
-                    media.video_buffer.pop_front();
-                }
-            }
+        let indication = MediaSourceIndication {
+            position: media.position(),
+            duration: media.duration(),
+        };
+
+        if indication == self.indication {
+            return None;
         }
 
-        None
+        self.indication = indication.clone();
+        Some(indication)
     }
 
     fn inputs(&self) -> &[Terminal] {
@@ -132,21 +405,82 @@ impl ModuleT for MediaSource {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn memory_usage(&self) -> usize {
+        self.media.as_ref().map(|media| {
+            let video = media.video_buffer.iter()
+                .map(|frame| frame.frame.decoded.buffer_size())
+                .sum::<usize>();
+
+            let audio = media.audio_buffer.capacity() * mem::size_of::<Sample>();
+
+            video + audio
+        }).unwrap_or(0)
+    }
+
+    fn ready(&mut self) -> bool {
+        if self.opening {
+            return false;
+        }
+
+        match &mut self.media {
+            Some(media) if self.params.playing => {
+                media.receive();
+                media.ended || (media.pending_seek.is_none() && media.ready())
+            }
+            _ => true,
+        }
+    }
+
+    fn finished(&self) -> bool {
+        if !self.params.playing {
+            return true;
+        }
+
+        if self.opening {
+            return false;
+        }
+
+        match &self.media {
+            Some(media) => media.ended && media.video_buffer.is_empty() && media.audio_buffer.is_empty(),
+            None => true,
+        }
+    }
 }
 
-async fn open_media(project: ProjectBaseRef, media_id: MediaId) -> Option<OpenMedia> {
+fn samples(time: MediaTime) -> i64 {
+    time.round_to_base(SAMPLE_RATE as i64)
+}
+
+async fn open_media(project: ProjectBaseRef, media_id: MediaId, start: f64, looping: bool) -> Option<OpenMedia> {
     match media::open(project, media_id).await {
         Ok(Some(stream)) => {
-            let (tx, rx) = mpsc::sync_channel(2);
+            let (tx, rx) = mpsc::sync_channel(DECODE_AHEAD);
+            let (commands_tx, commands_rx) = mpsc::channel();
+
+            let start = (start.max(0.0) * SAMPLE_RATE as f64) as i64;
+
             thread::spawn(move || {
-                let result = run_decode_thread(stream, tx);
+                let start = MediaTime::new(start, SAMPLE_RATE as i64);
+                let result = run_decode_thread(stream, tx, commands_rx, start, looping);
                 println!("decode thread said: {:?}", result);
             });
+
             Some(OpenMedia {
                 media_id,
                 rx,
-                epoch: None,
+                commands: commands_tx,
+                looping,
+                tracks: None,
+                pending_seek: None,
+                playhead: start,
+                media_start: 0,
+                loops: VecDeque::new(),
+                ended: false,
                 video_buffer: VecDeque::new(),
+                held: None,
+                audio_buffer: VecDeque::new(),
+                audio_position: start,
             })
         }
         Ok(None) => None,
@@ -157,18 +491,54 @@ async fn open_media(project: ProjectBaseRef, media_id: MediaId) -> Option<OpenMe
     }
 }
 
+#[derive(Debug)]
+enum DecodeCommand {
+    Seek(u64, MediaTime),
+    SetLooping(bool),
+}
+
+#[derive(Debug)]
+enum Decoded {
+    // always sent first
+    Tracks(Tracks),
+    Video(Frame),
+    Audio(AudioChunk),
+    // the media starts again from the beginning at this point in the timeline
+    Loop(MediaTime),
+    // reached the end of the media with looping off
+    End,
+    // acknowledges a seek, everything after is from the new position
+    Seeked(u64),
+}
+
+#[derive(Debug)]
+struct Tracks {
+    audio: bool,
+    video: bool,
+    duration: Option<MediaDuration>,
+}
+
 #[derive(Debug)]
 struct Frame {
     pts: MediaTime,
     frame: video::Frame,
 }
 
+#[derive(Debug)]
+struct AudioChunk {
+    pts: MediaTime,
+    // interleaved stereo at engine sample rate
+    samples: Vec<Sample>,
+}
+
 #[derive(Debug, From)]
 enum DecodeError {
     CodecBuild(codec::BuildError),
     CodecOpen(codec::OpenError),
+    NoStreams,
     NoFrames,
     RecvFrame(RecvFrameError),
+    SampleFormat(UnsupportedSampleFormat),
     Av(AvError),
     Io(<ReadStream as IoReader>::Error),
 }
@@ -182,7 +552,7 @@ impl From<AvIoError<ReadStream>> for DecodeError {
     }
 }
 
-fn run_decode_thread(stream: ReadStream, tx: SyncSender<Frame>) -> Result<(), DecodeError> {
+fn run_decode_thread(stream: ReadStream, tx: SyncSender<Decoded>, commands: Receiver<DecodeCommand>, start: MediaTime, looping: bool) -> Result<(), DecodeError> {
     let container = InputContainer::open(AvIoReader::new(stream))?;
 
     for (idx, stream) in container.streams().iter().enumerate() {
@@ -190,103 +560,448 @@ fn run_decode_thread(stream: ReadStream, tx: SyncSender<Frame>) -> Result<(), De
         // println!("            Time base: {}", stream.time_base());
     }
 
-    let video_stream = &container.streams()[0];
-    let video_time_base = video_stream.time_base();
-    let video_codec_params = video_stream.codec_parameters();
+    let streams = container.streams();
 
-    let video_decode = CodecBuilder::<Video>::new(video_codec_params.codec_id, video_time_base)?
-        .with_parameters(video_codec_params)
-        .open_decoder()?;
+    let video = match streams.iter().position(|stream| stream.is_media::<Video>()) {
+        Some(index) => {
+            let time_base = streams[index].time_base();
+            let codec_params = streams[index].codec_parameters();
+
+            let decode = CodecBuilder::<Video>::new(codec_params.codec_id, time_base)?
+                .with_parameters(codec_params)
+                .open_decoder()?;
+
+            Some(VideoTrack { index, decode, time_base })
+        }
+        None => None,
+    };
+
+    let audio = match streams.iter().position(|stream| stream.is_media::<Audio>()) {
+        Some(index) => {
+            let time_base = streams[index].time_base();
+            let codec_params = streams[index].codec_parameters();
+
+            let decode = CodecBuilder::<Audio>::new(codec_params.codec_id, time_base)?
+                .with_parameters(codec_params)
+                .open_decoder()?;
+
+            Some(AudioTrack {
+                index,
+                decode,
+                time_base,
+                resample: None,
+                samples: Vec::new(),
+            })
+        }
+        None => None,
+    };
+
+    if video.is_none() && audio.is_none() {
+        return Err(DecodeError::NoStreams);
+    }
+
+    let tracks = Tracks {
+        audio: audio.is_some(),
+        video: video.is_some(),
+        duration: container.duration(),
+    };
+
+    if tx.send(Decoded::Tracks(tracks)).is_err() {
+        return Ok(());
+    }
 
     let mut play = PlaybackContext {
         container,
-        video_decode,
-        video_time_base,
-        throttle: MediaThrottle::new(),
+        video,
+        audio,
         tx,
+        looping,
+        iter_start: MediaTime::zero(),
+        iter_end: None,
+        trim_before: None,
     };
 
-    let mut iter_start = MediaTime::zero();
-
-    while let Some(iter_end) = play_once(&mut play, iter_start)? {
-        play.video_decode.flush_buffers();
-        play.container.seek(MediaTime::zero())?;
-        iter_start = iter_end;
+    if start > MediaTime::zero() {
+        seek(&mut play, start)?;
     }
 
-    Ok(())
+    loop {
+        for command in commands.try_iter() {
+            if let Flow::Stop = apply_command(&mut play, command)? {
+                return Ok(());
+            }
+        }
+
+        match play.container.read_packet()? {
+            Some(pkt) => {
+                if let Flow::Stop = decode_packet(&mut play, &pkt)? {
+                    return Ok(());
+                }
+            }
+            None => {
+                if let Flow::Stop = finish_decoding(&mut play)? {
+                    return Ok(());
+                }
+
+                if play.looping {
+                    if let Flow::Stop = rewind(&mut play)? {
+                        return Ok(());
+                    }
+
+                    continue;
+                }
+
+                if play.tx.send(Decoded::End).is_err() {
+                    return Ok(());
+                }
+
+                // wait at the end for the engine to seek somewhere or turn
+                // looping back on
+                loop {
+                    let command = match commands.recv() {
+                        Ok(command) => command,
+                        Err(_) => return Ok(()),
+                    };
+
+                    let seeked = match command {
+                        DecodeCommand::Seek(..) => true,
+                        DecodeCommand::SetLooping(_) => false,
+                    };
+
+                    if let Flow::Stop = apply_command(&mut play, command)? {
+                        return Ok(());
+                    }
+
+                    if seeked {
+                        break;
+                    }
+
+                    if play.looping {
+                        if let Flow::Stop = rewind(&mut play)? {
+                            return Ok(());
+                        }
+
+                        break;
+                    }
+                }
+            }
+        }
+    }
 }
 
 struct PlaybackContext {
     container: InputContainer<ReadStream>,
-    video_decode: Decode<Video>,
-    video_time_base: TimeBase,
-    throttle: MediaThrottle,
-    tx: SyncSender<Frame>,
+    video: Option<VideoTrack>,
+    audio: Option<AudioTrack>,
+    tx: SyncSender<Decoded>,
+    looping: bool,
+    // timeline position of the start of this pass through the media
+    iter_start: MediaTime,
+    // timeline position of the end of the latest frame decoded
+    iter_end: Option<MediaTime>,
+    // after seeking, anything decoded before this media time is discarded
+    trim_before: Option<MediaTime>,
 }
 
-fn play_once(play: &mut PlaybackContext, iter_start: MediaTime) -> Result<Option<MediaTime>, DecodeError> {
-    let mut iter_end = None;
-    let mut reached_end_of_stream = false;
+struct VideoTrack {
+    index: usize,
+    decode: Decode<Video>,
+    time_base: TimeBase,
+}
 
-    loop {
-        // read packet and send to decoder
-        if !reached_end_of_stream {
-            match play.container.read_packet()? {
-                Some(pkt) => {
-                    if pkt.stream_index() != 0 {
-                        continue;
-                    }
+struct AudioTrack {
+    index: usize,
+    decode: Decode<Audio>,
+    time_base: TimeBase,
+    resample: Option<AudioResample>,
+    // scratch space for decoded samples
+    samples: Vec<Sample>,
+}
 
-                    play.video_decode.send_packet(&pkt)?;
-                }
-                None => {
-                    play.video_decode.end_of_stream()?;
-                    reached_end_of_stream = true;
-                }
+// a run of contiguous audio passing through one resampler
+struct AudioResample {
+    resampler: Resampler,
+    source_rate: usize,
+    // pts of the first sample fed to the resampler
+    start: MediaTime,
+    // sample frames the resampler has produced so far
+    produced: i64,
+}
+
+enum Flow {
+    Continue,
+    // receiver disconnected
+    Stop,
+}
+
+fn apply_command(play: &mut PlaybackContext, command: DecodeCommand) -> Result<Flow, DecodeError> {
+    match command {
+        DecodeCommand::Seek(seq, time) => {
+            seek(play, time)?;
+
+            if play.tx.send(Decoded::Seeked(seq)).is_err() {
+                return Ok(Flow::Stop);
             }
         }
+        DecodeCommand::SetLooping(looping) => {
+            play.looping = looping;
+        }
+    }
 
-        // receive decoded frame from codec if ready
-        match play.video_decode.recv_frame() {
-            Ok(decoded) => {
-                // TODO what to do if packet duration is ever 0? some container
-                // formats do not encode frame duration. assert for now and
-                // deal with it later
-                assert!(decoded.packet_duration() != 0);
+    Ok(Flow::Continue)
+}
 
-                let pts = play.video_time_base
-                    .scale_timestamp(decoded.presentation_timestamp())
-                    .add_epoch(iter_start);
+fn seek(play: &mut PlaybackContext, time: MediaTime) -> Result<(), DecodeError> {
+    flush_decoders(play);
 
-                let duration = play.video_time_base
-                    .scale_duration(decoded.packet_duration());
+    // audio on either side of a seek is not contiguous, so start resampling
+    // afresh rather than flushing the tail
+    if let Some(audio) = &mut play.audio {
+        audio.resample = None;
+    }
 
-                iter_end = Some(pts + duration);
+    play.container.seek(time)?;
+    play.iter_start = MediaTime::zero();
+    play.iter_end = None;
+    play.trim_before = Some(time);
 
-                let frame = Frame {
-                    pts: pts,
-                    frame: video::Frame {
-                        decoded,
-                        duration_hint: duration,
-                    },
-                };
+    Ok(())
+}
 
-                match play.tx.send(frame) {
-                    Ok(()) => {}
-                    Err(_) => {
-                        // receiver disconnected
-                        return Ok(None);
-                    }
-                }
+fn rewind(play: &mut PlaybackContext) -> Result<Flow, DecodeError> {
+    let iter_end = play.iter_end.ok_or(DecodeError::NoFrames)?;
+
+    flush_decoders(play);
+    play.container.seek(MediaTime::zero())?;
+    play.iter_start = iter_end;
+    play.iter_end = None;
+    play.trim_before = None;
+
+    if play.tx.send(Decoded::Loop(iter_end)).is_err() {
+        return Ok(Flow::Stop);
+    }
+
+    Ok(Flow::Continue)
+}
+
+fn flush_decoders(play: &mut PlaybackContext) {
+    if let Some(video) = &mut play.video {
+        video.decode.flush_buffers();
+    }
+
+    if let Some(audio) = &mut play.audio {
+        audio.decode.flush_buffers();
+    }
+}
+
+fn decode_packet(play: &mut PlaybackContext, pkt: &AvPacket) -> Result<Flow, DecodeError> {
+    let index = pkt.stream_index() as usize;
+
+    if play.video.as_ref().map(|video| video.index) == Some(index) {
+        play.video.as_mut().unwrap().decode.send_packet(pkt)?;
+        recv_video(play)
+    } else if play.audio.as_ref().map(|audio| audio.index) == Some(index) {
+        play.audio.as_mut().unwrap().decode.send_packet(pkt)?;
+        recv_audio(play)
+    } else {
+        Ok(Flow::Continue)
+    }
+}
+
+// drains whatever the decoders are still holding on to at end of stream
+fn finish_decoding(play: &mut PlaybackContext) -> Result<Flow, DecodeError> {
+    if let Some(video) = &mut play.video {
+        video.decode.end_of_stream()?;
+
+        if let Flow::Stop = recv_video(play)? {
+            return Ok(Flow::Stop);
+        }
+    }
+
+    if let Some(audio) = &mut play.audio {
+        audio.decode.end_of_stream()?;
+
+        if let Flow::Stop = recv_audio(play)? {
+            return Ok(Flow::Stop);
+        }
+
+        if let Some(resample) = play.audio.as_mut().unwrap().resample.take() {
+            if let Flow::Stop = flush_resampler(play, resample) {
+                return Ok(Flow::Stop);
+            }
+        }
+    }
+
+    Ok(Flow::Continue)
+}
+
+fn recv_video(play: &mut PlaybackContext) -> Result<Flow, DecodeError> {
+    loop {
+        let track = play.video.as_mut().expect("video track");
+
+        let decoded = match track.decode.recv_frame() {
+            Ok(decoded) => decoded,
+            Err(RecvFrameError::NeedMoreInput) | Err(RecvFrameError::Eof) => {
+                return Ok(Flow::Continue);
+            }
+            Err(e) => { return Err(e.into()); }
+        };
+
+        // TODO what to do if packet duration is ever 0? some container
+        // formats do not encode frame duration. assert for now and
+        // deal with it later
+        assert!(decoded.packet_duration() != 0);
+
+        let pts = track.time_base.scale_timestamp(decoded.presentation_timestamp());
+        let duration = track.time_base.scale_duration(decoded.packet_duration());
 
-                play.throttle.wait_until(pts);
+        if let Some(trim_before) = play.trim_before {
+            if pts + duration <= trim_before {
+                continue;
+            }
+        }
+
+        let pts = pts.add_epoch(play.iter_start);
+        extend_end(&mut play.iter_end, pts + duration);
+
+        let frame = Frame {
+            pts: pts,
+            frame: video::Frame {
+                key_frame: decoded.is_key_frame(),
+                decoded,
+                duration_hint: duration,
+                passthrough: None,
+            },
+        };
+
+        if play.tx.send(Decoded::Video(frame)).is_err() {
+            return Ok(Flow::Stop);
+        }
+    }
+}
+
+fn recv_audio(play: &mut PlaybackContext) -> Result<Flow, DecodeError> {
+    loop {
+        let track = play.audio.as_mut().expect("audio track");
+
+        let decoded = match track.decode.recv_frame() {
+            Ok(decoded) => decoded,
+            Err(RecvFrameError::NeedMoreInput) | Err(RecvFrameError::Eof) => {
+                return Ok(Flow::Continue);
             }
-            Err(RecvFrameError::NeedMoreInput) => { continue; }
-            Err(RecvFrameError::Eof) => { break; }
             Err(e) => { return Err(e.into()); }
+        };
+
+        let mut pts = track.time_base.scale_timestamp(decoded.presentation_timestamp());
+        let source_rate = decoded.sample_rate();
+        let duration = MediaDuration::new(decoded.sample_count() as i64, source_rate as i64);
+
+        // skip leading samples from before a seek target
+        let mut skip = 0;
+
+        if let Some(trim_before) = play.trim_before {
+            if pts + duration <= trim_before {
+                continue;
+            }
+
+            if pts < trim_before {
+                skip = (trim_before - pts).round_to_base(source_rate as i64) as usize;
+                pts = trim_before;
+            }
         }
+
+        let pts = pts.add_epoch(play.iter_start);
+        extend_end(&mut play.iter_end, pts + duration);
+
+        // start a new resampler if the sample rate ever changes mid stream
+        if track.resample.as_ref().map(|resample| resample.source_rate) != Some(source_rate) {
+            if let Some(resample) = track.resample.take() {
+                if let Flow::Stop = flush_resampler(play, resample) {
+                    return Ok(Flow::Stop);
+                }
+            }
+
+            play.audio.as_mut().unwrap().resample = Some(AudioResample {
+                resampler: Resampler::new(source_rate, SAMPLE_RATE, CHANNELS),
+                source_rate,
+                start: pts,
+                produced: 0,
+            });
+        }
+
+        let track = play.audio.as_mut().unwrap();
+
+        track.samples.clear();
+        read_stereo(&decoded, &mut track.samples)?;
+
+        let skip = cmp::min(skip * CHANNELS, track.samples.len());
+
+        let resample = track.resample.as_mut().unwrap();
+        let mut samples = Vec::new();
+        resample.resampler.process(&track.samples[skip..], &mut samples);
+
+        let chunk = take_chunk(resample, samples);
+
+        if let Flow::Stop = send_audio(play, chunk) {
+            return Ok(Flow::Stop);
+        }
+    }
+}
+
+fn flush_resampler(play: &mut PlaybackContext, mut resample: AudioResample) -> Flow {
+    let mut samples = Vec::new();
+    resample.resampler.flush(&mut samples);
+
+    let chunk = take_chunk(&mut resample, samples);
+    send_audio(play, chunk)
+}
+
+fn take_chunk(resample: &mut AudioResample, samples: Vec<Sample>) -> AudioChunk {
+    // timestamps come from the resampler's output position rather than the
+    // decoded frame, which keeps chunks contiguous
+    let pts = resample.start + MediaDuration::new(resample.produced, SAMPLE_RATE as i64);
+    resample.produced += (samples.len() / CHANNELS) as i64;
+
+    AudioChunk { pts, samples }
+}
+
+fn send_audio(play: &mut PlaybackContext, chunk: AudioChunk) -> Flow {
+    if chunk.samples.is_empty() {
+        return Flow::Continue;
+    }
+
+    match play.tx.send(Decoded::Audio(chunk)) {
+        Ok(()) => Flow::Continue,
+        Err(_) => Flow::Stop,
+    }
+}
+
+// maps whatever channel layout the media has onto stereo: mono is duplicated
+// to both sides, anything wider keeps only its first two channels
+pub fn read_stereo(frame: &AvFrame<Audio>, out: &mut Vec<Sample>) -> Result<(), UnsupportedSampleFormat> {
+    let channels = frame.channel_count();
+
+    if channels == CHANNELS {
+        return frame.read_interleaved(out);
     }
 
-    Ok(Some(iter_end.ok_or(DecodeError::NoFrames)?))
+    let mut interleaved = Vec::new();
+    frame.read_interleaved(&mut interleaved)?;
+
+    for sample in interleaved.chunks(channels) {
+        match sample {
+            [mono] => { out.extend(&[*mono, *mono]); }
+            [left, right, ..] => { out.extend(&[*left, *right]); }
+            [] => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn extend_end(iter_end: &mut Option<MediaTime>, end: MediaTime) {
+    *iter_end = Some(match *iter_end {
+        Some(iter_end) => cmp::max(iter_end, end),
+        None => end,
+    });
 }
@@ -0,0 +1,86 @@
+use mixlab_protocol::{LineType, Terminal, MeterIndication, MeterCommand, ModuleCommand};
+
+use crate::engine::{self, InputRef, OutputRef, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::meter::Meter as Levels;
+use crate::module::ModuleT;
+
+// ticks between level indications, fast enough for meters to look smooth
+const INDICATION_TICKS: u64 = 3;
+
+#[derive(Debug)]
+pub struct Meter {
+    levels: Levels,
+    indication: MeterIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Meter {
+    type Params = ();
+    type Indication = MeterIndication;
+    type Event = MeterCommand;
+
+    fn create(_: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let levels = Levels::new(SAMPLE_RATE, CHANNELS);
+        let indication = MeterIndication { channels: levels.levels() };
+
+        let module = Meter {
+            levels,
+            indication: indication.clone(),
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        ()
+    }
+
+    fn update(&mut self, _: Self::Params) -> Option<Self::Indication> {
+        None
+    }
+
+    fn command_event(command: ModuleCommand) -> Option<MeterCommand> {
+        match command {
+            ModuleCommand::Meter(command) => Some(command),
+            _ => None,
+        }
+    }
+
+    fn receive_event(&mut self, command: MeterCommand) {
+        match command {
+            MeterCommand::ResetClip => self.levels.reset_clip(),
+        }
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let output = outputs[0].expect_stereo();
+
+        output.copy_from_slice(input);
+        self.levels.process(input);
+
+        if (t / SAMPLES_PER_TICK as u64) % INDICATION_TICKS != 0 {
+            return None;
+        }
+
+        let indication = MeterIndication { channels: self.levels.levels() };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
@@ -0,0 +1,236 @@
+use mixlab_protocol::{MidiNoteParams, VoiceAllocation, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, SAMPLES_PER_TICK, CONTROL_PERIOD};
+use crate::midi::{MidiEvent, MidiSubscription};
+use crate::module::ModuleT;
+
+#[derive(Debug)]
+pub struct MidiNote {
+    params: MidiNoteParams,
+    midi: MidiSubscription,
+    voices: Voices,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Voice {
+    held: Option<u8>,
+    // pitch stays at the last note played after it is released, so that the
+    // release of an envelope downstream doesn't change pitch
+    note: u8,
+    // order in which voices were started, for taking over the oldest
+    started: u64,
+    // the gate drops for one sample when a held voice is taken over by a new
+    // note, so that envelopes downstream retrigger
+    retrigger: bool,
+}
+
+#[derive(Debug)]
+struct Voices {
+    voices: Vec<Voice>,
+    // where round robin allocation looks first
+    next: usize,
+    notes_started: u64,
+}
+
+impl Voices {
+    fn new(count: usize) -> Self {
+        Voices {
+            voices: vec![Voice::default(); count.max(1)],
+            next: 0,
+            notes_started: 0,
+        }
+    }
+
+    fn note_on(&mut self, allocation: VoiceAllocation, note: u8) {
+        // playing a note that is already held retriggers its voice rather
+        // than doubling it
+        let index = match self.voices.iter().position(|voice| voice.held == Some(note)) {
+            Some(index) => index,
+            None => self.allocate(allocation),
+        };
+
+        let voice = &mut self.voices[index];
+        voice.retrigger = voice.held.is_some();
+        voice.held = Some(note);
+        voice.note = note;
+        voice.started = self.notes_started;
+
+        self.notes_started += 1;
+    }
+
+    fn note_off(&mut self, note: u8) {
+        for voice in &mut self.voices {
+            if voice.held == Some(note) {
+                voice.held = None;
+            }
+        }
+    }
+
+    fn release_all(&mut self) {
+        for voice in &mut self.voices {
+            voice.held = None;
+        }
+    }
+
+    fn allocate(&mut self, allocation: VoiceAllocation) -> usize {
+        let count = self.voices.len();
+
+        let index = match allocation {
+            VoiceAllocation::RoundRobin => {
+                (0..count)
+                    .map(|offset| (self.next + offset) % count)
+                    .find(|index| self.voices[*index].held.is_none())
+                    .unwrap_or(self.next)
+            }
+            VoiceAllocation::LastNote => {
+                self.voices.iter()
+                    .position(|voice| voice.held.is_none())
+                    .or_else(|| {
+                        self.voices.iter()
+                            .enumerate()
+                            .min_by_key(|(_, voice)| voice.started)
+                            .map(|(index, _)| index)
+                    })
+                    .unwrap_or(0)
+            }
+        };
+
+        self.next = (index + 1) % count;
+        index
+    }
+}
+
+impl ModuleT for MidiNote {
+    type Params = MidiNoteParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let voices = Voices::new(params.voices);
+        let count = voices.voices.len();
+
+        let outputs = (0..count).flat_map(|index| {
+            if count == 1 {
+                vec![LineType::Control.labeled("Pitch"), LineType::Mono.labeled("Gate")]
+            } else {
+                vec![
+                    LineType::Control.labeled(&format!("Pitch {}", index + 1)),
+                    LineType::Mono.labeled(&format!("Gate {}", index + 1)),
+                ]
+            }
+        }).collect();
+
+        (Self {
+            params: MidiNoteParams { voices: count, ..params },
+            midi: ctx.subscribe_midi(),
+            voices,
+            inputs: vec![],
+            outputs,
+        }, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        if self.params.channel != params.channel {
+            self.voices.release_all();
+        }
+
+        // the voice count is fixed by the outputs created with the module
+        self.params = MidiNoteParams { voices: self.params.voices, ..params };
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let mut midi_events = self.midi.tick_events().into_iter().peekable();
+
+        for i in 0..SAMPLES_PER_TICK {
+            // Apply any MIDI notes due at this sample
+            while let Some(&(_, event)) = midi_events.peek().filter(|(offset, _)| *offset <= i) {
+                match event {
+                    MidiEvent::NoteOn { channel, note, .. } if self.listens(channel) => {
+                        self.voices.note_on(self.params.allocation, note);
+                    }
+                    MidiEvent::NoteOff { channel, note } if self.listens(channel) => {
+                        self.voices.note_off(note);
+                    }
+                    _ => {}
+                }
+
+                midi_events.next();
+            }
+
+            for (index, voice) in self.voices.voices.iter_mut().enumerate() {
+                if i % CONTROL_PERIOD == 0 {
+                    // note number scaled so the full MIDI range spans 0.0 to 1.0
+                    outputs[index * 2].expect_control()[i / CONTROL_PERIOD] = voice.note as Sample / 127.0;
+                }
+
+                let gate = voice.held.is_some() && !voice.retrigger;
+                outputs[index * 2 + 1].expect_mono()[i] = if gate { 1.0 } else { 0.0 };
+
+                voice.retrigger = false;
+            }
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl MidiNote {
+    fn listens(&self, channel: u8) -> bool {
+        self.params.channel.map(|ch| ch == channel).unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(voices: &Voices) -> Vec<Option<u8>> {
+        voices.voices.iter().map(|voice| voice.held).collect()
+    }
+
+    #[test]
+    fn round_robin_cycles_through_voices() {
+        let mut voices = Voices::new(3);
+
+        voices.note_on(VoiceAllocation::RoundRobin, 60);
+        voices.note_off(60);
+        voices.note_on(VoiceAllocation::RoundRobin, 62);
+        voices.note_on(VoiceAllocation::RoundRobin, 64);
+        assert_eq!(vec![None, Some(62), Some(64)], held(&voices));
+
+        // free voices are preferred, then the next in turn is taken over
+        voices.note_on(VoiceAllocation::RoundRobin, 65);
+        voices.note_on(VoiceAllocation::RoundRobin, 67);
+        assert_eq!(vec![Some(65), Some(67), Some(64)], held(&voices));
+    }
+
+    #[test]
+    fn last_note_takes_over_oldest_voice() {
+        let mut voices = Voices::new(2);
+
+        voices.note_on(VoiceAllocation::LastNote, 60);
+        voices.note_on(VoiceAllocation::LastNote, 62);
+        voices.note_on(VoiceAllocation::LastNote, 64);
+        assert_eq!(vec![Some(64), Some(62)], held(&voices));
+        assert!(voices.voices[0].retrigger);
+
+        voices.note_off(62);
+        voices.note_on(VoiceAllocation::LastNote, 65);
+        assert_eq!(vec![Some(64), Some(65)], held(&voices));
+    }
+}
@@ -1,49 +1,113 @@
-use mixlab_protocol::{MixerParams, LineType, Terminal};
+use mixlab_protocol::{MixerParams, MixerIndication, MixerChannelLevels, LineType, Terminal, METER_FLOOR_DB, MIXER_MAX_CHANNELS};
 
-use crate::engine::{self, Sample, InputRef, OutputRef};
+use crate::engine::{self, Sample, ControlRef, InputRef, OutputRef, MonitorPolicy, Smoothed, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::meter::Meter;
 use crate::module::ModuleT;
 use crate::util;
 
+// cue ducking dims quickly so talkback is intelligible immediately, and
+// restores slowly to avoid an abrupt jump in level:
+const CUE_DUCK_ATTACK_SECS: f64 = 0.05;
+const CUE_DUCK_RELEASE_SECS: f64 = 1.0;
+
+// ticks between level indications, the same as the meter module
+const INDICATION_TICKS: u64 = 3;
+
 #[derive(Debug)]
 pub struct Mixer {
     params: MixerParams,
     ctx: Option<engine::ModuleCtx<Self>>,
+    monitor: MonitorPolicy,
+    cue_gain: f64,
+    // each channel's fader and gain together
+    gains: Vec<Smoothed>,
+    meters: Vec<ChannelMeters>,
+    // each channel after its fader, before summing into master
+    channel_buffer: Vec<Sample>,
+    indication: MixerIndication,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
 }
 
 impl ModuleT for Mixer {
     type Params = MixerParams;
-    type Indication = ();
+    type Indication = MixerIndication;
     type Event = ();
 
-    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+    fn create(mut params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        params.channels.truncate(MIXER_MAX_CHANNELS);
+
         let mixer = Mixer {
+            // fader modulation inputs follow all channel inputs, labelled so
+            // that connections follow their channel as the mixer is resized
             inputs: params.channels.iter().enumerate().map(|(i, _)| {
                 LineType::Stereo.labeled(&(i+1).to_string())
-            }).collect(),
+            }).chain(params.channels.iter().enumerate().map(|(i, _)| {
+                LineType::Control.labeled(&format!("Fader {}", i+1))
+            })).collect(),
             outputs: vec![
                 LineType::Stereo.labeled("Master"),
                 LineType::Stereo.labeled("Cue"),
             ],
+            gains: params.channels.iter()
+                .map(|channel| Smoothed::with_default(channel.fader * channel.gain.to_linear()))
+                .collect(),
+            meters: params.channels.iter().map(|_| ChannelMeters::new()).collect(),
+            channel_buffer: vec![0.0; SAMPLES_PER_TICK * CHANNELS],
+            indication: MixerIndication::default(),
             params,
+            monitor: ctx.monitor_policy(),
+            cue_gain: 1.0,
             ctx: Some(ctx),
         };
 
-        (mixer, ())
+        let indication = mixer.levels();
+        (mixer, indication)
     }
 
     fn params(&self) -> Self::Params {
         self.params.clone()
     }
 
-    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+    fn update(&mut self, mut params: Self::Params) -> Option<Self::Indication> {
+        params.channels.truncate(MIXER_MAX_CHANNELS);
+
+        // a fader or gain moved, rather than the mixer being resized. its
+        // terminals stay as they are and the channel just glides to its new
+        // gain
+        if params.channels.len() == self.params.channels.len() {
+            for (gain, channel) in self.gains.iter_mut().zip(&params.channels) {
+                gain.set(channel.fader * channel.gain.to_linear());
+            }
+
+            self.params = params;
+            return None;
+        }
+
+        let cue_gain = self.cue_gain;
+        let gains = std::mem::take(&mut self.gains);
+        let meters = std::mem::take(&mut self.meters);
         let (new, _) = Self::create(params, self.ctx.take().unwrap());
         *self = new;
-        None
+        self.cue_gain = cue_gain;
+
+        // and keep their meters, so levels don't drop out as a fader moves
+        for (meter, old) in self.meters.iter_mut().zip(meters) {
+            *meter = old;
+        }
+
+        // channels carry on from where they were, gliding to their new gain
+        for (gain, mut old) in self.gains.iter_mut().zip(gains) {
+            old.set(gain.target());
+            *gain = old;
+        }
+
+        let indication = self.levels();
+        self.indication = indication.clone();
+        Some(indication)
     }
 
-    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
         let (master, cue) = match outputs {
             [master, cue] => (master.expect_stereo(), cue.expect_stereo()),
             _ => unreachable!(),
@@ -54,20 +118,60 @@ impl ModuleT for Mixer {
         util::zero(master);
         util::zero(cue);
 
-        for (ch, channel) in self.params.channels.iter().enumerate() {
+        let channel_count = self.params.channels.len();
+
+        let channels = self.params.channels.iter().zip(self.gains.iter_mut()).zip(self.meters.iter_mut());
+
+        for (ch, ((channel, gain), meters)) in channels.enumerate() {
             let input = inputs[ch].expect_stereo();
-            let channel_gain = channel.fader * channel.gain.to_linear();
+            let fader_mod = inputs[channel_count + ch].expect_control();
+
+            let faded = &mut self.channel_buffer[0..len];
+            util::zero(faded);
+            sum_channel(faded, input, gain, fader_mod);
 
-            for i in 0..len {
-                master[i] += (input[i] as f64 * channel_gain) as Sample;
+            meters.pre_fader.process(input);
+            meters.post_fader.process(faded);
 
-                if channel.cue {
+            for (master, sample) in master.iter_mut().zip(faded.iter()) {
+                *master += sample;
+            }
+
+            if channel.cue {
+                for i in 0..len {
                     cue[i] += input[i];
                 }
             }
         }
 
-        None
+        let (target_gain, time_constant) = if self.monitor.should_duck() {
+            (self.params.cue_duck.to_linear(), CUE_DUCK_ATTACK_SECS)
+        } else {
+            (1.0, CUE_DUCK_RELEASE_SECS)
+        };
+
+        let coeff = 1.0 - (-1.0 / (time_constant * SAMPLE_RATE as f64)).exp();
+
+        for frame in cue.chunks_mut(2) {
+            self.cue_gain += (target_gain - self.cue_gain) * coeff;
+
+            for sample in frame {
+                *sample = (*sample as f64 * self.cue_gain) as Sample;
+            }
+        }
+
+        if (t / SAMPLES_PER_TICK as u64) % INDICATION_TICKS != 0 {
+            return None;
+        }
+
+        let indication = self.levels();
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
     }
 
     fn inputs(&self) -> &[Terminal] {
@@ -78,3 +182,50 @@ impl ModuleT for Mixer {
         &self.outputs
     }
 }
+
+impl Mixer {
+    fn levels(&self) -> MixerIndication {
+        MixerIndication {
+            channels: self.meters.iter().map(ChannelMeters::levels).collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ChannelMeters {
+    pre_fader: Meter,
+    post_fader: Meter,
+}
+
+impl ChannelMeters {
+    fn new() -> Self {
+        ChannelMeters {
+            pre_fader: Meter::new(SAMPLE_RATE, CHANNELS),
+            post_fader: Meter::new(SAMPLE_RATE, CHANNELS),
+        }
+    }
+
+    fn levels(&self) -> MixerChannelLevels {
+        MixerChannelLevels {
+            pre_fader_db: peak_db(&self.pre_fader),
+            post_fader_db: peak_db(&self.post_fader),
+        }
+    }
+}
+
+fn peak_db(meter: &Meter) -> f32 {
+    meter.levels().iter().map(|level| level.peak_db).fold(METER_FLOOR_DB, f32::max)
+}
+
+// adds a stereo channel into the master bus at the given gain, modulated by
+// the channel's fader input if connected
+pub fn sum_channel(master: &mut [Sample], input: &[Sample], gain: &mut Smoothed, fader_mod: Option<ControlRef>) {
+    for (frame, (master, input)) in master.chunks_mut(CHANNELS).zip(input.chunks(CHANNELS)).enumerate() {
+        let gain = gain.next();
+        let gain = fader_mod.map(|ctl| ctl.at(frame) as f64 * gain).unwrap_or(gain);
+
+        for (master, input) in master.iter_mut().zip(input) {
+            *master += (*input as f64 * gain) as Sample;
+        }
+    }
+}
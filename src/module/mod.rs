@@ -1,8 +1,8 @@
 use std::any::Any;
 
-use mixlab_protocol::{Terminal, LineType};
+use mixlab_protocol::{Terminal, LineType, ModuleCommand};
 
-use crate::engine::{InputRef, OutputRef, ModuleCtx};
+use crate::engine::{InputRef, OutputRef, ModuleCtx, ModuleHost, DynModuleHost, DynModuleHostT};
 
 pub trait ModuleT: Any + Sized {
     type Params;
@@ -12,10 +12,71 @@ pub trait ModuleT: Any + Sized {
     fn create(params: Self::Params, ctx: ModuleCtx<Self>) -> (Self, Self::Indication);
     fn params(&self) -> Self::Params;
     fn receive_event(&mut self, _: Self::Event) {}
+    // turns a client's command for this module into one of its events, to
+    // be received as any other. modules taking no commands ignore them
+    fn command_event(_: ModuleCommand) -> Option<Self::Event> { None }
     fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication>;
     fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication>;
     fn inputs(&self) -> &[Terminal];
     fn outputs(&self) -> &[Terminal];
+
+    // approximate bytes held by the module for performance reporting. only
+    // large allocations such as sample buffers and video frames are worth
+    // counting
+    fn memory_usage(&self) -> usize { 0 }
+
+    // offline renders run ticks faster than realtime, so before each tick
+    // they wait until every module is ready rather than let sources which
+    // can't keep up play out silence. pending events are received first
+    fn ready(&mut self) -> bool { true }
+
+    // whether the module has played all it has to play, for offline renders
+    // to know when to stop. only sources with an end have anything to say
+    fn finished(&self) -> bool { true }
+
+    // boxes the module for the engine. modules which may run on an engine
+    // worker thread alongside modules in other branches of the graph opt in
+    // by returning `module::parallel(host)`, which only compiles for those
+    // that are Send
+    fn host(host: ModuleHost<Self>) -> DynModuleHost where ModuleHost<Self>: DynModuleHostT + 'static {
+        DynModuleHost::Local(Box::new(host))
+    }
+
+    // frame rate the module wants its video inputs at. the engine conforms
+    // whatever is connected to one frame each frame interval, repeating and
+    // dropping frames, so the module can count on a steady cadence rather
+    // than source duration hints
+    fn frame_rate(&self) -> Option<u32> { None }
+
+    // what kind of work the module does, for the engine to decide what runs
+    // first and what can be dropped when time is short. by default modules
+    // with only video terminals are video, everything else is audio
+    fn priority(&self) -> PriorityClass {
+        let mut terminals = self.inputs().iter().chain(self.outputs());
+        let video_only = terminals.clone().all(|terminal| terminal.line_type() == LineType::Video);
+
+        if video_only && terminals.next().is_some() {
+            PriorityClass::Video
+        } else {
+            PriorityClass::RealtimeAudio
+        }
+    }
+}
+
+pub fn parallel<M: ModuleT + Send>(host: ModuleHost<M>) -> DynModuleHost
+    where ModuleHost<M>: DynModuleHostT + Send + 'static
+{
+    DynModuleHost::Parallel(Box::new(host))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriorityClass {
+    RealtimeAudio,
+    Video,
+    // modules which only display signals to the operator, such as plotters
+    // and meters. these run after everything else in a tick, and only if
+    // there is time left over, so their outputs are empty to other modules
+    UiFeedback,
 }
 
 macro_rules! gen_modules {
@@ -28,21 +89,59 @@ macro_rules! gen_modules {
 macro_rules! enumerate_modules {
     (then $cb:ident!) => {
         $cb!{
+            ambisonic_encoder::AmbisonicEncoder,
             amplifier::Amplifier,
+            binaural_decoder::BinauralDecoder,
+            chroma_key::ChromaKey,
+            clock::Clock,
+            compositor::Compositor,
+            compressor::Compressor,
+            crossover::Crossover,
+            delay::Delay,
+            echo_canceller::EchoCanceller,
             envelope::Envelope,
             eq_three::EqThree,
+            feedback_suppressor::FeedbackSuppressor,
+            file_output::FileOutput,
+            filter::Filter,
             fm_sine::FmSine,
+            goniometer::Goniometer,
+            hls_output::HlsOutput,
+            icecast_output::IcecastOutput,
+            image_overlay::ImageOverlay,
+            input_device::InputDevice,
+            lfo::Lfo,
+            limiter::Limiter,
+            looper::Looper,
+            macro_module::Macro,
+            meter::Meter,
+            midi_note::MidiNote,
             mixer::Mixer,
             monitor::Monitor,
+            noise_gate::NoiseGate,
             oscillator::Oscillator,
             output_device::OutputDevice,
+            pitch_shift::PitchShift,
             plotter::Plotter,
+            profanity_delay::ProfanityDelay,
+            recorder::Recorder,
+            reverb::Reverb,
+            sampler::Sampler,
+            sequencer::Sequencer,
+            silence_detector::SilenceDetector,
+            slate::Slate,
+            spectrum_analyzer::SpectrumAnalyzer,
             stereo_panner::StereoPanner,
             stereo_splitter::StereoSplitter,
             stream_input::StreamInput,
             stream_output::StreamOutput,
+            sync_check::SyncCheck,
+            talkback::Talkback,
+            text_overlay::TextOverlay,
             trigger::Trigger,
+            tuner::Tuner,
             video_mixer::VideoMixer,
+            waveshaper::Waveshaper,
             media_source::MediaSource,
         }
     }
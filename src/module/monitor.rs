@@ -88,7 +88,8 @@ impl ModuleT for Monitor {
 
     fn create(_: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
         let socket_id = Uuid::new_v4();
-        let codec = AsyncCodec::start(socket_id);
+        let codec = AsyncCodec::start(socket_id,
+            PictureSettings::yuv420p(MONITOR_WIDTH, MONITOR_HEIGHT), Profile::Monitor);
 
         let module = Monitor {
             epoch: None,
@@ -142,17 +143,23 @@ impl ModuleT for Monitor {
     fn outputs(&self)-> &[Terminal] {
         &[]
     }
+
+    fn frame_rate(&self) -> Option<u32> {
+        Some(engine::DEFAULT_FPS)
+    }
 }
 
+/// Encodes ticks on its own thread for streaming to monitors in the browser,
+/// which connect with the socket id.
 #[derive(Debug)]
-struct AsyncCodec {
+pub struct AsyncCodec {
     codec_tx: mpsc::SyncSender<Tick>,
 }
 
 impl AsyncCodec {
-    pub fn start(socket_id: Uuid) -> AsyncCodec {
+    pub fn start(socket_id: Uuid, picture: PictureSettings, profile: Profile) -> AsyncCodec {
         let (codec_tx, codec_rx) = mpsc::sync_channel(2);
-        thread::spawn(move || run_codec_thread(socket_id, codec_rx));
+        thread::spawn(move || run_codec_thread(socket_id, picture, profile, codec_rx));
 
         AsyncCodec {
             codec_tx,
@@ -176,13 +183,13 @@ impl AsyncCodec {
     }
 }
 
-struct Tick {
-    timestamp: MediaTime,
-    audio: Vec<engine::Sample>,
-    video: Option<engine::VideoFrame>,
+pub struct Tick {
+    pub timestamp: MediaTime,
+    pub audio: Vec<engine::Sample>,
+    pub video: Option<engine::VideoFrame>,
 }
 
-fn run_codec_thread(socket_id: Uuid, rx: mpsc::Receiver<Tick>) {
+fn run_codec_thread(socket_id: Uuid, picture: PictureSettings, profile: Profile, rx: mpsc::Receiver<Tick>) {
     // create encoders
     let audio_ctx = AudioCtx::new(AudioParams {
         bit_rate: aac::BitRate::VbrVeryHigh,
@@ -191,9 +198,10 @@ fn run_codec_thread(socket_id: Uuid, rx: mpsc::Receiver<Tick>) {
     });
 
     let video_ctx = VideoCtx::new(VideoParams {
-        picture: PictureSettings::yuv420p(MONITOR_WIDTH, MONITOR_HEIGHT),
+        picture: picture.clone(),
         time_base: SAMPLE_RATE,
-        profile: Profile::Monitor,
+        profile,
+        keyframe_interval: None,
     });
 
     // mp4 params placeholder
@@ -204,8 +212,8 @@ fn run_codec_thread(socket_id: Uuid, rx: mpsc::Receiver<Tick>) {
 
         Mp4Params {
             timescale: SAMPLE_RATE as u32,
-            width: MONITOR_WIDTH as u32,
-            height: MONITOR_HEIGHT as u32,
+            width: picture.width as u32,
+            height: picture.height as u32,
             dcr: Cow::Owned(dcr_bytes),
         }
     };
@@ -248,4 +256,7 @@ fn run_codec_thread(socket_id: Uuid, rx: mpsc::Receiver<Tick>) {
             let _ = segments_tx.send(segment.clone());
         }
     }
+
+    // the module has gone away
+    (*SOCKETS).lock().unwrap().remove(&socket_id);
 }
@@ -0,0 +1,221 @@
+use mixlab_protocol::{NoiseGateParams, GateState, Decibel, LineType, Terminal};
+
+use crate::dsp::smoothing_coeff;
+use crate::engine::{self, InputRef, OutputRef, Sample, CHANNELS, SAMPLE_RATE};
+use crate::module::ModuleT;
+
+// the detector falls back from a peak over about this long, slow enough not
+// to follow each cycle of a low note back down to the threshold
+const DETECTOR_RELEASE_SECS: f64 = 0.01;
+
+#[derive(Debug)]
+pub struct NoiseGate {
+    params: NoiseGateParams,
+    gate: Gate,
+    // open state last indicated
+    open: bool,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for NoiseGate {
+    type Params = NoiseGateParams;
+    type Indication = GateState;
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = NoiseGate {
+            gate: Gate::new(&params, SAMPLE_RATE),
+            params,
+            open: false,
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        };
+
+        (module, GateState::Closed)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.gate.configure(&params, SAMPLE_RATE);
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let output = outputs[0].expect_stereo();
+
+        self.gate.process(input, output);
+
+        if self.gate.open == self.open {
+            return None;
+        }
+
+        self.open = self.gate.open;
+
+        Some(match self.open {
+            true => GateState::Open,
+            false => GateState::Closed,
+        })
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+// Opens when the peak level of either channel rises above the open level,
+// and closes once it has stayed below the lower close level for the hold
+// time. Both channels are gated together so the stereo image holds still.
+#[derive(Debug)]
+struct Gate {
+    open_level: f64,
+    close_level: f64,
+    closed_gain: f64,
+    attack_coeff: f64,
+    release_coeff: f64,
+    detector_coeff: f64,
+    hold_samples: usize,
+    open: bool,
+    detector: f64,
+    gain: f64,
+    // samples the detector has been below the close level while open
+    below_for: usize,
+}
+
+impl Gate {
+    fn new(params: &NoiseGateParams, sample_rate: usize) -> Self {
+        let mut gate = Gate {
+            open_level: 0.0,
+            close_level: 0.0,
+            closed_gain: 0.0,
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+            detector_coeff: 0.0,
+            hold_samples: 0,
+            open: false,
+            detector: 0.0,
+            gain: 0.0,
+            below_for: 0,
+        };
+
+        gate.configure(params, sample_rate);
+        gate.gain = gate.closed_gain;
+        gate
+    }
+
+    fn configure(&mut self, params: &NoiseGateParams, sample_rate: usize) {
+        let sample_rate = sample_rate as f64;
+
+        self.open_level = params.threshold.to_linear();
+        self.close_level = Decibel(params.threshold.0 - params.hysteresis.0.max(0.0)).to_linear();
+        self.closed_gain = params.range.to_linear().min(1.0);
+        self.attack_coeff = smoothing_coeff(params.attack, sample_rate);
+        self.release_coeff = smoothing_coeff(params.release, sample_rate);
+        self.detector_coeff = smoothing_coeff(DETECTOR_RELEASE_SECS, sample_rate);
+        self.hold_samples = (params.hold.max(0.0) * sample_rate) as usize;
+    }
+
+    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        for (input, output) in input.chunks(CHANNELS).zip(output.chunks_mut(CHANNELS)) {
+            let peak = input.iter().fold(0.0, |peak: f64, sample| peak.max(sample.abs() as f64));
+
+            // rises with the signal at once, falls back gradually
+            self.detector = peak.max(self.detector - self.detector * self.detector_coeff);
+
+            if self.detector >= self.open_level {
+                self.open = true;
+                self.below_for = 0;
+            } else if self.open && self.detector < self.close_level {
+                self.below_for += 1;
+
+                if self.below_for >= self.hold_samples {
+                    self.open = false;
+                }
+            } else {
+                self.below_for = 0;
+            }
+
+            let (target, coeff) = match self.open {
+                true => (1.0, self.attack_coeff),
+                false => (self.closed_gain, self.release_coeff),
+            };
+
+            self.gain += (target - self.gain) * coeff;
+
+            for (input, output) in input.iter().zip(output) {
+                *output = (*input as f64 * self.gain) as Sample;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(gate: &mut Gate, secs: f64, level: Sample) -> Vec<Sample> {
+        // alternating sign, so that it's a signal rather than a DC offset
+        let input = (0..(secs * SAMPLE_RATE as f64) as usize * CHANNELS)
+            .map(|i| if (i / CHANNELS) % 2 == 0 { level } else { -level })
+            .collect::<Vec<_>>();
+
+        let mut output = vec![0.0; input.len()];
+        gate.process(&input, &mut output);
+        output
+    }
+
+    #[test]
+    fn opens_above_threshold_and_closes_to_range() {
+        let params = NoiseGateParams::default();
+        let mut gate = Gate::new(&params, SAMPLE_RATE);
+
+        let output = run(&mut gate, 0.5, 0.1);
+        assert!(gate.open);
+        assert!((output.last().unwrap().abs() - 0.1).abs() < 1e-4);
+
+        // well under the threshold, past hold and release
+        let output = run(&mut gate, 2.0, 0.001);
+        assert!(!gate.open);
+        let expected = 0.001 * params.range.to_linear() as Sample;
+        assert!((output.last().unwrap().abs() - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn hysteresis_keeps_it_open() {
+        let params = NoiseGateParams::default();
+        let mut gate = Gate::new(&params, SAMPLE_RATE);
+
+        run(&mut gate, 0.1, Decibel(-30.0).to_linear() as Sample);
+        assert!(gate.open);
+
+        // under the threshold, above the close level
+        run(&mut gate, 1.0, Decibel(-43.0).to_linear() as Sample);
+        assert!(gate.open);
+
+        // and under the close level
+        run(&mut gate, 1.0, Decibel(-50.0).to_linear() as Sample);
+        assert!(!gate.open);
+    }
+
+    #[test]
+    fn holds_before_closing() {
+        let params = NoiseGateParams { hold: 0.5, ..NoiseGateParams::default() };
+        let mut gate = Gate::new(&params, SAMPLE_RATE);
+
+        run(&mut gate, 0.1, 0.1);
+        run(&mut gate, 0.4, 0.0);
+        assert!(gate.open);
+
+        run(&mut gate, 0.2, 0.0);
+        assert!(!gate.open);
+    }
+}
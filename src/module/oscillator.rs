@@ -1,24 +1,55 @@
 use std::f64;
 
-use mixlab_protocol::{OscillatorParams, Waveform, LineType, Terminal};
+use mixlab_protocol::{OscillatorParams, Waveform, FreqScale, LineType, Terminal};
 
-use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
-use crate::module::ModuleT;
+use crate::engine::{self, InputRef, OutputRef, Sample, Smoothed, SAMPLE_RATE, ModuleHost, DynModuleHost};
+use crate::module::{self, ModuleT};
+
+// pulses never get narrower than this either way, or they all but vanish
+const MIN_PULSE_WIDTH: f64 = 0.01;
+
+// a full scale signal on the PWM input moves the pulse width this far from
+// its setting
+const PWM_DEPTH: f64 = 0.5;
+
+// modulated frequencies stay within what can be played without aliasing
+// into a mess
+const MAX_FREQ: f64 = SAMPLE_RATE as f64 / 2.0;
+
+// the sync input counts as high above this level
+const SYNC_THRESHOLD: Sample = 0.5;
 
 #[derive(Debug)]
 pub struct Oscillator {
     params: OscillatorParams,
+    freq: Smoothed,
+    // position within the current cycle, from 0 to 1. accumulated rather
+    // than worked out from t so that frequency can glide without jumping.
+    // starts from t on the first tick, so oscillators of the same frequency
+    // stay in phase however they were created
+    phase: Option<f64>,
+    // whether the sync input was high at the last sample, carried across
+    // ticks so that a gate held high doesn't restart the cycle every tick
+    sync_high: bool,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
 }
 
-fn sign(n: f64) -> f64 {
-    if n.is_sign_positive() {
-        1.0
-    } else if n.is_sign_negative() {
-        -1.0
-    } else {
-        0.0
+// One sample of a waveform at phase n, with the oscillator moving dt through
+// its cycle each sample. The steps in saw, square and pulse waves would alias
+// at high frequencies, so each is smoothed by a polyBLEP correction over the
+// samples either side of it. Triangles only have corners, whose aliasing is
+// far quieter, and are left as they are.
+fn wave(waveform: Waveform, n: f64, dt: f64, width: f64) -> f64 {
+    match waveform {
+        Waveform::Sine => sine(n),
+        Waveform::Square => pulse(n, 0.5, dt),
+        // steps down by 2 half way through the cycle
+        Waveform::Saw => saw(n) - poly_blep((n + 0.5).fract(), dt),
+        Waveform::Triangle => triangle(n),
+        Waveform::Pulse => pulse(n, width, dt),
+        Waveform::On => 1.0,
+        Waveform::Off => 0.0,
     }
 }
 
@@ -36,6 +67,36 @@ fn triangle(n: f64) -> f64 {
     2.0 * saw(n).abs() - 1.0
 }
 
+// steps up by 2 at the start of the cycle, and down again at width
+fn pulse(n: f64, width: f64, dt: f64) -> f64 {
+    let naive = if n < width { 1.0 } else { -1.0 };
+    naive + poly_blep(n, dt) - poly_blep((n - width + 1.0).fract(), dt)
+}
+
+// Correction for a step up by 2 at t = 0, where t is the phase relative to
+// the step and wraps from 1 back to 0. Zero more than a sample away from it.
+// https://www.kvraudio.com/forum/viewtopic.php?t=375517
+fn poly_blep(t: f64, dt: f64) -> f64 {
+    if t < dt {
+        let t = t / dt;
+        2.0 * t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + 2.0 * t + 1.0
+    } else {
+        0.0
+    }
+}
+
+fn modulate(freq: f64, cv: f64, scale: FreqScale, depth: f64) -> f64 {
+    let freq = match scale {
+        FreqScale::Hz => freq + cv * depth,
+        FreqScale::VoltPerOctave => freq * 2f64.powf(cv * depth),
+    };
+
+    freq.max(0.0).min(MAX_FREQ)
+}
+
 impl ModuleT for Oscillator {
     type Params = OscillatorParams;
     type Indication = ();
@@ -43,8 +104,15 @@ impl ModuleT for Oscillator {
 
     fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
         (Self {
+            freq: Smoothed::with_default(params.freq),
+            phase: None,
+            sync_high: false,
             params,
-            inputs: vec![],
+            inputs: vec![
+                LineType::Mono.labeled("Freq"),
+                LineType::Mono.labeled("PWM"),
+                LineType::Mono.labeled("Sync"),
+            ],
             outputs: vec![
                 LineType::Mono.labeled("Mono"),
                 LineType::Stereo.labeled("Stereo"),
@@ -57,12 +125,21 @@ impl ModuleT for Oscillator {
     }
 
     fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.freq.set(new_params.freq);
         self.params = new_params;
         None
     }
 
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let freq_mod = if inputs[0].connected() {
+            Some(inputs[0].expect_mono())
+        } else {
+            None
+        };
+
+        let pwm = inputs[1].expect_mono();
+        let sync = inputs[2].expect_mono();
 
-    fn run_tick(&mut self, t: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
         let (mono, stereo) = match outputs {
             [mono, stereo] => (mono.expect_mono(), stereo.expect_stereo()),
             _ => unreachable!(),
@@ -70,24 +147,38 @@ impl ModuleT for Oscillator {
 
         let len = mono.len();
 
+        let start = (t as f64 / SAMPLE_RATE as f64 * self.freq.target()).fract();
+        let mut phase = self.phase.unwrap_or(start);
+
         for i in 0..len {
-            let t0 = (t + i as u64) as f64 / SAMPLE_RATE as f64;
-            let n = t0 * self.params.freq as f64;
-
-            let sample: f32 = match &self.params.waveform {
-                Waveform::Sine => sine(n),
-                Waveform::Square => sign(sine(n)),
-                Waveform::Saw => saw(n),
-                Waveform::Triangle => triangle(n),
-                Waveform::On => 1.0,
-                Waveform::Off => 0.0,
-            } as f32;
+            let freq = freq_mod
+                .map(|cv| modulate(self.freq.next(), cv[i] as f64, self.params.freq_scale, self.params.freq_mod))
+                .unwrap_or_else(|| self.freq.next());
+
+            // hard sync, each rising edge starts a new cycle
+            let sync_high = sync[i] > SYNC_THRESHOLD;
+            if sync_high && !self.sync_high {
+                phase = 0.0;
+            }
+            self.sync_high = sync_high;
+
+            let n = phase;
+            let dt = freq / SAMPLE_RATE as f64;
+            phase = (phase + dt).fract();
+
+            let width = (self.params.pulse_width + pwm[i] as f64 * PWM_DEPTH)
+                .max(MIN_PULSE_WIDTH)
+                .min(1.0 - MIN_PULSE_WIDTH);
+
+            let sample = wave(self.params.waveform, n, dt, width) as Sample;
 
             mono[i] = sample;
             stereo[i * 2 + 0] = sample;
             stereo[i * 2 + 1] = sample;
         }
 
+        self.phase = Some(phase);
+
         None
     }
 
@@ -98,4 +189,44 @@ impl ModuleT for Oscillator {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn host(host: ModuleHost<Self>) -> DynModuleHost {
+        module::parallel(host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(waveform: Waveform, freq: f64) -> Vec<f64> {
+        let dt = freq / SAMPLE_RATE as f64;
+        (0..SAMPLE_RATE / 10).map(|i| wave(waveform, (i as f64 * dt).fract(), dt, 0.5)).collect()
+    }
+
+    fn largest_step(samples: &[f64]) -> f64 {
+        samples.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn steps_are_spread_over_two_samples() {
+        for &waveform in &[Waveform::Square, Waveform::Saw, Waveform::Pulse] {
+            let step = largest_step(&render(waveform, 5000.0));
+            assert!(step < 1.6, "{:?} steps by {}", waveform, step);
+        }
+    }
+
+    #[test]
+    fn untouched_away_from_steps() {
+        let samples = render(Waveform::Saw, 100.0);
+        let dt = 100.0 / SAMPLE_RATE as f64;
+
+        for (i, sample) in samples.iter().enumerate() {
+            let n = (i as f64 * dt).fract();
+
+            if (n - 0.5).abs() > dt {
+                assert!((sample - saw(n)).abs() < 1e-9, "{} at {}", sample, n);
+            }
+        }
+    }
 }
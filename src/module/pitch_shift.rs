@@ -0,0 +1,89 @@
+use mixlab_protocol::{PitchShiftParams, LineType, Terminal, PITCH_SHIFT_MAX_SEMITONES};
+
+use crate::engine::{self, InputRef, OutputRef, CHANNELS, SAMPLES_PER_TICK};
+use crate::module::ModuleT;
+use crate::pitch_shift::PitchShifter;
+
+#[derive(Debug)]
+pub struct PitchShift {
+    params: PitchShiftParams,
+    // one shifter per channel, each with its own phases
+    shifters: [PitchShifter; CHANNELS],
+    // deinterleaved input and output for one tick
+    dry: [Vec<f32>; CHANNELS],
+    wet: [Vec<f32>; CHANNELS],
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for PitchShift {
+    type Params = PitchShiftParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut module = PitchShift {
+            params: params.clone(),
+            shifters: [PitchShifter::new(), PitchShifter::new()],
+            dry: [vec![0.0; SAMPLES_PER_TICK], vec![0.0; SAMPLES_PER_TICK]],
+            wet: [vec![0.0; SAMPLES_PER_TICK], vec![0.0; SAMPLES_PER_TICK]],
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        };
+
+        module.update(params);
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        let semitones = params.semitones.max(-PITCH_SHIFT_MAX_SEMITONES).min(PITCH_SHIFT_MAX_SEMITONES);
+
+        for shifter in &mut self.shifters {
+            shifter.set_semitones(semitones);
+            shifter.set_preserve_formants(params.preserve_formants);
+        }
+
+        self.params = PitchShiftParams { semitones, ..params };
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let output = outputs[0].expect_stereo();
+
+        for (i, frame) in input.chunks(CHANNELS).enumerate() {
+            for chan in 0..CHANNELS {
+                self.dry[chan][i] = frame[chan];
+            }
+        }
+
+        for chan in 0..CHANNELS {
+            self.shifters[chan].process(&self.dry[chan], &mut self.wet[chan]);
+        }
+
+        for (i, frame) in output.chunks_mut(CHANNELS).enumerate() {
+            for chan in 0..CHANNELS {
+                frame[chan] = self.wet[chan][i];
+            }
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.shifters.iter().map(PitchShifter::memory_usage).sum()
+    }
+}
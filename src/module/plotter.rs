@@ -1,5 +1,5 @@
 use crate::engine::{self, InputRef, OutputRef};
-use crate::module::ModuleT;
+use crate::module::{ModuleT, PriorityClass};
 
 use mixlab_protocol::{PlotterIndication, LineType, Terminal};
 
@@ -62,4 +62,8 @@ impl ModuleT for Plotter {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn priority(&self) -> PriorityClass {
+        PriorityClass::UiFeedback
+    }
 }
@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+
+use mixlab_protocol::{LineType, Terminal, ProfanityDelayParams, ProfanityDelayIndication};
+use mixlab_util::time::MediaDuration;
+
+use crate::dsp::DelayLine;
+use crate::engine::{self, InputRef, OutputRef, Sample, VideoFrame, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::module::ModuleT;
+
+// longest delay the buffers can hold, in seconds
+const MAX_DELAY: usize = 40;
+const MAX_DELAY_SAMPLES: usize = MAX_DELAY * SAMPLE_RATE;
+
+// samples the delay grows or shrinks by per sample of output while moving
+// towards its set length. the program plays this much slower or faster in the
+// meantime, about a third of a semitone, which goes unnoticed on speech
+const REBUILD_RATE: f64 = 0.02;
+
+// crossfade from the delayed program to the live program on a dump, in
+// samples. jumping straight over clicks
+const DUMP_FADE_SAMPLES: usize = SAMPLE_RATE / 100;
+
+#[derive(Debug)]
+pub struct ProfanityDelay {
+    params: ProfanityDelayParams,
+    buffer: DelayLine,
+    // current delay in samples, moving towards the set delay at REBUILD_RATE
+    delay: f64,
+    // delay in effect before a dump, faded out over the remaining samples
+    fade: Option<(f64, usize)>,
+    // delayed video frames, alongside when they were captured in samples
+    // since the engine started
+    frames: VecDeque<(f64, VideoFrame)>,
+    indication: ProfanityDelayIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for ProfanityDelay {
+    type Params = ProfanityDelayParams;
+    type Indication = ProfanityDelayIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        // a new delay starts out at its full length, rather than building up
+        // from live
+        let delay = target_delay(&params);
+        let indication = ProfanityDelayIndication { delay: delay_secs(delay) };
+
+        let module = ProfanityDelay {
+            params,
+            buffer: DelayLine::new(MAX_DELAY_SAMPLES),
+            delay,
+            fade: None,
+            frames: VecDeque::new(),
+            indication: indication.clone(),
+            inputs: vec![
+                LineType::Stereo.labeled("Audio"),
+                LineType::Video.labeled("Video"),
+            ],
+            outputs: vec![
+                LineType::Stereo.labeled("Audio"),
+                LineType::Video.labeled("Video"),
+            ],
+        };
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        if new_params.dump_seq != self.params.dump_seq {
+            self.dump();
+        }
+
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let target = target_delay(&self.params);
+
+        let output = outputs[0].expect_stereo();
+
+        for (input, output) in input.chunks(CHANNELS).zip(output.chunks_mut(CHANNELS)) {
+            self.buffer.write(input);
+
+            if self.delay < target {
+                self.delay = (self.delay + REBUILD_RATE).min(target);
+            } else if self.delay > target {
+                self.delay = (self.delay - REBUILD_RATE).max(target);
+            }
+
+            let mut frame = self.buffer.read(self.delay);
+
+            if let Some((old_delay, remaining)) = &mut self.fade {
+                let old_frame = self.buffer.read(*old_delay);
+                let old_gain = *remaining as Sample / DUMP_FADE_SAMPLES as Sample;
+
+                for (sample, old) in frame.iter_mut().zip(old_frame.iter()) {
+                    *sample = *sample * (1.0 - old_gain) + *old * old_gain;
+                }
+
+                *remaining -= 1;
+
+                if *remaining == 0 {
+                    self.fade = None;
+                }
+            }
+
+            output.copy_from_slice(&frame);
+        }
+
+        self.run_video(t, &inputs[1], &mut outputs[1]);
+
+        let delay = delay_secs(self.delay);
+
+        if self.indication.delay != delay {
+            self.indication.delay = delay;
+            Some(self.indication.clone())
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        let audio = self.buffer.memory_usage();
+
+        let video = self.frames.iter()
+            .map(|(_, frame)| frame.data.decoded.buffer_size())
+            .sum::<usize>();
+
+        audio + video
+    }
+}
+
+impl ProfanityDelay {
+    // jumps the program forward to live, after which the delay rebuilds
+    // gradually
+    fn dump(&mut self) {
+        self.fade = Some((self.delay, DUMP_FADE_SAMPLES));
+        self.delay = 0.0;
+    }
+
+    // video is held back to line up with the audio. a frame is due once the
+    // audio read position passes the time it was captured, which is taken
+    // from the delay at the end of the tick. frames skipped over by a dump
+    // are dropped, leaving a gap in passthrough packets which outputs already
+    // recover from
+    fn run_video(&mut self, t: u64, input: &InputRef, output: &mut OutputRef) {
+        if !input.connected() {
+            self.frames.clear();
+            return;
+        }
+
+        if let Some(frame) = input.expect_video() {
+            let captured = t as f64 + duration_secs(frame.tick_offset) * SAMPLE_RATE as f64;
+            self.frames.push_back((captured, frame.clone()));
+        }
+
+        let tick_start = t as f64;
+        let tick_end = tick_start + SAMPLES_PER_TICK as f64;
+
+        let mut due = None;
+
+        while let Some((captured, _)) = self.frames.front() {
+            if captured + self.delay >= tick_end {
+                break;
+            }
+
+            due = self.frames.pop_front();
+        }
+
+        *output.expect_video() = due.map(|(captured, frame)| {
+            let offset = (captured + self.delay - tick_start).max(0.0);
+
+            VideoFrame {
+                tick_offset: MediaDuration::new(offset as i64, SAMPLE_RATE as i64),
+                ..frame
+            }
+        });
+    }
+}
+
+fn target_delay(params: &ProfanityDelayParams) -> f64 {
+    (params.delay * SAMPLE_RATE as f64).max(0.0).min(MAX_DELAY_SAMPLES as f64)
+}
+
+// delay in seconds as reported in the indication, rounded so that it isn't
+// sent every tick while rebuilding
+fn delay_secs(delay: f64) -> f64 {
+    (delay / SAMPLE_RATE as f64 * 10.0).round() / 10.0
+}
+
+fn duration_secs(duration: MediaDuration) -> f64 {
+    let duration = duration.as_rational();
+    *duration.numer() as f64 / *duration.denom() as f64
+}
@@ -0,0 +1,242 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::engine::{self, InputRef, OutputRef, CHANNELS, SAMPLE_RATE, TICKS_PER_SECOND};
+use crate::module::ModuleT;
+use crate::output_file::{expand_filename, unix_time, unused_path};
+
+use mixlab_protocol::{RecorderParams, RecorderIndication, WaveformOverview, LineType, Terminal, Sample};
+
+// caps the size of the overview sent to clients, once reached adjacent
+// buckets are merged and the bucket size doubled:
+const MAX_OVERVIEW_BUCKETS: usize = 1024;
+
+#[derive(Debug)]
+pub struct Recorder {
+    params: RecorderParams,
+    dir: PathBuf,
+    writer: Option<Writer>,
+    // name of the file being written, or last written
+    file: Option<String>,
+    error: bool,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+    overview: WaveformOverview,
+    bucket_peak: Sample,
+    bucket_frames: usize,
+    recorded_frames: usize,
+    count: usize,
+}
+
+impl Recorder {
+    fn start(&mut self) {
+        self.overview = new_overview();
+        self.bucket_peak = 0.0;
+        self.bucket_frames = 0;
+        self.recorded_frames = 0;
+
+        let name = expand_filename(&self.params.filename, unix_time());
+        let path = unused_path(&self.dir, &name, "wav");
+
+        self.file = path.file_name().map(|name| name.to_string_lossy().into_owned());
+        self.error = false;
+        self.writer = Some(Writer::start(path));
+    }
+
+    fn indication(&self) -> RecorderIndication {
+        RecorderIndication {
+            overview: self.overview.clone(),
+            file: self.file.clone(),
+            error: self.error,
+        }
+    }
+
+    fn push_bucket(&mut self) {
+        self.overview.peaks.push(self.bucket_peak);
+        self.bucket_peak = 0.0;
+        self.bucket_frames = 0;
+
+        if self.overview.peaks.len() >= MAX_OVERVIEW_BUCKETS {
+            self.overview.peaks = self.overview.peaks.chunks(2)
+                .map(|pair| pair.iter().cloned().fold(0.0, Sample::max))
+                .collect();
+
+            self.overview.bucket_secs *= 2;
+        }
+    }
+}
+
+fn new_overview() -> WaveformOverview {
+    WaveformOverview {
+        bucket_secs: 1,
+        peaks: Vec::new(),
+        duration_secs: 0.0,
+    }
+}
+
+impl ModuleT for Recorder {
+    type Params = RecorderParams;
+    type Indication = RecorderIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut module = Self {
+            params,
+            dir: ctx.project().recordings_dir(),
+            writer: None,
+            file: None,
+            error: false,
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![LineType::Stereo.unlabeled()],
+            overview: new_overview(),
+            bucket_peak: 0.0,
+            bucket_frames: 0,
+            recorded_frames: 0,
+            count: 0,
+        };
+
+        // a recorder loaded while recording carries on in a new file
+        if module.params.recording {
+            module.start();
+        }
+
+        let indication = module.indication();
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        let started = new_params.recording && !self.params.recording;
+        let stopped = !new_params.recording && self.params.recording;
+        self.params = new_params;
+
+        if started {
+            self.start();
+            Some(self.indication())
+        } else if stopped {
+            // the writer finishes off the file once it has drained
+            self.writer = None;
+            None
+        } else {
+            None
+        }
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let output = outputs[0].expect_stereo();
+
+        output.copy_from_slice(input);
+
+        if !self.params.recording {
+            return None;
+        }
+
+        if let Some(writer) = &mut self.writer {
+            if !writer.send(input.to_vec()) {
+                self.writer = None;
+                self.error = true;
+                return Some(self.indication());
+            }
+        }
+
+        for frame in input.chunks(2) {
+            let peak = frame.iter().map(|sample| sample.abs()).fold(0.0, Sample::max);
+            self.bucket_peak = self.bucket_peak.max(peak);
+            self.bucket_frames += 1;
+
+            if self.bucket_frames >= SAMPLE_RATE * self.overview.bucket_secs {
+                self.push_bucket();
+            }
+        }
+
+        self.recorded_frames += input.len() / 2;
+        self.overview.duration_secs = self.recorded_frames as f64 / SAMPLE_RATE as f64;
+
+        self.count += 1;
+
+        if self.count % TICKS_PER_SECOND == 0 {
+            Some(self.indication())
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+}
+
+// writes ticks of interleaved stereo to a WAV file on its own thread, which
+// finishes the file and stops when dropped
+#[derive(Debug)]
+struct Writer {
+    tx: mpsc::SyncSender<Vec<Sample>>,
+}
+
+impl Writer {
+    fn start(path: PathBuf) -> Self {
+        // a second of audio, to ride out the disk stalling
+        let (tx, rx) = mpsc::sync_channel(TICKS_PER_SECOND);
+
+        thread::spawn(move || {
+            if let Err(e) = run_writer_thread(&path, rx) {
+                eprintln!("Recorder: could not write {}: {}", path.display(), e);
+            }
+        });
+
+        Writer { tx }
+    }
+
+    // returns false once the writer thread has given up
+    fn send(&mut self, samples: Vec<Sample>) -> bool {
+        use mpsc::TrySendError;
+
+        match self.tx.try_send(samples) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                println!("Recorder: writer not keeping up, dropping tick");
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+fn run_writer_thread(path: &Path, rx: mpsc::Receiver<Vec<Sample>>) -> Result<(), hound::Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+
+    let spec = WavSpec {
+        channels: CHANNELS as u16,
+        sample_rate: SAMPLE_RATE as u32,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+
+    let mut wav: WavWriter<BufWriter<File>> = WavWriter::new(BufWriter::new(file), spec)?;
+
+    while let Ok(samples) = rx.recv() {
+        for sample in samples {
+            wav.write_sample(sample)?;
+        }
+    }
+
+    wav.finalize()
+}
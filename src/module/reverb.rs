@@ -0,0 +1,267 @@
+use std::io::Cursor;
+
+use derive_more::From;
+use tokio::task;
+
+use mixlab_codec::ffmpeg::IoReader;
+use mixlab_protocol::{MediaId, ReverbParams, LineType, Terminal};
+
+use crate::convolve::Convolver;
+use crate::engine::{InputRef, OutputRef, ModuleCtx, Sample, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::module::ModuleT;
+use crate::project::media;
+use crate::project::ProjectBaseRef;
+use crate::project::stream::ReadStream;
+use crate::resample;
+
+// longer impulses are truncated, the cost of every tick grows with impulse
+// length
+const MAX_IMPULSE_SECONDS: usize = 10;
+
+#[derive(Debug)]
+pub struct Reverb {
+    ctx: ModuleCtx<Self>,
+    params: ReverbParams,
+    impulse: Option<Impulse>,
+    // mix reached at the end of the last tick, ramped towards the new mix
+    // across the next tick
+    mix: f64,
+    // deinterleaved input and output for one tick
+    dry: [Vec<f32>; CHANNELS],
+    wet: [Vec<f32>; CHANNELS],
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+pub enum ReverbEvent {
+    SetImpulse(MediaId, Option<Impulse>),
+}
+
+// one convolver per output channel, each with its own input history
+#[derive(Debug)]
+pub struct Impulse {
+    channels: [Convolver; CHANNELS],
+}
+
+impl ModuleT for Reverb {
+    type Params = ReverbParams;
+    type Indication = ();
+    type Event = ReverbEvent;
+
+    fn create(params: Self::Params, ctx: ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut module = Reverb {
+            ctx,
+            params: ReverbParams { impulse: None, ..params.clone() },
+            impulse: None,
+            mix: params.mix,
+            dry: [vec![0.0; SAMPLES_PER_TICK], vec![0.0; SAMPLES_PER_TICK]],
+            wet: [vec![0.0; SAMPLES_PER_TICK], vec![0.0; SAMPLES_PER_TICK]],
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        };
+
+        module.update(params);
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        if self.params.impulse != params.impulse {
+            self.impulse = None;
+
+            if let Some(media_id) = params.impulse {
+                let project = self.ctx.project();
+
+                self.ctx.spawn_async(async move {
+                    ReverbEvent::SetImpulse(media_id, load_impulse(project, media_id).await)
+                });
+            }
+        }
+
+        self.params = params;
+        None
+    }
+
+    fn receive_event(&mut self, event: ReverbEvent) {
+        match event {
+            ReverbEvent::SetImpulse(media_id, impulse) => {
+                // the impulse may have been changed again while loading
+                if self.params.impulse == Some(media_id) {
+                    self.impulse = impulse;
+                }
+            }
+        }
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let output = outputs[0].expect_stereo();
+
+        let impulse = match &mut self.impulse {
+            Some(impulse) => impulse,
+            None => {
+                // nothing to convolve with yet, pass through untouched
+                output.copy_from_slice(input);
+                return None;
+            }
+        };
+
+        for (i, frame) in input.chunks(CHANNELS).enumerate() {
+            for chan in 0..CHANNELS {
+                self.dry[chan][i] = frame[chan];
+            }
+        }
+
+        for chan in 0..CHANNELS {
+            impulse.channels[chan].process(&self.dry[chan], &mut self.wet[chan]);
+        }
+
+        let mix_from = self.mix;
+        let mix_to = self.params.mix.max(0.0).min(1.0);
+
+        for (i, frame) in output.chunks_mut(CHANNELS).enumerate() {
+            let progress = (i + 1) as f64 / SAMPLES_PER_TICK as f64;
+            let mix = (mix_from + (mix_to - mix_from) * progress) as Sample;
+
+            for chan in 0..CHANNELS {
+                frame[chan] = self.dry[chan][i] * (1.0 - mix) + self.wet[chan][i] * mix;
+            }
+        }
+
+        self.mix = mix_to;
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.impulse.iter()
+            .flat_map(|impulse| impulse.channels.iter())
+            .map(|convolver| convolver.memory_usage())
+            .sum()
+    }
+}
+
+#[derive(Debug, From)]
+enum ImpulseError {
+    Read(rusqlite::Error),
+    Wav(hound::Error),
+    NoChannels,
+}
+
+async fn load_impulse(project: ProjectBaseRef, media_id: MediaId) -> Option<Impulse> {
+    let stream = match media::open(project, media_id).await {
+        Ok(Some(stream)) => stream,
+        Ok(None) => return None,
+        Err(e) => {
+            eprintln!("reverb: could not open {:?}: {:?}", media_id, e);
+            return None;
+        }
+    };
+
+    // decoding, resampling and transforming the impulse takes far longer
+    // than a tick, so it all happens off the engine thread
+    let result = task::spawn_blocking(move || prepare_impulse(stream))
+        .await
+        .expect("join impulse thread");
+
+    match result {
+        Ok(impulse) => Some(impulse),
+        Err(e) => {
+            eprintln!("reverb: could not load impulse {:?}: {:?}", media_id, e);
+            None
+        }
+    }
+}
+
+fn prepare_impulse(mut stream: ReadStream) -> Result<Impulse, ImpulseError> {
+    let mut data = Vec::new();
+    let mut buff = vec![0u8; ReadStream::BUFFER_SIZE];
+
+    loop {
+        match stream.read(&mut buff)? {
+            0 => break,
+            len => data.extend_from_slice(&buff[..len]),
+        }
+    }
+
+    let mut wav = hound::WavReader::new(Cursor::new(data))?;
+    let spec = wav.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => {
+            wav.samples::<f32>().collect::<Result<Vec<_>, _>>()?
+        }
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u32 << (spec.bits_per_sample - 1)) as f32;
+            wav.samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    let file_channels = spec.channels as usize;
+
+    if file_channels == 0 {
+        return Err(ImpulseError::NoChannels);
+    }
+
+    let max_len = MAX_IMPULSE_SECONDS * spec.sample_rate as usize;
+
+    // mono impulses are used for both channels, channels beyond stereo are
+    // ignored
+    let mut channels = (0..CHANNELS)
+        .map(|chan| {
+            let file_chan = chan.min(file_channels - 1);
+
+            let deinterleaved = samples.iter()
+                .skip(file_chan)
+                .step_by(file_channels)
+                .take(max_len)
+                .copied()
+                .collect::<Vec<_>>();
+
+            resample::resample(&deinterleaved, spec.sample_rate as usize, SAMPLE_RATE)
+        })
+        .collect::<Vec<_>>();
+
+    normalize(&mut channels);
+
+    let right = channels.pop().expect("right channel");
+    let left = channels.pop().expect("left channel");
+
+    Ok(Impulse {
+        channels: [
+            Convolver::new(&left, SAMPLES_PER_TICK),
+            Convolver::new(&right, SAMPLES_PER_TICK),
+        ],
+    })
+}
+
+// scales impulses to unit energy, so that the wet signal sits at roughly the
+// same level as the dry regardless of how loud the impulse was recorded
+fn normalize(channels: &mut [Vec<f32>]) {
+    let energy = channels.iter()
+        .map(|samples| samples.iter().map(|sample| sample * sample).sum::<f32>())
+        .fold(0.0, f32::max);
+
+    if energy > 0.0 {
+        let scale = 1.0 / energy.sqrt();
+
+        for sample in channels.iter_mut().flat_map(|samples| samples.iter_mut()) {
+            *sample *= scale;
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::mem;
+
+use derive_more::From;
+use tokio::task;
+
+use mixlab_codec::ffmpeg::codec::{self, CodecBuilder, RecvFrameError};
+use mixlab_codec::ffmpeg::media::Audio;
+use mixlab_codec::ffmpeg::{AvError, AvIoError, AvIoReader, IoReader, InputContainer, UnsupportedSampleFormat};
+use mixlab_protocol::{MediaId, SamplerParams, LineType, Terminal};
+
+use crate::engine::{InputRef, OutputRef, ModuleCtx, Sample, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::module::ModuleT;
+use crate::module::media_source::read_stereo;
+use crate::project::media;
+use crate::project::ProjectBaseRef;
+use crate::project::stream::ReadStream;
+use crate::resample::Resampler;
+
+// longer clips are truncated, the whole clip is held in memory
+const MAX_CLIP_SECONDS: usize = 30;
+
+// triggering while this many plays are still sounding cuts off the oldest
+const MAX_VOICES: usize = 8;
+
+// trigger input counts as open above this level
+const TRIGGER_THRESHOLD: Sample = 0.5;
+
+#[derive(Debug)]
+pub struct Sampler {
+    ctx: ModuleCtx<Self>,
+    params: SamplerParams,
+    clip: Option<Clip>,
+    // position of each sounding play in clip frames. fractional when pitched
+    voices: Vec<f64>,
+    trigger_open: bool,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+pub enum SamplerEvent {
+    SetClip(MediaId, Option<Clip>),
+}
+
+// decoded clip, interleaved stereo at the engine sample rate
+#[derive(Debug)]
+pub struct Clip {
+    samples: Vec<Sample>,
+}
+
+impl Clip {
+    fn frames(&self) -> usize {
+        self.samples.len() / CHANNELS
+    }
+
+    // linearly interpolated between neighbouring frames, silent past the end
+    fn frame_at(&self, position: f64) -> [Sample; CHANNELS] {
+        let index = position as usize;
+        let frac = (position - index as f64) as Sample;
+
+        let frame = |index: usize| {
+            let offset = index * CHANNELS;
+
+            match self.samples.get(offset..offset + CHANNELS) {
+                Some(frame) => [frame[0], frame[1]],
+                None => [0.0; CHANNELS],
+            }
+        };
+
+        let a = frame(index);
+        let b = frame(index + 1);
+
+        [a[0] + (b[0] - a[0]) * frac, a[1] + (b[1] - a[1]) * frac]
+    }
+}
+
+impl ModuleT for Sampler {
+    type Params = SamplerParams;
+    type Indication = ();
+    type Event = SamplerEvent;
+
+    fn create(params: Self::Params, ctx: ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut module = Sampler {
+            ctx,
+            params: SamplerParams { clip: None, ..params.clone() },
+            clip: None,
+            voices: Vec::with_capacity(MAX_VOICES),
+            trigger_open: false,
+            inputs: vec![LineType::Mono.labeled("Trigger")],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        };
+
+        module.update(params);
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        if self.params.clip != params.clip {
+            self.clip = None;
+            self.voices.clear();
+
+            if let Some(media_id) = params.clip {
+                let project = self.ctx.project();
+
+                self.ctx.spawn_async(async move {
+                    SamplerEvent::SetClip(media_id, load_clip(project, media_id).await)
+                });
+            }
+        }
+
+        self.params = params;
+        None
+    }
+
+    fn receive_event(&mut self, event: SamplerEvent) {
+        match event {
+            SamplerEvent::SetClip(media_id, clip) => {
+                // the clip may have been changed again while loading
+                if self.params.clip == Some(media_id) {
+                    self.clip = clip;
+                }
+            }
+        }
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let trigger = inputs[0].expect_mono();
+        let output = outputs[0].expect_stereo();
+
+        let rate = f64::powf(2.0, self.params.pitch / 12.0);
+        let gain = self.params.gain as Sample;
+
+        for i in 0..SAMPLES_PER_TICK {
+            let open = trigger[i] > TRIGGER_THRESHOLD;
+
+            // plays start on the exact sample the trigger opens, not at the
+            // next tick
+            if open && !self.trigger_open && self.clip.is_some() {
+                if self.voices.len() == MAX_VOICES {
+                    self.voices.remove(0);
+                }
+
+                self.voices.push(0.0);
+            }
+
+            self.trigger_open = open;
+
+            let clip = match &self.clip {
+                Some(clip) => clip,
+                None => continue,
+            };
+
+            for position in self.voices.iter_mut() {
+                let frame = clip.frame_at(*position);
+
+                for chan in 0..CHANNELS {
+                    output[i * CHANNELS + chan] += frame[chan] * gain;
+                }
+
+                *position += rate;
+            }
+        }
+
+        if let Some(clip) = &self.clip {
+            let frames = clip.frames() as f64;
+            self.voices.retain(|position| *position < frames);
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.clip.as_ref()
+            .map(|clip| clip.samples.capacity() * mem::size_of::<Sample>())
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, From)]
+enum ClipError {
+    CodecBuild(codec::BuildError),
+    CodecOpen(codec::OpenError),
+    NoAudio,
+    RecvFrame(RecvFrameError),
+    SampleFormat(UnsupportedSampleFormat),
+    Av(AvError),
+    Io(<ReadStream as IoReader>::Error),
+}
+
+impl From<AvIoError<ReadStream>> for ClipError {
+    fn from(e: AvIoError<ReadStream>) -> ClipError {
+        match e {
+            AvIoError::Av(e) => ClipError::Av(e),
+            AvIoError::Io(e) => ClipError::Io(e),
+        }
+    }
+}
+
+async fn load_clip(project: ProjectBaseRef, media_id: MediaId) -> Option<Clip> {
+    let stream = match media::open(project, media_id).await {
+        Ok(Some(stream)) => stream,
+        Ok(None) => return None,
+        Err(e) => {
+            eprintln!("sampler: could not open {:?}: {:?}", media_id, e);
+            return None;
+        }
+    };
+
+    // the whole clip is decoded up front so that triggering it never waits
+    // on the decoder
+    let result = task::spawn_blocking(move || decode_clip(stream))
+        .await
+        .expect("join clip thread");
+
+    match result {
+        Ok(clip) => Some(clip),
+        Err(e) => {
+            eprintln!("sampler: could not decode clip {:?}: {:?}", media_id, e);
+            None
+        }
+    }
+}
+
+fn decode_clip(stream: ReadStream) -> Result<Clip, ClipError> {
+    let mut container = InputContainer::open(AvIoReader::new(stream))?;
+
+    let streams = container.streams();
+
+    let index = streams.iter()
+        .position(|stream| stream.is_media::<Audio>())
+        .ok_or(ClipError::NoAudio)?;
+
+    let time_base = streams[index].time_base();
+    let codec_params = streams[index].codec_parameters();
+
+    let mut decode = CodecBuilder::<Audio>::new(codec_params.codec_id, time_base)?
+        .with_parameters(codec_params)
+        .open_decoder()?;
+
+    let max_len = MAX_CLIP_SECONDS * SAMPLE_RATE * CHANNELS;
+
+    // clips are resampled at the rate of their first frame
+    let mut resampler = None;
+    let mut stereo = Vec::new();
+    let mut samples = Vec::new();
+
+    loop {
+        let pkt = container.read_packet()?;
+
+        match &pkt {
+            Some(pkt) if pkt.stream_index() as usize == index => { decode.send_packet(pkt)?; }
+            Some(_) => { continue; }
+            None => { decode.end_of_stream()?; }
+        }
+
+        loop {
+            let frame = match decode.recv_frame() {
+                Ok(frame) => frame,
+                Err(RecvFrameError::NeedMoreInput) | Err(RecvFrameError::Eof) => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            let resampler = resampler.get_or_insert_with(|| {
+                Resampler::new(frame.sample_rate(), SAMPLE_RATE, CHANNELS)
+            });
+
+            stereo.clear();
+            read_stereo(&frame, &mut stereo)?;
+            resampler.process(&stereo, &mut samples);
+        }
+
+        if pkt.is_none() || samples.len() >= max_len {
+            break;
+        }
+    }
+
+    if let Some(resampler) = &mut resampler {
+        resampler.flush(&mut samples);
+    }
+
+    samples.truncate(max_len);
+    samples.shrink_to_fit();
+
+    Ok(Clip { samples })
+}
@@ -0,0 +1,149 @@
+use mixlab_protocol::{SequencerParams, SequencerIndication, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, SAMPLE_RATE, SAMPLES_PER_TICK, CONTROL_PERIOD};
+use crate::module::ModuleT;
+
+// internal clock steps are sixteenth notes
+const STEPS_PER_BEAT: f64 = 4.0;
+
+// clock input counts as high above this level
+const CLOCK_THRESHOLD: Sample = 0.5;
+
+#[derive(Debug)]
+pub struct Sequencer {
+    params: SequencerParams,
+    step: usize,
+    // sample frames since the current step started
+    position: u64,
+    clock_high: bool,
+    // sample frame of the last rising clock edge and the period between the
+    // last two, which sets gate lengths under an external clock
+    last_edge: Option<u64>,
+    clock_period: Option<u64>,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Sequencer {
+    type Params = SequencerParams;
+    type Indication = SequencerIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Sequencer {
+            params,
+            step: 0,
+            position: 0,
+            clock_high: false,
+            last_edge: None,
+            clock_period: None,
+            inputs: vec![LineType::Mono.labeled("Clock")],
+            outputs: vec![
+                LineType::Mono.labeled("Gate"),
+                LineType::Control.labeled("Pitch"),
+            ],
+        };
+
+        (module, SequencerIndication::default())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let clock = if inputs[0].connected() {
+            Some(inputs[0].expect_mono())
+        } else {
+            // forget the old period so reconnecting doesn't pick up a stale
+            // tempo before the first full clock cycle
+            self.last_edge = None;
+            self.clock_period = None;
+            None
+        };
+
+        let (gate_out, pitch_out) = match outputs {
+            [gate, pitch] => (gate.expect_mono(), pitch.expect_control()),
+            _ => unimplemented!(),
+        };
+
+        let step_count = self.params.steps.len();
+
+        if step_count == 0 {
+            return None;
+        }
+
+        let start_step = self.step;
+        let internal_len = self.internal_step_len();
+
+        for i in 0..SAMPLES_PER_TICK {
+            let advance = match clock {
+                Some(clock) => self.clock_edge(t + i as u64, clock[i]),
+                None => self.position >= internal_len,
+            };
+
+            if advance {
+                self.step = (self.step + 1) % step_count;
+                self.position = 0;
+            }
+
+            // steps may have been removed since the last tick
+            self.step %= step_count;
+
+            let step = &self.params.steps[self.step];
+            let step_len = self.clock_period.unwrap_or(internal_len);
+            let gate_len = (step.length.max(0.0).min(1.0) * step_len as f64) as u64;
+
+            gate_out[i] = if step.gate && self.position < gate_len { 1.0 } else { 0.0 };
+
+            if i % CONTROL_PERIOD == 0 {
+                pitch_out[i / CONTROL_PERIOD] = step.value.max(0.0).min(1.0) as Sample;
+            }
+
+            self.position += 1;
+        }
+
+        if self.step != start_step {
+            Some(SequencerIndication { step: self.step })
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl Sequencer {
+    fn internal_step_len(&self) -> u64 {
+        let steps_per_second = self.params.bpm.max(1.0) / 60.0 * STEPS_PER_BEAT;
+        (SAMPLE_RATE as f64 / steps_per_second) as u64
+    }
+
+    // whether the clock rises at this sample, also measuring its period
+    fn clock_edge(&mut self, sample_seq: u64, value: Sample) -> bool {
+        let high = value > CLOCK_THRESHOLD;
+        let rising = high && !self.clock_high;
+        self.clock_high = high;
+
+        if rising {
+            if let Some(last_edge) = self.last_edge {
+                self.clock_period = Some(sample_seq - last_edge);
+            }
+
+            self.last_edge = Some(sample_seq);
+        }
+
+        rising
+    }
+}
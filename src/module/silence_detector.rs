@@ -0,0 +1,87 @@
+use mixlab_protocol::{SilenceDetectorParams, SilenceDetectorIndication, Decibel, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, DuckRequest, SAMPLE_RATE};
+use crate::module::ModuleT;
+
+#[derive(Debug)]
+pub struct SilenceDetector {
+    params: SilenceDetectorParams,
+    duck: DuckRequest,
+    silent_frames: usize,
+    alarm: bool,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl SilenceDetector {
+    fn indication(&self) -> SilenceDetectorIndication {
+        SilenceDetectorIndication { alarm: self.alarm }
+    }
+}
+
+impl ModuleT for SilenceDetector {
+    type Params = SilenceDetectorParams;
+    type Indication = SilenceDetectorIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Self {
+            params,
+            duck: ctx.monitor_policy().duck_request(),
+            silent_frames: 0,
+            alarm: false,
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        };
+
+        let indication = module.indication();
+
+        (module, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+        let output = outputs[0].expect_stereo();
+
+        output.copy_from_slice(input);
+
+        let peak = input.iter().map(|sample| sample.abs()).fold(0.0, f32::max);
+
+        if Decibel::from_linear(peak as f64).0 < self.params.threshold.0 {
+            self.silent_frames += input.len() / 2;
+        } else {
+            self.silent_frames = 0;
+        }
+
+        let hold_frames = (self.params.hold_secs * SAMPLE_RATE as f64) as usize;
+
+        // a disconnected input is not silence on air, it's an unused module
+        let alarm = inputs[0].connected() && self.silent_frames >= hold_frames;
+
+        self.duck.set_active(alarm);
+
+        if alarm != self.alarm {
+            self.alarm = alarm;
+            Some(self.indication())
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+}
@@ -0,0 +1,181 @@
+use mixlab_protocol::{LineType, Terminal, SlateParams, SlateIndication};
+use mixlab_util::time::{MediaTime, MediaDuration};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, VideoFrame, CHANNELS, SAMPLE_RATE, TICKS_PER_SECOND};
+use crate::module::{ModuleT, PriorityClass};
+use crate::video;
+
+// crossfade between program audio and the slate bed, in samples. video cuts
+// straight over
+const FADE_SAMPLES: usize = SAMPLE_RATE / 5;
+
+#[derive(Debug)]
+pub struct Slate {
+    params: SlateParams,
+    indication: SlateIndication,
+    // gain of the slate bed against program audio, from 0.0 to 1.0
+    mix: Sample,
+    // last slate picture, repeated once it runs out so that a still image
+    // keeps going for as long as the slate is engaged
+    held: Option<HeldFrame>,
+    // whether the last tick's video was from the slate
+    showing: bool,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+#[derive(Debug)]
+struct HeldFrame {
+    frame: VideoFrame,
+    active_until: MediaTime,
+}
+
+impl ModuleT for Slate {
+    type Params = SlateParams;
+    type Indication = SlateIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Slate {
+            mix: if params.engaged { 1.0 } else { 0.0 },
+            params,
+            indication: SlateIndication::default(),
+            held: None,
+            showing: false,
+            inputs: vec![
+                LineType::Stereo.labeled("Program Audio"),
+                LineType::Video.labeled("Program Video"),
+                LineType::Stereo.labeled("Slate Audio"),
+                LineType::Video.labeled("Slate Video"),
+            ],
+            outputs: vec![
+                LineType::Stereo.labeled("Audio"),
+                LineType::Video.labeled("Video"),
+            ],
+        };
+
+        (module, SlateIndication::default())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let program = inputs[0].expect_stereo();
+        let bed = inputs[2].expect_stereo();
+
+        let target = if self.params.engaged { 1.0 } else { 0.0 };
+        let step = 1.0 / FADE_SAMPLES as Sample;
+
+        {
+            let output = outputs[0].expect_stereo();
+
+            let frames = program.chunks(CHANNELS)
+                .zip(bed.chunks(CHANNELS))
+                .zip(output.chunks_mut(CHANNELS));
+
+            for ((program, bed), output) in frames {
+                if self.mix < target {
+                    self.mix = (self.mix + step).min(target);
+                } else if self.mix > target {
+                    self.mix = (self.mix - step).max(target);
+                }
+
+                for ((out, program), bed) in output.iter_mut().zip(program).zip(bed) {
+                    *out = program * (1.0 - self.mix) + bed * self.mix;
+                }
+            }
+        }
+
+        *outputs[1].expect_video() = self.run_video(t, &inputs[1], &inputs[3]);
+
+        let indication = SlateIndication { picture: self.held.is_some() };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.held.as_ref()
+            .map(|held| held.frame.data.decoded.buffer_size())
+            .unwrap_or(0)
+    }
+
+    // the slate covers for trouble elsewhere, which is when the engine is
+    // most likely to be short of time. it must never be shed along with the
+    // video modules it shows pictures from
+    fn priority(&self) -> PriorityClass {
+        PriorityClass::RealtimeAudio
+    }
+}
+
+impl Slate {
+    fn run_video(&mut self, t: u64, program: &InputRef, slate: &InputRef) -> Option<VideoFrame> {
+        let now = MediaTime::new(t as i64, SAMPLE_RATE as i64);
+
+        // the slate picture is kept up to date while off air too, so that
+        // engaging shows it on the same tick
+        if !slate.connected() {
+            self.held = None;
+        }
+
+        let incoming = slate.expect_video().cloned();
+
+        if let Some(frame) = &incoming {
+            self.held = Some(HeldFrame {
+                frame: frame.clone(),
+                active_until: now + frame.tick_offset + frame.data.duration_hint,
+            });
+        }
+
+        let was_showing = self.showing;
+        self.showing = self.params.engaged;
+
+        if !self.params.engaged {
+            return program.expect_video().cloned();
+        }
+
+        if incoming.is_some() {
+            return incoming;
+        }
+
+        let held = self.held.as_mut()?;
+
+        if was_showing && now < held.active_until {
+            return None;
+        }
+
+        // repeats are freshly encoded pictures rather than the source's
+        // packets over again
+        let tick = MediaDuration::new(1, TICKS_PER_SECOND as i64);
+        held.active_until = now + tick;
+
+        Some(VideoFrame {
+            data: video::Frame {
+                decoded: held.frame.data.decoded.clone(),
+                duration_hint: tick,
+                key_frame: true,
+                passthrough: None,
+            },
+            tick_offset: MediaDuration::new(0, 1),
+        })
+    }
+}
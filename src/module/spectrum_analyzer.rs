@@ -0,0 +1,241 @@
+use std::f64;
+use std::fmt::{self, Debug};
+use std::mem;
+use std::ops::Range;
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use rustfft::{FFTplanner, FFT};
+
+use mixlab_protocol::{SpectrumAnalyzerParams, SpectrumAnalyzerIndication, LineType, Terminal};
+use mixlab_protocol::{SPECTRUM_BANDS, SPECTRUM_MIN_HZ, SPECTRUM_MAX_HZ, SPECTRUM_FLOOR_DB};
+
+use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::module::{ModuleT, PriorityClass};
+
+// analysis window, about 93ms and 11Hz per bin at 44.1kHz. the lowest bands
+// are still narrower than a bin
+const FFT_LEN: usize = 4096;
+
+// ticks between analyses, the same rate the plotter redraws at
+const INDICATION_TICKS: u64 = 6;
+
+#[derive(Debug)]
+pub struct SpectrumAnalyzer {
+    params: SpectrumAnalyzerParams,
+    analyzer: Analyzer,
+    seq: u64,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for SpectrumAnalyzer {
+    type Params = SpectrumAnalyzerParams;
+    type Indication = SpectrumAnalyzerIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = SpectrumAnalyzer {
+            params,
+            analyzer: Analyzer::new(),
+            seq: 0,
+            inputs: vec![LineType::Stereo.unlabeled()],
+            outputs: vec![],
+        };
+
+        (module, SpectrumAnalyzerIndication::default())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        // display is up to the frontend, nothing changes here
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_stereo();
+
+        self.analyzer.push(input.chunks(2).map(|frame| (frame[0] + frame[1]) / 2.0));
+
+        if (t / SAMPLES_PER_TICK as u64) % INDICATION_TICKS != 0 {
+            return None;
+        }
+
+        self.seq += 1;
+
+        Some(SpectrumAnalyzerIndication {
+            seq: self.seq,
+            levels: self.analyzer.analyse(),
+        })
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.analyzer.memory_usage()
+    }
+
+    fn priority(&self) -> PriorityClass {
+        PriorityClass::UiFeedback
+    }
+}
+
+// Measures the level of each band over the last FFT_LEN samples pushed.
+struct Analyzer {
+    fft: Arc<dyn FFT<f32>>,
+    window: Vec<f32>,
+    window_sum: f32,
+    // the last FFT_LEN samples, oldest first
+    history: Vec<f32>,
+    scratch: Vec<Complex<f32>>,
+    spectrum: Vec<Complex<f32>>,
+    // bins summed for each band
+    bands: Vec<Range<usize>>,
+}
+
+impl Debug for Analyzer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Analyzer")
+    }
+}
+
+impl Analyzer {
+    fn new() -> Self {
+        // hann window
+        let window = (0..FFT_LEN)
+            .map(|i| 0.5 - 0.5 * (2.0 * f64::consts::PI * i as f64 / FFT_LEN as f64).cos())
+            .map(|w| w as f32)
+            .collect::<Vec<_>>();
+
+        Analyzer {
+            fft: FFTplanner::new(false).plan_fft(FFT_LEN),
+            window_sum: window.iter().sum(),
+            window,
+            history: vec![0.0; FFT_LEN],
+            scratch: vec![Complex::zero(); FFT_LEN],
+            spectrum: vec![Complex::zero(); FFT_LEN],
+            bands: band_bins(),
+        }
+    }
+
+    fn memory_usage(&self) -> usize {
+        (self.window.len() + self.history.len()) * mem::size_of::<f32>()
+            + (self.scratch.len() + self.spectrum.len()) * mem::size_of::<Complex<f32>>()
+    }
+
+    fn push(&mut self, samples: impl ExactSizeIterator<Item = f32>) {
+        // only the last FFT_LEN samples are ever analysed
+        let total = samples.len();
+        let len = total.min(FFT_LEN);
+        self.history.copy_within(len.., 0);
+
+        for (slot, sample) in self.history[FFT_LEN - len..].iter_mut().zip(samples.skip(total - len)) {
+            *slot = sample;
+        }
+    }
+
+    fn analyse(&mut self) -> Vec<f32> {
+        for ((value, sample), w) in self.scratch.iter_mut().zip(&self.history).zip(&self.window) {
+            *value = Complex::new(sample * w, 0.0);
+        }
+
+        self.fft.process(&mut self.scratch, &mut self.spectrum);
+
+        let spectrum = &self.spectrum;
+        let window_sum = self.window_sum;
+
+        self.bands.iter()
+            .map(|bins| {
+                let power = spectrum[bins.clone()].iter().map(|bin| bin.norm_sqr()).sum::<f32>();
+
+                // scaled so that a full scale sine reads close to 0dB
+                let amplitude = 2.0 * power.sqrt() / window_sum;
+
+                (20.0 * amplitude.log10()).max(SPECTRUM_FLOOR_DB)
+            })
+            .collect()
+    }
+}
+
+// bin k is centred on k * SAMPLE_RATE / FFT_LEN Hz, and belongs to the band
+// that frequency falls in
+fn band_bins() -> Vec<Range<usize>> {
+    let bin_hz = SAMPLE_RATE as f64 / FFT_LEN as f64;
+    let ratio = (SPECTRUM_MAX_HZ / SPECTRUM_MIN_HZ).powf(1.0 / SPECTRUM_BANDS as f64);
+
+    (0..SPECTRUM_BANDS)
+        .map(|band| {
+            let lo = SPECTRUM_MIN_HZ * ratio.powi(band as i32);
+            let hi = lo * ratio;
+
+            let start = (lo / bin_hz).ceil() as usize;
+            let end = (hi / bin_hz).ceil() as usize;
+
+            if start < end {
+                start..end
+            } else {
+                // narrower than a bin, take the nearest one
+                let bin = ((lo * hi).sqrt() / bin_hz).round() as usize;
+                bin..bin + 1
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sine_reads_in_its_band() {
+        let mut analyzer = Analyzer::new();
+
+        let sine = (0..FFT_LEN)
+            .map(|i| 0.5 * (2.0 * f64::consts::PI * 1000.0 * i as f64 / SAMPLE_RATE as f64).sin() as f32)
+            .collect::<Vec<_>>();
+
+        analyzer.push(sine.into_iter());
+        let levels = analyzer.analyse();
+
+        let loudest = (0..SPECTRUM_BANDS)
+            .fold(0, |loudest, band| if levels[band] > levels[loudest] { band } else { loudest });
+
+        let bin_hz = SAMPLE_RATE as f64 / FFT_LEN as f64;
+        let bins = &analyzer.bands[loudest];
+        assert!(bins.start as f64 * bin_hz <= 1000.0 && 1000.0 < bins.end as f64 * bin_hz);
+
+        // half scale, -6dB give or take the window spreading it over bins
+        assert!(levels[loudest] > -9.0 && levels[loudest] < -3.0, "level: {}", levels[loudest]);
+    }
+
+    #[test]
+    fn silence_reads_at_the_floor() {
+        let mut analyzer = Analyzer::new();
+        analyzer.push(vec![0.0; SAMPLES_PER_TICK].into_iter());
+
+        assert_eq!(vec![SPECTRUM_FLOOR_DB; SPECTRUM_BANDS], analyzer.analyse());
+    }
+
+    #[test]
+    fn every_band_has_a_bin() {
+        let bands = band_bins();
+
+        // the lowest bands can share a bin, but never go backwards
+        for pair in bands.windows(2) {
+            assert!(pair[0].start <= pair[1].start);
+        }
+
+        assert!(bands.iter().all(|bins| !bins.is_empty()));
+    }
+}
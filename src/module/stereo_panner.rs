@@ -1,5 +1,5 @@
-use crate::engine::{self, InputRef, OutputRef};
-use crate::module::{ModuleT, LineType, Terminal};
+use crate::engine::{self, InputRef, OutputRef, ModuleHost, DynModuleHost};
+use crate::module::{self, ModuleT, LineType, Terminal};
 
 #[derive(Debug)]
 pub struct StereoPanner {
@@ -47,4 +47,8 @@ impl ModuleT for StereoPanner {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn host(host: ModuleHost<Self>) -> DynModuleHost {
+        module::parallel(host)
+    }
 }
@@ -1,5 +1,5 @@
-use crate::engine::{self, InputRef, OutputRef};
-use crate::module::{ModuleT, LineType, Terminal};
+use crate::engine::{self, InputRef, OutputRef, ModuleHost, DynModuleHost};
+use crate::module::{self, ModuleT, LineType, Terminal};
 
 #[derive(Debug)]
 pub struct StereoSplitter {
@@ -53,4 +53,8 @@ impl ModuleT for StereoSplitter {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn host(host: ModuleHost<Self>) -> DynModuleHost {
+        module::parallel(host)
+    }
 }
@@ -1,20 +1,28 @@
 use std::cmp;
+use std::mem;
+use std::path::{Path, PathBuf};
 
-use mixlab_protocol::{StreamInputParams, LineType, Terminal, StreamProtocol};
+use mixlab_protocol::{StreamInputParams, StreamInputIndication, LineType, Terminal, StreamProtocol};
 use mixlab_util::time::{MediaTime, MediaDuration};
 
 use crate::engine::{self, InputRef, OutputRef, Sample, VideoFrame, SAMPLE_RATE};
+use crate::guest;
 use crate::icecast;
 use crate::module::ModuleT;
+use crate::mpegts;
 use crate::rtmp;
-use crate::source::{SourceRecv, SourceId, Frame, AudioData, VideoData};
+use crate::source::{SourceRecv, SourceId, SourceInfo, Frame, AudioData, VideoData};
 use crate::util;
 
 #[derive(Debug)]
 pub struct StreamInput {
     params: StreamInputParams,
+    // where file sources are played from
+    media_dir: PathBuf,
     recv: Option<SourceRecv>,
+    info: SourceInfo,
     source: Option<SourceTiming>,
+    settle: Option<Settle>,
     audio_frame: Option<Frame<AudioData>>,
     video_frame: Option<Frame<VideoData>>,
     inputs: Vec<Terminal>,
@@ -27,18 +35,82 @@ struct SourceTiming {
     epoch: MediaTime,
 }
 
+// frames from a newly connected source are discarded until it has been
+// connected for the settle window, its timestamps run forwards, and for
+// video, a keyframe arrives
+#[derive(Debug)]
+struct Settle {
+    source_id: SourceId,
+    // source time the window started, restarts if timestamps go backwards
+    start: MediaTime,
+    last_audio: Option<MediaTime>,
+    last_video: Option<MediaTime>,
+    audio_live: bool,
+    video_live: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Track {
+    Audio,
+    Video,
+}
+
+impl Settle {
+    fn new(source_id: SourceId, start: MediaTime) -> Self {
+        Settle {
+            source_id,
+            start,
+            last_audio: None,
+            last_video: None,
+            audio_live: false,
+            video_live: false,
+        }
+    }
+
+    // whether a frame should be played rather than discarded
+    fn admit(&mut self, track: Track, time: MediaTime, keyframe: bool, window: MediaDuration) -> bool {
+        let (last, live) = match track {
+            Track::Audio => (&mut self.last_audio, &mut self.audio_live),
+            Track::Video => (&mut self.last_video, &mut self.video_live),
+        };
+
+        if *live {
+            return true;
+        }
+
+        if last.map(|last| time < last).unwrap_or(false) {
+            self.start = time;
+        }
+
+        *last = Some(time);
+
+        let settled = time - self.start >= window;
+
+        *live = match track {
+            Track::Audio => settled,
+            Track::Video => settled && keyframe,
+        };
+
+        *live
+    }
+}
+
 impl ModuleT for StreamInput {
     type Params = StreamInputParams;
-    type Indication = ();
+    type Indication = StreamInputIndication;
     type Event = ();
 
-    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
-        let recv = listen_mountpoint(&params);
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let media_dir = ctx.project().media_dir();
+        let recv = listen_mountpoint(&params, &media_dir);
 
         let module = StreamInput {
             params,
+            media_dir,
             recv,
+            info: SourceInfo::default(),
             source: None,
+            settle: None,
             audio_frame: None,
             video_frame: None,
             inputs: vec![],
@@ -48,7 +120,9 @@ impl ModuleT for StreamInput {
             ],
         };
 
-        (module, ())
+        let indication = module.indication();
+
+        (module, indication)
     }
 
     fn params(&self) -> Self::Params {
@@ -61,7 +135,11 @@ impl ModuleT for StreamInput {
 
         if current_mountpoint != new_mountpoint || self.params.protocol != new_params.protocol {
             // TODO - tell the user about this one too
-            self.recv = listen_mountpoint(&new_params);
+            self.recv = listen_mountpoint(&new_params, &self.media_dir);
+        } else if self.params.stream_key != new_params.stream_key {
+            if let Some(recv) = &self.recv {
+                recv.set_stream_key(new_params.stream_key.clone());
+            }
         }
 
         self.params = new_params;
@@ -70,6 +148,15 @@ impl ModuleT for StreamInput {
     }
 
     fn run_tick(&mut self, engine_time: u64, _: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let info = self.recv.as_ref().map(SourceRecv::info).unwrap_or_default();
+
+        let indication = if info != self.info {
+            self.info = info;
+            Some(self.indication())
+        } else {
+            None
+        };
+
         let engine_time = MediaTime::new(engine_time as i64, SAMPLE_RATE as i64);
 
         let (video_out, mut audio_out) = match outputs {
@@ -79,22 +166,20 @@ impl ModuleT for StreamInput {
 
         let tick_duration = MediaDuration::new(audio_out.len() as i64 / 2, SAMPLE_RATE as i64);
 
-        let video_frame = self.video_frame.take()
-            .or_else(|| {
-                self.recv.as_mut()
-                    .and_then(|recv| recv.read_video())
-            });
+        let video_frame = match self.video_frame.take() {
+            Some(frame) => Some(frame),
+            None => self.read_video(),
+        };
 
         let existing_source_id = self.source.as_ref().map(|src| src.id);
 
         // process audio frames. we may have to consume multiple input audio
         // frames to fill the output buffer
         while audio_out.len() > 0 {
-            let audio_frame = self.audio_frame.take()
-                .or_else(|| {
-                    self.recv.as_mut()
-                        .and_then(|recv| recv.read_audio())
-                    });
+            let audio_frame = match self.audio_frame.take() {
+                Some(frame) => Some(frame),
+                None => self.read_audio(),
+            };
 
             if let Some(mut frame) = audio_frame {
                 if existing_source_id != Some(frame.source_id) {
@@ -143,7 +228,7 @@ impl ModuleT for StreamInput {
             }
         });
 
-        None
+        indication
     }
 
     fn inputs(&self) -> &[Terminal] {
@@ -153,14 +238,85 @@ impl ModuleT for StreamInput {
     fn outputs(&self)-> &[Terminal] {
         &self.outputs
     }
+
+    fn memory_usage(&self) -> usize {
+        let audio = self.audio_frame.as_ref()
+            .map(|frame| frame.data.capacity() * mem::size_of::<i16>())
+            .unwrap_or(0);
+
+        let video = self.video_frame.as_ref()
+            .map(|frame| frame.data.decoded.buffer_size())
+            .unwrap_or(0);
+
+        audio + video
+    }
 }
 
-fn listen_mountpoint(params: &StreamInputParams) -> Option<SourceRecv> {
+impl StreamInput {
+    fn read_audio(&mut self) -> Option<Frame<AudioData>> {
+        loop {
+            let frame = self.recv.as_mut()?.read_audio()?;
+
+            if self.admit(Track::Audio, frame.source_id, frame.source_time, false) {
+                return Some(frame);
+            }
+        }
+    }
+
+    fn read_video(&mut self) -> Option<Frame<VideoData>> {
+        loop {
+            let frame = self.recv.as_mut()?.read_video()?;
+            let keyframe = frame.data.key_frame;
+
+            if self.admit(Track::Video, frame.source_id, frame.source_time, keyframe) {
+                return Some(frame);
+            }
+        }
+    }
+
+    fn admit(&mut self, track: Track, source_id: SourceId, time: MediaTime, keyframe: bool) -> bool {
+        let window = MediaDuration::new((self.params.settle_secs.max(0.0) * 1000.0) as i64, 1000);
+
+        if self.settle.as_ref().map(|settle| settle.source_id) != Some(source_id) {
+            self.settle = Some(Settle::new(source_id, time));
+        }
+
+        self.settle.as_mut().unwrap().admit(track, time, keyframe, window)
+    }
+
+    fn indication(&self) -> StreamInputIndication {
+        StreamInputIndication {
+            audio_codec: self.info.audio_codec.clone(),
+            audio_bitrate: self.info.audio_bitrate,
+            video_codec: self.info.video_codec.clone(),
+            link: self.info.link.clone(),
+        }
+    }
+}
+
+fn listen_mountpoint(params: &StreamInputParams, media_dir: &Path) -> Option<SourceRecv> {
     let mountpoint = params.mountpoint.as_ref()?;
 
     match params.protocol? {
-        StreamProtocol::Icecast => icecast::listen(mountpoint).ok(),
-        StreamProtocol::Rtmp => rtmp::listen(mountpoint).ok(),
+        StreamProtocol::Icecast => Some(icecast::listen(mountpoint, params.stream_key.clone())),
+        StreamProtocol::Rtmp => Some(rtmp::listen(mountpoint, params.stream_key.clone())),
+        StreamProtocol::File => {
+            rtmp::file::resolve(media_dir, mountpoint)
+                .map(|path| rtmp::file::play(mountpoint, path))
+                .map_err(|e| eprintln!("stream_input: could not play {:?}: {:?}", mountpoint, e))
+                .ok()
+        }
+        StreamProtocol::MpegTsUdp => {
+            mpegts::listen(mountpoint)
+                .map_err(|e| eprintln!("stream_input: could not listen on {:?}: {:?}", mountpoint, e))
+                .ok()
+        }
+        StreamProtocol::Srt => {
+            mpegts::listen_srt(mountpoint, params.stream_key.clone())
+                .map_err(|e| eprintln!("stream_input: could not listen on {:?}: {:?}", mountpoint, e))
+                .ok()
+        }
+        StreamProtocol::Guest => Some(guest::listen(mountpoint, params.stream_key.clone())),
     }
 }
 
@@ -1,45 +1,95 @@
-use std::sync::mpsc;
+use std::collections::VecDeque;
+use std::io;
+use std::mem;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use bytes::BytesMut;
+use bytes::Bytes;
 use derive_more::From;
 use fdk_aac::enc as aac;
 use rml_rtmp::time::RtmpTimestamp;
-use tokio::net::TcpStream;
 use tokio::runtime;
 use tokio::sync::oneshot;
+use tokio::task;
+use uuid::Uuid;
 
 use mixlab_codec::ffmpeg::PictureSettings;
-use mixlab_protocol::{StreamOutputParams, LineType, Terminal, StreamOutputIndication, StreamOutputLiveStatus};
-use mixlab_util::time::MediaTime;
+use mixlab_mux::mp4::AvcFrame;
+use mixlab_protocol::{StreamOutputParams, StreamOutputCommand, ModuleCommand, LineType, Terminal, StreamOutputIndication, StreamOutputLiveStatus, StreamSummary, Decibel, StreamDestination, StreamDestinationStatus};
+use mixlab_util::time::{MediaTime, MediaDuration};
 
-use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
+use crate::engine::{self, InputRef, OutputRef, CHANNELS, SAMPLE_RATE, TICKS_PER_SECOND};
+use crate::listen::srt;
 use crate::module::ModuleT;
+use crate::module::monitor::{self, AsyncCodec};
+use crate::mpegts::publish::SrtPublish;
+use crate::project::{report, ProjectBaseRef};
 use crate::rtmp;
-use crate::rtmp::packet::{AudioPacket, VideoPacket, VideoFrameType, VideoPacketType};
-use crate::rtmp::client::{self, StreamMetadata, PublishInfo, PublishClient};
-use crate::video::encode::{EncodeStream, AudioCtx, AudioParams, VideoCtx, VideoParams, StreamSegment, Profile};
+use crate::rtmp::client::{self, StreamMetadata, PublishClient, PublishError};
+use crate::video::encode::{EncodeStream, AudioCtx, AudioParams, VideoCtx, VideoParams, StreamSegment, VideoSegment, Profile};
 
 const OUTPUT_WIDTH: usize = 1120;
 const OUTPUT_HEIGHT: usize = 700;
 
+const PREVIEW_WIDTH: usize = 320;
+const PREVIEW_HEIGHT: usize = 200;
+
+// cue input counts as high above this level
+const CUE_THRESHOLD: engine::Sample = 0.5;
+
+// used when a cue is sent without a name
+const DEFAULT_CUE_NAME: &str = "cue";
+
+// short-term loudness is measured over this many seconds and logged once a
+// second for the session's loudness log
+const SHORT_TERM_WINDOW_SECS: usize = 3;
+
 #[derive(Debug)]
 pub struct StreamOutput {
     params: StreamOutputParams,
     connection: Connection,
     inputs: Vec<Terminal>,
     indication: StreamOutputIndication,
+    project: ProjectBaseRef,
+    session: Option<StreamSession>,
+    preview: Option<Preview>,
+    cue_high: bool,
+    // set by a cue impulse in params, sent on the next tick
+    pending_cue: bool,
+    switchover: Switchover,
+    // set by a switchover impulse in params, acted on the next tick
+    pending_switchover: bool,
+    // one for each of params.destinations while connecting or live
+    simulcast: Vec<Simulcast>,
+}
+
+#[derive(Debug)]
+struct Preview {
+    socket_id: Uuid,
+    epoch: Option<MediaTime>,
+    codec: AsyncCodec,
 }
 
 impl ModuleT for StreamOutput {
     type Params = StreamOutputParams;
     type Indication = StreamOutputIndication;
-    type Event = ();
+    type Event = StreamOutputCommand;
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let preview = if params.preview {
+            Some(Preview::start())
+        } else {
+            None
+        };
 
-    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
         let indic = StreamOutputIndication {
             live: StreamOutputLiveStatus::Offline,
             error: false,
+            preview: preview.as_ref().map(|preview| preview.socket_id),
+            backup: false,
+            destinations: offline_statuses(&params.destinations),
         };
 
         let module = StreamOutput {
@@ -48,8 +98,17 @@ impl ModuleT for StreamOutput {
             inputs: vec![
                 LineType::Video.labeled("Video"),
                 LineType::Stereo.labeled("Audio"),
+                LineType::Mono.labeled("Cue"),
             ],
             indication: indic.clone(),
+            project: ctx.project(),
+            session: None,
+            preview,
+            cue_high: false,
+            pending_cue: false,
+            switchover: Switchover::Primary,
+            pending_switchover: false,
+            simulcast: Vec::new(),
         };
 
         (module, indic)
@@ -60,63 +119,94 @@ impl ModuleT for StreamOutput {
     }
 
     fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
-        if new_params.seq <= self.params.seq {
-            // out of date update, reject
-            return None;
+        if new_params.preview != self.params.preview {
+            self.preview = if new_params.preview {
+                Some(Preview::start())
+            } else {
+                None
+            };
         }
 
         if self.connection.is_active() {
-            if new_params.disconnect_seq == new_params.seq {
-                self.connection = Connection::Offline;
-
-                Some(StreamOutputIndication {
-                    live: StreamOutputLiveStatus::Offline,
-                    error: false,
-                })
-            } else {
-                // cannot change params on a live stream output
-                None
-            }
+            // only the preview, cue name, backup and which destinations are
+            // enabled can change on a live stream output
+            self.params.preview = new_params.preview;
+            self.params.cue_name = new_params.cue_name;
+            self.params.backup_rtmp_url = new_params.backup_rtmp_url;
+            self.params.backup_rtmp_stream_key = new_params.backup_rtmp_stream_key;
+            self.update_simulcast(&new_params.destinations);
         } else {
             self.params = new_params;
+        }
 
-            if self.params.connect_seq == self.params.seq {
-                // connect with current details
-                let (completion_tx, completion_rx) = oneshot::channel();
-
-                // spawn task to connect to RTMP
-                tokio::spawn({
-                    let params = self.params.clone();
-                    async move {
-                        let _ = completion_tx.send(connect_rtmp(params.clone()).await);
-                    }
-                });
+        self.indicate()
+    }
 
-                self.connection = Connection::Connecting(completion_rx);
+    fn command_event(command: ModuleCommand) -> Option<StreamOutputCommand> {
+        match command {
+            ModuleCommand::StreamOutput(command) => Some(command),
+            _ => None,
+        }
+    }
 
-                Some(StreamOutputIndication {
-                    live: StreamOutputLiveStatus::Connecting,
-                    error: false,
-                })
-            } else {
-                None
+    fn receive_event(&mut self, command: StreamOutputCommand) {
+        match command {
+            StreamOutputCommand::Connect if !self.connection.is_active() => {
+                self.connect();
+            }
+            StreamOutputCommand::Disconnect if self.connection.is_active() => {
+                self.connection = Connection::Offline;
+                self.switchover = Switchover::Primary;
+                self.simulcast.clear();
+                self.finish_session();
             }
+            StreamOutputCommand::Cue if self.connection.is_active() => {
+                self.pending_cue = true;
+            }
+            StreamOutputCommand::SwitchToBackup if self.connection.is_active() => {
+                self.pending_switchover = true;
+            }
+            // stale commands from a client that hadn't yet seen the
+            // connection change
+            _ => {}
         }
     }
 
     fn run_tick(&mut self, engine_time: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
-        let (video, audio) = match inputs {
-            [video, audio] => (video.expect_video(), audio.expect_stereo()),
+        let (video, audio, cue) = match inputs {
+            [video, audio, cue] => (video.expect_video(), audio.expect_stereo(), cue.expect_mono()),
             _ => unreachable!()
         };
 
         let timestamp = MediaTime::new(engine_time as i64, SAMPLE_RATE as i64);
 
+        // cues are placed at the start of the tick they land in, which is as
+        // fine grained as video gets anyway
+        for sample in cue {
+            let high = *sample > CUE_THRESHOLD;
+
+            if high && !self.cue_high {
+                self.pending_cue = true;
+            }
+
+            self.cue_high = high;
+        }
+
+        let send_cue = mem::replace(&mut self.pending_cue, false);
+        let promote = mem::replace(&mut self.pending_switchover, false);
+
+        // the preview shows what would go out whether live or not
+        if let Some(preview) = &mut self.preview {
+            preview.send(timestamp, audio, video);
+        }
+
         let live = match &mut self.connection {
             Connection::Offline => {
+                self.simulcast.clear();
                 return self.indicate();
             }
             Connection::Failed(_) => {
+                self.simulcast.clear();
                 return self.indicate();
             }
             Connection::Connecting(completion) => {
@@ -124,7 +214,14 @@ impl ModuleT for StreamOutput {
 
                 match completion.try_recv() {
                     Ok(Ok(publish)) => {
-                        self.connection = Connection::Live(LiveOutputTask::start(timestamp, publish));
+                        let bitrate = self.session.as_ref()
+                            .map(|session| session.bitrate.clone())
+                            .unwrap_or_default();
+
+                        let settings = LiveSettings::new(&self.params);
+
+                        self.connection = Connection::Live(
+                            LiveOutputTask::start(timestamp, publish, self.simulcast.len(), bitrate, settings));
 
                         match &mut self.connection {
                             Connection::Live(live) => live,
@@ -151,15 +248,133 @@ impl ModuleT for StreamOutput {
             Connection::Live(live) => live,
         };
 
+        // the backup takes over from a failed primary without restarting the
+        // encoder, so all the audience sees is a short stall
+        let failed = live.publish_failed();
+
+        match self.switchover {
+            Switchover::Primary if promote || failed => {
+                if self.params.backup_rtmp_url.is_empty() {
+                    if failed {
+                        self.connection = Connection::Failed(None);
+                        return self.indicate();
+                    }
+                } else {
+                    self.switchover = Switchover::Connecting(
+                        connect_backup(&self.params));
+
+                    if let Some(session) = &mut self.session {
+                        session.reconnects += 1;
+                    }
+                }
+            }
+            Switchover::Failed if promote => {
+                self.switchover = Switchover::Connecting(
+                    connect_backup(&self.params));
+            }
+            Switchover::Backup | Switchover::Failed if failed => {
+                self.connection = Connection::Failed(None);
+                return self.indicate();
+            }
+            _ => {}
+        }
+
+        if let Switchover::Connecting(completion) = &mut self.switchover {
+            use oneshot::error::TryRecvError;
+
+            match completion.try_recv() {
+                Ok(Ok(publish)) => {
+                    self.switchover = Switchover::Ready(publish);
+                }
+                Ok(Err(e)) => {
+                    eprintln!("StreamOutput failed to connect to backup: {:?}", e);
+                    self.switchover = Switchover::Failed;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Closed) => {
+                    self.switchover = Switchover::Failed;
+                }
+            }
+        }
+
+        if let Switchover::Ready(_) = self.switchover {
+            if let Switchover::Ready(publish) = mem::replace(&mut self.switchover, Switchover::Backup) {
+                if let Some(publish) = live.switch(publish) {
+                    // live output is lagging, try again next tick
+                    self.switchover = Switchover::Ready(publish);
+                }
+            }
+        }
+
+        for (index, simulcast) in self.simulcast.iter_mut().enumerate() {
+            if let Simulcast::Connecting(completion) = simulcast {
+                use oneshot::error::TryRecvError;
+
+                match completion.try_recv() {
+                    Ok(Ok(publish)) => {
+                        *simulcast = Simulcast::Ready(publish);
+                    }
+                    Ok(Err(e)) => {
+                        eprintln!("StreamOutput failed to connect to destination {}: {:?}", index, e);
+                        *simulcast = Simulcast::Failed;
+                    }
+                    Err(TryRecvError::Empty) => {}
+                    Err(TryRecvError::Closed) => {
+                        *simulcast = Simulcast::Failed;
+                    }
+                }
+            }
+
+            // hand overs to a lagging live output are tried again next tick
+            *simulcast = match mem::replace(simulcast, Simulcast::Disabled) {
+                Simulcast::Ready(publish) => match live.add_destination(index, publish) {
+                    Some(publish) => Simulcast::Ready(publish),
+                    None => Simulcast::Live,
+                },
+                Simulcast::Removing => match live.remove_destination(index) {
+                    true => Simulcast::Disabled,
+                    false => Simulcast::Removing,
+                },
+                other => other,
+            };
+        }
+
+        if send_cue {
+            let name = match self.params.cue_name.as_str() {
+                "" => DEFAULT_CUE_NAME.to_owned(),
+                name => name.to_owned(),
+            };
+
+            // a lagging encoder thread will have dropped this tick's audio
+            // and video too, the cue is no worse off than they are
+            let _ = live.send(LiveOutputMsg::Cue { timestamp, name });
+        }
+
+        let has_video = video.is_some();
+
         let msg = LiveOutputMsg::Tick {
             timestamp,
             audio: audio.to_vec(),
             video: video.cloned(),
         };
 
-        match live.send(msg) {
-            Ok(()) => {}
-            Err(()) => {
+        let result = live.send(msg);
+
+        if let Some(session) = &mut self.session {
+            session.live_ticks += 1;
+            session.loudness.record(audio);
+            session.loudness_log.record(audio);
+
+            if let Err(LiveOutputError::Lagged) = result {
+                if has_video {
+                    session.dropped_frames += 1;
+                }
+            }
+        }
+
+        match result {
+            Ok(()) | Err(LiveOutputError::Lagged) => {}
+            Err(LiveOutputError::Disconnected) => {
                 self.connection = Connection::Failed(None);
             }
         }
@@ -174,77 +389,218 @@ impl ModuleT for StreamOutput {
     fn outputs(&self) -> &[Terminal] {
         &[]
     }
+
+    fn frame_rate(&self) -> Option<u32> {
+        // forwarding needs every source frame as it comes, with its packets
+        if self.params.passthrough {
+            None
+        } else {
+            Some(engine::DEFAULT_FPS)
+        }
+    }
 }
 
-#[derive(Debug, From)]
-enum RtmpConnectError {
-    Url(url::ParseError),
-    UnsupportedScheme,
-    MissingHost,
-    Io(tokio::io::Error),
-    Client(client::Error),
-}
-
-async fn connect_rtmp(params: StreamOutputParams) -> Result<PublishClient, RtmpConnectError> {
-    let url = url::Url::parse(&params.rtmp_url)?;
-
-    if url.scheme() != "rtmp" {
-        return Err(RtmpConnectError::UnsupportedScheme);
-    }
-
-    let hostname = url.host_str().ok_or(RtmpConnectError::MissingHost)?;
-    let port = url.port().unwrap_or(1935);
-
-    let path = url.path();
-    // url docs guarantee path is /-prefixed except for a specific handful of known urls:
-    assert!(path.chars().nth(0) == Some('/'));
-    let app_name = &path[1..];
-
-    let conn = TcpStream::connect((hostname, port)).await?;
-    conn.set_nodelay(true)?;
-
-    let client = client::start(conn)
-        .await?
-        .publish(PublishInfo {
-            app_name: app_name.to_owned(),
-            stream_key: params.rtmp_stream_key.to_owned(),
-            meta: StreamMetadata {
-                video_width: Some(OUTPUT_WIDTH as u32),
-                video_height: Some(OUTPUT_HEIGHT as u32),
-                video_codec: Some("avc1".to_owned()),
-                video_frame_rate: Some(30.0),
-                video_bitrate_kbps: None, //Some(2500),
-                audio_codec: Some("aac1".to_owned()),
-                audio_bitrate_kbps: Some(160),
-                audio_sample_rate: Some(SAMPLE_RATE as u32),
-                audio_channels: Some(2),
-                audio_is_stereo: Some(true),
-                encoder: Some("Mixlab".to_owned()),
-            },
+fn connect_backup(params: &StreamOutputParams) -> oneshot::Receiver<Result<Destination, ConnectError>> {
+    spawn_connect(params.backup_rtmp_url.clone(), params.backup_rtmp_stream_key.clone())
+}
+
+fn spawn_connect(url: String, stream_key: String) -> oneshot::Receiver<Result<Destination, ConnectError>> {
+    let (completion_tx, completion_rx) = oneshot::channel();
+
+    tokio::spawn(async move {
+        let _ = completion_tx.send(connect(url, stream_key).await);
+    });
+
+    completion_rx
+}
+
+fn offline_statuses(destinations: &[StreamDestination]) -> Vec<StreamDestinationStatus> {
+    destinations.iter()
+        .map(|destination| match destination.enabled {
+            true => StreamDestinationStatus::Offline,
+            false => StreamDestinationStatus::Disabled,
         })
-        .await?;
+        .collect()
+}
+
+// urls beginning srt:// are sent a transport stream over SRT, with the stream
+// key as its stream id. anything else is taken to be RTMP
+async fn connect(url: String, stream_key: String) -> Result<Destination, ConnectError> {
+    if url.starts_with("srt://") {
+        let publish = task::spawn_blocking(move || SrtPublish::connect(&url, &stream_key))
+            .await
+            .expect("join srt connect thread")?;
+
+        return Ok(Destination::Srt(publish));
+    }
 
-    Ok(client)
+    let publish = connect_rtmp(url, stream_key).await?;
+    Ok(Destination::Rtmp(publish))
+}
+
+async fn connect_rtmp(url: String, stream_key: String) -> Result<PublishClient, client::ConnectError> {
+    client::connect(&url, stream_key, StreamMetadata {
+        video_width: Some(OUTPUT_WIDTH as u32),
+        video_height: Some(OUTPUT_HEIGHT as u32),
+        video_codec: Some("avc1".to_owned()),
+        video_frame_rate: Some(engine::DEFAULT_FPS as f32),
+        video_bitrate_kbps: None, //Some(2500),
+        audio_codec: Some("aac1".to_owned()),
+        audio_bitrate_kbps: Some(160),
+        audio_sample_rate: Some(SAMPLE_RATE as u32),
+        audio_channels: Some(2),
+        audio_is_stereo: Some(true),
+        encoder: Some("Mixlab".to_owned()),
+    }).await
+}
+
+impl Drop for StreamOutput {
+    fn drop(&mut self) {
+        self.finish_session();
+    }
 }
 
 impl StreamOutput {
+    fn finish_session(&mut self) {
+        if let Some(session) = self.session.take() {
+            let summary = session.summary();
+            let loudness = session.loudness_log.entries;
+            let project = self.project.clone();
+
+            tokio::spawn(async move {
+                if let Err(e) = report::save(&project, summary, loudness).await {
+                    eprintln!("StreamOutput: could not save stream report: {:?}", e);
+                }
+            });
+        }
+    }
+
+    // takes only the enabled flags from new params, as the destinations
+    // themselves are fixed while live. each connects or drops out on its own
+    fn update_simulcast(&mut self, destinations: &[StreamDestination]) {
+        let current = self.params.destinations.iter_mut()
+            .zip(destinations)
+            .zip(&mut self.simulcast);
+
+        for ((destination, new_destination), simulcast) in current {
+            if destination.enabled == new_destination.enabled {
+                continue;
+            }
+
+            destination.enabled = new_destination.enabled;
+
+            *simulcast = if destination.enabled {
+                Simulcast::Connecting(spawn_connect(
+                    destination.url.clone(), destination.stream_key.clone()))
+            } else if let Simulcast::Live = simulcast {
+                Simulcast::Removing
+            } else {
+                Simulcast::Disabled
+            };
+        }
+    }
+
+    fn destination_statuses(&self) -> Vec<StreamDestinationStatus> {
+        let live = match &self.connection {
+            Connection::Offline | Connection::Failed(_) => {
+                return offline_statuses(&self.params.destinations);
+            }
+            Connection::Connecting(_) => None,
+            Connection::Live(live) => Some(live),
+        };
+
+        self.simulcast.iter()
+            .enumerate()
+            .map(|(index, simulcast)| match simulcast {
+                Simulcast::Disabled | Simulcast::Removing => StreamDestinationStatus::Disabled,
+                Simulcast::Connecting(_) | Simulcast::Ready(_) => StreamDestinationStatus::Connecting,
+                Simulcast::Live => match live {
+                    Some(live) if live.destination_failed(index) => StreamDestinationStatus::Failed,
+                    _ => StreamDestinationStatus::Live,
+                },
+                Simulcast::Failed => StreamDestinationStatus::Failed,
+            })
+            .collect()
+    }
+
+    fn preview_socket(&self) -> Option<Uuid> {
+        self.preview.as_ref().map(|preview| preview.socket_id)
+    }
+
+    // connects with the current details
+    fn connect(&mut self) {
+        let (completion_tx, completion_rx) = oneshot::channel();
+
+        // spawn task to connect to the destination
+        tokio::spawn({
+            let url = self.params.rtmp_url.clone();
+            let stream_key = self.params.rtmp_stream_key.clone();
+            async move {
+                let _ = completion_tx.send(connect(url, stream_key).await);
+            }
+        });
+
+        self.connection = Connection::Connecting(completion_rx);
+        self.switchover = Switchover::Primary;
+
+        // further destinations connect alongside the primary, and are
+        // published to once both it and they are ready
+        self.simulcast = self.params.destinations.iter()
+            .map(|destination| {
+                if destination.enabled {
+                    Simulcast::Connecting(spawn_connect(
+                        destination.url.clone(), destination.stream_key.clone()))
+                } else {
+                    Simulcast::Disabled
+                }
+            })
+            .collect();
+
+        // connecting again after a failure continues the same session
+        match &mut self.session {
+            Some(session) => { session.reconnects += 1; }
+            None => { self.session = Some(StreamSession::new(&self.params)); }
+        }
+    }
+
     fn indicate(&mut self) -> Option<StreamOutputIndication> {
+        let preview = self.preview_socket();
+        let destinations = self.destination_statuses();
+
+        let backup = match self.switchover {
+            Switchover::Backup => true,
+            _ => false,
+        };
+
         let new_indication = match &self.connection {
             Connection::Offline => StreamOutputIndication {
                 live: StreamOutputLiveStatus::Offline,
                 error: false,
+                preview,
+                backup,
+                destinations,
             },
             Connection::Failed(_) => StreamOutputIndication {
                 live: StreamOutputLiveStatus::Offline,
                 error: true,
+                preview,
+                backup,
+                destinations,
             },
             Connection::Connecting(_) => StreamOutputIndication {
                 live: StreamOutputLiveStatus::Connecting,
                 error: false,
+                preview,
+                backup,
+                destinations,
             },
-            Connection::Live(_) => StreamOutputIndication {
+            Connection::Live(live) => StreamOutputIndication {
                 live: StreamOutputLiveStatus::Live,
-                error: false,
+                // until the backup takes over
+                error: live.publish_failed(),
+                preview,
+                backup,
+                destinations,
             },
         };
 
@@ -258,14 +614,109 @@ impl StreamOutput {
     }
 }
 
+impl Preview {
+    fn start() -> Self {
+        let socket_id = Uuid::new_v4();
+
+        Preview {
+            socket_id,
+            epoch: None,
+            codec: AsyncCodec::start(socket_id,
+                PictureSettings::yuv420p(PREVIEW_WIDTH, PREVIEW_HEIGHT), Profile::Preview),
+        }
+    }
+
+    fn send(&mut self, timestamp: MediaTime, audio: &[engine::Sample], video: Option<&engine::VideoFrame>) {
+        let epoch = *self.epoch.get_or_insert(timestamp);
+
+        let result = self.codec.send(monitor::Tick {
+            timestamp: timestamp.remove_epoch(epoch),
+            audio: audio.to_vec(),
+            video: video.cloned(),
+        });
+
+        if let Err(()) = result {
+            eprintln!("StreamOutput: preview codec thread died");
+        }
+    }
+}
+
 #[derive(Debug)]
 enum Connection {
     Offline,
-    Failed(Option<RtmpConnectError>),
-    Connecting(oneshot::Receiver<Result<PublishClient, RtmpConnectError>>),
+    Failed(Option<ConnectError>),
+    Connecting(oneshot::Receiver<Result<Destination, ConnectError>>),
     Live(LiveOutputTask),
 }
 
+#[derive(Debug, From)]
+enum ConnectError {
+    Rtmp(client::ConnectError),
+    Srt(srt::ConnectError),
+}
+
+// where a live stream is being published to
+#[derive(Debug)]
+enum Switchover {
+    Primary,
+    Connecting(oneshot::Receiver<Result<Destination, ConnectError>>),
+    // connected, waiting to be handed to the live output
+    Ready(Destination),
+    Backup,
+    // the backup could not be reached, and can be tried again
+    Failed,
+}
+
+// one of params.destinations, published to alongside the primary
+#[derive(Debug)]
+enum Simulcast {
+    Disabled,
+    Connecting(oneshot::Receiver<Result<Destination, ConnectError>>),
+    // connected, waiting to be handed to the live output
+    Ready(Destination),
+    Live,
+    // disabled while live, waiting to be taken from the live output
+    Removing,
+    Failed,
+}
+
+// a connected destination, which the live output publishes to the same way
+// whatever the protocol
+#[derive(Debug)]
+enum Destination {
+    Rtmp(PublishClient),
+    Srt(SrtPublish),
+}
+
+impl Destination {
+    fn publish_sequence_headers(&mut self, audio: &AudioCtx, video: &VideoCtx) -> Result<(), PublishError> {
+        match self {
+            Destination::Rtmp(publish) => publish.publish_sequence_headers(audio, video),
+            Destination::Srt(publish) => publish.publish_sequence_headers(audio, video).map_err(srt_failed),
+        }
+    }
+
+    fn publish_cue_point(&mut self, name: &str, timestamp: RtmpTimestamp) -> Result<(), PublishError> {
+        match self {
+            Destination::Rtmp(publish) => publish.publish_cue_point(name, timestamp),
+            // transport streams have nowhere standard for named cues
+            Destination::Srt(_) => Ok(()),
+        }
+    }
+
+    fn publish_segment(&mut self, segment: StreamSegment) -> Result<(), PublishError> {
+        match self {
+            Destination::Rtmp(publish) => publish.publish_segment(segment),
+            Destination::Srt(publish) => publish.publish_segment(segment).map_err(srt_failed),
+        }
+    }
+}
+
+fn srt_failed(e: io::Error) -> PublishError {
+    eprintln!("StreamOutput: srt destination failed: {:?}", e);
+    PublishError::Disconnected
+}
+
 impl Connection {
     pub fn is_active(&self) -> bool {
         match self {
@@ -277,38 +728,259 @@ impl Connection {
     }
 }
 
+// statistics gathered over the course of a stream session, from the first
+// connect until an explicit disconnect. a report is saved once it ends
+#[derive(Debug)]
+struct StreamSession {
+    destination: String,
+    started_at: SystemTime,
+    live_ticks: u64,
+    dropped_frames: u64,
+    reconnects: u64,
+    loudness: LoudnessStat,
+    loudness_log: LoudnessLog,
+    bitrate: Arc<Mutex<BitrateStat>>,
+}
+
+impl StreamSession {
+    fn new(params: &StreamOutputParams) -> Self {
+        StreamSession {
+            destination: params.rtmp_url.clone(),
+            started_at: SystemTime::now(),
+            live_ticks: 0,
+            dropped_frames: 0,
+            reconnects: 0,
+            loudness: LoudnessStat::default(),
+            loudness_log: LoudnessLog::default(),
+            bitrate: Arc::default(),
+        }
+    }
+
+    fn summary(&self) -> StreamSummary {
+        let duration_secs = self.live_ticks as f64 / TICKS_PER_SECOND as f64;
+
+        let started_at = self.started_at.duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_secs() as i64)
+            .unwrap_or(0);
+
+        let bitrate = self.bitrate.lock().expect("lock bitrate stat");
+
+        let average_bitrate_kbps = if duration_secs > 0.0 {
+            (bitrate.total_bytes * 8) as f64 / duration_secs / 1000.0
+        } else {
+            0.0
+        };
+
+        StreamSummary {
+            destination: self.destination.clone(),
+            started_at,
+            duration_secs,
+            average_bitrate_kbps,
+            peak_bitrate_kbps: (bitrate.peak_bytes_per_second * 8) as f64 / 1000.0,
+            dropped_frames: self.dropped_frames,
+            reconnects: self.reconnects,
+            loudness_average: self.loudness.average(),
+            loudness_peak: self.loudness.peak(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LoudnessStat {
+    sum_squares: f64,
+    samples: u64,
+    peak: f64,
+}
+
+impl LoudnessStat {
+    // floor for reported levels so that silence doesn't come out as -inf
+    const FLOOR: Decibel = Decibel(-100.0);
+
+    fn record(&mut self, audio: &[engine::Sample]) {
+        for sample in audio {
+            let sample = *sample as f64;
+            self.sum_squares += sample * sample;
+            self.peak = self.peak.max(sample.abs());
+        }
+
+        self.samples += audio.len() as u64;
+    }
+
+    fn average(&self) -> Decibel {
+        if self.samples == 0 {
+            return Self::FLOOR;
+        }
+
+        let rms = (self.sum_squares / self.samples as f64).sqrt();
+        Decibel(Decibel::from_linear(rms).0.max(Self::FLOOR.0))
+    }
+
+    fn peak(&self) -> Decibel {
+        Decibel(Decibel::from_linear(self.peak).0.max(Self::FLOOR.0))
+    }
+}
+
+// unweighted RMS over a sliding window, logged each second for compliance.
+// kept in memory for the session and written out with its report
+#[derive(Debug, Default)]
+struct LoudnessLog {
+    second: LoudnessStat,
+    window: VecDeque<LoudnessStat>,
+    entries: Vec<report::LoudnessEntry>,
+}
+
+impl LoudnessLog {
+    fn record(&mut self, audio: &[engine::Sample]) {
+        self.second.record(audio);
+
+        if self.second.samples < (SAMPLE_RATE * CHANNELS) as u64 {
+            return;
+        }
+
+        self.window.push_back(mem::take(&mut self.second));
+
+        if self.window.len() > SHORT_TERM_WINDOW_SECS {
+            self.window.pop_front();
+        }
+
+        let short_term = LoudnessStat {
+            sum_squares: self.window.iter().map(|stat| stat.sum_squares).sum(),
+            samples: self.window.iter().map(|stat| stat.samples).sum(),
+            peak: 0.0,
+        };
+
+        self.entries.push(report::LoudnessEntry {
+            offset_secs: self.entries.len() as u64 + 1,
+            short_term: short_term.average(),
+        });
+    }
+}
+
+#[derive(Debug, Default)]
+struct BitrateStat {
+    total_bytes: u64,
+    second: i64,
+    second_bytes: u64,
+    peak_bytes_per_second: u64,
+}
+
+impl BitrateStat {
+    fn record(&mut self, timestamp: MediaTime, bytes: usize) {
+        let second = timestamp.round_to_base(1);
+
+        if second != self.second {
+            self.second = second;
+            self.second_bytes = 0;
+        }
+
+        self.total_bytes += bytes as u64;
+        self.second_bytes += bytes as u64;
+        self.peak_bytes_per_second = self.peak_bytes_per_second.max(self.second_bytes);
+    }
+}
+
 #[derive(Debug)]
 struct LiveOutputTask {
     tx: mpsc::SyncSender<LiveOutputMsg>,
+    // set by the live output once the destination stops accepting data
+    failed: Arc<AtomicBool>,
+    // as with failed, for each simulcast destination
+    destinations_failed: Arc<Vec<AtomicBool>>,
 }
 
 enum LiveOutputMsg {
-    Tick { timestamp: MediaTime, audio: Vec<engine::Sample>, video: Option<engine::VideoFrame> }
+    Tick { timestamp: MediaTime, audio: Vec<engine::Sample>, video: Option<engine::VideoFrame> },
+    Cue { timestamp: MediaTime, name: String },
+    Switch(Destination),
+    AddDestination(usize, Destination),
+    RemoveDestination(usize),
+}
+
+enum LiveOutputError {
+    Lagged,
+    Disconnected,
 }
 
 impl LiveOutputTask {
-    pub fn start(epoch: MediaTime, publish: PublishClient) -> Self {
+    pub fn start(epoch: MediaTime, publish: Destination, destinations: usize, bitrate: Arc<Mutex<BitrateStat>>, settings: LiveSettings) -> Self {
         let runtime = runtime::Handle::current();
         let (tx, rx) = mpsc::sync_channel(100);
+        let failed = Arc::new(AtomicBool::new(false));
+        let destinations_failed = Arc::new((0..destinations).map(|_| AtomicBool::new(false)).collect::<Vec<_>>());
 
-        thread::spawn(move || {
-            runtime.enter(move || {
-                let mut live = LiveOutput::start(epoch, publish);
+        thread::spawn({
+            let failed = failed.clone();
+            let destinations_failed = destinations_failed.clone();
+            move || runtime.enter(move || {
+                let mut live = LiveOutput::start(epoch, publish, bitrate, settings, failed, destinations_failed);
 
                 while let Ok(msg) = rx.recv() {
                     match msg {
                         LiveOutputMsg::Tick { timestamp, audio, video } => {
                             live.tick(timestamp, audio, video);
                         }
+                        LiveOutputMsg::Cue { timestamp, name } => {
+                            live.cue(timestamp, &name);
+                        }
+                        LiveOutputMsg::Switch(publish) => {
+                            live.switch(publish);
+                        }
+                        LiveOutputMsg::AddDestination(index, publish) => {
+                            live.add_destination(index, publish);
+                        }
+                        LiveOutputMsg::RemoveDestination(index) => {
+                            live.remove_destination(index);
+                        }
                     }
                 }
-            });
+            })
         });
 
-        LiveOutputTask { tx }
+        LiveOutputTask { tx, failed, destinations_failed }
+    }
+
+    pub fn publish_failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    pub fn destination_failed(&self, index: usize) -> bool {
+        self.destinations_failed.get(index)
+            .map(|failed| failed.load(Ordering::Relaxed))
+            .unwrap_or(false)
+    }
+
+    // as with switch, giving the destination back if it can't be taken now
+    pub fn add_destination(&mut self, index: usize, publish: Destination) -> Option<Destination> {
+        use mpsc::TrySendError;
+
+        match self.tx.try_send(LiveOutputMsg::AddDestination(index, publish)) {
+            Err(TrySendError::Full(LiveOutputMsg::AddDestination(_, publish))) => Some(publish),
+            _ => None,
+        }
+    }
+
+    // returns false if the live output is too busy to take this now
+    pub fn remove_destination(&mut self, index: usize) -> bool {
+        use mpsc::TrySendError;
+
+        match self.tx.try_send(LiveOutputMsg::RemoveDestination(index)) {
+            Err(TrySendError::Full(_)) => false,
+            _ => true,
+        }
     }
 
-    pub fn send(&mut self, msg: LiveOutputMsg) -> Result<(), ()> {
+    // hands the live output a new destination, giving it back if the live
+    // output is too busy to take it now
+    pub fn switch(&mut self, publish: Destination) -> Option<Destination> {
+        use mpsc::TrySendError;
+
+        match self.tx.try_send(LiveOutputMsg::Switch(publish)) {
+            Err(TrySendError::Full(LiveOutputMsg::Switch(publish))) => Some(publish),
+            _ => None,
+        }
+    }
+
+    pub fn send(&mut self, msg: LiveOutputMsg) -> Result<(), LiveOutputError> {
         use mpsc::TrySendError;
 
         match self.tx.try_send(msg) {
@@ -316,10 +988,10 @@ impl LiveOutputTask {
             Err(TrySendError::Full(_)) => {
                 // encoder thread is lagging what do? just drop for now
                 // TODO
-                Ok(())
+                Err(LiveOutputError::Lagged)
             }
             Err(TrySendError::Disconnected(_)) => {
-                Err(())
+                Err(LiveOutputError::Disconnected)
             }
         }
     }
@@ -329,44 +1001,156 @@ impl LiveOutputTask {
 struct LiveOutput {
     epoch: MediaTime,
     encode: EncodeStream,
-    publish: PublishClient,
+    publish: Destination,
+    failed: Arc<AtomicBool>,
+    // a switched to destination is sent nothing until the next key frame
+    awaiting_keyframe: bool,
+    // indexed as params.destinations, None until connected
+    simulcast: Vec<Option<SimulcastOutput>>,
+    destinations_failed: Arc<Vec<AtomicBool>>,
+    bitrate: Arc<Mutex<BitrateStat>>,
+    settings: LiveSettings,
+    // added to video timestamps when audio is to be ahead of video
+    video_delay: MediaDuration,
+    video_mode: VideoMode,
+}
+
+#[derive(Debug)]
+struct SimulcastOutput {
+    publish: Destination,
+    // as with LiveOutput, for a destination joining part way through
+    awaiting_keyframe: bool,
+}
+
+// parts of the params that are fixed for as long as a stream output is live
+#[derive(Debug)]
+struct LiveSettings {
+    passthrough: bool,
+    keyframe_interval: MediaDuration,
+    // in milliseconds, as in params
+    audio_delay: i64,
+}
+
+impl LiveSettings {
+    fn new(params: &StreamOutputParams) -> Self {
+        LiveSettings {
+            passthrough: params.passthrough,
+            // floored to keep x264 sane
+            keyframe_interval: MediaDuration::new(
+                (params.keyframe_interval.max(0.1) * 1000.0).round() as i64, 1000),
+            audio_delay: params.audio_delay_ms.round() as i64,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum VideoMode {
+    Encode,
+    // forwarding packets from an AVC source as they arrive
+    Passthrough {
+        config: Bytes,
+        next_seq: u64,
+        // moves source timestamps onto the output timeline
+        offset: MediaDuration,
+        last_decode_timestamp: MediaTime,
+    },
 }
 
 impl LiveOutput {
-    pub fn start(epoch: MediaTime, mut publish: PublishClient) -> Self {
+    pub fn start(epoch: MediaTime, mut publish: Destination, bitrate: Arc<Mutex<BitrateStat>>, settings: LiveSettings, failed: Arc<AtomicBool>, destinations_failed: Arc<Vec<AtomicBool>>) -> Self {
+        // audio is always encoded here, even in passthrough mode: engine lines
+        // carry samples only, with nothing alongside like video::Passthrough
+        // to hold the source's AAC access units. forwarding those is a
+        // separate change
         let audio_ctx = AudioCtx::new(AudioParams {
             bit_rate: aac::BitRate::Cbr(160000),
             sample_rate: SAMPLE_RATE,
             transport: aac::Transport::Raw,
         });
 
-        // configuration buffer is ASC when raw transport is in use:
-        let asc = audio_ctx.configuration_data();
-        publish.publish_audio(AudioPacket::AacSequenceHeader(asc), RtmpTimestamp::new(0)).expect("TODO");
-
-        let video_ctx = VideoCtx::new(VideoParams {
-            picture: PictureSettings::yuv420p(OUTPUT_WIDTH, OUTPUT_HEIGHT),
-            time_base: SAMPLE_RATE,
-            profile: Profile::Stream,
-        });
+        let video_ctx = new_video_ctx(settings.keyframe_interval);
 
-        let mut dsc = BytesMut::new();
-        video_ctx.decoder_configuration_record().write_to(&mut dsc);
-        let dsc = dsc.freeze();
+        let mut encode = EncodeStream::new(audio_ctx, video_ctx);
 
-        publish.publish_video(VideoPacket {
-            frame_type: VideoFrameType::KeyFrame,
-            packet_type: VideoPacketType::SequenceHeader,
-            composition_time: 0,
-            data: dsc,
-        }, RtmpTimestamp::new(0)).expect("TODO");
+        publish_sequence_headers(&mut publish, &encode, &failed);
 
-        let encode = EncodeStream::new(audio_ctx, video_ctx);
+        // audio is delayed by leading it with silence, video by pushing its
+        // timestamps later
+        let video_delay = if settings.audio_delay > 0 {
+            let frames = settings.audio_delay as usize * SAMPLE_RATE / 1000;
+            encode.send_audio(&vec![0.0; frames * CHANNELS]);
+            MediaDuration::zero()
+        } else {
+            MediaDuration::new(-settings.audio_delay, 1000)
+        };
 
         LiveOutput {
             epoch,
             encode,
             publish,
+            failed,
+            awaiting_keyframe: false,
+            simulcast: destinations_failed.iter().map(|_| None).collect(),
+            destinations_failed,
+            bitrate,
+            settings,
+            video_delay,
+            video_mode: VideoMode::Encode,
+        }
+    }
+
+    pub fn cue(&mut self, timestamp: MediaTime, name: &str) {
+        let timestamp = timestamp.remove_epoch(self.epoch).round_to_base(rtmp::TIME_BASE.into());
+
+        let timestamp = RtmpTimestamp::new(timestamp as u32);
+
+        if let Err(e) = self.publish.publish_cue_point(name, timestamp) {
+            eprintln!("StreamOutput: could not send cue point: {:?}", e);
+        }
+
+        for output in self.simulcast.iter_mut().flatten() {
+            if let Err(e) = output.publish.publish_cue_point(name, timestamp) {
+                eprintln!("StreamOutput: could not send cue point to destination: {:?}", e);
+            }
+        }
+    }
+
+    pub fn switch(&mut self, mut publish: Destination) {
+        self.restart_video();
+
+        publish_sequence_headers(&mut publish, &self.encode, &self.failed);
+
+        self.publish = publish;
+        self.awaiting_keyframe = true;
+    }
+
+    pub fn add_destination(&mut self, index: usize, mut publish: Destination) {
+        if index >= self.simulcast.len() {
+            return;
+        }
+
+        self.restart_video();
+
+        publish_sequence_headers(&mut publish, &self.encode, &self.destinations_failed[index]);
+
+        self.simulcast[index] = Some(SimulcastOutput { publish, awaiting_keyframe: true });
+    }
+
+    pub fn remove_destination(&mut self, index: usize) {
+        if let Some(output) = self.simulcast.get_mut(index) {
+            *output = None;
+        }
+    }
+
+    // brings on a key frame soon for a destination that has just joined
+    fn restart_video(&mut self) {
+        if let VideoMode::Passthrough { .. } = self.video_mode {
+            // forwarded packets only reach a key frame when the source sends
+            // one, a fresh encoder starts on one straight away
+            self.encode.reset_video(new_video_ctx(self.settings.keyframe_interval));
+            self.video_mode = VideoMode::Encode;
+        } else {
+            self.encode.force_keyframe();
         }
     }
 
@@ -374,34 +1158,165 @@ impl LiveOutput {
         self.encode.send_audio(&audio);
 
         if let Some(video_frame) = video {
-            let frame = video_frame.data.decoded.clone();
-            let frame_timestamp = timestamp.remove_epoch(self.epoch) + video_frame.tick_offset;
+            let frame_timestamp = timestamp.remove_epoch(self.epoch) + self.video_delay + video_frame.tick_offset;
+
+            if !self.forward_video(frame_timestamp, &video_frame) {
+                self.encode_video(frame_timestamp, &video_frame);
+            }
+        }
 
-            self.encode.send_video(frame_timestamp, video_frame.data.duration_hint, frame);
+        // forwarded video only moves on as source packets arrive
+        if let VideoMode::Encode = self.video_mode {
+            self.encode.barrier(timestamp.remove_epoch(self.epoch) + self.video_delay);
         }
 
-        self.encode.barrier(timestamp.remove_epoch(self.epoch));
+        let mut bitrate = self.bitrate.lock().expect("lock bitrate stat");
 
         while let Some(segment) = self.encode.recv_segment() {
-            match segment {
-                StreamSegment::Audio(audio) => {
-                    let timestamp = RtmpTimestamp::new(audio.decode_timestamp.round_to_base(rtmp::TIME_BASE.into()) as u32);
-                    self.publish.publish_audio(AudioPacket::AacRawData(audio.frame), timestamp).expect("TODO");
+            // every destination is sent the same encoded segments, each
+            // behind its own publish client
+            let simulcast = self.simulcast.iter_mut().zip(self.destinations_failed.iter());
+
+            for (output, failed) in simulcast {
+                if let Some(output) = output {
+                    publish_segment(&mut output.publish, &mut output.awaiting_keyframe, failed, segment.clone());
                 }
-                StreamSegment::Video(video) => {
-                    let timestamp = RtmpTimestamp::new(video.decode_timestamp.round_to_base(rtmp::TIME_BASE.into()) as u32);
-                    self.publish.publish_video(VideoPacket {
-                        frame_type: if video.frame.is_key_frame {
-                            VideoFrameType::KeyFrame
-                        } else {
-                            VideoFrameType::InterFrame
-                        },
-                        packet_type: VideoPacketType::Nalu,
-                        composition_time: video.frame.composition_time.round_to_base(rtmp::TIME_BASE.into()) as u32,
-                        data: video.frame.data,
-                    }, timestamp).expect("TODO");
+            }
+
+            let (decode_timestamp, bytes) = match &segment {
+                StreamSegment::Audio(audio) => (audio.decode_timestamp, audio.frame.len()),
+                StreamSegment::Video(video) => (video.decode_timestamp, video.frame.data.len()),
+            };
+
+            if publish_segment(&mut self.publish, &mut self.awaiting_keyframe, &self.failed, segment) {
+                bitrate.record(decode_timestamp, bytes);
+            }
+        }
+    }
+
+    fn encode_video(&mut self, frame_timestamp: MediaTime, video_frame: &engine::VideoFrame) {
+        if let VideoMode::Passthrough { .. } = self.video_mode {
+            // a fresh encoder starts on a key frame
+            self.encode.reset_video(new_video_ctx(self.settings.keyframe_interval));
+            self.video_mode = VideoMode::Encode;
+        }
+
+        let frame = video_frame.data.decoded.clone();
+        self.encode.send_video(frame_timestamp, video_frame.data.duration_hint, frame);
+    }
+
+    // sends on the source packets carried by a frame, returning false if the
+    // frame must be encoded instead
+    fn forward_video(&mut self, frame_timestamp: MediaTime, video_frame: &engine::VideoFrame) -> bool {
+        let passthrough = match &video_frame.data.passthrough {
+            Some(passthrough) if self.settings.passthrough => passthrough,
+            _ => return false,
+        };
+
+        let first = passthrough.packets.first();
+
+        let (offset, next_seq, mut last_decode_timestamp, mut sequence_header) = match &self.video_mode {
+            VideoMode::Passthrough { config, next_seq, offset, last_decode_timestamp }
+                if *config == passthrough.config && first.map(|packet| packet.seq == *next_seq).unwrap_or(true) =>
+            {
+                (*offset, *next_seq, *last_decode_timestamp, None)
+            }
+            _ => match first {
+                // switching sources or recovering from dropped frames can
+                // only happen at a key frame
+                Some(packet) if packet.is_key_frame => {
+                    let presentation_timestamp = packet.decode_timestamp + packet.composition_time;
+                    let offset = frame_timestamp - presentation_timestamp;
+                    (offset, packet.seq, self.encode.video_timestamp(), Some(passthrough.config.clone()))
                 }
+                _ => return false,
             }
+        };
+
+        for packet in &passthrough.packets {
+            // never step back behind video already sent
+            let decode_timestamp = packet.decode_timestamp + offset;
+            let decode_timestamp = if decode_timestamp < last_decode_timestamp {
+                last_decode_timestamp
+            } else {
+                decode_timestamp
+            };
+
+            self.encode.send_video_segment(VideoSegment {
+                decode_timestamp,
+                duration: video_frame.data.duration_hint,
+                sequence_header: sequence_header.take(),
+                frame: AvcFrame {
+                    is_key_frame: packet.is_key_frame,
+                    composition_time: packet.composition_time,
+                    data: packet.data.clone(),
+                },
+            });
+
+            last_decode_timestamp = decode_timestamp;
         }
+
+        self.video_mode = VideoMode::Passthrough {
+            config: passthrough.config.clone(),
+            next_seq: passthrough.packets.last().map(|packet| packet.seq + 1).unwrap_or(next_seq),
+            offset,
+            last_decode_timestamp,
+        };
+
+        true
+    }
+}
+
+// a destination can't decode anything sent before the sequence headers, so
+// one they can't be sent to is failed like any other publish error, for the
+// module to switch to the backup or show the destination as failed
+fn publish_sequence_headers(publish: &mut Destination, encode: &EncodeStream, failed: &AtomicBool) {
+    match publish.publish_sequence_headers(encode.audio_ctx(), encode.video_ctx()) {
+        Ok(()) => {
+            failed.store(false, Ordering::Relaxed);
+        }
+        Err(e) => {
+            eprintln!("StreamOutput: could not send sequence headers: {:?}", e);
+            failed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+// publishes a segment unless the destination has failed or is waiting on a key
+// frame, returning whether it was sent
+fn publish_segment(publish: &mut Destination, awaiting_keyframe: &mut bool, failed: &AtomicBool, segment: StreamSegment) -> bool {
+    // encoding carries on while there's nowhere to send to, so a backup can
+    // pick up without a restart
+    if failed.load(Ordering::Relaxed) {
+        return false;
     }
+
+    if *awaiting_keyframe {
+        match &segment {
+            StreamSegment::Video(video) if video.frame.is_key_frame => {
+                *awaiting_keyframe = false;
+            }
+            StreamSegment::Video(_) => return false,
+            StreamSegment::Audio(_) => {}
+        }
+    }
+
+    match publish.publish_segment(segment) {
+        Ok(()) | Err(PublishError::Lagged) => {}
+        Err(PublishError::Disconnected) => {
+            eprintln!("StreamOutput: destination disconnected");
+            failed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    true
+}
+
+fn new_video_ctx(keyframe_interval: MediaDuration) -> VideoCtx {
+    VideoCtx::new(VideoParams {
+        picture: PictureSettings::yuv420p(OUTPUT_WIDTH, OUTPUT_HEIGHT),
+        time_base: SAMPLE_RATE,
+        profile: Profile::Stream,
+        keyframe_interval: Some(keyframe_interval),
+    })
 }
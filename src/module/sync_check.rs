@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+use std::ptr;
+
+use mixlab_codec::ffmpeg::{AvFrame, PictureSettings, PixelFormat};
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_protocol::{LineType, Terminal, SyncCheckIndication};
+use mixlab_util::time::{MediaTime, MediaDuration};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK, TICKS_PER_SECOND};
+use crate::module::ModuleT;
+use crate::video;
+
+const PICTURE_WIDTH: usize = 320;
+const PICTURE_HEIGHT: usize = 180;
+
+// a flash and a beep start together once per period
+const PERIOD_TICKS: u64 = TICKS_PER_SECOND as u64;
+const PULSE_TICKS: u64 = TICKS_PER_SECOND as u64 / 10;
+
+const BEEP_FREQ: f64 = 1000.0;
+const BEEP_AMPLITUDE: Sample = 0.5;
+
+// returned audio counts as a beep above this level, and returned video as a
+// flash when its average luma is above this
+const AUDIO_THRESHOLD: Sample = 0.1;
+const LUMA_THRESHOLD: u8 = 128;
+
+// only every so many pixels are looked at to judge a picture's brightness
+const LUMA_SAMPLE_STEP: usize = 8;
+
+// measurements averaged for the reported offset
+const MEASUREMENTS: usize = 5;
+
+#[derive(Debug)]
+pub struct SyncCheck {
+    flash_frame: AvFrame<Video>,
+    dark_frame: AvFrame<Video>,
+    audio_high: bool,
+    video_high: bool,
+    last_audio_onset: Option<MediaTime>,
+    last_video_onset: Option<MediaTime>,
+    // audio onset minus video onset for recent pulses, in seconds
+    measurements: VecDeque<f64>,
+    indication: SyncCheckIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for SyncCheck {
+    type Params = ();
+    type Indication = SyncCheckIndication;
+    type Event = ();
+
+    fn create(_: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let picture = PictureSettings::yuv420p(PICTURE_WIDTH, PICTURE_HEIGHT);
+
+        let dark_frame = AvFrame::blank(&picture);
+        let mut flash_frame = AvFrame::blank(&picture);
+        fill_luma(&mut flash_frame, 0xff);
+
+        let indication = SyncCheckIndication { offset_ms: None };
+
+        (SyncCheck {
+            flash_frame,
+            dark_frame,
+            audio_high: false,
+            video_high: false,
+            last_audio_onset: None,
+            last_video_onset: None,
+            measurements: VecDeque::new(),
+            indication: indication.clone(),
+            inputs: vec![
+                LineType::Video.labeled("Video Return"),
+                LineType::Stereo.labeled("Audio Return"),
+            ],
+            outputs: vec![
+                LineType::Video.labeled("Video"),
+                LineType::Stereo.labeled("Audio"),
+            ],
+        }, indication)
+    }
+
+    fn params(&self) -> Self::Params {
+        ()
+    }
+
+    fn update(&mut self, _: Self::Params) -> Option<Self::Indication> {
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let tick = t / SAMPLES_PER_TICK as u64;
+        let tick_time = MediaTime::new(t as i64, SAMPLE_RATE as i64);
+        let pulse = tick % PERIOD_TICKS < PULSE_TICKS;
+
+        // generate test pattern
+        *outputs[0].expect_video() = Some(engine::VideoFrame {
+            data: video::Frame {
+                decoded: if pulse { self.flash_frame.clone() } else { self.dark_frame.clone() },
+                duration_hint: MediaDuration::new(1, TICKS_PER_SECOND as i64),
+                key_frame: true,
+                passthrough: None,
+            },
+            tick_offset: MediaDuration::new(0, 1),
+        });
+
+        for (i, frame) in outputs[1].expect_stereo().chunks_mut(CHANNELS).enumerate() {
+            let sample = if pulse {
+                let time = (t + i as u64) as f64 / SAMPLE_RATE as f64;
+                (time * BEEP_FREQ * 2.0 * std::f64::consts::PI).sin() as Sample * BEEP_AMPLITUDE
+            } else {
+                0.0
+            };
+
+            for out in frame {
+                *out = sample;
+            }
+        }
+
+        // look for the pattern coming back
+        if let Some(frame) = inputs[0].expect_video() {
+            let high = average_luma(&frame.data.decoded)
+                .map(|luma| luma > LUMA_THRESHOLD)
+                .unwrap_or(false);
+
+            if high && !self.video_high {
+                let onset = tick_time + frame.tick_offset;
+                self.last_video_onset = Some(onset);
+                self.measure();
+            }
+
+            self.video_high = high;
+        }
+
+        let audio_onset = inputs[1].expect_stereo()
+            .chunks(CHANNELS)
+            .position(|frame| frame.iter().any(|sample| sample.abs() > AUDIO_THRESHOLD));
+
+        if let Some(i) = audio_onset {
+            if !self.audio_high {
+                let onset = tick_time + MediaDuration::new(i as i64, SAMPLE_RATE as i64);
+                self.last_audio_onset = Some(onset);
+                self.measure();
+            }
+        }
+
+        // the beep crosses zero every cycle, so it's only over once a whole
+        // tick has been quiet
+        self.audio_high = audio_onset.is_some();
+
+        let offset_ms = if self.measurements.is_empty() {
+            None
+        } else {
+            let average = self.measurements.iter().sum::<f64>() / self.measurements.len() as f64;
+            Some((average * 1000.0).round())
+        };
+
+        if self.indication.offset_ms != offset_ms {
+            self.indication.offset_ms = offset_ms;
+            Some(self.indication.clone())
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+impl SyncCheck {
+    // pairs up the latest flash and beep once both of a pulse have arrived
+    fn measure(&mut self) {
+        let (audio, video) = match (self.last_audio_onset, self.last_video_onset) {
+            (Some(audio), Some(video)) => (audio, video),
+            _ => return,
+        };
+
+        let offset = audio_minus_video(audio, video);
+
+        // onsets more than half a period apart belong to different pulses
+        if offset.abs() >= PERIOD_TICKS as f64 / TICKS_PER_SECOND as f64 / 2.0 {
+            return;
+        }
+
+        self.last_audio_onset = None;
+        self.last_video_onset = None;
+
+        self.measurements.push_back(offset);
+
+        if self.measurements.len() > MEASUREMENTS {
+            self.measurements.pop_front();
+        }
+    }
+}
+
+fn audio_minus_video(audio: MediaTime, video: MediaTime) -> f64 {
+    let audio = audio.as_rational();
+    let video = video.as_rational();
+    let offset = audio - video;
+    *offset.numer() as f64 / *offset.denom() as f64
+}
+
+fn fill_luma(frame: &mut AvFrame<Video>, value: u8) {
+    let picture = frame.picture_settings();
+    let data = frame.frame_data_mut();
+
+    unsafe {
+        for y in 0..picture.height {
+            ptr::write_bytes(data.data(0).add(y * data.stride(0)), value, picture.width);
+        }
+    }
+}
+
+// only planar YUV pictures can be judged, where the first plane is luma
+fn average_luma(frame: &AvFrame<Video>) -> Option<u8> {
+    let picture = frame.picture_settings();
+
+    if picture.pixel_format != PixelFormat::yuv420p() || picture.width == 0 || picture.height == 0 {
+        return None;
+    }
+
+    let data = frame.frame_data();
+    let mut sum = 0u64;
+    let mut count = 0u64;
+
+    unsafe {
+        for y in (0..picture.height).step_by(LUMA_SAMPLE_STEP) {
+            let row = data.data(0).add(y * data.stride(0));
+
+            for x in (0..picture.width).step_by(LUMA_SAMPLE_STEP) {
+                sum += *row.add(x) as u64;
+                count += 1;
+            }
+        }
+    }
+
+    Some((sum / count) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_luma_tells_flash_from_dark() {
+        let picture = PictureSettings::yuv420p(PICTURE_WIDTH, PICTURE_HEIGHT);
+
+        let dark = AvFrame::blank(&picture);
+        let mut flash = AvFrame::blank(&picture);
+        fill_luma(&mut flash, 0xff);
+
+        assert_eq!(Some(0), average_luma(&dark));
+        assert_eq!(Some(0xff), average_luma(&flash));
+    }
+}
@@ -0,0 +1,72 @@
+use mixlab_protocol::{GateState, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, DuckRequest};
+use crate::module::ModuleT;
+use crate::util;
+
+#[derive(Debug)]
+pub struct Talkback {
+    params: GateState,
+    duck: DuckRequest,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Talkback {
+    type Params = GateState;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, ctx: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let mut duck = ctx.monitor_policy().duck_request();
+        duck.set_active(is_open(&params));
+
+        (Self {
+            params,
+            duck,
+            inputs: vec![LineType::Mono.labeled("Mic")],
+            outputs: vec![LineType::Stereo.unlabeled()],
+        }, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.duck.set_active(is_open(&new_params));
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_mono();
+        let output = outputs[0].expect_stereo();
+
+        if is_open(&self.params) {
+            for (i, sample) in input.iter().enumerate() {
+                output[i * 2 + 0] = *sample;
+                output[i * 2 + 1] = *sample;
+            }
+        } else {
+            util::zero(output);
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+}
+
+fn is_open(state: &GateState) -> bool {
+    match state {
+        GateState::Open => true,
+        GateState::Closed => false,
+    }
+}
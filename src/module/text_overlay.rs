@@ -0,0 +1,179 @@
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::AvFrame;
+use mixlab_protocol::{TextOverlayParams, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef};
+use crate::module::ModuleT;
+use crate::video;
+use crate::video::composite::{blend, place, yuv, yuv420p};
+use crate::video::encode::DynamicScaler;
+use crate::video::font::{self, Raster};
+
+#[derive(Debug)]
+pub struct TextOverlay {
+    params: TextOverlayParams,
+    // drawn again only when the text or its size change
+    raster: Raster,
+    scaler: Option<DynamicScaler>,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for TextOverlay {
+    type Params = TextOverlayParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = TextOverlay {
+            raster: raster(&params),
+            params,
+            scaler: None,
+            inputs: vec![LineType::Video.unlabeled()],
+            outputs: vec![LineType::Video.unlabeled()],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        if new_params.text != self.params.text || new_params.font_size != self.params.font_size {
+            self.raster = raster(&new_params);
+        }
+
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let output = outputs[0].expect_video();
+
+        let input = match inputs[0].expect_video() {
+            Some(input) => input,
+            None => {
+                *output = None;
+                return None;
+            }
+        };
+
+        if self.raster.is_empty() {
+            *output = Some(input.clone());
+            return None;
+        }
+
+        // text is drawn in yuv420p, other pictures are converted first
+        let mut frame = input.data.decoded.clone();
+        let target = yuv420p(&frame.picture_settings());
+
+        if frame.picture_settings() != target {
+            if self.scaler.as_ref().map(|scaler| scaler.output()) != Some(&target) {
+                self.scaler = Some(DynamicScaler::new(target));
+            }
+
+            let scaler = self.scaler.as_mut().unwrap();
+            frame = scaler.scale(&mut frame).clone();
+        }
+
+        draw(&mut frame, &self.raster, &self.params);
+
+        *output = Some(engine::VideoFrame {
+            data: video::Frame {
+                decoded: frame,
+                // the picture has changed, so the source's packets no longer
+                // describe it
+                passthrough: None,
+                ..input.data.clone()
+            },
+            tick_offset: input.tick_offset,
+        });
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+
+fn raster(params: &TextOverlayParams) -> Raster {
+    let scale = (params.font_size as f64 / font::CAP_HEIGHT as f64).round() as usize;
+    Raster::new(&params.text, scale)
+}
+
+fn draw(frame: &mut AvFrame<Video>, raster: &Raster, params: &TextOverlayParams) {
+    let picture = frame.picture_settings();
+
+    // the box reaches a little past the text on every side
+    let padding = raster.height() / 4;
+    let box_size = (raster.width() + padding * 2, raster.height() + padding * 2);
+
+    let (box_x, box_y) = place(params.position, box_size, (picture.width, picture.height));
+    let (text_x, text_y) = (box_x + padding, box_y + padding);
+
+    let box_right = (box_x + box_size.0).min(picture.width);
+    let box_bottom = (box_y + box_size.1).min(picture.height);
+
+    let text_color = yuv(params.color);
+    let box_color = yuv(params.box_color);
+    let box_alpha = (params.box_opacity.max(0.0).min(1.0) * 256.0) as u16;
+
+    let data = frame.frame_data_mut();
+
+    unsafe {
+        let planes = [
+            (data.data(0), data.stride(0)),
+            (data.data(1), data.stride(1)),
+            (data.data(2), data.stride(2)),
+        ];
+
+        let pixel = |plane: usize, x: usize, y: usize| {
+            let (ptr, stride) = planes[plane];
+            ptr.add(y * stride + x)
+        };
+
+        if box_alpha > 0 {
+            for y in box_y..box_bottom {
+                for x in box_x..box_right {
+                    blend(pixel(0, x, y), box_color[0], box_alpha);
+                }
+            }
+
+            for y in (box_y / 2)..(box_bottom / 2) {
+                for x in (box_x / 2)..(box_right / 2) {
+                    blend(pixel(1, x, y), box_color[1], box_alpha);
+                    blend(pixel(2, x, y), box_color[2], box_alpha);
+                }
+            }
+        }
+
+        for y in 0..raster.height() {
+            let picture_y = text_y + y;
+
+            if picture_y >= picture.height {
+                break;
+            }
+
+            for x in 0..raster.width() {
+                let picture_x = text_x + x;
+
+                if picture_x >= picture.width {
+                    break;
+                }
+
+                if raster.covers(x, y) {
+                    *pixel(0, picture_x, picture_y) = text_color[0];
+                    *pixel(1, picture_x / 2, picture_y / 2) = text_color[1];
+                    *pixel(2, picture_x / 2, picture_y / 2) = text_color[2];
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::mem;
+
+use mixlab_protocol::{TunerParams, TunerIndication, TunerPitch, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, SAMPLE_RATE, SAMPLES_PER_TICK};
+use crate::module::{ModuleT, PriorityClass};
+
+// range of fundamentals detected, from below a bass's low E to well above
+// anything sung
+const MIN_HZ: usize = 40;
+const MAX_HZ: usize = 2000;
+
+// lags searched for the period, in samples
+const MIN_LAG: usize = SAMPLE_RATE / MAX_HZ;
+const MAX_LAG: usize = SAMPLE_RATE / MIN_HZ;
+
+// samples compared at each lag, about 46ms
+const WINDOW: usize = 2048;
+
+// the first dip in the normalised difference below this is taken as the
+// period. 0.1 to 0.15 is what the YIN paper recommends
+const THRESHOLD: f64 = 0.15;
+
+// below this power, about -60dBFS, the input is taken as silence
+const SILENCE: f64 = 1e-6;
+
+// ticks between detections
+const INDICATION_TICKS: u64 = 6;
+
+#[derive(Debug)]
+pub struct Tuner {
+    params: TunerParams,
+    detector: Detector,
+    indication: TunerIndication,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Tuner {
+    type Params = TunerParams;
+    type Indication = TunerIndication;
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Tuner {
+            params,
+            detector: Detector::new(),
+            indication: TunerIndication::default(),
+            inputs: vec![LineType::Mono.unlabeled()],
+            outputs: vec![],
+        };
+
+        (module, TunerIndication::default())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        // the next detection is named against the new reference
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, t: u64, inputs: &[InputRef], _: &mut [OutputRef]) -> Option<Self::Indication> {
+        self.detector.push(inputs[0].expect_mono());
+
+        if (t / SAMPLES_PER_TICK as u64) % INDICATION_TICKS != 0 {
+            return None;
+        }
+
+        let indication = TunerIndication {
+            pitch: self.detector.detect()
+                .and_then(|(freq, confidence)| pitch(freq, confidence, self.params.reference_hz)),
+        };
+
+        if indication != self.indication {
+            self.indication = indication.clone();
+            Some(indication)
+        } else {
+            None
+        }
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self)-> &[Terminal] {
+        &self.outputs
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.detector.history.len() * mem::size_of::<Sample>()
+            + self.detector.difference.len() * mem::size_of::<f64>()
+    }
+
+    fn priority(&self) -> PriorityClass {
+        PriorityClass::UiFeedback
+    }
+}
+
+// YIN pitch detector (de Cheveigné and Kawahara, 2002). The difference
+// between the signal and itself delayed is smallest at multiples of its
+// period, and normalising by the running mean stops it from settling on
+// a lag of zero or on an octave below.
+#[derive(Debug)]
+struct Detector {
+    // the last WINDOW + MAX_LAG samples, oldest first
+    history: Vec<Sample>,
+    // cumulative mean normalised difference at each lag
+    difference: Vec<f64>,
+}
+
+impl Detector {
+    fn new() -> Self {
+        Detector {
+            history: vec![0.0; WINDOW + MAX_LAG],
+            difference: vec![0.0; MAX_LAG + 1],
+        }
+    }
+
+    fn push(&mut self, samples: &[Sample]) {
+        let total = self.history.len();
+        let len = samples.len().min(total);
+        self.history.copy_within(len.., 0);
+        self.history[total - len..].copy_from_slice(&samples[samples.len() - len..]);
+    }
+
+    // frequency and confidence of the period found, if any
+    fn detect(&mut self) -> Option<(f64, f64)> {
+        let history = &self.history;
+
+        let power = history[..WINDOW].iter().map(|sample| (*sample as f64).powi(2)).sum::<f64>() / WINDOW as f64;
+
+        if power < SILENCE {
+            return None;
+        }
+
+        let mut running_sum = 0.0;
+        self.difference[0] = 1.0;
+
+        for lag in 1..=MAX_LAG {
+            let lag_difference = difference(history, lag);
+            running_sum += lag_difference;
+
+            self.difference[lag] = if running_sum > 0.0 {
+                lag_difference * lag as f64 / running_sum
+            } else {
+                1.0
+            };
+        }
+
+        let normalised = &self.difference;
+
+        let mut lag = (MIN_LAG..MAX_LAG).find(|lag| normalised[*lag] < THRESHOLD)?;
+
+        // follow the dip down to its bottom
+        while lag + 1 < MAX_LAG && normalised[lag + 1] < normalised[lag] {
+            lag += 1;
+        }
+
+        // fit a parabola through the bottom of the raw difference for a
+        // period between samples
+        let before = difference(history, lag - 1);
+        let at = difference(history, lag);
+        let after = difference(history, lag + 1);
+        let curvature = before - 2.0 * at + after;
+
+        let offset = if curvature > 0.0 {
+            0.5 * (before - after) / curvature
+        } else {
+            0.0
+        };
+
+        let period = lag as f64 + offset;
+
+        Some((SAMPLE_RATE as f64 / period, (1.0 - normalised[lag]).max(0.0)))
+    }
+}
+
+// squared difference between the window and the window delayed by lag
+fn difference(history: &[Sample], lag: usize) -> f64 {
+    history[..WINDOW].iter()
+        .zip(&history[lag..lag + WINDOW])
+        .map(|(a, b)| (*a as f64 - *b as f64).powi(2))
+        .sum()
+}
+
+// names the nearest note to a frequency, rounded so that the indication only
+// changes when the display would
+fn pitch(freq: f64, confidence: f64, reference_hz: f64) -> Option<TunerPitch> {
+    let semitones = 69.0 + 12.0 * (freq / reference_hz).log2();
+    let note = semitones.round();
+
+    if note < 0.0 || note > 127.0 {
+        return None;
+    }
+
+    Some(TunerPitch {
+        freq: ((freq * 10.0).round() / 10.0) as f32,
+        note: note as u8,
+        cents: ((semitones - note) * 100.0).round() as f32,
+        confidence: ((confidence * 100.0).round() / 100.0) as f32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64;
+
+    use super::*;
+
+    fn detect(freq: f64) -> Option<(f64, f64)> {
+        let mut detector = Detector::new();
+
+        let sine = (0..WINDOW + MAX_LAG)
+            .map(|i| 0.5 * (2.0 * f64::consts::PI * freq * i as f64 / SAMPLE_RATE as f64).sin() as Sample)
+            .collect::<Vec<_>>();
+
+        detector.push(&sine);
+        detector.detect()
+    }
+
+    #[test]
+    fn detects_sines_across_the_range() {
+        for &freq in &[41.2, 82.41, 440.0, 1318.5] {
+            let (detected, confidence) = detect(freq).unwrap();
+
+            // within a couple of cents
+            assert!((1200.0 * (detected / freq).log2()).abs() < 2.0, "{}Hz read as {}Hz", freq, detected);
+            assert!(confidence > 0.9, "confidence: {}", confidence);
+        }
+    }
+
+    #[test]
+    fn silence_has_no_pitch() {
+        let mut detector = Detector::new();
+        detector.push(&vec![0.0; SAMPLES_PER_TICK]);
+
+        assert_eq!(None, detector.detect());
+    }
+
+    #[test]
+    fn names_notes_against_the_reference() {
+        let a4 = pitch(440.0, 1.0, 440.0).unwrap();
+        assert_eq!(69, a4.note);
+        assert_eq!(0.0, a4.cents);
+
+        let sharp = pitch(445.0, 1.0, 440.0).unwrap();
+        assert_eq!(69, sharp.note);
+        assert_eq!(20.0, sharp.cents);
+
+        let low_e = pitch(82.41, 1.0, 440.0).unwrap();
+        assert_eq!(40, low_e.note);
+
+        let baroque = pitch(415.0, 1.0, 415.0).unwrap();
+        assert_eq!(69, baroque.note);
+        assert_eq!(0.0, baroque.cents);
+    }
+}
@@ -2,10 +2,10 @@ use itertools::Itertools;
 
 use mixlab_codec::ffmpeg::media::Video;
 use mixlab_codec::ffmpeg::{AvFrame, PictureSettings, PixelFormat};
-use mixlab_protocol::{VideoMixerParams, LineType, Terminal, VIDEO_MIXER_CHANNELS};
+use mixlab_protocol::{VideoMixerParams, LineType, Terminal, VIDEO_MIXER_MAX_CHANNELS};
 use mixlab_util::time::{MediaTime, MediaDuration};
 
-use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE, TICKS_PER_SECOND};
+use crate::engine::{self, InputRef, OutputRef, SAMPLE_RATE};
 use crate::module::ModuleT;
 use crate::video;
 use crate::video::encode::DynamicScaler;
@@ -13,12 +13,16 @@ use crate::video::encode::DynamicScaler;
 #[derive(Debug)]
 pub struct VideoMixer {
     params: VideoMixerParams,
+    // channels actually on A and B. these trail the params while a cut is
+    // waiting for a key frame
+    live_a: Option<usize>,
+    live_b: Option<usize>,
     inputs: Vec<Terminal>,
     outputs: Vec<Terminal>,
     channels: Vec<Channel>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct Channel {
     stored: Option<StoredFrame>,
     scaler: Option<DynamicScaler>,
@@ -37,25 +41,7 @@ impl ModuleT for VideoMixer {
     type Event = ();
 
     fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
-        let mixer = VideoMixer {
-            params,
-            inputs: (0..VIDEO_MIXER_CHANNELS).map(|i|
-                LineType::Video.labeled(&(i + 1).to_string())
-            ).collect(),
-            outputs: vec![
-                LineType::Video.labeled("Output"),
-                LineType::Video.labeled("A"),
-                LineType::Video.labeled("B"),
-            ],
-            channels: (0..VIDEO_MIXER_CHANNELS).map(|_| {
-                Channel {
-                    stored: None,
-                    scaler: None,
-                }
-            }).collect(),
-        };
-
-        (mixer, ())
+        (VideoMixer::new(params), ())
     }
 
     fn params(&self) -> Self::Params {
@@ -63,7 +49,15 @@ impl ModuleT for VideoMixer {
     }
 
     fn update(&mut self, new_params: VideoMixerParams) -> Option<Self::Indication> {
-        self.params = new_params;
+        let params = clamp_channels(new_params);
+
+        // channels which remain keep their stored frames and scalers
+        self.inputs = channel_inputs(params.channels);
+        self.channels.resize_with(params.channels, Channel::default);
+        self.live_a = self.live_a.filter(|a| *a < params.channels);
+        self.live_b = self.live_b.filter(|b| *b < params.channels);
+
+        self.params = params;
         None
     }
 
@@ -76,14 +70,17 @@ impl ModuleT for VideoMixer {
         let out_a = out_a.expect_video();
         let out_b = out_b.expect_video();
 
+        self.live_a = cut(self.live_a, self.params.a, inputs, self.params.cut_on_key_frame);
+        self.live_b = cut(self.live_b, self.params.b, inputs, self.params.cut_on_key_frame);
+
         // send channel specific outputs
         {
-            *out_a = self.params.a
+            *out_a = self.live_a
                 .and_then(|a| inputs.get(a))
                 .and_then(|input| input.expect_video())
                 .cloned();
 
-            *out_b = self.params.b
+            *out_b = self.live_b
                 .and_then(|b| inputs.get(b))
                 .and_then(|input| input.expect_video())
                 .cloned();
@@ -100,6 +97,22 @@ impl ModuleT for VideoMixer {
             }
         }
 
+        // inputs are conformed to the same frame rate, so all that are
+        // connected bring a frame at the start of each frame interval. the
+        // mix goes out then too
+        let interval = inputs.iter()
+            .filter_map(|input| input.expect_video())
+            .map(|video| video.tick_offset)
+            .next();
+
+        let interval = match interval {
+            Some(interval) => interval,
+            None => {
+                // not the start of a frame interval
+                return None;
+            }
+        };
+
         // calculate compatible output picture settings
         let target = inputs.iter().enumerate()
             .flat_map(|(idx, input)| {
@@ -155,12 +168,12 @@ impl ModuleT for VideoMixer {
             let pixfmt = pict.pixel_format.descriptor();
             let output = output_frame.frame_data_mut();
 
-            let channel_a = self.params.a
+            let channel_a = self.live_a
                 .and_then(|a| self.channels.get(a))
                 .and_then(|ch| ch.stored.as_ref())
                 .map(|stored| stored.frame.frame_data());
 
-            let channel_b = self.params.b
+            let channel_b = self.live_b
                 .and_then(|b| self.channels.get(b))
                 .and_then(|ch| ch.stored.as_ref())
                 .map(|stored| stored.frame.frame_data());
@@ -206,33 +219,6 @@ impl ModuleT for VideoMixer {
                         let out_ptr = out_ptr.add(y * out_linesize);
 
                         fade_line(out_ptr, a_ptr, b_ptr, width, crossfade);
-
-                        #[inline(never)]
-                        unsafe fn fade_line(mut out: *mut u8, mut a: *const u8, mut b: *const u8, len: usize, fade: u8) {
-                            use std::slice;
-                            use packed_simd::{u8x32, u16x32, Cast};
-
-                            let a_fade = u16x32::splat(fade as u16);
-                            let b_fade = u16x32::splat((255 - fade) as u16);
-                            let div = u16x32::splat(255);
-
-                            let end = out.add(len);
-                            while out < end {
-                                let a_vals: u16x32 = u8x32::from_slice_aligned_unchecked(slice::from_raw_parts(a, 32)).cast();
-                                let b_vals: u16x32 = u8x32::from_slice_aligned_unchecked(slice::from_raw_parts(b, 32)).cast();
-
-                                let a_comp = a_vals * a_fade;
-                                let b_comp = b_vals * b_fade;
-
-                                let crossfaded: u8x32 = ((a_comp + b_comp) / div).cast();
-
-                                crossfaded.write_to_slice_aligned_unchecked(slice::from_raw_parts_mut(out, 32));
-
-                                a = a.add(32);
-                                b = b.add(32);
-                                out = out.add(32);
-                            }
-                        }
                     }
                 }
             }
@@ -241,9 +227,11 @@ impl ModuleT for VideoMixer {
         *out = Some(engine::VideoFrame {
             data: video::Frame {
                 decoded: output_frame,
-                duration_hint: MediaDuration::new(1, TICKS_PER_SECOND as i64), // TODO this assumes 1 output frame per tick
+                duration_hint: MediaDuration::new(1, engine::DEFAULT_FPS as i64),
+                key_frame: true,
+                passthrough: None,
             },
-            tick_offset: MediaDuration::new(0, 1),
+            tick_offset: interval,
         });
 
         None
@@ -256,6 +244,55 @@ impl ModuleT for VideoMixer {
     fn outputs(&self) -> &[Terminal] {
         &self.outputs
     }
+
+    fn frame_rate(&self) -> Option<u32> {
+        Some(engine::DEFAULT_FPS)
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.channels.iter()
+            .filter_map(|channel| channel.stored.as_ref())
+            .map(|stored| stored.frame.buffer_size())
+            .sum()
+    }
+}
+
+impl VideoMixer {
+    fn new(params: VideoMixerParams) -> Self {
+        let params = clamp_channels(params);
+
+        VideoMixer {
+            live_a: params.a,
+            live_b: params.b,
+            inputs: channel_inputs(params.channels),
+            outputs: vec![
+                LineType::Video.labeled("Output"),
+                LineType::Video.labeled("A"),
+                LineType::Video.labeled("B"),
+            ],
+            channels: (0..params.channels).map(|_| Channel::default()).collect(),
+            params,
+        }
+    }
+}
+
+// at least one channel and no more than the maximum, with A and B cleared if
+// their channel is gone
+fn clamp_channels(params: VideoMixerParams) -> VideoMixerParams {
+    let channels = params.channels.max(1).min(VIDEO_MIXER_MAX_CHANNELS);
+
+    VideoMixerParams {
+        a: params.a.filter(|a| *a < channels),
+        b: params.b.filter(|b| *b < channels),
+        channels,
+        ..params
+    }
+}
+
+// labelled by number, so connections follow their channel as the mixer is
+// resized
+fn channel_inputs(channels: usize) -> Vec<Terminal> {
+    (0..channels).map(|i| LineType::Video.labeled(&(i + 1).to_string())).collect()
 }
 
 impl Channel {
@@ -273,6 +310,56 @@ impl Channel {
     }
 }
 
+// crossfades `len` bytes of two lines into `out`, with `fade` of 255 giving
+// all of `a` and 0 all of `b`. all pointers must be 32 byte aligned and
+// readable/writable up to `len` rounded up to a multiple of 32
+#[inline(never)]
+pub unsafe fn fade_line(mut out: *mut u8, mut a: *const u8, mut b: *const u8, len: usize, fade: u8) {
+    use std::slice;
+    use packed_simd::{u8x32, u16x32, Cast};
+
+    let a_fade = u16x32::splat(fade as u16);
+    let b_fade = u16x32::splat((255 - fade) as u16);
+    let div = u16x32::splat(255);
+
+    let end = out.add(len);
+    while out < end {
+        let a_vals: u16x32 = u8x32::from_slice_aligned_unchecked(slice::from_raw_parts(a, 32)).cast();
+        let b_vals: u16x32 = u8x32::from_slice_aligned_unchecked(slice::from_raw_parts(b, 32)).cast();
+
+        let a_comp = a_vals * a_fade;
+        let b_comp = b_vals * b_fade;
+
+        let crossfaded: u8x32 = ((a_comp + b_comp) / div).cast();
+
+        crossfaded.write_to_slice_aligned_unchecked(slice::from_raw_parts_mut(out, 32));
+
+        a = a.add(32);
+        b = b.add(32);
+        out = out.add(32);
+    }
+}
+
+// the channel to use after a tick with `target` selected. a waiting cut only
+// happens on a key frame from the target channel
+fn cut(live: Option<usize>, target: Option<usize>, inputs: &[InputRef], wait: bool) -> Option<usize> {
+    if live == target || !wait {
+        return target;
+    }
+
+    let key_frame = target
+        .and_then(|idx| inputs.get(idx))
+        .and_then(|input| input.expect_video())
+        .map(|video| video.data.key_frame);
+
+    match (target, key_frame) {
+        // cutting away to nothing doesn't start a new stream
+        (None, _) => None,
+        (Some(_), Some(true)) => target,
+        (Some(_), _) => live,
+    }
+}
+
 fn unify_picture_settings(a: PictureSettings, b: PictureSettings) -> PictureSettings {
     use std::cmp;
 
@@ -295,3 +382,115 @@ fn unify_picture_settings(a: PictureSettings, b: PictureSettings) -> PictureSett
         pixel_format: pixfmt,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mixlab_protocol::VIDEO_MIXER_CHANNELS;
+    use crate::video::golden;
+
+    // output of one tick of a mixer with a test card on each of the given
+    // channels
+    fn mix(params: VideoMixerParams, cards: &[(usize, usize)]) -> AvFrame<Video> {
+        let picture = PictureSettings::yuv420p(128, 72);
+        let mut mixer = VideoMixer::new(params);
+
+        let frames = (0..VIDEO_MIXER_CHANNELS)
+            .map(|channel| {
+                cards.iter()
+                    .find(|(card_channel, _)| *card_channel == channel)
+                    .map(|(_, seed)| golden::video_frame(golden::test_card(&picture, *seed)))
+            })
+            .collect::<Vec<_>>();
+
+        let inputs = frames.iter()
+            .map(|frame| InputRef::Video(frame.as_ref()))
+            .collect::<Vec<_>>();
+
+        let mut outputs = (None, None, None);
+
+        mixer.run_tick(0, &inputs, &mut [
+            OutputRef::Video(&mut outputs.0),
+            OutputRef::Video(&mut outputs.1),
+            OutputRef::Video(&mut outputs.2),
+        ]);
+
+        outputs.0.expect("mixer output").data.decoded
+    }
+
+    fn crossfade(fader: f64) -> AvFrame<Video> {
+        mix(VideoMixerParams { a: Some(0), b: Some(1), fader, cut_on_key_frame: false, channels: VIDEO_MIXER_CHANNELS }, &[(0, 1), (1, 2)])
+    }
+
+    #[test]
+    fn fader_at_a_passes_a() {
+        golden::assert_matches("video_mixer_fader_a", &crossfade(1.0), 1);
+    }
+
+    #[test]
+    fn fader_at_b_passes_b() {
+        golden::assert_matches("video_mixer_fader_b", &crossfade(0.0), 1);
+    }
+
+    #[test]
+    fn fader_midpoint_blends() {
+        golden::assert_matches("video_mixer_fader_mid", &crossfade(0.5), 1);
+    }
+
+    #[test]
+    fn missing_channel_fades_to_black() {
+        let frame = mix(VideoMixerParams { a: Some(2), b: None, fader: 0.75, cut_on_key_frame: false, channels: VIDEO_MIXER_CHANNELS }, &[(2, 3)]);
+        golden::assert_matches("video_mixer_fade_to_black", &frame, 1);
+    }
+
+    #[test]
+    fn waiting_cut_holds_until_key_frame() {
+        let params = VideoMixerParams { a: Some(0), b: None, fader: 1.0, cut_on_key_frame: true, channels: VIDEO_MIXER_CHANNELS };
+        let mut mixer = VideoMixer::new(params.clone());
+        mixer.update(VideoMixerParams { a: Some(1), ..params });
+
+        // channels are told apart by picture width
+        let frame = |width, key_frame| {
+            let mut frame = golden::video_frame(AvFrame::blank(&PictureSettings::yuv420p(width, 72)));
+            frame.data.key_frame = key_frame;
+            Some(frame)
+        };
+
+        let mut tick = |channel_1_key_frame| {
+            let frames = vec![frame(128, false), frame(64, channel_1_key_frame), None, None];
+
+            let inputs = frames.iter()
+                .map(|frame| InputRef::Video(frame.as_ref()))
+                .collect::<Vec<_>>();
+
+            let mut outputs = (None, None, None);
+
+            mixer.run_tick(0, &inputs, &mut [
+                OutputRef::Video(&mut outputs.0),
+                OutputRef::Video(&mut outputs.1),
+                OutputRef::Video(&mut outputs.2),
+            ]);
+
+            outputs.1.expect("channel a output").data.decoded.picture_settings().width
+        };
+
+        assert_eq!(128, tick(false));
+        assert_eq!(64, tick(true));
+        assert_eq!(64, tick(false));
+    }
+
+    #[test]
+    fn shrinking_clears_channels_which_are_gone() {
+        let params = VideoMixerParams { a: Some(3), b: Some(1), ..VideoMixerParams::default() };
+        let mut mixer = VideoMixer::new(params.clone());
+
+        mixer.update(VideoMixerParams { channels: 2, ..params });
+        assert_eq!(mixer.inputs().len(), 2);
+        assert_eq!(mixer.channels.len(), 2);
+        assert_eq!((mixer.params.a, mixer.params.b), (None, Some(1)));
+        assert_eq!(mixer.live_a, None);
+
+        mixer.update(VideoMixerParams { channels: 100, ..mixer.params() });
+        assert_eq!(mixer.inputs().len(), VIDEO_MIXER_MAX_CHANNELS);
+    }
+}
@@ -0,0 +1,101 @@
+use mixlab_protocol::{WaveshaperParams, WaveshaperMode, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef, Sample, ModuleHost, DynModuleHost};
+use crate::module::{self, ModuleT};
+
+const MIN_DRIVE: f64 = 0.0;
+const MAX_DRIVE: f64 = 20.0;
+
+#[derive(Debug)]
+pub struct Waveshaper {
+    params: WaveshaperParams,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for Waveshaper {
+    type Params = WaveshaperParams;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = Waveshaper {
+            params,
+            inputs: vec![
+                LineType::Mono.labeled("Input"),
+                LineType::Mono.labeled("Carrier"),
+            ],
+            outputs: vec![LineType::Mono.unlabeled()],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, params: Self::Params) -> Option<Self::Indication> {
+        self.params = params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_mono();
+        let carrier = inputs[1].expect_mono();
+        let output = outputs[0].expect_mono();
+
+        let WaveshaperParams { mode, drive } = self.params;
+        let drive = drive.max(MIN_DRIVE).min(MAX_DRIVE);
+
+        for (i, sample) in input.iter().enumerate() {
+            output[i] = shape(mode, *sample as f64 * drive, carrier[i] as f64) as Sample;
+        }
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+
+    fn host(host: ModuleHost<Self>) -> DynModuleHost {
+        module::parallel(host)
+    }
+}
+
+fn shape(mode: WaveshaperMode, x: f64, carrier: f64) -> f64 {
+    match mode {
+        WaveshaperMode::RingMod => x * carrier,
+        WaveshaperMode::Fold => fold(x),
+        WaveshaperMode::SoftClip => x.tanh(),
+    }
+}
+
+// reflects off +1 and -1 as many times as it takes to land between them,
+// a triangle wave of period 4 in x
+fn fold(x: f64) -> f64 {
+    ((x - 1.0).rem_euclid(4.0) - 2.0).abs() - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fold_reflects_at_full_scale() {
+        for &(x, y) in &[(0.0, 0.0), (0.5, 0.5), (1.0, 1.0), (1.5, 0.5), (3.0, -1.0), (-1.5, -0.5), (5.0, 1.0)] {
+            assert!((fold(x) - y).abs() < 1e-9, "fold({}) = {}", x, fold(x));
+        }
+    }
+
+    #[test]
+    fn ring_mod_multiplies() {
+        assert_eq!(0.25, shape(WaveshaperMode::RingMod, 0.5, 0.5));
+        assert_eq!(0.0, shape(WaveshaperMode::RingMod, 0.5, 0.0));
+    }
+}
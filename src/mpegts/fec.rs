@@ -0,0 +1,114 @@
+// SMPTE 2022-1 style forward error correction, as used by RIST. Each FEC
+// packet carries the XOR of the payloads of a group of media packets, which
+// is enough to rebuild any single packet missing from that group.
+
+// RTP header (12 bytes) followed by the FEC header (16 bytes)
+const RTP_HEADER_LEN: usize = 12;
+const FEC_HEADER_LEN: usize = 16;
+
+#[derive(Debug)]
+pub struct FecPacket {
+    pub sn_base: u16,
+    pub length_recovery: u16,
+    pub offset: u8,
+    pub na: u8,
+    pub payload: Vec<u8>,
+}
+
+impl FecPacket {
+    pub fn parse(datagram: &[u8]) -> Option<FecPacket> {
+        if datagram.len() < RTP_HEADER_LEN + FEC_HEADER_LEN {
+            return None;
+        }
+
+        // RTP version must be 2
+        if datagram[0] & 0xc0 != 0x80 {
+            return None;
+        }
+
+        let header_len = RTP_HEADER_LEN + 4 * (datagram[0] & 0x0f) as usize;
+
+        if datagram.len() < header_len + FEC_HEADER_LEN {
+            return None;
+        }
+
+        let fec = &datagram[header_len..];
+
+        let packet = FecPacket {
+            sn_base: u16::from_be_bytes([fec[0], fec[1]]),
+            length_recovery: u16::from_be_bytes([fec[2], fec[3]]),
+            offset: fec[13],
+            na: fec[14],
+            payload: fec[FEC_HEADER_LEN..].to_vec(),
+        };
+
+        if packet.offset == 0 || packet.na == 0 {
+            return None;
+        }
+
+        Some(packet)
+    }
+
+    /// Sequence numbers of the media packets protected by this FEC packet.
+    pub fn group(&self) -> impl Iterator<Item = u16> {
+        let sn_base = self.sn_base;
+        let offset = self.offset as u16;
+
+        (0..self.na as u16).map(move |i| sn_base.wrapping_add(i * offset))
+    }
+
+    /// Rebuilds the single missing payload of a group from all the others.
+    pub fn recover<'a>(&self, others: impl Iterator<Item = &'a [u8]>) -> Vec<u8> {
+        let mut recovered = self.payload.clone();
+        let mut length = self.length_recovery;
+
+        for payload in others {
+            if payload.len() > recovered.len() {
+                recovered.resize(payload.len(), 0);
+            }
+
+            for (out, byte) in recovered.iter_mut().zip(payload) {
+                *out ^= byte;
+            }
+
+            length ^= payload.len() as u16;
+        }
+
+        recovered.truncate(length as usize);
+        recovered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_missing_payload() {
+        let payloads: Vec<Vec<u8>> = vec![
+            vec![0x47, 0x01, 0x02, 0x03],
+            vec![0x47, 0x10, 0x20, 0x30],
+            vec![0x47, 0xaa, 0xbb, 0xcc],
+        ];
+
+        let mut xor = vec![0u8; 4];
+        for payload in &payloads {
+            for (out, byte) in xor.iter_mut().zip(payload) {
+                *out ^= byte;
+            }
+        }
+
+        let fec = FecPacket {
+            sn_base: 100,
+            length_recovery: 4 ^ 4 ^ 4,
+            offset: 1,
+            na: 3,
+            payload: xor,
+        };
+
+        assert_eq!(vec![100, 101, 102], fec.group().collect::<Vec<_>>());
+
+        let recovered = fec.recover(vec![&payloads[0][..], &payloads[2][..]].into_iter());
+        assert_eq!(payloads[1], recovered);
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use mixlab_protocol::StreamLinkStats;
+
+use crate::mpegts::fec::FecPacket;
+use crate::reorder::ReorderBuffer;
+
+// how long a missing packet is waited on for retransmission or FEC recovery
+// before giving up on it. this is the latency the link adds to the stream
+const RECOVERY_LATENCY: Duration = Duration::from_millis(200);
+
+// the socket is polled at this interval even when no packets are arriving so
+// that lost packets are given up on in a timely fashion
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// reads report end of stream after this long without packets, so the
+// receive thread notices when the StreamInput listening on it goes away
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// released payloads are kept around for this many packets so that FEC
+// packets arriving after their group can still recover from them
+const HISTORY_PACKETS: u64 = 1024;
+
+// a jump in sequence number larger than this is treated as the sender
+// restarting rather than as loss
+const MAX_GAP: u64 = 1024;
+
+const MAX_PENDING_FEC: usize = 64;
+
+const RTP_HEADER_LEN: usize = 12;
+const RTP_PAYLOAD_TYPE_MP2T: u8 = 33;
+
+/// A UDP link carrying a transport stream, either as raw TS datagrams or as
+/// RTP. For RTP, a pragmatic subset of RIST is supported: lost packets are
+/// requested again with RTCP NACKs on port+1 and rebuilt from SMPTE 2022-1
+/// FEC on port+2, when the sender provides it.
+pub struct Link {
+    media: UdpSocket,
+    fec: Option<UdpSocket>,
+    rtcp: Option<UdpSocket>,
+    rtcp_peer: Option<SocketAddr>,
+    media_ssrc: u32,
+    buff: Vec<u8>,
+    recovery: ReorderBuffer,
+    pending_fec: VecDeque<FecPacket>,
+    ready: VecDeque<Vec<u8>>,
+    current: Vec<u8>,
+    pos: usize,
+    last_activity: Instant,
+    stats: StreamLinkStats,
+    shared_stats: Arc<Mutex<Option<StreamLinkStats>>>,
+    is_rtp: bool,
+}
+
+impl Link {
+    pub fn bind(addr: SocketAddr) -> io::Result<Link> {
+        let media = bind(addr)?;
+        media.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        // FEC and RTCP ports are optional extras, the link works without them
+        let fec = bind_nonblocking(addr, 2);
+        let rtcp = bind_nonblocking(addr, 1);
+
+        Ok(Link {
+            media,
+            fec,
+            rtcp,
+            rtcp_peer: None,
+            media_ssrc: 0,
+            buff: vec![0u8; 65536],
+            recovery: ReorderBuffer::new("mpegts", 16, MAX_GAP).with_history(HISTORY_PACKETS),
+            pending_fec: VecDeque::new(),
+            ready: VecDeque::new(),
+            current: Vec::new(),
+            pos: 0,
+            last_activity: Instant::now(),
+            stats: StreamLinkStats::default(),
+            shared_stats: Arc::new(Mutex::new(None)),
+            is_rtp: false,
+        })
+    }
+
+    /// Loss and recovery stats for the link, which remain `None` unless the
+    /// sender is using RTP. Shared so they can be read while the link is
+    /// borrowed by a reader.
+    pub fn stats(&self) -> Arc<Mutex<Option<StreamLinkStats>>> {
+        self.shared_stats.clone()
+    }
+
+    fn poll(&mut self) -> io::Result<()> {
+        match self.media.recv_from(&mut self.buff) {
+            Ok((len, from)) => {
+                self.last_activity = Instant::now();
+                self.receive_media(len, from);
+            }
+            Err(e) if is_timeout(&e) => {}
+            Err(e) => { return Err(e); }
+        }
+
+        self.poll_rtcp();
+        self.poll_fec();
+
+        self.recovery.release(Instant::now(), RECOVERY_LATENCY, &mut self.ready, &mut self.stats);
+
+        if self.is_rtp {
+            *self.shared_stats.lock().expect("link stats lock") = Some(self.stats.clone());
+        }
+
+        Ok(())
+    }
+
+    fn receive_media(&mut self, len: usize, from: SocketAddr) {
+        let datagram = &self.buff[0..len];
+
+        let rtp = match parse_rtp(datagram) {
+            Some(rtp) => rtp,
+            None => {
+                // raw TS over UDP, no recovery possible
+                self.ready.push_back(datagram.to_vec());
+                return;
+            }
+        };
+
+        self.is_rtp = true;
+        self.media_ssrc = rtp.ssrc;
+
+        // until the sender sends us RTCP, assume it listens on the
+        // conventional port
+        if self.rtcp_peer.is_none() {
+            self.rtcp_peer = Some(SocketAddr::new(from.ip(), from.port().wrapping_add(1)));
+        }
+
+        let payload = datagram[rtp.header_len..].to_vec();
+        let seq = self.recovery.extend(rtp.seq as u64);
+
+        self.stats.received += 1;
+
+        let missing = self.recovery.insert(seq, payload, Instant::now(), &mut self.stats, self.rtcp.is_some());
+
+        if !missing.is_empty() {
+            self.send_nack(&missing);
+        }
+    }
+
+    fn poll_rtcp(&mut self) {
+        let rtcp = match &self.rtcp {
+            Some(rtcp) => rtcp,
+            None => return,
+        };
+
+        // we don't need anything from the sender's reports except for the
+        // address they come from
+        while let Ok((_, from)) = rtcp.recv_from(&mut self.buff) {
+            self.rtcp_peer = Some(from);
+        }
+    }
+
+    fn poll_fec(&mut self) {
+        if let Some(fec) = &self.fec {
+            while let Ok(len) = fec.recv(&mut self.buff) {
+                if let Some(packet) = FecPacket::parse(&self.buff[0..len]) {
+                    self.pending_fec.push_back(packet);
+                }
+            }
+        }
+
+        while self.pending_fec.len() > MAX_PENDING_FEC {
+            self.pending_fec.pop_front();
+        }
+
+        let recovery = &mut self.recovery;
+        let stats = &mut self.stats;
+
+        // keep FEC packets around until their group is complete, they may
+        // arrive before the media packets they protect
+        self.pending_fec.retain(|fec| !recover_with(recovery, fec, stats));
+    }
+
+    // RFC 4585 generic NACK, missing sequence numbers must be sorted
+    fn send_nack(&self, missing: &[u64]) {
+        let (rtcp, peer) = match (&self.rtcp, self.rtcp_peer) {
+            (Some(rtcp), Some(peer)) => (rtcp, peer),
+            _ => return,
+        };
+
+        let fci = nack_fci(missing);
+
+        let mut packet = Vec::with_capacity(12 + fci.len());
+        // V=2, P=0, FMT=1 (generic NACK), PT=205 (transport layer feedback)
+        packet.push(0x81);
+        packet.push(205);
+        // length in 32 bit words minus one
+        packet.extend_from_slice(&((2 + fci.len() / 4) as u16).to_be_bytes());
+        // sender ssrc, we have none of our own
+        packet.extend_from_slice(&0u32.to_be_bytes());
+        packet.extend_from_slice(&self.media_ssrc.to_be_bytes());
+        packet.extend_from_slice(&fci);
+
+        if let Err(e) = rtcp.send_to(&packet, peer) {
+            eprintln!("mpegts: could not send nack to {}: {:?}", peer, e);
+        }
+    }
+}
+
+// each FCI entry is a packet id along with a bitmask of which of the 16
+// following packets are also missing
+fn nack_fci(missing: &[u64]) -> Vec<u8> {
+    let mut fci = Vec::new();
+    let mut iter = missing.iter().peekable();
+
+    while let Some(&pid) = iter.next() {
+        let mut blp = 0u16;
+
+        while let Some(&&seq) = iter.peek() {
+            let distance = seq - pid;
+
+            if distance > 16 {
+                break;
+            }
+
+            blp |= 1 << (distance - 1);
+            iter.next();
+        }
+
+        fci.extend_from_slice(&(pid as u16).to_be_bytes());
+        fci.extend_from_slice(&blp.to_be_bytes());
+    }
+
+    fci
+}
+
+impl io::Read for Link {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.pos < self.current.len() {
+                let len = std::cmp::min(out.len(), self.current.len() - self.pos);
+                out[0..len].copy_from_slice(&self.current[self.pos..(self.pos + len)]);
+                self.pos += len;
+                return Ok(len);
+            }
+
+            if let Some(payload) = self.ready.pop_front() {
+                self.current = payload;
+                self.pos = 0;
+                continue;
+            }
+
+            if self.last_activity.elapsed() >= IDLE_TIMEOUT {
+                self.last_activity = Instant::now();
+                return Ok(0);
+            }
+
+            self.poll()?;
+        }
+    }
+}
+
+// returns true once the FEC packet has nothing more to offer
+fn recover_with(recovery: &mut ReorderBuffer, fec: &FecPacket, stats: &mut StreamLinkStats) -> bool {
+    let group = fec.group().map(|seq| recovery.extend(seq as u64)).collect::<Vec<_>>();
+
+    let next = match recovery.next() {
+        Some(next) => next,
+        None => return false,
+    };
+
+    if group.iter().all(|seq| *seq < next) && group.iter().any(|seq| recovery.get(*seq).is_none()) {
+        // group has already been released and fallen out of history
+        return true;
+    }
+
+    let missing = group.iter()
+        .filter(|seq| recovery.is_missing(**seq))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let available = group.iter()
+        .filter_map(|seq| recovery.get(*seq))
+        .count();
+
+    match missing.as_slice() {
+        [] if available == group.len() => true,
+        [seq] if available == group.len() - 1 => {
+            let others = group.iter()
+                .filter_map(|seq| recovery.get(*seq));
+
+            let recovered = fec.recover(others);
+
+            recovery.recover(*seq, recovered);
+            stats.recovered_fec += 1;
+            true
+        }
+        // more than one packet missing from group, or group not yet
+        // fully received
+        _ => false,
+    }
+}
+
+struct RtpHeader {
+    seq: u16,
+    ssrc: u32,
+    header_len: usize,
+}
+
+fn parse_rtp(datagram: &[u8]) -> Option<RtpHeader> {
+    // raw TS datagrams begin with the TS sync byte
+    if datagram.len() < RTP_HEADER_LEN || datagram[0] == 0x47 {
+        return None;
+    }
+
+    // RTP version must be 2, and payload type MP2T
+    if datagram[0] & 0xc0 != 0x80 || datagram[1] & 0x7f != RTP_PAYLOAD_TYPE_MP2T {
+        return None;
+    }
+
+    let csrc_count = (datagram[0] & 0x0f) as usize;
+    let mut header_len = RTP_HEADER_LEN + 4 * csrc_count;
+
+    // header extension present
+    if datagram[0] & 0x10 != 0 {
+        if datagram.len() < header_len + 4 {
+            return None;
+        }
+
+        let ext_words = u16::from_be_bytes([datagram[header_len + 2], datagram[header_len + 3]]) as usize;
+        header_len += 4 + 4 * ext_words;
+    }
+
+    if datagram.len() < header_len {
+        return None;
+    }
+
+    Some(RtpHeader {
+        seq: u16::from_be_bytes([datagram[2], datagram[3]]),
+        ssrc: u32::from_be_bytes([datagram[8], datagram[9], datagram[10], datagram[11]]),
+        header_len,
+    })
+}
+
+fn bind(addr: SocketAddr) -> io::Result<UdpSocket> {
+    match addr.ip() {
+        IpAddr::V4(group) if group.is_multicast() => {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, addr.port()))?;
+            socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)?;
+            Ok(socket)
+        }
+        IpAddr::V6(group) if group.is_multicast() => {
+            let socket = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, addr.port()))?;
+            socket.join_multicast_v6(&group, 0)?;
+            Ok(socket)
+        }
+        _ => UdpSocket::bind(addr),
+    }
+}
+
+fn bind_nonblocking(addr: SocketAddr, port_offset: u16) -> Option<UdpSocket> {
+    let addr = SocketAddr::new(addr.ip(), addr.port().checked_add(port_offset)?);
+
+    let socket = bind(addr)
+        .and_then(|socket| socket.set_nonblocking(true).map(|()| socket));
+
+    match socket {
+        Ok(socket) => Some(socket),
+        Err(e) => {
+            eprintln!("mpegts: could not bind {}, continuing without it: {:?}", addr, e);
+            None
+        }
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nack_fci_packs_following_losses_into_the_bitmask() {
+        // 11 and 13 ride along with 10, 27 is 17 on so starts another entry
+        assert_eq!(vec![0, 10, 0, 0b101, 0, 27, 0, 0], nack_fci(&[10, 11, 13, 27]));
+        assert_eq!(vec![0, 1, 0x80, 0], nack_fci(&[1, 17]));
+    }
+
+    #[test]
+    fn nack_fci_uses_the_16_bit_sequence_number() {
+        let missing = [(3 << 16) + 0xffff, (4 << 16) + 1];
+        assert_eq!(vec![0xff, 0xff, 0, 0b10], nack_fci(&missing));
+    }
+}
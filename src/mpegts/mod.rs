@@ -0,0 +1,305 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use derive_more::From;
+use mpeg2ts::pes::{PesPacket, PesPacketReader, ReadPesPacket};
+use mpeg2ts::ts::TsPacketReader;
+
+use mixlab_codec::ffmpeg::codec::{self, CodecBuilder, Decode, RecvFrameError};
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::{AvError, AvPacketRef, PacketInfo};
+use mixlab_protocol::StreamLinkStats;
+use mixlab_util::time::{MediaDuration, MediaTime, TimeBase};
+
+use crate::engine::SAMPLE_RATE;
+use crate::listen::srt::SrtReceiver;
+use crate::source::{Registry, SourceRecv, SourceSend};
+use crate::video;
+
+mod fec;
+mod link;
+pub mod publish;
+pub mod write;
+
+use link::Link;
+
+// all timestamps in a transport stream are in units of a 90kHz clock
+const TIME_BASE: i32 = 90_000;
+
+// how often link loss and recovery stats are published to the source info
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(From, Debug)]
+pub enum MpegTsError {
+    Io(io::Error),
+    BadAddress,
+    Demux(mpeg2ts::Error),
+    AacCodec(fdk_aac::dec::DecoderError),
+    CodecOpen(codec::OpenError),
+    AvCodec(AvError),
+    SourceSend,
+}
+
+/// Listens for a transport stream on a UDP address, which may be a
+/// multicast group. `addr` is of the form `host:port`, optionally prefixed
+/// with `udp://`.
+pub fn listen(addr: &str) -> Result<SourceRecv, MpegTsError> {
+    let socket_addr: SocketAddr = addr.trim_start_matches("udp://").parse()
+        .map_err(|_| MpegTsError::BadAddress)?;
+
+    let link = Link::bind(socket_addr)?;
+    let stats = link.stats();
+
+    let (recv, send) = channel(addr);
+    spawn_receive_thread(link, stats, send);
+
+    Ok(recv)
+}
+
+/// Listens for SRT callers sending a transport stream on a UDP address of
+/// the form `host:port`, optionally prefixed with `srt://`. Callers must send
+/// the stream key as their stream id, when one is set.
+pub fn listen_srt(addr: &str, stream_key: Option<String>) -> Result<SourceRecv, MpegTsError> {
+    let socket_addr: SocketAddr = addr.trim_start_matches("srt://").parse()
+        .map_err(|_| MpegTsError::BadAddress)?;
+
+    let (recv, send) = channel(addr);
+    recv.set_stream_key(stream_key);
+
+    let link = SrtReceiver::bind(socket_addr, Box::new(send.authorizer()))?;
+    let stats = link.stats();
+
+    spawn_receive_thread(link, stats, send);
+
+    Ok(recv)
+}
+
+fn channel(addr: &str) -> (SourceRecv, SourceSend) {
+    // each UDP listener gets a registry of its own, there is only ever one
+    // sender for a bound socket
+    let registry = Registry::new();
+    let recv = registry.listen(addr);
+    let send = registry.connect(addr, None)
+        .expect("connect to newly created udp source");
+
+    (recv, send)
+}
+
+fn spawn_receive_thread(link: impl io::Read + Send + 'static, stats: Arc<Mutex<Option<StreamLinkStats>>>, send: SourceSend) {
+    thread::spawn(move || {
+        match run_receive_thread(link, stats, send) {
+            Ok(()) => {}
+            Err(e) => {
+                eprintln!("mpegts: receive thread stopped: {:?}", e);
+            }
+        }
+    });
+}
+
+struct ReceiveContext {
+    source: SourceSend,
+    audio_codec: fdk_aac::dec::Decoder,
+    video_codec: Option<Decode<Video>>,
+    last_video_timestamp: Option<MediaTime>,
+    link_stats: Arc<Mutex<Option<StreamLinkStats>>>,
+    stats_published: Instant,
+}
+
+impl ReceiveContext {
+    fn publish_stats(&mut self) {
+        if self.stats_published.elapsed() < STATS_INTERVAL {
+            return;
+        }
+
+        self.stats_published = Instant::now();
+
+        let stats = self.link_stats.lock().expect("link stats lock").clone();
+        self.source.set_info(|info| info.link = stats);
+    }
+}
+
+fn run_receive_thread(mut link: impl io::Read, link_stats: Arc<Mutex<Option<StreamLinkStats>>>, source: SourceSend) -> Result<(), MpegTsError> {
+    let mut audio_codec = fdk_aac::dec::Decoder::new(fdk_aac::dec::Transport::Adts);
+
+    // enable automatic stereo mix-down:
+    audio_codec.set_min_output_channels(2)?;
+    audio_codec.set_max_output_channels(2)?;
+
+    let mut ctx = ReceiveContext {
+        source,
+        audio_codec,
+        video_codec: None,
+        last_video_timestamp: None,
+        link_stats,
+        stats_published: Instant::now(),
+    };
+
+    while ctx.source.connected() {
+        // the PES reader treats an idle link as end of stream, so make a
+        // fresh one each time. any partially received PES packet is lost
+        // but the link has been silent for a whole second anyway
+        let mut reader = PesPacketReader::new(TsPacketReader::new(&mut link));
+
+        while let Some(pes) = reader.read_pes_packet()? {
+            if pes.header.stream_id.is_audio() {
+                receive_audio(&mut ctx, pes)?;
+            } else if pes.header.stream_id.is_video() {
+                receive_video(&mut ctx, pes)?;
+            }
+
+            ctx.publish_stats();
+        }
+
+        ctx.publish_stats();
+    }
+
+    Ok(())
+}
+
+fn receive_audio(ctx: &mut ReceiveContext, pes: PesPacket<Vec<u8>>) -> Result<(), MpegTsError> {
+    // TODO - handle 33 bit timestamp rollover
+    let mut timestamp = match pes.header.pts {
+        Some(pts) => MediaTime::new(pts.as_u64() as i64, TIME_BASE.into()),
+        None => {
+            eprintln!("mpegts: audio PES packet without timestamp, dropping");
+            return Ok(());
+        }
+    };
+
+    ctx.source.set_info(|info| info.audio_codec = Some("AAC".to_owned()));
+
+    // a single PES packet may carry several ADTS frames, feed them to the
+    // decoder one at a time
+    for frame in AdtsFrames(&pes.data) {
+        let bytes_consumed = ctx.audio_codec.fill(frame)?;
+
+        if bytes_consumed < frame.len() {
+            eprintln!("mpegts: codec did not read all bytes from audio frame");
+            return Ok(());
+        }
+
+        // AAC standard defines a frame to be 1024 samples per channel:
+        let mut pcm_buffer = vec![0; 2048];
+
+        match ctx.audio_codec.decode_frame(&mut pcm_buffer) {
+            Ok(()) => {
+                let sample_rate = ctx.audio_codec.stream_info().sampleRate;
+
+                if sample_rate as usize != SAMPLE_RATE {
+                    eprintln!("mpegts: expected audio sample rate of {}, got {}, dropping", SAMPLE_RATE, sample_rate);
+                    return Ok(());
+                }
+
+                pcm_buffer.truncate(ctx.audio_codec.decoded_frame_size());
+
+                let frame_time = MediaDuration::new(pcm_buffer.len() as i64 / 2, sample_rate as i64);
+
+                ctx.source.write_audio(timestamp, pcm_buffer)
+                    .map_err(|()| MpegTsError::SourceSend)?;
+
+                timestamp += frame_time;
+            }
+            Err(e) => {
+                eprintln!("mpegts: audio codec frame decode error: {:?}", e);
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn receive_video(ctx: &mut ReceiveContext, pes: PesPacket<Vec<u8>>) -> Result<(), MpegTsError> {
+    let pts = match pes.header.pts {
+        Some(pts) => pts.as_u64() as i64,
+        None => {
+            eprintln!("mpegts: video PES packet without timestamp, dropping");
+            return Ok(());
+        }
+    };
+
+    let dts = pes.header.dts.map(|dts| dts.as_u64() as i64).unwrap_or(pts);
+
+    if ctx.video_codec.is_none() {
+        // h264 in transport streams is annex-b with parameter sets in band,
+        // so no extradata is needed to open the decoder
+        ctx.video_codec = Some(CodecBuilder::h264(TimeBase::new(1, TIME_BASE)).open_decoder()?);
+        ctx.source.set_info(|info| info.video_codec = Some("H.264".to_owned()));
+    }
+
+    let codec = ctx.video_codec.as_mut().unwrap();
+
+    let av_packet = AvPacketRef::borrowed(PacketInfo {
+        dts,
+        pts,
+        data: &pes.data,
+    });
+
+    codec.send_packet(&av_packet)
+        .expect("avc::decode::send_packet in mpegts");
+
+    loop {
+        match codec.recv_frame() {
+            Ok(decoded) => {
+                let timestamp = MediaTime::new(decoded.presentation_timestamp(), TIME_BASE.into());
+
+                // transport streams carry no frame rate, estimate frame
+                // duration from the gap between successive frames
+                let duration_hint = ctx.last_video_timestamp
+                    .map(|last| timestamp - last)
+                    .filter(|duration| *duration > MediaDuration::zero())
+                    .unwrap_or(MediaDuration::new(1, 30));
+
+                ctx.last_video_timestamp = Some(timestamp);
+
+                let frame = video::Frame {
+                    key_frame: decoded.is_key_frame(),
+                    decoded,
+                    duration_hint,
+                    passthrough: None,
+                };
+
+                let _ = ctx.source.write_video(timestamp, frame);
+            }
+            Err(RecvFrameError::NeedMoreInput) => break,
+            Err(RecvFrameError::Eof) => panic!("EOF should never happen"),
+            Err(RecvFrameError::Codec(e)) => {
+                return Err(MpegTsError::AvCodec(e));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// splits a buffer of concatenated ADTS frames into individual frames using
+// the frame length field of each header
+struct AdtsFrames<'a>(&'a [u8]);
+
+impl<'a> Iterator for AdtsFrames<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let data = self.0;
+
+        // fixed header is 7 bytes, must begin with syncword
+        if data.len() < 7 || data[0] != 0xff || (data[1] & 0xf0) != 0xf0 {
+            return None;
+        }
+
+        let frame_len = ((data[3] as usize & 0x03) << 11)
+            | ((data[4] as usize) << 3)
+            | ((data[5] as usize) >> 5);
+
+        if frame_len < 7 || frame_len > data.len() {
+            return None;
+        }
+
+        let (frame, rest) = data.split_at(frame_len);
+        self.0 = rest;
+        Some(frame)
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+use std::io;
+
+use crate::listen::srt::{self, SrtSender};
+use crate::mpegts::write::TsWriter;
+use crate::video::encode::{AudioCtx, VideoCtx, StreamSegment};
+
+/// Publishes encoded segments as a transport stream to an SRT listener, in
+/// the way the RTMP client publishes them as FLV.
+pub struct SrtPublish {
+    sender: SrtSender,
+    writer: TsWriter,
+    // transport stream waiting to make up a whole SRT payload
+    pending: Vec<u8>,
+}
+
+impl fmt::Debug for SrtPublish {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SrtPublish")
+    }
+}
+
+impl SrtPublish {
+    /// Blocks until connected.
+    pub fn connect(url: &str, stream_id: &str) -> Result<Self, srt::ConnectError> {
+        let sender = SrtSender::connect(url, stream_id, srt::DEFAULT_LATENCY)?;
+
+        Ok(SrtPublish {
+            sender,
+            writer: TsWriter::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    pub fn publish_sequence_headers(&mut self, audio: &AudioCtx, video: &VideoCtx) -> io::Result<()> {
+        self.writer.set_sequence_headers(audio, video);
+        Ok(())
+    }
+
+    pub fn publish_segment(&mut self, segment: StreamSegment) -> io::Result<()> {
+        self.writer.write_segment(&mut self.pending, segment);
+
+        while self.pending.len() >= srt::PAYLOAD_LEN {
+            let payload = self.pending.drain(0..srt::PAYLOAD_LEN).collect::<Vec<_>>();
+            self.sender.send(&payload)?;
+        }
+
+        Ok(())
+    }
+}
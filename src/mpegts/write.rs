@@ -0,0 +1,119 @@
+use bytes::Bytes;
+
+use mixlab_codec::aac::{AudioDataTransportStream, AudioSpecificConfiguration};
+use mixlab_codec::avc::{bitstream, DecoderConfigurationRecord};
+use mixlab_mux::ts::{self, TsMux, Track};
+use mixlab_util::time::MediaTime;
+
+use crate::video::encode::{AudioCtx, VideoCtx, StreamSegment};
+
+// everything is pushed this far along the 90kHz clock, which leaves room
+// for the PCR to run behind the first frames
+const TIMESTAMP_OFFSET: i64 = ts::TIME_BASE;
+
+const START_CODE: &[u8] = &[0x00, 0x00, 0x00, 0x01];
+
+// access unit delimiter, which H.264 in transport streams begins each
+// frame with
+const ACCESS_UNIT_DELIMITER: &[u8] = &[0x00, 0x00, 0x00, 0x01, 0x09, 0xf0];
+
+/// Writes encoded segments as a transport stream, converting them from the
+/// raw AAC and length prefixed H.264 that RTMP carries. Audio must be
+/// encoded with the raw transport.
+pub struct TsWriter {
+    mux: TsMux,
+    asc: Option<AudioSpecificConfiguration>,
+    // length prefix size of NAL units from the encoder
+    nalu_size: usize,
+    // SPS and PPS in annex-b form, sent ahead of each key frame
+    parameter_sets: Vec<u8>,
+}
+
+impl TsWriter {
+    pub fn new() -> Self {
+        TsWriter {
+            mux: TsMux::new(),
+            asc: None,
+            nalu_size: 4,
+            parameter_sets: Vec::new(),
+        }
+    }
+
+    pub fn set_sequence_headers(&mut self, audio: &AudioCtx, video: &VideoCtx) {
+        // configuration buffer is ASC when raw transport is in use:
+        self.asc = AudioSpecificConfiguration::parse(audio.configuration_data())
+            .map_err(|e| eprintln!("mpegts: could not parse audio specific config: {:?}", e))
+            .ok();
+
+        self.set_decoder_configuration(video.decoder_configuration_record());
+    }
+
+    pub fn write_segment(&mut self, out: &mut Vec<u8>, segment: StreamSegment) {
+        match segment {
+            StreamSegment::Audio(audio) => {
+                let asc = match &self.asc {
+                    Some(asc) => asc,
+                    None => return,
+                };
+
+                let adts = AudioDataTransportStream::new(audio.frame, asc).into_bytes();
+                let timestamp = ts_timestamp(audio.decode_timestamp);
+
+                self.mux.write(out, Track::Audio, timestamp, timestamp, false, &adts);
+            }
+            StreamSegment::Video(video) => {
+                if let Some(mut header) = video.sequence_header {
+                    match DecoderConfigurationRecord::parse(&mut header) {
+                        Ok(dcr) => self.set_decoder_configuration(dcr),
+                        Err(e) => eprintln!("mpegts: could not parse decoder configuration: {:?}", e),
+                    }
+                }
+
+                let dts = ts_timestamp(video.decode_timestamp);
+                let pts = ts_timestamp(video.decode_timestamp + video.frame.composition_time);
+                let key_frame = video.frame.is_key_frame;
+
+                let mut data = ACCESS_UNIT_DELIMITER.to_vec();
+
+                if key_frame {
+                    data.extend_from_slice(&self.parameter_sets);
+                    self.mux.tables(out);
+                }
+
+                annex_b(&mut data, video.frame.data, self.nalu_size);
+
+                self.mux.write(out, Track::Video, pts, dts, key_frame, &data);
+            }
+        }
+    }
+
+    fn set_decoder_configuration(&mut self, dcr: DecoderConfigurationRecord) {
+        self.nalu_size = dcr.nalu_size as usize;
+        self.parameter_sets.clear();
+
+        for unit in dcr.sps.iter().chain(dcr.pps.iter()) {
+            self.parameter_sets.extend_from_slice(START_CODE);
+            unit.write_to(&mut self.parameter_sets);
+        }
+    }
+}
+
+// converts length prefixed NAL units, as the encoder gives them, to annex-b
+fn annex_b(out: &mut Vec<u8>, data: Bytes, nalu_size: usize) {
+    for unit in bitstream::read(data, nalu_size) {
+        match unit {
+            Ok(unit) => {
+                out.extend_from_slice(START_CODE);
+                unit.write_to(&mut *out);
+            }
+            Err(e) => {
+                eprintln!("mpegts: bad NAL unit in video frame: {:?}", e);
+                return;
+            }
+        }
+    }
+}
+
+fn ts_timestamp(time: MediaTime) -> u64 {
+    (time.round_to_base(ts::TIME_BASE) + TIMESTAMP_OFFSET).max(0) as u64
+}
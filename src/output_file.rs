@@ -0,0 +1,83 @@
+// Naming of the files that outputs record to.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+// fills in the placeholders of a filename template, and keeps the result to
+// a single path component
+pub fn expand_filename(template: &str, unix_time: u64) -> String {
+    let (year, month, day) = civil_date((unix_time / 86400) as i64);
+    let secs = unix_time % 86400;
+
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    // no colons, which some filesystems don't allow
+    let time = format!("{:02}-{:02}-{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+
+    let name = template
+        .replace("{date}", &date)
+        .replace("{time}", &time)
+        .replace("{timestamp}", &unix_time.to_string())
+        .replace(|c: char| c == '/' || c == '\\', "-");
+
+    match name.trim() {
+        "" | "." | ".." => "recording".to_owned(),
+        name => name.to_owned(),
+    }
+}
+
+// the days since the unix epoch as a proleptic gregorian (year, month, day),
+// after Howard Hinnant's civil_from_days
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // months counted from march, so the leap day falls at the end
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month as u32, day as u32)
+}
+
+// recordings are never overwritten, a name already taken gets a number
+pub fn unused_path(dir: &Path, name: &str, extension: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.{}", name, extension));
+    let mut number = 2;
+
+    while path.exists() {
+        path = dir.join(format!("{}-{}.{}", name, number, extension));
+        number += 1;
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_placeholders_in_utc() {
+        assert_eq!("program-1970-01-01-00-00-00", expand_filename("program-{date}-{time}", 0));
+        assert_eq!("show-2020-09-13-12-26-40", expand_filename("show-{date}-{time}", 1_600_000_000));
+        assert_eq!("1600000000", expand_filename("{timestamp}", 1_600_000_000));
+        // the day after a leap day
+        assert_eq!("2024-03-01", expand_filename("{date}", 1_709_251_200));
+    }
+
+    #[test]
+    fn keeps_to_one_path_component() {
+        assert_eq!("..-etc-passwd", expand_filename("../etc/passwd", 0));
+        assert_eq!("recording", expand_filename("..", 0));
+        assert_eq!("recording", expand_filename(" ", 0));
+    }
+}
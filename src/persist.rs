@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use serde::{Serialize, Deserialize};
 
-use mixlab_protocol::{ModuleId, ModuleParams, OutputId, WindowGeometry};
+use mixlab_protocol::{ModuleId, ModuleParams, OutputId, WindowGeometry, MidiMapping, OverrunPolicy, SurfaceControl, ViewerLink, Freeze, ChannelAdapter};
 
 use crate::util::Sequence;
 
@@ -10,6 +10,16 @@ use crate::util::Sequence;
 pub struct Workspace {
     pub module_seq: Sequence,
     pub modules: HashMap<ModuleId, Module>,
+    #[serde(default)]
+    pub midi_mappings: Vec<MidiMapping>,
+    #[serde(default)]
+    pub surface: Vec<SurfaceControl>,
+    #[serde(default)]
+    pub viewer_links: Vec<ViewerLink>,
+    #[serde(default)]
+    pub freezes: Vec<Freeze>,
+    #[serde(default)]
+    pub overrun_policy: OverrunPolicy,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -17,4 +27,13 @@ pub struct Module {
     pub params: ModuleParams,
     pub geometry: WindowGeometry,
     pub inputs: Vec<Option<OutputId>>,
+    #[serde(default)]
+    pub protected: bool,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub bypassed: bool,
+    // inputs connected through other than the default adapter, by index
+    #[serde(default)]
+    pub adapters: Vec<(usize, ChannelAdapter)>,
 }
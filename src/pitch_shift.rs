@@ -0,0 +1,294 @@
+use std::f64;
+use std::fmt::{self, Debug};
+use std::mem;
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use rustfft::{FFTplanner, FFT};
+
+// analysis window, about 46ms and 21.5Hz per bin at 44.1kHz
+const FFT_LEN: usize = 2048;
+const BINS: usize = FFT_LEN / 2 + 1;
+
+// frames overlap four times over, the least that keeps phases coherent
+// between them
+const OVERSAMPLING: usize = 4;
+const HOP: usize = FFT_LEN / OVERSAMPLING;
+
+// output lags input by a whole frame, about 46ms at 44.1kHz
+pub const LATENCY: usize = FFT_LEN;
+
+// the spectral envelope is the magnitude averaged over this many bins either
+// side, about 170Hz. wider than the gap between a voice's harmonics, narrower
+// than its formants
+const ENVELOPE_BINS: usize = 8;
+
+// formant correction is limited to this much gain or cut, so that bins with
+// next to no energy in the envelope don't blow up
+const MAX_FORMANT_CORRECTION: f32 = 16.0;
+
+// Phase vocoder pitch shifter for a mono signal. Each frame is analysed into
+// the true frequency of each bin from how its phase advanced since the last
+// frame, then the bins are moved up or down by the pitch ratio and
+// resynthesised with their phases advanced at the new frequencies. Timing is
+// untouched, so the output runs at the same speed as the input.
+//
+// Moving bins moves the formants too, which makes voices sound chipmunked or
+// dull. With formants preserved, each bin keeps the level of the spectral
+// envelope where it lands instead. That suits voices and other sounds rich
+// in harmonics, but dims pure tones, whose envelope is a single narrow peak.
+pub struct PitchShifter {
+    ratio: f64,
+    preserve_formants: bool,
+    fft: Arc<dyn FFT<f32>>,
+    ifft: Arc<dyn FFT<f32>>,
+    window: Vec<f32>,
+    // windowing on the way in and out, and overlapping frames, scale the
+    // output by this much
+    norm: f32,
+    // the last FFT_LEN samples of input, oldest first, filled up to rover.
+    // a frame is shifted each time it fills, HOP samples since the last
+    input: Vec<f32>,
+    // output from the last frame, read out while the next fills
+    output: Vec<f32>,
+    // frames overlap-added, the start of it complete
+    accumulator: Vec<f32>,
+    rover: usize,
+    scratch: Vec<Complex<f32>>,
+    spectrum: Vec<Complex<f32>>,
+    // phase of each bin in the last frame analysed and synthesised
+    analysis_phase: Vec<f64>,
+    synthesis_phase: Vec<f64>,
+    magnitude: Vec<f32>,
+    // true frequency of each bin, in bins
+    frequency: Vec<f64>,
+    envelope: Vec<f32>,
+    shifted_magnitude: Vec<f32>,
+    shifted_frequency: Vec<f64>,
+}
+
+impl Debug for PitchShifter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PitchShifter {{ ratio: {}, preserve_formants: {} }}", self.ratio, self.preserve_formants)
+    }
+}
+
+impl PitchShifter {
+    pub fn new() -> Self {
+        // hann window
+        let window = (0..FFT_LEN)
+            .map(|i| 0.5 - 0.5 * (2.0 * f64::consts::PI * i as f64 / FFT_LEN as f64).cos())
+            .map(|w| w as f32)
+            .collect::<Vec<_>>();
+
+        let norm = FFT_LEN as f32 * window.iter().map(|w| w * w).sum::<f32>() / HOP as f32;
+
+        PitchShifter {
+            ratio: 1.0,
+            preserve_formants: false,
+            fft: FFTplanner::new(false).plan_fft(FFT_LEN),
+            ifft: FFTplanner::new(true).plan_fft(FFT_LEN),
+            window,
+            norm,
+            input: vec![0.0; FFT_LEN],
+            output: vec![0.0; HOP],
+            accumulator: vec![0.0; FFT_LEN],
+            rover: FFT_LEN - HOP,
+            scratch: vec![Complex::zero(); FFT_LEN],
+            spectrum: vec![Complex::zero(); FFT_LEN],
+            analysis_phase: vec![0.0; BINS],
+            synthesis_phase: vec![0.0; BINS],
+            magnitude: vec![0.0; BINS],
+            frequency: vec![0.0; BINS],
+            envelope: vec![0.0; BINS],
+            shifted_magnitude: vec![0.0; BINS],
+            shifted_frequency: vec![0.0; BINS],
+        }
+    }
+
+    pub fn set_semitones(&mut self, semitones: f64) {
+        self.ratio = 2f64.powf(semitones / 12.0);
+    }
+
+    pub fn set_preserve_formants(&mut self, preserve_formants: bool) {
+        self.preserve_formants = preserve_formants;
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        (self.window.len() + self.input.len() + self.output.len() + self.accumulator.len()
+            + self.magnitude.len() + self.envelope.len() + self.shifted_magnitude.len()) * mem::size_of::<f32>()
+            + (self.scratch.len() + self.spectrum.len()) * mem::size_of::<Complex<f32>>()
+            + (self.analysis_phase.len() + self.synthesis_phase.len()
+                + self.frequency.len() + self.shifted_frequency.len()) * mem::size_of::<f64>()
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        for (sample, out) in input.iter().zip(output) {
+            self.input[self.rover] = *sample;
+            *out = self.output[self.rover - (FFT_LEN - HOP)];
+            self.rover += 1;
+
+            if self.rover == FFT_LEN {
+                self.rover = FFT_LEN - HOP;
+                self.shift_frame();
+
+                self.output.copy_from_slice(&self.accumulator[..HOP]);
+                self.accumulator.copy_within(HOP.., 0);
+
+                for value in &mut self.accumulator[FFT_LEN - HOP..] {
+                    *value = 0.0;
+                }
+
+                self.input.copy_within(HOP.., 0);
+            }
+        }
+    }
+
+    fn shift_frame(&mut self) {
+        for ((value, sample), w) in self.scratch.iter_mut().zip(&self.input).zip(&self.window) {
+            *value = Complex::new(sample * w, 0.0);
+        }
+
+        self.fft.process(&mut self.scratch, &mut self.spectrum);
+
+        // phase a bin centred frequency advances by over a hop, per bin
+        let expected = 2.0 * f64::consts::PI * HOP as f64 / FFT_LEN as f64;
+
+        for bin in 0..BINS {
+            let (magnitude, phase) = self.spectrum[bin].to_polar();
+            let phase = phase as f64;
+
+            let deviation = wrap(phase - self.analysis_phase[bin] - bin as f64 * expected);
+            self.analysis_phase[bin] = phase;
+
+            self.magnitude[bin] = magnitude;
+            self.frequency[bin] = bin as f64 + deviation / expected;
+        }
+
+        if self.preserve_formants {
+            envelope(&self.magnitude, &mut self.envelope);
+        }
+
+        for bin in 0..BINS {
+            self.shifted_magnitude[bin] = 0.0;
+            self.shifted_frequency[bin] = 0.0;
+        }
+
+        for bin in 0..BINS {
+            let target = (bin as f64 * self.ratio).round() as usize;
+
+            if target >= BINS {
+                break;
+            }
+
+            let mut magnitude = self.magnitude[bin];
+
+            if self.preserve_formants && self.envelope[bin] > 0.0 {
+                let correction = self.envelope[target] / self.envelope[bin];
+                magnitude *= correction.max(1.0 / MAX_FORMANT_CORRECTION).min(MAX_FORMANT_CORRECTION);
+            }
+
+            self.shifted_magnitude[target] += magnitude;
+            self.shifted_frequency[target] = self.frequency[bin] * self.ratio;
+        }
+
+        for bin in 0..BINS {
+            self.synthesis_phase[bin] = wrap(self.synthesis_phase[bin] + self.shifted_frequency[bin] * expected);
+            let phase = self.synthesis_phase[bin] as f32;
+            self.spectrum[bin] = Complex::from_polar(&self.shifted_magnitude[bin], &phase);
+        }
+
+        // negative frequencies mirror the positive, for a real output
+        for bin in 1..FFT_LEN / 2 {
+            self.spectrum[FFT_LEN - bin] = self.spectrum[bin].conj();
+        }
+
+        self.ifft.process(&mut self.spectrum, &mut self.scratch);
+
+        for ((value, out), w) in self.scratch.iter().zip(&mut self.accumulator).zip(&self.window) {
+            *out += value.re * w / self.norm;
+        }
+    }
+}
+
+// into -π..π
+fn wrap(phase: f64) -> f64 {
+    phase - 2.0 * f64::consts::PI * (phase / (2.0 * f64::consts::PI)).round()
+}
+
+// magnitude averaged over ENVELOPE_BINS either side of each bin
+fn envelope(magnitude: &[f32], envelope: &mut [f32]) {
+    let len = magnitude.len();
+
+    for (bin, value) in envelope.iter_mut().enumerate() {
+        let lo = bin.saturating_sub(ENVELOPE_BINS);
+        let hi = (bin + ENVELOPE_BINS + 1).min(len);
+
+        *value = magnitude[lo..hi].iter().sum::<f32>() / (hi - lo) as f32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 44100.0;
+
+    fn sine(freq: f64, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| 0.5 * (2.0 * f64::consts::PI * freq * i as f64 / SAMPLE_RATE).sin() as f32)
+            .collect()
+    }
+
+    fn shift(shifter: &mut PitchShifter, input: &[f32]) -> Vec<f32> {
+        let mut output = vec![0.0; input.len()];
+
+        // in blocks the size of a tick
+        for (input, output) in input.chunks(735).zip(output.chunks_mut(735)) {
+            shifter.process(input, output);
+        }
+
+        output
+    }
+
+    // frequency from how often a steady tone crosses zero
+    fn measure(signal: &[f32]) -> f64 {
+        let crossings = signal.windows(2).filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0).count();
+        crossings as f64 * SAMPLE_RATE / signal.len() as f64
+    }
+
+    #[test]
+    fn unshifted_passes_through_late() {
+        let mut shifter = PitchShifter::new();
+        let input = sine(440.0, 44100);
+        let output = shift(&mut shifter, &input);
+
+        // once the first frames have fully overlapped
+        for i in FFT_LEN * 2..input.len() {
+            assert!((output[i] - input[i - LATENCY]).abs() < 1e-3, "sample {}: {} != {}", i, output[i], input[i - LATENCY]);
+        }
+    }
+
+    #[test]
+    fn shifts_up_and_down_an_octave() {
+        for &(semitones, expected) in &[(12.0, 440.0), (-12.0, 110.0), (7.0, 220.0 * 1.4983)] {
+            let mut shifter = PitchShifter::new();
+            shifter.set_semitones(semitones);
+
+            let output = shift(&mut shifter, &sine(220.0, 44100));
+            let freq = measure(&output[FFT_LEN * 2..]);
+
+            assert!((freq / expected - 1.0).abs() < 0.02, "{} semitones: {}Hz, expected {}Hz", semitones, freq, expected);
+        }
+    }
+
+    #[test]
+    fn envelope_follows_a_flat_spectrum() {
+        let magnitude = vec![2.0; BINS];
+        let mut result = vec![0.0; BINS];
+        envelope(&magnitude, &mut result);
+
+        assert!(result.iter().all(|value| (value - 2.0).abs() < 1e-6));
+    }
+}
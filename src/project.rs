@@ -12,17 +12,23 @@ use mixlab_protocol as protocol;
 use mixlab_protocol::{WorkspaceState, PerformanceInfo};
 
 use crate::db;
-use crate::engine::{self, EngineHandle, EngineEvents, EngineError, EngineSession, WorkspaceEmbryo};
+use crate::engine::{self, EngineHandle, EngineEvents, EngineError, EngineSession, WorkspaceEmbryo, MonitorPolicy, Recorder, Recording};
+use crate::midi::MidiInputs;
 use crate::persist;
 
 pub mod stream;
+pub mod macros;
 pub mod media;
+pub mod report;
+pub mod snapshot;
 
 #[derive(Clone)]
 pub struct ProjectHandle {
     base: ProjectBaseRef,
     engine: EngineHandle,
     notify: NotifyRx,
+    // the workspace as last persisted, which is what snapshots are taken of
+    workspace: watch::Receiver<persist::Workspace>,
 }
 
 pub struct ProjectBase {
@@ -34,6 +40,10 @@ pub struct ProjectBase {
     database: Arc<std::sync::Mutex<Connection>>,
 
     notify: NotifyTx,
+
+    monitor: MonitorPolicy,
+
+    midi: MidiInputs,
 }
 
 impl Debug for ProjectBase {
@@ -58,6 +68,30 @@ impl ProjectBase {
         f(&mut self.database.lock().expect("lock sqlite connection"))
     }
 
+    pub fn monitor_policy(&self) -> &MonitorPolicy {
+        &self.monitor
+    }
+
+    pub fn midi(&self) -> &MidiInputs {
+        &self.midi
+    }
+
+    // files written by outputs go alongside the project database, in a
+    // directory named after it
+    pub fn recordings_dir(&self) -> PathBuf {
+        let mut dir = self.path.clone();
+        dir.set_extension("recordings");
+        dir
+    }
+
+    // files played by inputs are read from beside the project in the same
+    // way, and never from anywhere else
+    pub fn media_dir(&self) -> PathBuf {
+        let mut dir = self.path.clone();
+        dir.set_extension("media");
+        dir
+    }
+
     pub async fn with_database<T: Send + 'static>(&self, f: impl FnOnce(&mut Connection) -> T + Send + 'static) -> T {
         let conn = self.database.clone();
         task::spawn_blocking(move || {
@@ -74,6 +108,8 @@ impl ProjectBase {
             path,
             database: Arc::new(std::sync::Mutex::new(database)),
             notify,
+            monitor: MonitorPolicy::default(),
+            midi: MidiInputs::open(),
         })
     }
 
@@ -106,7 +142,7 @@ impl ProjectBase {
     }
 }
 
-pub async fn open_or_create(path: PathBuf) -> Result<ProjectHandle, OpenError> {
+pub async fn open_or_create(path: PathBuf, recorder: Option<Recorder>) -> Result<ProjectHandle, OpenError> {
     let (notify_tx, notify_rx) = notify();
     let base = ProjectBase::attach(path, notify_tx).await?;
     let workspace = base.read_workspace().await?;
@@ -115,7 +151,8 @@ pub async fn open_or_create(path: PathBuf) -> Result<ProjectHandle, OpenError> {
 
     // start engine update thread
     let (embryo, mut persist_rx) = WorkspaceEmbryo::new(workspace);
-    let engine = engine::start(runtime::Handle::current(), embryo, base.clone());
+    let engine = engine::start(runtime::Handle::current(), embryo, base.clone(), recorder);
+    let persisted = persist_rx.clone();
 
     task::spawn({
         let base = base.clone();
@@ -135,18 +172,42 @@ pub async fn open_or_create(path: PathBuf) -> Result<ProjectHandle, OpenError> {
         base,
         engine,
         notify: notify_rx,
+        workspace: persisted,
     })
 }
 
+// replays a recording against the project at `path`, which supplies media and
+// other project data but whose own workspace is ignored
+pub async fn replay(path: PathBuf, recording: Recording) -> Result<Vec<(u64, protocol::ServerUpdate)>, OpenError> {
+    let (notify_tx, _) = notify();
+    let base = Arc::new(ProjectBase::attach(path, notify_tx).await?);
+
+    Ok(engine::replay(runtime::Handle::current(), recording, base).await)
+}
+
+// renders the project at `path` offline, leaving its workspace untouched.
+// the outer error is from opening the project, the inner from the render
+pub async fn render(path: PathBuf, output: Option<protocol::OutputId>, length_secs: Option<f64>) -> Result<Result<Vec<engine::Sample>, engine::RenderError>, OpenError> {
+    let (notify_tx, _) = notify();
+    let base = ProjectBase::attach(path, notify_tx).await?;
+    let workspace = base.read_workspace().await?;
+    let (embryo, _) = WorkspaceEmbryo::new(workspace);
+
+    Ok(engine::render(runtime::Handle::current(), embryo, Arc::new(base), output, length_secs).await)
+}
+
 impl ProjectHandle {
-    pub async fn connect_engine(&self) -> Result<(WorkspaceState, EngineEvents, EngineSession), EngineError> {
-        self.engine.connect().await
+    pub async fn connect_engine(&self, group: Option<String>) -> Result<(WorkspaceState, EngineEvents, EngineSession), EngineError> {
+        self.engine.connect(group).await
     }
 
     pub fn notifications(&self) -> impl Stream<Item = Notification> {
         let perf_info = self.engine.performance_info().map(Notification::PerformanceInfo);
         let media = self.notify.media.clone().map(|()| Notification::MediaLibrary);
-        futures::stream::select(perf_info, media)
+        let reports = self.notify.reports.clone().map(|()| Notification::StreamReports);
+        let snapshots = self.notify.snapshots.clone().map(|()| Notification::Snapshots);
+        let macros = self.notify.macros.clone().map(|()| Notification::Macros);
+        futures::stream::select(perf_info, futures::stream::select(media, futures::stream::select(reports, futures::stream::select(snapshots, macros))))
     }
 
     pub async fn begin_media_upload(&self, info: media::UploadInfo) -> Result<media::MediaUpload, media::UploadError> {
@@ -156,31 +217,94 @@ impl ProjectHandle {
     pub async fn fetch_media_library(&self) -> Result<protocol::MediaLibrary, rusqlite::Error> {
         media::library(&self.base).await
     }
+
+    pub async fn fetch_stream_reports(&self) -> Result<protocol::StreamReports, rusqlite::Error> {
+        report::list(&self.base).await
+    }
+
+    pub fn midi_devices(&self) -> protocol::MidiDevices {
+        protocol::MidiDevices { inputs: self.base.midi().devices() }
+    }
+
+    pub async fn fetch_stream_report(&self, id: protocol::StreamReportId) -> Result<Option<protocol::StreamReport>, rusqlite::Error> {
+        report::get(&self.base, id).await
+    }
+
+    pub async fn fetch_stream_loudness(&self, id: protocol::StreamReportId) -> Result<Vec<report::LoudnessEntry>, rusqlite::Error> {
+        report::loudness_log(&self.base, id).await
+    }
+
+    pub async fn fetch_snapshots(&self) -> Result<protocol::Snapshots, rusqlite::Error> {
+        snapshot::list(&self.base).await
+    }
+
+    pub async fn fetch_snapshot(&self, name: String) -> Result<Option<persist::Workspace>, OpenError> {
+        snapshot::get(&self.base, name).await
+    }
+
+    pub async fn save_snapshot(&self, name: String) -> Result<(), rusqlite::Error> {
+        let workspace = self.workspace.borrow().clone();
+        snapshot::save(&self.base, name, workspace).await
+    }
+
+    pub async fn delete_snapshot(&self, name: String) -> Result<(), rusqlite::Error> {
+        snapshot::delete(&self.base, name).await
+    }
+
+    pub async fn fetch_macros(&self) -> Result<protocol::Macros, rusqlite::Error> {
+        macros::list(&self.base).await
+    }
+
+    pub async fn save_macro(&self, params: protocol::MacroParams) -> Result<(), rusqlite::Error> {
+        macros::save(&self.base, params).await
+    }
+
+    pub async fn delete_macro(&self, name: String) -> Result<(), rusqlite::Error> {
+        macros::delete(&self.base, name).await
+    }
 }
 
 pub enum Notification {
     PerformanceInfo(Arc<PerformanceInfo>),
     MediaLibrary,
+    StreamReports,
+    Snapshots,
+    Macros,
 }
 
 pub struct NotifyTx {
     media: watch::Sender<()>,
+    reports: watch::Sender<()>,
+    snapshots: watch::Sender<()>,
+    macros: watch::Sender<()>,
 }
 
 #[derive(Clone)]
 pub struct NotifyRx {
     media: watch::Receiver<()>,
+    reports: watch::Receiver<()>,
+    snapshots: watch::Receiver<()>,
+    macros: watch::Receiver<()>,
 }
 
 pub fn notify() -> (NotifyTx, NotifyRx) {
     let (media_tx, media_rx) = watch::channel(());
+    let (reports_tx, reports_rx) = watch::channel(());
+    let (snapshots_tx, snapshots_rx) = watch::channel(());
+    let (macros_tx, macros_rx) = watch::channel(());
 
     let tx = NotifyTx {
         media: media_tx,
+        reports: reports_tx,
+        snapshots: snapshots_tx,
+        macros: macros_tx,
     };
 
     let rx = NotifyRx {
         media: media_rx,
+        reports: reports_rx,
+        snapshots: snapshots_rx,
+        macros: macros_rx,
     };
 
     (tx, rx)
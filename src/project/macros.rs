@@ -0,0 +1,51 @@
+use mixlab_protocol as protocol;
+use rusqlite::params;
+
+use crate::project::ProjectBaseRef;
+
+// saving under an existing name replaces that macro
+pub async fn save(base: &ProjectBaseRef, params: protocol::MacroParams) -> Result<(), rusqlite::Error> {
+    let name = params.name.clone();
+    let serialized = serde_json::to_vec(&params).expect("serde_json::to_vec");
+
+    base.with_database(move |conn| -> Result<(), rusqlite::Error> {
+        conn.execute(r"
+                INSERT INTO macros (name, serialized) VALUES (?, ?)
+                ON CONFLICT (name) DO UPDATE SET serialized = excluded.serialized
+            ",
+            params![name, serialized])?;
+
+        Ok(())
+    }).await?;
+
+    let _ = base.notify.macros.broadcast(());
+
+    Ok(())
+}
+
+pub async fn list(base: &ProjectBaseRef) -> Result<protocol::Macros, rusqlite::Error> {
+    let serialized = base.with_database(|conn| -> Result<Vec<Vec<u8>>, rusqlite::Error> {
+        conn.prepare("SELECT serialized FROM macros ORDER BY name")?
+            .query_map(rusqlite::NO_PARAMS, |row| row.get(0))?
+            .collect()
+    }).await?;
+
+    // a macro saved by a build with modules this one doesn't have is left
+    // out rather than failing the whole list
+    let macros = serialized.iter()
+        .filter_map(|serialized| serde_json::from_slice(serialized).ok())
+        .collect();
+
+    Ok(protocol::Macros { macros })
+}
+
+pub async fn delete(base: &ProjectBaseRef, name: String) -> Result<(), rusqlite::Error> {
+    base.with_database(move |conn| -> Result<(), rusqlite::Error> {
+        conn.execute("DELETE FROM macros WHERE name = ?", params![name])?;
+        Ok(())
+    }).await?;
+
+    let _ = base.notify.macros.broadcast(());
+
+    Ok(())
+}
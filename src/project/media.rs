@@ -40,21 +40,21 @@ impl MediaUpload {
         Ok(())
     }
 
-    pub async fn finalize(self) -> Result<(), UploadError> {
+    pub async fn finalize(self) -> Result<MediaId, UploadError> {
         let stream_id = self.stream.finalize().await?;
         let info = self.info;
 
-        self.base.with_database(move |conn| -> Result<(), rusqlite::Error> {
+        let media_id = self.base.with_database(move |conn| -> Result<MediaId, rusqlite::Error> {
             conn.execute(
                     "INSERT INTO media (name, kind, stream_id) VALUES (?, ?, ?)",
                     params![info.name, info.kind, stream_id.0])?;
 
-            Ok(())
+            Ok(MediaId(conn.last_insert_rowid()))
         }).await?;
 
         let _ = self.base.notify.media.broadcast(());
 
-        Ok(())
+        Ok(media_id)
     }
 }
 
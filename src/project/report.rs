@@ -0,0 +1,154 @@
+use mixlab_protocol as protocol;
+use mixlab_protocol::{Decibel, StreamReportId, StreamSummary};
+use rusqlite::{params, OptionalExtension};
+
+use crate::project::ProjectBaseRef;
+
+/// Short-term program loudness at one point in a stream session
+#[derive(Debug, Clone, Copy)]
+pub struct LoudnessEntry {
+    pub offset_secs: u64,
+    pub short_term: Decibel,
+}
+
+pub async fn save(base: &ProjectBaseRef, summary: StreamSummary, loudness: Vec<LoudnessEntry>) -> Result<StreamReportId, rusqlite::Error> {
+    let id = base.with_database(move |conn| -> Result<StreamReportId, rusqlite::Error> {
+        let txn = conn.transaction()?;
+
+        txn.execute(r"
+                INSERT INTO stream_reports (
+                    destination, started_at, duration_secs, average_bitrate_kbps, peak_bitrate_kbps,
+                    dropped_frames, reconnects, loudness_average, loudness_peak
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ",
+            params![
+                summary.destination,
+                summary.started_at,
+                summary.duration_secs,
+                summary.average_bitrate_kbps,
+                summary.peak_bitrate_kbps,
+                summary.dropped_frames as i64,
+                summary.reconnects as i64,
+                summary.loudness_average.0,
+                summary.loudness_peak.0,
+            ])?;
+
+        let id = StreamReportId(txn.last_insert_rowid());
+
+        {
+            let mut insert = txn.prepare("INSERT INTO stream_loudness (report_id, offset_secs, short_term) VALUES (?, ?, ?)")?;
+
+            for entry in loudness {
+                insert.execute(params![id.0, entry.offset_secs as i64, entry.short_term.0])?;
+            }
+        }
+
+        txn.commit()?;
+
+        Ok(id)
+    }).await?;
+
+    let _ = base.notify.reports.broadcast(());
+
+    Ok(id)
+}
+
+const SELECT_REPORTS: &str = r"
+    SELECT id, destination, started_at, duration_secs, average_bitrate_kbps, peak_bitrate_kbps,
+        dropped_frames, reconnects, loudness_average, loudness_peak
+    FROM stream_reports
+";
+
+fn report_from_row(row: &rusqlite::Row) -> Result<protocol::StreamReport, rusqlite::Error> {
+    Ok(protocol::StreamReport {
+        id: StreamReportId(row.get(0)?),
+        summary: StreamSummary {
+            destination: row.get(1)?,
+            started_at: row.get(2)?,
+            duration_secs: row.get(3)?,
+            average_bitrate_kbps: row.get(4)?,
+            peak_bitrate_kbps: row.get(5)?,
+            dropped_frames: row.get::<_, i64>(6)? as u64,
+            reconnects: row.get::<_, i64>(7)? as u64,
+            loudness_average: Decibel(row.get(8)?),
+            loudness_peak: Decibel(row.get(9)?),
+        },
+    })
+}
+
+pub async fn list(base: &ProjectBaseRef) -> Result<protocol::StreamReports, rusqlite::Error> {
+    let reports = base.with_database(|conn| -> Result<Vec<protocol::StreamReport>, rusqlite::Error> {
+        conn.prepare(&format!("{} ORDER BY id DESC", SELECT_REPORTS))?
+            .query_map(rusqlite::NO_PARAMS, report_from_row)?
+            .collect()
+    }).await?;
+
+    Ok(protocol::StreamReports { reports })
+}
+
+pub async fn get(base: &ProjectBaseRef, id: StreamReportId) -> Result<Option<protocol::StreamReport>, rusqlite::Error> {
+    base.with_database(move |conn| -> Result<Option<protocol::StreamReport>, rusqlite::Error> {
+        conn.query_row(&format!("{} WHERE id = ?", SELECT_REPORTS),
+            params![id.0],
+            report_from_row,
+        ).optional()
+    }).await
+}
+
+pub async fn loudness_log(base: &ProjectBaseRef, id: StreamReportId) -> Result<Vec<LoudnessEntry>, rusqlite::Error> {
+    base.with_database(move |conn| -> Result<Vec<LoudnessEntry>, rusqlite::Error> {
+        conn.prepare("SELECT offset_secs, short_term FROM stream_loudness WHERE report_id = ? ORDER BY offset_secs")?
+            .query_map(params![id.0], |row| {
+                Ok(LoudnessEntry {
+                    offset_secs: row.get::<_, i64>(0)? as u64,
+                    short_term: Decibel(row.get(1)?),
+                })
+            })?
+            .collect()
+    }).await
+}
+
+pub fn to_csv(report: &protocol::StreamReport) -> String {
+    let summary = &report.summary;
+
+    let mut csv = String::new();
+    csv.push_str("id,destination,started_at,duration_secs,average_bitrate_kbps,peak_bitrate_kbps,dropped_frames,reconnects,loudness_average_db,loudness_peak_db\n");
+    csv.push_str(&format!("{},{},{},{:.3},{:.1},{:.1},{},{},{:.2},{:.2}\n",
+        report.id.0,
+        csv_escape(&summary.destination),
+        summary.started_at,
+        summary.duration_secs,
+        summary.average_bitrate_kbps,
+        summary.peak_bitrate_kbps,
+        summary.dropped_frames,
+        summary.reconnects,
+        summary.loudness_average.0,
+        summary.loudness_peak.0,
+    ));
+    csv
+}
+
+// timestamps are absolute so that the log stands on its own as evidence of
+// what was broadcast when
+pub fn loudness_to_csv(report: &protocol::StreamReport, loudness: &[LoudnessEntry]) -> String {
+    let mut csv = String::new();
+    csv.push_str("timestamp,offset_secs,short_term_db\n");
+
+    for entry in loudness {
+        csv.push_str(&format!("{},{},{:.2}\n",
+            report.summary.started_at + entry.offset_secs as i64,
+            entry.offset_secs,
+            entry.short_term.0,
+        ));
+    }
+
+    csv
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
@@ -0,0 +1,75 @@
+use mixlab_protocol as protocol;
+use rusqlite::{params, OptionalExtension};
+
+use crate::persist;
+use crate::project::{ProjectBaseRef, OpenError};
+use crate::viewer;
+
+// saving under an existing name replaces that snapshot
+pub async fn save(base: &ProjectBaseRef, name: String, mut workspace: persist::Workspace) -> Result<(), rusqlite::Error> {
+    // viewer links are handed out to people, applying a snapshot must never
+    // bring back one that has since been revoked
+    workspace.viewer_links.clear();
+
+    let module_count = workspace.modules.len() as i64;
+    let serialized = serde_json::to_vec(&workspace).expect("serde_json::to_vec");
+    let saved_at = viewer::unix_now() as i64;
+
+    base.with_database(move |conn| -> Result<(), rusqlite::Error> {
+        conn.execute(r"
+                INSERT INTO snapshots (name, saved_at, module_count, serialized) VALUES (?, ?, ?, ?)
+                ON CONFLICT (name) DO UPDATE SET
+                    saved_at = excluded.saved_at,
+                    module_count = excluded.module_count,
+                    serialized = excluded.serialized
+            ",
+            params![name, saved_at, module_count, serialized])?;
+
+        Ok(())
+    }).await?;
+
+    let _ = base.notify.snapshots.broadcast(());
+
+    Ok(())
+}
+
+pub async fn list(base: &ProjectBaseRef) -> Result<protocol::Snapshots, rusqlite::Error> {
+    let snapshots = base.with_database(|conn| -> Result<Vec<protocol::Snapshot>, rusqlite::Error> {
+        conn.prepare("SELECT name, saved_at, module_count FROM snapshots ORDER BY name")?
+            .query_map(rusqlite::NO_PARAMS, |row| {
+                Ok(protocol::Snapshot {
+                    name: row.get(0)?,
+                    saved_at: row.get(1)?,
+                    module_count: row.get::<_, i64>(2)? as usize,
+                })
+            })?
+            .collect()
+    }).await?;
+
+    Ok(protocol::Snapshots { snapshots })
+}
+
+pub async fn get(base: &ProjectBaseRef, name: String) -> Result<Option<persist::Workspace>, OpenError> {
+    let serialized = base.with_database(move |conn| -> Result<Option<Vec<u8>>, rusqlite::Error> {
+        conn.query_row("SELECT serialized FROM snapshots WHERE name = ?",
+            params![name],
+            |row| row.get(0),
+        ).optional()
+    }).await?;
+
+    match serialized {
+        Some(serialized) => Ok(Some(serde_json::from_slice(&serialized)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn delete(base: &ProjectBaseRef, name: String) -> Result<(), rusqlite::Error> {
+    base.with_database(move |conn| -> Result<(), rusqlite::Error> {
+        conn.execute("DELETE FROM snapshots WHERE name = ?", params![name])?;
+        Ok(())
+    }).await?;
+
+    let _ = base.notify.snapshots.broadcast(());
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+// Headless offline render of a project. The engine runs ticks back to back
+// rather than in realtime, waiting on Media Sources to decode instead of
+// playing silence, and the chosen output is written out as a WAV file.
+
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use mixlab_protocol::{ModuleId, OutputId};
+
+use crate::engine::{self, RenderError as EngineRenderError, CHANNELS, SAMPLE_RATE};
+use crate::project::{self, OpenError};
+
+#[derive(StructOpt, Debug)]
+pub struct RenderOpts {
+    /// Project to render, its workspace is left untouched
+    workspace_path: PathBuf,
+    /// WAV file to write
+    file: PathBuf,
+    /// Stereo output to render, as module:index. Defaults to whatever feeds
+    /// the first Output Device
+    #[structopt(long, parse(try_from_str = parse_output))]
+    output: Option<OutputId>,
+    /// Seconds to render. Defaults to until every playing Media Source has
+    /// reached its end
+    #[structopt(long)]
+    length: Option<f64>,
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    Project(OpenError),
+    Engine(EngineRenderError),
+    Write(io::Error),
+}
+
+pub async fn run(opts: RenderOpts) -> Result<(), RenderError> {
+    let samples = project::render(opts.workspace_path, opts.output, opts.length).await
+        .map_err(RenderError::Project)?
+        .map_err(RenderError::Engine)?;
+
+    fs::write(&opts.file, engine::encode_wav(&samples))
+        .map_err(RenderError::Write)?;
+
+    let secs = (samples.len() / CHANNELS) as f64 / SAMPLE_RATE as f64;
+    eprintln!("render: {:.2} seconds to {}", secs, opts.file.display());
+
+    Ok(())
+}
+
+fn parse_output(s: &str) -> Result<OutputId, String> {
+    let mut parts = s.splitn(2, ':');
+    let module = parts.next().and_then(|module| module.parse().ok()).and_then(NonZeroUsize::new);
+    let index = parts.next().and_then(|index| index.parse().ok());
+
+    match (module, index) {
+        (Some(module), Some(index)) => Ok(OutputId(ModuleId(module), index)),
+        _ => Err(format!("expected module:index, got {:?}", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_module_and_index() {
+        let module = ModuleId(NonZeroUsize::new(3).unwrap());
+        assert_eq!(Ok(OutputId(module, 1)), parse_output("3:1"));
+        assert!(parse_output("0:1").is_err());
+        assert!(parse_output("3").is_err());
+    }
+}
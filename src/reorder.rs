@@ -0,0 +1,354 @@
+// Puts packets from UDP transports back in order, holding each back until
+// those before it have arrived or been given up on as lost. Shared by the
+// RTP link, where sequence numbers are 16 bits, and SRT, where they are 31.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use mixlab_protocol::StreamLinkStats;
+
+/// Received packets waiting to be released in sequence number order.
+/// Sequence numbers wrap at `bits` on the wire and are extended to 64 bits
+/// on the way in, so everything here takes and returns extended numbers.
+pub struct ReorderBuffer {
+    // prefix for log messages, naming the transport
+    name: &'static str,
+    bits: u32,
+    // a jump in sequence number larger than this, either way, is treated as
+    // the sender restarting rather than as loss or a late packet
+    max_gap: u64,
+    // released packets are kept for this many packets, so that FEC packets
+    // arriving after their group can still recover from them
+    history_len: u64,
+    // next extended sequence number to release
+    next: Option<u64>,
+    highest: Option<u64>,
+    packets: BTreeMap<u64, Vec<u8>>,
+    missing: BTreeMap<u64, Instant>,
+    history: BTreeMap<u64, Vec<u8>>,
+}
+
+impl ReorderBuffer {
+    /// Starts from whichever packet arrives first.
+    pub fn new(name: &'static str, bits: u32, max_gap: u64) -> Self {
+        ReorderBuffer {
+            name,
+            bits,
+            max_gap,
+            history_len: 0,
+            next: None,
+            highest: None,
+            packets: BTreeMap::new(),
+            missing: BTreeMap::new(),
+            history: BTreeMap::new(),
+        }
+    }
+
+    /// Starts from a sequence number known before any packet arrives, as
+    /// SRT's handshake gives it, so the loss of the first packets is noticed.
+    pub fn starting_at(name: &'static str, bits: u32, max_gap: u64, seq: u64) -> Self {
+        let mut buffer = ReorderBuffer::new(name, bits, max_gap);
+
+        // start one wrap period in so that numbers just before the first
+        // still extend to something
+        let next = buffer.period() + seq;
+        buffer.next = Some(next);
+        buffer.highest = Some(next - 1);
+        buffer
+    }
+
+    /// Keeps the last `packets` released for `get`.
+    pub fn with_history(mut self, packets: u64) -> Self {
+        self.history_len = packets;
+        self
+    }
+
+    fn period(&self) -> u64 {
+        1 << self.bits
+    }
+
+    /// Extends a sequence number from the wire, picking whichever wrap
+    /// period puts it closest to the highest seen.
+    pub fn extend(&self, seq: u64) -> u64 {
+        let period = self.period();
+
+        let highest = match self.highest {
+            Some(highest) => highest,
+            // start one wrap period in so that reordered packets from just
+            // before the first one received still fit
+            None => return period + seq,
+        };
+
+        let candidate = (highest & !(period - 1)) | seq;
+
+        [candidate.saturating_sub(period), candidate, candidate + period].iter()
+            .cloned()
+            .min_by_key(|ext| (*ext as i64 - highest as i64).abs())
+            .unwrap()
+    }
+
+    /// Cuts an extended sequence number back down to its width on the wire.
+    pub fn wrap(&self, seq: u64) -> u64 {
+        seq & (self.period() - 1)
+    }
+
+    /// Holds a packet until its turn, returning sequence numbers newly
+    /// discovered to be missing. Packets filling a gap count as recovered by
+    /// retransmission if `arq`.
+    pub fn insert(&mut self, seq: u64, payload: Vec<u8>, now: Instant, stats: &mut StreamLinkStats, arq: bool) -> Vec<u64> {
+        let next = *self.next.get_or_insert(seq);
+        let highest = *self.highest.get_or_insert(seq);
+
+        if seq > highest + self.max_gap || seq + self.max_gap < next {
+            // sender restarted, start again from here. whatever was held
+            // from before belongs to the old numbering
+            eprintln!("{}: sequence number jumped from {} to {}, resyncing", self.name, highest, seq);
+            self.missing.clear();
+            self.packets.clear();
+            self.history.clear();
+            self.next = Some(seq);
+            self.highest = Some(seq);
+            self.packets.insert(seq, payload);
+            return Vec::new();
+        }
+
+        if seq < next || self.packets.contains_key(&seq) {
+            // late or duplicate
+            return Vec::new();
+        }
+
+        if self.missing.remove(&seq).is_some() && arq {
+            stats.recovered_arq += 1;
+        }
+
+        let mut newly_missing = Vec::new();
+
+        if seq > highest {
+            for missing in (highest + 1)..seq {
+                self.missing.insert(missing, now);
+                newly_missing.push(missing);
+            }
+
+            self.highest = Some(seq);
+        }
+
+        self.packets.insert(seq, payload);
+
+        newly_missing
+    }
+
+    /// Fills in a missing packet rebuilt some other way, such as from FEC.
+    pub fn recover(&mut self, seq: u64, payload: Vec<u8>) {
+        self.missing.remove(&seq);
+        self.packets.insert(seq, payload);
+    }
+
+    /// Moves packets which are next in order to `ready`, giving up on those
+    /// missing for `latency`.
+    pub fn release(&mut self, now: Instant, latency: Duration, ready: &mut VecDeque<Vec<u8>>, stats: &mut StreamLinkStats) {
+        while let Some(next) = self.next {
+            if let Some(payload) = self.packets.remove(&next) {
+                if self.history_len > 0 {
+                    self.history.insert(next, payload.clone());
+                }
+
+                ready.push_back(payload);
+            } else if let Some(noticed) = self.missing.get(&next) {
+                if now.duration_since(*noticed) < latency {
+                    break;
+                }
+
+                self.missing.remove(&next);
+                stats.lost += 1;
+            } else {
+                break;
+            }
+
+            self.next = Some(next + 1);
+        }
+
+        if let Some(next) = self.next {
+            let oldest = next.saturating_sub(self.history_len);
+            self.history = self.history.split_off(&oldest);
+        }
+    }
+
+    /// The next sequence number to release, once any packet has arrived.
+    pub fn next(&self) -> Option<u64> {
+        self.next
+    }
+
+    pub fn highest(&self) -> Option<u64> {
+        self.highest
+    }
+
+    /// A packet held or, if kept, already released.
+    pub fn get(&self, seq: u64) -> Option<&[u8]> {
+        self.packets.get(&seq)
+            .or_else(|| self.history.get(&seq))
+            .map(Vec::as_slice)
+    }
+
+    pub fn is_missing(&self, seq: u64) -> bool {
+        self.missing.contains_key(&seq)
+    }
+
+    /// Sequence numbers still waited on, in order.
+    pub fn missing(&self) -> impl Iterator<Item = u64> + '_ {
+        self.missing.keys().cloned()
+    }
+
+    /// Packets held, not counting history.
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LATENCY: Duration = Duration::from_millis(100);
+
+    fn released(buffer: &mut ReorderBuffer, now: Instant, stats: &mut StreamLinkStats) -> Vec<Vec<u8>> {
+        let mut ready = VecDeque::new();
+        buffer.release(now, LATENCY, &mut ready, stats);
+        ready.into_iter().collect()
+    }
+
+    #[test]
+    fn reordered_packets_are_released_in_order() {
+        let mut buffer = ReorderBuffer::new("test", 16, 1024);
+        let mut stats = StreamLinkStats::default();
+        let now = Instant::now();
+
+        assert!(buffer.insert(100, vec![0], now, &mut stats, true).is_empty());
+        assert_eq!(vec![101, 102], buffer.insert(103, vec![3], now, &mut stats, true));
+        assert!(buffer.insert(102, vec![2], now, &mut stats, true).is_empty());
+        assert_eq!(vec![vec![0]], released(&mut buffer, now, &mut stats));
+
+        buffer.insert(101, vec![1], now, &mut stats, true);
+        assert_eq!(vec![vec![1], vec![2], vec![3]], released(&mut buffer, now, &mut stats));
+        assert_eq!(2, stats.recovered_arq);
+        assert_eq!(0, stats.lost);
+    }
+
+    #[test]
+    fn missing_packets_are_given_up_on_after_the_latency() {
+        let mut buffer = ReorderBuffer::new("test", 16, 1024);
+        let mut stats = StreamLinkStats::default();
+        let now = Instant::now();
+
+        buffer.insert(100, vec![0], now, &mut stats, true);
+        buffer.insert(102, vec![2], now, &mut stats, true);
+        assert_eq!(vec![vec![0]], released(&mut buffer, now, &mut stats));
+
+        let later = now + LATENCY;
+        assert_eq!(vec![vec![2]], released(&mut buffer, later, &mut stats));
+        assert_eq!(1, stats.lost);
+
+        // too late to be of use once given up on
+        buffer.insert(101, vec![1], later, &mut stats, true);
+        assert!(released(&mut buffer, later, &mut stats).is_empty());
+    }
+
+    #[test]
+    fn resyncs_when_the_sender_restarts_ahead() {
+        let mut buffer = ReorderBuffer::new("test", 16, 1024).with_history(16);
+        let mut stats = StreamLinkStats::default();
+        let now = Instant::now();
+
+        buffer.insert(100, vec![0], now, &mut stats, true);
+        buffer.insert(102, vec![2], now, &mut stats, true);
+        released(&mut buffer, now, &mut stats);
+        assert!(buffer.insert(5000, vec![9], now, &mut stats, true).is_empty());
+
+        // nothing from before waits on the lost packet, or is kept
+        assert_eq!(vec![vec![9]], released(&mut buffer, now, &mut stats));
+        assert_eq!(None, buffer.get(100));
+        assert_eq!(0, buffer.missing().count());
+    }
+
+    #[test]
+    fn resyncs_when_the_sender_restarts_behind() {
+        let mut buffer = ReorderBuffer::new("test", 16, 1024);
+        let mut stats = StreamLinkStats::default();
+        let now = Instant::now();
+
+        buffer.insert(5000, vec![0], now, &mut stats, true);
+        buffer.insert(5002, vec![2], now, &mut stats, true);
+        assert_eq!(vec![vec![0]], released(&mut buffer, now, &mut stats));
+
+        assert!(buffer.insert(10, vec![9], now, &mut stats, true).is_empty());
+        buffer.insert(11, vec![10], now, &mut stats, true);
+        assert_eq!(vec![vec![9], vec![10]], released(&mut buffer, now, &mut stats));
+    }
+
+    #[test]
+    fn late_packets_within_the_gap_are_dropped() {
+        let mut buffer = ReorderBuffer::new("test", 16, 1024);
+        let mut stats = StreamLinkStats::default();
+        let now = Instant::now();
+
+        buffer.insert(100, vec![0], now, &mut stats, true);
+        released(&mut buffer, now, &mut stats);
+
+        assert!(buffer.insert(90, vec![9], now, &mut stats, true).is_empty());
+        assert!(released(&mut buffer, now, &mut stats).is_empty());
+    }
+
+    #[test]
+    fn history_keeps_released_packets() {
+        let mut buffer = ReorderBuffer::new("test", 16, 1024).with_history(2);
+        let mut stats = StreamLinkStats::default();
+        let now = Instant::now();
+
+        for seq in 100..104 {
+            buffer.insert(seq, vec![seq as u8], now, &mut stats, true);
+        }
+
+        released(&mut buffer, now, &mut stats);
+
+        assert_eq!(None, buffer.get(101));
+        assert_eq!(Some(&[102][..]), buffer.get(102));
+        assert_eq!(Some(&[103][..]), buffer.get(103));
+    }
+
+    #[test]
+    fn sequence_numbers_extend_across_16_bit_rollover() {
+        let mut buffer = ReorderBuffer::new("test", 16, 1024);
+        let mut stats = StreamLinkStats::default();
+
+        let first = buffer.extend(0xfffe);
+        buffer.insert(first, vec![0], Instant::now(), &mut stats, true);
+
+        assert_eq!(first + 1, buffer.extend(0xffff));
+        assert_eq!(first + 2, buffer.extend(0));
+        assert_eq!(first - 1, buffer.extend(0xfffd));
+        assert_eq!(0, buffer.wrap(first + 2));
+    }
+
+    #[test]
+    fn sequence_numbers_extend_across_31_bit_wrap() {
+        let mask = (1 << 31) - 1;
+        let buffer = ReorderBuffer::starting_at("test", 31, 8192, mask);
+        let first = buffer.next().unwrap();
+
+        assert_eq!(first, buffer.extend(mask));
+        assert_eq!(first + 1, buffer.extend(0));
+        assert_eq!(first - 1, buffer.extend(mask - 1));
+        assert_eq!(0, buffer.wrap(first + 1));
+    }
+
+    #[test]
+    fn starting_sequence_number_is_waited_on() {
+        let mut buffer = ReorderBuffer::starting_at("test", 31, 8192, 7);
+        let mut stats = StreamLinkStats::default();
+        let now = Instant::now();
+
+        let seq = buffer.extend(8);
+        assert_eq!(vec![7], buffer.insert(seq, vec![8], now, &mut stats, true).into_iter()
+            .map(|seq| buffer.wrap(seq))
+            .collect::<Vec<_>>());
+    }
+}
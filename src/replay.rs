@@ -0,0 +1,43 @@
+// Headless replay of a session recorded with `mixlab --record`. Every
+// workspace update the engine produces is printed with its tick, so the
+// output of two replays can be diffed.
+
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use crate::engine::{Recording, RecordingError};
+use crate::project::{self, OpenError};
+
+#[derive(StructOpt, Debug)]
+pub struct ReplayOpts {
+    /// Recording made with --record
+    recording: PathBuf,
+    /// Project to replay against. Provides media referenced by the recording,
+    /// its workspace is left untouched
+    workspace_path: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Recording(RecordingError),
+    Project(OpenError),
+}
+
+pub async fn run(opts: ReplayOpts) -> Result<(), ReplayError> {
+    let recording = Recording::open(&opts.recording)
+        .map_err(ReplayError::Recording)?;
+
+    let ticks = recording.end_tick() + 1;
+
+    let log = project::replay(opts.workspace_path, recording).await
+        .map_err(ReplayError::Project)?;
+
+    for (tick, update) in &log {
+        println!("{} {:?}", tick, update);
+    }
+
+    eprintln!("replay: {} ticks, {} updates", ticks, log.len());
+
+    Ok(())
+}
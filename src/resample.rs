@@ -0,0 +1,193 @@
+use std::f64::consts::PI;
+
+// zero crossings of the sinc kernel either side of each output sample. more
+// is a sharper anti-aliasing filter at the cost of time
+const ZERO_CROSSINGS: f64 = 32.0;
+
+// resamples a whole signal at once with a windowed sinc filter. this is for
+// offline use on short signals such as impulse responses
+pub fn resample(input: &[f32], from_rate: usize, to_rate: usize) -> Vec<f32> {
+    let mut resampler = Resampler::new(from_rate, to_rate, 1);
+    let mut output = Vec::new();
+
+    resampler.process(input, &mut output);
+    resampler.flush(&mut output);
+
+    // flushing rounds up to a whole output sample past the end of input
+    output.truncate((input.len() as f64 * to_rate as f64 / from_rate as f64).ceil() as usize);
+    output
+}
+
+// streaming windowed sinc resampler for interleaved audio. output lags input
+// by half the filter width, ZERO_CROSSINGS input samples when upsampling
+#[derive(Debug)]
+pub struct Resampler {
+    channels: usize,
+    // input samples per output sample
+    ratio: f64,
+    cutoff: f64,
+    half_width: f64,
+    passthrough: bool,
+    // interleaved input not yet fully consumed
+    buffer: Vec<f32>,
+    // index in input frames of the first frame in buffer
+    buffer_start: u64,
+    // input frames received
+    input_len: u64,
+    // output frames produced
+    output_len: u64,
+}
+
+impl Resampler {
+    pub fn new(from_rate: usize, to_rate: usize, channels: usize) -> Self {
+        let ratio = from_rate as f64 / to_rate as f64;
+
+        // when downsampling, lower the filter cutoff to the new nyquist
+        // frequency
+        let cutoff = f64::min(1.0, 1.0 / ratio);
+
+        Resampler {
+            channels,
+            ratio,
+            cutoff,
+            half_width: ZERO_CROSSINGS / cutoff,
+            passthrough: from_rate == to_rate,
+            buffer: Vec::new(),
+            buffer_start: 0,
+            input_len: 0,
+            output_len: 0,
+        }
+    }
+
+    // appends as much resampled output as the input so far allows
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        if self.passthrough {
+            output.extend_from_slice(input);
+            return;
+        }
+
+        self.buffer.extend_from_slice(input);
+        self.input_len += (input.len() / self.channels) as u64;
+
+        // an output sample needs input up to half the filter width after it
+        self.produce(self.input_len as f64 - self.half_width, output);
+    }
+
+    // produces the remaining output, treating input past the end as silence
+    pub fn flush(&mut self, output: &mut Vec<f32>) {
+        if !self.passthrough {
+            self.produce(self.input_len as f64, output);
+        }
+    }
+
+    fn produce(&mut self, until: f64, output: &mut Vec<f32>) {
+        let channels = self.channels;
+
+        loop {
+            let pos = self.output_len as f64 * self.ratio;
+
+            if pos >= until {
+                break;
+            }
+
+            let first = (pos - self.half_width).ceil().max(self.buffer_start as f64) as u64;
+            let last = ((pos + self.half_width).floor() as u64).min(self.input_len.saturating_sub(1));
+
+            let out_start = output.len();
+            output.resize(out_start + channels, 0.0);
+
+            for k in first..=last {
+                let offset = pos - k as f64;
+                let weight = self.cutoff * sinc(self.cutoff * offset) * blackman(offset / self.half_width);
+                let frame = (k - self.buffer_start) as usize * channels;
+
+                for chan in 0..channels {
+                    output[out_start + chan] += (self.buffer[frame + chan] as f64 * weight) as f32;
+                }
+            }
+
+            self.output_len += 1;
+        }
+
+        // drop input that no future output sample can reach
+        let next_pos = self.output_len as f64 * self.ratio;
+        let keep_from = ((next_pos - self.half_width).ceil().max(0.0) as u64).max(self.buffer_start);
+        let drop_frames = (keep_from.min(self.input_len) - self.buffer_start) as usize;
+
+        self.buffer.drain(..(drop_frames * channels));
+        self.buffer_start += drop_frames as u64;
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        f64::sin(PI * x) / (PI * x)
+    }
+}
+
+// window over -1..1, zero at either end
+fn blackman(x: f64) -> f64 {
+    0.42 + 0.5 * f64::cos(PI * x) + 0.08 * f64::cos(2.0 * PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, rate: usize, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| f64::sin(2.0 * PI * freq * i as f64 / rate as f64) as f32)
+            .collect()
+    }
+
+    fn max_error(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn preserves_tone_across_rates() {
+        let input = sine(1000.0, 48000, 48000);
+        let output = resample(&input, 48000, 44100);
+
+        assert_eq!(44100, output.len());
+
+        // ignore the edges, where the filter runs off the end of the input
+        let expected = sine(1000.0, 44100, 44100);
+        assert!(max_error(&expected[1000..43000], &output[1000..43000]) < 0.01);
+    }
+
+    #[test]
+    fn streaming_matches_whole_signal() {
+        let input = sine(1000.0, 48000, 4800);
+        let whole = resample(&input, 48000, 44100);
+
+        // stereo, fed in uneven chunks
+        let interleaved = input.iter().flat_map(|s| vec![*s, -*s]).collect::<Vec<_>>();
+        let mut resampler = Resampler::new(48000, 44100, 2);
+        let mut output = Vec::new();
+
+        for chunk in interleaved.chunks(2 * 377) {
+            resampler.process(chunk, &mut output);
+        }
+
+        resampler.flush(&mut output);
+
+        let left = output.iter().step_by(2).copied().collect::<Vec<_>>();
+        let right = output.iter().skip(1).step_by(2).map(|s| -*s).collect::<Vec<_>>();
+
+        assert!(max_error(&whole, &left[..whole.len()]) < 1e-6);
+        assert!(max_error(&whole, &right[..whole.len()]) < 1e-6);
+    }
+
+    #[test]
+    fn removes_frequencies_above_new_nyquist() {
+        // 30kHz is representable at 96kHz but not at 44.1kHz
+        let input = sine(30000.0, 96000, 96000);
+        let output = resample(&input, 96000, 44100);
+
+        let silence = vec![0.0; output.len()];
+        assert!(max_error(&silence[1000..43000], &output[1000..43000]) < 0.01);
+    }
+}
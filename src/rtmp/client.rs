@@ -6,6 +6,8 @@ use futures::future;
 use futures::stream::{self, Stream, StreamExt};
 use bytes::{Bytes, BytesMut};
 use derive_more::From;
+use rml_rtmp::chunk_io::{ChunkSerializer, ChunkSerializationError};
+use rml_rtmp::messages::{RtmpMessage, MessageSerializationError};
 use rml_rtmp::time::RtmpTimestamp;
 use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType, HandshakeError};
 use rml_rtmp::sessions::{ClientSession, ClientSessionConfig, ClientSessionResult, ClientSessionEvent, ClientSessionError, PublishRequestType};
@@ -13,7 +15,10 @@ use tokio::net::{tcp, TcpStream};
 use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
 use tokio::sync::mpsc::{self, error::TrySendError};
 
-use crate::rtmp::packet::{AudioPacket, VideoPacket};
+use crate::rtmp;
+use crate::rtmp::flv;
+use crate::rtmp::packet::{AudioPacket, VideoPacket, VideoCodec, VideoFrameType, VideoPacketType};
+use crate::video::encode::{AudioCtx, VideoCtx, StreamSegment};
 
 pub use rml_rtmp::sessions::StreamMetadata;
 
@@ -24,18 +29,38 @@ pub enum Error {
     Io(io::Error),
     Handshake(HandshakeError),
     Session(ClientSessionError),
+    ChunkSerialization(ChunkSerializationError),
+    MessageSerialization(MessageSerializationError),
     #[from(ignore)]
     RtmpConnectionRefused(String),
     #[from(ignore)]
     UnexpectedEvent(ClientSessionEvent),
 }
 
+#[derive(Debug, From)]
+pub enum ConnectError {
+    Url(url::ParseError),
+    UnsupportedScheme,
+    MissingHost,
+    Io(io::Error),
+    Client(Error),
+}
+
 #[derive(Debug)]
 enum ClientCommand {
     PublishVideo { data: Bytes, timestamp: RtmpTimestamp },
     PublishAudio { data: Bytes, timestamp: RtmpTimestamp },
+    PublishData { data: Bytes, timestamp: RtmpTimestamp },
 }
 
+// message type id of AMF0 script data
+const AMF0_DATA_TYPE_ID: u8 = 18;
+
+// rml_rtmp doesn't expose the id of the stream it creates to publish on, but
+// it's the first and only stream created on the connection, which servers
+// number from 1
+const PUBLISH_STREAM_ID: u32 = 1;
+
 #[derive(Debug)]
 enum Event {
     Command(ClientCommand),
@@ -44,6 +69,37 @@ enum Event {
     ServerEof,
 }
 
+// connects to an rtmp://host[:port]/app url and requests to publish on it
+pub async fn connect(url: &str, stream_key: String, meta: StreamMetadata) -> Result<PublishClient, ConnectError> {
+    let url = url::Url::parse(url)?;
+
+    if url.scheme() != "rtmp" {
+        return Err(ConnectError::UnsupportedScheme);
+    }
+
+    let hostname = url.host_str().ok_or(ConnectError::MissingHost)?;
+    let port = url.port().unwrap_or(1935);
+
+    let path = url.path();
+    // url docs guarantee path is /-prefixed except for a specific handful of known urls:
+    assert!(path.chars().nth(0) == Some('/'));
+    let app_name = &path[1..];
+
+    let conn = TcpStream::connect((hostname, port)).await?;
+    conn.set_nodelay(true)?;
+
+    let client = start(conn)
+        .await?
+        .publish(PublishInfo {
+            app_name: app_name.to_owned(),
+            stream_key,
+            meta,
+        })
+        .await?;
+
+    Ok(client)
+}
+
 pub async fn start(mut stream: TcpStream) -> Result<PrepublishClient, Error> {
     let mut handshake = Handshake::new(PeerType::Client);
 
@@ -70,6 +126,14 @@ pub async fn start(mut stream: TcpStream) -> Result<PrepublishClient, Error> {
     let mut session_config = ClientSessionConfig::new();
     session_config.chunk_size = 8 * 65536;
 
+    // rml_rtmp's session can only send the few kinds of data message it knows
+    // about, so others are serialized separately. every message it sends is
+    // given a full chunk header, so the session's own serializer state never
+    // matters to the server. the packet announcing the chunk size is the
+    // session's job and is dropped here
+    let mut data_serializer = ChunkSerializer::new();
+    data_serializer.set_max_chunk_size(session_config.chunk_size, RtmpTimestamp::new(0))?;
+
     let (session, results) = ClientSession::new(session_config)?;
 
     // send initial packets from session setup
@@ -111,6 +175,7 @@ pub async fn start(mut stream: TcpStream) -> Result<PrepublishClient, Error> {
 
     let client = ClientState {
         session,
+        data_serializer,
         rtmp_tx,
         rtmp_events: VecDeque::new(),
     };
@@ -261,10 +326,71 @@ impl PublishClient {
 
         Ok(self.command_tx.try_send(ClientCommand::PublishVideo { data: data.freeze(), timestamp })?)
     }
+
+    // marks a point in the stream with an onCuePoint message, for ingest
+    // services that insert ads or chapters on cue
+    pub fn publish_cue_point(&mut self, name: &str, timestamp: RtmpTimestamp) -> Result<(), PublishError> {
+        let time_secs = timestamp.value as f64 / f64::from(rtmp::TIME_BASE);
+        let data = flv::cue_point(name, time_secs);
+
+        Ok(self.command_tx.try_send(ClientCommand::PublishData { data, timestamp })?)
+    }
+
+    pub fn publish_sequence_headers(&mut self, audio: &AudioCtx, video: &VideoCtx) -> Result<(), PublishError> {
+        // configuration buffer is ASC when raw transport is in use:
+        let asc = audio.configuration_data();
+        self.publish_audio(AudioPacket::AacSequenceHeader(asc), RtmpTimestamp::new(0))?;
+
+        let mut dsc = BytesMut::new();
+        video.decoder_configuration_record().write_to(&mut dsc);
+
+        self.publish_video(VideoPacket {
+            codec: VideoCodec::Avc,
+            frame_type: VideoFrameType::KeyFrame,
+            packet_type: VideoPacketType::SequenceHeader,
+            composition_time: 0,
+            data: dsc.freeze(),
+        }, RtmpTimestamp::new(0))
+    }
+
+    pub fn publish_segment(&mut self, segment: StreamSegment) -> Result<(), PublishError> {
+        match segment {
+            StreamSegment::Audio(audio) => {
+                let timestamp = RtmpTimestamp::new(audio.decode_timestamp.round_to_base(rtmp::TIME_BASE.into()) as u32);
+                self.publish_audio(AudioPacket::AacRawData(audio.frame), timestamp)
+            }
+            StreamSegment::Video(video) => {
+                let timestamp = RtmpTimestamp::new(video.decode_timestamp.round_to_base(rtmp::TIME_BASE.into()) as u32);
+
+                if let Some(header) = video.sequence_header {
+                    self.publish_video(VideoPacket {
+                        codec: VideoCodec::Avc,
+                        frame_type: VideoFrameType::KeyFrame,
+                        packet_type: VideoPacketType::SequenceHeader,
+                        composition_time: 0,
+                        data: header,
+                    }, timestamp)?;
+                }
+
+                self.publish_video(VideoPacket {
+                    codec: VideoCodec::Avc,
+                    frame_type: if video.frame.is_key_frame {
+                        VideoFrameType::KeyFrame
+                    } else {
+                        VideoFrameType::InterFrame
+                    },
+                    packet_type: VideoPacketType::Nalu,
+                    composition_time: video.frame.composition_time.round_to_base(rtmp::TIME_BASE.into()) as u32,
+                    data: video.frame.data,
+                }, timestamp)
+            }
+        }
+    }
 }
 
 struct ClientState {
     session: ClientSession,
+    data_serializer: ChunkSerializer,
     rtmp_tx: tcp::OwnedWriteHalf,
     rtmp_events: VecDeque<ClientSessionEvent>,
 }
@@ -292,6 +418,12 @@ async fn run_client(mut client: ClientState, mut events: impl Stream<Item = Even
                 let action = client.session.publish_video_data(data, timestamp, false)?;
                 handle_session_results(&mut client, iter::once(action)).await?;
             }
+            Event::Command(ClientCommand::PublishData { data, timestamp }) => {
+                let message = RtmpMessage::Unknown { type_id: AMF0_DATA_TYPE_ID, data };
+                let payload = message.into_message_payload(timestamp, PUBLISH_STREAM_ID)?;
+                let packet = client.data_serializer.serialize(&payload, true, false)?;
+                client.rtmp_tx.write_all(&packet.bytes).await?;
+            }
             Event::CommandEof => {
                 println!("command eof, goodbye");
                 break;
@@ -0,0 +1,317 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use mixlab_codec::ffmpeg::{AvIoError, AvIoReader, InputContainer, SeekableReader};
+use mixlab_codec::ffmpeg::sys as ff;
+use rml_rtmp::time::RtmpTimestamp;
+
+use crate::rtmp::{MediaContext, RtmpError, receive_audio_packet, receive_video_packet};
+use crate::rtmp::flv::{self, FlvReader, TagType};
+use crate::rtmp::packet::{AudioPacket, VideoCodec, VideoFrameType, VideoPacket, VideoPacketType};
+use crate::source::{Registry, SourceRecv};
+
+#[derive(Debug)]
+pub enum PathError {
+    // absolute, or climbing out with `..` or a symlink
+    OutsideMediaDir,
+    Io(io::Error),
+}
+
+/// Resolves a file source's path within the project's media directory.
+/// Paths are taken relative to it, and anything which would reach outside
+/// of it is refused, so that a workspace can never be used to read
+/// arbitrary files from the server.
+pub fn resolve(media_dir: &Path, name: &str) -> Result<PathBuf, PathError> {
+    let relative = Path::new(name);
+
+    if !relative.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(PathError::OutsideMediaDir);
+    }
+
+    let media_dir = fs::canonicalize(media_dir).map_err(PathError::Io)?;
+    let path = fs::canonicalize(media_dir.join(relative)).map_err(PathError::Io)?;
+
+    if path.starts_with(&media_dir) {
+        Ok(path)
+    } else {
+        Err(PathError::OutsideMediaDir)
+    }
+}
+
+/// Plays a local FLV or MP4 file on loop as a fake live source, for
+/// development and rehearsal without a real encoder. Tags are fed through
+/// the same decode path as RTMP ingest so timing behaviour matches, with MP4
+/// packets wrapped up as the FLV tags an encoder would have sent.
+pub fn play(name: &str, path: PathBuf) -> SourceRecv {
+    // each file source gets a registry of its own, so that it is never
+    // reachable by remote publishers
+    let registry = Registry::new();
+    let recv = registry.listen(name);
+    let source = registry.connect(name, None)
+        .expect("connect to newly created file source");
+
+    thread::spawn(move || {
+        let result = MediaContext::new(source)
+            .and_then(|mut media| run_playback(&path, &mut media));
+
+        if let Err(e) = result {
+            eprintln!("rtmp: playback of {:?} stopped: {:?}", path, e);
+        }
+    });
+
+    recv
+}
+
+fn is_mp4(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ["mp4", "m4v", "mov"].iter().any(|mp4| ext.eq_ignore_ascii_case(mp4)),
+        None => false,
+    }
+}
+
+fn run_playback(path: &Path, media: &mut MediaContext) -> Result<(), RtmpError> {
+    let mut playback = Playback {
+        start: Instant::now(),
+        loop_offset: 0,
+        last_timestamp: None,
+    };
+
+    loop {
+        if is_mp4(path) {
+            play_mp4(path, media, &mut playback)?;
+        } else {
+            play_flv(path, media, &mut playback)?;
+        }
+
+        if !media.source.connected() {
+            return Ok(());
+        }
+
+        playback.loop_offset = match playback.last_timestamp.take() {
+            Some(timestamp) => timestamp + 1,
+            // file contains no tags, don't spin on it forever
+            None => return Ok(()),
+        };
+    }
+}
+
+struct Playback {
+    start: Instant,
+    // timestamps keep increasing across loops, exactly as they would for a
+    // long running live stream
+    loop_offset: u32,
+    last_timestamp: Option<u32>,
+}
+
+impl Playback {
+    // pace reads as if they were arriving from the network, returning the
+    // timestamp to give the tag
+    fn wait(&mut self, file_timestamp: u32) -> u32 {
+        let timestamp = self.loop_offset + file_timestamp;
+
+        // audio and video are interleaved, so the last tag is not always the
+        // latest
+        self.last_timestamp = Some(self.last_timestamp.map_or(timestamp, |last| last.max(timestamp)));
+
+        let due = self.start + Duration::from_millis(u64::from(timestamp));
+        let now = Instant::now();
+
+        if due > now {
+            thread::sleep(due - now);
+        }
+
+        timestamp
+    }
+}
+
+fn play_flv(path: &Path, media: &mut MediaContext, playback: &mut Playback) -> Result<(), RtmpError> {
+    let mut flv = FlvReader::new(BufReader::new(File::open(path)?))?;
+
+    while let Some(tag) = flv.read_tag()? {
+        if !media.source.connected() {
+            return Ok(());
+        }
+
+        let timestamp = playback.wait(tag.timestamp);
+
+        match tag.tag_type {
+            TagType::Audio => {
+                receive_audio_packet(media, tag.data, RtmpTimestamp::new(timestamp))?;
+            }
+            TagType::Video => {
+                receive_video_packet(media, tag.data, RtmpTimestamp::new(timestamp))?;
+            }
+            TagType::Script => {
+                if let Some(frame_rate) = flv::metadata_frame_rate(tag.data) {
+                    media.set_frame_rate(Some(frame_rate));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl From<AvIoError<SeekableReader<File>>> for RtmpError {
+    fn from(e: AvIoError<SeekableReader<File>>) -> Self {
+        match e {
+            AvIoError::Av(e) => RtmpError::AvCodec(e),
+            AvIoError::Io(e) => RtmpError::Io(e),
+        }
+    }
+}
+
+// MP4 stores h264 and hevc as length prefixed NALs with the decoder
+// configuration record as extradata, and AAC as raw frames with the
+// AudioSpecificConfig as extradata, exactly as FLV carries them. So demuxed
+// packets only need the FLV tag header put back on
+fn play_mp4(path: &Path, media: &mut MediaContext, playback: &mut Playback) -> Result<(), RtmpError> {
+    let mut container = InputContainer::open(AvIoReader::new(SeekableReader(File::open(path)?)))?;
+
+    let mut video = None;
+    let mut audio = None;
+
+    for (index, stream) in container.streams().iter().enumerate() {
+        let params = stream.codec_parameters();
+
+        let codec = match params.codec_id {
+            ff::AVCodecID_AV_CODEC_ID_H264 => Some(VideoCodec::Avc),
+            ff::AVCodecID_AV_CODEC_ID_HEVC => Some(VideoCodec::Hevc),
+            ff::AVCodecID_AV_CODEC_ID_AV1 => Some(VideoCodec::Av1),
+            ff::AVCodecID_AV_CODEC_ID_AAC => {
+                if audio.is_none() {
+                    audio = Some((index, stream.time_base(), Bytes::copy_from_slice(params.extradata())));
+                }
+                None
+            }
+            _ => None,
+        };
+
+        if let (Some(codec), None) = (codec, &video) {
+            video = Some((index, codec, stream.time_base(), Bytes::copy_from_slice(params.extradata())));
+        }
+    }
+
+    if video.is_none() && audio.is_none() {
+        eprintln!("rtmp: {:?} has no h264, hevc, av1 or aac streams to play", path);
+        return Ok(());
+    }
+
+    // sequence headers go ahead of the first packet, but don't count as
+    // played if the file turns out to have no packets at all
+    let timestamp = playback.loop_offset;
+
+    if let Some((_, codec, _, config)) = &video {
+        let packet = VideoPacket {
+            codec: *codec,
+            frame_type: VideoFrameType::KeyFrame,
+            packet_type: VideoPacketType::SequenceHeader,
+            composition_time: 0,
+            data: config.clone(),
+        };
+
+        receive_video_packet(media, tag_bytes(|out| packet.write_to(out)), RtmpTimestamp::new(timestamp))?;
+    }
+
+    if let Some((_, _, config)) = &audio {
+        let packet = AudioPacket::AacSequenceHeader(config.clone());
+        receive_audio_packet(media, tag_bytes(|out| packet.write_to(out)), RtmpTimestamp::new(timestamp))?;
+    }
+
+    while let Some(pkt) = container.read_packet()? {
+        if !media.source.connected() {
+            return Ok(());
+        }
+
+        let index = pkt.stream_index() as usize;
+
+        if let Some((_, codec, time_base, _)) = video.as_ref().filter(|video| video.0 == index) {
+            let decode_ms = time_base.scale_timestamp(pkt.decode_timestamp()).round_to_base(1000);
+            let present_ms = time_base.scale_timestamp(pkt.presentation_timestamp()).round_to_base(1000);
+
+            let packet = VideoPacket {
+                codec: *codec,
+                frame_type: if pkt.is_key_frame() { VideoFrameType::KeyFrame } else { VideoFrameType::InterFrame },
+                packet_type: VideoPacketType::Nalu,
+                composition_time: (present_ms - decode_ms).max(0) as u32,
+                data: Bytes::copy_from_slice(pkt.data()),
+            };
+
+            let timestamp = playback.wait(decode_ms.max(0) as u32);
+            receive_video_packet(media, tag_bytes(|out| packet.write_to(out)), RtmpTimestamp::new(timestamp))?;
+        } else if let Some((_, time_base, _)) = audio.as_ref().filter(|audio| audio.0 == index) {
+            let decode_ms = time_base.scale_timestamp(pkt.decode_timestamp()).round_to_base(1000);
+
+            let packet = AudioPacket::AacRawData(Bytes::copy_from_slice(pkt.data()));
+
+            let timestamp = playback.wait(decode_ms.max(0) as u32);
+            receive_audio_packet(media, tag_bytes(|out| packet.write_to(out)), RtmpTimestamp::new(timestamp))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn tag_bytes(write: impl FnOnce(&mut BytesMut)) -> Bytes {
+    let mut out = BytesMut::new();
+    write(&mut out);
+    out.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use uuid::Uuid;
+
+    fn media_dir() -> PathBuf {
+        let dir = env::temp_dir().join(format!("mixlab-media-{}", Uuid::new_v4()));
+        fs::create_dir_all(dir.join("shows")).unwrap();
+        fs::write(dir.join("shows").join("rehearsal.flv"), b"").unwrap();
+        dir
+    }
+
+    #[test]
+    fn resolves_paths_within_the_media_dir() {
+        let dir = media_dir();
+        let expected = fs::canonicalize(dir.join("shows/rehearsal.flv")).unwrap();
+
+        assert_eq!(expected, resolve(&dir, "shows/rehearsal.flv").unwrap());
+        assert_eq!(expected, resolve(&dir, "./shows/rehearsal.flv").unwrap());
+    }
+
+    #[test]
+    fn refuses_paths_outside_the_media_dir() {
+        let dir = media_dir();
+        let outside = dir.with_extension("secret");
+        fs::write(&outside, b"").unwrap();
+
+        assert!(matches!(resolve(&dir, outside.to_str().unwrap()), Err(PathError::OutsideMediaDir)));
+        assert!(matches!(resolve(&dir, "../etc/passwd"), Err(PathError::OutsideMediaDir)));
+        assert!(matches!(resolve(&dir, "shows/../../x"), Err(PathError::OutsideMediaDir)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_symlinks_out_of_the_media_dir() {
+        let dir = media_dir();
+        let outside = dir.with_extension("secret");
+        fs::write(&outside, b"").unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join("link.flv")).unwrap();
+
+        assert!(matches!(resolve(&dir, "link.flv"), Err(PathError::OutsideMediaDir)));
+    }
+
+    #[test]
+    fn recognises_mp4_by_extension() {
+        assert!(is_mp4(Path::new("a/show.mp4")));
+        assert!(is_mp4(Path::new("show.MOV")));
+        assert!(!is_mp4(Path::new("show.flv")));
+        assert!(!is_mp4(Path::new("mp4")));
+    }
+}
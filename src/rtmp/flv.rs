@@ -0,0 +1,291 @@
+use std::io::{self, Read};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use derive_more::From;
+
+// See https://www.adobe.com/content/dam/acom/en/devnet/flv/video_file_format_spec_v10_1.pdf
+// Annex E for reference. FLV tag bodies are identical to RTMP audio/video
+// message payloads, so they can be handed straight to the RTMP packet parsers.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagType {
+    Audio,
+    Video,
+    Script,
+}
+
+#[derive(Debug)]
+pub struct Tag {
+    pub tag_type: TagType,
+    pub timestamp: u32,
+    pub data: Bytes,
+}
+
+#[derive(From, Debug)]
+pub enum FlvError {
+    Io(io::Error),
+    BadSignature,
+    BadTagType(u8),
+}
+
+pub struct FlvReader<R> {
+    reader: R,
+}
+
+impl<R: Read> FlvReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, FlvError> {
+        let mut signature = [0u8; 3];
+        reader.read_exact(&mut signature)?;
+
+        if &signature != b"FLV" {
+            return Err(FlvError::BadSignature);
+        }
+
+        let _version = reader.read_u8()?;
+        let _flags = reader.read_u8()?;
+        let header_size = reader.read_u32::<BigEndian>()?;
+
+        // skip any header bytes beyond those we know about
+        let extra_header = u64::from(header_size.saturating_sub(9));
+        io::copy(&mut (&mut reader).take(extra_header), &mut io::sink())?;
+
+        // PreviousTagSize0, always zero
+        let _ = reader.read_u32::<BigEndian>()?;
+
+        Ok(FlvReader { reader })
+    }
+
+    pub fn read_tag(&mut self) -> Result<Option<Tag>, FlvError> {
+        let tag_type = match self.reader.read_u8() {
+            Ok(tag_type) => tag_type,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let data_size = self.reader.read_u24::<BigEndian>()?;
+        let timestamp = self.reader.read_u24::<BigEndian>()?;
+        let timestamp_extended = self.reader.read_u8()?;
+        let _stream_id = self.reader.read_u24::<BigEndian>()?;
+
+        let mut data = vec![0u8; data_size as usize];
+        self.reader.read_exact(&mut data)?;
+
+        let _previous_tag_size = self.reader.read_u32::<BigEndian>()?;
+
+        let tag_type = match tag_type & 0x1f {
+            8 => TagType::Audio,
+            9 => TagType::Video,
+            18 => TagType::Script,
+            x => return Err(FlvError::BadTagType(x)),
+        };
+
+        Ok(Some(Tag {
+            tag_type,
+            timestamp: (u32::from(timestamp_extended) << 24) | timestamp,
+            data: Bytes::from(data),
+        }))
+    }
+}
+
+const AMF0_NUMBER: u8 = 0x00;
+const AMF0_BOOLEAN: u8 = 0x01;
+const AMF0_STRING: u8 = 0x02;
+const AMF0_OBJECT: u8 = 0x03;
+const AMF0_NULL: u8 = 0x05;
+const AMF0_UNDEFINED: u8 = 0x06;
+const AMF0_ECMA_ARRAY: u8 = 0x08;
+const AMF0_OBJECT_END: u8 = 0x09;
+const AMF0_STRICT_ARRAY: u8 = 0x0a;
+const AMF0_DATE: u8 = 0x0b;
+const AMF0_LONG_STRING: u8 = 0x0c;
+
+// Pulls the frame rate out of an onMetaData script tag. Only as much AMF0 as
+// is needed to walk the metadata properties is understood, anything else
+// gives up and returns None.
+pub fn metadata_frame_rate(mut data: Bytes) -> Option<f32> {
+    if read_u8(&mut data)? != AMF0_STRING || read_string(&mut data)? != "onMetaData" {
+        return None;
+    }
+
+    match read_u8(&mut data)? {
+        AMF0_ECMA_ARRAY => { skip(&mut data, 4)?; }
+        AMF0_OBJECT => {}
+        _ => return None,
+    }
+
+    loop {
+        let key = read_string(&mut data)?;
+        let marker = read_u8(&mut data)?;
+
+        if key.is_empty() && marker == AMF0_OBJECT_END {
+            return None;
+        }
+
+        if key == "framerate" && marker == AMF0_NUMBER {
+            if data.remaining() < 8 {
+                return None;
+            }
+
+            return Some(data.get_f64() as f32);
+        }
+
+        skip_value(&mut data, marker)?;
+    }
+}
+
+// Builds the body of an onCuePoint script data message marking an event at
+// the given time into the stream, as understood by Flash era players and
+// most ingest services.
+pub fn cue_point(name: &str, time_secs: f64) -> Bytes {
+    let mut data = BytesMut::new();
+
+    write_string_value(&mut data, "onCuePoint");
+
+    data.put_u8(AMF0_OBJECT);
+    write_string(&mut data, "name");
+    write_string_value(&mut data, name);
+    write_string(&mut data, "time");
+    data.put_u8(AMF0_NUMBER);
+    data.put_f64(time_secs);
+    write_string(&mut data, "type");
+    write_string_value(&mut data, "event");
+    write_string(&mut data, "parameters");
+    data.put_u8(AMF0_OBJECT);
+    write_object_end(&mut data);
+    write_object_end(&mut data);
+
+    data.freeze()
+}
+
+fn write_string(data: &mut BytesMut, value: &str) {
+    // AMF0 strings are limited to 64k, anything longer is cut short
+    let value = &value.as_bytes()[0..value.len().min(u16::max_value() as usize)];
+    data.put_u16(value.len() as u16);
+    data.put_slice(value);
+}
+
+fn write_string_value(data: &mut BytesMut, value: &str) {
+    data.put_u8(AMF0_STRING);
+    write_string(data, value);
+}
+
+fn write_object_end(data: &mut BytesMut) {
+    write_string(data, "");
+    data.put_u8(AMF0_OBJECT_END);
+}
+
+fn read_u8(data: &mut Bytes) -> Option<u8> {
+    if data.remaining() < 1 {
+        return None;
+    }
+
+    Some(data.get_u8())
+}
+
+fn read_string(data: &mut Bytes) -> Option<String> {
+    if data.remaining() < 2 {
+        return None;
+    }
+
+    let len = data.get_u16() as usize;
+
+    if data.remaining() < len {
+        return None;
+    }
+
+    String::from_utf8(data.split_to(len).to_vec()).ok()
+}
+
+fn skip(data: &mut Bytes, len: usize) -> Option<()> {
+    if data.remaining() < len {
+        return None;
+    }
+
+    data.advance(len);
+    Some(())
+}
+
+fn skip_value(data: &mut Bytes, marker: u8) -> Option<()> {
+    match marker {
+        AMF0_NUMBER => skip(data, 8),
+        AMF0_BOOLEAN => skip(data, 1),
+        AMF0_STRING => read_string(data).map(|_| ()),
+        AMF0_NULL | AMF0_UNDEFINED => Some(()),
+        AMF0_DATE => skip(data, 10),
+        AMF0_LONG_STRING => {
+            if data.remaining() < 4 {
+                return None;
+            }
+
+            let len = data.get_u32() as usize;
+            skip(data, len)
+        }
+        AMF0_OBJECT | AMF0_ECMA_ARRAY => {
+            if marker == AMF0_ECMA_ARRAY {
+                skip(data, 4)?;
+            }
+
+            loop {
+                let key = read_string(data)?;
+                let marker = read_u8(data)?;
+
+                if key.is_empty() && marker == AMF0_OBJECT_END {
+                    return Some(());
+                }
+
+                skip_value(data, marker)?;
+            }
+        }
+        AMF0_STRICT_ARRAY => {
+            if data.remaining() < 4 {
+                return None;
+            }
+
+            for _ in 0..data.get_u32() {
+                let marker = read_u8(data)?;
+                skip_value(data, marker)?;
+            }
+
+            Some(())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_frame_rate_from_metadata() {
+        let mut data = vec![AMF0_STRING, 0, 10];
+        data.extend_from_slice(b"onMetaData");
+        data.extend_from_slice(&[AMF0_ECMA_ARRAY, 0, 0, 0, 2]);
+
+        data.extend_from_slice(&[0, 7]);
+        data.extend_from_slice(b"encoder");
+        data.extend_from_slice(&[AMF0_STRING, 0, 3]);
+        data.extend_from_slice(b"obs");
+
+        data.extend_from_slice(&[0, 9]);
+        data.extend_from_slice(b"framerate");
+        data.push(AMF0_NUMBER);
+        data.extend_from_slice(&30.0f64.to_be_bytes());
+
+        data.extend_from_slice(&[0, 0, AMF0_OBJECT_END]);
+
+        assert_eq!(Some(30.0), metadata_frame_rate(Bytes::from(data)));
+    }
+
+    #[test]
+    fn writes_well_formed_cue_point() {
+        let mut data = cue_point("ad-break", 12.5);
+
+        assert_eq!(Some(AMF0_STRING), read_u8(&mut data));
+        assert_eq!(Some("onCuePoint".to_owned()), read_string(&mut data));
+        assert_eq!(Some(AMF0_OBJECT), read_u8(&mut data));
+        assert_eq!(Some(()), skip_value(&mut data, AMF0_OBJECT));
+        assert_eq!(0, data.remaining());
+    }
+}
@@ -3,10 +3,11 @@ use std::mem;
 
 use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType};
 use rml_rtmp::sessions::{ServerSession, ServerSessionConfig, ServerSessionResult, ServerSessionEvent};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::AsyncReadExt;
 
-use crate::listen::PeekTcpStream;
+use crate::listen::{write_all_timeout, PeekTcpStream};
 use crate::rtmp::RtmpError;
+use crate::source::ConnectError;
 
 pub async fn handshake(stream: &mut PeekTcpStream, buff: &mut [u8]) -> Result<(Handshake, Vec<u8>), RtmpError> {
     println!("RTMP incoming!");
@@ -18,10 +19,10 @@ pub async fn handshake(stream: &mut PeekTcpStream, buff: &mut [u8]) -> Result<(H
 
         match handshake.process_bytes(&buff[0..bytes])? {
             HandshakeProcessResult::InProgress { response_bytes } => {
-                stream.write_all(&response_bytes).await?;
+                write_all_timeout(stream, &response_bytes).await?;
             }
             HandshakeProcessResult::Completed { response_bytes, remaining_bytes } => {
-                stream.write_all(&response_bytes).await?;
+                write_all_timeout(stream, &response_bytes).await?;
                 return Ok((handshake, remaining_bytes));
             }
         }
@@ -39,7 +40,7 @@ pub async fn setup_session(stream: &mut PeekTcpStream) -> Result<ServerSession,
     for result in results {
         match result {
             ServerSessionResult::OutboundResponse(packet) => {
-                stream.write_all(&packet.bytes).await?;
+                write_all_timeout(stream, &packet.bytes).await?;
             }
             ServerSessionResult::RaisedEvent(_) |
             ServerSessionResult::UnhandleableMessageReceived(_) => {
@@ -97,7 +98,7 @@ pub async fn accept_publish(
 
     for result in results {
         if let ServerSessionResult::OutboundResponse(resp) = result {
-            stream.write_all(&resp.bytes).await?;
+            write_all_timeout(stream, &resp.bytes).await?;
         } else {
             // accept_request never returns any variant of ServerSessionResult other than OutboundReponse:
             panic!("rtmp: unexpected result from accept_request: {:?}", result);
@@ -107,6 +108,29 @@ pub async fn accept_publish(
     Ok(())
 }
 
+pub async fn reject_publish(
+    stream: &mut PeekTcpStream,
+    session: &mut ServerSession,
+    publish: &PublishInfo,
+    error: &ConnectError,
+) -> Result<(), RtmpError> {
+    let (code, description) = match error {
+        ConnectError::NoMountpoint => ("NetStream.Publish.BadName", "no such mountpoint"),
+        ConnectError::AlreadyConnected => ("NetStream.Publish.BadName", "mountpoint is already being published to"),
+        ConnectError::Unauthorized => ("NetStream.Publish.Unauthorized", "incorrect stream key"),
+    };
+
+    let results = session.reject_request(publish.request_id, code, description)?;
+
+    for result in results {
+        if let ServerSessionResult::OutboundResponse(resp) = result {
+            write_all_timeout(stream, &resp.bytes).await?;
+        }
+    }
+
+    Ok(())
+}
+
 async fn handle_session_results(stream: &mut PeekTcpStream, session: &mut ServerSession, actions: Vec<ServerSessionResult>) -> Result<Option<PublishInfo>, RtmpError> {
     let mut actions: VecDeque<_> = actions.into();
     let mut publish_info = None;
@@ -114,7 +138,7 @@ async fn handle_session_results(stream: &mut PeekTcpStream, session: &mut Server
     while let Some(action) = actions.pop_front() {
         match action {
             ServerSessionResult::OutboundResponse(packet) => {
-                stream.write_all(&packet.bytes).await?;
+                write_all_timeout(stream, &packet.bytes).await?;
             }
             ServerSessionResult::RaisedEvent(ev) => {
                 match handle_event(session, ev).await? {
@@ -1,5 +1,6 @@
 use std::io;
 use std::mem;
+use std::sync::Arc;
 use std::thread;
 
 use bytes::Bytes;
@@ -9,7 +10,7 @@ use num_rational::Rational64;
 use rml_rtmp::handshake::HandshakeError;
 use rml_rtmp::sessions::{ServerSession, ServerSessionResult, ServerSessionError, ServerSessionEvent};
 use rml_rtmp::time::RtmpTimestamp;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncReadExt, ReadHalf};
 
 use mixlab_codec::aac;
 use mixlab_codec::ffmpeg::media::Video;
@@ -17,26 +18,31 @@ use mixlab_codec::ffmpeg::codec::{self, CodecBuilder, Decode, RecvFrameError};
 use mixlab_codec::ffmpeg::{AvError, AvPacketRef, PacketInfo};
 use mixlab_util::time::{MediaDuration, MediaTime, TimeBase};
 
-use crate::listen::PeekTcpStream;
-use crate::source::{Registry, ConnectError, SourceRecv, SourceSend, ListenError};
+use crate::engine::{Sample, CHANNELS, SAMPLE_RATE};
+use crate::listen::{PeekTcpStream, WriteQueue, WriteQueueError};
+use crate::resample::Resampler;
+use crate::source::{Registry, ConnectError, SourceRecv, SourceSend};
 use crate::video;
 
 pub mod client;
+pub mod file;
+pub mod flv;
 pub mod incoming;
 pub mod packet;
+pub mod simulate;
 
-use packet::{AudioPacket, VideoPacket, VideoPacketType};
+use packet::{AudioPacket, VideoPacket, VideoPacketType, VideoCodec, VideoFrameType};
 
+// mountpoints exist for as long as a StreamInput is listening on them, and
+// are named by the app part of the RTMP url publishers connect to
 lazy_static::lazy_static! {
-    static ref MOUNTPOINTS: Registry = {
-        let reg = Registry::new();
-        mem::forget(reg.listen("my_stream_endpoint"));
-        reg
-    };
+    static ref MOUNTPOINTS: Registry = Registry::new();
 }
 
-pub fn listen(mountpoint: &str) -> Result<SourceRecv, ListenError> {
-    MOUNTPOINTS.listen(mountpoint)
+pub fn listen(mountpoint: &str, stream_key: Option<String>) -> SourceRecv {
+    let recv = MOUNTPOINTS.listen(mountpoint);
+    recv.set_stream_key(stream_key);
+    recv
 }
 
 pub const TIME_BASE: i32 = 1000;
@@ -47,13 +53,14 @@ pub enum RtmpError {
     Handshake(HandshakeError),
     Session(ServerSessionError),
     SourceConnect(ConnectError),
-    MetadataNotYetSent,
-    UnsupportedStream,
     SourceSend,
     Aac(aac::AacError),
     AacCodec(fdk_aac::dec::DecoderError),
+    CodecBuild(codec::BuildError),
     CodecOpen(codec::OpenError),
     AvCodec(AvError),
+    Flv(flv::FlvError),
+    Write(WriteQueueError),
 }
 
 pub async fn accept(mut stream: PeekTcpStream) -> Result<(), RtmpError> {
@@ -65,12 +72,15 @@ pub async fn accept(mut stream: PeekTcpStream) -> Result<(), RtmpError> {
 
     let source = match publish {
         Some(publish) => {
-            println!("rtmp: client wants to publish on {:?} with stream_key {:?}",
-                publish.app_name, publish.stream_key);
-
-            // TODO handle stream keys
+            println!("rtmp: client wants to publish on {:?}", publish.app_name);
 
-            let source = MOUNTPOINTS.connect(&publish.app_name)?;
+            let source = match MOUNTPOINTS.connect(&publish.app_name, Some(&publish.stream_key)) {
+                Ok(source) => source,
+                Err(e) => {
+                    incoming::reject_publish(&mut stream, &mut session, &publish, &e).await?;
+                    return Err(e.into());
+                }
+            };
 
             incoming::accept_publish(&mut stream, &mut session, &publish).await?;
 
@@ -79,21 +89,15 @@ pub async fn accept(mut stream: PeekTcpStream) -> Result<(), RtmpError> {
         None => { return Ok(()); }
     };
 
-    let mut audio_codec = fdk_aac::dec::Decoder::new(fdk_aac::dec::Transport::Adts);
-
-    // enable automatic stereo mix-down:
-    audio_codec.set_min_output_channels(2)?;
-    audio_codec.set_max_output_channels(2)?;
+    // writes from the receive thread go through a queue so that a client
+    // which stops reading can't block it
+    let (stream, writer) = tokio::io::split(stream);
 
     let mut ctx = ReceiveContext {
         stream,
+        writer: WriteQueue::spawn(writer),
         session,
-        source,
-        meta: None,
-        audio_codec,
-        audio_asc: None,
-        audio_timestamp: MediaTime::new(0, 1),
-        video_codec: None,
+        media: MediaContext::new(source)?,
     };
 
     thread::spawn(move || {
@@ -104,18 +108,114 @@ pub async fn accept(mut stream: PeekTcpStream) -> Result<(), RtmpError> {
 }
 
 struct ReceiveContext {
-    stream: PeekTcpStream,
+    stream: ReadHalf<PeekTcpStream>,
+    writer: WriteQueue,
     session: ServerSession,
+    media: MediaContext,
+}
+
+// decoding state for a single incoming stream, independent of whether packets
+// are arriving over the network or being read from a file
+struct MediaContext {
     source: SourceSend,
-    meta: Option<StreamMeta>,
     audio_codec: fdk_aac::dec::Decoder,
     audio_asc: Option<aac::AudioSpecificConfiguration>,
+    // timestamp of the next audio sample, at the engine sample rate
     audio_timestamp: MediaTime,
+    audio_resample: Option<AudioResample>,
     video_codec: Option<Decode<Video>>,
+    // from stream metadata, not present for audio-only streams and some
+    // encoders that omit it
+    video_frame_duration: Option<MediaDuration>,
+    last_video_timestamp: Option<MediaTime>,
+    // for AVC streams, the configuration record and the packets not yet
+    // attached to a decoded frame, kept for pass-through outputs
+    video_config: Option<Bytes>,
+    passthrough_packets: Vec<video::PassthroughPacket>,
+    passthrough_seq: u64,
+}
+
+struct AudioResample {
+    source_rate: usize,
+    resampler: Resampler,
+}
+
+impl MediaContext {
+    fn new(source: SourceSend) -> Result<Self, RtmpError> {
+        let mut audio_codec = fdk_aac::dec::Decoder::new(fdk_aac::dec::Transport::Adts);
+
+        // enable automatic stereo mix-down:
+        audio_codec.set_min_output_channels(2)?;
+        audio_codec.set_max_output_channels(2)?;
+
+        Ok(MediaContext {
+            source,
+            audio_codec,
+            audio_asc: None,
+            audio_timestamp: MediaTime::new(0, 1),
+            audio_resample: None,
+            video_codec: None,
+            video_frame_duration: None,
+            last_video_timestamp: None,
+            video_config: None,
+            passthrough_packets: Vec::new(),
+            passthrough_seq: 0,
+        })
+    }
+
+    // converts stereo audio decoded at any other rate to the engine's. output
+    // is a little shorter than input at first, as the resampler looks ahead
+    fn resample_audio(&mut self, source_rate: usize, pcm: &[i16]) -> Vec<i16> {
+        // start a new resampler if the sample rate ever changes mid stream
+        if self.audio_resample.as_ref().map(|resample| resample.source_rate) != Some(source_rate) {
+            self.audio_resample = Some(AudioResample {
+                source_rate,
+                resampler: Resampler::new(source_rate, SAMPLE_RATE, CHANNELS),
+            });
+        }
+
+        let resample = self.audio_resample.as_mut().unwrap();
+
+        // i16::min_value is a greater absolute distance away from 0 than
+        // max_value, so divide by that to stay within -1.0 to 1.0
+        let input = pcm.iter()
+            .map(|sample| *sample as Sample / -(i16::min_value() as Sample))
+            .collect::<Vec<_>>();
+
+        let mut output = Vec::new();
+        resample.resampler.process(&input, &mut output);
+
+        output.into_iter()
+            .map(|sample| (sample.max(-1.0).min(1.0) * i16::max_value() as Sample) as i16)
+            .collect()
+    }
+
+    fn set_frame_rate(&mut self, frame_rate: Option<f32>) {
+        self.video_frame_duration = frame_rate
+            .filter(|frame_rate| *frame_rate > 0.0)
+            .map(|frame_rate| {
+                let frame_rate = Rational64::new((frame_rate * TIME_BASE as f32) as i64, TIME_BASE.into());
+                MediaDuration::from(frame_rate.recip())
+            });
+    }
 }
 
-struct StreamMeta {
-    video_frame_duration: MediaDuration,
+// without a frame rate from metadata, estimate frame duration from the gap
+// between successive frames
+fn frame_duration_hint(
+    frame_duration: Option<MediaDuration>,
+    last_timestamp: &mut Option<MediaTime>,
+    timestamp: MediaTime,
+) -> MediaDuration {
+    let estimate = last_timestamp
+        .map(|last| timestamp - last)
+        .filter(|duration| *duration > MediaDuration::zero());
+
+    *last_timestamp = Some(timestamp);
+
+    frame_duration
+        .or(estimate)
+        .unwrap_or(MediaDuration::new(1, 30))
 }
 
 fn run_receive_thread(ctx: &mut ReceiveContext, mut buff: Vec<u8>) -> Result<(), RtmpError> {
@@ -139,7 +239,7 @@ fn handle_session_results(
     for action in actions {
         match action {
             ServerSessionResult::OutboundResponse(packet) => {
-                block_on(ctx.stream.write_all(&packet.bytes))?;
+                ctx.writer.write(packet.bytes)?;
             }
             ServerSessionResult::RaisedEvent(ev) => {
                 handle_event(ctx, ev)?;
@@ -159,27 +259,16 @@ fn handle_event(
 ) -> Result<(), RtmpError> {
     match event {
         ServerSessionEvent::AudioDataReceived { app_name: _, stream_key: _, data, timestamp } => {
-            receive_audio_packet(ctx, data, timestamp)?;
+            receive_audio_packet(&mut ctx.media, data, timestamp)?;
             Ok(())
         }
         ServerSessionEvent::VideoDataReceived { data, timestamp, .. } => {
-            receive_video_packet(ctx, data, timestamp)?;
+            receive_video_packet(&mut ctx.media, data, timestamp)?;
             Ok(())
         }
         ServerSessionEvent::StreamMetadataChanged { app_name: _, stream_key: _, metadata } => {
-            let video_frame_duration =
-                if let Some(frame_rate) = metadata.video_frame_rate {
-                    let frame_rate = Rational64::new((frame_rate * TIME_BASE as f32) as i64, TIME_BASE.into());
-                    MediaDuration::from(frame_rate.recip())
-                } else {
-                    eprintln!("rtmp: no frame rate in metadata");
-                    return Err(RtmpError::UnsupportedStream);
-                };
-
-            ctx.meta = Some(StreamMeta {
-                video_frame_duration,
-            });
-
+            // audio-only streams carry no video frame rate:
+            ctx.media.set_frame_rate(metadata.video_frame_rate);
             Ok(())
         }
         _ => {
@@ -190,7 +279,7 @@ fn handle_event(
 }
 
 fn receive_audio_packet(
-    ctx: &mut ReceiveContext,
+    ctx: &mut MediaContext,
     data: Bytes,
     _timestamp: RtmpTimestamp,
 ) -> Result<(), RtmpError> {
@@ -200,6 +289,7 @@ fn receive_audio_packet(
         Ok(AudioPacket::AacSequenceHeader(bytes)) => {
             let asc = aac::AudioSpecificConfiguration::parse(bytes)?;
             ctx.audio_asc = Some(asc);
+            ctx.source.set_info(|info| info.audio_codec = Some("AAC".to_owned()));
         }
         Ok(AudioPacket::AacRawData(bytes)) => {
             let asc = if let Some(asc) = &ctx.audio_asc {
@@ -224,18 +314,27 @@ fn receive_audio_packet(
 
             match ctx.audio_codec.decode_frame(&mut pcm_buffer) {
                 Ok(()) => {
-                    let sample_rate = ctx.audio_codec.stream_info().sampleRate;
-
-                    if sample_rate != 44100 {
-                        // TODO fix me
-                        panic!("expected stream sample rate to be 44100");
-                    }
-
-                    let frame_time = MediaDuration::new(pcm_buffer.len() as i64 / 2, sample_rate as i64);
+                    let sample_rate = ctx.audio_codec.stream_info().sampleRate as usize;
 
                     pcm_buffer.truncate(ctx.audio_codec.decoded_frame_size());
                     // println!("decoded frame! timestamp: {:?}, frame size: {}", timestamp, pcm_buffer.len());
 
+                    let pcm_buffer = if sample_rate == SAMPLE_RATE {
+                        ctx.audio_resample = None;
+                        pcm_buffer
+                    } else {
+                        ctx.resample_audio(sample_rate, &pcm_buffer)
+                    };
+
+                    if pcm_buffer.is_empty() {
+                        return Ok(());
+                    }
+
+                    // timestamps count samples at the engine rate rather
+                    // than following the decoded frames, so resampled audio
+                    // stays contiguous and keeps time with video
+                    let frame_time = MediaDuration::new((pcm_buffer.len() / CHANNELS) as i64, SAMPLE_RATE as i64);
+
                     // TODO do we use ctx.audio_timestamp or the rtmp timestamp here?
 
                     ctx.source.write_audio(ctx.audio_timestamp, pcm_buffer)
@@ -258,12 +357,10 @@ fn receive_audio_packet(
 }
 
 fn receive_video_packet(
-    ctx: &mut ReceiveContext,
+    ctx: &mut MediaContext,
     data: Bytes,
     timestamp: RtmpTimestamp,
 ) -> Result<(), RtmpError> {
-    let meta = ctx.meta.as_ref().ok_or(RtmpError::MetadataNotYetSent)?;
-
     let packet = match VideoPacket::parse(data) {
         Ok(packet) => packet,
         Err(e) => {
@@ -276,14 +373,38 @@ fn receive_video_packet(
         VideoPacketType::SequenceHeader => {
             let time_base = TimeBase::new(1, TIME_BASE);
 
-            let decode = CodecBuilder::h264(time_base)
-                // h264 extradata is the decoder configuration record:
-                .with_extradata(&packet.data)
-                // use avcc encoding (length-prefixed NALs) rather than default of annex-b:
-                .with_opt("is_avc", "1")
-                .open_decoder()?;
+            let decode = match packet.codec {
+                VideoCodec::Avc => {
+                    CodecBuilder::h264(time_base)
+                        // h264 extradata is the decoder configuration record:
+                        .with_extradata(&packet.data)
+                        // use avcc encoding (length-prefixed NALs) rather than default of annex-b:
+                        .with_opt("is_avc", "1")
+                        .open_decoder()?
+                }
+                VideoCodec::Hevc => {
+                    // hevc extradata is the hvcC record, ffmpeg detects
+                    // length-prefixed NALs from it automatically:
+                    CodecBuilder::hevc(time_base)?
+                        .with_extradata(&packet.data)
+                        .open_decoder()?
+                }
+                VideoCodec::Av1 => {
+                    // av1 extradata is the av1C record:
+                    CodecBuilder::av1(time_base)?
+                        .with_extradata(&packet.data)
+                        .open_decoder()?
+                }
+            };
 
             ctx.video_codec = Some(decode);
+
+            ctx.video_config = match packet.codec {
+                VideoCodec::Avc => Some(packet.data.clone()),
+                _ => None,
+            };
+
+            ctx.source.set_info(|info| info.video_codec = Some(packet.codec.name().to_owned()));
         }
         VideoPacketType::Nalu => {
             let codec = match ctx.video_codec.as_mut() {
@@ -309,14 +430,37 @@ fn receive_video_packet(
             codec.send_packet(&av_packet)
                 .expect("avc::decode::send_packet in rtmp");
 
+            if ctx.video_config.is_some() {
+                ctx.passthrough_packets.push(video::PassthroughPacket {
+                    seq: ctx.passthrough_seq,
+                    decode_timestamp: MediaTime::new(dts, TIME_BASE.into()),
+                    composition_time: MediaDuration::new(packet.composition_time as i64, TIME_BASE.into()),
+                    is_key_frame: packet.frame_type == VideoFrameType::KeyFrame,
+                    data: packet.data.clone(),
+                });
+
+                ctx.passthrough_seq += 1;
+            }
+
             loop {
                 match codec.recv_frame() {
                     Ok(decoded) => {
                         let timestamp = MediaTime::new(decoded.presentation_timestamp(), TIME_BASE.into());
+                        let duration_hint = frame_duration_hint(
+                            ctx.video_frame_duration, &mut ctx.last_video_timestamp, timestamp);
+
+                        let passthrough = ctx.video_config.as_ref().map(|config| {
+                            Arc::new(video::Passthrough {
+                                config: config.clone(),
+                                packets: mem::take(&mut ctx.passthrough_packets),
+                            })
+                        });
 
                         let frame = video::Frame {
+                            key_frame: decoded.is_key_frame(),
                             decoded: decoded,
-                            duration_hint: meta.video_frame_duration,
+                            duration_hint,
+                            passthrough,
                         };
 
                         let _ = ctx.source.write_video(timestamp, frame);
@@ -63,11 +63,55 @@ pub enum VideoFrameType {
     VideoInfoFrame,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    Avc,
+    Hevc,
+    Av1,
+}
+
+impl VideoCodec {
+    // enhanced rtmp identifies codecs by FourCC rather than legacy codec id
+    fn from_fourcc(fourcc: &[u8]) -> Option<VideoCodec> {
+        match fourcc {
+            b"avc1" => Some(VideoCodec::Avc),
+            b"hvc1" => Some(VideoCodec::Hevc),
+            b"av01" => Some(VideoCodec::Av1),
+            _ => None,
+        }
+    }
+
+    fn fourcc(&self) -> &'static [u8; 4] {
+        match self {
+            VideoCodec::Avc => b"avc1",
+            VideoCodec::Hevc => b"hvc1",
+            VideoCodec::Av1 => b"av01",
+        }
+    }
+
+    // av1 carries no composition time offset, it has no B-frame reordering
+    fn has_composition_time(&self) -> bool {
+        match self {
+            VideoCodec::Avc | VideoCodec::Hevc => true,
+            VideoCodec::Av1 => false,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoCodec::Avc => "H.264",
+            VideoCodec::Hevc => "HEVC",
+            VideoCodec::Av1 => "AV1",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum VideoPacketError {
     Eof,
     BadFrameType(u8),
     BadCodec(u8),
+    BadFourCc([u8; 4]),
     BadVideoPacketType(u8),
 }
 
@@ -80,6 +124,7 @@ pub enum VideoPacketType {
 
 #[derive(Debug)]
 pub struct VideoPacket {
+    pub codec: VideoCodec,
     pub frame_type: VideoFrameType,
     pub packet_type: VideoPacketType,
     pub composition_time: u32,
@@ -94,14 +139,11 @@ impl VideoPacket {
 
         let ident = bytes.get_u8();
 
-        let frame_type = match ident >> 4 {
-            1 => VideoFrameType::KeyFrame,
-            2 => VideoFrameType::InterFrame,
-            3 => VideoFrameType::DisposableInterFrame,
-            4 => VideoFrameType::GeneratedKeyFrame,
-            5 => VideoFrameType::VideoInfoFrame,
-            x => return Err(VideoPacketError::BadFrameType(x)),
-        };
+        if ident & 0x80 != 0 {
+            return Self::parse_enhanced(ident, bytes);
+        }
+
+        let frame_type = parse_frame_type(ident >> 4)?;
 
         match ident & 0x0f {
             7 => { /* avc codec */ }
@@ -124,6 +166,55 @@ impl VideoPacket {
         let data = bytes.to_bytes();
 
         Ok(VideoPacket {
+            codec: VideoCodec::Avc,
+            frame_type,
+            packet_type,
+            composition_time,
+            data,
+        })
+    }
+
+    // See https://github.com/veovera/enhanced-rtmp/blob/main/enhanced-rtmp.pdf
+    // ExVideoTagHeader for reference
+    fn parse_enhanced(ident: u8, mut bytes: Bytes) -> Result<VideoPacket, VideoPacketError> {
+        let frame_type = parse_frame_type((ident >> 4) & 0x07)?;
+
+        if bytes.remaining() < 4 {
+            return Err(VideoPacketError::Eof);
+        }
+
+        let mut fourcc = [0u8; 4];
+        bytes.copy_to_slice(&mut fourcc);
+
+        let codec = VideoCodec::from_fourcc(&fourcc)
+            .ok_or(VideoPacketError::BadFourCc(fourcc))?;
+
+        let (packet_type, composition_time) = match ident & 0x0f {
+            0 => (VideoPacketType::SequenceHeader, 0),
+            1 => {
+                // CodedFrames, carries composition time if the codec has one
+                let composition_time = if codec.has_composition_time() {
+                    if bytes.remaining() < 3 {
+                        return Err(VideoPacketError::Eof);
+                    }
+
+                    bytes.get_uint(3) as u32
+                } else {
+                    0
+                };
+
+                (VideoPacketType::Nalu, composition_time)
+            }
+            2 => (VideoPacketType::EndOfSequence, 0),
+            // CodedFramesX, composition time is implicitly zero
+            3 => (VideoPacketType::Nalu, 0),
+            x => return Err(VideoPacketError::BadVideoPacketType(x)),
+        };
+
+        let data = bytes.to_bytes();
+
+        Ok(VideoPacket {
+            codec,
             frame_type,
             packet_type,
             composition_time,
@@ -140,6 +231,10 @@ impl VideoPacket {
             VideoFrameType::VideoInfoFrame => 5,
         };
 
+        if self.codec != VideoCodec::Avc {
+            return self.write_enhanced_to(frame_type, out);
+        }
+
         let codec: u8 = 7; // AVC
 
         // write ident (frame type + codec)
@@ -158,4 +253,72 @@ impl VideoPacket {
         // write data
         out.extend_from_slice(&self.data);
     }
+
+    fn write_enhanced_to(&self, frame_type: u8, out: &mut BytesMut) {
+        let packet_type: u8 = match self.packet_type {
+            VideoPacketType::SequenceHeader => 0,
+            VideoPacketType::Nalu => 1,
+            VideoPacketType::EndOfSequence => 2,
+        };
+
+        // write ident (ex header flag + frame type + packet type)
+        out.put_u8(0x80 | (frame_type << 4) | packet_type);
+
+        out.extend_from_slice(self.codec.fourcc());
+
+        if let VideoPacketType::Nalu = self.packet_type {
+            if self.codec.has_composition_time() {
+                out.put_uint(self.composition_time as u64, 3usize);
+            }
+        }
+
+        out.extend_from_slice(&self.data);
+    }
+}
+
+fn parse_frame_type(frame_type: u8) -> Result<VideoFrameType, VideoPacketError> {
+    match frame_type {
+        1 => Ok(VideoFrameType::KeyFrame),
+        2 => Ok(VideoFrameType::InterFrame),
+        3 => Ok(VideoFrameType::DisposableInterFrame),
+        4 => Ok(VideoFrameType::GeneratedKeyFrame),
+        5 => Ok(VideoFrameType::VideoInfoFrame),
+        x => Err(VideoPacketError::BadFrameType(x)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_enhanced_hevc_coded_frames() {
+        let bytes = Bytes::from_static(&[
+            0b1001_0001, b'h', b'v', b'c', b'1',
+            0x00, 0x00, 0x21,       // composition time
+            0xde, 0xad, 0xbe, 0xef, // dummy payload
+        ]);
+
+        let packet = VideoPacket::parse(bytes.clone()).unwrap();
+
+        assert_eq!(VideoCodec::Hevc, packet.codec);
+        assert_eq!(VideoFrameType::KeyFrame, packet.frame_type);
+        assert!(matches!(packet.packet_type, VideoPacketType::Nalu));
+        assert_eq!(0x21, packet.composition_time);
+        assert_eq!(&[0xde, 0xad, 0xbe, 0xef], &packet.data[..]);
+
+        let mut out = BytesMut::new();
+        packet.write_to(&mut out);
+        assert_eq!(bytes[..], out[..]);
+    }
+
+    #[test]
+    fn rejects_unknown_fourcc() {
+        let bytes = Bytes::from_static(&[0b1001_0000, b'v', b'p', b'0', b'8']);
+
+        match VideoPacket::parse(bytes) {
+            Err(VideoPacketError::BadFourCc(fourcc)) => assert_eq!(b"vp08", &fourcc),
+            other => panic!("expected BadFourCc, got {:?}", other),
+        }
+    }
 }
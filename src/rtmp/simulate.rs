@@ -0,0 +1,313 @@
+// Publishes a synthetic stream to an RTMP server so that the ingest path can be
+// exercised without a real encoder. Run with `mixlab simulate-publish`.
+//
+// The stream carries a sync marker once a second: a tone burst in the audio
+// and a white flash in the video, both starting on the second and lasting
+// MARKER_SAMPLES. The rest of the stream is silent and black. Finding the
+// marker onsets on the receiving side gives the A/V offset of whatever path
+// the stream took.
+
+use std::f32::consts::PI;
+use std::ops::Range;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fdk_aac::enc as aac;
+use structopt::StructOpt;
+use tokio::task;
+
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::{AvFrame, PictureSettings};
+use mixlab_util::time::{MediaDuration, MediaTime};
+
+use crate::engine::{Sample, CHANNELS, SAMPLE_RATE, SAMPLES_PER_TICK, TICKS_PER_SECOND};
+use crate::rtmp::client::{self, ConnectError, PublishClient, PublishError, StreamMetadata};
+use crate::video::encode::{EncodeStream, AudioCtx, AudioParams, VideoCtx, VideoParams, Profile};
+
+pub const FRAMES_PER_SECOND: usize = 30;
+
+// 100ms, a whole number of both ticks and video frames
+pub const MARKER_SAMPLES: usize = SAMPLE_RATE / 10;
+
+const SAMPLES_PER_FRAME: usize = SAMPLE_RATE / FRAMES_PER_SECOND;
+
+const MARKER_AMPLITUDE: Sample = 0.5;
+
+// luma values for limited range yuv
+const LUMA_BLACK: u8 = 16;
+const LUMA_WHITE: u8 = 235;
+
+#[derive(StructOpt, Debug)]
+pub struct SimulateOpts {
+    /// RTMP url to publish to. On a mixlab server, the path is the mountpoint
+    #[structopt(default_value = "rtmp://127.0.0.1:8000/my_stream_endpoint")]
+    url: String,
+    #[structopt(long, default_value = "")]
+    stream_key: String,
+    /// Seconds to publish for, runs until killed if not given
+    #[structopt(long)]
+    duration: Option<u64>,
+    /// Frequency of the marker tone in Hz
+    #[structopt(long, default_value = "1000")]
+    tone: f32,
+    #[structopt(long, default_value = "640")]
+    width: usize,
+    #[structopt(long, default_value = "360")]
+    height: usize,
+    /// Delays the video marker relative to the audio marker, for checking
+    /// that sync measurements pick up a known offset
+    #[structopt(long, default_value = "0", allow_hyphen_values = true)]
+    video_offset_ms: i64,
+}
+
+#[derive(Debug)]
+pub enum SimulateError {
+    Connect(ConnectError),
+    Publish(PublishError),
+}
+
+pub async fn run(opts: SimulateOpts) -> Result<(), SimulateError> {
+    let publish = client::connect(&opts.url, opts.stream_key.clone(), StreamMetadata {
+        video_width: Some(opts.width as u32),
+        video_height: Some(opts.height as u32),
+        video_codec: Some("avc1".to_owned()),
+        video_frame_rate: Some(FRAMES_PER_SECOND as f32),
+        video_bitrate_kbps: None,
+        audio_codec: Some("aac1".to_owned()),
+        audio_bitrate_kbps: Some(160),
+        audio_sample_rate: Some(SAMPLE_RATE as u32),
+        audio_channels: Some(2),
+        audio_is_stereo: Some(true),
+        encoder: Some("Mixlab simulate-publish".to_owned()),
+    }).await.map_err(SimulateError::Connect)?;
+
+    println!("simulate-publish: publishing to {}", opts.url);
+
+    // encoding is blocking work, keep it off the async threads
+    task::spawn_blocking(move || publish_pattern(publish, &opts))
+        .await
+        .expect("join publish thread")
+        .map_err(SimulateError::Publish)
+}
+
+fn publish_pattern(mut publish: PublishClient, opts: &SimulateOpts) -> Result<(), PublishError> {
+    let audio_ctx = AudioCtx::new(AudioParams {
+        bit_rate: aac::BitRate::Cbr(160000),
+        sample_rate: SAMPLE_RATE,
+        transport: aac::Transport::Raw,
+    });
+
+    let picture = PictureSettings::yuv420p(opts.width, opts.height);
+
+    // the monitor profile encodes every frame as a key frame with no
+    // lookahead, so a receiver can start decoding at any point
+    let video_ctx = VideoCtx::new(VideoParams {
+        picture: picture.clone(),
+        time_base: SAMPLE_RATE,
+        profile: Profile::Monitor,
+        keyframe_interval: None,
+    });
+
+    publish.publish_sequence_headers(&audio_ctx, &video_ctx)?;
+
+    let mut encode = EncodeStream::new(audio_ctx, video_ctx);
+    let pattern = SyncPattern::new(opts.tone, opts.video_offset_ms);
+
+    let black = solid_frame(&picture, LUMA_BLACK);
+    let white = solid_frame(&picture, LUMA_WHITE);
+
+    let total_ticks = opts.duration.map(|secs| secs * TICKS_PER_SECOND as u64);
+    let tick_duration = Duration::from_secs(1) / TICKS_PER_SECOND as u32;
+    let start = Instant::now();
+
+    let mut audio = Vec::with_capacity(SAMPLES_PER_TICK * CHANNELS);
+    let mut next_frame = 0;
+
+    for tick in 0.. {
+        if total_ticks.map(|total| tick >= total).unwrap_or(false) {
+            break;
+        }
+
+        let tick_start = tick as usize * SAMPLES_PER_TICK;
+        let tick_end = tick_start + SAMPLES_PER_TICK;
+
+        audio.clear();
+        pattern.audio(tick_start..tick_end, &mut audio);
+        encode.send_audio(&audio);
+
+        while next_frame * SAMPLES_PER_FRAME < tick_end {
+            let frame_start = next_frame * SAMPLES_PER_FRAME;
+
+            let frame = if pattern.video_marker(frame_start) {
+                white.clone()
+            } else {
+                black.clone()
+            };
+
+            encode.send_video(
+                MediaTime::new(frame_start as i64, SAMPLE_RATE as i64),
+                MediaDuration::new(SAMPLES_PER_FRAME as i64, SAMPLE_RATE as i64),
+                frame);
+
+            next_frame += 1;
+        }
+
+        while let Some(segment) = encode.recv_segment() {
+            publish.publish_segment(segment)?;
+        }
+
+        // publish in real time, as a live encoder would
+        let deadline = start + tick_duration * (tick + 1);
+        let now = Instant::now();
+
+        if deadline > now {
+            thread::sleep(deadline - now);
+        }
+    }
+
+    Ok(())
+}
+
+fn solid_frame(picture: &PictureSettings, luma: u8) -> AvFrame<Video> {
+    let mut frame = AvFrame::blank(picture);
+
+    {
+        let data = frame.frame_data_mut();
+
+        // chroma planes are left neutral by AvFrame::blank
+        unsafe {
+            for y in 0..picture.height {
+                let line = data.data(0).add(y * data.stride(0));
+                line.write_bytes(luma, picture.width);
+            }
+        }
+    }
+
+    frame
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncPattern {
+    tone: f32,
+    video_offset: i64,
+}
+
+impl SyncPattern {
+    pub fn new(tone: f32, video_offset_ms: i64) -> Self {
+        SyncPattern {
+            tone,
+            video_offset: video_offset_ms * SAMPLE_RATE as i64 / 1000,
+        }
+    }
+
+    // appends interleaved stereo samples for the given range of sample
+    // positions to `out`
+    pub fn audio(&self, range: Range<usize>, out: &mut Vec<Sample>) {
+        for pos in range {
+            let sample = if is_marker(pos as i64) {
+                // restart the tone's phase with each marker
+                let t = (pos % SAMPLE_RATE) as f32 / SAMPLE_RATE as f32;
+                (t * self.tone * 2.0 * PI).sin() * MARKER_AMPLITUDE
+            } else {
+                0.0
+            };
+
+            out.push(sample);
+            out.push(sample);
+        }
+    }
+
+    // whether the video frame starting at the given sample position shows
+    // the marker
+    pub fn video_marker(&self, frame_start: usize) -> bool {
+        is_marker(frame_start as i64 - self.video_offset)
+    }
+}
+
+fn is_marker(pos: i64) -> bool {
+    (pos.rem_euclid(SAMPLE_RATE as i64) as usize) < MARKER_SAMPLES
+}
+
+// returns the positions of marker onsets in interleaved stereo audio, in
+// samples per channel. a marker begins where the signal rises above the
+// threshold after at least half a second of silence
+pub fn audio_marker_onsets(samples: &[Sample]) -> Vec<usize> {
+    let threshold = MARKER_AMPLITUDE / 4.0;
+    let min_gap = SAMPLE_RATE / 2;
+
+    let mut onsets = Vec::new();
+    let mut last_loud: Option<usize> = None;
+
+    for (pos, frame) in samples.chunks(CHANNELS).enumerate() {
+        let loud = frame.iter().any(|sample| sample.abs() > threshold);
+
+        if !loud {
+            continue;
+        }
+
+        if last_loud.map(|last| pos - last > min_gap).unwrap_or(true) {
+            onsets.push(pos);
+        }
+
+        last_loud = Some(pos);
+    }
+
+    onsets
+}
+
+// returns the indexes of frames where the video marker begins, given the
+// mean luma of each frame in sequence
+pub fn video_marker_onsets(frame_luma: &[u8]) -> Vec<usize> {
+    let threshold = (LUMA_BLACK as u16 + LUMA_WHITE as u16) / 2;
+
+    let mut onsets = Vec::new();
+    let mut previous = false;
+
+    for (idx, luma) in frame_luma.iter().enumerate() {
+        let marker = *luma as u16 > threshold;
+
+        if marker && !previous {
+            onsets.push(idx);
+        }
+
+        previous = marker;
+    }
+
+    onsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(pattern: &SyncPattern, seconds: usize) -> (Vec<Sample>, Vec<u8>) {
+        let mut audio = Vec::new();
+        pattern.audio(0..(seconds * SAMPLE_RATE), &mut audio);
+
+        let video = (0..(seconds * FRAMES_PER_SECOND))
+            .map(|frame| if pattern.video_marker(frame * SAMPLES_PER_FRAME) { LUMA_WHITE } else { LUMA_BLACK })
+            .collect();
+
+        (audio, video)
+    }
+
+    #[test]
+    fn markers_are_in_sync() {
+        let (audio, video) = render(&SyncPattern::new(1000.0, 0), 3);
+
+        // the tone starts at phase 0, so the first loud sample is just after
+        // each second boundary
+        let audio_onsets = audio_marker_onsets(&audio).into_iter()
+            .map(|pos| pos / SAMPLES_PER_FRAME)
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![0, 30, 60], audio_onsets);
+        assert_eq!(vec![0, 30, 60], video_marker_onsets(&video));
+    }
+
+    #[test]
+    fn video_offset_delays_video_marker() {
+        let (_, video) = render(&SyncPattern::new(1000.0, 100), 3);
+        assert_eq!(vec![3, 33, 63], video_marker_onsets(&video));
+    }
+}
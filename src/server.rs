@@ -8,6 +8,7 @@ use derive_more::From;
 use futures::sink::{Sink, SinkExt};
 use futures::stream::{self, Stream, StreamExt};
 use percent_encoding::percent_decode;
+use serde::Deserialize;
 use structopt::StructOpt;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc;
@@ -16,18 +17,23 @@ use warp::Filter;
 use warp::reply::{self, Reply};
 use warp::ws::{self, Ws, WebSocket};
 
-use mixlab_protocol::{ClientMessage, ServerMessage};
+use mixlab_protocol::{ClientMessage, ServerMessage, StreamReportId};
 
-use crate::engine::EngineEvent;
+use crate::engine::{self, EngineEvent};
 use crate::listen::{self, Disambiguation};
 use crate::project::{self, ProjectHandle, Notification};
-use crate::{icecast, module, rtmp};
+use crate::{diagram, guest, icecast, module, rtmp, viewer};
 
 #[derive(StructOpt)]
 pub struct RunOpts {
     #[structopt(short, long, default_value = "127.0.0.1:8000")]
     listen: SocketAddr,
-    workspace_path: PathBuf,
+    // only optional so that a subcommand can be given instead
+    #[structopt(required = true)]
+    workspace_path: Option<PathBuf>,
+    /// Record all engine input to this file, for later use with `mixlab replay`
+    #[structopt(long)]
+    record: Option<PathBuf>,
 }
 
 struct Server {
@@ -45,7 +51,13 @@ impl Server {
 }
 
 pub async fn run(opts: RunOpts) {
-    let project = project::open_or_create(opts.workspace_path).await
+    let workspace_path = opts.workspace_path.expect("workspace path is required");
+
+    let recorder = opts.record.map(|path| {
+        engine::Recorder::create(&path).expect("create recording")
+    });
+
+    let project = project::open_or_create(workspace_path, recorder).await
         .expect("create_or_open_project");
 
     let server = Arc::new(Server::new(project));
@@ -62,21 +74,28 @@ pub async fn run(opts: RunOpts) {
     let wasm = warp::path!("app.wasm")
         .map(wasm);
 
+    let catalog = warp::path!("_i18n" / String)
+        .and_then(|language: String| async move {
+            i18n(&language).ok_or_else(warp::reject::not_found)
+        });
+
     let static_content = warp::get()
         .and(index
             .or(style)
             .or(js)
-            .or(wasm));
+            .or(wasm)
+            .or(catalog));
 
     let websocket = warp::get()
         .and(warp::path("session"))
+        .and(warp::query::<SessionQuery>())
         .and(warp::ws())
         .map({
             let server = server.clone();
-            move |ws: Ws| {
+            move |query: SessionQuery, ws: Ws| {
                 let server = server.clone();
                 ws.on_upgrade(move |websocket| {
-                    session(websocket, server.clone())
+                    session(websocket, query, server.clone())
                 })
             }
         });
@@ -90,6 +109,44 @@ pub async fn run(opts: RunOpts) {
             })
         });
 
+    let guest_socket = warp::get()
+        .and(warp::path!("_guest" / String)
+            .map(|mountpoint: String| percent_decode(mountpoint.as_bytes()).decode_utf8_lossy().into_owned()))
+        .and(warp::query::<GuestQuery>())
+        .and(warp::ws())
+        .map(|mountpoint: String, query: GuestQuery, ws: Ws| {
+            ws.on_upgrade(move |websocket| guest::accept(mountpoint, query.key, websocket))
+        });
+
+    let hls = warp::get()
+        .and(warp::path!("_hls" / String / String))
+        .and_then(|name: String, file: String| async move {
+            serve_hls(&name, &file, false)
+        });
+
+    // viewer links, which stand in for the paths above. an HLS link is the
+    // playlist's directory, so its segments resolve through the link too
+    let viewer_socket = warp::get()
+        .and(warp::path!("_view" / String))
+        .and(warp::ws())
+        .and_then(|token: String, ws: Ws| async move {
+            match viewer::authorize(&token) {
+                Some(viewer::Target::Monitor(socket_id)) => {
+                    Ok(ws.on_upgrade(move |websocket| viewer::watch_monitor(token, socket_id, websocket)))
+                }
+                _ => Err(warp::reject::not_found()),
+            }
+        });
+
+    let viewer_hls = warp::get()
+        .and(warp::path!("_view" / String / String))
+        .and_then(|token: String, file: String| async move {
+            match viewer::authorize(&token) {
+                Some(viewer::Target::Hls(name)) => serve_hls(&name, &file, true),
+                _ => Err(warp::reject::not_found()),
+            }
+        });
+
     let media_upload = warp::post()
         .and(warp::path!("_upload" / String)
             .map(|filename: String| percent_decode(filename.as_bytes()).decode_utf8_lossy().into_owned()))
@@ -116,10 +173,42 @@ pub async fn run(opts: RunOpts) {
             }
         });
 
+    let report_export = warp::get()
+        .and(warp::path!("_reports" / i64 / String))
+        .and_then({
+            let server = server.clone();
+            move |id: i64, format: String| {
+                let server = server.clone();
+                async move {
+                    export_report(server, StreamReportId(id), &format).await
+                        .ok_or_else(warp::reject::not_found)
+                }
+            }
+        });
+
+    let patch_export = warp::get()
+        .and(warp::path!("_patch" / String))
+        .and_then({
+            let server = server.clone();
+            move |format: String| {
+                let server = server.clone();
+                async move {
+                    export_patch(server, &format).await
+                        .ok_or_else(warp::reject::not_found)
+                }
+            }
+        });
+
     let routes = static_content
         .or(websocket)
         .or(monitor_socket)
+        .or(guest_socket)
+        .or(hls)
+        .or(viewer_socket)
+        .or(viewer_hls)
         .or(media_upload)
+        .or(report_export)
+        .or(patch_export)
         .with(warp::log("mixlab-http"));
 
     let warp = warp::serve(routes);
@@ -162,6 +251,16 @@ fn content(content_type: &str, reply: impl Reply) -> impl Reply {
     reply::with_header(reply, "content-type", content_type)
 }
 
+fn serve_hls(name: &str, file: &str, via_link: bool) -> Result<impl Reply, warp::Rejection> {
+    module::hls_output::serve(name, file, via_link)
+        .map(|(content_type, data)| {
+            // players are often on other origins
+            let reply = reply::with_header(data.to_vec(), "access-control-allow-origin", "*");
+            content(content_type, reply)
+        })
+        .ok_or_else(warp::reject::not_found)
+}
+
 fn index() -> impl Reply {
     #[cfg(not(debug_assertions))]
     let index_html: &str = include_str!("../frontend/static/index.html");
@@ -194,13 +293,48 @@ fn wasm() -> impl Reply {
     content("application/wasm", app_wasm)
 }
 
-async fn session(websocket: WebSocket, server: ServerRef) {
+// message catalogs for the frontend, which has English built in
+fn i18n(language: &str) -> Option<impl Reply> {
+    #[cfg(not(debug_assertions))]
+    let catalog: &str = match language {
+        "en" => include_str!("../frontend/static/i18n/en.txt"),
+        "de" => include_str!("../frontend/static/i18n/de.txt"),
+        _ => return None,
+    };
+    #[cfg(debug_assertions)]
+    let catalog = match language {
+        "en" | "de" => std::fs::read_to_string(format!("frontend/static/i18n/{}.txt", language)).expect("frontend built"),
+        _ => return None,
+    };
+    Some(content("text/plain; charset=utf-8", catalog))
+}
+
+#[derive(Deserialize)]
+struct SessionQuery {
+    // permission group this session operates as, eg. `/session?group=audio`.
+    // chosen by the client and never checked, so groups keep operators out of
+    // each other's way but are not access control. anyone able to open a
+    // session can leave the group off and edit everything
+    group: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GuestQuery {
+    // the guest link's secret, checked against the StreamInput's stream key
+    key: Option<String>,
+}
+
+async fn session(websocket: WebSocket, query: SessionQuery, server: ServerRef) {
     let (tx, rx) = websocket.split();
     let mut tx = ClientTx(tx);
 
     let notifications = server.project.notifications();
 
-    let (state, engine_ops, engine) = server.project.connect_engine().await
+    // snapshots cover the whole workspace, so sessions in a permission group
+    // may not touch them. saved macros are shared by everyone, so likewise
+    let manages_snapshots = query.group.is_none();
+
+    let (state, engine_ops, engine) = server.project.connect_engine(query.group).await
         .expect("connect engine");
 
     let library = server.project.fetch_media_library().await
@@ -214,6 +348,31 @@ async fn session(websocket: WebSocket, server: ServerRef) {
         .await
         .expect("tx.send MediaLibrary");
 
+    let reports = server.project.fetch_stream_reports().await
+        .expect("fetch_stream_reports");
+
+    tx.send(ServerMessage::StreamReports(reports))
+        .await
+        .expect("tx.send StreamReports");
+
+    tx.send(ServerMessage::MidiDevices(server.project.midi_devices()))
+        .await
+        .expect("tx.send MidiDevices");
+
+    let snapshots = server.project.fetch_snapshots().await
+        .expect("fetch_snapshots");
+
+    tx.send(ServerMessage::Snapshots(snapshots))
+        .await
+        .expect("tx.send Snapshots");
+
+    let macros = server.project.fetch_macros().await
+        .expect("fetch_macros");
+
+    tx.send(ServerMessage::Macros(macros))
+        .await
+        .expect("tx.send Macros");
+
     enum Event {
         ClientMessage(Result<ws::Message, warp::Error>),
         Engine(Result<EngineEvent, broadcast::RecvError>),
@@ -246,6 +405,47 @@ async fn session(websocket: WebSocket, server: ServerRef) {
                             println!("Engine update failed: {:?}", e);
                         }
                     }
+                    ClientMessage::SaveSnapshot(_) |
+                    ClientMessage::ApplySnapshot(_) |
+                    ClientMessage::DeleteSnapshot(_) |
+                    ClientMessage::SaveMacro(_) |
+                    ClientMessage::DeleteMacro(_) if !manages_snapshots => {
+                        // client should have guarded against this, just drop
+                        eprintln!("dropping {:?}, not permitted for session", msg);
+                    }
+                    ClientMessage::SaveSnapshot(name) => {
+                        if let Err(e) = server.project.save_snapshot(name).await {
+                            eprintln!("failed to save snapshot: {:?}", e);
+                        }
+                    }
+                    ClientMessage::ApplySnapshot(name) => {
+                        match server.project.fetch_snapshot(name).await {
+                            Ok(Some(workspace)) => {
+                                if let Err(e) = engine.load_workspace(workspace) {
+                                    println!("Engine update failed: {:?}", e);
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                eprintln!("failed to fetch snapshot: {:?}", e);
+                            }
+                        }
+                    }
+                    ClientMessage::DeleteSnapshot(name) => {
+                        if let Err(e) = server.project.delete_snapshot(name).await {
+                            eprintln!("failed to delete snapshot: {:?}", e);
+                        }
+                    }
+                    ClientMessage::SaveMacro(params) => {
+                        if let Err(e) = server.project.save_macro(params).await {
+                            eprintln!("failed to save macro: {:?}", e);
+                        }
+                    }
+                    ClientMessage::DeleteMacro(name) => {
+                        if let Err(e) = server.project.delete_macro(name).await {
+                            eprintln!("failed to delete macro: {:?}", e);
+                        }
+                    }
                 }
             }
             Event::Engine(Err(broadcast::RecvError::Lagged(skipped))) => {
@@ -293,6 +493,33 @@ async fn session(websocket: WebSocket, server: ServerRef) {
                             }
                         }
                     }
+                    Notification::StreamReports => {
+                        match server.project.fetch_stream_reports().await {
+                            Ok(reports) => Some(ServerMessage::StreamReports(reports)),
+                            Err(e) => {
+                                eprintln!("failed to query stream reports: {:?}", e);
+                                None
+                            }
+                        }
+                    }
+                    Notification::Snapshots => {
+                        match server.project.fetch_snapshots().await {
+                            Ok(snapshots) => Some(ServerMessage::Snapshots(snapshots)),
+                            Err(e) => {
+                                eprintln!("failed to query snapshots: {:?}", e);
+                                None
+                            }
+                        }
+                    }
+                    Notification::Macros => {
+                        match server.project.fetch_macros().await {
+                            Ok(macros) => Some(ServerMessage::Macros(macros)),
+                            Err(e) => {
+                                eprintln!("failed to query macros: {:?}", e);
+                                None
+                            }
+                        }
+                    }
                 };
 
                 if let Some(msg) = msg {
@@ -309,6 +536,59 @@ async fn session(websocket: WebSocket, server: ServerRef) {
     }
 }
 
+async fn export_report(server: ServerRef, id: StreamReportId, format: &str) -> Option<reply::Response> {
+    let report = match server.project.fetch_stream_report(id).await {
+        Ok(report) => report?,
+        Err(e) => {
+            eprintln!("failed to fetch stream report: {:?}", e);
+            return None;
+        }
+    };
+
+    let (content_type, filename, body) = match format {
+        "json" => ("application/json", format!("stream-report-{}.json", id.0), serde_json::to_string_pretty(&report).expect("serde_json::to_string_pretty")),
+        "csv" => ("text/csv; charset=utf-8", format!("stream-report-{}.csv", id.0), project::report::to_csv(&report)),
+        "loudness" => {
+            let loudness = match server.project.fetch_stream_loudness(id).await {
+                Ok(loudness) => loudness,
+                Err(e) => {
+                    eprintln!("failed to fetch stream loudness log: {:?}", e);
+                    return None;
+                }
+            };
+
+            ("text/csv; charset=utf-8", format!("stream-loudness-{}.csv", id.0), project::report::loudness_to_csv(&report, &loudness))
+        }
+        _ => return None,
+    };
+
+    let disposition = format!("attachment; filename=\"{}\"", filename);
+
+    Some(reply::with_header(content(content_type, body), "content-disposition", disposition).into_response())
+}
+
+async fn export_patch(server: ServerRef, format: &str) -> Option<reply::Response> {
+    // the diagram is drawn from the same state a session starts with. the
+    // session ends again as soon as it is dropped
+    let state = match server.project.connect_engine(None).await {
+        Ok((state, _, _)) => state,
+        Err(e) => {
+            eprintln!("failed to fetch workspace for patch export: {:?}", e);
+            return None;
+        }
+    };
+
+    let (content_type, filename, body) = match format {
+        "svg" => ("image/svg+xml", "patch.svg", diagram::to_svg(&state)),
+        "dot" => ("text/vnd.graphviz; charset=utf-8", "patch.dot", diagram::to_dot(&state)),
+        _ => return None,
+    };
+
+    let disposition = format!("attachment; filename=\"{}\"", filename);
+
+    Some(reply::with_header(content(content_type, body), "content-disposition", disposition).into_response())
+}
+
 #[derive(From, Debug)]
 enum UploadError {
     Warp(warp::Error),
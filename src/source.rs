@@ -5,9 +5,12 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use ringbuf::{RingBuffer, Producer, Consumer};
+use serde::{Serialize, Deserialize};
 
+use mixlab_protocol::StreamLinkStats;
 use mixlab_util::time::MediaTime;
 
+use crate::engine;
 use crate::util::Sequence;
 use crate::video;
 
@@ -23,7 +26,16 @@ struct RegistryInner {
 struct Source {
     shared: Arc<SourceShared>,
     seq: Sequence,
-    tx: Option<TxPair>,
+    listener_seq: Sequence,
+    listeners: Arc<Mutex<Vec<Listener>>>,
+    sender_connected: bool,
+}
+
+// every listener has buffers of its own which the sender copies each frame
+// into, so one decode of a source can feed any number of receivers
+struct Listener {
+    id: NonZeroUsize,
+    tx: TxPair,
 }
 
 struct TxPair {
@@ -31,33 +43,41 @@ struct TxPair {
     video: Producer<Frame<VideoData>>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SourceId(NonZeroUsize);
 
 #[derive(Debug)]
 pub struct SourceShared {
     channel_name: String,
     recv_online: AtomicBool,
+    info: Mutex<SourceInfo>,
+    // senders must present this key to connect, when set
+    stream_key: Mutex<Option<String>>,
 }
 
-#[derive(Debug)]
-pub enum ListenError {
-    AlreadyInUse,
+// describes the media the currently connected sender is producing, for
+// display to the user
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SourceInfo {
+    pub audio_codec: Option<String>,
+    // bits per second, where the sender or stream format reports it
+    pub audio_bitrate: Option<u32>,
+    pub video_codec: Option<String>,
+    pub link: Option<StreamLinkStats>,
 }
 
 #[derive(Debug)]
 pub enum ConnectError {
     NoMountpoint,
     AlreadyConnected,
+    Unauthorized,
 }
 
 pub struct SourceSend {
     registry: Registry,
     shared: Arc<SourceShared>,
     source_id: SourceId,
-    // this is, regrettably, an Option because we need to take the tx pair
-    // and put it back in the mountpoints table on drop:
-    tx: Option<TxPair>,
+    listeners: Arc<Mutex<Vec<Listener>>>,
 }
 
 pub type AudioData = Vec<i16>;
@@ -73,6 +93,10 @@ pub struct Frame<T> {
 pub struct SourceRecv {
     registry: Registry,
     shared: Arc<SourceShared>,
+    listener_id: NonZeroUsize,
+    // name reads are recorded under in the engine's tape, unique among the
+    // listeners to a source
+    tap_name: String,
     audio_rx: Consumer<Frame<AudioData>>,
     video_rx: Consumer<Frame<VideoData>>,
 }
@@ -86,44 +110,62 @@ impl Registry {
         Registry { inner: Arc::new(Mutex::new(inner)) }
     }
 
-    pub fn listen(&self, channel_name: &str) -> Result<SourceRecv, ListenError> {
+    /// Receives from a channel, creating it if this is the first
+    /// receiver. Receivers to the same channel all share one sender.
+    pub fn listen(&self, channel_name: &str) -> SourceRecv {
         let mut registry = self.inner.lock()
             .expect("registry lock");
 
-        if registry.channels.contains_key(channel_name) {
-            return Err(ListenError::AlreadyInUse);
-        }
+        let source = registry.channels.entry(channel_name.to_owned())
+            .or_insert_with(|| Source {
+                shared: Arc::new(SourceShared {
+                    channel_name: channel_name.to_owned(),
+                    recv_online: AtomicBool::new(true),
+                    info: Mutex::new(SourceInfo::default()),
+                    stream_key: Mutex::new(None),
+                }),
+                seq: Sequence::new(),
+                listener_seq: Sequence::new(),
+                listeners: Arc::new(Mutex::new(Vec::new())),
+                sender_connected: false,
+            });
 
         let (audio_tx, audio_rx) = RingBuffer::<Frame<AudioData>>::new(65536).split();
         let (video_tx, video_rx) = RingBuffer::<Frame<VideoData>>::new(65536).split();
 
-        let shared = Arc::new(SourceShared {
-            channel_name: channel_name.to_owned(),
-            recv_online: AtomicBool::new(true),
-        });
+        let listener_id = source.listener_seq.next();
+
+        source.listeners.lock()
+            .expect("listeners lock")
+            .push(Listener {
+                id: listener_id,
+                tx: TxPair {
+                    audio: audio_tx,
+                    video: video_tx,
+                },
+            });
+
+        // the first listener keeps the plain channel name so that recordings
+        // made before sources could be shared still replay
+        let tap_name = if listener_id.get() == 1 {
+            channel_name.to_owned()
+        } else {
+            format!("{}#{}", channel_name, listener_id)
+        };
 
-        let recv = SourceRecv {
+        SourceRecv {
             registry: self.clone(),
-            shared: shared.clone(),
+            shared: source.shared.clone(),
+            listener_id,
+            tap_name,
             audio_rx,
             video_rx,
-        };
-
-        let source = Source {
-            shared: shared.clone(),
-            seq: Sequence::new(),
-            tx: Some(TxPair {
-                audio: audio_tx,
-                video: video_tx,
-            }),
-        };
-
-        registry.channels.insert(channel_name.to_owned(), source);
-
-        Ok(recv)
+        }
     }
 
-    pub fn connect(&self, channel_name: &str) -> Result<SourceSend, ConnectError> {
+    /// Sends to a channel that has receivers. Channels with a stream key
+    /// set only accept senders presenting the same key.
+    pub fn connect(&self, channel_name: &str, stream_key: Option<&str>) -> Result<SourceSend, ConnectError> {
         let mut registry = self.inner.lock()
             .expect("registry lock");
 
@@ -132,56 +174,83 @@ impl Registry {
             Some(source) => source,
         };
 
-        let source_id = SourceId(source.seq.next());
+        if !source.shared.accepts(stream_key) {
+            return Err(ConnectError::Unauthorized);
+        }
+
+        if source.sender_connected {
+            return Err(ConnectError::AlreadyConnected);
+        }
 
-        let tx = source.tx.take().ok_or(ConnectError::AlreadyConnected)?;
+        source.sender_connected = true;
 
         Ok(SourceSend {
             registry: self.clone(),
             shared: source.shared.clone(),
-            tx: Some(tx),
-            source_id,
+            source_id: SourceId(source.seq.next()),
+            listeners: source.listeners.clone(),
         })
     }
 }
 
+impl SourceShared {
+    fn accepts(&self, stream_key: Option<&str>) -> bool {
+        match self.stream_key.lock().expect("stream key lock").as_ref() {
+            Some(expected_key) => stream_key == Some(expected_key.as_str()),
+            None => true,
+        }
+    }
+}
+
 impl SourceSend {
     pub fn connected(&self) -> bool {
         self.shared.recv_online.load(Ordering::Relaxed)
     }
 
-    pub fn write_audio(&mut self, timestamp: MediaTime, data: AudioData) -> Result<(), ()> {
-        if self.connected() {
-            // tx is always Some for a valid (non-dropped) SourceSend:
-            let tx = self.tx.as_mut().unwrap();
+    // checks keys against the channel's current stream key, for protocols
+    // where a sender presents its key after the channel is connected to
+    pub fn authorizer(&self) -> impl Fn(Option<&str>) -> bool + Send + 'static {
+        let shared = self.shared.clone();
+        move |stream_key| shared.accepts(stream_key)
+    }
 
-            let frame = Frame {
-                source_id: self.source_id,
-                source_time: timestamp,
-                data,
-            };
+    pub fn set_info(&mut self, f: impl FnOnce(&mut SourceInfo)) {
+        f(&mut self.shared.info.lock().expect("source info lock"));
+    }
 
-            tx.audio.push(frame).map_err(|_| ())
-        } else {
-            Err(())
-        }
+    pub fn write_audio(&mut self, timestamp: MediaTime, data: AudioData) -> Result<(), ()> {
+        self.write(timestamp, data, |tx| &mut tx.audio)
     }
 
     pub fn write_video(&mut self, timestamp: MediaTime, data: VideoData) -> Result<(), ()> {
-        if self.connected() {
-            // tx is always Some for a valid (non-dropped) SourceSend:
-            let tx = self.tx.as_mut().unwrap();
+        self.write(timestamp, data, |tx| &mut tx.video)
+    }
+
+    // succeeds if at least one listener took the frame. a listener with full
+    // buffers only misses out itself
+    fn write<T: Clone>(&mut self, timestamp: MediaTime, data: T, producer: impl Fn(&mut TxPair) -> &mut Producer<Frame<T>>) -> Result<(), ()> {
+        if !self.connected() {
+            return Err(());
+        }
 
+        let mut listeners = self.listeners.lock()
+            .expect("listeners lock");
+
+        let mut result = Err(());
+
+        for listener in listeners.iter_mut() {
             let frame = Frame {
                 source_id: self.source_id,
                 source_time: timestamp,
-                data,
+                data: data.clone(),
             };
 
-            tx.video.push(frame).map_err(|_| ())
-        } else {
-            Err(())
+            if producer(&mut listener.tx).push(frame).is_ok() {
+                result = Ok(());
+            }
         }
+
+        result
     }
 }
 
@@ -193,15 +262,17 @@ impl Debug for SourceSend {
 
 impl Drop for SourceSend {
     fn drop(&mut self) {
+        self.set_info(|info| *info = SourceInfo::default());
+
         let mut registry = self.registry.inner.lock()
             .expect("registry lock");
 
         match registry.channels.get_mut(&self.shared.channel_name) {
             None => {
-                // receiver has disconnected, there is nothing to do
+                // receivers have all disconnected, there is nothing to do
             }
             Some(channel) => {
-                channel.tx = self.tx.take();
+                channel.sender_connected = false;
             }
         }
     }
@@ -218,22 +289,93 @@ impl SourceRecv {
         &self.shared.channel_name
     }
 
+    // applies to the whole channel, so the last listener to set a key wins.
+    // a sender already connected stays connected
+    pub fn set_stream_key(&self, stream_key: Option<String>) {
+        *self.shared.stream_key.lock().expect("stream key lock") = stream_key;
+    }
+
+    // reads go via the engine's tape so that sessions can be recorded and
+    // replayed, see engine::replay
+
+    pub fn info(&self) -> SourceInfo {
+        let shared = &self.shared;
+        engine::tap_info(&self.tap_name,
+            || shared.info.lock().expect("source info lock").clone())
+    }
+
     pub fn read_audio(&mut self) -> Option<Frame<AudioData>> {
-        self.audio_rx.pop()
+        let audio_rx = &mut self.audio_rx;
+        engine::tap_audio(&self.tap_name, || audio_rx.pop())
     }
 
     pub fn read_video(&mut self) -> Option<Frame<VideoData>> {
-        self.video_rx.pop()
+        let video_rx = &mut self.video_rx;
+        engine::tap_video(&self.tap_name, || video_rx.pop())
     }
 }
 
 impl Drop for SourceRecv {
     fn drop(&mut self) {
-        self.registry.inner.lock()
-            .expect("registry lock")
-            .channels
-            .remove(&self.shared.channel_name);
+        let mut registry = self.registry.inner.lock()
+            .expect("registry lock");
+
+        let channel = match registry.channels.get_mut(&self.shared.channel_name) {
+            Some(channel) => channel,
+            None => return,
+        };
+
+        let mut listeners = channel.listeners.lock()
+            .expect("listeners lock");
+
+        listeners.retain(|listener| listener.id != self.listener_id);
+
+        // the channel goes away with its last listener, which tells the
+        // sender to stop
+        if listeners.is_empty() {
+            drop(listeners);
+            registry.channels.remove(&self.shared.channel_name);
+            self.shared.recv_online.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listeners_share_one_sender() {
+        let registry = Registry::new();
+
+        let mut first = registry.listen("camera");
+        let mut second = registry.listen("camera");
+
+        let mut send = registry.connect("camera", None).expect("connect");
+        assert!(registry.connect("camera", None).is_err());
+
+        send.write_audio(MediaTime::zero(), vec![1, 2]).expect("write");
+
+        assert_eq!(vec![1, 2], first.read_audio().expect("first read").data);
+        assert_eq!(vec![1, 2], second.read_audio().expect("second read").data);
+
+        // the source stays up until its last listener goes
+        drop(first);
+        assert!(send.connected());
+
+        drop(second);
+        assert!(!send.connected());
+    }
+
+    #[test]
+    fn stream_key_is_checked_on_connect() {
+        let registry = Registry::new();
+
+        let recv = registry.listen("camera");
+        recv.set_stream_key(Some("secret".to_owned()));
 
-        self.shared.recv_online.store(false, Ordering::Relaxed);
+        assert!(matches!(registry.connect("camera", None), Err(ConnectError::Unauthorized)));
+        assert!(matches!(registry.connect("camera", Some("guess")), Err(ConnectError::Unauthorized)));
+        assert!(registry.connect("camera", Some("secret")).is_ok());
     }
 }
@@ -0,0 +1,13 @@
+// Fixtures shared between unit tests.
+
+/// Deterministic white noise in -0.5..0.5, the same for the same seed.
+pub(crate) fn noise(len: usize, seed: u32) -> Vec<f32> {
+    let mut state = seed;
+
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+        })
+        .collect()
+}
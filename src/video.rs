@@ -1,8 +1,16 @@
+pub mod composite;
 pub mod encode;
+pub mod font;
+#[cfg(test)]
+pub mod golden;
+
+use std::sync::Arc;
+
+use bytes::Bytes;
 
 use mixlab_codec::ffmpeg::media::Video;
 use mixlab_codec::ffmpeg::AvFrame;
-use mixlab_util::time::MediaDuration;
+use mixlab_util::time::{MediaDuration, MediaTime};
 
 #[derive(Debug, Clone)]
 pub struct Frame {
@@ -11,4 +19,36 @@ pub struct Frame {
     // frame duration in fractional seconds, possibly an estimate if frame
     // duration information is not available:
     pub duration_hint: MediaDuration,
+
+    // whether this frame was decoded from a key frame in its source stream.
+    // frames generated by the engine itself have no source stream and are
+    // always key frames:
+    pub key_frame: bool,
+
+    // compressed data this frame was decoded from, when it came from an AVC
+    // source. anything that changes the picture produces a frame without it:
+    pub passthrough: Option<Arc<Passthrough>>,
+}
+
+/// Compressed AVC data carried alongside decoded frames, so that outputs can
+/// forward a source without re-encoding it.
+#[derive(Debug)]
+pub struct Passthrough {
+    // avcC decoder configuration record from the source's sequence header
+    pub config: Bytes,
+    // packets sent to the decoder since the previous frame came out of it, in
+    // decode order. with reordered frames these are not this frame's packets,
+    // but taken over successive frames they are the whole stream
+    pub packets: Vec<PassthroughPacket>,
+}
+
+#[derive(Debug)]
+pub struct PassthroughPacket {
+    // increments by one for each packet from a source, so gaps from dropped
+    // frames can be detected
+    pub seq: u64,
+    pub decode_timestamp: MediaTime,
+    pub composition_time: MediaDuration,
+    pub is_key_frame: bool,
+    pub data: Bytes,
 }
@@ -0,0 +1,263 @@
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::{AvFrame, PictureSettings, PixelFormat};
+use mixlab_protocol::{Rgb, TextPosition};
+
+/// A picture with an alpha channel, converted ahead of time for compositing
+/// over yuv420p frames. Converting is the expensive part, so overlays are
+/// kept for as long as the picture and its size stay the same.
+#[derive(Debug)]
+pub struct Overlay {
+    width: usize,
+    height: usize,
+    // luma and alpha of each pixel
+    luma: Vec<[u8; 2]>,
+    // chroma and alpha of each 2x2 block, chroma weighted by the alpha of
+    // the pixels it comes from
+    chroma: Vec<[u8; 3]>,
+}
+
+impl Overlay {
+    /// Converts an rgba picture.
+    pub fn new(frame: &AvFrame<Video>) -> Self {
+        let picture = frame.picture_settings();
+
+        if picture.pixel_format != PixelFormat::rgba() {
+            panic!("overlay must be rgba, got {:?}", picture.pixel_format);
+        }
+
+        let data = frame.frame_data();
+        let (ptr, stride) = unsafe { (data.data(0), data.stride(0)) };
+
+        Overlay::convert(picture.width, picture.height, |x, y| unsafe {
+            let pixel = ptr.add(y * stride + x * 4);
+            [*pixel, *pixel.add(1), *pixel.add(2), *pixel.add(3)]
+        })
+    }
+
+    fn convert(width: usize, height: usize, rgba: impl Fn(usize, usize) -> [u8; 4]) -> Self {
+        let mut luma = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let [r, g, b, a] = rgba(x, y);
+                luma.push([yuv(Rgb(r, g, b))[0], a]);
+            }
+        }
+
+        let chroma_width = (width + 1) / 2;
+        let chroma_height = (height + 1) / 2;
+        let mut chroma = Vec::with_capacity(chroma_width * chroma_height);
+
+        for y in 0..chroma_height {
+            for x in 0..chroma_width {
+                let (mut u, mut v, mut alpha) = (0, 0, 0);
+
+                for (px, py) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+                    let (px, py) = (x * 2 + px, y * 2 + py);
+
+                    if px >= width || py >= height {
+                        continue;
+                    }
+
+                    let [r, g, b, a] = rgba(px, py);
+                    let [_, pixel_u, pixel_v] = yuv(Rgb(r, g, b));
+
+                    u += pixel_u as u32 * a as u32;
+                    v += pixel_v as u32 * a as u32;
+                    alpha += a as u32;
+                }
+
+                if alpha == 0 {
+                    chroma.push([128, 128, 0]);
+                } else {
+                    // the block covers four pixels of the picture underneath
+                    // even where the overlay has fewer to put over them
+                    chroma.push([(u / alpha) as u8, (v / alpha) as u8, (alpha / 4) as u8]);
+                }
+            }
+        }
+
+        Overlay { width, height, luma, chroma }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Blends the overlay into a yuv420p frame with its top left corner at
+    /// `x`, `y`, rounded down to even. Anything past the edges of the frame
+    /// is cut off.
+    pub fn composite(&self, frame: &mut AvFrame<Video>, x: usize, y: usize, opacity: f64) {
+        let opacity = (opacity.max(0.0).min(1.0) * 256.0) as u32;
+
+        if opacity == 0 {
+            return;
+        }
+
+        // chroma is subsampled 2x2, keep the overlay's blocks on its grid
+        let (x, y) = (x & !1, y & !1);
+
+        let picture = frame.picture_settings();
+        let right = (x + self.width).min(picture.width);
+        let bottom = (y + self.height).min(picture.height);
+
+        let alpha = |a: u8| (a as u32 * opacity / 255) as u16;
+
+        let data = frame.frame_data_mut();
+
+        unsafe {
+            let planes = [
+                (data.data(0), data.stride(0)),
+                (data.data(1), data.stride(1)),
+                (data.data(2), data.stride(2)),
+            ];
+
+            let pixel = |plane: usize, x: usize, y: usize| {
+                let (ptr, stride) = planes[plane];
+                ptr.add(y * stride + x)
+            };
+
+            for picture_y in y..bottom {
+                for picture_x in x..right {
+                    let [value, a] = self.luma[(picture_y - y) * self.width + (picture_x - x)];
+                    blend(pixel(0, picture_x, picture_y), value, alpha(a));
+                }
+            }
+
+            let chroma_width = (self.width + 1) / 2;
+
+            for picture_y in (y / 2)..((bottom + 1) / 2) {
+                for picture_x in (x / 2)..((right + 1) / 2) {
+                    let [u, v, a] = self.chroma[(picture_y - y / 2) * chroma_width + (picture_x - x / 2)];
+                    blend(pixel(1, picture_x, picture_y), u, alpha(a));
+                    blend(pixel(2, picture_x, picture_y), v, alpha(a));
+                }
+            }
+        }
+    }
+}
+
+/// Blends a yuv420p `picture` into a yuv420p `frame` with its top left
+/// corner at `x`, `y`, rounded down to even. `alpha` gives the weight out of
+/// 256 of each 2x2 block of the picture, by its position in the chroma
+/// planes. Anything past the edges of the frame is cut off.
+pub fn blend_picture(frame: &mut AvFrame<Video>, picture: &AvFrame<Video>, x: usize, y: usize, alpha: impl Fn(usize, usize) -> u16) {
+    let (x, y) = (x & !1, y & !1);
+
+    let settings = picture.picture_settings();
+    let right = (x + settings.width).min(frame.picture_settings().width);
+    let bottom = (y + settings.height).min(frame.picture_settings().height);
+
+    let src = picture.frame_data();
+    let dst = frame.frame_data_mut();
+
+    unsafe {
+        for plane in 0..3 {
+            // chroma planes are half size, rounding up
+            let shift = if plane == 0 { 0 } else { 1 };
+            let (left, top) = (x >> shift, y >> shift);
+
+            for frame_y in top..((bottom + shift) >> shift) {
+                for frame_x in left..((right + shift) >> shift) {
+                    let (picture_x, picture_y) = (frame_x - left, frame_y - top);
+                    let value = *src.data(plane).add(picture_y * src.stride(plane) + picture_x);
+                    let a = alpha(picture_x >> (1 - shift), picture_y >> (1 - shift));
+
+                    blend(dst.data(plane).add(frame_y * dst.stride(plane) + frame_x), value, a);
+                }
+            }
+        }
+    }
+}
+
+/// The yuv420p picture settings frames are converted to for compositing
+/// over. Chroma is subsampled 2x2, so sizes round up to even.
+pub fn yuv420p(picture: &PictureSettings) -> PictureSettings {
+    PictureSettings::yuv420p((picture.width + 1) & !1, (picture.height + 1) & !1)
+}
+
+/// Luma and chroma of an sRGB colour, BT.601 limited range.
+pub fn yuv(Rgb(r, g, b): Rgb) -> [u8; 3] {
+    let (r, g, b) = (r as f64, g as f64, b as f64);
+
+    let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+    let u = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+    let v = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+
+    [y.round() as u8, u.round() as u8, v.round() as u8]
+}
+
+/// Where a box of the given size sits in a picture of the given size, a
+/// margin in from the edges it is placed against.
+pub fn place(position: TextPosition, box_size: (usize, usize), picture: (usize, usize)) -> (usize, usize) {
+    let (box_width, box_height) = box_size;
+    let (width, height) = picture;
+    let margin = height / 20;
+
+    let left = margin;
+    let center = width.saturating_sub(box_width) / 2;
+    let right = width.saturating_sub(box_width + margin);
+
+    let top = margin;
+    let middle = height.saturating_sub(box_height) / 2;
+    let bottom = height.saturating_sub(box_height + margin);
+
+    match position {
+        TextPosition::TopLeft => (left, top),
+        TextPosition::Top => (center, top),
+        TextPosition::TopRight => (right, top),
+        TextPosition::Center => (center, middle),
+        TextPosition::BottomLeft => (left, bottom),
+        TextPosition::Bottom => (center, bottom),
+        TextPosition::BottomRight => (right, bottom),
+    }
+}
+
+/// Mixes `value` into the byte at `ptr`, `alpha` out of 256.
+pub unsafe fn blend(ptr: *mut u8, value: u8, alpha: u16) {
+    let mixed = (*ptr as u16 * (256 - alpha) + value as u16 * alpha) / 256;
+    *ptr = mixed as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_and_black_are_limited_range() {
+        assert_eq!([235, 128, 128], yuv(Rgb(255, 255, 255)));
+        assert_eq!([16, 128, 128], yuv(Rgb(0, 0, 0)));
+    }
+
+    #[test]
+    fn boxes_are_placed_a_margin_in() {
+        let picture = (1280, 720);
+        let size = (200, 50);
+
+        assert_eq!((36, 36), place(TextPosition::TopLeft, size, picture));
+        assert_eq!((540, 335), place(TextPosition::Center, size, picture));
+        assert_eq!((1044, 634), place(TextPosition::BottomRight, size, picture));
+    }
+
+    #[test]
+    fn chroma_comes_from_opaque_pixels_only() {
+        // opaque white on the left, transparent black on the right
+        let overlay = Overlay::convert(2, 2, |x, _| {
+            if x == 0 { [255, 255, 255, 255] } else { [0, 0, 0, 0] }
+        });
+
+        assert_eq!(vec![[235, 255], [16, 0], [235, 255], [16, 0]], overlay.luma);
+        assert_eq!(vec![[128, 128, 127]], overlay.chroma);
+    }
+
+    #[test]
+    fn partial_blocks_are_weighted_by_the_whole_block() {
+        let overlay = Overlay::convert(3, 1, |_, _| [0, 0, 0, 255]);
+
+        assert_eq!(vec![[128, 128, 127], [128, 128, 63]], overlay.chroma);
+    }
+}
@@ -2,7 +2,7 @@ use std::cmp;
 use std::collections::VecDeque;
 use std::convert::TryInto;
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use fdk_aac::enc as aac;
 use num_rational::Ratio;
 
@@ -29,6 +29,8 @@ pub struct EncodeStream {
     video_segments: VecDeque<VideoSegment>,
     video_timestamp: MediaTime,
     video_ctx: VideoCtx,
+    // configuration of a replaced encoder, sent with its first segment
+    video_header: Option<Bytes>,
 }
 
 impl EncodeStream {
@@ -40,6 +42,7 @@ impl EncodeStream {
             video_segments: VecDeque::new(),
             video_timestamp: MediaTime::new(0, 1),
             video_ctx,
+            video_header: None,
         }
     }
 
@@ -75,6 +78,47 @@ impl EncodeStream {
         self.encode_video(duration, frame);
     }
 
+    // queues an already compressed segment in place of encoding a frame, for
+    // outputs forwarding their input as is
+    pub fn send_video_segment(&mut self, segment: VideoSegment) {
+        let end_timestamp = segment.decode_timestamp + segment.duration;
+
+        if self.video_timestamp < end_timestamp {
+            self.video_timestamp = end_timestamp;
+        }
+
+        self.video_segments.push_back(segment);
+    }
+
+    // replaces the video encoder, dropping any frames the old one still holds.
+    // receivers are sent the new encoder's configuration ahead of its first
+    // segment
+    pub fn reset_video(&mut self, video_ctx: VideoCtx) {
+        let mut header = BytesMut::new();
+        video_ctx.decoder_configuration_record().write_to(&mut header);
+
+        self.video_ctx = video_ctx;
+        self.video_header = Some(header.freeze());
+    }
+
+    pub fn video_timestamp(&self) -> MediaTime {
+        self.video_timestamp
+    }
+
+    pub fn audio_ctx(&self) -> &AudioCtx {
+        &self.audio_ctx
+    }
+
+    pub fn video_ctx(&self) -> &VideoCtx {
+        &self.video_ctx
+    }
+
+    // makes the next frame sent a key frame, for receivers joining part way
+    // through the stream
+    pub fn force_keyframe(&mut self) {
+        self.video_ctx.force_keyframe();
+    }
+
     pub fn barrier(&mut self, timestamp: MediaTime) {
         if self.video_timestamp < timestamp {
             let duration = timestamp - self.video_timestamp;
@@ -101,6 +145,7 @@ impl EncodeStream {
             self.video_segments.push_back(VideoSegment {
                 decode_timestamp: MediaTime::new(packet.decode_timestamp(), time_base),
                 duration: MediaDuration::new(duration_in_base, time_base),
+                sequence_header: self.video_header.take(),
                 frame: AvcFrame {
                     is_key_frame: packet.is_key_frame(),
                     composition_time: MediaDuration::new(packet.presentation_timestamp() - packet.decode_timestamp(), time_base),
@@ -140,6 +185,9 @@ pub struct AudioSegment {
 pub struct VideoSegment {
     pub decode_timestamp: MediaTime,
     pub duration: MediaDuration,
+    // avcC decoder configuration to send ahead of this segment, when it
+    // differs from the segments before it
+    pub sequence_header: Option<Bytes>,
     pub frame: AvcFrame,
 }
 
@@ -180,7 +228,7 @@ impl AudioCtx {
         Bytes::copy_from_slice(&info.confBuf[0..info.confSize as usize])
     }
 
-    fn send_audio(&mut self, samples: &[Sample]) -> Option<(MediaDuration, Bytes)> {
+    pub fn send_audio(&mut self, samples: &[Sample]) -> Option<(MediaDuration, Bytes)> {
         self.pcm_buff.extend(samples.iter().copied().map(|sample| {
             // TODO set CLIP flag if sample is out of range
             let sample = if sample > 1.0 {
@@ -227,16 +275,23 @@ pub struct VideoCtx {
     scaler: DynamicScaler,
     blank_frame: AvFrame<Video>,
     time_base: i64,
+    // in time base units
+    keyframe_interval: Option<i64>,
+    next_keyframe: i64,
 }
 
 pub struct VideoParams {
     pub picture: PictureSettings,
     pub time_base: usize,
     pub profile: Profile,
+    // forces a key frame at least this often, however many frames that is
+    pub keyframe_interval: Option<MediaDuration>,
 }
 
 pub enum Profile {
     Monitor,
+    // smaller and cheaper than the monitor, for glancing at an output
+    Preview,
     Stream,
 }
 
@@ -244,6 +299,8 @@ impl VideoCtx {
     pub fn new(params: VideoParams) -> Self {
         let time_base = params.time_base;
         let picture = params.picture;
+        let keyframe_interval = params.keyframe_interval
+            .map(|interval| interval.round_to_base(time_base as i64).max(1));
 
         let params = AvcParams {
             time_base: time_base,
@@ -254,19 +311,23 @@ impl VideoCtx {
             rate_control: match params.profile {
                 // cannot use constant bitrate in zero latency mode apparently:
                 Profile::Monitor => RateControl::ConstantQuality { crf: 30 },
+                Profile::Preview => RateControl::ConstantQuality { crf: 36 },
                 Profile::Stream => RateControl::ConstantBitRate { bitrate: 1_500_000 },
             },
             preset: match params.profile {
                 Profile::Monitor => Preset::Veryfast,
+                Profile::Preview => Preset::Ultrafast,
                 Profile::Stream => Preset::Slow,
             },
             tune: match params.profile {
-                Profile::Monitor => Some(Tune::Zerolatency),
+                Profile::Monitor | Profile::Preview => Some(Tune::Zerolatency),
                 Profile::Stream => Some(Tune::Film),
             },
             gop_size: match params.profile {
-                Profile::Monitor => Some(1), // every frame is key frame
-                Profile::Stream => Some(60),
+                Profile::Monitor | Profile::Preview => Some(1), // every frame is key frame
+                // key frames are placed by keyframe_interval rather than
+                // frame count, as the frame rate varies with the sources
+                Profile::Stream => None,
             },
         };
 
@@ -277,6 +338,8 @@ impl VideoCtx {
             scaler: DynamicScaler::new(picture.clone()),
             blank_frame: AvFrame::blank(&picture),
             time_base: time_base.try_into().unwrap(),
+            keyframe_interval,
+            next_keyframe: 0,
         }
     }
 
@@ -285,8 +348,18 @@ impl VideoCtx {
     }
 
     pub fn send_frame(&mut self, mut frame: AvFrame<Video>) {
-        // clear picture type so x264 can make its own decisions about keyframes:
-        frame.set_picture_type(mixlab_codec::ffmpeg::sys::AVPictureType_AV_PICTURE_TYPE_NONE);
+        let pts = frame.presentation_timestamp();
+
+        let picture_type = match self.keyframe_interval {
+            Some(interval) if pts >= self.next_keyframe => {
+                self.next_keyframe = pts + interval;
+                sys::AVPictureType_AV_PICTURE_TYPE_I
+            }
+            // clear picture type so x264 can make its own decisions about keyframes:
+            _ => sys::AVPictureType_AV_PICTURE_TYPE_NONE,
+        };
+
+        frame.set_picture_type(picture_type);
 
         // scale picture to expected size if necessary
         let frame = self.scaler.scale(&mut frame);
@@ -294,6 +367,10 @@ impl VideoCtx {
         self.codec.send_frame(frame).unwrap();
     }
 
+    pub fn force_keyframe(&mut self) {
+        self.next_keyframe = i64::min_value();
+    }
+
     pub fn recv_packet(&mut self) -> Option<AvPacket> {
         match self.codec.recv_packet() {
             Ok(pkt) => Some(pkt),
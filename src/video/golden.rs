@@ -0,0 +1,203 @@
+// Golden image comparisons for video modules. Known frames go in, and the
+// output planes are compared against images stored under fixtures/ with a
+// per-sample tolerance, so that refactors of the pixel loops (SIMD, GPU) can't
+// subtly shift colours or alignment without a test noticing.
+//
+// Golden images are yuv420p stored as PGM: the luma plane on top and the two
+// chroma planes side by side beneath it, the usual layout for viewing raw yuv
+// as greyscale. Run tests with MIXLAB_BLESS_GOLDEN=1 to (re)write goldens from
+// the current output after checking the change is intended.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use mixlab_codec::ffmpeg::media::Video;
+use mixlab_codec::ffmpeg::{AvFrame, PictureSettings, PixelFormat};
+use mixlab_util::time::MediaDuration;
+
+use crate::engine::{self, TICKS_PER_SECOND};
+use crate::video;
+
+const PLANES: usize = 3;
+
+// a frame with distinct ramps in every plane, so that crossed planes,
+// transposed axes or off by one offsets all show up. `seed` shifts the ramps
+// to give different frames of the same size
+pub fn test_card(picture: &PictureSettings, seed: usize) -> AvFrame<Video> {
+    let mut frame = AvFrame::blank(picture);
+
+    {
+        let data = frame.frame_data_mut();
+
+        for plane in 0..PLANES {
+            let (width, height) = plane_size(picture, plane);
+
+            for y in 0..height {
+                for x in 0..width {
+                    let value = match plane {
+                        0 => 16 + (x + y * 3 + seed * 37) % 220,
+                        1 => 16 + (x * 2 + seed * 53) % 225,
+                        _ => 16 + (y * 4 + seed * 91) % 225,
+                    };
+
+                    unsafe {
+                        *data.data(plane).add(y * data.stride(plane) + x) = value as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    frame
+}
+
+// a decoded key frame lasting one tick, as it arrives on a video input
+pub fn video_frame(decoded: AvFrame<Video>) -> engine::VideoFrame {
+    engine::VideoFrame {
+        data: video::Frame {
+            decoded,
+            duration_hint: MediaDuration::new(1, TICKS_PER_SECOND as i64),
+            key_frame: true,
+            passthrough: None,
+        },
+        tick_offset: MediaDuration::new(0, 1),
+    }
+}
+
+// panics if any sample of `frame` differs from the golden image `name` by more
+// than `tolerance`
+pub fn assert_matches(name: &str, frame: &AvFrame<Video>, tolerance: u8) {
+    let actual = encode_pgm(frame);
+    let path = golden_path(name);
+
+    if env::var_os("MIXLAB_BLESS_GOLDEN").is_some() {
+        fs::write(&path, &actual).expect("write golden image");
+        return;
+    }
+
+    let expected = fs::read(&path).unwrap_or_else(|e| {
+        panic!("reading golden image {}: {} (run with MIXLAB_BLESS_GOLDEN=1 to create it)", path.display(), e)
+    });
+
+    if let Some(mismatch) = compare(&frame.picture_settings(), &expected, &actual, tolerance) {
+        // leave the actual output next to the golden for inspection
+        let actual_path = path.with_extension("actual.pgm");
+        fs::write(&actual_path, &actual).expect("write actual image");
+
+        panic!("{} does not match golden image: {} (actual output written to {})",
+            name, mismatch, actual_path.display());
+    }
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("fixtures/video/golden");
+    path.push(name);
+    path.set_extension("pgm");
+    path
+}
+
+fn plane_size(picture: &PictureSettings, plane: usize) -> (usize, usize) {
+    assert!(picture.pixel_format == PixelFormat::yuv420p(), "golden images are yuv420p only");
+
+    if plane == 0 {
+        (picture.width, picture.height)
+    } else {
+        (picture.width / 2, picture.height / 2)
+    }
+}
+
+fn encode_pgm(frame: &AvFrame<Video>) -> Vec<u8> {
+    let picture = frame.picture_settings();
+    let data = frame.frame_data();
+
+    let (chroma_width, chroma_height) = plane_size(&picture, 1);
+
+    let mut pgm = format!("P5\n{} {}\n255\n", picture.width, picture.height + chroma_height).into_bytes();
+
+    let row = |plane: usize, y: usize, width: usize| unsafe {
+        std::slice::from_raw_parts(data.data(plane).add(y * data.stride(plane)), width)
+    };
+
+    for y in 0..picture.height {
+        pgm.extend(row(0, y, picture.width));
+    }
+
+    for y in 0..chroma_height {
+        pgm.extend(row(1, y, chroma_width));
+        pgm.extend(row(2, y, chroma_width));
+    }
+
+    pgm
+}
+
+// describes the first sample out of tolerance along with the overall extent
+// of the difference, or None if the images match
+fn compare(picture: &PictureSettings, expected: &[u8], actual: &[u8], tolerance: u8) -> Option<String> {
+    if expected.len() != actual.len() || !expected.starts_with(pgm_header(actual)) {
+        return Some(format!("expected {:?}, got {:?}",
+            String::from_utf8_lossy(pgm_header(expected)),
+            String::from_utf8_lossy(pgm_header(actual))));
+    }
+
+    let header_len = pgm_header(actual).len();
+    let expected = &expected[header_len..];
+    let actual = &actual[header_len..];
+
+    let mut first = None;
+    let mut count = 0;
+    let mut max_diff = 0;
+
+    for (idx, (e, a)) in expected.iter().zip(actual).enumerate() {
+        let diff = (*e as i16 - *a as i16).abs() as u8;
+
+        if diff > tolerance {
+            first = first.or(Some((idx, *e, *a)));
+            count += 1;
+            max_diff = max_diff.max(diff);
+        }
+    }
+
+    first.map(|(idx, expected, actual)| {
+        format!("{}: expected {}, got {}; {} samples out of tolerance, max difference {}",
+            sample_name(picture, idx), expected, actual, count, max_diff)
+    })
+}
+
+// the magic, dimensions and maxval lines
+fn pgm_header(pgm: &[u8]) -> &[u8] {
+    let mut newlines = 0;
+
+    for (idx, byte) in pgm.iter().enumerate() {
+        if *byte == b'\n' {
+            newlines += 1;
+
+            if newlines == 3 {
+                return &pgm[..(idx + 1)];
+            }
+        }
+    }
+
+    pgm
+}
+
+// maps an offset into the pgm body back to plane and coordinates
+fn sample_name(picture: &PictureSettings, idx: usize) -> String {
+    let luma_len = picture.width * picture.height;
+
+    if idx < luma_len {
+        return format!("Y({}, {})", idx % picture.width, idx / picture.width);
+    }
+
+    let (chroma_width, _) = plane_size(picture, 1);
+    let idx = idx - luma_len;
+    let x = idx % picture.width;
+    let y = idx / picture.width;
+
+    if x < chroma_width {
+        format!("U({}, {})", x, y)
+    } else {
+        format!("V({}, {})", x - chroma_width, y)
+    }
+}
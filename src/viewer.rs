@@ -0,0 +1,81 @@
+//! Expiring links for sharing an HLS Output or Monitor with one viewer,
+//! rather than handing out its raw path. Links are kept in the workspace,
+//! and the engine publishes what each one currently points to here so that
+//! the server can check them without going through the engine thread.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::future;
+use uuid::Uuid;
+use warp::ws::WebSocket;
+
+use crate::module;
+
+// how often a watching viewer's link is checked, which bounds how long they
+// keep watching after it expires or is revoked
+const RECHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+lazy_static::lazy_static! {
+    static ref GRANTS: Mutex<HashMap<String, Grant>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone)]
+pub struct Grant {
+    pub target: Target,
+    pub expires: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum Target {
+    Hls(String),
+    Monitor(Uuid),
+}
+
+pub fn new_token() -> String {
+    Uuid::new_v4().to_simple().to_string()
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+/// Replaces every grant, revoking any links not among them.
+pub fn publish(grants: HashMap<String, Grant>) {
+    *GRANTS.lock().unwrap() = grants;
+}
+
+/// What the link with this token gives access to, if it still does.
+pub fn authorize(token: &str) -> Option<Target> {
+    let grants = GRANTS.lock().unwrap();
+    let grant = grants.get(token)?;
+
+    if unix_now() < grant.expires {
+        Some(grant.target.clone())
+    } else {
+        None
+    }
+}
+
+/// Streams a monitor to a viewer until their link stops working.
+pub async fn watch_monitor(token: String, socket_id: Uuid, websocket: WebSocket) {
+    let stream = module::monitor::stream(socket_id, websocket);
+
+    let revoked = async {
+        loop {
+            tokio::time::delay_for(RECHECK_INTERVAL).await;
+
+            if authorize(&token).is_none() {
+                break;
+            }
+        }
+    };
+
+    futures::pin_mut!(stream, revoked);
+
+    // dropping the stream closes the viewer's websocket
+    future::select(stream, revoked).await;
+}
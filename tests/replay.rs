@@ -0,0 +1,61 @@
+mod support;
+
+use std::fs;
+use std::process::Command;
+use std::time::Duration;
+
+use tokio::time;
+
+use mixlab_protocol::{ModuleParams, OscillatorParams, Waveform, WindowGeometry, WorkspaceOp, InputId, OutputId};
+
+use support::{created_id, temp_path, TestServer};
+
+fn replay(recording: &std::path::Path) -> String {
+    let workspace_path = temp_path("mixlab-replay");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mixlab"))
+        .arg("replay")
+        .arg(recording)
+        .arg(&workspace_path)
+        .output()
+        .expect("run mixlab replay");
+
+    let _ = fs::remove_file(workspace_path.with_extension("mixlab"));
+
+    assert!(output.status.success(), "replay failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).expect("replay output utf-8")
+}
+
+#[tokio::test]
+async fn replays_recorded_session_deterministically() {
+    let recording = temp_path("mixlab-recording");
+
+    {
+        let server = TestServer::start_with_args(&["--record".as_ref(), recording.as_os_str()]).await;
+        let mut client = server.connect().await;
+
+        let osc = created_id(&client.apply(WorkspaceOp::CreateModule(
+            ModuleParams::Oscillator(OscillatorParams { freq: 440.0, waveform: Waveform::Sine, ..OscillatorParams::default() }),
+            WindowGeometry::default())).await);
+
+        let panner = created_id(&client.apply(WorkspaceOp::CreateModule(
+            ModuleParams::StereoPanner(()),
+            WindowGeometry::default())).await);
+
+        client.apply(WorkspaceOp::CreateConnection(InputId(panner, 0), OutputId(osc, 0))).await;
+        client.apply(WorkspaceOp::DeleteModule(osc)).await;
+
+        // the recording is written from a background thread, give it a
+        // moment before the server is killed
+        time::delay_for(Duration::from_millis(200)).await;
+    }
+
+    let first = replay(&recording);
+    let second = replay(&recording);
+
+    let _ = fs::remove_file(&recording);
+
+    assert!(first.lines().any(|line| line.contains("CreateConnection")), "unexpected replay output:\n{}", first);
+    assert!(first.lines().any(|line| line.contains("DeleteModule")), "unexpected replay output:\n{}", first);
+    assert_eq!(first, second);
+}
@@ -0,0 +1,43 @@
+mod support;
+
+use std::time::Duration;
+
+use tokio::time;
+
+use mixlab_protocol::{ModuleParams, StreamInputParams, StreamProtocol, WindowGeometry, WorkspaceOp, ServerUpdate, Indication};
+
+use support::{created_id, TestServer};
+
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[tokio::test]
+async fn stream_input_receives_simulated_publish() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let input = created_id(&client.apply(WorkspaceOp::CreateModule(
+        ModuleParams::StreamInput(StreamInputParams {
+            protocol: Some(StreamProtocol::Rtmp),
+            mountpoint: Some("simulated".to_owned()),
+            ..StreamInputParams::default()
+        }),
+        WindowGeometry::default())).await);
+
+    let _publisher = server.simulate_publish("simulated", 30);
+
+    // the stream input indicates codecs as it receives each sequence header
+    time::timeout(PUBLISH_TIMEOUT, async {
+        loop {
+            match client.recv_update().await {
+                ServerUpdate::UpdateModuleIndication(id, Indication::StreamInput(indication)) if id == input => {
+                    if indication.audio_codec.is_some() && indication.video_codec.is_some() {
+                        assert_eq!(Some("AAC"), indication.audio_codec.as_deref());
+                        assert_eq!(Some("H.264"), indication.video_codec.as_deref());
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }).await.expect("timed out waiting for stream input to receive publish");
+}
@@ -0,0 +1,239 @@
+// Harness for tests which run against a real mixlab server process. Each
+// `TestServer` gets its own project file in the temp directory and listens on
+// an ephemeral port.
+
+#![allow(unused)]
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::time;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+use mixlab_protocol::{ClientMessage, ClientSequence, ModuleId, ServerMessage, ServerUpdate, WorkspaceMessage, WorkspaceOp, WorkspaceState};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+const RUNNING_BANNER: &str = "Mixlab is now running at http://";
+
+pub struct TestServer {
+    pub addr: SocketAddr,
+    workspace_path: PathBuf,
+    _process: Child,
+}
+
+impl TestServer {
+    pub async fn start() -> TestServer {
+        TestServer::start_with_args(&[]).await
+    }
+
+    pub async fn start_with_args(args: &[&OsStr]) -> TestServer {
+        let workspace_path = temp_path("mixlab-test");
+
+        let mut process = Command::new(env!("CARGO_BIN_EXE_mixlab"))
+            .arg("--listen").arg("127.0.0.1:0")
+            .args(args)
+            .arg(&workspace_path)
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .expect("spawn mixlab server");
+
+        let mut stdout = BufReader::new(process.stdout.take().expect("server stdout")).lines();
+
+        let addr = time::timeout(STARTUP_TIMEOUT, async {
+            while let Some(line) = stdout.next().await {
+                let line = line.expect("read server stdout");
+
+                if line.starts_with(RUNNING_BANNER) {
+                    return line[RUNNING_BANNER.len()..].parse::<SocketAddr>()
+                        .expect("parse server address");
+                }
+            }
+
+            panic!("server exited before it started listening");
+        }).await.expect("server startup timed out");
+
+        // keep draining stdout so the server never blocks on a full pipe
+        tokio::spawn(async move {
+            while let Some(_) = stdout.next().await {}
+        });
+
+        TestServer {
+            addr,
+            workspace_path,
+            _process: process,
+        }
+    }
+
+    pub async fn connect(&self) -> TestClient {
+        TestClient::connect(self.addr, None).await
+    }
+
+    pub async fn connect_group(&self, group: &str) -> TestClient {
+        TestClient::connect(self.addr, Some(group)).await
+    }
+
+    /// Directory the server writes recordings to, next to its project.
+    pub fn recordings_dir(&self) -> PathBuf {
+        self.workspace_path.with_extension("recordings")
+    }
+
+    /// Publishes a synthetic stream to the server's RTMP ingest on the given
+    /// mountpoint. The publisher is killed when the returned handle is dropped.
+    pub fn simulate_publish(&self, mountpoint: &str, duration_secs: u64) -> Child {
+        Command::new(env!("CARGO_BIN_EXE_mixlab"))
+            .arg("simulate-publish")
+            .arg(format!("rtmp://{}/{}", self.addr, mountpoint))
+            .arg("--duration").arg(duration_secs.to_string())
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .expect("spawn mixlab simulate-publish")
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        // the server process itself is killed when its handle is dropped
+        let mut sqlite_path = self.workspace_path.clone();
+        sqlite_path.set_extension("mixlab");
+        let _ = fs::remove_file(sqlite_path);
+        let _ = fs::remove_dir_all(self.recordings_dir());
+    }
+}
+
+pub fn temp_path(prefix: &str) -> PathBuf {
+    env::temp_dir().join(format!("{}-{}", prefix, Uuid::new_v4()))
+}
+
+pub struct TestClient {
+    websocket: WebSocketStream<TcpStream>,
+    seq: usize,
+    pub state: WorkspaceState,
+}
+
+impl TestClient {
+    async fn connect(addr: SocketAddr, group: Option<&str>) -> TestClient {
+        let url = match group {
+            Some(group) => format!("ws://{}/session?group={}", addr, group),
+            None => format!("ws://{}/session", addr),
+        };
+
+        let (mut websocket, _) = tokio_tungstenite::connect_async(url).await
+            .expect("connect session websocket");
+
+        // the server always opens a session with the workspace state
+        let state = match recv_message(&mut websocket).await {
+            ServerMessage::WorkspaceState(state) => state,
+            msg => panic!("expected WorkspaceState, got {:?}", msg),
+        };
+
+        TestClient {
+            websocket,
+            seq: 0,
+            state,
+        }
+    }
+
+    /// Sends a workspace op, returning the sequence number the server will
+    /// acknowledge it with.
+    pub async fn send(&mut self, op: WorkspaceOp) -> ClientSequence {
+        self.seq += 1;
+
+        let sequence = ClientSequence(NonZeroUsize::new(self.seq).unwrap());
+
+        self.send_message(ClientMessage::Workspace(WorkspaceMessage { sequence, op })).await;
+
+        sequence
+    }
+
+    /// Sends a message outside of the sequenced workspace ops, eg. snapshots.
+    pub async fn send_message(&mut self, msg: ClientMessage) {
+        let packet = bincode::serialize(&msg).expect("bincode::serialize");
+
+        self.websocket.send(Message::Binary(packet)).await
+            .expect("send client message");
+    }
+
+    pub async fn recv(&mut self) -> ServerMessage<'static> {
+        recv_message(&mut self.websocket).await
+    }
+
+    /// Sends an op and collects every update up to the server's
+    /// acknowledgement of it.
+    pub async fn apply(&mut self, op: WorkspaceOp) -> Vec<ServerUpdate> {
+        let sequence = self.send(op).await;
+        self.recv_until_sync(sequence).await
+    }
+
+    pub async fn recv_until_sync(&mut self, sequence: ClientSequence) -> Vec<ServerUpdate> {
+        let mut updates = Vec::new();
+
+        loop {
+            match self.recv().await {
+                ServerMessage::Update(update) => updates.push(update),
+                ServerMessage::Sync(synced) if synced == sequence => return updates,
+                ServerMessage::Sync(synced) => panic!("out of order sync: expected {:?}, got {:?}", sequence, synced),
+                msg => panic!("unexpected message: {:?}", msg),
+            }
+        }
+    }
+
+    /// Receives the next workspace update, eg. one caused by another session.
+    pub async fn recv_update(&mut self) -> ServerUpdate {
+        match self.recv().await {
+            ServerMessage::Update(update) => update,
+            msg => panic!("expected Update, got {:?}", msg),
+        }
+    }
+}
+
+pub fn created_id(updates: &[ServerUpdate]) -> ModuleId {
+    updates.iter()
+        .find_map(|update| match update {
+            ServerUpdate::CreateModule { id, .. } => Some(*id),
+            _ => None,
+        })
+        .expect("CreateModule update")
+}
+
+// receives the next message, ignoring the periodic and informational messages
+// which are not part of the workspace protocol
+async fn recv_message(websocket: &mut WebSocketStream<TcpStream>) -> ServerMessage<'static> {
+    loop {
+        let msg = time::timeout(RECV_TIMEOUT, websocket.next()).await
+            .expect("timed out waiting for server message")
+            .expect("session websocket closed")
+            .expect("recv server message");
+
+        let packet = match msg {
+            Message::Binary(packet) => packet,
+            _ => continue,
+        };
+
+        match bincode::deserialize::<ServerMessage<'static>>(&packet).expect("bincode::deserialize") {
+            ServerMessage::Performance(_) |
+            ServerMessage::MediaLibrary(_) |
+            ServerMessage::StreamReports(_) |
+            ServerMessage::MidiDevices(_) |
+            ServerMessage::Snapshots(_) |
+            ServerMessage::Macros(_) => continue,
+            msg => return msg,
+        }
+    }
+}
@@ -0,0 +1,510 @@
+mod support;
+
+use std::time::Duration;
+
+use tokio::time;
+
+use mixlab_protocol::{ChannelAdapter, ClientMessage, Indication, ModuleId, ModuleParams, ModuleSafety, OscillatorParams, LfoParams, MixerParams, RecorderParams, StreamOutputParams, Waveform, WindowGeometry, WorkspaceOp, ServerUpdate, InputId, OutputId, LineType, FreezeState};
+
+use support::{created_id, TestClient, TestServer};
+
+fn oscillator() -> WorkspaceOp {
+    WorkspaceOp::CreateModule(
+        ModuleParams::Oscillator(OscillatorParams { freq: 440.0, waveform: Waveform::Sine, ..OscillatorParams::default() }),
+        WindowGeometry::default())
+}
+
+fn stereo_panner() -> WorkspaceOp {
+    WorkspaceOp::CreateModule(ModuleParams::StereoPanner(()), WindowGeometry::default())
+}
+
+#[tokio::test]
+async fn create_module_is_broadcast_to_all_sessions() {
+    let server = TestServer::start().await;
+
+    let mut alice = server.connect().await;
+    let mut bob = server.connect().await;
+
+    assert!(alice.state.modules.is_empty());
+
+    let updates = alice.apply(oscillator()).await;
+
+    let id = match updates.as_slice() {
+        [ServerUpdate::CreateModule { id, params: ModuleParams::Oscillator(_), outputs, .. }] => {
+            assert_eq!(vec![LineType::Mono, LineType::Stereo],
+                outputs.iter().map(|terminal| terminal.line_type()).collect::<Vec<_>>());
+            *id
+        }
+        _ => panic!("unexpected updates: {:?}", updates),
+    };
+
+    match bob.recv_update().await {
+        ServerUpdate::CreateModule { id: bob_id, .. } => assert_eq!(id, bob_id),
+        update => panic!("unexpected update: {:?}", update),
+    }
+
+    // sessions connecting later see the module in their initial state
+    let carol = server.connect().await;
+    assert_eq!(vec![id], carol.state.modules.iter().map(|(id, _)| *id).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn connections_must_match_line_type() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let osc = created_id(&client.apply(oscillator()).await);
+    let panner = created_id(&client.apply(stereo_panner()).await);
+    let mixer = created_id(&client.apply(WorkspaceOp::CreateModule(ModuleParams::Mixer(MixerParams::with_channels(2)), WindowGeometry::default())).await);
+
+    // stereo output into a fader's control input is dropped
+    let updates = client.apply(WorkspaceOp::CreateConnection(InputId(mixer, 2), OutputId(osc, 1))).await;
+    assert!(updates.is_empty(), "unexpected updates: {:?}", updates);
+
+    let updates = client.apply(WorkspaceOp::CreateConnection(InputId(panner, 0), OutputId(osc, 0))).await;
+
+    match updates.as_slice() {
+        [ServerUpdate::CreateConnection(input, output)] => {
+            assert_eq!(InputId(panner, 0), *input);
+            assert_eq!(OutputId(osc, 0), *output);
+        }
+        _ => panic!("unexpected updates: {:?}", updates),
+    }
+}
+
+#[tokio::test]
+async fn mono_and_stereo_connect_through_an_adapter() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let osc = created_id(&client.apply(oscillator()).await);
+    let panner = created_id(&client.apply(stereo_panner()).await);
+
+    // stereo output into a mono input
+    let updates = client.apply(WorkspaceOp::CreateConnection(InputId(panner, 0), OutputId(osc, 1))).await;
+    assert!(matches!(updates.as_slice(), [ServerUpdate::CreateConnection(..)]), "unexpected updates: {:?}", updates);
+
+    let updates = client.apply(WorkspaceOp::UpdateConnectionAdapter(InputId(panner, 0), ChannelAdapter::Right)).await;
+    assert!(matches!(updates.as_slice(),
+        [ServerUpdate::UpdateConnectionAdapter(input, ChannelAdapter::Right)] if *input == InputId(panner, 0)));
+
+    let other = server.connect().await;
+    assert_eq!(vec![(InputId(panner, 0), ChannelAdapter::Right)], other.state.adapters);
+
+    // connections of matching line types have no adapter to set
+    client.apply(WorkspaceOp::CreateConnection(InputId(panner, 1), OutputId(osc, 0))).await;
+    let updates = client.apply(WorkspaceOp::UpdateConnectionAdapter(InputId(panner, 1), ChannelAdapter::Left)).await;
+    assert!(updates.is_empty(), "unexpected updates: {:?}", updates);
+
+    // and reconnecting an input goes back to both channels
+    client.apply(WorkspaceOp::CreateConnection(InputId(panner, 0), OutputId(osc, 1))).await;
+    assert!(server.connect().await.state.adapters.is_empty());
+}
+
+#[tokio::test]
+async fn deleting_a_module_removes_its_connections() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let osc = created_id(&client.apply(oscillator()).await);
+    let panner = created_id(&client.apply(stereo_panner()).await);
+
+    client.apply(WorkspaceOp::CreateConnection(InputId(panner, 1), OutputId(osc, 0))).await;
+
+    let updates = client.apply(WorkspaceOp::DeleteModule(osc)).await;
+
+    match updates.as_slice() {
+        [ServerUpdate::DeleteConnection(input), ServerUpdate::DeleteModule(deleted)] => {
+            assert_eq!(InputId(panner, 1), *input);
+            assert_eq!(osc, *deleted);
+        }
+        _ => panic!("unexpected updates: {:?}", updates),
+    }
+
+    let state = server.connect().await.state;
+    assert!(state.connections.is_empty());
+    assert_eq!(vec![panner], state.modules.iter().map(|(id, _)| *id).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn protected_modules_must_be_armed_for_each_destructive_op() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let osc = created_id(&client.apply(oscillator()).await);
+    let output = created_id(&client.apply(WorkspaceOp::CreateModule(ModuleParams::StreamOutput(StreamOutputParams::default()), WindowGeometry::default())).await);
+
+    client.apply(WorkspaceOp::CreateConnection(InputId(output, 1), OutputId(osc, 1))).await;
+    client.apply(WorkspaceOp::UpdateModuleSafety(output, ModuleSafety::Safe)).await;
+
+    // dropped while safe, so acknowledged without any update
+    let updates = apply_ignoring_indications(&mut client, WorkspaceOp::DeleteConnection(InputId(output, 1))).await;
+    assert!(updates.is_empty(), "unexpected updates: {:?}", updates);
+
+    let updates = apply_ignoring_indications(&mut client, WorkspaceOp::DeleteModule(output)).await;
+    assert!(updates.is_empty(), "unexpected updates: {:?}", updates);
+
+    // protection can't be removed without arming first either
+    let updates = apply_ignoring_indications(&mut client, WorkspaceOp::UpdateModuleSafety(output, ModuleSafety::Unprotected)).await;
+    assert!(updates.is_empty(), "unexpected updates: {:?}", updates);
+
+    // once armed, one disconnect goes through and disarms the module again
+    client.apply(WorkspaceOp::UpdateModuleSafety(output, ModuleSafety::Armed)).await;
+
+    let updates = apply_ignoring_indications(&mut client, WorkspaceOp::DeleteConnection(InputId(output, 1))).await;
+
+    match updates.as_slice() {
+        [ServerUpdate::DeleteConnection(input), ServerUpdate::UpdateModuleSafety(id, ModuleSafety::Safe)] => {
+            assert_eq!(InputId(output, 1), *input);
+            assert_eq!(output, *id);
+        }
+        _ => panic!("unexpected updates: {:?}", updates),
+    }
+
+    let state = server.connect().await.state;
+    assert!(state.connections.is_empty());
+    assert_eq!(vec![(output, ModuleSafety::Safe)], state.safety);
+
+    // so deleting needs arming again
+    let updates = apply_ignoring_indications(&mut client, WorkspaceOp::DeleteModule(output)).await;
+    assert!(updates.is_empty(), "unexpected updates: {:?}", updates);
+
+    client.apply(WorkspaceOp::UpdateModuleSafety(output, ModuleSafety::Armed)).await;
+
+    let updates = apply_ignoring_indications(&mut client, WorkspaceOp::DeleteModule(output)).await;
+    assert!(matches!(updates.as_slice(), [ServerUpdate::DeleteModule(id)] if *id == output), "unexpected updates: {:?}", updates);
+
+    let state = server.connect().await.state;
+    assert_eq!(vec![osc], state.modules.iter().map(|(id, _)| *id).collect::<Vec<_>>());
+    assert!(state.safety.is_empty());
+}
+
+// updates from an op, less the indications modules send in among them as
+// they run
+async fn apply_ignoring_indications(client: &mut TestClient, op: WorkspaceOp) -> Vec<ServerUpdate> {
+    client.apply(op).await.into_iter()
+        .filter(|update| !matches!(update, ServerUpdate::UpdateModuleIndication(..)))
+        .collect()
+}
+
+#[tokio::test]
+async fn grouped_sessions_cannot_edit_other_modules() {
+    let server = TestServer::start().await;
+
+    let mut admin = server.connect().await;
+    let mut audio = server.connect_group("audio").await;
+
+    let osc = created_id(&admin.apply(oscillator()).await);
+    audio.recv_update().await;
+
+    // not permitted, so acknowledged without any update
+    let updates = audio.apply(WorkspaceOp::DeleteModule(osc)).await;
+    assert!(updates.is_empty(), "unexpected updates: {:?}", updates);
+
+    // modules created by a grouped session belong to its group
+    let updates = audio.apply(stereo_panner()).await;
+    let panner = created_id(&updates);
+
+    assert!(updates.iter().any(|update| match update {
+        ServerUpdate::UpdateModuleGroup(id, Some(group)) => *id == panner && group == "audio",
+        _ => false,
+    }));
+
+    let updates = audio.apply(WorkspaceOp::DeleteModule(panner)).await;
+    assert!(matches!(updates.as_slice(), [ServerUpdate::DeleteModule(id)] if *id == panner));
+}
+
+#[tokio::test]
+async fn bypass_is_kept_for_new_sessions() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let panner = created_id(&client.apply(stereo_panner()).await);
+
+    let updates = client.apply(WorkspaceOp::UpdateModuleBypass(panner, true)).await;
+    assert!(matches!(updates.as_slice(), [ServerUpdate::UpdateModuleBypass(id, true)] if *id == panner));
+
+    let other = server.connect().await;
+    assert_eq!(vec![panner], other.state.bypassed);
+
+    // grouped sessions may only bypass their own modules
+    let mut audio = server.connect_group("audio").await;
+    let updates = audio.apply(WorkspaceOp::UpdateModuleBypass(panner, false)).await;
+    assert!(updates.is_empty(), "unexpected updates: {:?}", updates);
+}
+
+#[tokio::test]
+async fn viewer_links_are_checked_and_revoked() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let monitor = created_id(&client.apply(
+        WorkspaceOp::CreateModule(ModuleParams::Monitor(()), WindowGeometry::default())).await);
+
+    let updates = client.apply(WorkspaceOp::CreateViewerLink(monitor, "client".to_owned(), 3600)).await;
+
+    let token = match updates.as_slice() {
+        [ServerUpdate::UpdateViewerLinks(links)] if links.len() == 1 && links[0].module == monitor => {
+            links[0].token.clone()
+        }
+        _ => panic!("unexpected updates: {:?}", updates),
+    };
+
+    let watch = |token: &str| tokio_tungstenite::connect_async(format!("ws://{}/_view/{}", server.addr, token));
+
+    assert!(watch(&token).await.is_ok());
+    assert!(watch("not-a-token").await.is_err());
+
+    // grouped sessions may not hand out links
+    let mut audio = server.connect_group("audio").await;
+    let updates = audio.apply(WorkspaceOp::CreateViewerLink(monitor, "other".to_owned(), 3600)).await;
+    assert!(updates.is_empty(), "unexpected updates: {:?}", updates);
+
+    let updates = client.apply(WorkspaceOp::RevokeViewerLink(token.clone())).await;
+    assert!(matches!(updates.as_slice(), [ServerUpdate::UpdateViewerLinks(links)] if links.is_empty()));
+
+    assert!(watch(&token).await.is_err());
+}
+
+#[tokio::test]
+async fn applying_a_snapshot_restores_modules_and_connections() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let osc = created_id(&client.apply(oscillator()).await);
+    let panner = created_id(&client.apply(stereo_panner()).await);
+
+    client.apply(WorkspaceOp::CreateConnection(InputId(panner, 1), OutputId(osc, 0))).await;
+    client.send_message(ClientMessage::SaveSnapshot("show".to_owned())).await;
+
+    client.apply(WorkspaceOp::DeleteModule(osc)).await;
+    client.send_message(ClientMessage::ApplySnapshot("show".to_owned())).await;
+
+    // a load ends by restoring the workspace wide settings
+    let mut updates = Vec::new();
+
+    loop {
+        let update = client.recv_update().await;
+        let done = matches!(update, ServerUpdate::UpdateOverrunPolicy(_));
+        updates.push(update);
+
+        if done {
+            break;
+        }
+    }
+
+    assert!(matches!(updates[0], ServerUpdate::DeleteModule(id) if id == panner));
+
+    let mut created = updates.iter()
+        .filter_map(|update| match update {
+            ServerUpdate::CreateModule { id, .. } => Some(*id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    created.sort();
+
+    assert_eq!(vec![osc, panner], created);
+
+    assert!(updates.iter().any(|update| match update {
+        ServerUpdate::CreateConnection(input, output) => *input == InputId(panner, 1) && *output == OutputId(osc, 0),
+        _ => false,
+    }));
+}
+
+#[tokio::test]
+async fn freezing_an_output_swaps_in_a_media_source() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let osc = created_id(&client.apply(oscillator()).await);
+    let panner = created_id(&client.apply(stereo_panner()).await);
+
+    client.apply(WorkspaceOp::CreateConnection(InputId(panner, 1), OutputId(osc, 0))).await;
+
+    let updates = client.apply(WorkspaceOp::FreezeOutput(OutputId(osc, 0), 0.1)).await;
+
+    assert!(matches!(updates.as_slice(), [ServerUpdate::UpdateFreezes(freezes)]
+        if matches!(freezes[0].state, FreezeState::Rendering { .. })));
+
+    // the render runs in realtime, then the chain is swapped out
+    let mut updates = Vec::new();
+
+    let (source, modules) = loop {
+        let update = client.recv_update().await;
+
+        if let ServerUpdate::UpdateFreezes(freezes) = &update {
+            if let FreezeState::Frozen { source, modules, .. } = &freezes[0].state {
+                break (*source, modules.clone());
+            }
+        }
+
+        updates.push(update);
+    };
+
+    assert_eq!(vec![osc], modules);
+    assert_eq!(source, created_id(&updates));
+
+    assert!(updates.iter().any(|update| match update {
+        ServerUpdate::CreateConnection(input, output) => *input == InputId(panner, 1) && *output == OutputId(source, 1),
+        _ => false,
+    }));
+
+    let updates = client.apply(WorkspaceOp::ThawOutput(OutputId(osc, 0))).await;
+
+    assert!(updates.iter().any(|update| match update {
+        ServerUpdate::CreateConnection(input, output) => *input == InputId(panner, 1) && *output == OutputId(osc, 0),
+        _ => false,
+    }));
+
+    assert!(updates.iter().any(|update| matches!(update, ServerUpdate::DeleteModule(id) if *id == source)));
+}
+
+#[tokio::test]
+async fn creating_a_macro_moves_cables_inside_it() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let osc = created_id(&client.apply(oscillator()).await);
+    let panner = created_id(&client.apply(stereo_panner()).await);
+    let splitter = created_id(&client.apply(WorkspaceOp::CreateModule(ModuleParams::StereoSplitter(()), WindowGeometry::default())).await);
+
+    client.apply(WorkspaceOp::CreateConnection(InputId(panner, 0), OutputId(osc, 0))).await;
+    client.apply(WorkspaceOp::CreateConnection(InputId(splitter, 0), OutputId(panner, 0))).await;
+
+    let updates = client.apply(WorkspaceOp::CreateMacro("tone".to_owned(), vec![panner, osc])).await;
+
+    let deleted = updates.iter()
+        .filter(|update| matches!(update, ServerUpdate::DeleteModule(id) if *id == osc || *id == panner))
+        .count();
+
+    assert_eq!(2, deleted);
+
+    let (id, params, outputs) = updates.iter()
+        .find_map(|update| match update {
+            ServerUpdate::CreateModule { id, params: ModuleParams::Macro(params), outputs, .. } => Some((*id, params.clone(), outputs.clone())),
+            _ => None,
+        })
+        .expect("CreateModule Macro");
+
+    assert_eq!("tone", params.name);
+    assert_eq!(2, params.modules.len());
+    assert_eq!(1, params.connections.len());
+
+    // only the panner's output leaves the macro
+    assert_eq!(1, outputs.len());
+
+    assert!(updates.iter().any(|update| match update {
+        ServerUpdate::CreateConnection(input, output) => *input == InputId(splitter, 0) && *output == OutputId(id, 0),
+        _ => false,
+    }));
+}
+
+#[tokio::test]
+async fn resizing_a_mixer_keeps_its_connections() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let osc = created_id(&client.apply(oscillator()).await);
+    let lfo = created_id(&client.apply(WorkspaceOp::CreateModule(ModuleParams::Lfo(LfoParams::default()), WindowGeometry::default())).await);
+    let mixer = created_id(&client.apply(WorkspaceOp::CreateModule(ModuleParams::Mixer(MixerParams::with_channels(2)), WindowGeometry::default())).await);
+
+    // channel 2 input, and channel 2 fader modulation after both channels
+    client.apply(WorkspaceOp::CreateConnection(InputId(mixer, 1), OutputId(osc, 1))).await;
+    client.apply(WorkspaceOp::CreateConnection(InputId(mixer, 3), OutputId(lfo, 0))).await;
+
+    let updates = resize_mixer(&mut client, mixer, 3).await;
+
+    // the fader input moves along as a channel input is added before it
+    match updates.as_slice() {
+        [
+            ServerUpdate::DeleteConnection(deleted),
+            ServerUpdate::UpdateModuleParams { id, inputs: Some(inputs), outputs: None, .. },
+            ServerUpdate::CreateConnection(input, output),
+        ] => {
+            assert_eq!(InputId(mixer, 3), *deleted);
+            assert_eq!(mixer, *id);
+            assert_eq!(6, inputs.len());
+            assert_eq!(InputId(mixer, 4), *input);
+            assert_eq!(OutputId(lfo, 0), *output);
+        }
+        _ => panic!("unexpected updates: {:?}", updates),
+    }
+
+    // and connections to channels which are removed go with them
+    let updates = resize_mixer(&mut client, mixer, 1).await;
+
+    match updates.as_slice() {
+        [
+            ServerUpdate::DeleteConnection(first),
+            ServerUpdate::DeleteConnection(second),
+            ServerUpdate::UpdateModuleParams { inputs: Some(inputs), .. },
+        ] => {
+            assert_eq!(InputId(mixer, 1), *first);
+            assert_eq!(InputId(mixer, 4), *second);
+            assert_eq!(2, inputs.len());
+        }
+        _ => panic!("unexpected updates: {:?}", updates),
+    }
+
+    let state = server.connect().await.state;
+    assert!(state.connections.is_empty());
+}
+
+#[tokio::test]
+async fn resizing_a_mixer_indicates_levels_for_its_new_channels() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let mixer = created_id(&client.apply(WorkspaceOp::CreateModule(ModuleParams::Mixer(MixerParams::with_channels(2)), WindowGeometry::default())).await);
+
+    let op = WorkspaceOp::UpdateModuleParams(mixer, ModuleParams::Mixer(MixerParams::with_channels(3)));
+    let updates = client.apply(op).await;
+
+    // the window redraws from levels sent with the resize, rather than
+    // waiting on the next indication from a running tick
+    let levels = updates.iter()
+        .skip_while(|update| !matches!(update, ServerUpdate::UpdateModuleParams { .. }))
+        .filter_map(|update| match update {
+            ServerUpdate::UpdateModuleIndication(id, Indication::Mixer(indication)) if *id == mixer => Some(indication.channels.len()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert!(!levels.is_empty(), "no levels after resize: {:?}", updates);
+    assert!(levels.iter().all(|channels| *channels == 3), "stale levels after resize: {:?}", levels);
+}
+
+// updates from resizing a mixer, less the level indications it sends in
+// among them as it runs
+async fn resize_mixer(client: &mut TestClient, mixer: ModuleId, channels: usize) -> Vec<ServerUpdate> {
+    let op = WorkspaceOp::UpdateModuleParams(mixer, ModuleParams::Mixer(MixerParams::with_channels(channels)));
+    apply_ignoring_indications(client, op).await
+}
+
+#[tokio::test]
+async fn recorder_writes_its_input_to_a_wav_file() {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await;
+
+    let recorder = created_id(&client.apply(WorkspaceOp::CreateModule(ModuleParams::Recorder(RecorderParams::default()), WindowGeometry::default())).await);
+
+    let params = RecorderParams { recording: true, filename: "take".to_owned(), ..RecorderParams::default() };
+    let updates = client.apply(WorkspaceOp::UpdateModuleParams(recorder, ModuleParams::Recorder(params.clone()))).await;
+
+    // the new file shows straight away, not only once a second has been
+    // recorded
+    let file = updates.iter().find_map(|update| match update {
+        ServerUpdate::UpdateModuleIndication(id, Indication::Recorder(indication)) if *id == recorder => indication.file.clone(),
+        _ => None,
+    });
+
+    assert_eq!(Some("take.wav".to_owned()), file);
+
+    time::delay_for(Duration::from_millis(200)).await;
+    client.apply(WorkspaceOp::UpdateModuleParams(recorder, ModuleParams::Recorder(RecorderParams { recording: false, ..params }))).await;
+    time::delay_for(Duration::from_millis(200)).await;
+
+    let wav = hound::WavReader::open(server.recordings_dir().join("take.wav")).expect("open recording");
+    assert_eq!(2, wav.spec().channels);
+    assert!(wav.duration() > 0);
+}
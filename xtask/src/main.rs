@@ -0,0 +1,437 @@
+// Development tasks, run with `cargo xtask <task>` from the repository root.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "usage: cargo xtask new-module <Name>";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["new-module", name] => new_module(name),
+        _ => Err(USAGE.to_owned()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("xtask: {}", e);
+        process::exit(1);
+    }
+}
+
+fn project_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask lives in a subdirectory of the project root")
+        .to_owned()
+}
+
+struct ModuleName {
+    // StereoPanner
+    name: String,
+    // stereo_panner
+    snake: String,
+    // Stereo Panner
+    label: String,
+}
+
+impl ModuleName {
+    fn parse(name: &str) -> Result<ModuleName, String> {
+        let valid = name.chars().next().map(|c| c.is_ascii_uppercase()).unwrap_or(false)
+            && name.chars().all(|c| c.is_ascii_alphanumeric());
+
+        if !valid {
+            return Err(format!("module name must be CamelCase, eg. StereoPanner, got {:?}", name));
+        }
+
+        let mut snake = String::new();
+        let mut label = String::new();
+
+        for (idx, c) in name.chars().enumerate() {
+            if idx > 0 && c.is_ascii_uppercase() {
+                snake.push('_');
+                label.push(' ');
+            }
+
+            snake.push(c.to_ascii_lowercase());
+            label.push(c);
+        }
+
+        Ok(ModuleName { name: name.to_owned(), snake, label })
+    }
+
+    fn expand(&self, template: &str) -> String {
+        template
+            .replace("__Name__", &self.name)
+            .replace("__snake__", &self.snake)
+            .replace("__Label__", &self.label)
+    }
+}
+
+struct Edit {
+    path: PathBuf,
+    contents: String,
+    create: bool,
+}
+
+// markers in frontend/src/workspace.rs, new entries go on the line above
+const MENU_MARKER: &str = "            // `cargo xtask new-module` adds menu items above this line\n";
+const VIEW_MARKER: &str = "            // `cargo xtask new-module` adds module views above this line\n";
+
+fn new_module(name: &str) -> Result<(), String> {
+    let module = ModuleName::parse(name)?;
+    let edits = plan_edits(&project_root(), &module)?;
+
+    for edit in &edits {
+        if edit.create && edit.path.exists() {
+            return Err(format!("{} already exists", edit.path.display()));
+        }
+    }
+
+    for edit in &edits {
+        fs::write(&edit.path, &edit.contents)
+            .map_err(|e| format!("could not write {}: {}", edit.path.display(), e))?;
+
+        println!("{} {}", if edit.create { "created" } else { "updated" }, edit.path.display());
+    }
+
+    Ok(())
+}
+
+// all edits are prepared before any file is written so that a failure leaves
+// the tree untouched
+fn plan_edits(root: &Path, module: &ModuleName) -> Result<Vec<Edit>, String> {
+    let mut edits = Vec::new();
+
+    // new files
+    edits.push(Edit {
+        path: root.join("src/module").join(format!("{}.rs", module.snake)),
+        contents: module.expand(SERVER_MODULE),
+        create: true,
+    });
+
+    edits.push(Edit {
+        path: root.join("frontend/src/module").join(format!("{}.rs", module.snake)),
+        contents: module.expand(FRONTEND_MODULE),
+        create: true,
+    });
+
+    // registry entries in existing files
+    edits.push(edit_file(&root.join("src/module/mod.rs"), |source| {
+        insert_sorted(source, "$cb!{\n", "        }\n",
+            &module.expand("            __snake__::__Name__,\n"))
+    })?);
+
+    edits.push(edit_file(&root.join("protocol/src/lib.rs"), |source| {
+        let source = insert_sorted(source, "pub enum ModuleParams {\n", "}\n",
+            &module.expand("    __Name__(__Name__Params),\n"))?;
+
+        let source = insert_sorted(&source, "pub enum Indication {\n", "}\n",
+            &module.expand("    __Name__(()),\n"))?;
+
+        insert_after_block(&source, "pub enum Indication {\n", "}\n",
+            &module.expand(PROTOCOL_PARAMS))
+    })?);
+
+    edits.push(edit_file(&root.join("frontend/src/module/mod.rs"), |source| {
+        insert_sorted_within(source, 0, source.len(),
+            &module.expand("pub mod __snake__;\n"))
+    })?);
+
+    edits.push(edit_file(&root.join("frontend/src/workspace.rs"), |source| {
+        let source = insert_sorted_lines(source, "use crate::module::",
+            &module.expand("use crate::module::__snake__::__Name__;\n"))?;
+
+        let source = add_to_use_list(&source, "use mixlab_protocol::{",
+            &module.expand("__Name__Params"))?;
+
+        let source = insert_before_marker(&source, MENU_MARKER,
+            &module.expand("            (t(\"module.__Name__\"), ModuleParams::__Name__(__Name__Params::default())),\n"))?;
+
+        insert_before_marker(&source, VIEW_MARKER, &module.expand(WORKSPACE_VIEW_ARM))
+    })?);
+
+    // only the English catalog, other languages fall back to it until
+    // translated
+    edits.push(edit_file(&root.join("frontend/static/i18n/en.txt"), |source| {
+        insert_sorted(source, "# modules\n", "\n\n",
+            &module.expand("module.__Name__ = __Label__\n"))
+    })?);
+
+    Ok(edits)
+}
+
+fn edit_file(path: &Path, f: impl FnOnce(&str) -> Result<String, String>) -> Result<Edit, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+
+    let contents = f(&source)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    Ok(Edit { path: path.to_owned(), contents, create: false })
+}
+
+// finds the lines between the first occurrence of `start` and the next
+// occurrence of `end`, returning their byte range in `source`
+fn find_block(source: &str, start: &str, end: &str) -> Result<(usize, usize), String> {
+    let block_start = source.find(start)
+        .map(|idx| idx + start.len())
+        .ok_or_else(|| format!("could not find {:?}", start))?;
+
+    let block_end = source[block_start..].find(end)
+        .map(|idx| block_start + idx)
+        .ok_or_else(|| format!("could not find {:?} after {:?}", end, start))?;
+
+    Ok((block_start, block_end))
+}
+
+// inserts `line` into the block of lines between `start` and `end`, keeping
+// it in alphabetical order. lists like `enumerate_modules!` keep a few
+// entries out of order at the end, so the line is placed within the leading
+// sorted run only
+fn insert_sorted(source: &str, start: &str, end: &str, line: &str) -> Result<String, String> {
+    let (block_start, block_end) = find_block(source, start, end)?;
+    insert_sorted_within(source, block_start, block_end, line)
+}
+
+fn insert_sorted_within(source: &str, block_start: usize, block_end: usize, line: &str) -> Result<String, String> {
+    let block = &source[block_start..block_end];
+
+    if block.lines().any(|existing| existing.trim() == line.trim()) {
+        return Err(format!("already contains {:?}", line.trim()));
+    }
+
+    let mut offset = 0;
+    let mut previous: Option<&str> = None;
+
+    for existing in block.lines() {
+        let key = existing.trim();
+
+        if key > line.trim() || previous.map(|previous| key < previous).unwrap_or(false) {
+            break;
+        }
+
+        previous = Some(key);
+        offset += existing.len() + 1;
+    }
+
+    let at = block_start + offset;
+    Ok(format!("{}{}{}", &source[..at], line, &source[at..]))
+}
+
+// inserts `line` into the run of lines starting with `prefix`, as with
+// insert_sorted
+fn insert_sorted_lines(source: &str, prefix: &str, line: &str) -> Result<String, String> {
+    let block_start = source.lines()
+        .scan(0, |offset, existing| {
+            let start = *offset;
+            *offset += existing.len() + 1;
+            Some((start, existing))
+        })
+        .find(|(_, existing)| existing.starts_with(prefix))
+        .map(|(start, _)| start)
+        .ok_or_else(|| format!("could not find a line starting {:?}", prefix))?;
+
+    let block_len = source[block_start..].lines()
+        .take_while(|existing| existing.starts_with(prefix))
+        .map(|existing| existing.len() + 1)
+        .sum::<usize>();
+
+    insert_sorted_within(source, block_start, block_start + block_len, line)
+}
+
+// adds `item` to the braced list of a single line `use path::{..};`,
+// wherever its items are or however they are ordered
+fn add_to_use_list(source: &str, start: &str, item: &str) -> Result<String, String> {
+    let (list_start, list_end) = find_block(source, start, "};")?;
+    let list = &source[list_start..list_end];
+
+    if list.contains('\n') {
+        return Err(format!("expected {:?} on a single line", start));
+    }
+
+    let mut items = list.split(',').map(str::trim).filter(|item| !item.is_empty()).collect::<Vec<_>>();
+
+    if items.contains(&item) {
+        return Err(format!("already imports {:?}", item));
+    }
+
+    // alongside the other params, ahead of the types that follow them
+    let at = items.iter().rposition(|existing| existing.ends_with("Params"))
+        .map(|idx| idx + 1)
+        .unwrap_or(items.len());
+
+    items.insert(at, item);
+
+    Ok(format!("{}{}{}", &source[..list_start], items.join(", "), &source[list_end..]))
+}
+
+fn insert_before_marker(source: &str, marker: &str, text: &str) -> Result<String, String> {
+    replace_once(source, marker, &format!("{}{}", text, marker))
+}
+
+fn insert_after_block(source: &str, start: &str, end: &str, text: &str) -> Result<String, String> {
+    let (_, block_end) = find_block(source, start, end)?;
+    let at = block_end + end.len();
+    Ok(format!("{}\n{}{}", &source[..at], text, &source[at..]))
+}
+
+// inserts `text` in front of the single occurrence of `anchor`
+fn replace_once(source: &str, anchor: &str, text: &str) -> Result<String, String> {
+    match source.matches(anchor).count() {
+        1 => Ok(source.replacen(anchor, text, 1)),
+        0 => Err(format!("could not find {:?}", anchor)),
+        _ => Err(format!("found {:?} more than once", anchor)),
+    }
+}
+
+const SERVER_MODULE: &str = r#"use mixlab_protocol::{__Name__Params, LineType, Terminal};
+
+use crate::engine::{self, InputRef, OutputRef};
+use crate::module::ModuleT;
+
+#[derive(Debug)]
+pub struct __Name__ {
+    params: __Name__Params,
+    inputs: Vec<Terminal>,
+    outputs: Vec<Terminal>,
+}
+
+impl ModuleT for __Name__ {
+    type Params = __Name__Params;
+    type Indication = ();
+    type Event = ();
+
+    fn create(params: Self::Params, _: engine::ModuleCtx<Self>) -> (Self, Self::Indication) {
+        let module = __Name__ {
+            params,
+            inputs: vec![LineType::Mono.unlabeled()],
+            outputs: vec![LineType::Mono.unlabeled()],
+        };
+
+        (module, ())
+    }
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    fn update(&mut self, new_params: Self::Params) -> Option<Self::Indication> {
+        self.params = new_params;
+        None
+    }
+
+    fn run_tick(&mut self, _t: u64, inputs: &[InputRef], outputs: &mut [OutputRef]) -> Option<Self::Indication> {
+        let input = inputs[0].expect_mono();
+        let output = outputs[0].expect_mono();
+
+        output.copy_from_slice(input);
+
+        None
+    }
+
+    fn inputs(&self) -> &[Terminal] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Terminal] {
+        &self.outputs
+    }
+}
+"#;
+
+const PROTOCOL_PARAMS: &str = r#"#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct __Name__Params {
+}
+"#;
+
+const FRONTEND_MODULE: &str = r#"use yew::{html, ComponentLink, Html};
+
+use mixlab_protocol::{ModuleId, __Name__Params};
+
+use crate::component::midi_target::MidiUiMode;
+use crate::component::pure_module::{Pure, PureModule};
+use crate::workspace::Window;
+
+pub type __Name__ = Pure<__Name__Params>;
+
+impl PureModule for __Name__Params {
+    fn view(&self, _: ModuleId, _: ComponentLink<Window>, _: MidiUiMode) -> Html {
+        html! {}
+    }
+}
+"#;
+
+const WORKSPACE_VIEW_ARM: &str = r#"            ModuleParams::__Name__(params) => {
+                html! { <__Name__ id={self.props.id} module={self.link.clone()} params={params} midi_mode={self.midi_mode} /> }
+            }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_module_names() {
+        let module = ModuleName::parse("StereoPanner").unwrap();
+        assert_eq!("stereo_panner", module.snake);
+        assert_eq!("Stereo Panner", module.label);
+
+        assert!(ModuleName::parse("stereo_panner").is_err());
+        assert!(ModuleName::parse("").is_err());
+    }
+
+    #[test]
+    fn inserts_within_sorted_run() {
+        let source = "list {\n    b,\n    d,\n    a,\n}\n";
+
+        assert_eq!("list {\n    b,\n    c,\n    d,\n    a,\n}\n",
+            insert_sorted(source, "list {\n", "}\n", "    c,\n").unwrap());
+
+        // past the end of the sorted run, but before the unsorted tail:
+        assert_eq!("list {\n    b,\n    d,\n    e,\n    a,\n}\n",
+            insert_sorted(source, "list {\n", "}\n", "    e,\n").unwrap());
+
+        assert!(insert_sorted(source, "list {\n", "}\n", "    d,\n").is_err());
+    }
+
+    #[test]
+    fn adds_to_use_lists() {
+        let source = "use a::{X, AParams, BParams, Y};\nuse b::{Z};\n";
+
+        assert_eq!("use a::{X, AParams, BParams, CParams, Y};\nuse b::{Z};\n",
+            add_to_use_list(source, "use a::{", "CParams").unwrap());
+
+        assert!(add_to_use_list(source, "use a::{", "AParams").is_err());
+    }
+
+    // runs the edits against the real tree without writing them, so that a
+    // change to any of the lines they look for fails here rather than the
+    // next time someone adds a module
+    #[test]
+    fn plans_edits_against_the_project() {
+        let root = project_root();
+        let module = ModuleName::parse("XtaskTestModule").unwrap();
+        let edits = plan_edits(&root, &module).unwrap();
+
+        let contents = |path: &str| edits.iter()
+            .find(|edit| edit.path == root.join(path))
+            .map(|edit| edit.contents.as_str())
+            .unwrap_or_else(|| panic!("no edit to {}", path));
+
+        assert!(contents("src/module/mod.rs").contains("            xtask_test_module::XtaskTestModule,\n"));
+        assert!(contents("protocol/src/lib.rs").contains("    XtaskTestModule(XtaskTestModuleParams),\n"));
+        assert!(contents("protocol/src/lib.rs").contains("    XtaskTestModule(()),\n"));
+        assert!(contents("protocol/src/lib.rs").contains("pub struct XtaskTestModuleParams {\n"));
+        assert!(contents("frontend/src/module/mod.rs").contains("pub mod xtask_test_module;\n"));
+        assert!(contents("frontend/static/i18n/en.txt").contains("module.XtaskTestModule = Xtask Test Module\n"));
+
+        let workspace = contents("frontend/src/workspace.rs");
+        assert!(workspace.contains("use crate::module::xtask_test_module::XtaskTestModule;\n"));
+        assert!(workspace.contains(", XtaskTestModuleParams, "));
+        assert!(workspace.contains(&format!("ModuleParams::XtaskTestModule(XtaskTestModuleParams::default())),\n{}", MENU_MARKER)));
+        assert!(workspace.contains(&format!("midi_mode={{self.midi_mode}} /> }}\n            }}\n{}", VIEW_MARKER)));
+    }
+}